@@ -1,4 +1,6 @@
-use env_to_png::{env_to_image, env_to_sdf_image, Percentage, PixelsPerTile};
+use env_to_png::{
+    env_to_distance_field, env_to_image, env_to_sdf_image, Percentage, PixelsPerTile,
+};
 use gbp_environment::Environment;
 
 fn main() {
@@ -25,4 +27,24 @@ fn main() {
         sdf.save("./output/sdf.png")
             .expect("Failed to save SDF image");
     }
+
+    let clamp = environment
+        .tiles
+        .settings
+        .sdf
+        .clamp_distance(environment.tile_size());
+    if let Ok(distances) =
+        env_to_distance_field(&environment, resolution, Percentage::new(0.015), clamp)
+    {
+        // Map [-clamp, clamp] to [black, white], so the boundaries are mid grey
+        let image = image::GrayImage::from_fn(distances.width(), distances.height(), |x, y| {
+            let distance = distances.get_pixel(x, y)[0];
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let value = ((distance / clamp + 1.0) * 127.5) as u8;
+            image::Luma([value])
+        });
+        image
+            .save("./output/distance_field.png")
+            .expect("Failed to save distance field image");
+    }
 }
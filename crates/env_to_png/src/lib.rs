@@ -1,15 +1,19 @@
 //! This crate converts an [`Environment`] Configuration from the magics
 //! crate to a PNG image. It is possible to customize the colors of the
-//! different elements of the environment. And it can blur the edges to mimic an
-//! SDF.
+//! different elements of the environment. And it can either blur the edges to
+//! mimic an SDF, or compute the exact signed distance field of the obstacles.
 
 use std::num::NonZeroU32;
 
 // use magics::config::Environment;
-use gbp_environment::{Environment, PlaceableShape, RegularPolygon};
+use gbp_environment::Environment;
 use gbp_geometry::RelativePoint;
 use glam::{Vec2, Vec3Swizzles};
-use image::{imageops::FilterType::Triangle, RgbImage};
+use image::{imageops::FilterType::Triangle, ImageBuffer, Luma, RgbImage};
+
+/// Signed distance to the closest obstacle boundary in metres, for every pixel.
+/// Positive in free space, negative inside obstacles.
+pub type DistanceImage = ImageBuffer<Luma<f32>, Vec<f32>>;
 
 /// Custom resolution type, as pixels per tile.
 #[derive(Clone, Copy, Debug)]
@@ -152,15 +156,140 @@ pub fn env_to_sdf_image(
     expansion: Percentage,
     blur_percent: Percentage,
 ) -> anyhow::Result<RgbImage> {
-    let image = env_to_image(env, resolution, expansion).unwrap();
+    let image = env_to_image(env, resolution, expansion)?;
+    Ok(blur_image(&image, resolution, blur_percent))
+}
+
+/// Blur the edges of an image from [`env_to_image`] by `blur_percent` of a
+/// tile, to mimic an SDF.
+pub fn blur_image(
+    image: &RgbImage,
+    resolution: PixelsPerTile,
+    blur_percent: Percentage,
+) -> RgbImage {
     let blur_pixels = blur_percent.0 * resolution.get() as f32;
     // println!("Blur pixels: {}", blur_pixels);
     if blur_pixels < 1.0 {
-        return Ok(image);
+        return image.clone();
+    }
+    image::imageops::blur(image, blur_pixels)
+}
+
+/// Convert [`Environment`] to its exact signed distance field, with the
+/// distances clamped to `[-clamp, clamp]` metres.
+pub fn env_to_distance_field(
+    env: &Environment,
+    resolution: PixelsPerTile,
+    expansion: Percentage,
+    clamp: f32,
+) -> anyhow::Result<DistanceImage> {
+    let image = env_to_image(env, resolution, expansion)?;
+    let pixel_size = env.tile_size() / resolution.get() as f32;
+    Ok(image_to_distance_field(&image, pixel_size, clamp))
+}
+
+/// The exact signed distance field of an image from [`env_to_image`], where
+/// black pixels are obstacles and every pixel is `pixel_size` metres wide.
+///
+/// The obstacle boundary lies between the centers of an obstacle pixel and a
+/// free pixel, so adjacent pixels are half a pixel from it. The distances are
+/// clamped to `[-clamp, clamp]` metres.
+pub fn image_to_distance_field(image: &RgbImage, pixel_size: f32, clamp: f32) -> DistanceImage {
+    let (width, height) = image.dimensions();
+    let obstacle = image
+        .pixels()
+        .map(|pixel| pixel[0] < 128)
+        .collect::<Vec<_>>();
+
+    let to_obstacle = distance_transform(width as usize, height as usize, |i| obstacle[i]);
+    let to_free = distance_transform(width as usize, height as usize, |i| !obstacle[i]);
+
+    let distances = obstacle
+        .iter()
+        .zip(to_obstacle.iter().zip(&to_free))
+        .map(|(&obstacle, (&to_obstacle, &to_free))| {
+            let pixels = if obstacle {
+                -(to_free - 0.5)
+            } else {
+                to_obstacle - 0.5
+            };
+            (pixels * pixel_size).clamp(-clamp, clamp)
+        })
+        .collect();
+
+    DistanceImage::from_raw(width, height, distances).expect("one distance per pixel")
+}
+
+/// Exact euclidean distance in pixels from every pixel to the closest pixel
+/// `is_feature` is true for, with the algorithm of Felzenszwalb and
+/// Huttenlocher. Infinite if there are no features.
+fn distance_transform(width: usize, height: usize, is_feature: impl Fn(usize) -> bool) -> Vec<f32> {
+    let mut squared = (0..width * height)
+        .map(|i| if is_feature(i) { 0.0 } else { f32::INFINITY })
+        .collect::<Vec<_>>();
+
+    // The squared distance transform is separable, so transform every column
+    // and then every row
+    let mut line = Vec::with_capacity(width.max(height));
+    for x in 0..width {
+        line.clear();
+        line.extend((0..height).map(|y| squared[y * width + x]));
+        for (y, distance) in squared_distance_transform_1d(&line).into_iter().enumerate() {
+            squared[y * width + x] = distance;
+        }
+    }
+    for row in squared.chunks_mut(width) {
+        let transformed = squared_distance_transform_1d(row);
+        row.copy_from_slice(&transformed);
+    }
+
+    squared.into_iter().map(f32::sqrt).collect()
+}
+
+/// One dimensional squared distance transform of the sampled function `f`,
+/// i.e. `min_q (p - q)^2 + f(q)` for every `p`, computed as the lower envelope
+/// of the parabolas rooted at every `q`.
+fn squared_distance_transform_1d(f: &[f32]) -> Vec<f32> {
+    let n = f.len();
+    // The parabolas of the lower envelope, and where each of them starts
+    let mut vertices = Vec::with_capacity(n);
+    let mut starts = Vec::with_capacity(n + 1);
+    for q in (0..n).filter(|&q| f[q].is_finite()) {
+        let intersection = |p: usize| {
+            let (q_f, p_f) = (q as f32, p as f32);
+            ((f[q] + q_f * q_f) - (f[p] + p_f * p_f)) / (2.0 * (q_f - p_f))
+        };
+        let mut start = f32::NEG_INFINITY;
+        while let Some(&last) = vertices.last() {
+            start = intersection(last);
+            if start > starts[starts.len() - 1] {
+                break;
+            }
+            vertices.pop();
+            starts.pop();
+            start = f32::NEG_INFINITY;
+        }
+        vertices.push(q);
+        starts.push(start);
     }
-    let sdf = image::imageops::blur(&image, blur_pixels);
 
-    Ok(sdf)
+    if vertices.is_empty() {
+        return vec![f32::INFINITY; n];
+    }
+    starts.push(f32::INFINITY);
+
+    let mut k = 0;
+    (0..n)
+        .map(|p| {
+            let p_f = p as f32;
+            while starts[k + 1] < p_f {
+                k += 1;
+            }
+            let q = vertices[k];
+            let offset = p_f - q as f32;
+            offset * offset + f[q]
+        })
+        .collect()
 }
 
 /// Convert [`Environment`] to an image.
@@ -302,18 +431,7 @@ fn is_placeable_obstacle(
         let expanded_shape = obstacle.shape.expanded(expansion.0 as f64);
         let translated = Vec2::from(inverted_percentage) - Vec2::from(obstacle.translation); // - translation_offset;
                                                                                              // rotate the translated coordinated by the obstacle rotation
-        let rotation_offset = match obstacle.shape {
-            PlaceableShape::RegularPolygon(RegularPolygon { sides, radius: _ }) => {
-                std::f32::consts::FRAC_PI_2
-                    + std::f32::consts::FRAC_PI_2
-                    + if sides % 2 != 0 {
-                        std::f32::consts::PI / sides as f32
-                    } else {
-                        0.0
-                    }
-            }
-            _ => std::f32::consts::FRAC_PI_2,
-        };
+        let rotation_offset = obstacle.shape.rotation_offset();
 
         let rotated =
             glam::Quat::from_rotation_z(obstacle.rotation.as_radians() as f32 + rotation_offset)
@@ -506,6 +624,55 @@ mod tests {
         assert_eq!(tile_coords.y, 2);
     }
 
+    #[test]
+    fn test_squared_distance_transform_1d() {
+        let inf = f32::INFINITY;
+        assert_eq!(
+            squared_distance_transform_1d(&[inf, 0.0, inf, inf, 0.0]),
+            vec![1.0, 0.0, 1.0, 1.0, 0.0]
+        );
+        assert_eq!(squared_distance_transform_1d(&[inf, 0.0, inf, inf]), vec![
+            1.0, 0.0, 1.0, 4.0
+        ]);
+        assert!(squared_distance_transform_1d(&[inf, inf])
+            .iter()
+            .all(|d| d.is_infinite()));
+    }
+
+    #[test]
+    fn test_distance_transform_is_exact() {
+        let (width, height) = (13, 9);
+        let is_feature = |i: usize| i % 17 == 3 || i == 50;
+        let distances = distance_transform(width, height, is_feature);
+
+        // Compare with the distance to every feature
+        for (i, distance) in distances.iter().enumerate() {
+            let (x, y) = ((i % width) as f32, (i / width) as f32);
+            let brute_force = (0..width * height)
+                .filter(|&j| is_feature(j))
+                .map(|j| (x - (j % width) as f32).hypot(y - (j / width) as f32))
+                .fold(f32::INFINITY, f32::min);
+            assert!((distance - brute_force).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_image_to_distance_field() {
+        // The two leftmost columns are an obstacle
+        let image = RgbImage::from_fn(6, 3, |x, _| {
+            if x < 2 {
+                image::Rgb([0, 0, 0])
+            } else {
+                image::Rgb([255, 255, 255])
+            }
+        });
+        let pixel_size = 0.5;
+        let field = image_to_distance_field(&image, pixel_size, 1.5);
+        let row = (0..6).map(|x| field.get_pixel(x, 1)[0]).collect::<Vec<_>>();
+        // The boundary is between the second and third column
+        assert_eq!(row, vec![-0.75, -0.25, 0.25, 0.75, 1.25, 1.5]);
+    }
+
     #[test]
     fn test_is_obstacle() {
         let tile = '─';
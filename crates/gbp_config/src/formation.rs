@@ -48,6 +48,116 @@ pub enum PlanningStrategy {
     OnlyLocal,
     /// Global planning with RRT*
     RrtStar,
+    /// Global planning with A* over an occupancy grid of the environment
+    AStar,
+    /// Global planning with Theta*, i.e. any-angle A*, over an occupancy grid
+    /// of the environment
+    ThetaStar,
+    /// Global planning over a probabilistic roadmap shared by all robots
+    Prm,
+    /// Prioritised planning with A* over the occupancy grid and time. Robots
+    /// plan in order of priority, each around the cells reserved over time by
+    /// the robots that planned before it
+    Prioritised,
+}
+
+impl PlanningStrategy {
+    /// Whether the strategy plans a global path between the waypoints
+    pub const fn is_global(self) -> bool {
+        !matches!(self, Self::OnlyLocal)
+    }
+}
+
+/// Motion model of the robots in a formation, i.e. how a robot is able to move
+/// to follow the trajectory planned by its factorgraph.
+///
+/// ## Example
+/// ```yaml
+/// motion-model: !differential-drive
+///   wheel-separation: 0.5
+///   max-wheel-speed: 2.0
+///   max-wheel-acceleration: 4.0
+/// ```
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Component)]
+#[serde(rename_all = "kebab-case")]
+pub enum MotionModel {
+    /// Moves in any direction, exactly along the planned trajectory.
+    /// This is the default, as this is what the gbpplanner paper does
+    #[default]
+    Holonomic,
+    /// Moves forward along its heading, and is able to turn in place
+    Unicycle(UnicycleLimits),
+    /// Two independently driven wheels on a common axle
+    DifferentialDrive(DifferentialDriveLimits),
+    /// Car-like steering, unable to turn in place
+    Ackermann(AckermannLimits),
+}
+
+impl MotionModel {
+    /// Maximum speed of the robot in m/s, if the model limits it.
+    /// For the differential drive model, the maximum speed of each wheel
+    #[must_use]
+    pub const fn max_speed(&self) -> Option<StrictlyPositiveFinite<f32>> {
+        match self {
+            Self::Holonomic => None,
+            Self::Unicycle(limits) => Some(limits.max_speed),
+            Self::DifferentialDrive(limits) => Some(limits.max_wheel_speed),
+            Self::Ackermann(limits) => Some(limits.max_speed),
+        }
+    }
+
+    /// Maximum acceleration of the robot in m/s^2, if the model limits it.
+    /// For the differential drive model, the maximum acceleration of each
+    /// wheel
+    #[must_use]
+    pub const fn max_acceleration(&self) -> Option<StrictlyPositiveFinite<f32>> {
+        match self {
+            Self::Holonomic => None,
+            Self::Unicycle(limits) => Some(limits.max_acceleration),
+            Self::DifferentialDrive(limits) => Some(limits.max_wheel_acceleration),
+            Self::Ackermann(limits) => Some(limits.max_acceleration),
+        }
+    }
+}
+
+/// Limits of the [`MotionModel::Unicycle`] model
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct UnicycleLimits {
+    /// Maximum forward speed in m/s
+    pub max_speed: StrictlyPositiveFinite<f32>,
+    /// Maximum forward acceleration in m/s^2
+    pub max_acceleration: StrictlyPositiveFinite<f32>,
+    /// Maximum angular speed in rad/s
+    pub max_angular_speed: StrictlyPositiveFinite<f32>,
+    /// Maximum angular acceleration in rad/s^2
+    pub max_angular_acceleration: StrictlyPositiveFinite<f32>,
+}
+
+/// Limits of the [`MotionModel::DifferentialDrive`] model
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DifferentialDriveLimits {
+    /// Distance between the two wheels in m
+    pub wheel_separation: StrictlyPositiveFinite<f32>,
+    /// Maximum speed of each wheel in m/s
+    pub max_wheel_speed: StrictlyPositiveFinite<f32>,
+    /// Maximum acceleration of each wheel in m/s^2
+    pub max_wheel_acceleration: StrictlyPositiveFinite<f32>,
+}
+
+/// Limits of the [`MotionModel::Ackermann`] model
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AckermannLimits {
+    /// Distance between the front and rear axle in m
+    pub wheelbase: StrictlyPositiveFinite<f32>,
+    /// Maximum forward speed in m/s
+    pub max_speed: StrictlyPositiveFinite<f32>,
+    /// Maximum forward acceleration in m/s^2
+    pub max_acceleration: StrictlyPositiveFinite<f32>,
+    /// Maximum steering angle of the front wheels in radians
+    pub max_steering_angle: StrictlyPositiveFinite<f32>,
 }
 
 // pub struct Local;
@@ -229,6 +339,19 @@ pub struct Formation {
     pub waypoint_reached_when_intersects: ReachedWhen,
     #[serde(default = "Formation::default_finished_when_intersects")]
     pub finished_when_intersects: ReachedWhen,
+    /// How the robots move to follow their planned trajectory
+    #[serde(default)]
+    pub motion_model: MotionModel,
+    /// Name of the robot profile in the `[robot-profiles]` table of the config
+    /// to spawn robots of. If not set, the robots are given by the `[robot]`
+    /// section
+    #[serde(default)]
+    pub robot_profile: Option<String>,
+    /// Priority of the robots when avoiding other robots. Overrides the
+    /// priority of the robot profile if set, see
+    /// [`RobotSection::priority`](crate::RobotSection::priority)
+    #[serde(default)]
+    pub priority: Option<StrictlyPositiveFinite<f32>>,
 }
 
 impl Default for Formation {
@@ -261,6 +384,24 @@ impl Formation {
         }
     }
 
+    /// The motion model of the robots spawned by the formation. The motion
+    /// model of the robot `profile` is used, unless the formation sets one
+    /// other than the default holonomic one
+    #[must_use]
+    pub fn motion_model_of(&self, profile: &crate::RobotProfile) -> MotionModel {
+        match self.motion_model {
+            MotionModel::Holonomic => profile.motion_model.unwrap_or_default(),
+            motion_model => motion_model,
+        }
+    }
+
+    /// The priority of the robots spawned by the formation. The priority of the
+    /// formation is used if set, otherwise the one of the robot `profile`
+    #[must_use]
+    pub fn priority_of(&self, profile: &crate::RobotProfile) -> StrictlyPositiveFinite<f32> {
+        self.priority.unwrap_or(profile.priority)
+    }
+
     pub fn robots_to_spawn(&self) -> usize {
         let times = self.repeat.map_or(1, |repeat| match repeat.times {
             RepeatTimes::Infinite => usize::MAX,
@@ -291,6 +432,9 @@ impl Formation {
             waypoints: one_or_more![Waypoint::new(circle, ProjectionStrategy::Cross)],
             waypoint_reached_when_intersects: ReachedWhen::same_as_paper(),
             finished_when_intersects: ReachedWhen::same_as_paper(),
+            motion_model: MotionModel::default(),
+            robot_profile: None,
+            priority: None,
         }
     }
 
@@ -754,6 +898,9 @@ impl FormationGroup {
                         distance: IntersectionDistance::RobotRadius,
                         intersects_with: CheckIntersectionWith::Current,
                     },
+                    motion_model: MotionModel::default(),
+                    robot_profile: None,
+                    priority: None,
                 },
                 Formation {
                     // repeat: Some(Duration::from_secs(4)),
@@ -779,6 +926,9 @@ impl FormationGroup {
                         distance: IntersectionDistance::RobotRadius,
                         intersects_with: CheckIntersectionWith::Current,
                     },
+                    motion_model: MotionModel::default(),
+                    robot_profile: None,
+                    priority: None,
                 },
             ],
         }
@@ -866,5 +1016,159 @@ mod tests {
                     .all(|(p, (x, y))| float_eq(p.0, x) && float_eq(p.1, y)));
             }
         }
+
+        mod motion_model {
+            use super::*;
+
+            #[test]
+            fn defaults_to_holonomic() {
+                let group = FormationGroup::parse_from_yaml(
+                    "formations:
+                    - delay:
+                        secs: 1
+                        nanos: 0
+                      robots: 1
+                      planning-strategy: only-local
+                      initial-position:
+                        shape: !line-segment
+                        - x: 0.4
+                          y: 0.0
+                        - x: 0.6
+                          y: 0.0
+                        placement-strategy: equal
+                      waypoints:
+                      - shape: !line-segment
+                        - x: 0.4
+                          y: 1.0
+                        - x: 0.6
+                          y: 1.0
+                        projection-strategy: identity
+                      waypoint-reached-when-intersects:
+                        intersects-with: horizon",
+                )
+                .unwrap();
+                assert!(matches!(
+                    group.formations[0].motion_model,
+                    MotionModel::Holonomic
+                ));
+            }
+
+            #[test]
+            fn parse_differential_drive() {
+                let motion_model: MotionModel = serde_yaml::from_str(
+                    "!differential-drive
+                    wheel-separation: 0.5
+                    max-wheel-speed: 2.0
+                    max-wheel-acceleration: 4.0",
+                )
+                .unwrap();
+                let MotionModel::DifferentialDrive(limits) = motion_model else {
+                    panic!("expected a differential drive, got {motion_model:?}");
+                };
+                assert!((limits.wheel_separation.get() - 0.5).abs() < f32::EPSILON);
+
+                let negative_speed = serde_yaml::from_str::<MotionModel>(
+                    "!unicycle
+                    max-speed: -1.0
+                    max-acceleration: 1.0
+                    max-angular-speed: 1.0
+                    max-angular-acceleration: 1.0",
+                );
+                assert!(negative_speed.is_err());
+            }
+        }
+
+        mod robot_profile {
+            use super::*;
+            use crate::{Config, RobotProfileError};
+
+            fn config() -> Config {
+                let mut config = Config::default();
+                config.robot_profiles.insert(
+                    "truck".to_string(),
+                    toml::from_str(
+                        "target-speed = 2.0
+                        variables = 8
+                        motion-model = { unicycle = { max-speed = 2.0, max-acceleration = 1.0, \
+                         max-angular-speed = 0.5, max-angular-acceleration = 0.5 } }
+                        priority = 2.0
+                        sigmas = { dynamics = 0.2 }",
+                    )
+                    .unwrap(),
+                );
+                config
+                    .robot_profiles
+                    .insert("pin".to_string(), toml::from_str("variables = 1").unwrap());
+                config
+            }
+
+            #[test]
+            fn falls_back_to_the_robot_section() {
+                let config = config();
+                let truck = config.robot_profile(Some("truck")).unwrap();
+                assert_eq!(truck.name.as_deref(), Some("truck"));
+                assert!((truck.target_speed.get() - 2.0).abs() < f32::EPSILON);
+                assert_eq!(truck.variables.map(NonZeroUsize::get), Some(8));
+                assert_eq!(truck.planning_horizon, config.robot.planning_horizon);
+                assert!((truck.sigmas.dynamics - 0.2).abs() < f32::EPSILON);
+                assert!(
+                    (truck.sigmas.interrobot - config.gbp.sigma_factor_interrobot).abs()
+                        < f32::EPSILON
+                );
+
+                let robot = config.robot_profile(None).unwrap();
+                assert_eq!(robot.name, None);
+                assert_eq!(robot.target_speed, config.robot.target_speed);
+                assert_eq!(robot.variables, None);
+            }
+
+            #[test]
+            fn formation_motion_model_takes_precedence() {
+                let config = config();
+                let truck = config.robot_profile(Some("truck")).unwrap();
+                let mut formation = Formation::default();
+                assert!(matches!(
+                    formation.motion_model_of(&truck),
+                    MotionModel::Unicycle(_)
+                ));
+
+                formation.motion_model = serde_yaml::from_str(
+                    "!differential-drive
+                    wheel-separation: 0.5
+                    max-wheel-speed: 2.0
+                    max-wheel-acceleration: 4.0",
+                )
+                .unwrap();
+                assert!(matches!(
+                    formation.motion_model_of(&truck),
+                    MotionModel::DifferentialDrive(_)
+                ));
+            }
+
+            #[test]
+            fn formation_priority_takes_precedence() {
+                let config = config();
+                let truck = config.robot_profile(Some("truck")).unwrap();
+                assert!((truck.priority.get() - 2.0).abs() < f32::EPSILON);
+
+                let mut formation = Formation::default();
+                assert_eq!(formation.priority_of(&truck), truck.priority);
+                formation.priority = Some(StrictlyPositiveFinite::<f32>::new(4.0).unwrap());
+                assert!((formation.priority_of(&truck).get() - 4.0).abs() < f32::EPSILON);
+            }
+
+            #[test]
+            fn unknown_and_invalid_profiles_are_errors() {
+                let config = config();
+                assert!(matches!(
+                    config.robot_profile(Some("boat")),
+                    Err(RobotProfileError::Unknown(name)) if name == "boat"
+                ));
+                assert!(matches!(
+                    config.robot_profile(Some("pin")),
+                    Err(RobotProfileError::TooFewVariables(_))
+                ));
+            }
+        }
     }
 }
@@ -3,7 +3,7 @@ pub mod formation;
 pub mod geometry;
 pub mod reader;
 
-use std::{num::NonZeroUsize, ops::RangeInclusive};
+use std::{collections::BTreeMap, num::NonZeroUsize, ops::RangeInclusive};
 
 use bevy::{
     ecs::system::Resource,
@@ -11,11 +11,20 @@ use bevy::{
 };
 // pub use environment::{Environment, EnvironmentType};
 pub use formation::FormationGroup;
+pub use gbp_core::{
+    config::{
+        ConvergenceCriterion, ConvergenceSection, SdfFalloff, SdfInterpolation, SdfOutOfBounds,
+        SdfSamplingSection, TrackingSection,
+    },
+    factor::Attachment,
+    state::StateLayout,
+};
 use gbp_schedule::GbpSchedule;
 pub use reader::read_config;
 use serde::{Deserialize, Serialize};
 use struct_iterable::Iterable;
 use typed_floats::StrictlyPositiveFinite;
+use unit_interval::UnitInterval;
 
 #[derive(Debug, thiserror::Error)]
 pub enum ParseError {
@@ -45,24 +54,100 @@ pub struct GraphvizInterrobotSection {
     // pub edge: GraphvizEdgeAttributes,
 }
 
+impl From<&GraphvizEdgeAttributes> for gbp_core::graphviz::EdgeStyle {
+    fn from(attributes: &GraphvizEdgeAttributes) -> Self {
+        Self {
+            style: attributes.style.clone(),
+            len:   attributes.len,
+            color: attributes.color.clone(),
+        }
+    }
+}
+
+/// A file format the factorgraphs can be exported as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GraphvizFormat {
+    /// The DOT language of graphviz
+    Dot,
+    /// The nodes, edges and their attributes as JSON
+    Json,
+    /// An SVG image, laid out by the estimated positions of the variables
+    Svg,
+}
+
+impl GraphvizFormat {
+    /// The file extension of the format
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Dot => "dot",
+            Self::Json => "json",
+            Self::Svg => "svg",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct GraphvizSection {
-    pub interrobot:      GraphvizInterrobotSection,
+    pub interrobot: GraphvizInterrobotSection,
     #[serde(default = "GraphvizSection::default_export_location")]
     pub export_location: String,
+    /// The formats to export the factorgraphs as
+    #[serde(default = "GraphvizSection::default_formats")]
+    pub formats: Vec<GraphvizFormat>,
+    /// Kinds of factors to leave out of the export, e.g. `["tracking"]`
+    #[serde(default)]
+    pub exclude_factors: Vec<String>,
+    /// Annotate variables with the mean and covariance of their belief
+    #[serde(default)]
+    pub annotate_beliefs: bool,
+    /// Annotate edges with the norms of the latest messages sent along them
+    #[serde(default)]
+    pub annotate_messages: bool,
 }
 
 impl GraphvizSection {
     pub fn default_export_location() -> String {
         "./assets/export".to_string()
     }
+
+    pub fn default_formats() -> Vec<GraphvizFormat> {
+        vec![GraphvizFormat::Dot, GraphvizFormat::Svg]
+    }
+
+    /// How to draw the interrobot edges in the DOT output
+    pub fn dot_style(&self) -> gbp_core::graphviz::DotStyle {
+        gbp_core::graphviz::DotStyle {
+            active:   (&self.interrobot.active).into(),
+            inactive: (&self.interrobot.inactive).into(),
+        }
+    }
+
+    /// The filters and annotations of an export of every robot
+    pub fn export_options<Id: gbp_core::id::GraphId>(
+        &self,
+    ) -> gbp_core::graphviz::ExportOptions<Id> {
+        use gbp_core::graphviz::{Annotations, ExportOptions, FactorFilter};
+
+        let factors = if self.exclude_factors.is_empty() {
+            FactorFilter::All
+        } else {
+            FactorFilter::Exclude(self.exclude_factors.clone())
+        };
+        ExportOptions::default()
+            .with_factors(factors)
+            .with_annotations(Annotations {
+                beliefs:  self.annotate_beliefs,
+                messages: self.annotate_messages,
+            })
+    }
 }
 
 impl Default for GraphvizSection {
     fn default() -> Self {
         Self {
-            interrobot:      GraphvizInterrobotSection {
+            interrobot: GraphvizInterrobotSection {
                 active:   GraphvizEdgeAttributes {
                     style: "solid".to_string(),
                     len:   8.0,
@@ -75,6 +160,10 @@ impl Default for GraphvizSection {
                 },
             },
             export_location: "./assets/".to_string(),
+            formats: Self::default_formats(),
+            exclude_factors: Vec::new(),
+            annotate_beliefs: false,
+            annotate_messages: false,
         }
     }
 }
@@ -159,6 +248,7 @@ pub enum DrawSetting {
     RobotRobotCollisions,
     EnvironmentColliders,
     RobotEnvironmentCollisions,
+    LimitViolations,
     // InfiniteGrid,
 }
 
@@ -185,6 +275,10 @@ pub struct DrawSection {
     pub environment_colliders: bool,
     pub robot_robot_collisions: bool,
     pub robot_environment_collisions: bool,
+    /// Variables planned to exceed the speed or acceleration limits of their
+    /// robot
+    #[serde(default)]
+    pub limit_violations: bool,
     // pub infinite_grid: bool,
 }
 
@@ -209,6 +303,7 @@ impl Default for DrawSection {
             environment_colliders: false,
             robot_robot_collisions: false,
             robot_environment_collisions: false,
+            limit_violations: false,
             // infinite_grid: true,
         }
     }
@@ -235,6 +330,7 @@ impl DrawSection {
             "environment_colliders" => "Environment Colliders",
             "robot_robot_collisions" => "Robot-Robot Collisions",
             "robot_environment_collisions" => "Robot-Environment Collisions",
+            "limit_violations" => "Limit Violations",
             // "infinite_grid" => "Infinite Grid",
             _ => "Unknown",
         }
@@ -454,13 +550,19 @@ impl Default for GbpIterationSchedule {
 pub struct FactorsEnabledSection {
     // pub pose:       bool,
     #[serde(default = "FactorsEnabledSection::default_dynamic")]
-    pub dynamic:    bool,
+    pub dynamic: bool,
     #[serde(default = "FactorsEnabledSection::default_interrobot")]
     pub interrobot: bool,
     #[serde(default = "FactorsEnabledSection::default_obstacle")]
-    pub obstacle:   bool,
+    pub obstacle: bool,
     #[serde(default = "FactorsEnabledSection::default_tracking")]
-    pub tracking:   bool,
+    pub tracking: bool,
+    #[serde(default = "FactorsEnabledSection::default_dynamic_obstacle")]
+    pub dynamic_obstacle: bool,
+    #[serde(default = "FactorsEnabledSection::default_velocity")]
+    pub velocity: bool,
+    #[serde(default = "FactorsEnabledSection::default_acceleration")]
+    pub acceleration: bool,
 }
 
 impl FactorsEnabledSection {
@@ -479,59 +581,31 @@ impl FactorsEnabledSection {
     fn default_obstacle() -> bool {
         true
     }
-}
-
-impl Default for FactorsEnabledSection {
-    fn default() -> Self {
-        Self {
-            // pose:       true,
-            dynamic:    Self::default_dynamic(),
-            interrobot: Self::default_interrobot(),
-            obstacle:   Self::default_obstacle(),
-            tracking:   Self::default_tracking(),
-        }
-    }
-}
-
-/// **Tracking Section**
-/// Contains parameters for the tracking factor
-/// - `switch_padding`: Padding around the switch point
-/// - `attraction_distance`: Distance to the tracking line to normalise around
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "kebab-case")]
-pub struct TrackingSection {
-    #[serde(default = "TrackingSection::default_switch_padding")]
-    pub switch_padding:      f32,
-    #[serde(default = "TrackingSection::default_attraction_distance")]
-    pub attraction_distance: f32,
-    //#[serde(default = "TrackingSection::default_enabled")]
-    // pub enabled: bool,
-}
 
-impl TrackingSection {
-    fn default_enabled() -> bool {
+    fn default_dynamic_obstacle() -> bool {
         true
     }
 
-    fn default_attraction_distance() -> f32 {
-        2.0
+    fn default_velocity() -> bool {
+        true
     }
 
-    fn default_switch_padding() -> f32 {
-        1.0
+    fn default_acceleration() -> bool {
+        true
     }
 }
 
-/// Default value for the attraction distance
-
-/// Default value for the switch padding
-
-impl Default for TrackingSection {
+impl Default for FactorsEnabledSection {
     fn default() -> Self {
         Self {
-            switch_padding:      Self::default_switch_padding(),
-            attraction_distance: Self::default_attraction_distance(),
-            // enabled: Self::default_enabled(),
+            // pose:       true,
+            dynamic: Self::default_dynamic(),
+            interrobot: Self::default_interrobot(),
+            obstacle: Self::default_obstacle(),
+            tracking: Self::default_tracking(),
+            dynamic_obstacle: Self::default_dynamic_obstacle(),
+            velocity: Self::default_velocity(),
+            acceleration: Self::default_acceleration(),
         }
     }
 }
@@ -552,6 +626,15 @@ pub struct GbpSection {
     pub sigma_factor_obstacle: f32,
     /// Sigma for Tracking factors
     pub sigma_factor_tracking: f32,
+    /// Sigma for Dynamic obstacle factors
+    #[serde(default = "GbpSection::default_sigma_factor_dynamic_obstacle")]
+    pub sigma_factor_dynamic_obstacle: f32,
+    /// Sigma for Velocity limit factors
+    #[serde(default = "GbpSection::default_sigma_factor_velocity")]
+    pub sigma_factor_velocity: f32,
+    /// Sigma for Acceleration limit factors
+    #[serde(default = "GbpSection::default_sigma_factor_acceleration")]
+    pub sigma_factor_acceleration: f32,
     /// Parameter affecting how planned path is spaced out in time
     pub lookahead_multiple: usize,
     /// Tracking section
@@ -565,12 +648,68 @@ pub struct GbpSection {
     /// Number of variables to create
     #[serde(default = "GbpSection::default_variables")]
     pub variables: usize,
+    /// What the obstacle factors measure the distance to obstacles against
+    #[serde(default)]
+    pub obstacle_factor: ObstacleFactorKind,
+    /// How the obstacle factors sample the signed distance field image
+    #[serde(default)]
+    pub sdf_sampling: SdfSamplingSection,
+    /// Weight of the previous message, when a factor sends a new message to a
+    /// variable. The message sent is the convex combination
+    /// `(1 - damping) * new + damping * previous` in information form.
+    /// 0.0 disables damping
+    #[serde(default = "GbpSection::default_damping")]
+    pub damping: UnitInterval,
+    /// When to end the internal iterations of a timestep early
+    #[serde(default)]
+    pub convergence: ConvergenceSection,
+    /// Layout of the state of every variable, i.e. if the robots plan in the
+    /// plane, in 3D or in the plane with a heading
+    #[serde(default)]
+    pub state_layout: StateLayout,
+}
+
+/// What the obstacle factors measure the distance to obstacles against
+///
+/// ## Example
+/// ```toml
+/// [gbp]
+/// obstacle-factor = "colliders"
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ObstacleFactorKind {
+    /// Look up the nearest pixel of the signed distance field image, generated
+    /// from the environment
+    #[default]
+    Sdf,
+    /// Compute the exact signed distance to the colliders of the environment
+    Colliders,
+    /// Sample the exact signed distance field in metres, generated from the
+    /// environment, with the falloff of the `sdf-sampling` section
+    DistanceField,
 }
 
 impl GbpSection {
     fn default_variables() -> usize {
         10
     }
+
+    fn default_damping() -> UnitInterval {
+        UnitInterval::new(0.0).expect("0.0 in [0.0, 1.0]")
+    }
+
+    fn default_sigma_factor_dynamic_obstacle() -> f32 {
+        0.01
+    }
+
+    fn default_sigma_factor_velocity() -> f32 {
+        0.01
+    }
+
+    fn default_sigma_factor_acceleration() -> f32 {
+        0.01
+    }
 }
 
 impl Default for GbpSection {
@@ -581,6 +720,9 @@ impl Default for GbpSection {
             sigma_factor_interrobot: 0.01,
             sigma_factor_obstacle: 0.01,
             sigma_factor_tracking: 0.1,
+            sigma_factor_dynamic_obstacle: Self::default_sigma_factor_dynamic_obstacle(),
+            sigma_factor_velocity: Self::default_sigma_factor_velocity(),
+            sigma_factor_acceleration: Self::default_sigma_factor_acceleration(),
             lookahead_multiple: 3,
             tracking: TrackingSection::default(),
             // iterations_per_timestep: 10,
@@ -588,16 +730,79 @@ impl Default for GbpSection {
             // FIXME: not properly read when desirialized from toml
             factors_enabled: FactorsEnabledSection::default(),
             variables: Self::default_variables(),
+            obstacle_factor: ObstacleFactorKind::default(),
+            sdf_sampling: SdfSamplingSection::default(),
+            damping: Self::default_damping(),
+            convergence: ConvergenceSection::default(),
+            state_layout: StateLayout::default(),
             // ..Default::default()
         }
     }
 }
 
+/// **User Factor Section**
+/// Contains parameters for a factor kind registered outside of the simulator,
+/// in the `[factors]` table under the name of the factor kind.
+/// Every key other than `enabled`, `sigma` and `attach-to` is a setting of the
+/// factor kind itself.
+///
+/// ## Example
+/// ```toml
+/// [factors.speed-limit]
+/// enabled   = true
+/// sigma     = 0.1
+/// attach-to = "consecutive"
+/// max-speed = 2.0
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct UserFactorSection {
+    /// Whether the factors of this kind are enabled
+    #[serde(default = "UserFactorSection::default_enabled")]
+    pub enabled:   bool,
+    /// Sigma of the factors, instead of the default of the factor kind
+    #[serde(default)]
+    pub sigma:     Option<f32>,
+    /// Which variables the factors are attached to, instead of the default of
+    /// the factor kind
+    #[serde(default)]
+    pub attach_to: Option<Attachment>,
+    /// The settings of the factor kind
+    #[serde(flatten)]
+    pub settings:  toml::Table,
+}
+
+impl UserFactorSection {
+    const fn default_enabled() -> bool {
+        true
+    }
+}
+
+impl Default for UserFactorSection {
+    fn default() -> Self {
+        Self {
+            enabled:   Self::default_enabled(),
+            sigma:     None,
+            attach_to: None,
+            settings:  toml::Table::new(),
+        }
+    }
+}
+
+/// Sections of every user defined factor kind, by the name of the kind
+pub type UserFactorsSection = BTreeMap<String, UserFactorSection>;
+
 /// **Communication Section**
 /// Contains parameters for the communication between robots
 /// - `radius`: Inter-robot factors created if robots are within this range of
 ///   each other
 /// - `failure_rate`: Probability for failing to send/receive a message
+/// - `delay`: Delay before a message is delivered to the other robot
+/// - `drop_probability`: Probability for any single message to be lost
+/// - `distance_loss`: Additional message loss increasing with the distance
+///   between the robots
+/// - `bandwidth`: Max number of messages sent from one robot to another per
+///   timestep
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct CommunicationSection {
@@ -608,13 +813,99 @@ pub struct CommunicationSection {
     // TODO: use a percentage type instead of f32
     /// Probability for failing to send/receive a message
     pub failure_rate: f32,
+
+    /// Delay before a message sent to another robot is delivered
+    #[serde(default)]
+    pub delay: CommunicationDelay,
+
+    /// Probability for a message sent to another robot to be dropped
+    #[serde(default = "CommunicationSection::default_drop_probability")]
+    pub drop_probability: UnitInterval,
+
+    /// Message loss increasing with the distance between the robots.
+    /// Disabled if not set
+    #[serde(default)]
+    pub distance_loss: Option<DistanceLoss>,
+
+    /// Max number of messages each robot can send to another robot per
+    /// timestep. Messages exceeding it are dropped. Unlimited if not set
+    #[serde(default)]
+    pub bandwidth: Option<NonZeroUsize>,
+}
+
+impl CommunicationSection {
+    fn default_drop_probability() -> UnitInterval {
+        UnitInterval::new(0.0).expect("0.0 in [0.0, 1.0]")
+    }
 }
 
 impl Default for CommunicationSection {
     fn default() -> Self {
         Self {
-            radius:       20.0.try_into().expect("20.0 > 0.0"),
+            radius: 20.0.try_into().expect("20.0 > 0.0"),
             failure_rate: 0.2,
+            delay: CommunicationDelay::default(),
+            drop_probability: Self::default_drop_probability(),
+            distance_loss: None,
+            bandwidth: None,
+        }
+    }
+}
+
+/// Delay before a message sent to another robot is delivered
+///
+/// ```toml
+/// delay = { timesteps = 2 }
+/// # or
+/// delay = { seconds = 0.15 }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CommunicationDelay {
+    /// Delay in number of fixed timesteps of the simulation
+    Timesteps(u32),
+    /// Delay in seconds of simulated time. Rounded up to whole timesteps, as
+    /// messages are only exchanged once per timestep
+    Seconds(f32),
+}
+
+impl Default for CommunicationDelay {
+    fn default() -> Self {
+        Self::Timesteps(0)
+    }
+}
+
+impl CommunicationDelay {
+    /// The delay in seconds, given the duration of a timestep in seconds
+    #[must_use]
+    pub fn as_secs(&self, timestep: f64) -> f64 {
+        match *self {
+            Self::Timesteps(timesteps) => f64::from(timesteps) * timestep,
+            Self::Seconds(seconds) => f64::from(seconds),
+        }
+    }
+}
+
+/// Message loss increasing linearly with the distance between the sender and
+/// the receiver, from no loss at `start` to every message lost at `end`
+/// SI unit: m
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DistanceLoss {
+    pub start: f32,
+    pub end:   f32,
+}
+
+impl DistanceLoss {
+    /// Probability of losing a message sent over `distance` meters
+    #[must_use]
+    pub fn probability(&self, distance: f32) -> f64 {
+        if distance <= self.start {
+            0.0
+        } else if distance >= self.end {
+            1.0
+        } else {
+            f64::from((distance - self.start) / (self.end - self.start))
         }
     }
 }
@@ -661,6 +952,30 @@ pub struct RobotSection {
     /// Communication parameters
     pub communication: CommunicationSection,
     pub inter_robot_safety_distance_multiplier: StrictlyPositiveFinite<f32>,
+    /// Hard limit on the speed of the robot, enforced by the velocity limit
+    /// factors. Overridden by the limits of the motion model of the formation
+    /// the robot is spawned from, if any. No limit if not set
+    /// SI unit: m/s
+    #[serde(default)]
+    pub max_speed: Option<StrictlyPositiveFinite<f32>>,
+    /// Hard limit on the acceleration of the robot, enforced by the
+    /// acceleration limit factors. Overridden by the limits of the motion model
+    /// of the formation the robot is spawned from, if any. No limit if not set
+    /// SI unit: m/s^2
+    #[serde(default)]
+    pub max_acceleration: Option<StrictlyPositiveFinite<f32>>,
+    /// Priority of the robot when avoiding other robots. A robot with a higher
+    /// priority takes a smaller share of the responsibility for avoiding a
+    /// robot with a lower priority, e.g. 2.0 for a loaded robot and 4.0 for an
+    /// emergency robot, if empty robots have the default of 1.0
+    #[serde(default = "RobotSection::default_priority")]
+    pub priority: StrictlyPositiveFinite<f32>,
+}
+
+impl RobotSection {
+    fn default_priority() -> StrictlyPositiveFinite<f32> {
+        StrictlyPositiveFinite::<f32>::new(1.0).expect("1.0 > 0.0")
+    }
 }
 
 impl Default for RobotSection {
@@ -675,10 +990,164 @@ impl Default for RobotSection {
             // **gbpplanner** effectively uses 2.2 * radius with the way they calculate it
             inter_robot_safety_distance_multiplier: StrictlyPositiveFinite::<f32>::new(2.2)
                 .expect("2.2 > 0.0"),
+            max_speed: None,
+            max_acceleration: None,
+            priority: Self::default_priority(),
+        }
+    }
+}
+
+/// **Factor Sigmas Section**
+/// Sigmas of the factors of the robots of a robot profile. Every sigma not set
+/// is taken from the `[gbp]` section
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FactorSigmasSection {
+    #[serde(default)]
+    pub dynamics: Option<f32>,
+    #[serde(default)]
+    pub interrobot: Option<f32>,
+    #[serde(default)]
+    pub obstacle: Option<f32>,
+    #[serde(default)]
+    pub tracking: Option<f32>,
+    #[serde(default)]
+    pub dynamic_obstacle: Option<f32>,
+    #[serde(default)]
+    pub velocity: Option<f32>,
+    #[serde(default)]
+    pub acceleration: Option<f32>,
+}
+
+/// Sigmas of the factors of a robot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FactorSigmas {
+    pub dynamics: f32,
+    pub interrobot: f32,
+    pub obstacle: f32,
+    pub tracking: f32,
+    pub dynamic_obstacle: f32,
+    pub velocity: f32,
+    pub acceleration: f32,
+}
+
+impl FactorSigmas {
+    /// The sigmas of the `[gbp]` section, overridden by the ones set in
+    /// `section`
+    #[must_use]
+    pub fn new(gbp: &GbpSection, section: FactorSigmasSection) -> Self {
+        Self {
+            dynamics: section.dynamics.unwrap_or(gbp.sigma_factor_dynamics),
+            interrobot: section.interrobot.unwrap_or(gbp.sigma_factor_interrobot),
+            obstacle: section.obstacle.unwrap_or(gbp.sigma_factor_obstacle),
+            tracking: section.tracking.unwrap_or(gbp.sigma_factor_tracking),
+            dynamic_obstacle: section
+                .dynamic_obstacle
+                .unwrap_or(gbp.sigma_factor_dynamic_obstacle),
+            velocity: section.velocity.unwrap_or(gbp.sigma_factor_velocity),
+            acceleration: section
+                .acceleration
+                .unwrap_or(gbp.sigma_factor_acceleration),
         }
     }
 }
 
+/// **Robot Profile Section**
+/// A named kind of robot, in the `[robot-profiles]` table under its name.
+/// Formations spawn robots of a profile by referencing it by name with
+/// `robot-profile`. Every parameter not set is taken from the `[robot]` and
+/// `[gbp]` sections.
+///
+/// ## Example
+/// ```toml
+/// [robot-profiles.truck]
+/// radius               = { min = 2.5, max = 3.0 }
+/// target-speed         = 2.0
+/// planning-horizon     = 8.0
+/// variables            = 8
+/// communication-radius = 40.0
+/// sigmas               = { dynamics = 0.2 }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RobotProfileSection {
+    /// Range the radius of every robot is sampled from
+    #[serde(default)]
+    pub radius: Option<RobotRadiusSection>,
+    /// SI unit: m/s
+    #[serde(default)]
+    pub target_speed: Option<NaturalQuantity>,
+    /// SI unit: s
+    #[serde(default)]
+    pub planning_horizon: Option<NaturalQuantity>,
+    /// Number of variables in the factorgraph of every robot, spaced evenly
+    /// over the planning horizon. If not set, the variables are spaced out
+    /// further the further they are from the current state, as given by
+    /// `lookahead-multiple` of the `[gbp]` section. **constraint**: >= 2
+    #[serde(default)]
+    pub variables: Option<NonZeroUsize>,
+    /// SI unit: m
+    #[serde(default)]
+    pub communication_radius: Option<NaturalQuantity>,
+    /// SI unit: m/s
+    #[serde(default)]
+    pub max_speed: Option<NaturalQuantity>,
+    /// SI unit: m/s^2
+    #[serde(default)]
+    pub max_acceleration: Option<NaturalQuantity>,
+    /// Motion model of the robots, used by formations that do not set one
+    #[serde(default)]
+    pub motion_model: Option<formation::MotionModel>,
+    /// Priority when avoiding other robots, see [`RobotSection::priority`]
+    #[serde(default)]
+    pub priority: Option<NaturalQuantity>,
+    #[serde(default)]
+    pub sigmas: FactorSigmasSection,
+}
+
+/// Every robot profile of the config, by name
+pub type RobotProfilesSection = BTreeMap<String, RobotProfileSection>;
+
+/// The parameters of a robot, given by its robot profile, see
+/// [`Config::robot_profile`]
+#[derive(Debug, Clone)]
+pub struct RobotProfile {
+    /// Name of the robot profile, `None` for robots of the `[robot]` section
+    pub name: Option<String>,
+    /// Range the radius of the robot is sampled from
+    pub radius: RobotRadiusSection,
+    /// SI unit: m/s
+    pub target_speed: NaturalQuantity,
+    /// SI unit: s
+    pub planning_horizon: NaturalQuantity,
+    /// Number of variables, evenly spaced over the planning horizon. If
+    /// `None`, the variables are spaced by `lookahead-multiple`
+    pub variables: Option<NonZeroUsize>,
+    /// SI unit: m
+    pub communication_radius: NaturalQuantity,
+    /// SI unit: m/s
+    pub max_speed: Option<NaturalQuantity>,
+    /// SI unit: m/s^2
+    pub max_acceleration: Option<NaturalQuantity>,
+    /// Motion model of the robot, if it is not set by the formation
+    pub motion_model: Option<formation::MotionModel>,
+    /// Priority when avoiding other robots, see [`RobotSection::priority`]
+    pub priority: NaturalQuantity,
+    /// Sigmas of the factors of the robot
+    pub sigmas: FactorSigmas,
+}
+
+/// Error returned by [`Config::robot_profile`]
+#[derive(Debug, thiserror::Error)]
+pub enum RobotProfileError {
+    /// No profile with the name in the `[robot-profiles]` table
+    #[error("there is no robot profile named '{0}' in [robot-profiles]")]
+    Unknown(String),
+    /// The profile has less than 2 variables, i.e. no horizon state
+    #[error("the robot profile '{0}' has less than 2 variables")]
+    TooFewVariables(String),
+}
+
 /// Interaction Section
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -756,6 +1225,124 @@ impl Default for SmoothingSection {
     }
 }
 
+/// **Global Planner Section**
+/// Contains parameters for the global planners besides RRT*
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct GlobalPlannerSection {
+    /// The occupancy grid searched by A* and Theta*
+    #[serde(default)]
+    pub grid: GridPlannerSection,
+    /// The probabilistic roadmap
+    #[serde(default)]
+    pub prm: PrmSection,
+    /// The space-time reservation table of prioritised planning
+    #[serde(default)]
+    pub reservation: ReservationSection,
+}
+
+/// **Grid Planner Section**
+/// Contains parameters for the occupancy grid searched by A* and Theta*
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct GridPlannerSection {
+    /// Side length of the cells of the grid
+    #[serde(default = "GridPlannerSection::default_cell_size")]
+    pub cell_size: StrictlyPositiveFinite<f32>,
+    /// A cell is occupied if a ball of this radius at its center intersects an
+    /// obstacle
+    #[serde(default = "GridPlannerSection::default_collision_radius")]
+    pub collision_radius: StrictlyPositiveFinite<f32>,
+}
+
+impl GridPlannerSection {
+    fn default_cell_size() -> StrictlyPositiveFinite<f32> {
+        StrictlyPositiveFinite::<f32>::new(2.0).expect("2.0 > 0.0")
+    }
+
+    fn default_collision_radius() -> StrictlyPositiveFinite<f32> {
+        StrictlyPositiveFinite::<f32>::new(2.0).expect("2.0 > 0.0")
+    }
+}
+
+impl Default for GridPlannerSection {
+    fn default() -> Self {
+        Self {
+            cell_size: Self::default_cell_size(),
+            collision_radius: Self::default_collision_radius(),
+        }
+    }
+}
+
+/// **PRM Section**
+/// Contains parameters for the probabilistic roadmap shared between robots
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PrmSection {
+    /// Number of collision free samples in the roadmap
+    #[serde(default = "PrmSection::default_samples")]
+    pub samples: NonZeroUsize,
+    /// Samples closer than this are connected, if the line between them is
+    /// collision free
+    #[serde(default = "PrmSection::default_connection_radius")]
+    pub connection_radius: StrictlyPositiveFinite<f32>,
+    /// The collision radius to check samples and connections with
+    #[serde(default = "PrmSection::default_collision_radius")]
+    pub collision_radius: StrictlyPositiveFinite<f32>,
+}
+
+impl PrmSection {
+    fn default_samples() -> NonZeroUsize {
+        NonZeroUsize::new(500).expect("500 > 0")
+    }
+
+    fn default_connection_radius() -> StrictlyPositiveFinite<f32> {
+        StrictlyPositiveFinite::<f32>::new(20.0).expect("20.0 > 0.0")
+    }
+
+    fn default_collision_radius() -> StrictlyPositiveFinite<f32> {
+        StrictlyPositiveFinite::<f32>::new(2.0).expect("2.0 > 0.0")
+    }
+}
+
+impl Default for PrmSection {
+    fn default() -> Self {
+        Self {
+            samples: Self::default_samples(),
+            connection_radius: Self::default_connection_radius(),
+            collision_radius: Self::default_collision_radius(),
+        }
+    }
+}
+
+/// **Reservation Section**
+/// Contains parameters for prioritised planning, where robots plan one after
+/// the other on the occupancy grid, around the cells reserved by the robots
+/// that planned before them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ReservationSection {
+    /// The maximum number of cell to cell moves, waits included, of a planned
+    /// path. A robot without a path around the reservations within this many
+    /// moves plans as if there were no other robots
+    #[serde(default = "ReservationSection::default_max_timesteps")]
+    pub max_timesteps: NonZeroUsize,
+}
+
+impl ReservationSection {
+    fn default_max_timesteps() -> NonZeroUsize {
+        NonZeroUsize::new(500).expect("500 > 0")
+    }
+}
+
+impl Default for ReservationSection {
+    fn default() -> Self {
+        Self {
+            max_timesteps: Self::default_max_timesteps(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct DebugSection {
@@ -829,6 +1416,10 @@ pub struct Config {
     /// size and smoothing parameters
     #[serde(default)]
     pub rrt: RRTSection,
+    /// **Global planner section:**
+    /// Contains parameters for the grid search and roadmap global planners
+    #[serde(default)]
+    pub global_planner: GlobalPlannerSection,
     /// **Graphviz section:**
     /// Contains parameters for how to export to graphviz
     #[serde(default)]
@@ -840,6 +1431,16 @@ pub struct Config {
 
     #[serde(default)]
     pub debug: DebugSection,
+    /// **Factors section:**
+    /// Contains a section for every factor kind registered outside of the
+    /// simulator
+    #[serde(default)]
+    pub factors: UserFactorsSection,
+    /// **Robot profiles section:**
+    /// Contains a section for every kind of robot formations can spawn,
+    /// besides the one of the robot section
+    #[serde(default)]
+    pub robot_profiles: RobotProfilesSection,
 }
 
 impl Default for Config {
@@ -864,9 +1465,12 @@ impl Default for Config {
             robot: RobotSection::default(),
             simulation: SimulationSection::default(),
             rrt: RRTSection::default(),
+            global_planner: GlobalPlannerSection::default(),
             graphviz: GraphvizSection::default(),
             manual: ManualSection::default(),
             debug: DebugSection::default(),
+            factors: UserFactorsSection::new(),
+            robot_profiles: RobotProfilesSection::new(),
         }
     }
 }
@@ -891,4 +1495,49 @@ impl Config {
         // let config = toml::from_str(contents)?;
         // Ok(config)
     }
+
+    /// The parameters of the robots of the robot profile `name`, or of the
+    /// `[robot]` section if `name` is `None`
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if there is no robot profile named `name`, or if the
+    /// profile has less than 2 variables
+    pub fn robot_profile(&self, name: Option<&str>) -> Result<RobotProfile, RobotProfileError> {
+        let section = match name {
+            Some(name) => self
+                .robot_profiles
+                .get(name)
+                .cloned()
+                .ok_or_else(|| RobotProfileError::Unknown(name.to_string()))?,
+            None => RobotProfileSection::default(),
+        };
+
+        if section
+            .variables
+            .is_some_and(|variables| variables.get() < 2)
+        {
+            return Err(RobotProfileError::TooFewVariables(
+                name.unwrap_or_default().to_string(),
+            ));
+        }
+
+        Ok(RobotProfile {
+            name: name.map(ToString::to_string),
+            radius: section.radius.unwrap_or_else(|| self.robot.radius.clone()),
+            target_speed: section.target_speed.unwrap_or(self.robot.target_speed),
+            planning_horizon: section
+                .planning_horizon
+                .unwrap_or(self.robot.planning_horizon),
+            variables: section.variables,
+            communication_radius: section
+                .communication_radius
+                .unwrap_or(self.robot.communication.radius),
+            max_speed: section.max_speed.or(self.robot.max_speed),
+            max_acceleration: section.max_acceleration.or(self.robot.max_acceleration),
+            motion_model: section.motion_model,
+            priority: section.priority.unwrap_or(self.robot.priority),
+            sigmas: FactorSigmas::new(&self.gbp, section.sigmas),
+        })
+    }
 }
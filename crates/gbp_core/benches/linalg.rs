@@ -0,0 +1,201 @@
+//! The linear algebra of the GBP update steps, with the heap allocated ndarray
+//! path compared to the stack allocated path of `gbp_linalg::fixed`.
+//!
+//! ```sh
+//! cargo bench -p gbp_core --bench linalg
+//! ```
+//!
+//! Median time per iteration, on one core of an Intel Xeon:
+//!
+//! | benchmark      | ndarray | stack   |
+//! |----------------|---------|---------|
+//! | `marginalise`  | 2.28 µs | 0.36 µs |
+//! | `pair_message` | 2.45 µs | 0.71 µs |
+
+use std::{hint::black_box, ops::AddAssign};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use gbp_core::{
+    factor::{marginalise_factor_distance as marginalise, FactorNode},
+    factorgraph::FactorGraph,
+    id::{FactorId, VariableId},
+    message::{FixedMessage, InformationVec, Mean, Message, PrecisionMatrix},
+    state::StateLayout,
+    variable::VariableNode,
+    DOFS,
+};
+use gbp_linalg::{fixed, prelude::*};
+use ndarray::{array, s};
+
+/// Potential of a factor between two variables, as computed from the jacobian
+/// of a dynamic factor
+fn potential() -> (Vector<Float>, Matrix<Float>) {
+    let jacobian = Matrix::<Float>::from_shape_fn((DOFS, 2 * DOFS), |(r, c)| {
+        if c == r {
+            1.0
+        } else if c == r + DOFS {
+            -1.0
+        } else if c == r + 2 {
+            0.1
+        } else {
+            0.0
+        }
+    });
+    let precision_matrix = jacobian.t().dot(&jacobian) + Matrix::<Float>::eye(2 * DOFS);
+    let information_vector = precision_matrix.dot(&Vector::<Float>::ones(2 * DOFS));
+    (information_vector, precision_matrix)
+}
+
+fn bench_marginalise(c: &mut Criterion) {
+    let (information_vector, precision_matrix) = potential();
+    let fixed_information_vector = fixed::fixed_vector::<{ 2 * DOFS }>(information_vector.view())
+        .expect("the potential is between two variables");
+    let fixed_precision_matrix =
+        fixed::fixed_matrix::<{ 2 * DOFS }, { 2 * DOFS }>(precision_matrix.view())
+            .expect("the potential is between two variables");
+
+    let mut group = c.benchmark_group("marginalise");
+    group.bench_function("ndarray", |b| {
+        b.iter(|| {
+            marginalise::marginalise_factor_distance(
+                black_box(information_vector.clone()),
+                black_box(precision_matrix.clone()),
+                DOFS,
+                DOFS,
+            )
+        });
+    });
+    group.bench_function("stack", |b| {
+        b.iter(|| {
+            marginalise::marginalise_factor_pair(
+                black_box(&fixed_information_vector),
+                black_box(&fixed_precision_matrix),
+                DOFS,
+            )
+        });
+    });
+    group.finish();
+}
+
+/// A message from a variable, as received by a factor
+fn incoming(value: Float) -> Message {
+    let precision_matrix = Matrix::<Float>::eye(DOFS) * value;
+    let mean = Vector::<Float>::from_elem(DOFS, value);
+    Message::new(
+        InformationVec(precision_matrix.dot(&mean)),
+        PrecisionMatrix(precision_matrix),
+        Mean(mean),
+    )
+}
+
+/// Every step of a factor between two variables from the potential to the
+/// damped message sent to the second variable, i.e. aggregating the message
+/// from the first variable, marginalising, damping and converting to a
+/// [`Message`]
+fn bench_pair_message(c: &mut Criterion) {
+    let (information_vector, precision_matrix) = potential();
+    let fixed_information_vector = fixed::fixed_vector::<{ 2 * DOFS }>(information_vector.view())
+        .expect("the potential is between two variables");
+    let fixed_precision_matrix =
+        fixed::fixed_matrix::<{ 2 * DOFS }, { 2 * DOFS }>(precision_matrix.view())
+            .expect("the potential is between two variables");
+    let (received, previous) = (incoming(2.0), incoming(3.0));
+    let fixed_previous = FixedMessage::of(&previous).expect("not empty");
+
+    let mut group = c.benchmark_group("pair_message");
+    group.bench_function("ndarray", |b| {
+        b.iter(|| {
+            let received = black_box(&received)
+                .payload()
+                .expect("the message is not empty");
+            let mut information_vector = information_vector.clone();
+            let mut precision_matrix = precision_matrix.clone();
+            information_vector
+                .slice_mut(s![..DOFS])
+                .add_assign(&received.information_vector);
+            precision_matrix
+                .slice_mut(s![..DOFS, ..DOFS])
+                .add_assign(&received.precision_matrix);
+            marginalise::marginalise_factor_distance(
+                information_vector,
+                precision_matrix,
+                DOFS,
+                DOFS,
+            )
+            .damped(black_box(&previous), 0.5)
+        });
+    });
+    group.bench_function("stack", |b| {
+        b.iter(|| {
+            let received = FixedMessage::of(black_box(&received)).expect("not empty");
+            let mut information_vector = fixed_information_vector;
+            let mut precision_matrix = fixed_precision_matrix;
+            information_vector
+                .fixed_rows_mut::<DOFS>(0)
+                .add_assign(received.information_vector);
+            precision_matrix
+                .fixed_view_mut::<DOFS, DOFS>(0, 0)
+                .add_assign(received.precision_matrix);
+            marginalise::marginalise_factor_pair(&information_vector, &precision_matrix, DOFS)
+                .map(|message| message.damped(black_box(&fixed_previous), 0.5))
+                .map_or_else(Message::empty, Message::from)
+        });
+    });
+    group.finish();
+}
+
+/// A robot with a horizon of `variables` variables, connected by dynamic
+/// factors
+fn robot(variables: usize) -> FactorGraph<u32> {
+    const ID: u32 = 0;
+    let mut factorgraph = FactorGraph::new(ID);
+    let indices = (0..variables)
+        .map(|i| {
+            #[allow(clippy::cast_precision_loss)]
+            let x = i as Float;
+            let precision = if i == 0 || i == variables - 1 {
+                1e30
+            } else {
+                1.0
+            };
+            factorgraph.add_variable(VariableNode::new(
+                ID,
+                array![x, 0.0, 1.0, 0.0],
+                Matrix::<Float>::from_diag_elem(DOFS, precision),
+                StateLayout::Planar,
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    for pair in indices.windows(2) {
+        let factor = FactorNode::new_dynamic_factor(
+            ID,
+            StateLayout::Planar,
+            0.1,
+            Vector::<Float>::zeros(DOFS),
+            1.0,
+            true,
+        );
+        let factor = FactorId::new(ID, factorgraph.add_factor(factor));
+        for &variable in pair {
+            let _ = factorgraph.add_internal_edge(VariableId::new(ID, variable), factor);
+        }
+    }
+
+    factorgraph
+}
+
+fn bench_iteration(c: &mut Criterion) {
+    let mut factorgraph = robot(10);
+    c.bench_function("internal_iteration", |b| {
+        b.iter(|| factorgraph.internal_iteration());
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_marginalise,
+    bench_pair_message,
+    bench_iteration
+);
+criterion_main!(benches);
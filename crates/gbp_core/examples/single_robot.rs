@@ -0,0 +1,75 @@
+//! Plan the path of a single robot with GBP, and print the planned states.
+//!
+//! The current state of the robot and its horizon state are fixed, and the
+//! states in between are only constrained by dynamic factors, so GBP finds the
+//! constant velocity path between them.
+//!
+//! ```sh
+//! cargo run -p gbp_core --example single_robot
+//! ```
+
+use gbp_core::{
+    factor::FactorNode,
+    factorgraph::FactorGraph,
+    id::{FactorId, VariableId},
+    iteration::{self, Immediate, Participant},
+    state::StateLayout,
+    variable::VariableNode,
+    DOFS,
+};
+use gbp_linalg::prelude::*;
+use gbp_schedule::GbpScheduleAtIteration;
+use ndarray::array;
+
+fn main() {
+    const VARIABLES: usize = 6;
+    const ID: u32 = 0;
+
+    let mut factorgraph = FactorGraph::new(ID);
+    let variables = (0..VARIABLES)
+        .map(|i| {
+            // Start with every state but the horizon at the origin, far from
+            // the constant velocity path
+            let (mean, precision) = if i == VARIABLES - 1 {
+                (array![10.0, 5.0, 2.0, 1.0], 1e30)
+            } else if i == 0 {
+                (array![0.0, 0.0, 2.0, 1.0], 1e30)
+            } else {
+                (array![0.0, 0.0, 0.0, 0.0], 0.0)
+            };
+            let precision = Matrix::<Float>::from_diag_elem(DOFS, precision);
+            factorgraph.add_variable(VariableNode::new(ID, mean, precision, StateLayout::Planar))
+        })
+        .collect::<Vec<_>>();
+
+    for pair in variables.windows(2) {
+        let factor = FactorNode::new_dynamic_factor(
+            ID,
+            StateLayout::Planar,
+            0.1,
+            Vector::<Float>::zeros(DOFS),
+            1.0,
+            true,
+        );
+        let factor = FactorId::new(ID, factorgraph.add_factor(factor));
+        for &variable in pair {
+            let _ = factorgraph.add_internal_edge(VariableId::new(ID, variable), factor);
+        }
+    }
+
+    let schedule = std::iter::repeat(GbpScheduleAtIteration {
+        internal: true,
+        external: false,
+    })
+    .take(50);
+    let mut participants = [Participant::new(&mut factorgraph)];
+    iteration::iterate(&mut participants, schedule, &mut Immediate::default());
+
+    for (i, (_, variable)) in factorgraph.variables().enumerate() {
+        let mean = &variable.belief.mean;
+        println!(
+            "state {i}: position = ({:6.2}, {:6.2}), velocity = ({:5.2}, {:5.2})",
+            mean[0], mean[1], mean[2], mean[3]
+        );
+    }
+}
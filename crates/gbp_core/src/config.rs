@@ -0,0 +1,187 @@
+//! Configuration of the factors and the GBP iterations.
+//!
+//! These sections are part of the `[gbp]` section of the simulator config, and
+//! re-exported by `gbp_config`.
+
+use serde::{Deserialize, Serialize};
+use typed_floats::StrictlyPositiveFinite;
+
+/// **Tracking Section**
+/// Contains parameters for the tracking factor
+/// - `switch_padding`: Padding around the switch point
+/// - `attraction_distance`: Distance to the tracking line to normalise around
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TrackingSection {
+    /// Padding around the switch point
+    #[serde(default = "TrackingSection::default_switch_padding")]
+    pub switch_padding:      f32,
+    /// Distance to the tracking line to normalise around
+    #[serde(default = "TrackingSection::default_attraction_distance")]
+    pub attraction_distance: f32,
+    //#[serde(default = "TrackingSection::default_enabled")]
+    // pub enabled: bool,
+}
+
+impl TrackingSection {
+    fn default_enabled() -> bool {
+        true
+    }
+
+    fn default_attraction_distance() -> f32 {
+        2.0
+    }
+
+    fn default_switch_padding() -> f32 {
+        1.0
+    }
+}
+
+impl Default for TrackingSection {
+    fn default() -> Self {
+        Self {
+            switch_padding:      Self::default_switch_padding(),
+            attraction_distance: Self::default_attraction_distance(),
+            // enabled: Self::default_enabled(),
+        }
+    }
+}
+
+/// **Convergence Section**
+/// Criterion for ending the internal GBP iterations of a robot early, before
+/// all iterations in the `iterations-per-timestep` schedule have run
+///
+/// ## Example
+/// ```toml
+/// [gbp.convergence]
+/// criterion = "kl-divergence"
+/// tolerance = 0.001
+/// ```
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ConvergenceSection {
+    /// How the change in the beliefs of the variables between two iterations
+    /// is measured
+    #[serde(default)]
+    pub criterion: ConvergenceCriterion,
+    /// The iterations have converged when the largest change of any variable
+    /// is below this value
+    #[serde(default = "ConvergenceSection::default_tolerance")]
+    pub tolerance: StrictlyPositiveFinite<f32>,
+}
+
+impl ConvergenceSection {
+    fn default_tolerance() -> StrictlyPositiveFinite<f32> {
+        StrictlyPositiveFinite::<f32>::new(1e-3).expect("1e-3 > 0.0")
+    }
+}
+
+impl Default for ConvergenceSection {
+    fn default() -> Self {
+        Self {
+            criterion: ConvergenceCriterion::default(),
+            tolerance: Self::default_tolerance(),
+        }
+    }
+}
+
+/// How the change in the belief of a variable between two iterations is
+/// measured
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConvergenceCriterion {
+    /// Never end early, always run every iteration of the schedule
+    #[default]
+    None,
+    /// Euclidean distance between the previous and the new mean
+    MeanChange,
+    /// Kullback-Leibler divergence of the new belief from the previous
+    KlDivergence,
+}
+
+/// **SDF Sampling Section**
+/// How the obstacle factors sample the signed distance field image, when
+/// `obstacle-factor = "sdf"`
+///
+/// ## Example
+/// ```toml
+/// [gbp.sdf-sampling]
+/// interpolation = "bicubic"
+/// out-of-bounds = "clamp"
+/// falloff       = "quadratic"
+/// ```
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SdfSamplingSection {
+    /// How to interpolate between the pixels of the image
+    #[serde(default)]
+    pub interpolation: SdfInterpolation,
+    /// What to measure outside of the image
+    #[serde(default)]
+    pub out_of_bounds: SdfOutOfBounds,
+    /// How the measurement falls off with the distance to obstacles, when
+    /// `obstacle-factor = "distance-field"`
+    #[serde(default)]
+    pub falloff:       SdfFalloff,
+}
+
+/// How to interpolate between the pixels of the signed distance field image
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SdfInterpolation {
+    /// Use the value of the pixel the position is in. The jacobian is
+    /// computed with finite differences
+    #[default]
+    Nearest,
+    /// Interpolate linearly between the 2x2 closest pixels. The jacobian is
+    /// computed analytically
+    Bilinear,
+    /// Interpolate with a Catmull-Rom spline through the 4x4 closest pixels.
+    /// The jacobian is computed analytically
+    Bicubic,
+}
+
+/// How the measurement of an obstacle factor falls off with the signed distance
+/// `d` to the closest obstacle, relative to the clamp `c` of the distance
+/// field. The measurement is 1.0 at the boundary of obstacles, and 0.0 from `c`
+/// away from them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SdfFalloff {
+    /// `1 - d / c`
+    #[default]
+    Linear,
+    /// `(1 - d / c)^2`, which pushes away from obstacles gently when far from
+    /// them, and strongly when close
+    Quadratic,
+}
+
+impl SdfFalloff {
+    /// The measurement at the signed distance `distance` with the clamp
+    /// `clamp`, and its derivative with respect to the distance
+    pub fn measurement(self, distance: f64, clamp: f64) -> (f64, f64) {
+        let closeness = 1.0 - distance / clamp;
+        if closeness <= 0.0 {
+            return (0.0, 0.0);
+        }
+        match self {
+            Self::Linear => (closeness, -1.0 / clamp),
+            Self::Quadratic => (closeness * closeness, -2.0 * closeness / clamp),
+        }
+    }
+}
+
+/// What to measure outside of the signed distance field image
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SdfOutOfBounds {
+    /// Outside of the image is free space. Nearest sampling reads the pixels
+    /// on the edge of the image left of and above it, like the obstacle factor
+    /// did before interpolation was configurable
+    #[default]
+    Free,
+    /// Outside of the image is an obstacle
+    Obstacle,
+    /// Use the value of the closest pixel on the edge of the image
+    Clamp,
+}
@@ -0,0 +1,194 @@
+//! Acceleration limit factor in the factorgraph
+
+use std::borrow::Cow;
+
+use gbp_linalg::prelude::*;
+use ndarray::s;
+
+use super::{Factor, FactorState, Measurement};
+
+/// Acceleration limit factor: for respecting the max acceleration of the robot.
+///
+/// This factor is created between two consecutive variables, like the
+/// [`DynamicFactor`](super::dynamic::DynamicFactor), and results in a high
+/// energy or cost if the change in velocity between them is greater than what
+/// the robot can achieve in the time between them. Like the
+/// [`InterRobotFactor`](super::interrobot::InterRobotFactor) it is a hinge
+/// loss, and has 0 energy as long as the acceleration is within the limit.
+#[derive(Debug, Clone)]
+pub struct AccelerationFactor {
+    max_acceleration: Float,
+    delta_t: Float,
+}
+
+impl AccelerationFactor {
+    /// Neighbour to variable n and n+1
+    pub const NEIGHBORS: usize = 2;
+
+    /// Create a new `AccelerationFactor`, limiting the acceleration between
+    /// two variables `delta_t` seconds apart to `max_acceleration`
+    #[must_use]
+    pub const fn new(max_acceleration: Float, delta_t: Float) -> Self {
+        Self {
+            max_acceleration,
+            delta_t,
+        }
+    }
+
+    /// Get the max acceleration
+    #[inline(always)]
+    pub const fn max_acceleration(&self) -> Float {
+        self.max_acceleration
+    }
+
+    /// Update the max acceleration
+    pub fn update_max_acceleration(&mut self, max_acceleration: Float) {
+        self.max_acceleration = max_acceleration;
+    }
+
+    /// Fraction by which the acceleration from velocity `from` to velocity `to`
+    /// exceeds the max acceleration, e.g. 0.5 for an acceleration 50% above the
+    /// max acceleration. 0.0 if the acceleration is within the limit
+    #[must_use]
+    pub fn violation(&self, from: [Float; 3], to: [Float; 3]) -> Float {
+        let magnitude = from
+            .iter()
+            .zip(to.iter())
+            .map(|(from, to)| ((to - from) / self.delta_t).powi(2))
+            .sum::<Float>()
+            .sqrt();
+        (magnitude / self.max_acceleration - 1.0).max(0.0)
+    }
+
+    /// Returns the magnitude of the acceleration between the two variables in
+    /// `x`, and the acceleration
+    fn acceleration(&self, state: &FactorState, x: &Vector<Float>) -> (Float, Vector<Float>) {
+        let dofs = state.layout.dofs();
+        let offset = state.layout.velocity_offset();
+        let dims = state.layout.position_dims();
+        let acceleration = (&x.slice(s![dofs + offset..dofs + offset + dims])
+            - &x.slice(s![offset..offset + dims]))
+            / self.delta_t;
+        (acceleration.euclidean_norm(), acceleration)
+    }
+}
+
+impl Factor for AccelerationFactor {
+    #[inline(always)]
+    fn name(&self) -> &'static str {
+        "AccelerationFactor"
+    }
+
+    #[inline]
+    fn color(&self) -> [u8; 3] {
+        // #f5bde6
+        [245, 189, 230]
+    }
+
+    fn jacobian(&self, state: &FactorState, x: &Vector<Float>) -> Cow<'_, Matrix<Float>> {
+        let dofs = state.layout.dofs();
+        let offset = state.layout.velocity_offset();
+        let mut jacobian = Matrix::<Float>::zeros((state.initial_measurement.len(), dofs * 2));
+        let (magnitude, acceleration) = self.acceleration(state, x);
+        if magnitude > self.max_acceleration {
+            let dims = acceleration.len();
+            let gradient = 1.0 / self.max_acceleration / magnitude / self.delta_t * &acceleration;
+            jacobian
+                .slice_mut(s![0, offset..offset + dims])
+                .assign(&(-1.0 * &gradient));
+            jacobian
+                .slice_mut(s![0, dofs + offset..dofs + offset + dims])
+                .assign(&gradient);
+        }
+        Cow::Owned(jacobian)
+    }
+
+    fn measure(&self, state: &FactorState, x: &Vector<Float>) -> Measurement {
+        let mut measurement = Vector::<Float>::zeros(state.initial_measurement.len());
+        let (magnitude, _) = self.acceleration(state, x);
+        if magnitude > self.max_acceleration {
+            measurement[0] = magnitude / self.max_acceleration - 1.0;
+        }
+        Measurement::new(measurement)
+    }
+
+    #[inline(always)]
+    fn jacobian_delta(&self) -> Float {
+        1e-2
+    }
+
+    /// Returns true if the acceleration between the two variables is within
+    /// the max acceleration
+    fn skip(&self, state: &FactorState) -> bool {
+        let (magnitude, _) = self.acceleration(state, &state.linearisation_point);
+        magnitude <= self.max_acceleration
+    }
+
+    #[inline(always)]
+    fn linear(&self) -> bool {
+        false
+    }
+
+    #[inline(always)]
+    fn neighbours(&self) -> usize {
+        Self::NEIGHBORS
+    }
+}
+
+impl std::fmt::Display for AccelerationFactor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "max_acceleration: {}", self.max_acceleration)?;
+        writeln!(f, "delta_t: {}", self.delta_t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use ndarray::array;
+
+    use super::*;
+    use crate::state::StateLayout;
+
+    fn factor() -> (AccelerationFactor, FactorState) {
+        let state = FactorState::new(
+            array![0.0],
+            0.01,
+            AccelerationFactor::NEIGHBORS,
+            StateLayout::Planar,
+        );
+        (AccelerationFactor::new(2.0, 0.5), state)
+    }
+
+    #[test]
+    fn penalises_only_accelerations_above_the_limit() {
+        let (factor, state) = factor();
+        // 0.5 m/s change in velocity in 0.5 s
+        let within = array![0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.5, 0.0];
+        assert_relative_eq!(factor.measure(&state, &within).value[0], 0.0);
+        assert!(factor.skip(&FactorState {
+            linearisation_point: within,
+            ..state.clone()
+        }));
+
+        // 2.5 m/s change in velocity in 0.5 s
+        let above = array![0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 2.5, 2.0];
+        assert_relative_eq!(factor.measure(&state, &above).value[0], 1.5);
+        assert!(!factor.skip(&FactorState {
+            linearisation_point: above,
+            ..state
+        }));
+        assert_relative_eq!(factor.violation([1.0, 0.0, 0.0], [2.5, 2.0, 0.0]), 1.5);
+    }
+
+    #[test]
+    fn analytic_jacobian_matches_first_order() {
+        let (factor, state) = factor();
+        let linearisation_point = array![0.0, 0.0, 1.0, -1.0, 1.0, 0.5, -2.0, 1.5];
+        let analytic = factor.jacobian(&state, &linearisation_point).into_owned();
+        let numeric = factor.first_order_jacobian(&state, linearisation_point);
+        for (a, n) in analytic.iter().zip(numeric.iter()) {
+            assert_relative_eq!(a, n, epsilon = 1e-2);
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Exact signed distance to the colliders of the environment
+
+use std::sync::Arc;
+
+use glam::Vec2;
+use parry2d::{
+    bounding_volume::{Aabb, BoundingVolume},
+    math::{Isometry, Point},
+    partitioning::Qbvh,
+    query::PointQuery,
+    shape::Shape,
+};
+
+/// A shape placed in the environment
+#[derive(Clone)]
+pub struct Collider {
+    /// Where the shape is placed
+    pub isometry: Isometry<f32>,
+    /// The shape
+    pub shape:    Arc<dyn Shape>,
+}
+
+/// Signed distance function over the colliders of the environment.
+/// The colliders are stored in a bounding volume hierarchy, so only the few
+/// colliders near a queried point have their distance computed exactly.
+pub struct ColliderSdf {
+    colliders: Vec<Collider>,
+    /// Leaves are the index of the collider in `colliders`, with their
+    /// [`Aabb`] loosened by `safety_distance`
+    bvh: Qbvh<u32>,
+    /// Points further than this from every collider are considered free
+    safety_distance: f32,
+}
+
+#[allow(clippy::missing_fields_in_debug)]
+impl std::fmt::Debug for ColliderSdf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ColliderSdf")
+            .field("colliders", &self.colliders.len())
+            .field("safety_distance", &self.safety_distance)
+            .finish()
+    }
+}
+
+/// The closest collider to a point
+#[derive(Debug, Clone, Copy)]
+pub struct SignedDistance {
+    /// Distance to the surface of the collider. Negative if the point is
+    /// inside the collider
+    pub distance: f32,
+    /// Gradient of `distance` with respect to the point, i.e. the unit
+    /// direction moving the point away from the collider
+    pub gradient: Vec2,
+}
+
+impl ColliderSdf {
+    /// Build the bounding volume hierarchy over `colliders`
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn new(colliders: impl IntoIterator<Item = Collider>, safety_distance: f32) -> Self {
+        let colliders: Vec<Collider> = colliders.into_iter().collect();
+        let leaves: Vec<(u32, Aabb)> = colliders
+            .iter()
+            .enumerate()
+            .map(|(i, collider)| {
+                let aabb = collider.shape.compute_aabb(&collider.isometry);
+                (i as u32, aabb.loosened(safety_distance))
+            })
+            .collect();
+        let mut bvh = Qbvh::new();
+        bvh.clear_and_rebuild(leaves.into_iter(), 0.0);
+
+        Self {
+            colliders,
+            bvh,
+            safety_distance,
+        }
+    }
+
+    /// Points further than this from every collider are considered free
+    #[inline]
+    pub const fn safety_distance(&self) -> f32 {
+        self.safety_distance
+    }
+
+    /// Signed distance from `position` to the closest collider.
+    /// Returns `None` if no collider is within the safety distance
+    pub fn signed_distance(&self, position: Vec2) -> Option<SignedDistance> {
+        let point = Point::new(position.x, position.y);
+        let mut candidates = Vec::new();
+        self.bvh
+            .intersect_aabb(&Aabb::new(point, point), &mut candidates);
+
+        candidates
+            .into_iter()
+            .map(|i| {
+                let collider = &self.colliders[i as usize];
+                let projection = collider
+                    .shape
+                    .project_point(&collider.isometry, &point, false);
+                let to_surface = Vec2::new(projection.point.x, projection.point.y) - position;
+                let length = to_surface.length();
+                let direction = if length > 0.0 {
+                    to_surface / length
+                } else {
+                    Vec2::ZERO
+                };
+                if projection.is_inside {
+                    SignedDistance {
+                        distance: -length,
+                        gradient: direction,
+                    }
+                } else {
+                    SignedDistance {
+                        distance: length,
+                        gradient: -direction,
+                    }
+                }
+            })
+            .filter(|signed_distance| signed_distance.distance < self.safety_distance)
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use parry2d::{na::Isometry2, shape};
+
+    use super::*;
+
+    fn ball_and_box() -> ColliderSdf {
+        let colliders = [
+            Collider {
+                isometry: Isometry2::translation(0.0, 0.0),
+                shape:    Arc::new(shape::Ball::new(1.0)),
+            },
+            Collider {
+                isometry: Isometry2::translation(10.0, 0.0),
+                shape:    Arc::new(shape::Cuboid::new([1.0, 2.0].into())),
+            },
+        ];
+        ColliderSdf::new(colliders, 2.0)
+    }
+
+    #[test]
+    fn outside_points_away_from_closest_collider() {
+        let sdf = ball_and_box();
+        let closest = sdf.signed_distance(Vec2::new(0.0, 2.5)).unwrap();
+        assert_relative_eq!(closest.distance, 1.5);
+        assert_relative_eq!(closest.gradient.x, 0.0);
+        assert_relative_eq!(closest.gradient.y, 1.0);
+
+        let closest = sdf.signed_distance(Vec2::new(8.0, 1.0)).unwrap();
+        assert_relative_eq!(closest.distance, 1.0);
+        assert_relative_eq!(closest.gradient.x, -1.0);
+    }
+
+    #[test]
+    fn inside_is_negative() {
+        let sdf = ball_and_box();
+        let closest = sdf.signed_distance(Vec2::new(10.5, 0.0)).unwrap();
+        assert_relative_eq!(closest.distance, -0.5);
+        assert_relative_eq!(closest.gradient.x, 1.0);
+    }
+
+    #[test]
+    fn far_away_is_free() {
+        let sdf = ball_and_box();
+        assert!(sdf.signed_distance(Vec2::new(5.0, 0.0)).is_none());
+        assert!(sdf.signed_distance(Vec2::new(-100.0, 40.0)).is_none());
+    }
+}
@@ -6,9 +6,12 @@ use gbp_linalg::{prelude::*, pretty_format_matrix};
 use ndarray::{concatenate, Axis};
 
 use super::{Factor, FactorState, Measurement};
-use crate::factorgraph::DOFS;
 
-/// Dynamic factor: constant velocity model
+/// Dynamic factor: constant velocity model.
+///
+/// Every element of the configuration of the state layout moves with constant
+/// velocity, including the altitude of the spatial layout and the heading of
+/// the planar pose layout
 #[derive(Debug)]
 pub struct DynamicFactor {
     cached_jacobian: Matrix<Float>,
@@ -20,8 +23,10 @@ impl DynamicFactor {
     #[must_use]
     #[allow(clippy::similar_names)]
     pub fn new(state: &mut FactorState, delta_t: Float) -> Self {
-        let eye = Matrix::<Float>::eye(DOFS / 2);
-        let zeros = Matrix::<Float>::zeros((DOFS / 2, DOFS / 2));
+        let dofs = state.layout.dofs();
+        let configuration_dims = state.layout.configuration_dims();
+        let eye = Matrix::<Float>::eye(configuration_dims);
+        let zeros = Matrix::<Float>::zeros((configuration_dims, configuration_dims));
         let qc_inv = Float::powi(state.strength, -2) * &eye;
 
         let qi_inv = concatenate![
@@ -37,7 +42,7 @@ impl DynamicFactor {
                 (4.0 / delta_t) * &qc_inv
             ]
         ];
-        debug_assert_eq!(qi_inv.shape(), &[DOFS, DOFS]);
+        debug_assert_eq!(qi_inv.shape(), &[dofs, dofs]);
 
         state.measurement_precision = qi_inv;
 
@@ -46,7 +51,7 @@ impl DynamicFactor {
             concatenate![Axis(1), eye, delta_t * &eye, -1.0 * &eye, zeros],
             concatenate![Axis(1), zeros, eye, zeros, -1.0 * &eye]
         ];
-        debug_assert_eq!(cached_jacobian.shape(), &[DOFS, DOFS * 2]);
+        debug_assert_eq!(cached_jacobian.shape(), &[dofs, dofs * 2]);
 
         Self { cached_jacobian }
     }
@@ -0,0 +1,219 @@
+//! Dynamic obstacle factor
+
+use std::{borrow::Cow, sync::Arc};
+
+use gbp_linalg::prelude::*;
+use glam::Vec2;
+use ndarray::{array, s};
+
+use super::{Factor, FactorState, Measurement};
+
+/// The state of a moving obstacle, at the time it was observed
+#[derive(Debug, Clone, Copy)]
+pub struct ObstacleSnapshot {
+    /// Position of the center of the obstacle
+    pub position: Vec2,
+    /// Velocity of the obstacle
+    pub velocity: Vec2,
+    /// Radius of the bounding circle of the obstacle
+    pub radius:   f32,
+}
+
+impl ObstacleSnapshot {
+    /// Predicted position of the obstacle `seconds` into the future, assuming
+    /// it keeps its current velocity
+    #[inline]
+    pub fn predict(&self, seconds: f32) -> Vec2 {
+        self.position + self.velocity * seconds
+    }
+}
+
+/// Dynamic obstacle factor: for avoidance of moving obstacles
+/// This factor results in a high energy or cost if the variable is planned to
+/// be close to where a moving obstacle is predicted to be, at the timestep of
+/// the variable. The factor has 0 energy if the variable is further away than
+/// the safety distance from the bounding circle of every obstacle.
+#[derive(Debug, Clone)]
+pub struct DynamicObstacleFactor {
+    /// Seconds from the current timestep to the timestep of the variable
+    time_offset:     f32,
+    /// Distance from the bounding circle of an obstacle within which the
+    /// factor is active
+    safety_distance: Float,
+    /// The obstacles as they were observed in the current timestep
+    obstacles:       Arc<[ObstacleSnapshot]>,
+}
+
+impl DynamicObstacleFactor {
+    /// A dynamic obstacle factor has a single edge to another variable
+    pub const NEIGHBORS: usize = 1;
+
+    /// Creates a new [`DynamicObstacleFactor`], without any obstacles
+    #[must_use]
+    pub fn new(time_offset: f32, safety_distance: Float) -> Self {
+        Self {
+            time_offset,
+            safety_distance,
+            obstacles: Arc::new([]),
+        }
+    }
+
+    /// Replace the obstacles with the ones observed in the current timestep
+    pub fn update_obstacles(&mut self, obstacles: Arc<[ObstacleSnapshot]>) {
+        self.obstacles = obstacles;
+    }
+
+    /// Seconds from the current timestep to the timestep of the variable
+    #[inline]
+    pub const fn time_offset(&self) -> f32 {
+        self.time_offset
+    }
+
+    /// The obstacle closest to `position` at the timestep of the variable.
+    /// Returns the difference between `position` and the predicted center of
+    /// the obstacle, and the distance from `position` to its bounding circle
+    fn closest_obstacle(&self, position: &Vector<Float>) -> Option<(Vector<Float>, Float)> {
+        self.obstacles
+            .iter()
+            .map(|obstacle| {
+                let predicted = obstacle.predict(self.time_offset);
+                let diff = array![
+                    position[0] - Float::from(predicted.x),
+                    position[1] - Float::from(predicted.y)
+                ];
+                let distance = diff.euclidean_norm() - Float::from(obstacle.radius);
+                (diff, distance)
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+}
+
+impl Factor for DynamicObstacleFactor {
+    #[inline(always)]
+    fn name(&self) -> &'static str {
+        "DynamicObstacleFactor"
+    }
+
+    #[inline]
+    fn color(&self) -> [u8; 3] {
+        // #f5a97f
+        [245, 169, 127]
+    }
+
+    fn jacobian(
+        &self,
+        state: &FactorState,
+        linearisation_point: &Vector<Float>,
+    ) -> Cow<'_, Matrix<Float>> {
+        let mut jacobian =
+            Matrix::<Float>::zeros((state.initial_measurement.len(), state.layout.dofs()));
+        let position = linearisation_point.slice(s![..2]).to_owned();
+        if let Some((diff, distance)) = self.closest_obstacle(&position) {
+            let radius = diff.euclidean_norm();
+            if distance <= self.safety_distance && radius > 0.0 {
+                jacobian
+                    .slice_mut(s![0, ..2])
+                    .assign(&(-1.0 / self.safety_distance / radius * &diff));
+            }
+        }
+
+        Cow::Owned(jacobian)
+    }
+
+    fn measure(&self, state: &FactorState, linearisation_point: &Vector<Float>) -> Measurement {
+        let mut measurement = Vector::<Float>::zeros(state.initial_measurement.len());
+        let position = linearisation_point.slice(s![..2]).to_owned();
+        if let Some((_, distance)) = self.closest_obstacle(&position) {
+            if distance <= self.safety_distance {
+                measurement[0] = 1.0 - distance / self.safety_distance;
+            }
+        }
+
+        Measurement::new(measurement)
+    }
+
+    #[inline(always)]
+    fn jacobian_delta(&self) -> Float {
+        1e-2
+    }
+
+    /// Returns true if the variable is further than the safety distance from
+    /// every obstacle
+    fn skip(&self, state: &FactorState) -> bool {
+        let position = state.linearisation_point.slice(s![..2]).to_owned();
+        self.closest_obstacle(&position)
+            .map_or(true, |(_, distance)| distance > self.safety_distance)
+    }
+
+    #[inline(always)]
+    fn linear(&self) -> bool {
+        false
+    }
+
+    #[inline(always)]
+    fn neighbours(&self) -> usize {
+        Self::NEIGHBORS
+    }
+}
+
+impl std::fmt::Display for DynamicObstacleFactor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "time_offset: {}", self.time_offset)?;
+        writeln!(f, "safety_distance: {}", self.safety_distance)?;
+        writeln!(f, "obstacles: {}", self.obstacles.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::state::StateLayout;
+
+    fn factor_with_obstacle(time_offset: f32) -> (DynamicObstacleFactor, FactorState) {
+        let mut factor = DynamicObstacleFactor::new(time_offset, 2.0);
+        factor.update_obstacles(Arc::new([ObstacleSnapshot {
+            position: Vec2::new(0.0, 0.0),
+            velocity: Vec2::new(1.0, 0.0),
+            radius:   1.0,
+        }]));
+        let state = FactorState::new(
+            array![0.0],
+            0.01,
+            DynamicObstacleFactor::NEIGHBORS,
+            StateLayout::Planar,
+        );
+        (factor, state)
+    }
+
+    #[test]
+    fn measures_distance_to_predicted_position() {
+        let (factor, state) = factor_with_obstacle(4.0);
+        // The obstacle is predicted to be at (4, 0), so the variable is 1 m from
+        // its bounding circle
+        let linearisation_point = array![4.0, 2.0, 0.0, 0.0];
+        let measurement = factor.measure(&state, &linearisation_point);
+        assert_relative_eq!(measurement.value[0], 0.5);
+
+        // Without the prediction the variable would be outside the safety distance
+        let (factor, state) = factor_with_obstacle(0.0);
+        let measurement = factor.measure(&state, &linearisation_point);
+        assert_relative_eq!(measurement.value[0], 0.0);
+        assert!(factor.skip(&FactorState {
+            linearisation_point,
+            ..state
+        }));
+    }
+
+    #[test]
+    fn analytic_jacobian_matches_first_order() {
+        let (factor, state) = factor_with_obstacle(1.0);
+        let linearisation_point = array![1.5, 1.5, 0.3, -0.2];
+        let analytic = factor.jacobian(&state, &linearisation_point).into_owned();
+        let numeric = factor.first_order_jacobian(&state, linearisation_point);
+        for (a, n) in analytic.iter().zip(numeric.iter()) {
+            assert_relative_eq!(a, n, epsilon = 1e-2);
+        }
+    }
+}
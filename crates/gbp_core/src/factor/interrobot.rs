@@ -1,29 +1,26 @@
 use std::{borrow::Cow, num::NonZeroUsize, ops::Sub};
 
-use bevy::log::info;
 use gbp_linalg::prelude::*;
 use ndarray::s;
+use tracing::info;
 use typed_floats::StrictlyPositiveFinite;
 
 use super::{Factor, FactorState, Measurement};
-use crate::factorgraph::{
-    factorgraph::{FactorGraphId, VariableIndex},
-    DOFS,
-};
+use crate::factorgraph::VariableIndex;
 
 /// Identifier for a external variable, i.e. a variable in another factorgraph
 /// than the one this interrobot factor belongs to
 #[derive(Debug, Clone, Copy)]
-pub struct ExternalVariableId {
+pub struct ExternalVariableId<Id> {
     /// The factorgraph id
-    pub factorgraph_id: FactorGraphId,
+    pub factorgraph_id: Id,
     /// The variable index
     pub variable_index: VariableIndex,
 }
 
-impl ExternalVariableId {
+impl<Id> ExternalVariableId<Id> {
     /// Create a new `ExternalVariableId`
-    pub const fn new(factorgraph_id: FactorGraphId, variable_index: VariableIndex) -> Self {
+    pub const fn new(factorgraph_id: Id, variable_index: VariableIndex) -> Self {
         Self {
             factorgraph_id,
             variable_index,
@@ -31,22 +28,73 @@ impl ExternalVariableId {
     }
 }
 
+/// Priorities of the two robots connected by an interrobot factor, the robot
+/// the factor belongs to and the robot of the external variable.
+///
+/// A robot with a higher priority takes a smaller share of the responsibility
+/// for avoiding the other robot, i.e. it yields less. Robots with the same
+/// priority share the responsibility equally, as in **gbpplanner**.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Priorities {
+    /// Priority of the robot the factor belongs to
+    pub own:      StrictlyPositiveFinite<Float>,
+    /// Priority of the robot of the external variable
+    pub external: StrictlyPositiveFinite<Float>,
+}
+
+impl Priorities {
+    /// Create new `Priorities`
+    #[must_use]
+    pub const fn new(
+        own: StrictlyPositiveFinite<Float>,
+        external: StrictlyPositiveFinite<Float>,
+    ) -> Self {
+        Self { own, external }
+    }
+
+    /// Share of the avoidance responsibility of the robot the factor belongs
+    /// to if `own`, otherwise of the robot of the external variable.
+    /// The shares of the two robots sum to 2.0, and are both 1.0 for equal
+    /// priorities.
+    #[must_use]
+    pub fn share(&self, own: bool) -> Float {
+        let (own_priority, external_priority) = (self.own.get(), self.external.get());
+        let other_priority = if own { external_priority } else { own_priority };
+        2.0 * other_priority / (own_priority + external_priority)
+    }
+}
+
+impl Default for Priorities {
+    fn default() -> Self {
+        let equal = StrictlyPositiveFinite::<Float>::new(1.0).expect("1.0 > 0.0");
+        Self::new(equal, equal)
+    }
+}
+
 /// Interrobot factor: for avoidance of other robots
 /// This factor results in a high energy or cost if two robots are planning to
 /// be in the same position at the same timestep (collision). This factor is
 /// created between variables of two robots. The factor has 0 energy if the
 /// variables are further away than the safety distance.
+///
+/// The distance is measured between the positions of the state layout, i.e. in
+/// 3D for the spatial layout.
+///
+/// The precision of the message sent to each of the two variables is scaled
+/// by the share of the avoidance responsibility of its robot, given by the
+/// [`Priorities`] of the robots.
 #[derive(Debug, Clone)]
-pub struct InterRobotFactor {
+pub struct InterRobotFactor<Id> {
     safety_distance: Float,
     robot_radius: Float,
     skip: bool,
-    pub external_variable: ExternalVariableId,
+    pub external_variable: ExternalVariableId<Id>,
     tiny_offset: Float,
+    priorities: Priorities,
     // all_zeros_jacobian: Matrix<Float>,
 }
 
-impl InterRobotFactor {
+impl<Id> InterRobotFactor<Id> {
     pub const DEFAULT_SAFETY_DISTANCE_MULTIPLIER: Float = 2.2;
     pub const NEIGHBORS: usize = 2;
     pub const TINY_OFFSET_SCALE: f32 = 1e-6;
@@ -54,9 +102,10 @@ impl InterRobotFactor {
     #[must_use]
     pub fn new(
         robot_radius: StrictlyPositiveFinite<Float>,
-        external_variable: ExternalVariableId,
+        external_variable: ExternalVariableId<Id>,
         safety_distance_multiplier: Option<StrictlyPositiveFinite<Float>>,
         robot_number: NonZeroUsize,
+        priorities: Priorities,
     ) -> Self {
         let robot_radius = robot_radius.get();
         let safety_distance_multiplier = safety_distance_multiplier
@@ -73,9 +122,16 @@ impl InterRobotFactor {
             skip: false,
             external_variable,
             tiny_offset: Float::from(Self::TINY_OFFSET_SCALE) * robot_number.get() as f64,
+            priorities,
         }
     }
 
+    /// Get the priorities of the two robots
+    #[inline(always)]
+    pub const fn priorities(&self) -> Priorities {
+        self.priorities
+    }
+
     /// Get the safety distance
     #[inline(always)]
     pub const fn safety_distance(&self) -> Float {
@@ -90,12 +146,14 @@ impl InterRobotFactor {
 
     fn diff_between_estimated_positions(
         &self,
+        state: &FactorState,
         linearisation_point: &Vector<Float>,
     ) -> Vector<Float> {
-        let offset = DOFS / 2;
+        let dofs = state.layout.dofs();
+        let offset = state.layout.position_dims();
         let mut diff_between_estimated_positions = linearisation_point
             .slice(s![..offset])
-            .sub(&linearisation_point.slice(s![DOFS..DOFS + offset]));
+            .sub(&linearisation_point.slice(s![dofs..dofs + offset]));
         for i in 0..offset {
             // Add a tiny random offset to avoid div/0 errors
             // x_diff[i] += 1e-6 *
@@ -106,7 +164,7 @@ impl InterRobotFactor {
     }
 }
 
-impl Factor for InterRobotFactor {
+impl<Id> Factor for InterRobotFactor<Id> {
     #[inline(always)]
     fn name(&self) -> &'static str {
         "InterRobotFactor"
@@ -124,8 +182,10 @@ impl Factor for InterRobotFactor {
         lineraisation_point: &Vector<Float>,
     ) -> Cow<'_, Matrix<Float>> {
         // PERF: reuse allocation by
-        let mut jacobian = Matrix::<Float>::zeros((state.initial_measurement.len(), DOFS * 2));
-        let x_diff = self.diff_between_estimated_positions(lineraisation_point);
+        let dofs = state.layout.dofs();
+        let offset = state.layout.position_dims();
+        let mut jacobian = Matrix::<Float>::zeros((state.initial_measurement.len(), dofs * 2));
+        let x_diff = self.diff_between_estimated_positions(state, lineraisation_point);
 
         // let x_diff = {
         //     let offset = DOFS / 2;
@@ -149,12 +209,12 @@ impl Factor for InterRobotFactor {
         if radius <= self.safety_distance {
             // J(0, seqN(0, n_dofs_ / 2)) = -1.f / safety_distance_ / r * X_diff;
             jacobian
-                .slice_mut(s![0, ..DOFS / 2])
+                .slice_mut(s![0, ..offset])
                 .assign(&(-1.0 / self.safety_distance / radius * &x_diff));
 
             // J(0, seqN(n_dofs_, n_dofs_ / 2)) = 1.f / safety_distance_ / r * X_diff;
             jacobian
-                .slice_mut(s![0, DOFS..DOFS + (DOFS / 2)])
+                .slice_mut(s![0, dofs..dofs + offset])
                 .assign(&(1.0 / self.safety_distance / radius * &x_diff));
         }
         Cow::Owned(jacobian)
@@ -164,7 +224,7 @@ impl Factor for InterRobotFactor {
     // -> Vector<Float> {
     fn measure(&self, state: &FactorState, lineraisation_point: &Vector<Float>) -> Measurement {
         let mut measurement = Vector::<Float>::zeros(state.initial_measurement.len());
-        let x_diff = self.diff_between_estimated_positions(lineraisation_point);
+        let x_diff = self.diff_between_estimated_positions(state, lineraisation_point);
         // let x_diff = {
         //     let offset = DOFS / 2;
         //     let mut diff_between_estimated_positions = lineraisation_point
@@ -211,13 +271,14 @@ impl Factor for InterRobotFactor {
     /// Returns true if the distance between the two variables associated with
     /// this interrobot factor is greater than the safety distance
     fn skip(&self, state: &FactorState) -> bool {
-        let offset = DOFS / 2;
+        let dofs = state.layout.dofs();
+        let offset = state.layout.position_dims();
         // [..offset] is the position of the first variable
         // [dofs..dofs + offset] is the position of the other variable
         let difference_between_estimated_positions = state
             .linearisation_point
             .slice(s![..offset])
-            .sub(&state.linearisation_point.slice(s![DOFS..DOFS + offset]));
+            .sub(&state.linearisation_point.slice(s![dofs..dofs + offset]));
         let squared_distance = difference_between_estimated_positions
             .mapv(|x| x.powi(2))
             .sum();
@@ -236,9 +297,40 @@ impl Factor for InterRobotFactor {
     }
 }
 
-impl std::fmt::Display for InterRobotFactor {
+impl<Id> std::fmt::Display for InterRobotFactor<Id> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "safety_distance: {}", self.safety_distance)
+        writeln!(f, "safety_distance: {}", self.safety_distance)?;
+        writeln!(
+            f,
+            "priorities: own: {}, external: {}",
+            self.priorities.own, self.priorities.external
+        )
         // TODO: write more
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    fn priority(priority: Float) -> StrictlyPositiveFinite<Float> {
+        StrictlyPositiveFinite::<Float>::new(priority).expect("priority > 0.0")
+    }
+
+    #[test]
+    fn equal_priorities_share_equally() {
+        let priorities = Priorities::default();
+        assert_relative_eq!(priorities.share(true), 1.0);
+        assert_relative_eq!(priorities.share(false), 1.0);
+    }
+
+    #[test]
+    fn higher_priority_takes_a_smaller_share() {
+        let priorities = Priorities::new(priority(3.0), priority(1.0));
+        assert_relative_eq!(priorities.share(true), 0.5);
+        assert_relative_eq!(priorities.share(false), 1.5);
+        assert_relative_eq!(priorities.share(true) + priorities.share(false), 2.0);
+    }
+}
@@ -1,9 +1,18 @@
-use gbp_linalg::prelude::*;
+//! Marginalisation of the factor potential, into the message sent from a factor
+//! to one of its variables.
+//!
+//! [`marginalise_factor_distance`] works for any number of variables, while
+//! [`marginalise_factor_pair`] is specialised for factors between two variables
+//! and computes the message on the stack, without any heap allocations.
+
+use gbp_linalg::{
+    fixed::{self, SMatrix, SVector},
+    prelude::*,
+};
 use ndarray::prelude::*;
-use ndarray_inverse::Inverse;
 
-use crate::factorgraph::{
-    message::{InformationVec, Mean, PrecisionMatrix},
+use crate::{
+    message::{FixedMessage, InformationVec, Mean, PrecisionMatrix},
     prelude::Message,
     DOFS,
 };
@@ -22,28 +31,29 @@ type Bb<'a, T> = MatrixView<'a, T>;
 
 fn extract_submatrices_from_precision_matrix<T: GbpFloat>(
     precision_matrix: &Matrix<T>,
+    dofs: usize,
     marg_idx: usize,
 ) -> (Aa<T>, Ab<T>, Ba<T>, Bb<T>) {
     debug_assert!(precision_matrix.is_square());
-    debug_assert_eq!(precision_matrix.nrows() % DOFS, 0);
-    debug_assert_eq!(precision_matrix.ncols() % DOFS, 0);
+    debug_assert_eq!(precision_matrix.nrows() % dofs, 0);
+    debug_assert_eq!(precision_matrix.ncols() % dofs, 0);
 
-    let aa = precision_matrix.slice(s![seq_n(marg_idx, DOFS), seq_n(marg_idx, DOFS)]);
+    let aa = precision_matrix.slice(s![seq_n(marg_idx, dofs), seq_n(marg_idx, dofs)]);
 
     let ab = if marg_idx == 0 {
-        precision_matrix.slice(s![seq_n(marg_idx, DOFS), marg_idx + DOFS..])
+        precision_matrix.slice(s![seq_n(marg_idx, dofs), marg_idx + dofs..])
     } else {
-        precision_matrix.slice(s![seq_n(marg_idx, DOFS), ..marg_idx])
+        precision_matrix.slice(s![seq_n(marg_idx, dofs), ..marg_idx])
     };
 
     let ba = if marg_idx == 0 {
-        precision_matrix.slice(s![marg_idx + DOFS.., seq_n(marg_idx, DOFS)])
+        precision_matrix.slice(s![marg_idx + dofs.., seq_n(marg_idx, dofs)])
     } else {
-        precision_matrix.slice(s![..marg_idx, seq_n(marg_idx, DOFS)])
+        precision_matrix.slice(s![..marg_idx, seq_n(marg_idx, dofs)])
     };
 
     let bb = if marg_idx == 0 {
-        precision_matrix.slice(s![marg_idx + DOFS.., marg_idx + DOFS..])
+        precision_matrix.slice(s![marg_idx + dofs.., marg_idx + dofs..])
     } else {
         precision_matrix.slice(s![..marg_idx, ..marg_idx])
     };
@@ -51,16 +61,23 @@ fn extract_submatrices_from_precision_matrix<T: GbpFloat>(
     (aa, ab, ba, bb)
 }
 
+/// Marginalise the factor potential given by `information_vector` and
+/// `precision_matrix` onto the variable at `marg_idx`, i.e. the message sent to
+/// the variable.
+///
+/// Every variable of the factor has `dofs` degrees of freedom. The variable
+/// must be the first or the last variable of the factor.
 #[allow(clippy::similar_names)]
 pub fn marginalise_factor_distance(
     information_vector: Vector<Float>,
     precision_matrix: Matrix<Float>,
+    dofs: usize,
     marg_idx: usize,
 ) -> Message {
     debug_assert_eq!(information_vector.len(), precision_matrix.nrows());
     debug_assert_eq!(precision_matrix.nrows(), precision_matrix.ncols());
 
-    let factor_only_connected_to_one_variable = information_vector.len() == DOFS;
+    let factor_only_connected_to_one_variable = information_vector.len() == dofs;
     if factor_only_connected_to_one_variable {
         let mean = Vector::<Float>::zeros(information_vector.len());
 
@@ -72,40 +89,40 @@ pub fn marginalise_factor_distance(
     }
 
     let lam_bb = if marg_idx == 0 {
-        precision_matrix.slice(s![marg_idx + DOFS.., marg_idx + DOFS..])
+        precision_matrix.slice(s![marg_idx + dofs.., marg_idx + dofs..])
     } else {
         precision_matrix.slice(s![..marg_idx, ..marg_idx])
     };
-    let Some(lam_bb_inv) = lam_bb.to_owned().inv() else {
+    let Some(lam_bb_inv) = lam_bb.to_owned().cholesky_inverse() else {
         return Message::empty();
     };
 
-    let lam_aa = precision_matrix.slice(s![seq_n(marg_idx, DOFS), seq_n(marg_idx, DOFS)]);
+    let lam_aa = precision_matrix.slice(s![seq_n(marg_idx, dofs), seq_n(marg_idx, dofs)]);
 
     let lam_ab = if marg_idx == 0 {
-        precision_matrix.slice(s![seq_n(marg_idx, DOFS), marg_idx + DOFS..])
+        precision_matrix.slice(s![seq_n(marg_idx, dofs), marg_idx + dofs..])
     } else {
-        precision_matrix.slice(s![seq_n(marg_idx, DOFS), ..marg_idx])
+        precision_matrix.slice(s![seq_n(marg_idx, dofs), ..marg_idx])
     };
 
     let lam_ba = if marg_idx == 0 {
-        precision_matrix.slice(s![marg_idx + DOFS.., seq_n(marg_idx, DOFS)])
+        precision_matrix.slice(s![marg_idx + dofs.., seq_n(marg_idx, dofs)])
     } else {
-        precision_matrix.slice(s![..marg_idx, seq_n(marg_idx, DOFS)])
+        precision_matrix.slice(s![..marg_idx, seq_n(marg_idx, dofs)])
     };
 
     // let (lam_aa, lam_ab, lam_ba, lam_bb) =
     // extract_submatrices_from_precision_matrix(&precision_matrix, marg_idx);
 
-    let eta_a = information_vector.slice(s![seq_n(marg_idx, DOFS)]);
-    debug_assert_eq!(eta_a.len(), DOFS);
+    let eta_a = information_vector.slice(s![seq_n(marg_idx, dofs)]);
+    debug_assert_eq!(eta_a.len(), dofs);
 
     let eta_b = if marg_idx == 0 {
-        information_vector.slice(s![DOFS..])
+        information_vector.slice(s![dofs..])
     } else {
         information_vector.slice(s![..marg_idx])
     };
-    debug_assert_eq!(eta_b.len(), information_vector.len() - DOFS);
+    debug_assert_eq!(eta_b.len(), information_vector.len() - dofs);
 
     // let Some(lam_bb_inv) = lam_bb.to_owned().inv() else {
     //     return Message::empty();
@@ -126,6 +143,50 @@ pub fn marginalise_factor_distance(
     }
 }
 
+/// Information vector of a factor between two variables with the planar
+/// [`DOFS`] degrees of freedom
+pub type PairInformationVec = SVector<{ 2 * DOFS }>;
+/// Precision matrix of a factor between two variables with the planar
+/// [`DOFS`] degrees of freedom
+pub type PairPrecisionMatrix = SMatrix<{ 2 * DOFS }, { 2 * DOFS }>;
+
+/// Marginalise the potential of a factor between two variables onto the
+/// variable at `marg_idx`, which is either 0 or [`DOFS`].
+///
+/// Same as [`marginalise_factor_distance`], but computed on the stack.
+/// Returns `None` where [`marginalise_factor_distance`] returns an empty
+/// message.
+#[allow(clippy::similar_names)]
+pub fn marginalise_factor_pair(
+    information_vector: &PairInformationVec,
+    precision_matrix: &PairPrecisionMatrix,
+    marg_idx: usize,
+) -> Option<FixedMessage> {
+    debug_assert!(marg_idx == 0 || marg_idx == DOFS);
+    let other_idx = DOFS - marg_idx;
+
+    let lam_bb = precision_matrix
+        .fixed_view::<DOFS, DOFS>(other_idx, other_idx)
+        .into_owned();
+    let lam_bb_inv = fixed::invert_spd(&lam_bb)?;
+
+    let lam_aa = precision_matrix.fixed_view::<DOFS, DOFS>(marg_idx, marg_idx);
+    let lam_ab = precision_matrix.fixed_view::<DOFS, DOFS>(marg_idx, other_idx);
+    let lam_ba = precision_matrix.fixed_view::<DOFS, DOFS>(other_idx, marg_idx);
+    let eta_a = information_vector.fixed_rows::<DOFS>(marg_idx);
+    let eta_b = information_vector.fixed_rows::<DOFS>(other_idx);
+
+    let lam_ab_lam_bb_inv = lam_ab * lam_bb_inv;
+    let information_vector = eta_a - lam_ab_lam_bb_inv * eta_b;
+    let precision_matrix = lam_aa - lam_ab_lam_bb_inv * lam_ba;
+
+    (!precision_matrix.iter().any(|elem| elem.is_infinite())).then(|| FixedMessage {
+        information_vector,
+        precision_matrix,
+        mean: SVector::zeros(),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use ndarray::concatenate;
@@ -186,7 +247,8 @@ mod tests {
 
         assert!(precision_matrix.is_square());
 
-        let (aa, ab, ba, bb) = extract_submatrices_from_precision_matrix(&precision_matrix, 0);
+        let (aa, ab, ba, bb) =
+            extract_submatrices_from_precision_matrix(&precision_matrix, DOFS, 0);
 
         assert_eq!(aa, upper_left);
         assert_eq!(ab, upper_right);
@@ -201,7 +263,8 @@ mod tests {
 
         assert!(precision_matrix.is_square());
 
-        let (aa, ab, ba, bb) = extract_submatrices_from_precision_matrix(&precision_matrix, 4);
+        let (aa, ab, ba, bb) =
+            extract_submatrices_from_precision_matrix(&precision_matrix, DOFS, 4);
 
         assert_eq!(aa, lower_right);
         assert_eq!(ab, lower_left);
@@ -223,6 +286,7 @@ mod tests {
         let mut marginalised_msg = marginalise_factor_distance(
             information_vector.clone(),
             precision_matrix.clone(),
+            DOFS,
             marginalisation_idx,
         );
 
@@ -232,6 +296,60 @@ mod tests {
         assert_eq!(payload.precision_matrix, precision_matrix);
     }
 
+    #[test]
+    fn pair_matches_any_number_of_variables() {
+        #![allow(clippy::unwrap_used, clippy::cast_precision_loss)]
+        // A symmetric positive definite potential of a factor between two variables
+        let jacobian = Matrix::<Float>::from_shape_fn((DOFS, 2 * DOFS), |(r, c)| {
+            if c == r {
+                1.0
+            } else if c == r + DOFS {
+                -1.0
+            } else {
+                0.1 * (r + c) as Float
+            }
+        });
+        let precision_matrix = jacobian.t().dot(&jacobian) + Matrix::<Float>::eye(2 * DOFS) * 0.5;
+        let information_vector = Vector::<Float>::from_shape_fn(2 * DOFS, |i| i as Float - 3.0);
+
+        let fixed_information_vector =
+            fixed::fixed_vector::<{ 2 * DOFS }>(information_vector.view()).unwrap();
+        let fixed_precision_matrix =
+            fixed::fixed_matrix::<{ 2 * DOFS }, { 2 * DOFS }>(precision_matrix.view()).unwrap();
+
+        for marg_idx in [0, DOFS] {
+            let expected = marginalise_factor_distance(
+                information_vector.clone(),
+                precision_matrix.clone(),
+                DOFS,
+                marg_idx,
+            )
+            .take()
+            .unwrap();
+            let actual = marginalise_factor_pair(
+                &fixed_information_vector,
+                &fixed_precision_matrix,
+                marg_idx,
+            )
+            .unwrap();
+
+            for (a, e) in actual
+                .information_vector
+                .iter()
+                .zip(&expected.information_vector)
+            {
+                approx::assert_relative_eq!(a, e, epsilon = 1e-9);
+            }
+            for (a, e) in actual
+                .precision_matrix
+                .iter()
+                .zip(&expected.precision_matrix)
+            {
+                approx::assert_relative_eq!(a, e, epsilon = 1e-9);
+            }
+        }
+    }
+
     // #[test]
     // fn size5x5_marg_idx1_ndofs4() {
     //     let information_vector: Vector<f32> = array![1., 2., 3., 4., 5.];
@@ -1,36 +1,47 @@
-use std::{borrow::Cow, num::NonZeroUsize, ops::AddAssign};
+use std::{borrow::Cow, collections::BTreeMap, num::NonZeroUsize, ops::AddAssign};
 
-use bevy::math::Vec2;
-use gbp_linalg::{prelude::*, pretty_format_matrix, pretty_format_vector};
+use gbp_linalg::{fixed, prelude::*, pretty_format_matrix, pretty_format_vector};
+use glam::Vec2;
 use ndarray::{array, s};
 use typed_floats::StrictlyPositiveFinite;
 
 use self::{
-    dynamic::DynamicFactor, interrobot::InterRobotFactor, obstacle::ObstacleFactor,
-    tracking::TrackingFactor,
+    acceleration::AccelerationFactor, dynamic::DynamicFactor,
+    dynamic_obstacle::DynamicObstacleFactor, interrobot::InterRobotFactor,
+    obstacle::ObstacleFactor, tracking::TrackingFactor, velocity::VelocityFactor,
 };
 use super::{
-    factorgraph::{FactorGraphId, NodeIndex},
-    id::VariableId,
-    message::MessagesToVariables,
-    node::FactorGraphNode,
+    factorgraph::NodeIndex,
+    id::{GraphId, VariableId},
+    message::{FixedMessage, MessagesToVariables},
+    node::{FactorGraphNode, RemoveConnectionToError},
     prelude::Message,
+    state::StateLayout,
     MessageCount, MessagesReceived, MessagesSent, DOFS,
 };
-use crate::{factorgraph::node::RemoveConnectionToError, simulation_loader::SdfImage};
 
-pub(in crate::factorgraph) mod dynamic;
-pub(in crate::factorgraph) mod interrobot;
-mod marginalise_factor_distance;
-pub(crate) mod obstacle;
-pub(in crate::factorgraph) mod pose;
-pub(in crate::factorgraph) mod tracking;
-mod velocity;
-// pub(in crate::factorgraph) mod velocity;
-
-use marginalise_factor_distance::marginalise_factor_distance;
+pub mod acceleration;
+pub mod collider_sdf;
+pub mod dynamic;
+pub mod dynamic_obstacle;
+pub mod interrobot;
+pub mod marginalise_factor_distance;
+pub mod obstacle;
+pub mod pose;
+mod sdf_sampling;
+pub mod tracking;
+pub mod user;
+pub mod velocity;
+
+use marginalise_factor_distance::{
+    marginalise_factor_distance, marginalise_factor_pair, PairInformationVec, PairPrecisionMatrix,
+};
 
-pub use crate::factorgraph::factor::interrobot::ExternalVariableId;
+pub use self::{
+    interrobot::{ExternalVariableId, Priorities},
+    obstacle::{DistanceField, DistanceImage, SdfImage},
+    user::{Attachment, UserFactor},
+};
 
 /// The value and position of a measurement
 pub struct Measurement {
@@ -130,36 +141,39 @@ pub trait Factor: std::fmt::Display {
 
 /// Factor node in the factorgraph
 #[derive(Debug)]
-pub struct FactorNode {
-    factorgraph_id: FactorGraphId,
+pub struct FactorNode<Id> {
+    factorgraph_id: Id,
     /// Unique identifier that associates the variable with the factorgraph it
     /// is part of.
     pub node_index: Option<NodeIndex>,
     /// State common between all factor kinds
-    pub state:      FactorState,
+    pub state: FactorState,
     /// Variant storing the specialized behavior of each Factor kind.
-    pub kind:       FactorKind,
+    pub kind: FactorKind<Id>,
     /// ailbox for incoming message storage
-    pub inbox:      MessagesToVariables,
+    pub inbox: MessagesToVariables<Id>,
+    /// The messages sent to each variable in the previous update.
+    /// Only stored when the messages are damped
+    outbox: MessagesToVariables<Id>,
+    /// Same as `outbox`, for factors between two variables with [`DOFS`]
+    /// degrees of freedom, whose messages are computed on the stack
+    pair_outbox: BTreeMap<VariableId<Id>, Option<FixedMessage>>,
 
     message_count: MessageCount,
     /// Whether the factor is enabled
     pub enabled:   bool,
 }
 
-impl FactorNode {
-    fn new(
-        factorgraph_id: FactorGraphId,
-        state: FactorState,
-        kind: FactorKind,
-        enabled: bool,
-    ) -> Self {
+impl<Id: GraphId> FactorNode<Id> {
+    fn new(factorgraph_id: Id, state: FactorState, kind: FactorKind<Id>, enabled: bool) -> Self {
         Self {
             factorgraph_id,
             node_index: None,
             state,
             kind,
             inbox: MessagesToVariables::new(),
+            outbox: MessagesToVariables::new(),
+            pair_outbox: BTreeMap::new(),
             message_count: MessageCount::default(),
             enabled,
         }
@@ -167,7 +181,7 @@ impl FactorNode {
 
     /// Returns the factorgraph id that the factor belongs to
     #[inline]
-    pub fn factorgraph_id(&self) -> FactorGraphId {
+    pub fn factorgraph_id(&self) -> Id {
         self.factorgraph_id
     }
 
@@ -195,13 +209,14 @@ impl FactorNode {
 
     /// Create a new dynamic factor
     pub fn new_dynamic_factor(
-        factorgraph_id: FactorGraphId,
+        factorgraph_id: Id,
+        layout: StateLayout,
         strength: Float,
         measurement: Vector<Float>,
         delta_t: Float,
         enabled: bool,
     ) -> Self {
-        let mut state = FactorState::new(measurement, strength, DynamicFactor::NEIGHBORS);
+        let mut state = FactorState::new(measurement, strength, DynamicFactor::NEIGHBORS, layout);
         let dynamic_factor = DynamicFactor::new(&mut state, delta_t);
         let kind = FactorKind::Dynamic(dynamic_factor);
         Self::new(factorgraph_id, state, kind, enabled)
@@ -209,14 +224,16 @@ impl FactorNode {
 
     /// Create a new interrobot factor
     pub fn new_interrobot_factor(
-        factorgraph_id: FactorGraphId,
+        factorgraph_id: Id,
+        layout: StateLayout,
         strength: Float,
         measurement: Vector<Float>,
         // safety_radius: StrictlyPositiveFinite<Float>,
         robot_radius: StrictlyPositiveFinite<Float>,
         safety_distance_multiplier: StrictlyPositiveFinite<Float>,
-        external_variable: ExternalVariableId,
+        external_variable: ExternalVariableId<Id>,
         robot_number: NonZeroUsize,
+        priorities: Priorities,
         enabled: bool,
     ) -> Self {
         let interrobot_factor = InterRobotFactor::new(
@@ -224,9 +241,15 @@ impl FactorNode {
             external_variable,
             Some(safety_distance_multiplier),
             robot_number,
+            priorities,
         );
         let kind = FactorKind::InterRobot(interrobot_factor);
-        let state = FactorState::new(measurement, strength, InterRobotFactor::NEIGHBORS);
+        let state = FactorState::new(
+            measurement,
+            strength,
+            InterRobotFactor::<Id>::NEIGHBORS,
+            layout,
+        );
 
         Self::new(factorgraph_id, state, kind, enabled)
     }
@@ -237,34 +260,92 @@ impl FactorNode {
 
     /// Create a new obstacle factor
     pub fn new_obstacle_factor(
-        factorgraph_id: FactorGraphId,
+        factorgraph_id: Id,
+        layout: StateLayout,
         strength: Float,
         measurement: Vector<Float>,
         obstacle_sdf: SdfImage,
         world_size: obstacle::WorldSize,
+        sampling: crate::config::SdfSamplingSection,
         enabled: bool,
         // world_size_width: Float,
         // world_size_height: Float,
     ) -> Self {
-        let state = FactorState::new(measurement, strength, ObstacleFactor::NEIGHBORS);
-        let obstacle_factor = ObstacleFactor::new(obstacle_sdf, world_size);
+        let state = FactorState::new(measurement, strength, ObstacleFactor::NEIGHBORS, layout);
+        let obstacle_factor = ObstacleFactor::new(obstacle_sdf, world_size, sampling);
+        let kind = FactorKind::Obstacle(obstacle_factor);
+        Self::new(factorgraph_id, state, kind, enabled)
+    }
+
+    /// Create a new obstacle factor, that samples the exact signed distance
+    /// field of the environment
+    pub fn new_distance_field_obstacle_factor(
+        factorgraph_id: Id,
+        layout: StateLayout,
+        strength: Float,
+        measurement: Vector<Float>,
+        field: std::sync::Arc<DistanceField>,
+        world_size: obstacle::WorldSize,
+        sampling: crate::config::SdfSamplingSection,
+        enabled: bool,
+    ) -> Self {
+        let state = FactorState::new(measurement, strength, ObstacleFactor::NEIGHBORS, layout);
+        let obstacle_factor = ObstacleFactor::with_distance_field(field, world_size, sampling);
+        let kind = FactorKind::Obstacle(obstacle_factor);
+        Self::new(factorgraph_id, state, kind, enabled)
+    }
+
+    /// Create a new obstacle factor, that measures the exact signed distance to
+    /// the colliders of the environment
+    pub fn new_collider_obstacle_factor(
+        factorgraph_id: Id,
+        layout: StateLayout,
+        strength: Float,
+        measurement: Vector<Float>,
+        collider_sdf: std::sync::Arc<collider_sdf::ColliderSdf>,
+        enabled: bool,
+    ) -> Self {
+        let state = FactorState::new(measurement, strength, ObstacleFactor::NEIGHBORS, layout);
+        let obstacle_factor = ObstacleFactor::with_colliders(collider_sdf);
         let kind = FactorKind::Obstacle(obstacle_factor);
         Self::new(factorgraph_id, state, kind, enabled)
     }
 
+    /// Create a new dynamic obstacle factor
+    pub fn new_dynamic_obstacle_factor(
+        factorgraph_id: Id,
+        layout: StateLayout,
+        strength: Float,
+        measurement: Vector<Float>,
+        time_offset: f32,
+        safety_distance: Float,
+        enabled: bool,
+    ) -> Self {
+        let state = FactorState::new(
+            measurement,
+            strength,
+            DynamicObstacleFactor::NEIGHBORS,
+            layout,
+        );
+        let dynamic_obstacle_factor = DynamicObstacleFactor::new(time_offset, safety_distance);
+        let kind = FactorKind::DynamicObstacle(dynamic_obstacle_factor);
+        Self::new(factorgraph_id, state, kind, enabled)
+    }
+
     /// Create a new tracking factor
     pub fn new_tracking_factor(
-        factorgraph_id: FactorGraphId,
+        factorgraph_id: Id,
+        layout: StateLayout,
         strength: Float,
         measurement: Vector<Float>,
         linearisation_point: Vector<Float>,
-        tracking_config: gbp_config::TrackingSection,
+        tracking_config: crate::config::TrackingSection,
         // tracking_smoothing: f64,
         // rrt_path: Vec<Vec2>,
         rrt_path: Option<min_len_vec::TwoOrMore<Vec2>>,
         enabled: bool,
     ) -> Self {
-        let state = FactorState::new(measurement, strength, TrackingFactor::NEIGHBORS)
+        let state = FactorState::new(measurement, strength, TrackingFactor::NEIGHBORS, layout)
             .with_linearisation_point(linearisation_point.clone());
         let tracking_factor = TrackingFactor::new(rrt_path)
             .with_last_measurement(
@@ -276,6 +357,49 @@ impl FactorNode {
         Self::new(factorgraph_id, state, kind, enabled)
     }
 
+    /// Create a new velocity limit factor
+    pub fn new_velocity_factor(
+        factorgraph_id: Id,
+        layout: StateLayout,
+        strength: Float,
+        measurement: Vector<Float>,
+        max_speed: Float,
+        enabled: bool,
+    ) -> Self {
+        let state = FactorState::new(measurement, strength, VelocityFactor::NEIGHBORS, layout);
+        let kind = FactorKind::Velocity(VelocityFactor::new(max_speed));
+        Self::new(factorgraph_id, state, kind, enabled)
+    }
+
+    /// Create a new acceleration limit factor
+    pub fn new_acceleration_factor(
+        factorgraph_id: Id,
+        layout: StateLayout,
+        strength: Float,
+        measurement: Vector<Float>,
+        max_acceleration: Float,
+        delta_t: Float,
+        enabled: bool,
+    ) -> Self {
+        let state = FactorState::new(measurement, strength, AccelerationFactor::NEIGHBORS, layout);
+        let kind = FactorKind::Acceleration(AccelerationFactor::new(max_acceleration, delta_t));
+        Self::new(factorgraph_id, state, kind, enabled)
+    }
+
+    /// Create a new user defined factor, see [`user`]
+    pub fn new_user_factor(
+        factorgraph_id: Id,
+        layout: StateLayout,
+        strength: Float,
+        measurement: Vector<Float>,
+        factor: impl UserFactor,
+        enabled: bool,
+    ) -> Self {
+        let state = FactorState::new(measurement, strength, factor.neighbours(), layout);
+        let kind = FactorKind::User(Box::new(factor));
+        Self::new(factorgraph_id, state, kind, enabled)
+    }
+
     #[inline(always)]
     fn jacobian(&self, linearisation_point: &Vector<Float>) -> Cow<'_, Matrix<Float>> {
         self.kind.jacobian(&self.state, linearisation_point)
@@ -304,7 +428,7 @@ impl FactorNode {
     }
 
     /// Add a message to this factors inbox
-    pub fn receive_message_from(&mut self, from: VariableId, message: Message) {
+    pub fn receive_message_from(&mut self, from: VariableId<Id>, message: Message) {
         if !self.enabled {
             return;
         }
@@ -329,15 +453,18 @@ impl FactorNode {
         &self.state.initial_measurement - &self.state.cached_measurement
     }
 
-    /// Update the factor using the gbp message passing algorithm
+    /// Update the factor using the gbp message passing algorithm.
+    /// With `damping` > 0.0 each message is damped towards the message sent
+    /// to the same variable in the previous update, see [`Message::damped`]
     #[must_use]
-    pub fn update(&mut self) -> MessagesToVariables {
+    pub fn update(&mut self, damping: Float) -> MessagesToVariables<Id> {
+        let dofs = self.state.layout.dofs();
         // update the linearisation point
         for (i, (_, message)) in self.inbox.iter().enumerate() {
             let mut slice = self
                 .state
                 .linearisation_point
-                .slice_mut(s![i * DOFS..(i + 1) * DOFS]);
+                .slice_mut(s![i * dofs..(i + 1) * dofs]);
 
             if let Some(mean) = message.mean() {
                 slice.assign(mean);
@@ -350,8 +477,11 @@ impl FactorNode {
 
         // If the factor is to be skipped, send empty messages to all variables
         if self.skip() {
+            // Do not damp towards messages from before the factor was skipped
+            self.outbox.clear();
+            self.pair_outbox.clear();
             let mut messages_sent = MessagesSent::new();
-            let messages: MessagesToVariables = self
+            let messages: MessagesToVariables<Id> = self
                 .inbox
                 .keys()
                 .map(|variable_id| {
@@ -409,35 +539,69 @@ impl FactorNode {
 
         let mut messages_sent = MessagesSent::new();
 
-        for variable_id in self.inbox.keys() {
-            let mut information_vec = potential_information_vec.clone();
-            let mut precision_matrix = potential_precision_matrix.clone();
-
-            for (j, (other_variable_id, other_message)) in self.inbox.iter().enumerate() {
-                if other_variable_id == variable_id {
-                    // Do not aggregate data from the variable we're sending to
-                    continue;
-                }
-
-                if other_message.is_empty() {
-                    continue;
-                }
-
-                if let Some(message_information) = other_message.information_vector() {
-                    information_vec
-                        .slice_mut(s![j * DOFS..(j + 1) * DOFS])
-                        .add_assign(message_information);
+        // Factors between two variables of the planar layout are updated on the
+        // stack, with the potential and the messages from the variables converted
+        // once, and the messages to the variables converted when they are sent
+        let pair = (dofs == DOFS)
+            .then(|| {
+                let potential = fixed::fixed_vector(potential_information_vec.view())
+                    .zip(fixed::fixed_matrix(potential_precision_matrix.view()))?;
+                let mut incoming = self.inbox.values().map(FixedMessage::of);
+                Some((potential, [
+                    incoming.next().flatten(),
+                    incoming.next().flatten(),
+                ]))
+            })
+            .flatten();
+
+        for (j, variable_id) in self.inbox.keys().enumerate() {
+            // Robots with a higher priority are less certain that they have to avoid
+            let share = if let FactorKind::InterRobot(interrobot) = &self.kind {
+                let own = variable_id.factorgraph_id == self.factorgraph_id;
+                Some(interrobot.priorities().share(own))
+            } else {
+                None
+            };
+
+            let message = match pair {
+                Some(((ref information_vec, ref precision_matrix), ref incoming)) => {
+                    let mut message = Self::marginalise_pair_onto(
+                        information_vec,
+                        precision_matrix,
+                        incoming[1 - j].as_ref(),
+                        marginalisation_idx,
+                    );
+                    if let Some(share) = share {
+                        message = message.map(|message| message.scaled(share));
+                    }
+                    if damping > 0.0 {
+                        let sent = self.pair_outbox.entry(*variable_id).or_default();
+                        if let (Some(current), Some(previous)) = (message, sent.as_ref()) {
+                            message = Some(current.damped(previous, damping));
+                        }
+                        *sent = message;
+                    }
+                    message.map_or_else(Message::empty, Message::from)
                 }
-
-                if let Some(message_precision) = other_message.precision_matrix() {
-                    precision_matrix
-                        .slice_mut(s![j * DOFS..(j + 1) * DOFS, j * DOFS..(j + 1) * DOFS])
-                        .add_assign(message_precision);
+                None => {
+                    let mut message = self.marginalise_onto(
+                        variable_id,
+                        &potential_information_vec,
+                        &potential_precision_matrix,
+                        marginalisation_idx,
+                    );
+                    if let Some(share) = share {
+                        message = message.scaled(share);
+                    }
+                    if damping > 0.0 {
+                        if let Some(previous) = self.outbox.get(variable_id) {
+                            message = message.damped(previous, damping);
+                        }
+                        self.outbox.insert(*variable_id, message.clone());
+                    }
+                    message
                 }
-            }
-
-            let message =
-                marginalise_factor_distance(information_vec, precision_matrix, marginalisation_idx);
+            };
             messages.insert(*variable_id, message);
 
             if variable_id.factorgraph_id == self.factorgraph_id {
@@ -446,13 +610,77 @@ impl FactorNode {
                 messages_sent.external += 1;
             }
 
-            marginalisation_idx += DOFS;
+            marginalisation_idx += dofs;
         }
 
         self.message_count.sent += messages_sent;
         messages
     }
 
+    /// Marginalise the factor potential, combined with the messages from every
+    /// other variable, onto the variable with `variable_id`
+    fn marginalise_onto(
+        &self,
+        variable_id: &VariableId<Id>,
+        potential_information_vec: &Vector<Float>,
+        potential_precision_matrix: &Matrix<Float>,
+        marginalisation_idx: usize,
+    ) -> Message {
+        let dofs = self.state.layout.dofs();
+        let mut information_vec = potential_information_vec.clone();
+        let mut precision_matrix = potential_precision_matrix.clone();
+
+        for (j, (other_variable_id, other_message)) in self.inbox.iter().enumerate() {
+            if other_variable_id == variable_id {
+                // Do not aggregate data from the variable we're sending to
+                continue;
+            }
+
+            if other_message.is_empty() {
+                continue;
+            }
+
+            if let Some(message_information) = other_message.information_vector() {
+                information_vec
+                    .slice_mut(s![j * dofs..(j + 1) * dofs])
+                    .add_assign(message_information);
+            }
+
+            if let Some(message_precision) = other_message.precision_matrix() {
+                precision_matrix
+                    .slice_mut(s![j * dofs..(j + 1) * dofs, j * dofs..(j + 1) * dofs])
+                    .add_assign(message_precision);
+            }
+        }
+
+        marginalise_factor_distance(information_vec, precision_matrix, dofs, marginalisation_idx)
+    }
+
+    /// Same as [`Self::marginalise_onto`], for a factor between two variables
+    /// with [`DOFS`] degrees of freedom, where `other_message` is the message
+    /// from the variable that is not marginalised onto
+    fn marginalise_pair_onto(
+        potential_information_vec: &PairInformationVec,
+        potential_precision_matrix: &PairPrecisionMatrix,
+        other_message: Option<&FixedMessage>,
+        marginalisation_idx: usize,
+    ) -> Option<FixedMessage> {
+        let mut information_vec = *potential_information_vec;
+        let mut precision_matrix = *potential_precision_matrix;
+
+        if let Some(other_message) = other_message {
+            let other_idx = DOFS - marginalisation_idx;
+            information_vec
+                .fixed_rows_mut::<DOFS>(other_idx)
+                .add_assign(other_message.information_vector);
+            precision_matrix
+                .fixed_view_mut::<DOFS, DOFS>(other_idx, other_idx)
+                .add_assign(other_message.precision_matrix);
+        }
+
+        marginalise_factor_pair(&information_vec, &precision_matrix, marginalisation_idx)
+    }
+
     /// Check if the factor is an [`InterRobotFactor`]
     #[inline(always)]
     pub fn is_inter_robot(&self) -> bool {
@@ -477,6 +705,18 @@ impl FactorNode {
         self.kind.is_tracking()
     }
 
+    /// Check if the factor is a [`DynamicObstacleFactor`]
+    #[inline(always)]
+    pub fn is_dynamic_obstacle(&self) -> bool {
+        self.kind.is_dynamic_obstacle()
+    }
+
+    /// Check if the factor is a [`UserFactor`]
+    #[inline(always)]
+    pub fn is_user(&self) -> bool {
+        self.kind.is_user()
+    }
+
     pub fn empty_inbox(&mut self) {
         // empty_inbox
         self.inbox.values_mut().for_each(|m| *m = Message::empty());
@@ -487,35 +727,51 @@ impl FactorNode {
 /// Used instead of dynamic dispatch
 #[allow(missing_docs)]
 #[derive(Debug, derive_more::IsVariant, strum_macros::EnumTryAs)]
-pub enum FactorKind {
+pub enum FactorKind<Id> {
     /// `InterRobotFactor`
-    InterRobot(InterRobotFactor),
+    InterRobot(InterRobotFactor<Id>),
     /// `DynamicFactor`
     Dynamic(DynamicFactor),
     /// `ObstacleFactor`
     Obstacle(ObstacleFactor),
     /// `TrackingFactor`
     Tracking(TrackingFactor),
+    /// `DynamicObstacleFactor`
+    DynamicObstacle(DynamicObstacleFactor),
+    /// `VelocityFactor`
+    Velocity(VelocityFactor),
+    /// `AccelerationFactor`
+    Acceleration(AccelerationFactor),
+    /// A factor defined outside of this crate, see [`user`]
+    User(Box<dyn UserFactor>),
 }
 
-impl std::fmt::Display for FactorKind {
+impl<Id> std::fmt::Display for FactorKind<Id> {
     fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::InterRobot(f) => f.fmt(formatter),
             Self::Dynamic(f) => f.fmt(formatter),
             Self::Obstacle(f) => f.fmt(formatter),
             Self::Tracking(f) => f.fmt(formatter),
+            Self::DynamicObstacle(f) => f.fmt(formatter),
+            Self::Velocity(f) => f.fmt(formatter),
+            Self::Acceleration(f) => f.fmt(formatter),
+            Self::User(f) => f.fmt(formatter),
         }
     }
 }
 
-impl Factor for FactorKind {
+impl<Id> Factor for FactorKind<Id> {
     fn name(&self) -> &'static str {
         match self {
             Self::InterRobot(f) => f.name(),
             Self::Dynamic(f) => f.name(),
             Self::Obstacle(f) => f.name(),
             Self::Tracking(f) => f.name(),
+            Self::DynamicObstacle(f) => f.name(),
+            Self::Velocity(f) => f.name(),
+            Self::Acceleration(f) => f.name(),
+            Self::User(f) => f.name(),
         }
     }
 
@@ -525,6 +781,10 @@ impl Factor for FactorKind {
             Self::Dynamic(f) => f.color(),
             Self::Obstacle(f) => f.color(),
             Self::Tracking(f) => f.color(),
+            Self::DynamicObstacle(f) => f.color(),
+            Self::Velocity(f) => f.color(),
+            Self::Acceleration(f) => f.color(),
+            Self::User(f) => f.color(),
         }
     }
 
@@ -538,6 +798,10 @@ impl Factor for FactorKind {
             Self::InterRobot(f) => f.jacobian(state, linearisation_point),
             Self::Obstacle(f) => f.jacobian(state, linearisation_point),
             Self::Tracking(f) => f.jacobian(state, linearisation_point),
+            Self::DynamicObstacle(f) => f.jacobian(state, linearisation_point),
+            Self::Velocity(f) => f.jacobian(state, linearisation_point),
+            Self::Acceleration(f) => f.jacobian(state, linearisation_point),
+            Self::User(f) => f.jacobian(state, linearisation_point),
         }
     }
 
@@ -549,6 +813,10 @@ impl Factor for FactorKind {
             Self::InterRobot(f) => f.measure(state, linearisation_point),
             Self::Obstacle(f) => f.measure(state, linearisation_point),
             Self::Tracking(f) => f.measure(state, linearisation_point),
+            Self::DynamicObstacle(f) => f.measure(state, linearisation_point),
+            Self::Velocity(f) => f.measure(state, linearisation_point),
+            Self::Acceleration(f) => f.measure(state, linearisation_point),
+            Self::User(f) => f.measure(state, linearisation_point),
         }
     }
 
@@ -558,6 +826,10 @@ impl Factor for FactorKind {
             Self::InterRobot(f) => f.skip(state),
             Self::Obstacle(f) => f.skip(state),
             Self::Tracking(f) => f.skip(state),
+            Self::DynamicObstacle(f) => f.skip(state),
+            Self::Velocity(f) => f.skip(state),
+            Self::Acceleration(f) => f.skip(state),
+            Self::User(f) => f.skip(state),
         }
     }
 
@@ -567,6 +839,10 @@ impl Factor for FactorKind {
             Self::InterRobot(f) => f.jacobian_delta(),
             Self::Obstacle(f) => f.jacobian_delta(),
             Self::Tracking(f) => f.jacobian_delta(),
+            Self::DynamicObstacle(f) => f.jacobian_delta(),
+            Self::Velocity(f) => f.jacobian_delta(),
+            Self::Acceleration(f) => f.jacobian_delta(),
+            Self::User(f) => f.jacobian_delta(),
         }
     }
 
@@ -577,15 +853,23 @@ impl Factor for FactorKind {
             Self::InterRobot(f) => f.linear(),
             Self::Obstacle(f) => f.linear(),
             Self::Tracking(f) => f.linear(),
+            Self::DynamicObstacle(f) => f.linear(),
+            Self::Velocity(f) => f.linear(),
+            Self::Acceleration(f) => f.linear(),
+            Self::User(f) => f.linear(),
         }
     }
 
     fn neighbours(&self) -> usize {
         match self {
-            FactorKind::InterRobot(f) => f.neighbours(),
-            FactorKind::Dynamic(f) => f.neighbours(),
-            FactorKind::Obstacle(f) => f.neighbours(),
-            FactorKind::Tracking(f) => f.neighbours(),
+            Self::InterRobot(f) => f.neighbours(),
+            Self::Dynamic(f) => f.neighbours(),
+            Self::Obstacle(f) => f.neighbours(),
+            Self::Tracking(f) => f.neighbours(),
+            Self::DynamicObstacle(f) => f.neighbours(),
+            Self::Velocity(f) => f.neighbours(),
+            Self::Acceleration(f) => f.neighbours(),
+            Self::User(f) => f.neighbours(),
         }
     }
 }
@@ -620,11 +904,18 @@ pub struct FactorState {
     pub cached_measurement: Vector<Float>,
     /// Set to true after the first call to `self.update()`
     initialized: bool,
+    /// Layout of the state of the variables the factor is connected to
+    pub layout: StateLayout,
 }
 
 impl FactorState {
     /// Create a new [`FactorState`]
-    fn new(initial_measurement: Vector<Float>, strength: Float, neighbor_amount: usize) -> Self {
+    fn new(
+        initial_measurement: Vector<Float>,
+        strength: Float,
+        neighbor_amount: usize,
+        layout: StateLayout,
+    ) -> Self {
         // Initialise precision of the measurement function
         // this->meas_model_lambda_ = Eigen::MatrixXd::Identity(z_.rows(), z_.rows()) /
         // pow(sigma,2.);
@@ -634,11 +925,12 @@ impl FactorState {
         Self {
             initial_measurement,
             measurement_precision,
-            linearisation_point: Vector::<Float>::zeros(DOFS * neighbor_amount),
+            linearisation_point: Vector::<Float>::zeros(layout.dofs() * neighbor_amount),
             strength,
             cached_jacobian: array![[]],
             cached_measurement: array![],
             initialized: false,
+            layout,
         }
     }
 
@@ -695,18 +987,20 @@ impl std::fmt::Display for FactorState {
             self.cached_measurement.pretty_format()
         )?;
         writeln!(f, "strength: {:?}", self.strength)?;
+        writeln!(f, "layout: {}", self.layout)?;
         writeln!(f, "initialized: {:?}", self.initialized)
     }
 }
 
-impl FactorGraphNode for FactorNode {
-    fn remove_connection_to(
-        &mut self,
-        factorgraph_id: super::factorgraph::FactorGraphId,
-    ) -> Result<(), RemoveConnectionToError> {
+impl<Id: GraphId> FactorGraphNode<Id> for FactorNode<Id> {
+    fn remove_connection_to(&mut self, factorgraph_id: Id) -> Result<(), RemoveConnectionToError> {
         let connections_before = self.inbox.len();
         self.inbox
             .retain(|variable_id, _| variable_id.factorgraph_id != factorgraph_id);
+        self.outbox
+            .retain(|variable_id, _| variable_id.factorgraph_id != factorgraph_id);
+        self.pair_outbox
+            .retain(|variable_id, _| variable_id.factorgraph_id != factorgraph_id);
         let connections_after = self.inbox.len();
 
         let no_connections_removed = connections_before == connections_after;
@@ -0,0 +1,586 @@
+//! Obstacle factor
+
+use std::{
+    borrow::Cow,
+    cell::Cell,
+    sync::{Arc, Mutex},
+};
+
+use gbp_linalg::prelude::*;
+use glam::Vec2;
+use ndarray::{array, s};
+
+use super::{collider_sdf::ColliderSdf, sdf_sampling, Factor, FactorState, Measurement};
+use crate::config::{SdfInterpolation, SdfSamplingSection};
+
+/// Signed distance field image of the environment. Black pixels are inside
+/// obstacles, white pixels are free space.
+pub type SdfImage = image::ImageBuffer<image::Rgb<u8>, Vec<u8>>;
+
+/// Signed distance to the closest obstacle boundary in metres for every pixel.
+/// Positive in free space, negative inside obstacles.
+pub type DistanceImage = image::ImageBuffer<image::Luma<f32>, Vec<f32>>;
+
+/// Exact signed distance field of the environment, with the distances clamped
+/// to `[-clamp, clamp]` metres
+#[derive(Debug, Clone)]
+pub struct DistanceField {
+    distances: DistanceImage,
+    clamp:     f32,
+}
+
+impl DistanceField {
+    /// Creates a new [`DistanceField`] from `distances` clamped to `clamp`
+    #[must_use]
+    pub const fn new(distances: DistanceImage, clamp: f32) -> Self {
+        Self { distances, clamp }
+    }
+
+    /// The signed distance of every pixel
+    #[must_use]
+    pub const fn distances(&self) -> &DistanceImage {
+        &self.distances
+    }
+
+    /// The distances are clamped to `[-clamp, clamp]`
+    #[must_use]
+    pub const fn clamp(&self) -> f32 {
+        self.clamp
+    }
+}
+
+pub struct ObstacleFactor {
+    /// What the distance to obstacles is measured against
+    source: ObstacleSource,
+    // world_size:       Float,
+    last_measurement: Mutex<Cell<LastMeasurement>>,
+    jacobian_delta: Float,
+}
+
+/// What an [`ObstacleFactor`] measures the distance to obstacles against
+enum ObstacleSource {
+    /// Signed distance field image of the environment
+    Sdf {
+        /// The signed distance field of the environment
+        obstacle_sdf: SdfImage,
+        /// Copy of the `WORLD_SZ` setting from **gbpplanner**, that we store a
+        /// copy of here since `ObstacleFactor` needs this information to
+        /// calculate `.jacobian_delta()` and `.measurement()`
+        world_size:   WorldSize,
+        /// How the image is sampled between and outside of its pixels
+        sampling:     SdfSamplingSection,
+    },
+    /// Exact signed distance field of the environment, sampled like the image
+    DistanceField {
+        /// The signed distance field of the environment
+        field:      Arc<DistanceField>,
+        /// The size of the world the field covers
+        world_size: WorldSize,
+        /// How the field is sampled, and how the measurement falls off with
+        /// the sampled distance
+        sampling:   SdfSamplingSection,
+    },
+    /// Exact signed distance to the colliders of the environment
+    Colliders(Arc<ColliderSdf>),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WorldSize {
+    pub width:  Float,
+    pub height: Float,
+}
+
+impl std::fmt::Display for WorldSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(width: {}, height: {})", self.width, self.height)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LastMeasurement {
+    pub pos:   Vec2,
+    pub value: Float,
+}
+
+impl std::fmt::Display for LastMeasurement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use colored::Colorize;
+        // let v = self.value * 255.0;
+        // let r = v as u8;
+        // let g = 255 - r;
+
+        let green = colorgrad::Color::from_linear_rgba(0.0, 255.0, 0.0, 255.0);
+        let red = colorgrad::Color::from_linear_rgba(255.0, 0.0, 0.0, 255.0);
+        let gradient = colorgrad::CustomGradient::new()
+            .colors(&[green, red])
+            .domain(&[0.0, 1.0])
+            .mode(colorgrad::BlendMode::Hsv)
+            .build()
+            .unwrap();
+
+        let color = gradient.at(self.value);
+        let [r, g, _, _] = color.to_rgba8();
+
+        write!(
+            f,
+            "[pos: {}, value: {}]",
+            self.pos,
+            format!("{:.4}", self.value).truecolor(r, g, 0u8)
+        )
+    }
+}
+
+impl Default for LastMeasurement {
+    fn default() -> Self {
+        Self {
+            pos:   Vec2::ZERO,
+            value: 0.0,
+        }
+    }
+}
+
+#[allow(clippy::missing_fields_in_debug)]
+impl std::fmt::Debug for ObstacleFactor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Use custom impl instead of `derive(Debug)`, to not print the entire `Image`
+        // as a pixel array
+        let mut debug = f.debug_struct("ObstacleFactor");
+        match &self.source {
+            // .field("obstacle_sdf", &self.obstacle_sdf)
+            ObstacleSource::Sdf { world_size, .. }
+            | ObstacleSource::DistanceField { world_size, .. } => {
+                debug.field("world_size", world_size)
+            }
+            ObstacleSource::Colliders(collider_sdf) => debug.field("colliders", collider_sdf),
+        };
+        debug.finish()
+    }
+}
+
+impl ObstacleFactor {
+    /// An obstacle factor has a single edge to another variable
+    pub const NEIGHBORS: usize = 1;
+
+    /// Creates a new [`ObstacleFactor`].
+    #[must_use]
+    pub fn new(
+        obstacle_sdf: SdfImage,
+        world_size: WorldSize,
+        sampling: SdfSamplingSection,
+    ) -> Self {
+        let jacobian_delta = {
+            let width = world_size.width / Float::from(obstacle_sdf.width());
+            let height = world_size.height / Float::from(obstacle_sdf.height());
+            (width + height) / 2.0
+        };
+
+        Self {
+            source: ObstacleSource::Sdf {
+                obstacle_sdf,
+                world_size,
+                sampling,
+            },
+            last_measurement: Default::default(),
+            jacobian_delta,
+        }
+    }
+
+    /// Creates a new [`ObstacleFactor`], that samples the exact signed distance
+    /// field of the environment instead of the blurred image. The measurement
+    /// falls off with the distance as configured by `sampling.falloff`.
+    #[must_use]
+    pub fn with_distance_field(
+        field: Arc<DistanceField>,
+        world_size: WorldSize,
+        sampling: SdfSamplingSection,
+    ) -> Self {
+        let (width, height) = field.distances().dimensions();
+        let jacobian_delta = {
+            let width = world_size.width / Float::from(width);
+            let height = world_size.height / Float::from(height);
+            (width + height) / 2.0
+        };
+
+        Self {
+            source: ObstacleSource::DistanceField {
+                field,
+                world_size,
+                sampling,
+            },
+            last_measurement: Default::default(),
+            jacobian_delta,
+        }
+    }
+
+    /// Creates a new [`ObstacleFactor`], that measures the exact signed
+    /// distance to the colliders of the environment instead of sampling an
+    /// image.
+    #[must_use]
+    pub fn with_colliders(collider_sdf: Arc<ColliderSdf>) -> Self {
+        // Only used for the first order jacobian, which is not needed as the
+        // gradient is known analytically
+        let jacobian_delta = Float::from(collider_sdf.safety_distance()) / 100.0;
+        Self {
+            source: ObstacleSource::Colliders(collider_sdf),
+            last_measurement: Default::default(),
+            jacobian_delta,
+        }
+    }
+
+    /// Sample the signed distance field image at the world position `(x_pos,
+    /// y_pos)`. The gradient of the sample is with respect to the world
+    /// position
+    fn sample_sdf(
+        obstacle_sdf: &impl sdf_sampling::SampledImage,
+        world_size: WorldSize,
+        sampling: SdfSamplingSection,
+        x_pos: Float,
+        y_pos: Float,
+    ) -> sdf_sampling::Sample {
+        // The robots coordinate system is centered in the image, so we have to offset
+        // the pixel index, by half the height in the row index i.e. `y` and
+        // half the width in the column index i.e. `x`
+        let x_offset = world_size.width / 2.0;
+        let y_offset = world_size.height / 2.0;
+
+        let (width, height) = obstacle_sdf.dimensions();
+        let x_scale = Float::from(width) / world_size.width;
+        let y_scale = Float::from(height) / world_size.height;
+
+        let x_pixel = (x_pos + x_offset) * x_scale;
+        // NOTE: the -y_pos is because the y axis is flipped in the image
+        let y_pixel = (-y_pos + y_offset) * y_scale;
+
+        let sample = sdf_sampling::sample(obstacle_sdf, sampling, x_pixel, y_pixel);
+        sdf_sampling::Sample {
+            value:    sample.value,
+            gradient: [sample.gradient[0] * x_scale, -sample.gradient[1] * y_scale],
+        }
+    }
+
+    /// Sample the distance field at the world position `(x_pos, y_pos)`, and
+    /// turn the distance into a measurement with the falloff of `sampling`.
+    /// The gradient is of the measurement, with respect to the world position
+    fn sample_distance_field(
+        field: &DistanceField,
+        world_size: WorldSize,
+        sampling: SdfSamplingSection,
+        x_pos: Float,
+        y_pos: Float,
+    ) -> sdf_sampling::Sample {
+        let distance = Self::sample_sdf(field, world_size, sampling, x_pos, y_pos);
+        let (value, derivative) = sampling
+            .falloff
+            .measurement(distance.value, Float::from(field.clamp()));
+        sdf_sampling::Sample {
+            value,
+            gradient: [
+                derivative * distance.gradient[0],
+                derivative * distance.gradient[1],
+            ],
+        }
+    }
+
+    pub fn last_measurement(&self) -> LastMeasurement {
+        self.last_measurement.lock().unwrap().get()
+    }
+}
+
+impl Factor for ObstacleFactor {
+    #[inline]
+    fn name(&self) -> &'static str {
+        "ObstacleFactor"
+    }
+
+    fn color(&self) -> [u8; 3] {
+        // #ee99a0
+        [238, 153, 160]
+    }
+
+    #[inline]
+    fn jacobian(
+        &self,
+        state: &FactorState,
+        linearisation_point: &Vector<Float>,
+    ) -> Cow<'_, Matrix<Float>> {
+        let (x_pos, y_pos) = (linearisation_point[0], linearisation_point[1]);
+        // The interpolated images and the colliders are differentiable, so the jacobian
+        // is their gradient
+        let sample = match &self.source {
+            ObstacleSource::Sdf { sampling, .. }
+            | ObstacleSource::DistanceField { sampling, .. }
+                if sampling.interpolation == SdfInterpolation::Nearest =>
+            {
+                // Same as PoseFactor
+                // TODO: change to not clone x
+                return Cow::Owned(self.first_order_jacobian(state, linearisation_point.clone()));
+            }
+            ObstacleSource::Sdf {
+                obstacle_sdf,
+                world_size,
+                sampling,
+            } => Self::sample_sdf(obstacle_sdf, *world_size, *sampling, x_pos, y_pos),
+            ObstacleSource::DistanceField {
+                field,
+                world_size,
+                sampling,
+            } => Self::sample_distance_field(field, *world_size, *sampling, x_pos, y_pos),
+            ObstacleSource::Colliders(collider_sdf) => {
+                // h(x) = 1 - d(x) / safety_distance, so the jacobian is the gradient of the
+                // signed distance scaled by -1 / safety_distance
+                let position = Vec2::new(x_pos as f32, y_pos as f32);
+                let safety_distance = Float::from(collider_sdf.safety_distance());
+                collider_sdf.signed_distance(position).map_or(
+                    sdf_sampling::Sample {
+                        value:    0.0,
+                        gradient: [0.0, 0.0],
+                    },
+                    |closest| sdf_sampling::Sample {
+                        value:    1.0 - Float::from(closest.distance) / safety_distance,
+                        gradient: [
+                            -Float::from(closest.gradient.x) / safety_distance,
+                            -Float::from(closest.gradient.y) / safety_distance,
+                        ],
+                    },
+                )
+            }
+        };
+
+        let mut jacobian =
+            Matrix::<Float>::zeros((state.initial_measurement.len(), state.layout.dofs()));
+        jacobian
+            .slice_mut(s![0, ..2])
+            .assign(&array![sample.gradient[0], sample.gradient[1]]);
+        Cow::Owned(jacobian)
+    }
+
+    // fn measure(&self, _state: &FactorState, linearisation_point: &Vector<Float>)
+    // -> Vector<Float> {
+    fn measure(&self, _state: &FactorState, linearisation_point: &Vector<Float>) -> Measurement {
+        let x_pos = linearisation_point[0];
+        let y_pos = linearisation_point[1];
+
+        let value = match &self.source {
+            ObstacleSource::Sdf {
+                obstacle_sdf,
+                world_size,
+                sampling,
+            } => Self::sample_sdf(obstacle_sdf, *world_size, *sampling, x_pos, y_pos).value,
+            ObstacleSource::DistanceField {
+                field,
+                world_size,
+                sampling,
+            } => Self::sample_distance_field(field, *world_size, *sampling, x_pos, y_pos).value,
+            ObstacleSource::Colliders(collider_sdf) => collider_sdf
+                .signed_distance(Vec2::new(x_pos as f32, y_pos as f32))
+                .map_or(0.0, |closest| {
+                    1.0 - Float::from(closest.distance / collider_sdf.safety_distance())
+                }),
+        };
+
+        self.last_measurement.lock().unwrap().set(LastMeasurement {
+            pos: Vec2::new(x_pos as f32, y_pos as f32),
+            value,
+        });
+
+        Measurement::new(array![value])
+    }
+
+    #[inline(always)]
+    fn jacobian_delta(&self) -> Float {
+        self.jacobian_delta
+    }
+
+    #[inline(always)]
+    fn skip(&self, _state: &FactorState) -> bool {
+        false
+    }
+
+    #[inline(always)]
+    fn linear(&self) -> bool {
+        false
+    }
+
+    #[inline(always)]
+    fn neighbours(&self) -> usize {
+        Self::NEIGHBORS
+    }
+}
+
+impl std::fmt::Display for ObstacleFactor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.source {
+            ObstacleSource::Sdf {
+                world_size,
+                sampling,
+                ..
+            } => {
+                writeln!(f, "world_size: {world_size}")?;
+                writeln!(f, "interpolation: {:?}", sampling.interpolation)?;
+            }
+            ObstacleSource::DistanceField {
+                world_size,
+                sampling,
+                field,
+            } => {
+                writeln!(f, "world_size: {world_size}")?;
+                writeln!(f, "interpolation: {:?}", sampling.interpolation)?;
+                writeln!(f, "falloff: {:?}", sampling.falloff)?;
+                writeln!(f, "clamp: {}", field.clamp())?;
+            }
+            ObstacleSource::Colliders(collider_sdf) => {
+                writeln!(f, "colliders: {collider_sdf:?}")?;
+            }
+        }
+        writeln!(f, "last_measurement: {}", self.last_measurement())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use parry2d::{na::Isometry2, shape};
+
+    use super::*;
+    use crate::{config::SdfFalloff, factor::collider_sdf::Collider, state::StateLayout};
+
+    #[test]
+    fn colliders_analytic_jacobian_matches_first_order() {
+        let colliders = [Collider {
+            isometry: Isometry2::translation(0.0, 0.0),
+            shape:    Arc::new(shape::Cuboid::new([2.0, 1.0].into())),
+        }];
+        let factor = ObstacleFactor::with_colliders(Arc::new(ColliderSdf::new(colliders, 3.0)));
+
+        // The obstacles are in the plane, so every layout measures the same
+        for layout in [StateLayout::Planar, StateLayout::Spatial] {
+            let state = FactorState::new(array![0.0], 0.01, ObstacleFactor::NEIGHBORS, layout);
+
+            let linearisation_point = layout.state_from_planar([1.0, 2.5], [0.0, 0.0]);
+            let measurement = factor.measure(&state, &linearisation_point);
+            assert_relative_eq!(measurement.value[0], 0.5, epsilon = 1e-6);
+
+            let analytic = factor.jacobian(&state, &linearisation_point).into_owned();
+            assert_eq!(analytic.ncols(), layout.dofs());
+            let numeric = factor.first_order_jacobian(&state, linearisation_point);
+            for (a, n) in analytic.iter().zip(numeric.iter()) {
+                assert_relative_eq!(a, n, epsilon = 1e-3);
+            }
+        }
+    }
+
+    /// A distance field of a 10 x 10 world with a wall along the left side,
+    /// sampled at 10 pixels per metre
+    fn wall_distance_field(clamp: f32) -> Arc<DistanceField> {
+        #[allow(clippy::cast_precision_loss)]
+        let distances = DistanceImage::from_fn(100, 100, |x, _| {
+            // The wall ends 2 metres from the left edge
+            let distance = (x as f32 + 0.5) / 10.0 - 2.0;
+            image::Luma([distance.clamp(-clamp, clamp)])
+        });
+        Arc::new(DistanceField::new(distances, clamp))
+    }
+
+    #[test]
+    fn distance_field_falls_off_from_the_wall() {
+        let world_size = WorldSize {
+            width:  10.0,
+            height: 10.0,
+        };
+        let state = FactorState::new(
+            array![0.0],
+            0.01,
+            ObstacleFactor::NEIGHBORS,
+            StateLayout::Planar,
+        );
+        // The wall is at x = -3.0 in world coordinates
+        let measure = |falloff, x| {
+            let sampling = SdfSamplingSection {
+                interpolation: SdfInterpolation::Bilinear,
+                falloff,
+                ..Default::default()
+            };
+            let factor =
+                ObstacleFactor::with_distance_field(wall_distance_field(2.0), world_size, sampling);
+            factor.measure(&state, &array![x, 0.0, 0.0, 0.0]).value[0]
+        };
+
+        assert_relative_eq!(measure(SdfFalloff::Linear, -3.0), 1.0, epsilon = 1e-6);
+        assert_relative_eq!(measure(SdfFalloff::Linear, -2.0), 0.5, epsilon = 1e-6);
+        assert_relative_eq!(measure(SdfFalloff::Quadratic, -2.0), 0.25, epsilon = 1e-6);
+        assert_relative_eq!(measure(SdfFalloff::Linear, 0.0), 0.0);
+        // Inside the wall the measurement keeps growing until the clamp
+        assert_relative_eq!(measure(SdfFalloff::Linear, -4.0), 1.5, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn distance_field_analytic_jacobian_matches_first_order() {
+        let world_size = WorldSize {
+            width:  10.0,
+            height: 10.0,
+        };
+        let state = FactorState::new(
+            array![0.0],
+            0.01,
+            ObstacleFactor::NEIGHBORS,
+            StateLayout::Planar,
+        );
+        // A wide clamp keeps the curvature of the quadratic falloff small
+        // enough for the forward differences
+        let linearisation_point = array![-2.33, 1.7, 0.0, 0.0];
+
+        for falloff in [SdfFalloff::Linear, SdfFalloff::Quadratic] {
+            let sampling = SdfSamplingSection {
+                interpolation: SdfInterpolation::Bicubic,
+                falloff,
+                ..Default::default()
+            };
+            let factor =
+                ObstacleFactor::with_distance_field(wall_distance_field(5.0), world_size, sampling);
+            let analytic = factor.jacobian(&state, &linearisation_point).into_owned();
+            // Moving away from the wall lowers the measurement
+            assert!(analytic[(0, 0)] < 0.0);
+
+            let numeric = factor.first_order_jacobian(&state, linearisation_point.clone());
+            for (a, n) in analytic.iter().zip(numeric.iter()) {
+                assert_relative_eq!(a, n, epsilon = 1e-2);
+            }
+        }
+    }
+
+    #[test]
+    fn interpolated_sdf_analytic_jacobian_matches_first_order() {
+        // Darker towards the bottom right, i.e. towards positive x and negative y
+        let obstacle_sdf = SdfImage::from_fn(20, 20, |x, y| {
+            let v = u8::try_from(255 - x * 6 - y * 5).expect("within 0..=255");
+            image::Rgb([v, v, v])
+        });
+        let world_size = WorldSize {
+            width:  10.0,
+            height: 10.0,
+        };
+        let state = FactorState::new(
+            array![0.0],
+            0.01,
+            ObstacleFactor::NEIGHBORS,
+            StateLayout::Planar,
+        );
+        let linearisation_point = array![1.1, -0.7, 0.0, 0.0];
+
+        for interpolation in [SdfInterpolation::Bilinear, SdfInterpolation::Bicubic] {
+            let sampling = SdfSamplingSection {
+                interpolation,
+                ..Default::default()
+            };
+            let factor = ObstacleFactor::new(obstacle_sdf.clone(), world_size, sampling);
+            let analytic = factor.jacobian(&state, &linearisation_point).into_owned();
+            assert!(analytic[(0, 0)] > 0.0);
+            assert!(analytic[(0, 1)] < 0.0);
+
+            let numeric = factor.first_order_jacobian(&state, linearisation_point.clone());
+            for (a, n) in analytic.iter().zip(numeric.iter()) {
+                assert_relative_eq!(a, n, epsilon = 1e-2);
+            }
+        }
+    }
+}
@@ -0,0 +1,353 @@
+//! Sampling of the signed distance field images used by the obstacle factor
+
+use gbp_linalg::prelude::*;
+
+use super::{obstacle::DistanceField, SdfImage};
+use crate::config::{SdfInterpolation, SdfOutOfBounds, SdfSamplingSection};
+
+/// An image of the environment the obstacle factor can sample
+pub trait SampledImage {
+    /// Width and height of the image in pixels
+    fn dimensions(&self) -> (u32, u32);
+    /// The value of pixel `(x, y)`, which is inside the image
+    fn pixel(&self, x: u32, y: u32) -> Float;
+    /// The value in free space, far from any obstacle
+    fn free(&self) -> Float;
+    /// The value inside obstacles
+    fn obstacle(&self) -> Float;
+}
+
+impl SampledImage for SdfImage {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width(), self.height())
+    }
+
+    /// 1.0 inside obstacles, 0.0 in free space
+    fn pixel(&self, x: u32, y: u32) -> Float {
+        let red_channel = self.get_pixel(x, y)[0];
+        // Dark areas are obstacles, so h(0) should return a 1 for these regions.
+        1.0 - Float::from(red_channel) / 255.0
+    }
+
+    fn free(&self) -> Float {
+        0.0
+    }
+
+    fn obstacle(&self) -> Float {
+        1.0
+    }
+}
+
+impl SampledImage for DistanceField {
+    fn dimensions(&self) -> (u32, u32) {
+        self.distances().dimensions()
+    }
+
+    /// The signed distance in metres
+    fn pixel(&self, x: u32, y: u32) -> Float {
+        Float::from(self.distances().get_pixel(x, y)[0])
+    }
+
+    fn free(&self) -> Float {
+        Float::from(self.clamp())
+    }
+
+    fn obstacle(&self) -> Float {
+        -Float::from(self.clamp())
+    }
+}
+
+/// A sample of a [`SampledImage`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// The interpolated value of the pixels
+    pub value:    Float,
+    /// Gradient of `value` with respect to the pixel coordinates.
+    /// Always zero for [`SdfInterpolation::Nearest`]
+    pub gradient: [Float; 2],
+}
+
+/// Measurement at pixel `(x, y)`, with `out_of_bounds` deciding the value
+/// outside of the image
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn pixel_value(image: &impl SampledImage, x: i64, y: i64, out_of_bounds: SdfOutOfBounds) -> Float {
+    let (width, height) = image.dimensions();
+    let (width, height) = (i64::from(width), i64::from(height));
+    if width == 0 || height == 0 {
+        return image.free();
+    }
+    let (x, y) = if (0..width).contains(&x) && (0..height).contains(&y) {
+        (x, y)
+    } else {
+        match out_of_bounds {
+            SdfOutOfBounds::Free => return image.free(),
+            SdfOutOfBounds::Obstacle => return image.obstacle(),
+            SdfOutOfBounds::Clamp => (x.clamp(0, width - 1), y.clamp(0, height - 1)),
+        }
+    };
+
+    image.pixel(x as u32, y as u32)
+}
+
+/// Weights, and their derivatives, of the pixels contributing to a sample
+/// along one axis. `t` is the fractional position between the pixel centers
+/// at offset 0 and 1. Returns the offset of the first contributing pixel
+/// relative to offset 0, and how many pixels contribute.
+fn weights(interpolation: SdfInterpolation, t: Float) -> (i64, usize, [(Float, Float); 4]) {
+    match interpolation {
+        SdfInterpolation::Nearest => (0, 1, [(1.0, 0.0), (0.0, 0.0), (0.0, 0.0), (0.0, 0.0)]),
+        SdfInterpolation::Bilinear => (0, 2, [(1.0 - t, -1.0), (t, 1.0), (0.0, 0.0), (0.0, 0.0)]),
+        SdfInterpolation::Bicubic => {
+            // Catmull-Rom spline, which passes through the pixel values
+            let t2 = t * t;
+            let t3 = t2 * t;
+            (-1, 4, [
+                (
+                    0.5 * (-t3 + 2.0 * t2 - t),
+                    0.5 * (-3.0 * t2 + 4.0 * t - 1.0),
+                ),
+                (
+                    0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+                    0.5 * (9.0 * t2 - 10.0 * t),
+                ),
+                (
+                    0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+                    0.5 * (-9.0 * t2 + 8.0 * t + 1.0),
+                ),
+                (0.5 * (t3 - t2), 0.5 * (3.0 * t2 - 2.0 * t)),
+            ])
+        }
+    }
+}
+
+/// Sample `image` at the continuous pixel coordinates `(x, y)`, where pixel `i`
+/// covers the interval `[i, i + 1)`.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn sample(
+    image: &impl SampledImage,
+    sampling: SdfSamplingSection,
+    x: Float,
+    y: Float,
+) -> Sample {
+    if sampling.interpolation == SdfInterpolation::Nearest {
+        let (x, y) = match sampling.out_of_bounds {
+            // The obstacle factor used to index the image with a saturating cast, so
+            // coordinates left of or above the image read the pixels on its edge.
+            // Keep doing so with the legacy policies, to reproduce old runs
+            SdfOutOfBounds::Free => (i64::from(x as u32), i64::from(y as u32)),
+            SdfOutOfBounds::Obstacle | SdfOutOfBounds::Clamp => {
+                (x.floor() as i64, y.floor() as i64)
+            }
+        };
+        return Sample {
+            value:    pixel_value(image, x, y, sampling.out_of_bounds),
+            gradient: [0.0, 0.0],
+        };
+    }
+
+    // Interpolate between pixel centers
+    let (x, y) = (x - 0.5, y - 0.5);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (x_first, x_count, x_weights) = weights(sampling.interpolation, x - x0);
+    let (y_first, y_count, y_weights) = weights(sampling.interpolation, y - y0);
+    let (x0, y0) = (x0 as i64, y0 as i64);
+
+    let mut value = 0.0;
+    let mut gradient = [0.0, 0.0];
+    for (j, &(wy, dwy)) in y_weights.iter().take(y_count).enumerate() {
+        for (i, &(wx, dwx)) in x_weights.iter().take(x_count).enumerate() {
+            let pixel = pixel_value(
+                image,
+                x0 + x_first + i as i64,
+                y0 + y_first + j as i64,
+                sampling.out_of_bounds,
+            );
+            value += wx * wy * pixel;
+            gradient[0] += dwx * wy * pixel;
+            gradient[1] += wx * dwy * pixel;
+        }
+    }
+
+    Sample { value, gradient }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    /// The SDF image of the two way junction, generated by `env_to_png` for the
+    /// simulator
+    fn generated_map() -> SdfImage {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../magics/assets/imgs/obstacles/junction_twoway.sdf.png"
+        );
+        image::open(path)
+            .expect("the junction has an sdf image")
+            .into_rgb8()
+    }
+
+    fn sampling(interpolation: SdfInterpolation) -> SdfSamplingSection {
+        SdfSamplingSection {
+            interpolation,
+            out_of_bounds: SdfOutOfBounds::Free,
+            ..Default::default()
+        }
+    }
+
+    /// Pixel coordinates where the image is not constant, i.e. in the blurred
+    /// border around obstacles
+    fn border_pixels(image: &SdfImage) -> Vec<(u32, u32)> {
+        let y = image.height() / 4;
+        (1..image.width() - 2)
+            .filter(|&x| image.get_pixel(x, y)[0] != image.get_pixel(x + 1, y)[0])
+            .map(|x| (x, y))
+            .collect()
+    }
+
+    #[test]
+    fn interpolation_matches_nearest_at_pixel_centers() {
+        let image = generated_map();
+        for interpolation in [SdfInterpolation::Bilinear, SdfInterpolation::Bicubic] {
+            for y in (0..image.height()).step_by(7) {
+                for x in (0..image.width()).step_by(7) {
+                    let (x, y) = (Float::from(x) + 0.5, Float::from(y) + 0.5);
+                    let nearest = sample(&image, sampling(SdfInterpolation::Nearest), x, y);
+                    let interpolated = sample(&image, sampling(interpolation), x, y);
+                    assert_relative_eq!(nearest.value, interpolated.value, epsilon = 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn analytic_gradient_matches_finite_differences() {
+        let image = generated_map();
+        let border = border_pixels(&image);
+        assert!(!border.is_empty());
+
+        let delta = 1e-5;
+        for interpolation in [SdfInterpolation::Bilinear, SdfInterpolation::Bicubic] {
+            for &(x, y) in &border {
+                // Away from the pixel centers, where the bilinear interpolation has kinks
+                let (x, y) = (Float::from(x) + 0.8, Float::from(y) + 0.3);
+                let at = sample(&image, sampling(interpolation), x, y);
+                let dx = sample(&image, sampling(interpolation), x + delta, y);
+                let dy = sample(&image, sampling(interpolation), x, y + delta);
+                assert_relative_eq!(
+                    at.gradient[0],
+                    (dx.value - at.value) / delta,
+                    epsilon = 1e-4
+                );
+                assert_relative_eq!(
+                    at.gradient[1],
+                    (dy.value - at.value) / delta,
+                    epsilon = 1e-4
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_has_plateaus_where_interpolation_has_gradient() {
+        let image = generated_map();
+        let &(x, y) = border_pixels(&image)
+            .first()
+            .expect("the map has obstacles");
+        // Between the centers of pixel `x` and `x + 1`, which differ
+        let (x, y) = (Float::from(x) + 0.7, Float::from(y) + 0.5);
+
+        // A finite difference smaller than a pixel sees no change with nearest
+        let nearest = sampling(SdfInterpolation::Nearest);
+        let step = 0.25;
+        assert_eq!(
+            sample(&image, nearest, x, y).value,
+            sample(&image, nearest, x + step, y).value
+        );
+
+        for interpolation in [SdfInterpolation::Bilinear, SdfInterpolation::Bicubic] {
+            let interpolated = sample(&image, sampling(interpolation), x, y);
+            assert!(interpolated.gradient[0].abs() > 0.0);
+        }
+    }
+
+    #[test]
+    fn nearest_free_matches_the_legacy_indexing() {
+        let image = generated_map();
+        let (width, height) = image.dimensions();
+
+        // How the obstacle factor sampled the image before the sampling section
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let legacy = |x: Float, y: Float| {
+            image
+                .get_pixel_checked(x as u32, y as u32)
+                .map_or(0.0, |pixel| 1.0 - Float::from(pixel[0]) / 255.0)
+        };
+
+        // The corners of the junction are obstacles, which reading free space left
+        // of and above the image would miss
+        assert!(legacy(-0.1, -0.1) > 0.0);
+
+        let nearest = sampling(SdfInterpolation::Nearest);
+        let coordinates = |size: u32| {
+            (-20..=i32::try_from(size).expect("the map is small") * 4 + 20)
+                .map(|i| Float::from(i) * 0.25 - 0.1)
+        };
+        for y in coordinates(height).step_by(31) {
+            for x in coordinates(width).step_by(23) {
+                assert_eq!(sample(&image, nearest, x, y).value, legacy(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_bounds_policies() {
+        let mut image = SdfImage::new(2, 2);
+        for pixel in image.pixels_mut() {
+            // 1 - 51 / 255 = 0.8
+            *pixel = image::Rgb([51, 51, 51]);
+        }
+
+        let at = |out_of_bounds, interpolation, x| {
+            sample(
+                &image,
+                SdfSamplingSection {
+                    interpolation,
+                    out_of_bounds,
+                    ..Default::default()
+                },
+                x,
+                1.0,
+            )
+            .value
+        };
+
+        for interpolation in [
+            SdfInterpolation::Nearest,
+            SdfInterpolation::Bilinear,
+            SdfInterpolation::Bicubic,
+        ] {
+            for x in [-3.0, 5.0] {
+                assert_relative_eq!(at(SdfOutOfBounds::Obstacle, interpolation, x), 1.0);
+                assert_relative_eq!(
+                    at(SdfOutOfBounds::Clamp, interpolation, x),
+                    0.8,
+                    epsilon = 1e-9
+                );
+            }
+            assert_relative_eq!(at(SdfOutOfBounds::Free, interpolation, 5.0), 0.0);
+        }
+
+        // Like the legacy indexing, nearest reads the edge of the image left of it
+        assert_relative_eq!(
+            at(SdfOutOfBounds::Free, SdfInterpolation::Nearest, -3.0),
+            0.8,
+            epsilon = 1e-9
+        );
+        for interpolation in [SdfInterpolation::Bilinear, SdfInterpolation::Bicubic] {
+            assert_relative_eq!(at(SdfOutOfBounds::Free, interpolation, -3.0), 0.0);
+        }
+    }
+}
@@ -1,14 +1,13 @@
 //! Tracking Factor (extension)
 use std::{borrow::Cow, cell::Cell, ops::Sub, sync::Mutex};
 
-use bevy::{math::Vec2, utils::smallvec::ToSmallVec};
 use colored::Colorize;
 use gbp_linalg::{prelude::*, pretty_print_matrix};
+use glam::Vec2;
 use itertools::Itertools;
 use ndarray::{array, concatenate, s, Axis};
 
 use super::{Factor, FactorState, Measurement};
-use crate::factorgraph::DOFS;
 
 /// Tracking information for each tracking factor to follow
 #[derive(Debug)]
@@ -24,7 +23,7 @@ pub struct Tracking {
     /// Amount of projects being considered
     connections: Mutex<Cell<usize>>,
     /// The tracking config from the `gbp_config` input `config.toml`
-    pub config: gbp_config::TrackingSection,
+    pub config: crate::config::TrackingSection,
 }
 
 impl Default for Tracking {
@@ -34,7 +33,7 @@ impl Default for Tracking {
             index: 1,
             record: Mutex::new(Cell::new(0)),
             connections: Mutex::new(Cell::new(1)),
-            config: gbp_config::TrackingSection::default(),
+            config: crate::config::TrackingSection::default(),
         }
     }
 }
@@ -45,7 +44,7 @@ impl Tracking {
         self
     }
 
-    pub fn with_config(mut self, config: gbp_config::TrackingSection) -> Self {
+    pub fn with_config(mut self, config: crate::config::TrackingSection) -> Self {
         self.config = config;
         self
     }
@@ -80,7 +79,7 @@ pub struct TrackingFactor {
 
 #[derive(Debug, Clone, Copy)]
 pub struct LastMeasurement {
-    pub pos:   bevy::math::Vec2,
+    pub pos:   Vec2,
     pub value: Float,
 }
 
@@ -116,7 +115,7 @@ impl TrackingFactor {
         self
     }
 
-    pub fn with_config(mut self, config: gbp_config::TrackingSection) -> Self {
+    pub fn with_config(mut self, config: crate::config::TrackingSection) -> Self {
         self.tracking.config = config;
         self
     }
@@ -177,16 +176,14 @@ impl Factor for TrackingFactor {
         let h0 = array![last_measurement.value];
 
         let m = last_measurement.pos;
-        let pos = linearisation_point.slice(s![..DOFS / 2]).to_owned();
+        let pos = linearisation_point.slice(s![..2]).to_owned();
         // dbg!(&pos);
 
         let temp = array![m.x as Float, m.y as Float];
         let x_diff = pos - temp;
 
-        let mut jacobian = Matrix::<Float>::zeros((h0.len(), DOFS));
-        jacobian
-            .slice_mut(s![0, ..DOFS / 2])
-            .assign(&(1.0 / h0 * &x_diff));
+        let mut jacobian = Matrix::<Float>::zeros((h0.len(), state.layout.dofs()));
+        jacobian.slice_mut(s![0, ..2]).assign(&(1.0 / h0 * &x_diff));
 
         // pretty_print_matrix!(&jacobian);
 
@@ -194,10 +191,13 @@ impl Factor for TrackingFactor {
     }
 
     // fn measure(&self, _state: &FactorState, x: &Vector<Float>) -> Vector<Float> {
-    fn measure(&self, _state: &FactorState, x: &Vector<Float>) -> Measurement {
+    fn measure(&self, state: &FactorState, x: &Vector<Float>) -> Measurement {
         let current_record = self.tracking.record.lock().unwrap().get();
+        // The path is in the plane, so only the planar position and velocity are
+        // tracked, regardless of the state layout
+        let velocity_offset = state.layout.velocity_offset();
         let x_pos = x.slice(s![0..2]).to_owned();
-        let x_vel = x.slice(s![2..4]).to_owned();
+        let x_vel = x.slice(s![velocity_offset..velocity_offset + 2]).to_owned();
 
         // 1. Find which line in the `self.tracking.path` to project to, based off of
         //    the `self.tracking.record` e.g. if `self.tracking.record` is 3, then track
@@ -341,7 +341,7 @@ impl Factor for TrackingFactor {
         Measurement::new(array![measurement]).with_position(concatenate![
             Axis(0),
             measurement_point,
-            x.slice(s![2..4]).to_owned()
+            x_vel
         ])
     }
 
@@ -0,0 +1,252 @@
+//! Factors defined outside of this crate
+//!
+//! Any type implementing [`Factor`] can be added to a factorgraph as a
+//! [`FactorKind::User`](super::FactorKind::User) factor, with
+//! [`FactorNode::new_user_factor`](super::FactorNode::new_user_factor), without
+//! adding a variant to [`FactorKind`](super::FactorKind). The factor is updated
+//! in the internal iteration like the built-in factors, and is connected to the
+//! variables given by an [`Attachment`], see
+//! [`FactorGraph::attach_user_factors`](crate::factorgraph::FactorGraph::attach_user_factors).
+
+use std::{any::Any, ops::Range};
+
+use serde::{Deserialize, Serialize};
+
+use super::Factor;
+
+/// A factor defined outside of this crate.
+///
+/// Implemented for every [`Factor`] that can be stored in a factorgraph, i.e.
+/// one that is `Debug`, `Send` and `Sync`.
+pub trait UserFactor: Factor + std::fmt::Debug + Send + Sync + Any {
+    /// Upcast to [`Any`], to downcast to the concrete type of the factor
+    fn as_any(&self) -> &dyn Any;
+    /// Upcast to [`Any`], to downcast to the concrete type of the factor
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Factor + std::fmt::Debug + Send + Sync + Any> UserFactor for T {
+    #[inline]
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    #[inline]
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl dyn UserFactor {
+    /// Returns `true` if the factor is of type `T`
+    #[inline]
+    #[must_use]
+    pub fn is<T: UserFactor>(&self) -> bool {
+        self.as_any().is::<T>()
+    }
+
+    /// Returns the factor as a `T`, if it is of that type
+    #[inline]
+    #[must_use]
+    pub fn downcast_ref<T: UserFactor>(&self) -> Option<&T> {
+        self.as_any().downcast_ref::<T>()
+    }
+
+    /// Returns the factor as a mutable `T`, if it is of that type
+    #[inline]
+    #[must_use]
+    pub fn downcast_mut<T: UserFactor>(&mut self) -> Option<&mut T> {
+        self.as_any_mut().downcast_mut::<T>()
+    }
+}
+
+/// Which variables of a factorgraph a user defined factor is attached to.
+/// The variables are ordered from the current state to the horizon state.
+///
+/// ## Example
+/// ```toml
+/// [factors.speed-limit]
+/// attach-to = "consecutive"
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Attachment {
+    /// A factor for every variable
+    Every,
+    /// A factor for every variable, except the current state and the horizon
+    /// state, like the obstacle factors
+    #[default]
+    Inbetween,
+    /// A factor for every variable, except the current state
+    Future,
+    /// A single factor attached to the horizon state
+    Horizon,
+    /// A factor between every two consecutive variables, like the dynamic
+    /// factors
+    Consecutive,
+}
+
+impl Attachment {
+    /// Number of variables every factor is connected to
+    #[must_use]
+    pub const fn neighbours(self) -> usize {
+        match self {
+            Self::Every | Self::Inbetween | Self::Future | Self::Horizon => 1,
+            Self::Consecutive => 2,
+        }
+    }
+
+    /// The positions of the variables every factor is attached to, out of
+    /// `variables` variables ordered from the current state to the horizon
+    /// state
+    pub fn groups(self, variables: usize) -> impl Iterator<Item = Range<usize>> {
+        let starts = match self {
+            Self::Every => 0..variables,
+            Self::Inbetween => 1..variables.saturating_sub(1),
+            Self::Future => 1..variables,
+            Self::Horizon => variables.saturating_sub(1)..variables,
+            Self::Consecutive => 0..variables.saturating_sub(1),
+        };
+        let neighbours = self.neighbours();
+        starts.map(move |start| start..start + neighbours)
+    }
+}
+
+impl std::fmt::Display for Attachment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Every => "every",
+            Self::Inbetween => "inbetween",
+            Self::Future => "future",
+            Self::Horizon => "horizon",
+            Self::Consecutive => "consecutive",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use gbp_linalg::prelude::*;
+    use ndarray::array;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        factor::{FactorKind, FactorNode, FactorState, Measurement},
+        factorgraph::FactorGraph,
+        state::StateLayout,
+        variable::VariableNode,
+    };
+
+    /// Pulls the x position of a variable towards `x`
+    #[derive(Debug)]
+    struct Wall {
+        x: Float,
+    }
+
+    impl std::fmt::Display for Wall {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            writeln!(f, "x: {}", self.x)
+        }
+    }
+
+    impl Factor for Wall {
+        fn name(&self) -> &'static str {
+            "Wall"
+        }
+
+        fn color(&self) -> [u8; 3] {
+            [255, 0, 0]
+        }
+
+        fn jacobian_delta(&self) -> Float {
+            1e-8
+        }
+
+        fn neighbours(&self) -> usize {
+            1
+        }
+
+        fn skip(&self, _state: &FactorState) -> bool {
+            false
+        }
+
+        fn linear(&self) -> bool {
+            true
+        }
+
+        fn jacobian(&self, state: &FactorState, _x: &Vector<Float>) -> Cow<'_, Matrix<Float>> {
+            let mut jacobian = Matrix::<Float>::zeros((1, state.layout.dofs()));
+            jacobian[[0, 0]] = 1.0;
+            Cow::Owned(jacobian)
+        }
+
+        fn measure(&self, _state: &FactorState, x: &Vector<Float>) -> Measurement {
+            Measurement::new(array![x[0] - self.x])
+        }
+    }
+
+    #[test]
+    fn groups() {
+        let groups = |attachment: Attachment| attachment.groups(4).collect::<Vec<_>>();
+        assert_eq!(groups(Attachment::Every), vec![0..1, 1..2, 2..3, 3..4]);
+        assert_eq!(groups(Attachment::Inbetween), vec![1..2, 2..3]);
+        assert_eq!(groups(Attachment::Future), vec![1..2, 2..3, 3..4]);
+        assert_eq!(groups(Attachment::Horizon), vec![3..4]);
+        assert_eq!(groups(Attachment::Consecutive), vec![0..2, 1..3, 2..4]);
+        assert_eq!(Attachment::Inbetween.groups(1).count(), 0);
+    }
+
+    #[test]
+    fn downcast() {
+        let factor: Box<dyn UserFactor> = Box::new(Wall { x: 1.0 });
+        assert!(factor.is::<Wall>());
+        assert_eq!(factor.downcast_ref::<Wall>().map(|wall| wall.x), Some(1.0));
+    }
+
+    #[test]
+    fn user_factors_pull_the_variables() {
+        let layout = StateLayout::Planar;
+        let mut factorgraph = FactorGraph::new(0_u32);
+        for _ in 0..4 {
+            let variable = VariableNode::new(
+                0,
+                Vector::<Float>::zeros(layout.dofs()),
+                Matrix::<Float>::from_diag_elem(layout.dofs(), 1.0),
+                layout,
+            );
+            factorgraph.add_variable(variable);
+        }
+
+        let attached = factorgraph.attach_user_factors(Attachment::Inbetween, |variables| {
+            (variables.start != 2).then(|| {
+                FactorNode::new_user_factor(0, layout, 0.01, array![0.0], Wall { x: 5.0 }, true)
+            })
+        });
+        assert_eq!(attached.len(), 1);
+        assert_eq!(factorgraph.factor_count().user, 1);
+        assert!(matches!(
+            factorgraph
+                .get_factor(attached[0])
+                .map(|factor| &factor.kind),
+            Some(FactorKind::User(_))
+        ));
+        assert_eq!(factorgraph.user_factors_of::<Wall>().count(), 1);
+
+        for _ in 0..10 {
+            factorgraph.internal_iteration();
+        }
+
+        let x = |nth| {
+            factorgraph
+                .nth_variable(nth)
+                .map(|(_, variable)| variable.estimated_position()[0])
+                .expect("the variable exists")
+        };
+        assert!((x(1) - 5.0).abs() < 1e-2, "x is {}", x(1));
+        assert!(x(2).abs() < 1e-2, "x is {}", x(2));
+    }
+}
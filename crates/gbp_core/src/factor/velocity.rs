@@ -0,0 +1,184 @@
+//! Velocity limit factor in the factorgraph
+
+use std::borrow::Cow;
+
+use gbp_linalg::prelude::*;
+use ndarray::s;
+
+use super::{Factor, FactorState, Measurement};
+
+/// Velocity limit factor: for respecting the max speed of the robot.
+///
+/// This factor results in a high energy or cost if the planned speed of a
+/// variable is greater than the max speed of the robot. Like the
+/// [`InterRobotFactor`](super::interrobot::InterRobotFactor) it is a hinge
+/// loss, and has 0 energy as long as the speed is within the limit.
+///
+/// The speed is the norm of the translational velocity of the state layout,
+/// i.e. in 3D for the spatial layout.
+#[derive(Debug, Clone)]
+pub struct VelocityFactor {
+    max_speed: Float,
+}
+
+impl VelocityFactor {
+    /// Neighbour to a single variable
+    pub const NEIGHBORS: usize = 1;
+
+    /// Create a new `VelocityFactor`, limiting the speed to `max_speed`
+    #[must_use]
+    pub const fn new(max_speed: Float) -> Self {
+        Self { max_speed }
+    }
+
+    /// Get the max speed
+    #[inline(always)]
+    pub const fn max_speed(&self) -> Float {
+        self.max_speed
+    }
+
+    /// Update the max speed
+    pub fn update_max_speed(&mut self, max_speed: Float) {
+        self.max_speed = max_speed;
+    }
+
+    /// Fraction by which `velocity` exceeds the max speed, e.g. 0.5 for a speed
+    /// 50% above the max speed. 0.0 if the speed is within the limit
+    #[must_use]
+    pub fn violation(&self, velocity: [Float; 3]) -> Float {
+        let speed = velocity.iter().map(|v| v * v).sum::<Float>().sqrt();
+        (speed / self.max_speed - 1.0).max(0.0)
+    }
+
+    /// Returns the speed of the variable in `x`, and its velocity
+    fn speed(state: &FactorState, x: &Vector<Float>) -> (Float, Vector<Float>) {
+        let offset = state.layout.velocity_offset();
+        let velocity = x
+            .slice(s![offset..offset + state.layout.position_dims()])
+            .to_owned();
+        (velocity.euclidean_norm(), velocity)
+    }
+}
+
+impl Factor for VelocityFactor {
+    #[inline(always)]
+    fn name(&self) -> &'static str {
+        "VelocityFactor"
+    }
+
+    #[inline]
+    fn color(&self) -> [u8; 3] {
+        // #eed49f
+        [238, 212, 159]
+    }
+
+    fn jacobian(&self, state: &FactorState, x: &Vector<Float>) -> Cow<'_, Matrix<Float>> {
+        let offset = state.layout.velocity_offset();
+        let mut jacobian =
+            Matrix::<Float>::zeros((state.initial_measurement.len(), state.layout.dofs()));
+        let (speed, velocity) = Self::speed(state, x);
+        if speed > self.max_speed {
+            jacobian
+                .slice_mut(s![0, offset..offset + velocity.len()])
+                .assign(&(1.0 / self.max_speed / speed * &velocity));
+        }
+        Cow::Owned(jacobian)
+    }
+
+    fn measure(&self, state: &FactorState, x: &Vector<Float>) -> Measurement {
+        let mut measurement = Vector::<Float>::zeros(state.initial_measurement.len());
+        let (speed, _) = Self::speed(state, x);
+        if speed > self.max_speed {
+            measurement[0] = speed / self.max_speed - 1.0;
+        }
+        Measurement::new(measurement)
+    }
+
+    #[inline(always)]
+    fn jacobian_delta(&self) -> Float {
+        1e-2
+    }
+
+    /// Returns true if the speed of the variable is within the max speed
+    fn skip(&self, state: &FactorState) -> bool {
+        let (speed, _) = Self::speed(state, &state.linearisation_point);
+        speed <= self.max_speed
+    }
+
+    #[inline(always)]
+    fn linear(&self) -> bool {
+        false
+    }
+
+    #[inline(always)]
+    fn neighbours(&self) -> usize {
+        Self::NEIGHBORS
+    }
+}
+
+impl std::fmt::Display for VelocityFactor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "max_speed: {}", self.max_speed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use ndarray::array;
+
+    use super::*;
+    use crate::state::StateLayout;
+
+    fn factor(layout: StateLayout) -> (VelocityFactor, FactorState) {
+        let state = FactorState::new(array![0.0], 0.01, VelocityFactor::NEIGHBORS, layout);
+        (VelocityFactor::new(2.0), state)
+    }
+
+    #[test]
+    fn penalises_only_speeds_above_the_limit() {
+        let (factor, state) = factor(StateLayout::Planar);
+        let within = array![5.0, 5.0, 1.2, 1.6];
+        assert_relative_eq!(factor.measure(&state, &within).value[0], 0.0);
+        assert!(factor.skip(&FactorState {
+            linearisation_point: within,
+            ..state.clone()
+        }));
+
+        let above = array![5.0, 5.0, 3.0, 4.0];
+        assert_relative_eq!(factor.measure(&state, &above).value[0], 1.5);
+        assert!(!factor.skip(&FactorState {
+            linearisation_point: above,
+            ..state
+        }));
+        assert_relative_eq!(factor.violation([3.0, 4.0, 0.0]), 1.5);
+    }
+
+    #[test]
+    fn measures_the_velocity_of_the_layout() {
+        // The velocity of the planar pose layout comes after the heading
+        let (factor, state) = factor(StateLayout::PlanarPose);
+        let linearisation_point = array![0.0, 0.0, 10.0, 3.0, 4.0, 10.0];
+        assert_relative_eq!(factor.measure(&state, &linearisation_point).value[0], 1.5);
+    }
+
+    #[test]
+    fn analytic_jacobian_matches_first_order() {
+        for layout in [
+            StateLayout::Planar,
+            StateLayout::Spatial,
+            StateLayout::PlanarPose,
+        ] {
+            let (factor, state) = factor(layout);
+            let mut linearisation_point = Vector::<Float>::zeros(layout.dofs());
+            let offset = layout.velocity_offset();
+            linearisation_point[offset] = 3.0;
+            linearisation_point[offset + 1] = -2.5;
+            let analytic = factor.jacobian(&state, &linearisation_point).into_owned();
+            let numeric = factor.first_order_jacobian(&state, linearisation_point);
+            for (a, n) in analytic.iter().zip(numeric.iter()) {
+                assert_relative_eq!(a, n, epsilon = 1e-2);
+            }
+        }
+    }
+}
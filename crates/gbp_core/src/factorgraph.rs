@@ -1,32 +1,25 @@
-use bevy::{
-    ecs::{component::Component, entity::Entity},
-    log::{debug, info},
-    math::Vec2,
-};
-// use gbp_linalg::Float;
 use gbp_linalg::prelude::*;
+use glam::Vec2;
 use itertools::Itertools;
 use petgraph::{stable_graph::EdgeReference, visit::EdgeRef, Undirected};
+use tracing::{debug, info};
 use typed_floats::StrictlyPositiveFinite;
 
 use super::{
+    config::{ConvergenceCriterion, ConvergenceSection},
     factor::{
-        interrobot::InterRobotFactor, obstacle::ObstacleFactor, tracking::TrackingFactor, Factor,
-        FactorKind, FactorNode,
+        acceleration::AccelerationFactor, dynamic_obstacle::ObstacleSnapshot,
+        interrobot::InterRobotFactor, obstacle::ObstacleFactor, tracking::TrackingFactor,
+        user::Attachment, velocity::VelocityFactor, Factor, FactorKind, FactorNode, UserFactor,
     },
-    id::{FactorId, VariableId},
-    message::{FactorToVariableMessage, VariableToFactorMessage},
+    id::{FactorId, GraphId, VariableId},
+    message::{ExternalMessage, FactorToVariableMessage, VariableToFactorMessage},
     node::{FactorGraphNode, Node, NodeKind, RemoveConnectionToError},
     prelude::Message,
     variable::VariableNode,
     MessageCount, MessagesReceived, MessagesSent,
 };
 
-/// type alias used to represent the id of the factorgraph
-/// Since we use **Bevy** we can use the `Entity` id of the whatever entity the
-/// the factorgraph is attached to as a Component, as its unique identifier.
-pub type FactorGraphId = Entity;
-
 /// Type parameter setting the upper bound for the size of the graph
 /// u16 -> 2^16 -1 = 65535
 type IndexSize = u16;
@@ -39,7 +32,7 @@ pub type NodeIndex = petgraph::stable_graph::NodeIndex<IndexSize>;
 /// The type used to represent indices into the nodes of the factorgraph.
 pub type EdgeIndex = petgraph::stable_graph::EdgeIndex<IndexSize>;
 /// A factorgraph is an undirected graph
-pub type Graph = petgraph::stable_graph::StableGraph<Node, (), Undirected, IndexSize>;
+pub type Graph<Id> = petgraph::stable_graph::StableGraph<Node<Id>, (), Undirected, IndexSize>;
 
 /// A newtype used to enforce type safety of the indices of the factors in the
 /// factorgraph.
@@ -69,22 +62,50 @@ struct IterationCount {
     factor:   usize,
 }
 
+/// How many internal GBP iterations a factorgraph has run, and how often the
+/// iterations of a timestep ended early because the beliefs converged
+#[derive(Debug, Clone, Copy, Default, serde::Serialize)]
+pub struct IterationStatistics {
+    /// Number of timesteps the factorgraph has been iterated in
+    pub timesteps: usize,
+    /// Number of internal iterations run across all timesteps
+    pub internal: usize,
+    /// Number of internal iterations run in the latest timestep
+    pub latest_timestep: usize,
+    /// Number of timesteps where the internal iterations ended early
+    pub converged_timesteps: usize,
+}
+
 /// A factor graph is a bipartite graph consisting of two types of nodes:
 /// factors and variables.
-#[derive(Component, Debug)]
-// #[cfg_attr(feature = "bevy", derive(Component))]
-pub struct FactorGraph {
+///
+/// `Id` identifies the factorgraph among all the factorgraphs it exchanges
+/// messages with, see [`GraphId`].
+#[derive(Debug)]
+pub struct FactorGraph<Id> {
     /// The id of the factorgraph. We store a copy of it here, for convenience.
     /// **Invariants**:
     /// - The id of the factorgraph is unique among all factorgraphs in the
     ///   system.
     /// - The id does not change during the lifetime of the factorgraph.
-    id:    FactorGraphId,
+    id:    Id,
     /// The underlying graph data structure
-    graph: Graph,
+    graph: Graph<Id>,
 
     iteration_count: IterationCount,
 
+    /// Weight of the previous message when a factor sends a new message to a
+    /// variable, see [`Message::damped`]
+    damping: Float,
+    /// Criterion for ending the internal iterations of a timestep early
+    convergence: ConvergenceSection,
+    /// Whether the beliefs have converged since the last external message was
+    /// received in the current timestep
+    converged: bool,
+    /// Whether the beliefs have converged at any point in the current timestep
+    converged_in_timestep: bool,
+    iteration_statistics: IterationStatistics,
+
     message_count:    MessageCount,
     /// In **gbpplanner** the sequence in which variables are inserted/created
     /// in the graph is meaningful. `self.graph` does not capture this
@@ -117,6 +138,22 @@ pub struct FactorGraph {
     /// List of indices of the tracking factors in the graph.
     /// Used to speed up iteration over tracking factors.
     tracking_factor_indices: Vec<NodeIndex>,
+
+    /// List of indices of the dynamic obstacle factors in the graph.
+    /// Used to speed up updating the observed obstacles of every factor.
+    dynamic_obstacle_factor_indices: Vec<NodeIndex>,
+
+    /// List of indices of the velocity limit factors in the graph.
+    /// Used to speed up iteration over velocity limit factors.
+    velocity_factor_indices: Vec<NodeIndex>,
+
+    /// List of indices of the acceleration limit factors in the graph.
+    /// Used to speed up iteration over acceleration limit factors.
+    acceleration_factor_indices: Vec<NodeIndex>,
+
+    /// List of indices of the user defined factors in the graph, of every
+    /// kind. Used to speed up iteration over user defined factors.
+    user_factor_indices: Vec<NodeIndex>,
 }
 
 // macro_rules! internal_factor_iteration_inner {
@@ -141,28 +178,37 @@ pub struct FactorGraph {
 //     };
 // }
 
-impl FactorGraph {
+impl<Id: GraphId> FactorGraph<Id> {
     /// Construct a new empty factorgraph with a given id
     #[must_use]
-    pub fn new(id: FactorGraphId) -> Self {
+    pub fn new(id: Id) -> Self {
         Self {
             id,
             graph: Graph::with_capacity(0, 0),
             message_count: MessageCount::default(),
             iteration_count: IterationCount::default(),
+            damping: 0.0,
+            convergence: ConvergenceSection::default(),
+            converged: false,
+            converged_in_timestep: false,
+            iteration_statistics: IterationStatistics::default(),
             variable_indices: Vec::new(),
             factor_indices: Vec::new(),
             interrobot_factor_indices: Vec::new(),
             obstacle_factor_indices: Vec::new(),
             dynamic_factor_indices: Vec::new(),
             tracking_factor_indices: Vec::new(),
+            dynamic_obstacle_factor_indices: Vec::new(),
+            velocity_factor_indices: Vec::new(),
+            acceleration_factor_indices: Vec::new(),
+            user_factor_indices: Vec::new(),
         }
     }
 
     /// Construct a new empty factorgraph with the specified capacity
     /// for nodes and edges.
     #[must_use]
-    pub fn with_capacity(id: FactorGraphId, nodes: usize, edges: usize) -> Self {
+    pub fn with_capacity(id: Id, nodes: usize, edges: usize) -> Self {
         Self {
             id,
             graph: Graph::with_capacity(nodes, edges),
@@ -170,24 +216,69 @@ impl FactorGraph {
             factor_indices: Vec::with_capacity(edges),
             message_count: MessageCount::default(),
             iteration_count: IterationCount::default(),
+            damping: 0.0,
+            convergence: ConvergenceSection::default(),
+            converged: false,
+            converged_in_timestep: false,
+            iteration_statistics: IterationStatistics::default(),
             interrobot_factor_indices: Vec::new(),
             obstacle_factor_indices: Vec::new(),
             dynamic_factor_indices: Vec::new(),
             tracking_factor_indices: Vec::new(),
+            dynamic_obstacle_factor_indices: Vec::new(),
+            velocity_factor_indices: Vec::new(),
+            acceleration_factor_indices: Vec::new(),
+            user_factor_indices: Vec::new(),
         }
     }
 
-    /// Returns the `FactorGraphId` of the factorgraph
+    /// Returns the id of the factorgraph
     #[inline(always)]
     #[must_use]
-    pub const fn id(&self) -> FactorGraphId {
+    pub const fn id(&self) -> Id {
         self.id
     }
 
+    /// Damp the messages sent by factors to variables, see [`Message::damped`]
+    pub fn set_damping(&mut self, damping: Float) {
+        self.damping = damping;
+    }
+
+    /// Set the criterion for ending the internal iterations of a timestep
+    /// early
+    pub fn set_convergence(&mut self, convergence: ConvergenceSection) {
+        self.convergence = convergence;
+    }
+
+    /// Start a new timestep of GBP iterations.
+    /// Resets whether the beliefs have converged
+    pub fn start_timestep(&mut self) {
+        self.converged = false;
+        self.converged_in_timestep = false;
+        self.iteration_statistics.timesteps += 1;
+        self.iteration_statistics.latest_timestep = 0;
+    }
+
+    /// Whether the beliefs of the variables have converged in the current
+    /// timestep, such that the remaining internal iterations can be skipped.
+    /// Receiving an external message resumes the internal iterations
+    #[inline]
+    #[must_use]
+    pub const fn converged(&self) -> bool {
+        self.converged
+    }
+
+    /// How many internal iterations have been run
+    #[inline]
+    #[must_use]
+    pub const fn iteration_statistics(&self) -> IterationStatistics {
+        self.iteration_statistics
+    }
+
     /// Adds a variable to the factorgraph
     /// Returns the index of the variable in the factorgraph
     #[allow(clippy::missing_panics_doc)]
-    pub fn add_variable(&mut self, variable: VariableNode) -> VariableIndex {
+    pub fn add_variable(&mut self, variable: VariableNode<Id>) -> VariableIndex {
         let node = Node::new(self.id, NodeKind::Variable(variable));
         let node_index = self.graph.add_node(node);
         self.variable_indices.push(node_index);
@@ -205,7 +296,7 @@ impl FactorGraph {
     #[allow(clippy::missing_panics_doc)]
     /// Adds a factor to the factorgraph
     /// Returns the index of the factor in the factorgraph
-    pub fn add_factor(&mut self, factor: FactorNode) -> FactorIndex {
+    pub fn add_factor(&mut self, factor: FactorNode<Id>) -> FactorIndex {
         let node = Node::new(self.id, NodeKind::Factor(factor));
         let node_index = self.graph.add_node(node);
 
@@ -220,6 +311,12 @@ impl FactorGraph {
             FactorKind::Dynamic(_) => self.dynamic_factor_indices.push(node_index),
             FactorKind::Obstacle(_) => self.obstacle_factor_indices.push(node_index),
             FactorKind::Tracking(_) => self.tracking_factor_indices.push(node_index),
+            FactorKind::DynamicObstacle(_) => {
+                self.dynamic_obstacle_factor_indices.push(node_index);
+            }
+            FactorKind::Velocity(_) => self.velocity_factor_indices.push(node_index),
+            FactorKind::Acceleration(_) => self.acceleration_factor_indices.push(node_index),
+            FactorKind::User(_) => self.user_factor_indices.push(node_index),
         }
 
         node_index.into()
@@ -262,10 +359,14 @@ impl FactorGraph {
     /// **Computes in O(1) time**
     pub fn factor_count(&self) -> FactorCount {
         FactorCount {
-            obstacle:   self.obstacle_factor_indices.len(),
+            obstacle: self.obstacle_factor_indices.len(),
             interrobot: self.interrobot_factor_indices.len(),
-            dynamic:    self.dynamic_factor_indices.len(),
-            tracking:   self.tracking_factor_indices.len(),
+            dynamic: self.dynamic_factor_indices.len(),
+            tracking: self.tracking_factor_indices.len(),
+            dynamic_obstacle: self.dynamic_obstacle_factor_indices.len(),
+            velocity: self.velocity_factor_indices.len(),
+            acceleration: self.acceleration_factor_indices.len(),
+            user: self.user_factor_indices.len(),
         }
     }
 
@@ -280,7 +381,7 @@ impl FactorGraph {
     /// factorgraph, then return and Error.
     pub fn remove_connection_to(
         &mut self,
-        factorgraph_id: FactorGraphId,
+        factorgraph_id: Id,
     ) -> Result<(), RemoveConnectionToError> {
         let mut connections_removed: usize = 0;
         for node in self.graph.node_weights_mut() {
@@ -301,7 +402,11 @@ impl FactorGraph {
     /// **invariants**:
     /// - Both `a` and `b` must already be in the factorgraph. Panics if any of
     ///   the nodes does not exist.
-    pub fn add_internal_edge(&mut self, variable_id: VariableId, factor_id: FactorId) -> EdgeIndex {
+    pub fn add_internal_edge(
+        &mut self,
+        variable_id: VariableId<Id>,
+        factor_id: FactorId<Id>,
+    ) -> EdgeIndex {
         // let message_to_factor = {
         let Some(variable) = self.graph[variable_id.variable_index.0].as_variable_mut() else {
             panic!("the variable index either does not exist or does not point to a variable node");
@@ -337,7 +442,7 @@ impl FactorGraph {
     /// - Panics if the variable index does not point to an existing variable
     /// - Panics if the factor belongs to the this factorgraph, and not an
     ///   external one
-    pub fn add_external_edge(&mut self, factor_id: FactorId, nth_variable_index: usize) {
+    pub fn add_external_edge(&mut self, factor_id: FactorId<Id>, nth_variable_index: usize) {
         let variable_index = self
             .nth_variable_index(nth_variable_index)
             .expect("The variable index exist");
@@ -361,7 +466,7 @@ impl FactorGraph {
 
     /// Get the index and a reference to the nth variable in the factorgraph
     /// Returns `None` if the index is out of bounds
-    pub fn nth_variable(&self, index: usize) -> Option<(VariableIndex, &VariableNode)> {
+    pub fn nth_variable(&self, index: usize) -> Option<(VariableIndex, &VariableNode<Id>)> {
         let variable_index = self.nth_variable_index(index)?;
         let node = &self.graph[variable_index.0];
         let variable = node.as_variable()?;
@@ -370,14 +475,20 @@ impl FactorGraph {
 
     /// Get the index and a mutable reference to the nth variable in the
     /// factorgraph Returns `None` if the index is out of bounds
-    pub fn nth_variable_mut(&mut self, index: usize) -> Option<(VariableIndex, &mut VariableNode)> {
+    pub fn nth_variable_mut(
+        &mut self,
+        index: usize,
+    ) -> Option<(VariableIndex, &mut VariableNode<Id>)> {
         let variable_index = self.nth_variable_index(index)?;
         let node = &mut self.graph[variable_index.0];
         let variable = node.as_variable_mut()?;
         Some((variable_index, variable))
     }
 
-    pub(crate) fn delete_interrobot_factors_connected_to(&mut self, other: FactorGraphId) {
+    /// Remove every interrobot factor connected to a variable in the
+    /// factorgraph `other`, and the messages they sent to the variables of
+    /// this factorgraph
+    pub fn delete_interrobot_factors_connected_to(&mut self, other: Id) {
         // ) -> Result<(), &'static str> {
         // 1. Find all interrobot factors connected to the robot with id `other`
         // and remove them from the graph
@@ -435,7 +546,9 @@ impl FactorGraph {
         }
     }
 
-    pub(crate) fn delete_messages_from_interrobot_factor_at(&mut self, other: FactorGraphId) {
+    /// Remove the messages the interrobot factors of the factorgraph `other`
+    /// sent to the variables of this factorgraph
+    pub fn delete_messages_from_interrobot_factor_at(&mut self, other: Id) {
         // PERF: avoid allocation
         #[allow(clippy::needless_collect)]
         for node_index in self.graph.node_indices().collect::<Vec<_>>() {
@@ -495,14 +608,14 @@ impl FactorGraph {
         &mut self,
         variable_index: VariableIndex,
         new_mean: Vector<Float>,
-    ) -> Vec<VariableToFactorMessage> {
+    ) -> Vec<VariableToFactorMessage<Id>> {
         let variable_id = VariableId::new(self.id, variable_index);
         let Some(variable) = self.get_variable_mut(variable_id.variable_index) else {
             panic!("the variable index either does not exist or does not point to a variable node");
         };
 
         let factor_messages = variable.change_prior(&new_mean);
-        let mut messages_to_external_factors: Vec<VariableToFactorMessage> = Vec::new();
+        let mut messages_to_external_factors: Vec<VariableToFactorMessage<Id>> = Vec::new();
 
         for (factor_id, message) in factor_messages {
             let in_internal_graph = factor_id.factorgraph_id == self.id;
@@ -529,7 +642,7 @@ impl FactorGraph {
 
     /// Returns a refenrence to the factor with the given index.
     /// Returns `None`, if the factor does not exist.
-    pub fn get_factor(&self, index: FactorIndex) -> Option<&FactorNode> {
+    pub fn get_factor(&self, index: FactorIndex) -> Option<&FactorNode<Id>> {
         self.graph
             .node_weight(index.0)
             .and_then(|node| node.as_factor())
@@ -537,7 +650,7 @@ impl FactorGraph {
 
     /// Returns a mutable refenrence to the factor with the given index.
     /// Returns `None`, if the factor does not exist.
-    pub fn get_factor_mut(&mut self, index: FactorIndex) -> Option<&mut FactorNode> {
+    pub fn get_factor_mut(&mut self, index: FactorIndex) -> Option<&mut FactorNode<Id>> {
         self.graph
             .node_weight_mut(*index)
             .and_then(|node| node.as_factor_mut())
@@ -545,7 +658,7 @@ impl FactorGraph {
 
     /// Returns a refenrence to the variable with the given index.
     /// Returns `None`, if the variable does not exist.
-    pub fn get_variable(&self, index: VariableIndex) -> Option<&VariableNode> {
+    pub fn get_variable(&self, index: VariableIndex) -> Option<&VariableNode<Id>> {
         self.graph
             .node_weight(*index)
             .and_then(|node| node.as_variable())
@@ -553,7 +666,7 @@ impl FactorGraph {
 
     /// Returns a mutable refenrence to the variable with the given index.
     /// Returns `None`, if the variable does not exist.
-    pub fn get_variable_mut(&mut self, index: VariableIndex) -> Option<&mut VariableNode> {
+    pub fn get_variable_mut(&mut self, index: VariableIndex) -> Option<&mut VariableNode<Id>> {
         self.graph
             .node_weight_mut(*index)
             .and_then(|node| node.as_variable_mut())
@@ -565,7 +678,7 @@ impl FactorGraph {
     ///
     /// Panic if the `index` does not point to an existing variable
     #[inline]
-    fn variable(&self, index: VariableIndex) -> &VariableNode {
+    fn variable(&self, index: VariableIndex) -> &VariableNode<Id> {
         self.get_variable(index)
             .expect("variable index points to a variable in the graph")
     }
@@ -576,7 +689,7 @@ impl FactorGraph {
     ///
     /// Panic if the `index` does not point to an existing variable
     #[inline]
-    fn variable_mut(&mut self, index: VariableIndex) -> &mut VariableNode {
+    fn variable_mut(&mut self, index: VariableIndex) -> &mut VariableNode<Id> {
         self.get_variable_mut(index)
             .expect("variable index points to a variable in the graph")
     }
@@ -584,7 +697,7 @@ impl FactorGraph {
     /// Get the index of the first variable in the factorgraph and a reference
     /// to it to it Returns `None` if the factorgraph contains no variables
     #[inline(always)]
-    pub fn first_variable(&self) -> Option<(VariableIndex, &VariableNode)> {
+    pub fn first_variable(&self) -> Option<(VariableIndex, &VariableNode<Id>)> {
         self.nth_variable(0usize)
     }
 
@@ -592,7 +705,7 @@ impl FactorGraph {
     /// reference to it to it Returns `None` if the factorgraph contains no
     /// variables
     #[inline(always)]
-    pub fn last_variable(&self) -> Option<(VariableIndex, &VariableNode)> {
+    pub fn last_variable(&self) -> Option<(VariableIndex, &VariableNode<Id>)> {
         if self.variable_indices.is_empty() {
             None
         } else {
@@ -604,7 +717,7 @@ impl FactorGraph {
     /// reference to it to it Returns `None` if the factorgraph contains no
     /// variables
     #[inline(always)]
-    pub fn last_variable_mut(&mut self) -> Option<(VariableIndex, &mut VariableNode)> {
+    pub fn last_variable_mut(&mut self) -> Option<(VariableIndex, &mut VariableNode<Id>)> {
         if self.variable_indices.is_empty() {
             None
         } else {
@@ -629,8 +742,8 @@ impl FactorGraph {
     /// connected factors. As this indicates that the factorgraph is not
     /// correctly constructed.
     #[must_use]
-    pub fn variable_iteration(&mut self) -> Vec<VariableToFactorMessage> {
-        let mut messages_to_external_factors: Vec<VariableToFactorMessage> = Vec::new();
+    pub fn variable_iteration(&mut self) -> Vec<VariableToFactorMessage<Id>> {
+        let mut messages_to_external_factors: Vec<VariableToFactorMessage<Id>> = Vec::new();
 
         for &node_index in &self.variable_indices {
             let node = &mut self.graph[node_index];
@@ -683,6 +796,14 @@ impl FactorGraph {
         messages_to_external_factors
     }
 
+    /// A single internal GBP iteration, a factor iteration followed by a
+    /// variable iteration, where only messages within the factorgraph are
+    /// passed
+    pub fn internal_iteration(&mut self) {
+        self.internal_factor_iteration();
+        self.internal_variable_iteration();
+    }
+
     /// Internal Factor Iteration in Gaussian Belief Propagation (GBP).
     /// Only takes into account factors that are not interrobot factors.
     pub fn internal_factor_iteration(&mut self) {
@@ -702,7 +823,7 @@ impl FactorGraph {
                 _ => (),
             }
 
-            let variable_messages = factor.update();
+            let variable_messages = factor.update(self.damping);
             let factor_id = FactorId::new(self.id, FactorIndex(ix));
 
             for (variable_id, message) in variable_messages {
@@ -716,10 +837,10 @@ impl FactorGraph {
     /// External Factor Iteration in Gaussian Belief Propagation (GBP).
     /// Only takes into account factors that are interrobot factors.
     #[must_use]
-    pub fn external_factor_iteration(&mut self) -> Vec<FactorToVariableMessage> {
+    pub fn external_factor_iteration(&mut self) -> Vec<FactorToVariableMessage<Id>> {
         // Each interrobot factor is connected to an internal variable
         // So we can preallocate a vec of length the number of interrobot factors
-        let mut messages_to_external_variables: Vec<FactorToVariableMessage> =
+        let mut messages_to_external_variables: Vec<FactorToVariableMessage<Id>> =
             Vec::with_capacity(self.interrobot_factor_indices.len());
 
         for i in 0..self.interrobot_factor_indices.len() {
@@ -735,7 +856,7 @@ impl FactorGraph {
                 continue;
             }
 
-            let variable_messages = factor.update();
+            let variable_messages = factor.update(self.damping);
             let factor_id = FactorId::new(self.id, FactorIndex(ix));
 
             // Each interrobot factor is connected to an internal variable
@@ -760,13 +881,22 @@ impl FactorGraph {
     }
 
     pub fn internal_variable_iteration(&mut self) {
+        let criterion = self.convergence.criterion;
+        let mut largest_belief_change: Float = 0.0;
+
         for &ix in &self.variable_indices {
             let node = &mut self.graph[ix];
             let variable = node.variable_mut();
             let variable_index = VariableIndex(ix);
             let variable_id = VariableId::new(self.id, variable_index);
+            let previous_belief =
+                (criterion != ConvergenceCriterion::None).then(|| variable.belief.clone());
             // TODO: do internal only
             let factor_messages = variable.update_belief_and_create_factor_responses();
+            if let Some(previous_belief) = previous_belief {
+                largest_belief_change = largest_belief_change
+                    .max(variable.belief.change_from(&previous_belief, criterion));
+            }
 
             for (factor_id, message) in factor_messages {
                 let in_internal_graph = factor_id.factorgraph_id == self.id;
@@ -787,12 +917,26 @@ impl FactorGraph {
         }
 
         self.iteration_count.variable += 1;
+        self.iteration_statistics.internal += 1;
+        self.iteration_statistics.latest_timestep += 1;
+
+        let tolerance = Float::from(self.convergence.tolerance.get());
+        if criterion != ConvergenceCriterion::None
+            && !self.converged
+            && largest_belief_change < tolerance
+        {
+            self.converged = true;
+            if !self.converged_in_timestep {
+                self.converged_in_timestep = true;
+                self.iteration_statistics.converged_timesteps += 1;
+            }
+        }
     }
 
     // TODO(kpbaks): does this method even make sense?
     #[must_use]
-    pub fn external_variable_iteration(&mut self) -> Vec<VariableToFactorMessage> {
-        let mut messages_to_external_factors: Vec<VariableToFactorMessage> = Vec::new();
+    pub fn external_variable_iteration(&mut self) -> Vec<VariableToFactorMessage<Id>> {
+        let mut messages_to_external_factors: Vec<VariableToFactorMessage<Id>> = Vec::new();
         for &ix in &self.variable_indices {
             let node = &mut self.graph[ix];
             let variable = node.variable_mut();
@@ -828,8 +972,8 @@ impl FactorGraph {
     /// Aggregate and marginalise over all adjacent variables, and send.
     /// Aggregation: product of all incoming messages
     #[must_use]
-    pub fn factor_iteration(&mut self) -> Vec<FactorToVariableMessage> {
-        let mut messages_to_external_variables: Vec<FactorToVariableMessage> = Vec::new();
+    pub fn factor_iteration(&mut self) -> Vec<FactorToVariableMessage<Id>> {
+        let mut messages_to_external_variables: Vec<FactorToVariableMessage<Id>> = Vec::new();
 
         for ix in &self.factor_indices {
             let node = &mut self.graph[*ix];
@@ -838,7 +982,7 @@ impl FactorGraph {
                  graph",
             );
 
-            let variable_messages = factor.update();
+            let variable_messages = factor.update(self.damping);
             let factor_id = FactorId::new(self.id, FactorIndex(*ix));
 
             for (variable_id, message) in variable_messages {
@@ -865,6 +1009,40 @@ impl FactorGraph {
         messages_to_external_variables
     }
 
+    /// Receive a message sent from another factorgraph, by either an
+    /// interrobot factor to a variable in this factorgraph, or by a variable
+    /// to an interrobot factor in this factorgraph.
+    ///
+    /// Messages to variables or factors that no longer exist, or that are no
+    /// longer connected to the sender, are discarded.
+    /// A delivered message changes the beliefs, so the beliefs are no longer
+    /// considered converged.
+    /// Returns whether the message was delivered.
+    pub fn receive_external_message(&mut self, message: ExternalMessage<Id>) -> bool {
+        match message {
+            ExternalMessage::ToVariable(message) => {
+                let Some(variable) = self.get_variable_mut(message.to.variable_index) else {
+                    return false;
+                };
+                if !variable.inbox.contains_key(&message.from) {
+                    return false;
+                }
+                variable.receive_message_from(message.from, message.message);
+            }
+            ExternalMessage::ToFactor(message) => {
+                let Some(factor) = self.get_factor_mut(message.to.factor_index) else {
+                    return false;
+                };
+                if !factor.inbox.contains_key(&message.from) {
+                    return false;
+                }
+                factor.receive_message_from(message.from, message.message);
+            }
+        }
+        self.converged = false;
+        true
+    }
+
     // TODO:
     // pub fn receive_message(&mut self, from: NodeId, message: Message) {
     //     // self.messages_sent += 1;
@@ -934,13 +1112,21 @@ impl NodeCount {
 #[derive(Debug, Clone, Copy, Default)]
 pub struct FactorCount {
     /// Number of `ObstacleFactor`s
-    pub obstacle:   usize,
+    pub obstacle: usize,
     /// Number of `InterRobotFactor`s
     pub interrobot: usize,
     /// Number of `DynamicFactor`s
-    pub dynamic:    usize,
+    pub dynamic: usize,
     /// Number of `TrackingFactor`s
-    pub tracking:   usize,
+    pub tracking: usize,
+    /// Number of `DynamicObstacleFactor`s
+    pub dynamic_obstacle: usize,
+    /// Number of `VelocityFactor`s
+    pub velocity: usize,
+    /// Number of `AccelerationFactor`s
+    pub acceleration: usize,
+    /// Number of user defined factors, of every kind
+    pub user: usize,
 }
 
 /// Iterator over the factors in the factorgraph.
@@ -950,14 +1136,14 @@ pub struct FactorCount {
 /// Created with [`.factors()`][1]
 ///
 /// [1]: struct.FactorGraph.html#method.factors
-pub struct Factors<'fg> {
-    graph: &'fg Graph,
+pub struct Factors<'fg, Id> {
+    graph: &'fg Graph<Id>,
     factor_indices: std::slice::Iter<'fg, NodeIndex>,
 }
 
-impl<'fg> Factors<'fg> {
+impl<'fg, Id: GraphId> Factors<'fg, Id> {
     #[must_use]
-    fn new(graph: &'fg Graph, factor_indices: &'fg [NodeIndex]) -> Self {
+    fn new(graph: &'fg Graph<Id>, factor_indices: &'fg [NodeIndex]) -> Self {
         Self {
             graph,
             factor_indices: factor_indices.iter(),
@@ -965,17 +1151,17 @@ impl<'fg> Factors<'fg> {
     }
 }
 
-impl FactorGraph {
+impl<Id: GraphId> FactorGraph<Id> {
     /// Returns an iterator over the factors in the factorgraph.
     #[inline]
     #[must_use]
-    pub fn factors(&self) -> Factors<'_> {
+    pub fn factors(&self) -> Factors<'_, Id> {
         Factors::new(&self.graph, &self.factor_indices)
     }
 }
 
-impl<'fg> Iterator for Factors<'fg> {
-    type Item = (NodeIndex, &'fg FactorNode);
+impl<'fg, Id: GraphId> Iterator for Factors<'fg, Id> {
+    type Item = (NodeIndex, &'fg FactorNode<Id>);
 
     fn next(&mut self) -> Option<Self::Item> {
         let &index = self.factor_indices.next()?;
@@ -1015,7 +1201,7 @@ impl<'fg> Iterator for Factors<'fg> {
 //     #[must_use]
 //     // pub fn internal_factors<'graph>(&'graph self) ->
 // InternalFactors<'graph> {     pub fn internal_factors(&self) ->
-// InternalFactors<'_> {         let iter = self
+// InternalFactors<'_, Id> {         let iter = self
 //             .dynamic_factor_indices
 //             .iter()
 //             .chain(self.obstacle_factor_indices.iter());
@@ -1031,13 +1217,13 @@ impl<'fg> Iterator for Factors<'fg> {
 /// Created with [`.variables()`][1]
 ///
 /// [1]: struct.FactorGraph.html#method.variables
-pub struct Variables<'fg> {
-    graph: &'fg Graph,
+pub struct Variables<'fg, Id> {
+    graph: &'fg Graph<Id>,
     variable_indices: std::slice::Iter<'fg, NodeIndex>,
 }
 
-impl<'fg> Variables<'fg> {
-    fn new(graph: &'fg Graph, variable_indices: &'fg [NodeIndex]) -> Self {
+impl<'fg, Id: GraphId> Variables<'fg, Id> {
+    fn new(graph: &'fg Graph<Id>, variable_indices: &'fg [NodeIndex]) -> Self {
         Self {
             graph,
             variable_indices: variable_indices.iter(),
@@ -1045,8 +1231,8 @@ impl<'fg> Variables<'fg> {
     }
 }
 
-impl<'fg> Iterator for Variables<'fg> {
-    type Item = (VariableIndex, &'fg VariableNode);
+impl<'fg, Id: GraphId> Iterator for Variables<'fg, Id> {
+    type Item = (VariableIndex, &'fg VariableNode<Id>);
 
     fn next(&mut self) -> Option<Self::Item> {
         let &index = self.variable_indices.next()?;
@@ -1056,11 +1242,11 @@ impl<'fg> Iterator for Variables<'fg> {
     }
 }
 
-impl FactorGraph {
+impl<Id: GraphId> FactorGraph<Id> {
     /// Returns an iterator over the variables in the factorgraph.
     #[inline]
     #[must_use]
-    pub fn variables(&self) -> Variables<'_> {
+    pub fn variables(&self) -> Variables<'_, Id> {
         Variables::new(&self.graph, &self.variable_indices)
     }
 }
@@ -1070,13 +1256,13 @@ impl FactorGraph {
 /// Iterator element type is `(FactorIndex, &'a InterRobotFactor)`.
 ///
 /// Created with [`.inter_robot_factors()`][1]
-pub struct InterRobotFactors<'fg> {
-    graph: &'fg Graph,
+pub struct InterRobotFactors<'fg, Id> {
+    graph: &'fg Graph<Id>,
     factor_indices: std::slice::Iter<'fg, NodeIndex>,
 }
 
-impl<'fg> InterRobotFactors<'fg> {
-    fn new(graph: &'fg Graph, factor_indices: &'fg [NodeIndex]) -> Self {
+impl<'fg, Id: GraphId> InterRobotFactors<'fg, Id> {
+    fn new(graph: &'fg Graph<Id>, factor_indices: &'fg [NodeIndex]) -> Self {
         Self {
             graph,
             factor_indices: factor_indices.iter(),
@@ -1084,8 +1270,8 @@ impl<'fg> InterRobotFactors<'fg> {
     }
 }
 
-impl<'fg> Iterator for InterRobotFactors<'fg> {
-    type Item = (NodeIndex, &'fg InterRobotFactor);
+impl<'fg, Id: GraphId> Iterator for InterRobotFactors<'fg, Id> {
+    type Item = (NodeIndex, &'fg InterRobotFactor<Id>);
 
     fn next(&mut self) -> Option<Self::Item> {
         let &index = self.factor_indices.next()?;
@@ -1096,18 +1282,18 @@ impl<'fg> Iterator for InterRobotFactors<'fg> {
     }
 }
 
-impl FactorGraph {
+impl<Id: GraphId> FactorGraph<Id> {
     /// Returns an iterator over the interrobot factors in the factorgraph.
     #[inline]
     #[must_use]
-    pub fn inter_robot_factors(&self) -> InterRobotFactors<'_> {
+    pub fn inter_robot_factors(&self) -> InterRobotFactors<'_, Id> {
         InterRobotFactors::new(&self.graph, &self.interrobot_factor_indices)
     }
 }
 
 // pub struct VariableAndTheirInterRobotFactors<'fg,'edges> where 'edges: 'fg {
-pub struct VariableAndTheirInterRobotFactors<'fg> {
-    graph: &'fg Graph,
+pub struct VariableAndTheirInterRobotFactors<'fg, Id> {
+    graph: &'fg Graph<Id>,
     // iter: std::iter::Zip<std::slice::Iter<'fg, NodeIndex>, std::slice::Iter<'fg, NodeIndex>>,
     // iter: impl Iterator<Item = EdgeReference<'fg, (), IndexSize>>,
     iter:  Box<dyn Iterator<Item = EdgeReference<'fg, (), IndexSize>> + 'fg>,
@@ -1118,8 +1304,8 @@ pub struct VariableAndTheirInterRobotFactors<'fg> {
 
 // impl <'fg, 'edges> VariableAndTheirInterRobotFactors<'fg, 'edges> where
 // 'edges: 'fg {
-impl<'fg> VariableAndTheirInterRobotFactors<'fg> {
-    fn new(graph: &'fg Graph, variable_indices: &'fg [NodeIndex]) -> Self {
+impl<'fg, Id: GraphId> VariableAndTheirInterRobotFactors<'fg, Id> {
+    fn new(graph: &'fg Graph<Id>, variable_indices: &'fg [NodeIndex]) -> Self {
         let iter = variable_indices
             .iter()
             .flat_map(|var_ix| graph.edges(*var_ix));
@@ -1137,8 +1323,8 @@ impl<'fg> VariableAndTheirInterRobotFactors<'fg> {
 
 // impl<'fg, 'edges> Iterator for VariableAndTheirInterRobotFactors<'fg, 'edges>
 // where 'edges: 'fg {
-impl<'fg> Iterator for VariableAndTheirInterRobotFactors<'fg> {
-    type Item = (&'fg VariableNode, &'fg InterRobotFactor);
+impl<'fg, Id: GraphId> Iterator for VariableAndTheirInterRobotFactors<'fg, Id> {
+    type Item = (&'fg VariableNode<Id>, &'fg InterRobotFactor<Id>);
 
     fn next(&mut self) -> Option<Self::Item> {
         // A variable can be connected to 0 or more interrobot factors
@@ -1183,28 +1369,28 @@ impl<'fg> Iterator for VariableAndTheirInterRobotFactors<'fg> {
     }
 }
 
-impl FactorGraph {
+impl<Id: GraphId> FactorGraph<Id> {
     /// Returns an iterator over the variable and their connected interrobot
     /// factors in the factorgraph
     #[inline]
     #[must_use]
-    pub fn variable_and_inter_robot_factors(&self) -> VariableAndTheirInterRobotFactors<'_> {
+    pub fn variable_and_inter_robot_factors(&self) -> VariableAndTheirInterRobotFactors<'_, Id> {
         VariableAndTheirInterRobotFactors::new(&self.graph, &self.variable_indices)
     }
 }
 
 /// Iterator over the variable and their connected obstacle factors in the
 /// factorgraph
-pub struct VariableAndTheirObstacleFactors<'fg> {
-    graph: &'fg Graph,
+pub struct VariableAndTheirObstacleFactors<'fg, Id> {
+    graph: &'fg Graph<Id>,
     // variable_indices: std::slice::Iter<'a, NodeIndex>,
     // obstacle_factor_indices: std::slice::Iter<'a, NodeIndex>,
     pairs: std::iter::Zip<std::slice::Iter<'fg, NodeIndex>, std::slice::Iter<'fg, NodeIndex>>,
 }
 
-impl<'fg> VariableAndTheirObstacleFactors<'fg> {
+impl<'fg, Id: GraphId> VariableAndTheirObstacleFactors<'fg, Id> {
     fn new(
-        graph: &'fg Graph,
+        graph: &'fg Graph<Id>,
         variable_indices: &'fg [NodeIndex],
         obstacle_factor_indices: &'fg [NodeIndex],
     ) -> Self {
@@ -1215,8 +1401,8 @@ impl<'fg> VariableAndTheirObstacleFactors<'fg> {
     }
 }
 
-impl<'fg> Iterator for VariableAndTheirObstacleFactors<'fg> {
-    type Item = (&'fg VariableNode, &'fg ObstacleFactor);
+impl<'fg, Id: GraphId> Iterator for VariableAndTheirObstacleFactors<'fg, Id> {
+    type Item = (&'fg VariableNode<Id>, &'fg ObstacleFactor);
 
     fn next(&mut self) -> Option<Self::Item> {
         let (&variable_index, &factor_index) = self.pairs.next()?;
@@ -1236,16 +1422,16 @@ impl<'fg> Iterator for VariableAndTheirObstacleFactors<'fg> {
 
 /// Iterator over the variable and their connected tracking factors in the
 /// factorgraph
-pub struct VariableAndTheirTrackingFactors<'fg> {
-    graph: &'fg Graph,
+pub struct VariableAndTheirTrackingFactors<'fg, Id> {
+    graph: &'fg Graph<Id>,
     // variable_indices: std::slice::Iter<'a, NodeIndex>,
     // tracking_factor_indices: std::slice::Iter<'a, NodeIndex>,
     pairs: std::iter::Zip<std::slice::Iter<'fg, NodeIndex>, std::slice::Iter<'fg, NodeIndex>>,
 }
 
-impl<'fg> VariableAndTheirTrackingFactors<'fg> {
+impl<'fg, Id: GraphId> VariableAndTheirTrackingFactors<'fg, Id> {
     fn new(
-        graph: &'fg Graph,
+        graph: &'fg Graph<Id>,
         variable_indices: &'fg [NodeIndex],
         tracking_factor_indices: &'fg [NodeIndex],
     ) -> Self {
@@ -1256,8 +1442,8 @@ impl<'fg> VariableAndTheirTrackingFactors<'fg> {
     }
 }
 
-impl<'fg> Iterator for VariableAndTheirTrackingFactors<'fg> {
-    type Item = (&'fg VariableNode, &'fg TrackingFactor);
+impl<'fg, Id: GraphId> Iterator for VariableAndTheirTrackingFactors<'fg, Id> {
+    type Item = (&'fg VariableNode<Id>, &'fg TrackingFactor);
 
     fn next(&mut self) -> Option<Self::Item> {
         let (&variable_index, &factor_index) = self.pairs.next()?;
@@ -1275,12 +1461,12 @@ impl<'fg> Iterator for VariableAndTheirTrackingFactors<'fg> {
     }
 }
 
-impl FactorGraph {
+impl<Id: GraphId> FactorGraph<Id> {
     /// Returns an iterator over the variable and their obstacle factors in the
     /// factorgraph.
     #[inline]
     #[must_use]
-    pub fn variable_and_their_obstacle_factors(&self) -> VariableAndTheirObstacleFactors<'_> {
+    pub fn variable_and_their_obstacle_factors(&self) -> VariableAndTheirObstacleFactors<'_, Id> {
         VariableAndTheirObstacleFactors::new(
             &self.graph,
             &self.variable_indices[1..self.variable_indices.len() - 1],
@@ -1292,7 +1478,7 @@ impl FactorGraph {
     /// factorgraph.
     #[inline]
     #[must_use]
-    pub fn variable_and_their_tracking_factors(&self) -> VariableAndTheirTrackingFactors<'_> {
+    pub fn variable_and_their_tracking_factors(&self) -> VariableAndTheirTrackingFactors<'_, Id> {
         VariableAndTheirTrackingFactors::new(
             &self.graph,
             &self.variable_indices[1..],
@@ -1322,12 +1508,12 @@ impl FactorGraph {
 // }
 
 /// Iterator over the neighbours of a variable in the factorgraph
-pub struct VariableNeighboursDyn<'fg> {
-    graph:      &'fg Graph,
+pub struct VariableNeighboursDyn<'fg, Id> {
+    graph:      &'fg Graph<Id>,
     neighbours: petgraph::stable_graph::Neighbors<'fg, (), IndexSize>,
 }
 
-impl<'fg> Iterator for VariableNeighboursDyn<'fg> {
+impl<'fg, Id: GraphId> Iterator for VariableNeighboursDyn<'fg, Id> {
     type Item = &'fg dyn Factor;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -1340,13 +1526,13 @@ impl<'fg> Iterator for VariableNeighboursDyn<'fg> {
     }
 }
 
-impl FactorGraph {
+impl<Id: GraphId> FactorGraph<Id> {
     /// Returns an iterator over the factor neighbours of a variable
     /// If the variable does not exist in the factorgraph, returns None
     pub fn variable_neighbours_dyn(
         &self,
         variable_index: VariableIndex,
-    ) -> Option<VariableNeighboursDyn<'_>> {
+    ) -> Option<VariableNeighboursDyn<'_, Id>> {
         let node_ix = variable_index.0;
         self.graph.node_weight(node_ix)?;
 
@@ -1359,13 +1545,13 @@ impl FactorGraph {
     }
 }
 
-pub struct VariableNeighbours<'fg> {
-    graph:      &'fg Graph,
+pub struct VariableNeighbours<'fg, Id> {
+    graph:      &'fg Graph<Id>,
     neighbours: petgraph::stable_graph::Neighbors<'fg, (), IndexSize>,
 }
 
-impl<'fg> Iterator for VariableNeighbours<'fg> {
-    type Item = &'fg FactorNode;
+impl<'fg, Id: GraphId> Iterator for VariableNeighbours<'fg, Id> {
+    type Item = &'fg FactorNode<Id>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.neighbours.next().map(|index| {
@@ -1382,13 +1568,13 @@ impl<'fg> Iterator for VariableNeighbours<'fg> {
 //    }
 //}
 
-impl FactorGraph {
+impl<Id: GraphId> FactorGraph<Id> {
     /// Returns an iterator over the factor neighbours of a variable
     /// If the variable does not exist in the factorgraph, returns None
     pub fn variable_neighbours(
         &self,
         variable_index: VariableIndex,
-    ) -> Option<VariableNeighbours<'_>> {
+    ) -> Option<VariableNeighbours<'_, Id>> {
         let node_ix = variable_index.0;
         self.graph.node_weight(node_ix)?;
 
@@ -1402,13 +1588,13 @@ impl FactorGraph {
 }
 
 /// Iterator over the neighbours of a factor in the factorgraph
-pub struct FactorNeighbours<'fg> {
-    graph:      &'fg Graph,
+pub struct FactorNeighbours<'fg, Id> {
+    graph:      &'fg Graph<Id>,
     neighbours: petgraph::stable_graph::Neighbors<'fg, (), IndexSize>,
 }
 
-impl<'fg> Iterator for FactorNeighbours<'fg> {
-    type Item = &'fg VariableNode;
+impl<'fg, Id: GraphId> Iterator for FactorNeighbours<'fg, Id> {
+    type Item = &'fg VariableNode<Id>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.neighbours
@@ -1417,10 +1603,10 @@ impl<'fg> Iterator for FactorNeighbours<'fg> {
     }
 }
 
-impl FactorGraph {
+impl<Id: GraphId> FactorGraph<Id> {
     /// Returns an iterator over the variable neighbours of a factor
     /// If the factor does not exist in the factorgraph, returns None
-    pub fn factor_neighbours(&self, factor_index: FactorIndex) -> Option<FactorNeighbours<'_>> {
+    pub fn factor_neighbours(&self, factor_index: FactorIndex) -> Option<FactorNeighbours<'_, Id>> {
         let node_ix = factor_index.0;
         self.graph.node_weight(node_ix)?;
 
@@ -1434,12 +1620,12 @@ impl FactorGraph {
 }
 
 /// Iterator over the factors in the factorgraph
-pub struct FactorsDyn<'fg> {
-    graph: &'fg Graph,
+pub struct FactorsDyn<'fg, Id> {
+    graph: &'fg Graph<Id>,
     iter:  std::slice::Iter<'fg, NodeIndex>,
 }
 
-impl<'fg> Iterator for FactorsDyn<'fg> {
+impl<'fg, Id: GraphId> Iterator for FactorsDyn<'fg, Id> {
     type Item = &'fg dyn Factor;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -1452,9 +1638,9 @@ impl<'fg> Iterator for FactorsDyn<'fg> {
     }
 }
 
-impl FactorGraph {
+impl<Id: GraphId> FactorGraph<Id> {
     /// Returns an iterator over the factors in the factorgraph
-    pub fn factors_dyn(&self) -> FactorsDyn<'_> {
+    pub fn factors_dyn(&self) -> FactorsDyn<'_, Id> {
         FactorsDyn {
             graph: &self.graph,
             iter:  self.factor_indices.iter(),
@@ -1462,7 +1648,7 @@ impl FactorGraph {
     }
 }
 
-impl FactorGraph {
+impl<Id: GraphId> FactorGraph<Id> {
     /// Modify the tracking factors in the factorgraph
     pub fn modify_tracking_factors(&mut self, mut f: impl FnMut(&mut TrackingFactor)) {
         for ix in &self.tracking_factor_indices {
@@ -1474,12 +1660,171 @@ impl FactorGraph {
             f(inner);
         }
     }
+
+    /// Replace the observed dynamic obstacles of every dynamic obstacle factor
+    /// in the factorgraph
+    pub fn update_dynamic_obstacles(&mut self, obstacles: &std::sync::Arc<[ObstacleSnapshot]>) {
+        for ix in &self.dynamic_obstacle_factor_indices {
+            let node = &mut self.graph[*ix];
+            let factor = node.factor_mut();
+            let FactorKind::DynamicObstacle(ref mut inner) = factor.kind else {
+                panic!("Expected a dynamic obstacle factor");
+            };
+            inner.update_obstacles(std::sync::Arc::clone(obstacles));
+        }
+    }
+}
+
+impl<Id: GraphId> FactorGraph<Id> {
+    /// Returns the variables a factor is connected to, ordered from the current
+    /// state to the horizon state
+    fn variables_of_factor(&self, ix: NodeIndex) -> Vec<&VariableNode<Id>> {
+        let mut neighbours = self.graph.neighbors(ix).collect::<Vec<_>>();
+        neighbours.sort_unstable();
+        neighbours
+            .into_iter()
+            .map(|index| {
+                self.graph[index]
+                    .as_variable()
+                    .expect("a factor only has variables as neighbours")
+            })
+            .collect()
+    }
+
+    /// Returns an iterator over the velocity limit factors in the factorgraph,
+    /// and the variable each of them is connected to
+    pub fn variable_and_their_velocity_factors(
+        &self,
+    ) -> impl Iterator<Item = (&VariableNode<Id>, &VelocityFactor)> {
+        self.velocity_factor_indices.iter().filter_map(|&ix| {
+            let FactorKind::Velocity(ref inner) = self.graph[ix].factor().kind else {
+                panic!("Expected a velocity factor");
+            };
+            let [variable] = self.variables_of_factor(ix)[..] else {
+                return None;
+            };
+            Some((variable, inner))
+        })
+    }
+
+    /// Returns an iterator over the acceleration limit factors in the
+    /// factorgraph, and the two consecutive variables each of them is connected
+    /// to
+    pub fn variables_and_their_acceleration_factors(
+        &self,
+    ) -> impl Iterator<Item = ([&VariableNode<Id>; 2], &AccelerationFactor)> {
+        self.acceleration_factor_indices.iter().filter_map(|&ix| {
+            let FactorKind::Acceleration(ref inner) = self.graph[ix].factor().kind else {
+                panic!("Expected an acceleration factor");
+            };
+            let [from, to] = self.variables_of_factor(ix)[..] else {
+                return None;
+            };
+            Some(([from, to], inner))
+        })
+    }
+
+    /// Modify the velocity limit factors in the factorgraph
+    pub fn modify_velocity_factors(&mut self, mut f: impl FnMut(&mut VelocityFactor)) {
+        for ix in &self.velocity_factor_indices {
+            let factor = self.graph[*ix].factor_mut();
+            let FactorKind::Velocity(ref mut inner) = factor.kind else {
+                panic!("Expected a velocity factor");
+            };
+            f(inner);
+        }
+    }
+
+    /// Modify the acceleration limit factors in the factorgraph
+    pub fn modify_acceleration_factors(&mut self, mut f: impl FnMut(&mut AccelerationFactor)) {
+        for ix in &self.acceleration_factor_indices {
+            let factor = self.graph[*ix].factor_mut();
+            let FactorKind::Acceleration(ref mut inner) = factor.kind else {
+                panic!("Expected an acceleration factor");
+            };
+            f(inner);
+        }
+    }
+}
+
+impl<Id: GraphId> FactorGraph<Id> {
+    /// Add a user defined factor for every group of variables given by
+    /// `attachment`, and connect it to the variables of the group.
+    /// `create` is called with the positions of the variables of each group,
+    /// ordered from the current state to the horizon state, and returns the
+    /// factor to add, or `None` to not attach a factor to the group.
+    /// Returns the indices of the added factors.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `create` returns a factor with a different number of
+    /// neighbours than `attachment`
+    pub fn attach_user_factors(
+        &mut self,
+        attachment: Attachment,
+        mut create: impl FnMut(std::ops::Range<usize>) -> Option<FactorNode<Id>>,
+    ) -> Vec<FactorIndex> {
+        let mut attached = Vec::new();
+        for variables in attachment.groups(self.variable_indices.len()) {
+            let Some(factor) = create(variables.clone()) else {
+                continue;
+            };
+            assert_eq!(
+                factor.kind.neighbours(),
+                attachment.neighbours(),
+                "the {} factor has {} neighbours, but is attached to {} variables",
+                factor.kind.name(),
+                factor.kind.neighbours(),
+                attachment.neighbours()
+            );
+
+            let factor_index = self.add_factor(factor);
+            let factor_id = FactorId::new(self.id, factor_index);
+            for nth in variables {
+                let variable_index = VariableIndex(self.variable_indices[nth]);
+                let _ = self.add_internal_edge(VariableId::new(self.id, variable_index), factor_id);
+            }
+            attached.push(factor_index);
+        }
+        attached
+    }
+
+    /// Returns an iterator over the user defined factors in the factorgraph,
+    /// of every kind
+    pub fn user_factors(&self) -> impl Iterator<Item = (FactorIndex, &dyn UserFactor)> {
+        self.user_factor_indices.iter().map(|&ix| {
+            let FactorKind::User(ref inner) = self.graph[ix].factor().kind else {
+                panic!("Expected a user defined factor");
+            };
+            (FactorIndex(ix), &**inner)
+        })
+    }
+
+    /// Returns an iterator over the user defined factors of type `T` in the
+    /// factorgraph
+    pub fn user_factors_of<T: UserFactor>(&self) -> impl Iterator<Item = (FactorIndex, &T)> {
+        self.user_factors()
+            .filter_map(|(index, factor)| factor.downcast_ref::<T>().map(|factor| (index, factor)))
+    }
+
+    /// Modify the user defined factors of type `T` in the factorgraph
+    pub fn modify_user_factors<T: UserFactor>(&mut self, mut f: impl FnMut(&mut T)) {
+        for ix in &self.user_factor_indices {
+            let factor = self.graph[*ix].factor_mut();
+            let FactorKind::User(ref mut inner) = factor.kind else {
+                panic!("Expected a user defined factor");
+            };
+            if let Some(inner) = inner.downcast_mut::<T>() {
+                f(inner);
+            }
+        }
+    }
 }
 
 use super::graphviz;
 
-impl graphviz::ExportGraph for FactorGraph {
-    fn export_graph(&self) -> (Vec<super::graphviz::Node>, Vec<super::graphviz::Edge>) {
+impl<Id: GraphId> graphviz::ExportGraph<Id> for FactorGraph<Id> {
+    fn export_graph(&self) -> (Vec<graphviz::Node<Id>>, Vec<graphviz::Edge>) {
         let nodes = self
             .graph
             .node_indices()
@@ -1498,10 +1843,26 @@ impl graphviz::ExportGraph for FactorGraph {
                                 }
                             }
                             FactorKind::Tracking(_) => graphviz::NodeKind::TrackingFactor,
+                            FactorKind::DynamicObstacle(_) => {
+                                graphviz::NodeKind::DynamicObstacleFactor
+                            }
+                            FactorKind::Velocity(_) => graphviz::NodeKind::VelocityFactor,
+                            FactorKind::Acceleration(_) => graphviz::NodeKind::AccelerationFactor,
+                            FactorKind::User(ref inner) => graphviz::NodeKind::UserFactor {
+                                name:  inner.name(),
+                                color: inner.color(),
+                            },
                         },
                         NodeKind::Variable(variable) => {
                             let [x, y] = variable.estimated_position();
-                            graphviz::NodeKind::Variable { x, y }
+                            graphviz::NodeKind::Variable {
+                                x,
+                                y,
+                                belief: graphviz::Belief::new(
+                                    &variable.belief.mean,
+                                    &variable.belief.covariance_matrix,
+                                ),
+                            }
                         }
                     },
                 }
@@ -1512,12 +1873,25 @@ impl graphviz::ExportGraph for FactorGraph {
             .graph
             .edge_indices()
             .filter_map(|edge_index| {
-                self.graph
-                    .edge_endpoints(edge_index)
-                    .map(|(from, to)| graphviz::Edge {
-                        from: from.index(),
-                        to:   to.index(),
-                    })
+                self.graph.edge_endpoints(edge_index).map(|(from, to)| {
+                    // Internal edges go from the variable to the factor
+                    let to_variable = self.graph[from].as_variable().and_then(|variable| {
+                        variable.inbox.get(&FactorId::new(self.id, FactorIndex(to)))
+                    });
+                    let to_factor = self.graph[to].as_factor().and_then(|factor| {
+                        factor
+                            .inbox
+                            .get(&VariableId::new(self.id, VariableIndex(from)))
+                    });
+                    graphviz::Edge {
+                        from:     from.index(),
+                        to:       to.index(),
+                        messages: graphviz::MessageNorms {
+                            to_variable: to_variable.and_then(graphviz::MessageNorm::of),
+                            to_factor:   to_factor.and_then(graphviz::MessageNorm::of),
+                        },
+                    }
+                })
             })
             .collect::<Vec<_>>();
 
@@ -1525,22 +1899,18 @@ impl graphviz::ExportGraph for FactorGraph {
     }
 }
 
-impl FactorGraph {
-    pub fn change_factor_enabled(&mut self, settings: gbp_config::FactorsEnabledSection) {
+impl<Id: GraphId> FactorGraph<Id> {
+    /// Enable or disable every factor in the factorgraph, depending on its kind
+    pub fn set_factors_enabled(&mut self, mut enabled: impl FnMut(&FactorKind<Id>) -> bool) {
         for &ix in self.factor_indices.iter() {
             let factor = self.graph[ix].factor_mut();
-            factor.enabled = match factor.kind {
-                FactorKind::Dynamic(_) => settings.dynamic,
-                FactorKind::Obstacle(_) => settings.obstacle,
-                FactorKind::InterRobot(_) => settings.interrobot,
-                FactorKind::Tracking(_) => settings.tracking,
-            };
+            factor.enabled = enabled(&factor.kind);
         }
     }
 
     pub fn reset_variables(
         &mut self,
-        means: &[[f64; 4]],
+        means: &[Vector<Float>],
         first_last_sigma: f64,
         inbetween_sigma: f64,
     ) {
@@ -1548,14 +1918,14 @@ impl FactorGraph {
 
         for (i, ix) in self.variable_indices.iter().enumerate() {
             let variable = self.graph[*ix].as_variable_mut().unwrap();
-            let mean = means[i];
+            let mean = &means[i];
             let sigma = if i == 0 || i == means.len() - 1 {
                 first_last_sigma
             } else {
                 inbetween_sigma
             };
 
-            variable.reset(&mean, sigma);
+            variable.reset(mean, sigma);
         }
 
         for ix in self.factor_indices.iter() {
@@ -0,0 +1,204 @@
+//! Render a [`Graph`] in the DOT language of graphviz
+
+use std::fmt::Write;
+
+use super::{EdgeKind, Graph, GraphEdge, GraphNode, MessageNorms};
+
+/// Attributes of an edge in the DOT output
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdgeStyle {
+    /// Graphviz edge style, e.g. `"solid"` or `"dashed"`
+    pub style: String,
+    /// Preferred length of the edge, used by the `neato` layout
+    pub len:   f32,
+    /// Color of the edge
+    pub color: String,
+}
+
+/// How to draw the edges between interrobot factors and the variables of
+/// other robots
+#[derive(Debug, Clone, PartialEq)]
+pub struct DotStyle {
+    /// The radio antenna of the robot with the interrobot factor is active
+    pub active:   EdgeStyle,
+    /// The radio antenna of the robot with the interrobot factor is inactive
+    pub inactive: EdgeStyle,
+}
+
+impl Default for DotStyle {
+    fn default() -> Self {
+        Self {
+            active:   EdgeStyle {
+                style: "solid".to_string(),
+                len:   8.0,
+                color: "green".to_string(),
+            },
+            inactive: EdgeStyle {
+                style: "dashed".to_string(),
+                len:   4.0,
+                color: "green".to_string(),
+            },
+        }
+    }
+}
+
+const CLUSTER_MARGIN: usize = 16;
+
+impl Graph {
+    /// Render the graph in the DOT language, to be laid out with `neato`
+    pub fn to_dot(&self, style: &DotStyle) -> String {
+        let mut buf = String::with_capacity(4 * 1024); // 4 kB
+        let mut line = |line: &str| {
+            buf.push_str(line);
+            buf.push('\n');
+        };
+        line("graph {");
+        line("  dpi=96;");
+        line(r#"  label="factorgraph""#);
+        line("  node [style=filled];");
+        line("  layout=neato;");
+
+        for robot in &self.robots {
+            line(&format!(r#"  subgraph "{robot}" {{"#));
+            line(&format!("  margin={CLUSTER_MARGIN}"));
+            line(&format!(r#"  label="{robot}""#));
+            for node in self.nodes.iter().filter(|node| &node.robot == robot) {
+                line(&node_statement(node));
+            }
+            line("}");
+            line("");
+        }
+
+        for edge in &self.edges {
+            line(&edge_statement(edge, style));
+        }
+
+        line("}"); // closing '}' for starting "graph {"
+        buf
+    }
+}
+
+fn node_statement(node: &GraphNode) -> String {
+    let mut label = node.label.clone();
+    let mut tooltip = String::new();
+    if let Some(ref belief) = node.belief {
+        if let [x, y, ..] = belief.mean[..] {
+            let _ = write!(label, r"\nμ=({x:.2}, {y:.2})");
+        }
+        let _ = write!(
+            tooltip,
+            "mean: {:?}\ncovariance: {:?}",
+            belief.mean, belief.covariance
+        );
+    }
+
+    let mut statement = format!(
+        r#""{}" [label="{}", fillcolor="{}", shape={}, width="{}""#,
+        node.id, label, node.color, node.shape, node.width
+    );
+    if !tooltip.is_empty() {
+        let _ = write!(statement, r#", tooltip="{}""#, escape(&tooltip));
+    }
+    statement.push(']');
+    statement
+}
+
+fn edge_statement(edge: &GraphEdge, style: &DotStyle) -> String {
+    let mut attributes = Vec::new();
+    if let EdgeKind::InterRobot { active } = edge.kind {
+        let style = if active {
+            &style.active
+        } else {
+            &style.inactive
+        };
+        attributes.push(format!("len={}", style.len));
+        attributes.push(format!("style={}", style.style));
+        attributes.push(format!(r#"color="{}""#, style.color));
+        attributes.push("penwidth=3.0".to_string());
+    }
+    if let Some(messages) = edge.messages {
+        attributes.push(format!(r#"label="{}""#, describe(messages)));
+    }
+
+    let mut statement = format!(r#""{}" -- "{}""#, edge.from, edge.to);
+    if !attributes.is_empty() {
+        let _ = write!(statement, " [{}]", attributes.join(", "));
+    }
+    statement
+}
+
+/// Describe the norms of the messages in both directions of an edge, e.g.
+/// `"v: |η|=0.50 |Λ|=2.00\nf: -"`
+fn describe(messages: MessageNorms) -> String {
+    let describe = |norm: Option<super::MessageNorm>| {
+        norm.map_or_else(
+            || "-".to_string(),
+            |norm| format!("|η|={:.2} |Λ|={:.2}", norm.information, norm.precision),
+        )
+    };
+    format!(
+        r"v: {}\nf: {}",
+        describe(messages.to_variable),
+        describe(messages.to_factor)
+    )
+}
+
+/// Escape a string to be used inside a quoted DOT string
+fn escape(s: &str) -> String {
+    s.replace('"', r#"\""#).replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::MessageNorm, *};
+
+    fn node(robot: &str, index: usize) -> GraphNode {
+        GraphNode {
+            id: format!("{robot}_{index}"),
+            robot: robot.to_string(),
+            index,
+            kind: "variable",
+            label: format!("v{index}"),
+            color: "#eff1f5".to_string(),
+            shape: "circle",
+            width: 0.8,
+            position: Some([0.0, 0.0]),
+            belief: None,
+        }
+    }
+
+    #[test]
+    fn interrobot_edges_are_styled_by_antenna() {
+        let graph = Graph {
+            robots: vec!["0".to_string(), "1".to_string()],
+            nodes:  vec![node("0", 0), node("1", 0)],
+            edges:  vec![GraphEdge {
+                from:     "0_0".to_string(),
+                to:       "1_0".to_string(),
+                kind:     EdgeKind::InterRobot { active: false },
+                messages: None,
+            }],
+        };
+        let dot = graph.to_dot(&DotStyle::default());
+        assert!(dot.starts_with("graph {"));
+        assert!(dot.contains(r#"subgraph "1" {"#));
+        assert!(
+            dot.contains(r##""0_0" [label="v0", fillcolor="#eff1f5", shape=circle, width="0.8"]"##)
+        );
+        assert!(
+            dot.contains(r#""0_0" -- "1_0" [len=4, style=dashed, color="green", penwidth=3.0]"#)
+        );
+    }
+
+    #[test]
+    fn messages_are_described_in_both_directions() {
+        let messages = MessageNorms {
+            to_variable: Some(MessageNorm {
+                information: 0.5,
+                precision:   2.0,
+            }),
+            to_factor:   None,
+        };
+        assert_eq!(describe(messages), r"v: |η|=0.50 |Λ|=2.00\nf: -");
+    }
+}
@@ -0,0 +1,718 @@
+//! Export factorgraphs as a graph of nodes and edges.
+//!
+//! Every factorgraph exports its own nodes and edges through [`ExportGraph`].
+//! [`Graph::new`] combines the exports of several factorgraphs, connects the
+//! interrobot factors with the variables they are connected to in the other
+//! factorgraphs, and applies the filters and annotations of the
+//! [`ExportOptions`]. The resulting [`Graph`] can be rendered as graphviz DOT
+//! with [`Graph::to_dot`], as SVG with [`Graph::to_svg`] without needing the
+//! graphviz binaries, or be serialised e.g. as JSON.
+
+mod dot;
+mod svg;
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, VecDeque},
+};
+
+pub use dot::{DotStyle, EdgeStyle};
+use gbp_linalg::prelude::*;
+use serde::Serialize;
+
+use super::{factor::ExternalVariableId, id::GraphId, message::Message};
+
+/// Represents a factorgraph node in the graphviz output
+pub struct Node<Id> {
+    /// The index of the node
+    pub index: usize,
+    /// The kind of the node
+    pub kind:  NodeKind<Id>,
+}
+
+impl<Id> Node<Id> {
+    /// Returns the color of the node
+    pub fn color(&self) -> Cow<'static, str> {
+        self.kind.color()
+    }
+
+    /// Returns the shape of the node
+    pub const fn shape(&self) -> &'static str {
+        self.kind.shape()
+    }
+
+    /// Returns the width of the node
+    pub const fn width(&self) -> f64 {
+        self.kind.width()
+    }
+}
+
+pub enum NodeKind<Id> {
+    Variable {
+        x:      f64,
+        y:      f64,
+        /// The belief of the variable
+        belief: Belief,
+    },
+    InterRobotFactor {
+        active: bool,
+        external_variable_id: ExternalVariableId<Id>,
+    },
+    // InterRobotFactor {
+    //     /// The id of the robot the interrobot factor is connected to
+    //     other_robot_id: RobotId,
+    //     /// The index of the variable in the other robots factorgraph, that the interrobot
+    // factor is connected with     variable_index_in_other_robot: usize,
+    // },
+    DynamicFactor,
+    ObstacleFactor,
+    TrackingFactor, // PoseFactor,
+    DynamicObstacleFactor,
+    VelocityFactor,
+    AccelerationFactor,
+    /// A factor defined outside of this crate
+    UserFactor {
+        /// [`Factor::name`](crate::factor::Factor::name) of the factor
+        name:  &'static str,
+        /// [`Factor::color`](crate::factor::Factor::color) of the factor
+        color: [u8; 3],
+    },
+}
+
+impl<Id> NodeKind<Id> {
+    pub fn color(&self) -> Cow<'static, str> {
+        let color = match self {
+            Self::Variable { .. } => "#eff1f5",         // latte base (white)
+            Self::InterRobotFactor { .. } => "#a6da95", // green
+            Self::DynamicFactor => "#8aadf4",           // blue
+            Self::ObstacleFactor => "#ee99a0",          // mauve (purple)
+            // Self::PoseFactor => "#c6aof6",     // maroon (red)
+            Self::TrackingFactor => "#f4a15a",        // orange
+            Self::DynamicObstacleFactor => "#f5a97f", // peach
+            Self::VelocityFactor => "#eed49f",        // yellow
+            Self::AccelerationFactor => "#f5bde6",    // pink
+            Self::UserFactor {
+                color: [r, g, b], ..
+            } => return Cow::Owned(format!("#{r:02x}{g:02x}{b:02x}")),
+        };
+        Cow::Borrowed(color)
+    }
+
+    pub const fn shape(&self) -> &'static str {
+        match self {
+            Self::Variable { .. } => "circle",
+            _ => "square",
+        }
+    }
+
+    pub const fn width(&self) -> f64 {
+        match self {
+            Self::Variable { .. } => 0.8,
+            _ => 0.2,
+        }
+    }
+
+    /// The name of the kind, as used by the `[gbp.factors-enabled]` section of
+    /// the config, e.g. `"dynamic-obstacle"`. User defined factors are named
+    /// by their [`Factor::name`](crate::factor::Factor::name)
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Variable { .. } => "variable",
+            Self::InterRobotFactor { .. } => "interrobot",
+            Self::DynamicFactor => "dynamic",
+            Self::ObstacleFactor => "obstacle",
+            Self::TrackingFactor => "tracking",
+            Self::DynamicObstacleFactor => "dynamic-obstacle",
+            Self::VelocityFactor => "velocity",
+            Self::AccelerationFactor => "acceleration",
+            Self::UserFactor { name, .. } => name,
+        }
+    }
+
+    /// The short label of the node, e.g. `fd` for a dynamic factor
+    fn label(&self, index: usize) -> String {
+        match self {
+            Self::Variable { .. } => format!("v{index}"),
+            Self::InterRobotFactor { .. } => "fr".to_string(),
+            Self::DynamicFactor => "fd".to_string(),
+            Self::ObstacleFactor => "fo".to_string(),
+            Self::TrackingFactor => "ft".to_string(),
+            Self::DynamicObstacleFactor => "fdo".to_string(),
+            Self::VelocityFactor => "fv".to_string(),
+            Self::AccelerationFactor => "fa".to_string(),
+            Self::UserFactor { name, .. } => (*name).to_string(),
+        }
+    }
+}
+
+/// Mean and covariance of the belief of a variable
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Belief {
+    /// Mean of the belief
+    pub mean:       Vec<f64>,
+    /// Covariance of the belief, row by row
+    pub covariance: Vec<Vec<f64>>,
+}
+
+impl Belief {
+    /// Copy the mean and covariance out of the linear algebra types
+    pub fn new(mean: &Vector<Float>, covariance: &Matrix<Float>) -> Self {
+        Self {
+            mean:       mean.to_vec(),
+            covariance: covariance
+                .rows()
+                .into_iter()
+                .map(|row| row.to_vec())
+                .collect(),
+        }
+    }
+}
+
+/// Norms of a message, `None` if the message is empty
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct MessageNorm {
+    /// Euclidean norm of the information vector
+    pub information: f64,
+    /// Frobenius norm of the precision matrix
+    pub precision:   f64,
+}
+
+impl MessageNorm {
+    /// The norms of `message`, or `None` if the message is empty
+    pub fn of(message: &Message) -> Option<Self> {
+        let information = message.information_vector()?;
+        let precision = message.precision_matrix()?;
+        Some(Self {
+            information: information.iter().map(|x| x * x).sum::<Float>().sqrt(),
+            precision:   precision.iter().map(|x| x * x).sum::<Float>().sqrt(),
+        })
+    }
+}
+
+/// Norms of the latest messages sent in either direction along an edge
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct MessageNorms {
+    /// The message the factor sent to the variable
+    pub to_variable: Option<MessageNorm>,
+    /// The message the variable sent to the factor
+    pub to_factor:   Option<MessageNorm>,
+}
+
+/// An edge between a variable and a factor in the same factorgraph
+pub struct Edge {
+    pub from:     usize,
+    pub to:       usize,
+    /// The latest messages exchanged along the edge
+    pub messages: MessageNorms,
+}
+
+pub trait ExportGraph<Id> {
+    fn export_graph(&self) -> (Vec<Node<Id>>, Vec<Edge>);
+}
+
+/// Which factors to include in an export, by their [`NodeKind::name`].
+/// Variables are always included
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum FactorFilter {
+    /// Include every factor
+    #[default]
+    All,
+    /// Only include factors of the listed kinds
+    Include(Vec<String>),
+    /// Include every factor except the listed kinds
+    Exclude(Vec<String>),
+}
+
+impl FactorFilter {
+    /// Whether factors of the kind `name` are included
+    pub fn includes(&self, name: &str) -> bool {
+        match self {
+            Self::All => true,
+            Self::Include(names) => names.iter().any(|n| n == name),
+            Self::Exclude(names) => !names.iter().any(|n| n == name),
+        }
+    }
+}
+
+/// What to annotate the nodes and edges of an export with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Annotations {
+    /// Annotate variables with the mean and covariance of their belief
+    pub beliefs:  bool,
+    /// Annotate edges with the norms of the latest messages sent along them
+    pub messages: bool,
+}
+
+/// A robot and every robot within `hops` interrobot connections of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Neighbourhood<Id> {
+    /// The robot at the center of the neighbourhood
+    pub robot: Id,
+    /// The number of interrobot connections to follow away from `robot`
+    pub hops:  usize,
+}
+
+/// Filters and annotations of an export
+#[derive(Debug, Clone)]
+pub struct ExportOptions<Id> {
+    /// Only export these robots, or every robot if `None`
+    pub robots: Option<BTreeSet<Id>>,
+    /// Only export the robots in this neighbourhood
+    pub neighbourhood: Option<Neighbourhood<Id>>,
+    /// Which kinds of factors to export
+    pub factors: FactorFilter,
+    /// What to annotate the nodes and edges with
+    pub annotations: Annotations,
+}
+
+impl<Id> Default for ExportOptions<Id> {
+    fn default() -> Self {
+        Self {
+            robots: None,
+            neighbourhood: None,
+            factors: FactorFilter::All,
+            annotations: Annotations::default(),
+        }
+    }
+}
+
+impl<Id: GraphId> ExportOptions<Id> {
+    /// Only export the given robots
+    #[must_use]
+    pub fn with_robots(mut self, robots: impl IntoIterator<Item = Id>) -> Self {
+        self.robots = Some(robots.into_iter().collect());
+        self
+    }
+
+    /// Only export `robot` and the robots within `hops` interrobot
+    /// connections of it
+    #[must_use]
+    pub const fn with_neighbourhood(mut self, robot: Id, hops: usize) -> Self {
+        self.neighbourhood = Some(Neighbourhood { robot, hops });
+        self
+    }
+
+    /// Only export the factors allowed by `factors`
+    #[must_use]
+    pub fn with_factors(mut self, factors: FactorFilter) -> Self {
+        self.factors = factors;
+        self
+    }
+
+    /// Annotate the export with `annotations`
+    #[must_use]
+    pub const fn with_annotations(mut self, annotations: Annotations) -> Self {
+        self.annotations = annotations;
+        self
+    }
+}
+
+/// A node in a [`Graph`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GraphNode {
+    /// Unique id of the node in the graph, `"{robot}_{index}"`
+    pub id:       String,
+    /// The robot the node belongs to
+    pub robot:    String,
+    /// The index of the node in the factorgraph of the robot
+    pub index:    usize,
+    /// [`NodeKind::name`] of the node
+    pub kind:     &'static str,
+    /// Short label of the node, e.g. `v3` or `fd`
+    pub label:    String,
+    /// Fill color of the node as a hex string
+    pub color:    String,
+    /// Graphviz shape of the node
+    pub shape:    &'static str,
+    /// Graphviz width of the node
+    pub width:    f64,
+    /// Estimated position of the node, for variables
+    pub position: Option<[f64; 2]>,
+    /// The belief of the variable, if beliefs are annotated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub belief:   Option<Belief>,
+}
+
+/// The kind of a [`GraphEdge`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EdgeKind {
+    /// Between a variable and a factor of the same robot
+    Internal,
+    /// Between an interrobot factor and the variable of another robot it is
+    /// connected with
+    InterRobot {
+        /// Whether the radio antenna of the robot with the interrobot factor
+        /// is active
+        active: bool,
+    },
+}
+
+/// An edge in a [`Graph`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GraphEdge {
+    /// [`GraphNode::id`] of the first node
+    pub from:     String,
+    /// [`GraphNode::id`] of the second node
+    pub to:       String,
+    /// The kind of the edge
+    pub kind:     EdgeKind,
+    /// The latest messages sent along the edge, if messages are annotated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub messages: Option<MessageNorms>,
+}
+
+/// The combined export of the factorgraphs of several robots
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Graph {
+    /// The exported robots, in the order they were given
+    pub robots: Vec<String>,
+    /// The exported nodes, grouped by robot
+    pub nodes:  Vec<GraphNode>,
+    /// The exported edges, the internal edges of every robot first
+    pub edges:  Vec<GraphEdge>,
+}
+
+/// The unique id of the node `index` of `robot` in a [`Graph`]
+fn node_id<Id: std::fmt::Debug>(robot: Id, index: usize) -> String {
+    format!("{robot:?}_{index}")
+}
+
+impl Graph {
+    /// Export the factorgraphs of `robots` with `options`. Every robot is
+    /// given as its id, its factorgraph and whether its radio antenna is
+    /// active
+    pub fn new<'a, Id, G>(
+        robots: impl IntoIterator<Item = (Id, &'a G, bool)>,
+        options: &ExportOptions<Id>,
+    ) -> Self
+    where
+        Id: GraphId,
+        G: ExportGraph<Id> + 'a,
+    {
+        let exports = robots
+            .into_iter()
+            .map(|(id, graph, active)| {
+                let (nodes, edges) = graph.export_graph();
+                (id, nodes, edges, active)
+            })
+            .collect::<Vec<_>>();
+
+        let mut selected = exports.iter().map(|(id, ..)| *id).collect::<BTreeSet<_>>();
+        if let Some(ref robots) = options.robots {
+            selected.retain(|id| robots.contains(id));
+        }
+        if let Some(neighbourhood) = options.neighbourhood {
+            let connections = exports.iter().flat_map(|(id, nodes, ..)| {
+                nodes.iter().filter_map(move |node| match node.kind {
+                    NodeKind::InterRobotFactor {
+                        external_variable_id,
+                        ..
+                    } => Some((*id, external_variable_id.factorgraph_id)),
+                    _ => None,
+                })
+            });
+            let within = within_hops(connections, neighbourhood);
+            selected.retain(|id| within.contains(id));
+        }
+
+        let mut graph = Self::default();
+        // The ids of the exported variables of every robot, to only connect
+        // interrobot factors with variables that are exported
+        let mut variables = BTreeSet::new();
+        let mut interrobot_factors = Vec::new();
+
+        for (id, nodes, edges, active) in exports.iter().filter(|(id, ..)| selected.contains(id)) {
+            graph.robots.push(format!("{id:?}"));
+            let mut included = BTreeSet::new();
+
+            for node in nodes {
+                let name = node.kind.name();
+                let is_variable = matches!(node.kind, NodeKind::Variable { .. });
+                if !is_variable && !options.factors.includes(name) {
+                    continue;
+                }
+                included.insert(node.index);
+
+                let (position, belief) = match node.kind {
+                    NodeKind::Variable { x, y, ref belief } => {
+                        variables.insert((*id, node.index));
+                        (
+                            Some([x, y]),
+                            options.annotations.beliefs.then(|| belief.clone()),
+                        )
+                    }
+                    NodeKind::InterRobotFactor {
+                        external_variable_id,
+                        ..
+                    } => {
+                        interrobot_factors.push((
+                            node_id(*id, node.index),
+                            external_variable_id,
+                            *active,
+                        ));
+                        (None, None)
+                    }
+                    _ => (None, None),
+                };
+
+                graph.nodes.push(GraphNode {
+                    id: node_id(*id, node.index),
+                    robot: format!("{id:?}"),
+                    index: node.index,
+                    kind: name,
+                    label: node.kind.label(node.index),
+                    color: node.color().into_owned(),
+                    shape: node.shape(),
+                    width: node.width(),
+                    position,
+                    belief,
+                });
+            }
+
+            graph.edges.extend(
+                edges
+                    .iter()
+                    .filter(|edge| included.contains(&edge.from) && included.contains(&edge.to))
+                    .map(|edge| GraphEdge {
+                        from:     node_id(*id, edge.from),
+                        to:       node_id(*id, edge.to),
+                        kind:     EdgeKind::Internal,
+                        messages: options.annotations.messages.then_some(edge.messages),
+                    }),
+            );
+        }
+
+        graph.edges.extend(
+            interrobot_factors
+                .into_iter()
+                .filter(|(_, external, _)| {
+                    variables.contains(&(external.factorgraph_id, external.variable_index.index()))
+                })
+                .map(|(factor, external, active)| GraphEdge {
+                    from:     factor,
+                    to:       node_id(external.factorgraph_id, external.variable_index.index()),
+                    kind:     EdgeKind::InterRobot { active },
+                    messages: None,
+                }),
+        );
+
+        graph
+    }
+
+    /// Returns the node with the given [`GraphNode::id`]
+    pub fn node(&self, id: &str) -> Option<&GraphNode> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+}
+
+/// The robots within `neighbourhood.hops` of `neighbourhood.robot`, following
+/// `connections` between robots in both directions
+fn within_hops<Id: GraphId>(
+    connections: impl Iterator<Item = (Id, Id)>,
+    neighbourhood: Neighbourhood<Id>,
+) -> BTreeSet<Id> {
+    let mut adjacency = BTreeMap::<Id, BTreeSet<Id>>::new();
+    for (a, b) in connections {
+        adjacency.entry(a).or_default().insert(b);
+        adjacency.entry(b).or_default().insert(a);
+    }
+
+    let mut within = BTreeSet::from([neighbourhood.robot]);
+    let mut queue = VecDeque::from([(neighbourhood.robot, 0)]);
+    while let Some((robot, hops)) = queue.pop_front() {
+        if hops == neighbourhood.hops {
+            continue;
+        }
+        for &other in adjacency.get(&robot).into_iter().flatten() {
+            if within.insert(other) {
+                queue.push_back((other, hops + 1));
+            }
+        }
+    }
+    within
+}
+
+#[cfg(test)]
+mod tests {
+    use petgraph::graph::NodeIndex;
+
+    use super::*;
+    use crate::factorgraph::VariableIndex;
+
+    /// A chain of `variables` variables connected by dynamic factors, with an
+    /// interrobot factor on the last variable for every robot in `others`
+    struct Chain {
+        variables: usize,
+        others:    Vec<u32>,
+    }
+
+    impl ExportGraph<u32> for Chain {
+        fn export_graph(&self) -> (Vec<Node<u32>>, Vec<Edge>) {
+            let mut nodes = (0..self.variables)
+                .map(|index| Node {
+                    index,
+                    kind: NodeKind::Variable {
+                        #[allow(clippy::cast_precision_loss)]
+                        x: index as f64,
+                        y: 0.0,
+                        belief: Belief {
+                            mean:       vec![0.0; 4],
+                            covariance: vec![vec![0.0; 4]; 4],
+                        },
+                    },
+                })
+                .collect::<Vec<_>>();
+            let mut edges = Vec::new();
+            for i in 1..self.variables {
+                let index = nodes.len();
+                nodes.push(Node {
+                    index,
+                    kind: NodeKind::DynamicFactor,
+                });
+                for variable in [i - 1, i] {
+                    edges.push(Edge {
+                        from:     variable,
+                        to:       index,
+                        messages: MessageNorms::default(),
+                    });
+                }
+            }
+            for &other in &self.others {
+                let index = nodes.len();
+                nodes.push(Node {
+                    index,
+                    kind: NodeKind::InterRobotFactor {
+                        active: true,
+                        external_variable_id: ExternalVariableId::new(
+                            other,
+                            VariableIndex(NodeIndex::new(self.variables - 1)),
+                        ),
+                    },
+                });
+                edges.push(Edge {
+                    from:     self.variables - 1,
+                    to:       index,
+                    messages: MessageNorms::default(),
+                });
+            }
+            (nodes, edges)
+        }
+    }
+
+    /// Robots 0 - 1 - 2 - 3 connected in a line
+    fn line() -> Vec<(u32, Chain)> {
+        (0..4_u32)
+            .map(|id| {
+                let others = [id.checked_sub(1), (id < 3).then_some(id + 1)]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                (id, Chain {
+                    variables: 3,
+                    others,
+                })
+            })
+            .collect()
+    }
+
+    fn export(robots: &[(u32, Chain)], options: &ExportOptions<u32>) -> Graph {
+        Graph::new(robots.iter().map(|(id, chain)| (*id, chain, true)), options)
+    }
+
+    #[test]
+    fn everything_is_exported_by_default() {
+        let robots = line();
+        let graph = export(&robots, &ExportOptions::default());
+        assert_eq!(graph.robots, ["0", "1", "2", "3"]);
+        // 3 variables, 2 dynamic factors and 1 or 2 interrobot factors
+        assert_eq!(graph.nodes.len(), 4 * 5 + 6);
+        let interrobot = graph
+            .edges
+            .iter()
+            .filter(|edge| matches!(edge.kind, EdgeKind::InterRobot { .. }))
+            .count();
+        assert_eq!(interrobot, 6);
+        assert!(graph.edges.iter().all(|edge| edge.messages.is_none()));
+        assert!(graph.nodes.iter().all(|node| node.belief.is_none()));
+    }
+
+    #[test]
+    fn neighbourhood_follows_interrobot_connections() {
+        let robots = line();
+        let graph = export(&robots, &ExportOptions::default().with_neighbourhood(1, 1));
+        assert_eq!(graph.robots, ["0", "1", "2"]);
+
+        let graph = export(
+            &robots,
+            &ExportOptions::default()
+                .with_neighbourhood(0, 2)
+                .with_robots([0, 2, 3]),
+        );
+        assert_eq!(graph.robots, ["0", "2"]);
+        // Robot 1 is not exported, so neither robot has an interrobot edge
+        assert!(graph
+            .edges
+            .iter()
+            .all(|edge| edge.kind == EdgeKind::Internal));
+    }
+
+    #[test]
+    fn factors_are_filtered_by_kind() {
+        let robots = line();
+        let graph = export(
+            &robots,
+            &ExportOptions::default()
+                .with_factors(FactorFilter::Exclude(vec!["interrobot".to_string()])),
+        );
+        assert!(graph.nodes.iter().all(|node| node.kind != "interrobot"));
+        assert!(graph
+            .edges
+            .iter()
+            .all(|edge| edge.kind == EdgeKind::Internal));
+
+        let graph = export(
+            &robots,
+            &ExportOptions::default()
+                .with_factors(FactorFilter::Include(vec!["interrobot".to_string()])),
+        );
+        assert!(graph.nodes.iter().all(|node| node.kind != "dynamic"));
+        // Variables are always exported, and only their edges to the interrobot
+        // factors are left
+        assert_eq!(
+            graph.nodes.iter().filter(|n| n.kind == "variable").count(),
+            12
+        );
+        assert_eq!(graph.edges.len(), 6 + 6);
+    }
+
+    #[test]
+    fn annotations_are_opt_in() {
+        let robots = line();
+        let graph = export(
+            &robots,
+            &ExportOptions::default().with_annotations(Annotations {
+                beliefs:  true,
+                messages: true,
+            }),
+        );
+        let variable = graph.node("2_0").expect("robot 2 has a first variable");
+        assert_eq!(variable.position, Some([0.0, 0.0]));
+        assert!(variable.belief.is_some());
+        assert!(graph
+            .edges
+            .iter()
+            .filter(|edge| edge.kind == EdgeKind::Internal)
+            .all(|edge| edge.messages.is_some()));
+    }
+
+    #[test]
+    fn message_norm_of_empty_message_is_none() {
+        assert_eq!(MessageNorm::of(&Message::empty()), None);
+        let norm = MessageNorm::of(&Message::zero(4)).expect("the message has a payload");
+        assert_eq!(norm, MessageNorm {
+            information: 0.0,
+            precision:   0.0,
+        });
+    }
+}
@@ -0,0 +1,286 @@
+//! Render a [`Graph`] as SVG, without the graphviz binaries.
+//!
+//! The layout is geometric: variables are placed at their estimated position,
+//! factors connected to several variables between them, and factors connected
+//! to a single variable are spread in a circle around it.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use super::{EdgeKind, Graph};
+
+/// Width of the image in pixels
+const WIDTH: f64 = 1024.0;
+/// Space between the outermost variables and the edge of the image in pixels
+const PADDING: f64 = 32.0;
+const VARIABLE_RADIUS: f64 = 6.0;
+const FACTOR_SIZE: f64 = 6.0;
+/// Distance in pixels between a variable and the factors connected only to it
+const UNARY_FACTOR_DISTANCE: f64 = 14.0;
+
+/// Positions of the nodes in pixels, and the size of the image
+struct Layout<'a> {
+    positions: BTreeMap<&'a str, [f64; 2]>,
+    height:    f64,
+}
+
+impl Graph {
+    /// Render the graph as an SVG image.
+    ///
+    /// Variables are placed at their estimated position, so the image shows
+    /// the planned paths of the robots from above. Hovering a node shows its
+    /// label, and its belief if beliefs are annotated.
+    pub fn to_svg(&self) -> String {
+        let layout = self.layout();
+        let mut svg = String::with_capacity(16 * 1024);
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{height:.0}" viewBox="0 0 {WIDTH} {height:.0}">"#,
+            height = layout.height
+        );
+        svg.push_str("<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n");
+
+        for edge in &self.edges {
+            let (Some([x1, y1]), Some([x2, y2])) = (
+                layout.positions.get(edge.from.as_str()),
+                layout.positions.get(edge.to.as_str()),
+            ) else {
+                continue;
+            };
+            let style = match edge.kind {
+                EdgeKind::Internal => r##"stroke="#6c6f85" stroke-width="1""##,
+                EdgeKind::InterRobot { active: true } => r#"stroke="green" stroke-width="2""#,
+                EdgeKind::InterRobot { active: false } => {
+                    r#"stroke="green" stroke-width="2" stroke-dasharray="4 4""#
+                }
+            };
+            let _ = writeln!(
+                svg,
+                r#"<line x1="{x1:.1}" y1="{y1:.1}" x2="{x2:.1}" y2="{y2:.1}" {style}/>"#
+            );
+        }
+
+        for node in &self.nodes {
+            let Some(&[x, y]) = layout.positions.get(node.id.as_str()) else {
+                continue;
+            };
+            let mut title = format!("{} {}", node.robot, node.label);
+            if let Some(ref belief) = node.belief {
+                let _ = write!(
+                    title,
+                    "\nmean: {:?}\ncovariance: {:?}",
+                    belief.mean, belief.covariance
+                );
+            }
+            let title = escape(&title);
+            if node.position.is_some() {
+                let _ = writeln!(
+                    svg,
+                    r#"<circle cx="{x:.1}" cy="{y:.1}" r="{VARIABLE_RADIUS}" fill="{}" stroke="black"><title>{title}</title></circle>"#,
+                    node.color
+                );
+            } else {
+                let half = FACTOR_SIZE / 2.0;
+                let _ = writeln!(
+                    svg,
+                    r#"<rect x="{:.1}" y="{:.1}" width="{FACTOR_SIZE}" height="{FACTOR_SIZE}" fill="{}" stroke="black"><title>{title}</title></rect>"#,
+                    x - half,
+                    y - half,
+                    node.color
+                );
+            }
+        }
+
+        // Label every robot next to its first variable
+        for robot in &self.robots {
+            let first = self
+                .nodes
+                .iter()
+                .find(|node| &node.robot == robot && node.position.is_some())
+                .and_then(|node| layout.positions.get(node.id.as_str()));
+            if let Some(&[x, y]) = first {
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="{:.1}" y="{:.1}" font-family="monospace" font-size="12">{}</text>"#,
+                    x + VARIABLE_RADIUS + 2.0,
+                    y - VARIABLE_RADIUS - 2.0,
+                    escape(robot)
+                );
+            }
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Place every node of the graph in pixel coordinates
+    fn layout(&self) -> Layout<'_> {
+        let variables = self
+            .nodes
+            .iter()
+            .filter_map(|node| node.position.map(|position| (node.id.as_str(), position)))
+            .collect::<Vec<_>>();
+
+        let (min, max) = variables.iter().fold(
+            ([f64::INFINITY; 2], [f64::NEG_INFINITY; 2]),
+            |(min, max), (_, [x, y])| {
+                ([min[0].min(*x), min[1].min(*y)], [
+                    max[0].max(*x),
+                    max[1].max(*y),
+                ])
+            },
+        );
+        if variables.is_empty() {
+            return Layout {
+                positions: BTreeMap::new(),
+                height:    2.0 * PADDING,
+            };
+        }
+
+        let extent = (max[0] - min[0]).max(max[1] - min[1]).max(f64::EPSILON);
+        let scale = 2.0f64.mul_add(-PADDING, WIDTH) / extent;
+        let height = (max[1] - min[1]).mul_add(scale, 2.0 * PADDING);
+        // The y axis of the image points down
+        let mut positions = variables
+            .into_iter()
+            .map(|(id, [x, y])| {
+                (id, [
+                    (x - min[0]).mul_add(scale, PADDING),
+                    (max[1] - y).mul_add(scale, PADDING),
+                ])
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        // The variables every factor is connected to
+        let mut neighbours = BTreeMap::<&str, Vec<&str>>::new();
+        for edge in self
+            .edges
+            .iter()
+            .filter(|edge| edge.kind == EdgeKind::Internal)
+        {
+            let (variable, factor) = if positions.contains_key(edge.from.as_str()) {
+                (edge.from.as_str(), edge.to.as_str())
+            } else {
+                (edge.to.as_str(), edge.from.as_str())
+            };
+            neighbours.entry(factor).or_default().push(variable);
+        }
+
+        let mut unary = BTreeMap::<&str, Vec<&str>>::new();
+        for (factor, variables) in &neighbours {
+            match variables.as_slice() {
+                [variable] => unary.entry(*variable).or_default().push(*factor),
+                variables => {
+                    #[allow(clippy::cast_precision_loss)]
+                    let n = variables.len() as f64;
+                    let [x, y] = variables
+                        .iter()
+                        .filter_map(|variable| positions.get(variable))
+                        .fold([0.0, 0.0], |[x, y], [vx, vy]| [x + vx / n, y + vy / n]);
+                    positions.insert(factor, [x, y]);
+                }
+            }
+        }
+
+        for (variable, factors) in unary {
+            let [x, y] = positions[variable];
+            #[allow(clippy::cast_precision_loss)]
+            let step = std::f64::consts::TAU / factors.len() as f64;
+            for (i, factor) in factors.into_iter().enumerate() {
+                #[allow(clippy::cast_precision_loss)]
+                let angle = (i as f64).mul_add(step, -std::f64::consts::FRAC_PI_2);
+                positions.insert(factor, [
+                    angle.cos().mul_add(UNARY_FACTOR_DISTANCE, x),
+                    angle.sin().mul_add(UNARY_FACTOR_DISTANCE, y),
+                ]);
+            }
+        }
+
+        Layout { positions, height }
+    }
+}
+
+/// Escape the characters that are special in XML text and attributes
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{GraphEdge, GraphNode},
+        *,
+    };
+
+    fn node(id: &str, kind: &'static str, position: Option<[f64; 2]>) -> GraphNode {
+        GraphNode {
+            id: id.to_string(),
+            robot: "0".to_string(),
+            index: 0,
+            kind,
+            label: id.to_string(),
+            color: "#ffffff".to_string(),
+            shape: "circle",
+            width: 0.8,
+            position,
+            belief: None,
+        }
+    }
+
+    fn edge(from: &str, to: &str) -> GraphEdge {
+        GraphEdge {
+            from:     from.to_string(),
+            to:       to.to_string(),
+            kind:     EdgeKind::Internal,
+            messages: None,
+        }
+    }
+
+    /// Two variables 10 apart with a dynamic factor between them, and two
+    /// obstacle factors on the first variable
+    fn graph() -> Graph {
+        Graph {
+            robots: vec!["0".to_string()],
+            nodes:  vec![
+                node("v0", "variable", Some([0.0, 0.0])),
+                node("v1", "variable", Some([10.0, 0.0])),
+                node("fd", "dynamic", None),
+                node("fo0", "obstacle", None),
+                node("fo1", "obstacle", None),
+            ],
+            edges:  vec![
+                edge("v0", "fd"),
+                edge("v1", "fd"),
+                edge("v0", "fo0"),
+                edge("v0", "fo1"),
+            ],
+        }
+    }
+
+    #[test]
+    fn factors_are_placed_between_their_variables() {
+        let graph = graph();
+        let layout = graph.layout();
+        assert_eq!(layout.positions["v0"], [PADDING, PADDING]);
+        assert_eq!(layout.positions["v1"], [WIDTH - PADDING, PADDING]);
+        assert_eq!(layout.positions["fd"], [WIDTH / 2.0, PADDING]);
+
+        // The unary factors are on opposite sides of the first variable
+        let [ax, ay] = layout.positions["fo0"];
+        let [bx, by] = layout.positions["fo1"];
+        assert!(((ax - bx).hypot(ay - by) - 2.0 * UNARY_FACTOR_DISTANCE).abs() < 1e-9);
+        assert!(((ax + bx) / 2.0 - PADDING).abs() < 1e-9);
+    }
+
+    #[test]
+    fn every_node_and_edge_is_drawn() {
+        let svg = graph().to_svg();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<circle").count(), 2);
+        assert_eq!(svg.matches("<rect x=").count(), 3);
+        assert_eq!(svg.matches("<line").count(), 4);
+    }
+}
@@ -1,19 +1,42 @@
-use super::factorgraph::{FactorGraphId, FactorIndex, VariableIndex};
+//! Identifiers of factorgraphs, and of the factors and variables in them.
+
+use super::factorgraph::{FactorIndex, VariableIndex};
+
+/// Identifier of a factorgraph.
+///
+/// The id is chosen by the caller, e.g. the `Entity` the factorgraph is
+/// attached to in an ECS world, or the id of a robot on the network.
+/// **Invariants**:
+/// - The id of a factorgraph is unique among all the factorgraphs it exchanges
+///   messages with.
+/// - The id does not change during the lifetime of the factorgraph.
+///
+/// Messages are stored ordered by the id of their sender, so the ordering of
+/// ids decides the order messages are aggregated in.
+pub trait GraphId:
+    Copy + Eq + Ord + std::hash::Hash + std::fmt::Debug + Send + Sync + 'static
+{
+}
+
+impl<T> GraphId for T where
+    T: Copy + Eq + Ord + std::hash::Hash + std::fmt::Debug + Send + Sync + 'static
+{
+}
 
 /// Unique identifier of a factor in the world.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, derive_more::Display)]
 #[display(fmt = "{:?}-{}", factorgraph_id, "factor_index.0.index()")]
-pub struct FactorId {
+pub struct FactorId<Id> {
     /// The id of the factorgraph that the factor belongs to.
-    pub factorgraph_id: FactorGraphId,
+    pub factorgraph_id: Id,
     /// The index of the factor in the factorgraph.
     pub factor_index:   FactorIndex,
 }
 
-impl FactorId {
+impl<Id> FactorId<Id> {
     /// Create a new `FactorId`.
     #[must_use]
-    pub const fn new(factorgraph_id: FactorGraphId, factor_index: FactorIndex) -> Self {
+    pub const fn new(factorgraph_id: Id, factor_index: FactorIndex) -> Self {
         Self {
             factorgraph_id,
             factor_index,
@@ -22,7 +45,7 @@ impl FactorId {
 }
 
 #[allow(clippy::non_canonical_partial_ord_impl)]
-impl std::cmp::PartialOrd for FactorId {
+impl<Id: GraphId> std::cmp::PartialOrd for FactorId<Id> {
     /// Returns `Some(std::cmp::ordering::Equal)` if `self` and `other` are
     /// equal, `Some(std::cmp::ordering::Less)` if `other.factorgraph_id` is
     /// greater than `self.factorgraph_id`, or if the factors belong to the
@@ -52,7 +75,7 @@ impl std::cmp::PartialOrd for FactorId {
     }
 }
 
-impl std::cmp::Ord for FactorId {
+impl<Id: GraphId> std::cmp::Ord for FactorId<Id> {
     #[inline]
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         self.partial_cmp(other)
@@ -72,15 +95,15 @@ impl std::cmp::Ord for FactorId {
 /// Unique identifier of a variable in the world.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, derive_more::Display)]
 #[display(fmt = "{:?}-{}", factorgraph_id, "variable_index.0.index()")]
-pub struct VariableId {
+pub struct VariableId<Id> {
     /// The id of the factorgraph that the variable belongs to.
-    pub factorgraph_id: FactorGraphId,
+    pub factorgraph_id: Id,
     /// The index of the variable in the factorgraph.
     pub variable_index: VariableIndex,
 }
 
 #[allow(clippy::non_canonical_partial_ord_impl)]
-impl std::cmp::PartialOrd for VariableId {
+impl<Id: GraphId> std::cmp::PartialOrd for VariableId<Id> {
     /// Returns `Some(std::cmp::ordering::Equal)` if `self` and `other` are
     /// equal, `Some(std::cmp::ordering::Less)` if `other.factorgraph_id` is
     /// greater than `self.factorgraph_id`, or if the factors belong to the
@@ -110,7 +133,7 @@ impl std::cmp::PartialOrd for VariableId {
     }
 }
 
-impl std::cmp::Ord for VariableId {
+impl<Id: GraphId> std::cmp::Ord for VariableId<Id> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         self.partial_cmp(other)
             .expect("every branch in Self::partial_cmp() returns Some()")
@@ -125,10 +148,10 @@ impl std::cmp::Ord for VariableId {
 // }
 // impl std::cmp::Eq for VariableId {}
 
-impl VariableId {
+impl<Id> VariableId<Id> {
     /// Create a new `VariableId`.
     #[must_use]
-    pub const fn new(factorgraph_id: FactorGraphId, variable_index: VariableIndex) -> Self {
+    pub const fn new(factorgraph_id: Id, variable_index: VariableIndex) -> Self {
         Self {
             factorgraph_id,
             variable_index,
@@ -0,0 +1,282 @@
+//! Iteration of several factorgraphs together.
+//!
+//! Internal iterations only pass messages within each factorgraph, and can run
+//! for every factorgraph independently. External iterations pass messages
+//! between the interrobot factors and variables of different factorgraphs,
+//! which is done through a [`Transport`]. The transport decides when, and
+//! if, a message sent by one factorgraph arrives at another, e.g. to simulate
+//! an unreliable radio link.
+
+use std::collections::HashMap;
+
+use gbp_schedule::GbpScheduleAtIteration;
+
+use crate::{factorgraph::FactorGraph, id::GraphId, message::ExternalMessage};
+
+/// Carries messages between factorgraphs
+pub trait Transport<Id> {
+    /// Send a message from one factorgraph to another
+    fn send(&mut self, message: ExternalMessage<Id>);
+
+    /// Take the messages that have arrived at their receiver since the last
+    /// call
+    fn deliver(&mut self) -> Vec<ExternalMessage<Id>>;
+}
+
+/// A [`Transport`] delivering every message as soon as it is sent
+#[derive(Debug)]
+pub struct Immediate<Id> {
+    in_flight: Vec<ExternalMessage<Id>>,
+}
+
+impl<Id> Default for Immediate<Id> {
+    fn default() -> Self {
+        Self {
+            in_flight: Vec::new(),
+        }
+    }
+}
+
+impl<Id> Transport<Id> for Immediate<Id> {
+    fn send(&mut self, message: ExternalMessage<Id>) {
+        self.in_flight.push(message);
+    }
+
+    fn deliver(&mut self) -> Vec<ExternalMessage<Id>> {
+        std::mem::take(&mut self.in_flight)
+    }
+}
+
+/// A factorgraph taking part in an iteration
+#[derive(Debug)]
+pub struct Participant<'a, Id> {
+    /// The factorgraph to iterate
+    pub factorgraph:  &'a mut FactorGraph<Id>,
+    /// Whether the factorgraph is iterated at all, e.g. `false` for a robot
+    /// that has completed its mission
+    pub active:       bool,
+    /// Whether the factorgraph sends and receives external messages, e.g.
+    /// `false` for a robot with its radio turned off
+    pub communicates: bool,
+}
+
+impl<'a, Id> Participant<'a, Id> {
+    /// A participant that is active and communicates
+    pub fn new(factorgraph: &'a mut FactorGraph<Id>) -> Self {
+        Self {
+            factorgraph,
+            active: true,
+            communicates: true,
+        }
+    }
+}
+
+/// Start a new timestep in every active factorgraph, see
+/// [`FactorGraph::start_timestep`]
+pub fn start_timestep<Id: GraphId>(participants: &mut [Participant<'_, Id>]) {
+    for participant in participants.iter_mut().filter(|p| p.active) {
+        participant.factorgraph.start_timestep();
+    }
+}
+
+/// Run an internal iteration in every active factorgraph.
+/// Factorgraphs whose beliefs have converged in the current timestep are
+/// skipped.
+pub fn internal_iteration<Id: GraphId>(participants: &mut [Participant<'_, Id>]) {
+    for participant in participants.iter_mut() {
+        if participant.active && !participant.factorgraph.converged() {
+            participant.factorgraph.internal_iteration();
+        }
+    }
+}
+
+/// Run an external iteration between every active factorgraph that
+/// communicates.
+///
+/// First the interrobot factors send their messages to the external
+/// variables, then the variables send their messages to the external
+/// interrobot factors. Messages delivered by `transport` are given to their
+/// receiver after each half, and discarded if the receiver is not taking
+/// part, is inactive or does not communicate.
+pub fn external_iteration<Id: GraphId>(
+    participants: &mut [Participant<'_, Id>],
+    transport: &mut impl Transport<Id>,
+) {
+    let index_of: HashMap<Id, usize> = participants
+        .iter()
+        .enumerate()
+        .map(|(i, participant)| (participant.factorgraph.id(), i))
+        .collect();
+
+    for participant in participants.iter_mut() {
+        if participant.active && participant.communicates {
+            for message in participant.factorgraph.external_factor_iteration() {
+                transport.send(message.into());
+            }
+        }
+    }
+    deliver(participants, &index_of, transport.deliver());
+
+    for participant in participants.iter_mut() {
+        if participant.active && participant.communicates {
+            for message in participant.factorgraph.external_variable_iteration() {
+                transport.send(message.into());
+            }
+        }
+    }
+    deliver(participants, &index_of, transport.deliver());
+}
+
+/// Give every message to the factorgraph it is sent to
+fn deliver<Id: GraphId>(
+    participants: &mut [Participant<'_, Id>],
+    index_of: &HashMap<Id, usize>,
+    messages: Vec<ExternalMessage<Id>>,
+) {
+    for message in messages {
+        let (_, receiver) = message.link();
+        let Some(&index) = index_of.get(&receiver) else {
+            continue;
+        };
+        let participant = &mut participants[index];
+        if participant.active && participant.communicates {
+            participant.factorgraph.receive_external_message(message);
+        }
+    }
+}
+
+/// Run a timestep of GBP iterations, following `schedule`
+pub fn iterate<Id: GraphId>(
+    participants: &mut [Participant<'_, Id>],
+    schedule: impl IntoIterator<Item = GbpScheduleAtIteration>,
+    transport: &mut impl Transport<Id>,
+) {
+    start_timestep(participants);
+    for GbpScheduleAtIteration { internal, external } in schedule {
+        if internal {
+            internal_iteration(participants);
+        }
+        if external {
+            external_iteration(participants, transport);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gbp_linalg::prelude::*;
+    use ndarray::array;
+
+    use super::*;
+    use crate::{
+        config::{ConvergenceCriterion, ConvergenceSection},
+        factor::FactorNode,
+        factorgraph::{FactorIndex, NodeIndex, VariableIndex},
+        id::{FactorId, VariableId},
+        message::{FactorToVariableMessage, Message},
+        state::StateLayout,
+        variable::VariableNode,
+        DOFS,
+    };
+
+    /// Two variables connected by a dynamic factor
+    fn two_variables(id: u32) -> FactorGraph<u32> {
+        let mut factorgraph = FactorGraph::new(id);
+        let variables = [0.0, 1.0].map(|x| {
+            let variable = VariableNode::new(
+                id,
+                array![x, 0.0, 1.0, 0.0],
+                Matrix::<Float>::eye(DOFS),
+                StateLayout::Planar,
+            );
+            factorgraph.add_variable(variable)
+        });
+        let factor = FactorNode::new_dynamic_factor(
+            id,
+            StateLayout::Planar,
+            1.0,
+            Vector::<Float>::zeros(DOFS),
+            1.0,
+            true,
+        );
+        let factor = FactorId::new(id, factorgraph.add_factor(factor));
+        for variable in variables {
+            let _ = factorgraph.add_internal_edge(VariableId::new(id, variable), factor);
+        }
+        factorgraph
+    }
+
+    fn message_from(sender: u32) -> ExternalMessage<u32> {
+        ExternalMessage::ToVariable(FactorToVariableMessage {
+            from:    FactorId::new(sender, FactorIndex(NodeIndex::new(0))),
+            to:      VariableId::new(1, VariableIndex(NodeIndex::new(0))),
+            message: Message::empty(),
+        })
+    }
+
+    #[test]
+    fn immediate_delivers_in_order() {
+        let mut transport = Immediate::default();
+        transport.send(message_from(7));
+        transport.send(message_from(8));
+
+        let senders = transport
+            .deliver()
+            .iter()
+            .map(|message| message.link().0)
+            .collect::<Vec<_>>();
+        assert_eq!(senders, vec![7, 8]);
+        assert!(transport.deliver().is_empty());
+    }
+
+    #[test]
+    fn messages_from_unconnected_factors_are_discarded() {
+        let mut factorgraph = two_variables(1);
+        assert!(!factorgraph.receive_external_message(message_from(2)));
+    }
+
+    #[test]
+    fn external_messages_resume_converged_graphs() {
+        let mut factorgraph = two_variables(1);
+        factorgraph.add_external_edge(FactorId::new(2, FactorIndex(NodeIndex::new(0))), 0);
+        factorgraph.set_convergence(ConvergenceSection {
+            criterion: ConvergenceCriterion::MeanChange,
+            tolerance: 1e6.try_into().expect("the tolerance is positive"),
+        });
+
+        factorgraph.start_timestep();
+        factorgraph.internal_iteration();
+        assert!(factorgraph.converged());
+
+        assert!(factorgraph.receive_external_message(message_from(2)));
+        assert!(!factorgraph.converged());
+
+        factorgraph.internal_iteration();
+        assert!(factorgraph.converged());
+        assert_eq!(factorgraph.iteration_statistics().converged_timesteps, 1);
+    }
+
+    #[test]
+    fn inactive_graphs_are_not_iterated() {
+        let mut a = two_variables(1);
+        let mut b = two_variables(2);
+
+        let mut participants = [Participant::new(&mut a), Participant {
+            active: false,
+            ..Participant::new(&mut b)
+        }];
+        iterate(
+            &mut participants,
+            [GbpScheduleAtIteration {
+                internal: true,
+                external: true,
+            }],
+            &mut Immediate::default(),
+        );
+
+        assert_eq!(a.iteration_statistics().timesteps, 1);
+        assert_eq!(a.iteration_statistics().internal, 1);
+        assert_eq!(b.iteration_statistics().timesteps, 0);
+        assert_eq!(b.iteration_statistics().internal, 0);
+    }
+}
@@ -0,0 +1,485 @@
+//! Message module.
+//!
+//! Contains the message struct that variables and factors send between each
+//! other in the factorgraph.
+
+use std::collections::BTreeMap;
+
+use gbp_linalg::{
+    fixed::{self, SMatrix, SVector},
+    prelude::*,
+};
+
+use super::id::{FactorId, GraphId, VariableId};
+use crate::DOFS;
+
+// PERF: it seems the payload size is always the same no matter how many
+// external messages there are to be sent
+/// Payload of a message
+#[derive(Debug, Clone)]
+pub struct Payload {
+    /// Information vector of a multivariate gaussian
+    pub information_vector: Vector<Float>,
+    /// Precision matrix of a multivariate gaussian
+    pub precision_matrix: Matrix<Float>,
+    /// Mean vector of a multivariate gaussian
+    /// The mean can be computed from the information vector and the precision
+    /// matrix but the mean vector is stored here to trade some memory, for
+    /// having to compute it multiple times
+    pub mean: Vector<Float>,
+}
+
+/// Newtype used to make prevent the caller of `Message::new()` from mixing up
+/// the information vector and mean vector argument.
+pub struct InformationVec(pub Vector<Float>);
+/// Newtype used to make make it clear for the caller that the matrix argument
+/// for `Message::new()` has to be a precision matrix, and not a covariance.
+pub struct PrecisionMatrix(pub Matrix<Float>);
+/// Newtype used to make prevent the caller of `Message::new()` from mixing up
+/// the information vector and mean vector argument.
+pub struct Mean(pub Vector<Float>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageOrigin {
+    Internal,
+    External,
+}
+
+/// Container for the message exchanged between nodes in the factorgraph
+#[derive(Debug, Clone)]
+pub struct Message {
+    // payload: Option<Payload>,
+    payload: Option<Box<Payload>>,
+    // pub origin: MessageOrigin,
+}
+
+impl Message {
+    /// Returns a reference to the mean
+    /// or `None` if the message is empty.
+    #[inline]
+    pub fn mean(&self) -> Option<&Vector<Float>> {
+        self.payload.as_ref().map(|payload| &payload.mean)
+    }
+
+    /// Returns a reference to the precision matrix
+    /// or `None` if the message is empty.
+    #[inline]
+    pub fn precision_matrix(&self) -> Option<&Matrix<Float>> {
+        self.payload
+            .as_ref()
+            .map(|payload| &payload.precision_matrix)
+    }
+
+    /// Returns a reference to the information vector
+    /// or `None` if the message is empty.
+    #[inline]
+    pub fn information_vector(&self) -> Option<&Vector<Float>> {
+        self.payload
+            .as_ref()
+            .map(|payload| &payload.information_vector)
+    }
+
+    /// Returns `true` if the message is [`Empty`].
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.payload.is_none()
+    }
+
+    /// Take the inner `MultivariateNormal` from the message.
+    /// Leaving the message in an empty state.
+    #[inline]
+    pub fn take(&mut self) -> Option<Box<Payload>> {
+        self.payload.take()
+    }
+
+    #[must_use]
+    pub fn acquire(&mut self) -> Self {
+        Self {
+            payload: self.payload.take(),
+        }
+    }
+
+    /// Access the payload of the message.
+    /// Returns `None` if the message is empty.
+    #[inline]
+    pub fn payload(&self) -> Option<&Payload> {
+        self.payload.as_deref()
+
+        // self.payload.as_ref().as_deref()
+    }
+
+    /// Return the size in bytes of the payload
+    pub fn size_of_payload(&self) -> usize {
+        self.payload.as_ref().map_or(0, |p| {
+            // p.information_vector.len()
+            (p.information_vector.len()
+                + (p.precision_matrix.nrows() * p.precision_matrix.ncols())
+                + p.mean.len())
+                * std::mem::size_of::<Float>()
+        })
+    }
+
+    /// Create a message of zeros, to a variable with `dofs` degrees of freedom
+    pub fn zero(dofs: usize) -> Self {
+        Self {
+            payload: Some(Box::new(Payload {
+                information_vector: Vector::<Float>::zeros(dofs),
+                precision_matrix: Matrix::<Float>::zeros((dofs, dofs)),
+                mean: Vector::<Float>::zeros(dofs),
+            })),
+        }
+    }
+
+    /// Create an empty message
+    // PERF(kpbaks): set to None instead
+    #[must_use]
+    pub fn empty(/*origin: MessageOrigin*/) -> Self {
+        Self { payload: None }
+        // Self {
+        //     payload: Some(Payload {
+        //         information_vector: Vector::<Float>::zeros(DOFS),
+        //         precision_matrix: Matrix::<Float>::zeros((DOFS, DOFS)),
+        //         mean: Vector::<Float>::zeros(DOFS),
+        //     }),
+        //     // origin,
+        // }
+    }
+
+    /// Create a new message
+    ///
+    /// # Panics
+    ///
+    /// - if `lam.0` is not a square matrix
+    /// - if `eta.0.len()` or `mu.0.len()` differ from the size of `lam.0`
+    #[must_use]
+    pub fn new(
+        information_vector: InformationVec,
+        precision_matrix: PrecisionMatrix,
+        mean: Mean, // , origin: MessageOrigin
+    ) -> Self {
+        let dofs = precision_matrix.0.nrows();
+        debug_assert_eq!(precision_matrix.0.ncols(), dofs);
+        debug_assert_eq!(information_vector.0.len(), dofs);
+        debug_assert_eq!(mean.0.len(), dofs);
+
+        Self {
+            payload: Some(Box::new(Payload {
+                information_vector: information_vector.0,
+                precision_matrix: precision_matrix.0,
+                mean: mean.0,
+            })),
+            // origin,
+        }
+    }
+
+    /// Damp the message towards the `previous` message sent over the same edge.
+    /// The result is the convex combination `(1 - damping) * self + damping *
+    /// previous` in information form, with the mean `Λ⁻¹η` of the damped
+    /// information. Like the messages from factors, the mean is zero if the
+    /// damped precision matrix is singular. Returns the message unchanged if
+    /// either message is empty.
+    #[must_use]
+    pub fn damped(self, previous: &Self, damping: Float) -> Self {
+        let (Some(payload), Some(previous)) = (self.payload(), previous.payload()) else {
+            return self;
+        };
+
+        let information_vector =
+            (1.0 - damping) * &payload.information_vector + damping * &previous.information_vector;
+        let precision_matrix =
+            (1.0 - damping) * &payload.precision_matrix + damping * &previous.precision_matrix;
+        let mean = precision_matrix
+            .cholesky_inverse()
+            .map(|covariance| covariance.dot(&information_vector))
+            .filter(|mean| mean.iter().all(|x| x.is_finite()))
+            .unwrap_or_else(|| Vector::<Float>::zeros(information_vector.len()));
+
+        Self::new(
+            InformationVec(information_vector),
+            PrecisionMatrix(precision_matrix),
+            Mean(mean),
+        )
+    }
+
+    /// Scale the precision of the message by `factor`, keeping the mean.
+    /// A factor above 1.0 makes the receiver more certain of the message, and
+    /// below 1.0 less certain. Returns the message unchanged if it is empty.
+    #[must_use]
+    pub fn scaled(mut self, factor: Float) -> Self {
+        if let Some(payload) = self.payload.as_deref_mut() {
+            payload.information_vector *= factor;
+            payload.precision_matrix *= factor;
+        }
+        self
+    }
+}
+
+/// Payload of a message between a factor and a variable with [`DOFS`] degrees
+/// of freedom, stored on the stack.
+///
+/// Factors between two such variables compute their messages as
+/// [`FixedMessage`]s, and only convert them to a [`Message`] when they are
+/// sent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedMessage {
+    /// Information vector of a multivariate gaussian
+    pub information_vector: SVector<DOFS>,
+    /// Precision matrix of a multivariate gaussian
+    pub precision_matrix: SMatrix<DOFS, DOFS>,
+    /// Mean vector of a multivariate gaussian
+    pub mean: SVector<DOFS>,
+}
+
+impl FixedMessage {
+    /// Copy the payload of `message` onto the stack.
+    /// Returns `None` if the message is empty, or not to or from a variable
+    /// with [`DOFS`] degrees of freedom.
+    pub fn of(message: &Message) -> Option<Self> {
+        let payload = message.payload()?;
+        Some(Self {
+            information_vector: fixed::fixed_vector(payload.information_vector.view())?,
+            precision_matrix: fixed::fixed_matrix(payload.precision_matrix.view())?,
+            mean: fixed::fixed_vector(payload.mean.view())?,
+        })
+    }
+
+    /// Same as [`Message::damped`]
+    #[must_use]
+    pub fn damped(self, previous: &Self, damping: Float) -> Self {
+        let information_vector =
+            (1.0 - damping) * self.information_vector + damping * previous.information_vector;
+        let precision_matrix =
+            (1.0 - damping) * self.precision_matrix + damping * previous.precision_matrix;
+        let mean = fixed::invert_spd(&precision_matrix)
+            .map(|covariance| covariance * information_vector)
+            .filter(|mean| mean.iter().all(|x| x.is_finite()))
+            .unwrap_or_else(SVector::zeros);
+
+        Self {
+            information_vector,
+            precision_matrix,
+            mean,
+        }
+    }
+
+    /// Same as [`Message::scaled`]
+    #[must_use]
+    pub fn scaled(mut self, factor: Float) -> Self {
+        self.information_vector *= factor;
+        self.precision_matrix *= factor;
+        self
+    }
+}
+
+impl From<FixedMessage> for Message {
+    fn from(message: FixedMessage) -> Self {
+        Self::new(
+            InformationVec(fixed::ndarray_vector(&message.information_vector)),
+            PrecisionMatrix(fixed::ndarray_matrix(&message.precision_matrix)),
+            Mean(fixed::ndarray_vector(&message.mean)),
+        )
+    }
+}
+
+// TODO: add some kind of `stale: bool` or `used: bool` field
+
+/// A message from a factor to a variable
+#[derive(Debug)]
+pub struct VariableToFactorMessage<Id> {
+    /// The factor that sends the message
+    pub from:    VariableId<Id>,
+    /// The variable that receives the message
+    pub to:      FactorId<Id>,
+    /// The message
+    pub message: Message,
+}
+
+/// A message from a variable to a factor
+#[derive(Debug)]
+pub struct FactorToVariableMessage<Id> {
+    /// The variable that sends the message
+    pub from:    FactorId<Id>,
+    /// The factor that receives the message
+    pub to:      VariableId<Id>,
+    /// The message
+    pub message: Message,
+}
+
+/// A message sent from one factorgraph to another
+#[derive(Debug)]
+pub enum ExternalMessage<Id> {
+    /// From an interrobot factor to the external variable it is connected to
+    ToVariable(FactorToVariableMessage<Id>),
+    /// From a variable to an external interrobot factor it is connected to
+    ToFactor(VariableToFactorMessage<Id>),
+}
+
+impl<Id: GraphId> ExternalMessage<Id> {
+    /// The directed link the message is sent over, i.e. `(sender, receiver)`
+    pub const fn link(&self) -> (Id, Id) {
+        match self {
+            Self::ToVariable(message) => (message.from.factorgraph_id, message.to.factorgraph_id),
+            Self::ToFactor(message) => (message.from.factorgraph_id, message.to.factorgraph_id),
+        }
+    }
+}
+
+impl<Id> From<FactorToVariableMessage<Id>> for ExternalMessage<Id> {
+    fn from(message: FactorToVariableMessage<Id>) -> Self {
+        Self::ToVariable(message)
+    }
+}
+
+impl<Id> From<VariableToFactorMessage<Id>> for ExternalMessage<Id> {
+    fn from(message: VariableToFactorMessage<Id>) -> Self {
+        Self::ToFactor(message)
+    }
+}
+
+// pub type MessagesFromVariables = BTreeMap<FactorId, Message>;
+// pub type MessagesFromFactors = BTreeMap<VariableId, Message>;
+
+// PERF(kpbaks): use a indexmap or slotmap to improve performance
+
+/// Type alias for a map of messages from factors connected to a variable
+/// A (`BTreeMap`)[`std::collections::BTreeMap`] is used, instead of a
+/// (`HashMap`)[`std::collections::HashMap`] to ensure that the messages are
+/// stored in a consistent order This is necessary for the **gbpplanner**
+/// algorithm to work correctly.
+pub type MessagesToFactors<Id> = BTreeMap<FactorId<Id>, Message>;
+
+/// Type alias for a map of messages from variables connected to a factor
+/// A (`BTreeMap`)[`std::collections::BTreeMap`] is used, instead of a
+/// (`HashMap`)[`std::collections::HashMap`] to ensure that the messages are
+/// stored in a consistent order This is necessary for the **gbpplanner**
+/// algorithm to work correctly.
+pub type MessagesToVariables<Id> = BTreeMap<VariableId<Id>, Message>;
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    fn message(value: Float) -> Message {
+        Message::new(
+            InformationVec(Vector::<Float>::from_elem(DOFS, value)),
+            PrecisionMatrix(Matrix::<Float>::eye(DOFS) * value),
+            Mean(Vector::<Float>::zeros(DOFS)),
+        )
+    }
+
+    #[test]
+    fn damped_is_convex_combination() {
+        let damped = message(4.0).damped(&message(2.0), 0.25);
+        for &x in damped.information_vector().expect("not empty") {
+            assert_relative_eq!(x, 3.5);
+        }
+        assert_relative_eq!(damped.precision_matrix().expect("not empty")[(0, 0)], 3.5);
+        assert_relative_eq!(damped.precision_matrix().expect("not empty")[(0, 1)], 0.0);
+    }
+
+    #[test]
+    fn damped_mean_is_the_mean_of_the_damped_information() {
+        let with_mean = |precision: Float, mean: Float| {
+            let precision_matrix = Matrix::<Float>::eye(DOFS) * precision;
+            let mean = Vector::<Float>::from_elem(DOFS, mean);
+            Message::new(
+                InformationVec(precision_matrix.dot(&mean)),
+                PrecisionMatrix(precision_matrix),
+                Mean(mean),
+            )
+        };
+
+        let damped = with_mean(4.0, 1.0).damped(&with_mean(2.0, 3.0), 0.25);
+        let covariance = damped
+            .precision_matrix()
+            .expect("not empty")
+            .cholesky_inverse()
+            .expect("the precision matrix is positive definite");
+        let mean = covariance.dot(damped.information_vector().expect("not empty"));
+        for (&x, &expected) in damped.mean().expect("not empty").iter().zip(&mean) {
+            assert_relative_eq!(x, expected, epsilon = 1e-12);
+            // (0.75 * 4 + 0.25 * 6) / (0.75 * 4 + 0.25 * 2), not 0.75 * 1 + 0.25 * 3
+            assert_relative_eq!(x, 4.5 / 3.5, epsilon = 1e-12);
+        }
+
+        let singular = message(0.0).damped(&message(0.0), 0.5);
+        for &x in singular.mean().expect("not empty") {
+            assert_relative_eq!(x, 0.0);
+        }
+    }
+
+    #[test]
+    fn damped_towards_empty_is_unchanged() {
+        let damped = message(4.0).damped(&Message::empty(), 0.5);
+        assert_relative_eq!(damped.precision_matrix().expect("not empty")[(0, 0)], 4.0);
+        assert!(Message::empty().damped(&message(4.0), 0.5).is_empty());
+    }
+
+    #[test]
+    fn scaled_keeps_the_mean() {
+        let mut message = message(4.0);
+        message
+            .payload
+            .as_deref_mut()
+            .expect("not empty")
+            .mean
+            .fill(1.0);
+        let scaled = message.scaled(0.5);
+        assert_relative_eq!(scaled.precision_matrix().expect("not empty")[(0, 0)], 2.0);
+        assert_relative_eq!(scaled.information_vector().expect("not empty")[0], 2.0);
+        assert_relative_eq!(scaled.mean().expect("not empty")[0], 1.0);
+        assert!(Message::empty().scaled(0.5).is_empty());
+    }
+
+    #[test]
+    fn fixed_message_matches_message() {
+        let with_mean = |precision: Float, mean: Float| {
+            let precision_matrix = Matrix::<Float>::eye(DOFS) * precision;
+            let mean = Vector::<Float>::from_elem(DOFS, mean);
+            Message::new(
+                InformationVec(precision_matrix.dot(&mean)),
+                PrecisionMatrix(precision_matrix),
+                Mean(mean),
+            )
+        };
+        let (current, previous) = (with_mean(4.0, 1.0), with_mean(2.0, 3.0));
+
+        let fixed = FixedMessage::of(&current)
+            .expect("not empty")
+            .scaled(0.5)
+            .damped(&FixedMessage::of(&previous).expect("not empty"), 0.25);
+        let expected = current.scaled(0.5).damped(&previous, 0.25);
+        let actual = Message::from(fixed);
+
+        let (actual, expected) = (
+            actual.payload().expect("not empty"),
+            expected.payload().expect("not empty"),
+        );
+        for (a, e) in actual
+            .information_vector
+            .iter()
+            .zip(&expected.information_vector)
+        {
+            assert_relative_eq!(a, e, epsilon = 1e-12);
+        }
+        for (a, e) in actual
+            .precision_matrix
+            .iter()
+            .zip(&expected.precision_matrix)
+        {
+            assert_relative_eq!(a, e, epsilon = 1e-12);
+        }
+        for (a, e) in actual.mean.iter().zip(&expected.mean) {
+            assert_relative_eq!(a, e, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn fixed_message_of_empty_or_other_dimensions_is_none() {
+        assert_eq!(FixedMessage::of(&Message::empty()), None);
+        assert_eq!(FixedMessage::of(&Message::zero(DOFS + 2)), None);
+        assert!(FixedMessage::of(&Message::zero(DOFS)).is_some());
+    }
+}
@@ -1,6 +1,5 @@
 use super::{
-    factor::FactorNode, factorgraph::FactorGraphId, variable::VariableNode, MessagesReceived,
-    MessagesSent,
+    factor::FactorNode, id::GraphId, variable::VariableNode, MessagesReceived, MessagesSent,
 };
 
 /// Error type returned by `FactorGraphNode::remove_connection_to`
@@ -10,11 +9,8 @@ pub struct RemoveConnectionToError;
 
 impl std::error::Error for RemoveConnectionToError {}
 
-pub(in crate::factorgraph) trait FactorGraphNode {
-    fn remove_connection_to(
-        &mut self,
-        factorgraph_id: FactorGraphId,
-    ) -> Result<(), RemoveConnectionToError>;
+pub(crate) trait FactorGraphNode<Id> {
+    fn remove_connection_to(&mut self, factorgraph_id: Id) -> Result<(), RemoveConnectionToError>;
 
     fn messages_sent(&self) -> MessagesSent;
     fn messages_received(&self) -> MessagesReceived;
@@ -24,25 +20,25 @@ pub(in crate::factorgraph) trait FactorGraphNode {
 
 /// Different variants a factorgraph node can be
 #[derive(Debug, derive_more::IsVariant, strum_macros::EnumTryAs)]
-pub enum NodeKind {
+pub enum NodeKind<Id> {
     /// The node is a factor
-    Factor(FactorNode),
+    Factor(FactorNode<Id>),
     // TODO: wrap in Box<>
     /// The node is a variable
-    Variable(VariableNode),
+    Variable(VariableNode<Id>),
 }
 
 /// The node stored in the factorgraph
 #[derive(Debug)]
-pub struct Node {
-    // factorgraph_id: FactorGraphId,
+pub struct Node<Id> {
+    // factorgraph_id: Id,
     /// The kind of this node, either Variable or some Factor
-    pub kind: NodeKind,
+    pub kind: NodeKind<Id>,
 }
 
-impl Node {
+impl<Id: GraphId> Node<Id> {
     /// Construct a new node
-    pub const fn new(factorgraph_id: FactorGraphId, kind: NodeKind) -> Self {
+    pub const fn new(factorgraph_id: Id, kind: NodeKind<Id>) -> Self {
         Self { kind }
         // Self { factorgraph_id, kind }
     }
@@ -62,7 +58,7 @@ impl Node {
     ///
     /// Panics if the node is not a factor
     #[inline]
-    pub fn factor(&self) -> &FactorNode {
+    pub fn factor(&self) -> &FactorNode<Id> {
         self.as_factor().expect("The node should be a Factor")
     }
 
@@ -72,13 +68,13 @@ impl Node {
     ///
     /// Panics if the node is not a factor
     #[inline]
-    pub fn factor_mut(&mut self) -> &mut FactorNode {
+    pub fn factor_mut(&mut self) -> &mut FactorNode<Id> {
         self.as_factor_mut().expect("The node should be a Factor")
     }
 
     /// Returns `Some(&Factor)` if the node]s variant is [`Factor`], otherwise
     /// `None`.
-    pub const fn as_factor(&self) -> Option<&FactorNode> {
+    pub const fn as_factor(&self) -> Option<&FactorNode<Id>> {
         if let NodeKind::Factor(ref v) = self.kind {
             Some(v)
         } else {
@@ -88,7 +84,7 @@ impl Node {
 
     /// Returns `Some(&mut Factor)` if the node]s variant is [`Factor`],
     /// otherwise `None`.
-    pub fn as_factor_mut(&mut self) -> Option<&mut FactorNode> {
+    pub fn as_factor_mut(&mut self) -> Option<&mut FactorNode<Id>> {
         if let NodeKind::Factor(ref mut v) = self.kind {
             Some(v)
         } else {
@@ -107,7 +103,7 @@ impl Node {
 
     /// Returns `Some(&Variable)` if the node]s variant is [`Variable`],
     /// otherwise `None`.
-    pub const fn as_variable(&self) -> Option<&VariableNode> {
+    pub const fn as_variable(&self) -> Option<&VariableNode<Id>> {
         if let NodeKind::Variable(ref v) = self.kind {
             Some(v)
         } else {
@@ -117,7 +113,7 @@ impl Node {
 
     /// Returns `Some(&mut Variable)` if the node]s variant is [`Variable`],
     /// otherwise `None`.
-    pub fn as_variable_mut(&mut self) -> Option<&mut VariableNode> {
+    pub fn as_variable_mut(&mut self) -> Option<&mut VariableNode<Id>> {
         if let NodeKind::Variable(ref mut v) = self.kind {
             Some(v)
         } else {
@@ -131,7 +127,7 @@ impl Node {
     ///
     /// Panics if the node is not a variable
     #[inline]
-    pub fn variable(&self) -> &VariableNode {
+    pub fn variable(&self) -> &VariableNode<Id> {
         self.as_variable().expect("The node should be a Variable")
     }
 
@@ -141,17 +137,14 @@ impl Node {
     ///
     /// Panics if the node is not a variable
     #[inline]
-    pub fn variable_mut(&mut self) -> &mut VariableNode {
+    pub fn variable_mut(&mut self) -> &mut VariableNode<Id> {
         self.as_variable_mut()
             .expect("The node should be a Variable")
     }
 }
 
-impl FactorGraphNode for Node {
-    fn remove_connection_to(
-        &mut self,
-        factorgraph_id: FactorGraphId,
-    ) -> Result<(), RemoveConnectionToError> {
+impl<Id: GraphId> FactorGraphNode<Id> for Node<Id> {
+    fn remove_connection_to(&mut self, factorgraph_id: Id) -> Result<(), RemoveConnectionToError> {
         match self.kind {
             NodeKind::Factor(ref mut factor) => factor.remove_connection_to(factorgraph_id),
             NodeKind::Variable(ref mut variable) => variable.remove_connection_to(factorgraph_id),
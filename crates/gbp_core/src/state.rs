@@ -0,0 +1,254 @@
+//! Layout of the state of a variable.
+//!
+//! Every variable in a factorgraph stores the state of the robot at one
+//! timestep, as the configuration of the robot followed by its first
+//! derivative. The [`StateLayout`] decides what the configuration is:
+//!
+//! | layout          | state                      | dofs |
+//! |-----------------|----------------------------|------|
+//! | `planar`        | `[x, y, x', y']`           | 4    |
+//! | `spatial`       | `[x, y, z, x', y', z']`    | 6    |
+//! | `planar-pose`   | `[x, y, θ, x', y', θ']`    | 6    |
+//!
+//! `x` and `y` are always the first two elements of the state, so factors
+//! that only care about the position in the plane, i.e. the obstacle, dynamic
+//! obstacle and tracking factors, measure the same for every layout. The
+//! dynamic factor applies its constant velocity model to every element of the
+//! configuration, and the interrobot factor measures the distance between the
+//! positions of two robots, which is 3D for the `spatial` layout.
+
+use gbp_linalg::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Layout of the state of every variable in a factorgraph, see the
+/// [module documentation](self)
+///
+/// ## Example
+/// ```toml
+/// [gbp]
+/// state-layout = "spatial"
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StateLayout {
+    /// Position and velocity in the plane, `[x, y, x', y']`
+    #[default]
+    Planar,
+    /// Position and velocity in 3D, `[x, y, z, x', y', z']`
+    Spatial,
+    /// Position and heading in the plane, with their velocities,
+    /// `[x, y, θ, x', y', θ']`
+    PlanarPose,
+}
+
+impl StateLayout {
+    /// Degrees of freedom of a variable, i.e. the length of its state
+    #[inline]
+    #[must_use]
+    pub const fn dofs(self) -> usize {
+        2 * self.configuration_dims()
+    }
+
+    /// Number of elements of the configuration, the first half of the state
+    #[inline]
+    #[must_use]
+    pub const fn configuration_dims(self) -> usize {
+        match self {
+            Self::Planar => 2,
+            Self::Spatial | Self::PlanarPose => 3,
+        }
+    }
+
+    /// Number of elements of the position, at the start of the configuration
+    #[inline]
+    #[must_use]
+    pub const fn position_dims(self) -> usize {
+        match self {
+            Self::Planar | Self::PlanarPose => 2,
+            Self::Spatial => 3,
+        }
+    }
+
+    /// Index of the velocity in the state, the first element after the
+    /// configuration
+    #[inline]
+    #[must_use]
+    pub const fn velocity_offset(self) -> usize {
+        self.configuration_dims()
+    }
+
+    /// Create a state from a position and velocity in the plane.
+    /// The altitude of the `spatial` layout is 0, and the heading of the
+    /// `planar-pose` layout points along the velocity, with no angular
+    /// velocity.
+    #[must_use]
+    pub fn state_from_planar(self, position: [Float; 2], velocity: [Float; 2]) -> Vector<Float> {
+        let mut state = Vector::<Float>::zeros(self.dofs());
+        self.set_planar(&mut state, position, velocity);
+        state
+    }
+
+    /// Overwrite the position and velocity in the plane of `state`, and leave
+    /// the altitude of the `spatial` layout as is. The heading of the
+    /// `planar-pose` layout is turned to point along the velocity, unless the
+    /// velocity is 0. The heading is turned the shortest way, so it does not
+    /// jump by 2π when the velocity crosses the negative x axis.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `state` has fewer elements than [`Self::dofs`]
+    pub fn set_planar(self, state: &mut Vector<Float>, position: [Float; 2], velocity: [Float; 2]) {
+        let offset = self.velocity_offset();
+        let [vx, vy] = velocity;
+        [state[0], state[1]] = position;
+        [state[offset], state[offset + 1]] = velocity;
+        if self == Self::PlanarPose && (vx != 0.0 || vy != 0.0) {
+            state[2] = unwrap_angle(vy.atan2(vx), state[2]);
+        }
+    }
+
+    /// Position of `state` in 3D. The altitude is 0 for the planar layouts
+    ///
+    /// # Panics
+    ///
+    /// Panics if `state` has fewer elements than [`Self::dofs`]
+    #[must_use]
+    pub fn position(self, state: VectorView<Float>) -> [Float; 3] {
+        match self {
+            Self::Planar | Self::PlanarPose => [state[0], state[1], 0.0],
+            Self::Spatial => [state[0], state[1], state[2]],
+        }
+    }
+
+    /// Velocity of `state` in 3D. The vertical velocity is 0 for the planar
+    /// layouts
+    ///
+    /// # Panics
+    ///
+    /// Panics if `state` has fewer elements than [`Self::dofs`]
+    #[must_use]
+    pub fn velocity(self, state: VectorView<Float>) -> [Float; 3] {
+        let offset = self.velocity_offset();
+        match self {
+            Self::Planar | Self::PlanarPose => [state[offset], state[offset + 1], 0.0],
+            Self::Spatial => [state[offset], state[offset + 1], state[offset + 2]],
+        }
+    }
+
+    /// Heading and angular velocity of `state`, if the layout has a heading
+    ///
+    /// # Panics
+    ///
+    /// Panics if `state` has fewer elements than [`Self::dofs`]
+    #[must_use]
+    pub fn heading(self, state: VectorView<Float>) -> Option<(Float, Float)> {
+        match self {
+            Self::PlanarPose => Some((state[2], state[5])),
+            Self::Planar | Self::Spatial => None,
+        }
+    }
+}
+
+/// The angle equal to `angle` modulo 2π that is closest to `reference`
+fn unwrap_angle(angle: Float, reference: Float) -> Float {
+    use std::f64::consts::{PI, TAU};
+    reference + PI - (PI - (angle - reference)).rem_euclid(TAU)
+}
+
+impl std::fmt::Display for StateLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Planar => "planar",
+            Self::Spatial => "spatial",
+            Self::PlanarPose => "planar-pose",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::DOFS;
+
+    const LAYOUTS: [StateLayout; 3] = [
+        StateLayout::Planar,
+        StateLayout::Spatial,
+        StateLayout::PlanarPose,
+    ];
+
+    #[test]
+    fn planar_is_the_default_dofs() {
+        assert_eq!(StateLayout::default().dofs(), DOFS);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)] // the elements are copied, not computed
+    fn state_from_planar_round_trips() {
+        for layout in LAYOUTS {
+            let state = layout.state_from_planar([1.0, 2.0], [0.0, 3.0]);
+            assert_eq!(state.len(), layout.dofs(), "{layout}");
+            assert_eq!(layout.position(state.view()), [1.0, 2.0, 0.0], "{layout}");
+            assert_eq!(layout.velocity(state.view()), [0.0, 3.0, 0.0], "{layout}");
+        }
+    }
+
+    #[test]
+    fn heading_points_along_the_velocity() {
+        let layout = StateLayout::PlanarPose;
+        let state = layout.state_from_planar([0.0, 0.0], [0.0, 3.0]);
+        let (heading, angular_velocity) = layout
+            .heading(state.view())
+            .expect("the layout has a heading");
+        assert_relative_eq!(heading, std::f64::consts::FRAC_PI_2);
+        assert_relative_eq!(angular_velocity, 0.0);
+        assert_eq!(StateLayout::Spatial.heading(state.view()), None);
+    }
+
+    #[test]
+    fn set_planar_keeps_the_altitude() {
+        let layout = StateLayout::Spatial;
+        let mut state = Vector::from(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        layout.set_planar(&mut state, [-1.0, -2.0], [-4.0, -5.0]);
+        assert_eq!(state.to_vec(), vec![-1.0, -2.0, 3.0, -4.0, -5.0, 6.0]);
+    }
+
+    #[test]
+    fn heading_is_unwrapped_across_pi() {
+        let layout = StateLayout::PlanarPose;
+        let mut state = layout.state_from_planar([0.0, 0.0], [-1.0, 0.1]);
+        let (before, _) = layout
+            .heading(state.view())
+            .expect("the layout has a heading");
+        assert!(before > 3.0);
+
+        // Crossing the negative x axis turns the heading past π, instead of
+        // jumping to -π
+        layout.set_planar(&mut state, [0.0, 0.0], [-1.0, -0.1]);
+        let (after, _) = layout
+            .heading(state.view())
+            .expect("the layout has a heading");
+        assert_relative_eq!(after, std::f64::consts::TAU - before, epsilon = 1e-12);
+
+        // A full turn adds 2π
+        for i in 0..=16 {
+            let angle = after + Float::from(i) * std::f64::consts::TAU / 16.0;
+            layout.set_planar(&mut state, [0.0, 0.0], [angle.cos(), angle.sin()]);
+        }
+        let (turned, _) = layout
+            .heading(state.view())
+            .expect("the layout has a heading");
+        assert_relative_eq!(turned, after + std::f64::consts::TAU, epsilon = 1e-9);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)] // the elements are copied, not computed
+    fn spatial_position_has_altitude() {
+        let state = Vector::from(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(StateLayout::Spatial.position(state.view()), [1.0, 2.0, 3.0]);
+        assert_eq!(StateLayout::Spatial.velocity(state.view()), [4.0, 5.0, 6.0]);
+    }
+}
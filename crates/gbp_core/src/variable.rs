@@ -1,13 +1,16 @@
-use bevy::log::info;
-use gbp_linalg::{Float, Matrix, Vector};
-use ndarray_inverse::Inverse;
+//! Variables of the factorgraph, i.e. the states of the robot being planned
+
+use gbp_linalg::{fixed::CholeskyExt, Float, Matrix, Vector, VectorNorm};
+use tracing::info;
 
 use super::{
-    factorgraph::{FactorGraphId, NodeIndex},
-    id::FactorId,
+    config::ConvergenceCriterion,
+    factorgraph::NodeIndex,
+    id::{FactorId, GraphId},
     message::{InformationVec, Mean, Message, MessagesToFactors, PrecisionMatrix},
     node::{FactorGraphNode, RemoveConnectionToError},
-    MessageCount, MessagesReceived, MessagesSent, DOFS,
+    state::StateLayout,
+    MessageCount, MessagesReceived, MessagesSent,
 };
 
 /// Variable prior distribution
@@ -69,6 +72,43 @@ impl VariableBelief {
             valid,
         }
     }
+
+    /// How much the belief has changed from the `previous` belief, measured
+    /// with `criterion`. Returns [`Float::INFINITY`] if either belief is not
+    /// valid, and 0.0 for [`ConvergenceCriterion::None`]
+    #[must_use]
+    pub fn change_from(&self, previous: &Self, criterion: ConvergenceCriterion) -> Float {
+        if !self.valid || !previous.valid {
+            return Float::INFINITY;
+        }
+
+        let mean_difference = &previous.mean - &self.mean;
+        match criterion {
+            ConvergenceCriterion::None => 0.0,
+            ConvergenceCriterion::MeanChange => mean_difference.euclidean_norm(),
+            ConvergenceCriterion::KlDivergence => {
+                // KL(N(mean, covariance) || N(previous.mean, previous.covariance)), using that
+                // det(covariance) = 1 / det(precision)
+                let previous_precision_det = previous.precision_matrix.cholesky_determinant();
+                let precision_det = self.precision_matrix.cholesky_determinant();
+                if previous_precision_det <= 0.0 || precision_det <= 0.0 {
+                    return Float::INFINITY;
+                }
+
+                #[allow(clippy::cast_precision_loss)]
+                let dimensions = self.mean.len() as Float;
+                let trace = previous
+                    .precision_matrix
+                    .dot(&self.covariance_matrix)
+                    .diag()
+                    .sum();
+                let mahalanobis =
+                    mean_difference.dot(&previous.precision_matrix.dot(&mean_difference));
+                0.5 * (trace + mahalanobis - dimensions + precision_det.ln()
+                    - previous_precision_det.ln())
+            }
+        }
+    }
 }
 
 impl From<VariableBelief> for Message {
@@ -83,19 +123,21 @@ impl From<VariableBelief> for Message {
 
 /// A variable in the factor graph.
 #[derive(Debug)]
-pub struct VariableNode {
-    factorgraph_id: FactorGraphId,
+pub struct VariableNode<Id> {
+    factorgraph_id: Id,
     /// Prior distribution
-    pub prior:      VariablePrior,
+    pub prior: VariablePrior,
     /// Variables belief about its position and velocity
-    pub belief:     VariableBelief,
+    pub belief: VariableBelief,
+    /// Layout of the state stored in the prior and belief
+    layout: StateLayout,
 
     // / Flag to indicate if the variable's covariance is finite, i.e. it does
     // / not contain NaNs or Infs In gbpplanner it is used to control if a
     // / variable can be rendered.
     // pub valid: bool,
     /// Mailbox for incoming message storage
-    pub inbox: MessagesToFactors,
+    pub inbox: MessagesToFactors<Id>,
 
     /// index
     node_index: Option<NodeIndex>,
@@ -103,7 +145,7 @@ pub struct VariableNode {
     message_count: MessageCount,
 }
 
-impl VariableNode {
+impl<Id: GraphId> VariableNode<Id> {
     /// Returns the node index of the variable
     ///
     /// # Panics
@@ -116,7 +158,13 @@ impl VariableNode {
         self.node_index.unwrap()
     }
 
-    /// Returns the variables belief about its position
+    /// Returns the layout of the state of the variable
+    #[inline]
+    pub const fn layout(&self) -> StateLayout {
+        self.layout
+    }
+
+    /// Returns the variables belief about its position in the plane
     #[inline]
     pub fn estimated_position(&self) -> [Float; 2] {
         [self.belief.mean[0], self.belief.mean[1]]
@@ -124,25 +172,58 @@ impl VariableNode {
 
     /// Returns the variables belief about its position
     #[inline]
-    pub fn estimated_position_vec2(&self) -> bevy::math::Vec2 {
-        bevy::math::Vec2::new(self.belief.mean[0] as f32, self.belief.mean[1] as f32)
+    pub fn estimated_position_vec2(&self) -> glam::Vec2 {
+        glam::Vec2::new(self.belief.mean[0] as f32, self.belief.mean[1] as f32)
         // [self.belief.mean[0], self.belief.mean[1]]
     }
 
-    /// Returns the variables belief about its velocity
+    /// Returns the variables belief about its position in 3D, see
+    /// [`Self::estimated_position_3d`]
+    #[inline]
+    pub fn estimated_position_vec3(&self) -> glam::Vec3 {
+        let [x, y, z] = self.estimated_position_3d();
+        glam::Vec3::new(x as f32, y as f32, z as f32)
+    }
+
+    /// Returns the variables belief about its velocity in the plane
     #[inline]
     pub fn estimated_velocity(&self) -> [Float; 2] {
-        [self.belief.mean[2], self.belief.mean[3]]
+        let [vx, vy, _] = self.layout.velocity(self.belief.mean.view());
+        [vx, vy]
+    }
+
+    /// Returns the variables belief about its position in 3D.
+    /// The altitude is 0 for the planar state layouts
+    #[inline]
+    pub fn estimated_position_3d(&self) -> [Float; 3] {
+        self.layout.position(self.belief.mean.view())
+    }
+
+    /// Returns the variables belief about its velocity in 3D.
+    /// The vertical velocity is 0 for the planar state layouts
+    #[inline]
+    pub fn estimated_velocity_3d(&self) -> [Float; 3] {
+        self.layout.velocity(self.belief.mean.view())
+    }
+
+    /// Returns the variables belief about its heading and angular velocity, if
+    /// the state layout has a heading
+    #[inline]
+    pub fn estimated_heading(&self) -> Option<(Float, Float)> {
+        self.layout.heading(self.belief.mean.view())
     }
 
-    /// Construct a new variable
+    /// Construct a new variable, with a state of the given `layout`
     #[must_use]
     pub fn new(
-        factorgraph_id: FactorGraphId,
+        factorgraph_id: Id,
         prior_mean: Vector<Float>,
         mut prior_precision_matrix: Matrix<Float>,
-        dofs: usize,
+        layout: StateLayout,
     ) -> Self {
+        let dofs = layout.dofs();
+        debug_assert_eq!(prior_mean.len(), dofs);
+
         if !prior_precision_matrix.iter().all(|x| x.is_finite()) {
             prior_precision_matrix.fill(0.0);
         }
@@ -150,7 +231,7 @@ impl VariableNode {
         let eta_prior = prior_precision_matrix.dot(&prior_mean);
 
         let sigma = prior_precision_matrix
-            .inv()
+            .cholesky_inverse()
             .unwrap_or_else(|| Matrix::<Float>::zeros((dofs, dofs)));
         let eta = eta_prior.clone();
         let lam = prior_precision_matrix.clone();
@@ -159,6 +240,7 @@ impl VariableNode {
             factorgraph_id,
             prior: VariablePrior::new(eta_prior, prior_precision_matrix),
             belief: VariableBelief::new(eta, lam, prior_mean, sigma),
+            layout,
             inbox: MessagesToFactors::new(),
             node_index: None,
             message_count: MessageCount::default(),
@@ -176,7 +258,7 @@ impl VariableNode {
     }
 
     /// Receives a message from a factor
-    pub fn receive_message_from(&mut self, from: FactorId, message: Message) {
+    pub fn receive_message_from(&mut self, from: FactorId<Id>, message: Message) {
         // debug!("variable ? received message from {:?}", from);
         if message.is_empty() {
             // warn!("Empty message received from factor {:?}", from);
@@ -200,14 +282,14 @@ impl VariableNode {
     /// It updates the belief of the variable.
     /// The prior acts as the pose factor
     /// Called `Variable::change_variable_prior` in **gbpplanner**
-    pub fn change_prior(&mut self, mean: &Vector<Float>) -> MessagesToFactors {
+    pub fn change_prior(&mut self, mean: &Vector<Float>) -> MessagesToFactors<Id> {
         self.prior.information_vector = self.prior.precision_matrix.dot(mean);
         // self.belief.mean = mean;
         self.belief.mean.clone_from(mean);
 
         let mut messages_sent = MessagesSent::new();
 
-        let messages: MessagesToFactors = self
+        let messages: MessagesToFactors<Id> = self
             .inbox
             .keys()
             .map(|factor_id| {
@@ -248,7 +330,7 @@ impl VariableNode {
     // *******************************************************/
     /// Variable Belief Update step (Step 1 in the GBP algorithm)
     /// called `Variable::update_belief` in **gbpplanner**
-    pub fn update_belief_and_create_factor_responses(&mut self) -> MessagesToFactors {
+    pub fn update_belief_and_create_factor_responses(&mut self) -> MessagesToFactors<Id> {
         // Collect messages from all other factors, begin by "collecting message from
         // pose factor prior"
         self.belief
@@ -264,10 +346,8 @@ impl VariableNode {
             let Some(payload) = message.payload() else {
                 continue;
             };
-            self.belief.information_vector =
-                &self.belief.information_vector + &payload.information_vector;
-            self.belief.precision_matrix =
-                &self.belief.precision_matrix + &payload.precision_matrix;
+            self.belief.information_vector += &payload.information_vector;
+            self.belief.precision_matrix += &payload.precision_matrix;
         }
 
         // Update belief
@@ -275,7 +355,9 @@ impl VariableNode {
         // catch and all-zero matrix
         let precision_not_zero = self.belief.precision_matrix.iter().any(|x| *x - 1e-6 > 0.0);
         if precision_not_zero {
-            if let Some(covariance) = self.belief.precision_matrix.inv() {
+            // The precision matrix is symmetric positive definite, and small enough to be
+            // inverted on the stack
+            if let Some(covariance) = self.belief.precision_matrix.cholesky_inverse() {
                 self.belief.covariance_matrix = covariance;
                 self.belief.valid = self.belief.covariance_matrix.iter().all(|x| x.is_finite());
                 if self.belief.valid {
@@ -298,7 +380,7 @@ impl VariableNode {
 
         let mut messages_sent = MessagesSent::new();
 
-        let messages: MessagesToFactors = self
+        let messages: MessagesToFactors<Id> = self
             .inbox
             .iter()
             .map(|(&factor_id, received_message)| {
@@ -347,9 +429,10 @@ impl VariableNode {
         self.belief.valid
     }
 
-    pub fn reset(&mut self, mean: &[f64; 4], sigma: f64) {
-        self.belief.mean = Vector::from_iter(mean.to_owned());
-        self.belief.precision_matrix = Matrix::from_diag_elem(DOFS, sigma);
+    pub fn reset(&mut self, mean: &Vector<Float>, sigma: f64) {
+        debug_assert_eq!(mean.len(), self.layout.dofs());
+        self.belief.mean.clone_from(mean);
+        self.belief.precision_matrix = Matrix::from_diag_elem(self.layout.dofs(), sigma);
         self.inbox.values_mut().for_each(|message| {
             *message = Message::empty();
         });
@@ -360,11 +443,8 @@ impl VariableNode {
     }
 }
 
-impl FactorGraphNode for VariableNode {
-    fn remove_connection_to(
-        &mut self,
-        factorgraph_id: super::factorgraph::FactorGraphId,
-    ) -> Result<(), RemoveConnectionToError> {
+impl<Id: GraphId> FactorGraphNode<Id> for VariableNode<Id> {
+    fn remove_connection_to(&mut self, factorgraph_id: Id) -> Result<(), RemoveConnectionToError> {
         let connections_before = self.inbox.len();
         self.inbox
             .retain(|factor_id, _| factor_id.factorgraph_id != factorgraph_id);
@@ -400,3 +480,72 @@ impl FactorGraphNode for VariableNode {
 //         // writeln!(f, )
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use ndarray::array;
+
+    use super::*;
+    use crate::DOFS;
+
+    fn belief(mean: Vector<Float>, variance: Float) -> VariableBelief {
+        let precision_matrix = Matrix::<Float>::eye(DOFS) / variance;
+        let covariance_matrix = Matrix::<Float>::eye(DOFS) * variance;
+        VariableBelief::new(
+            precision_matrix.dot(&mean),
+            precision_matrix,
+            mean,
+            covariance_matrix,
+        )
+    }
+
+    #[test]
+    fn unchanged_belief_has_no_change() {
+        let previous = belief(array![1.0, 2.0, 0.5, 0.0], 2.0);
+        for criterion in [
+            ConvergenceCriterion::None,
+            ConvergenceCriterion::MeanChange,
+            ConvergenceCriterion::KlDivergence,
+        ] {
+            assert_relative_eq!(
+                previous.change_from(&previous, criterion),
+                0.0,
+                epsilon = 1e-12
+            );
+        }
+    }
+
+    #[test]
+    fn change_of_mean() {
+        let previous = belief(array![0.0, 0.0, 0.0, 0.0], 1.0);
+        let current = belief(array![3.0, 4.0, 0.0, 0.0], 1.0);
+        assert_relative_eq!(
+            current.change_from(&previous, ConvergenceCriterion::MeanChange),
+            5.0
+        );
+        // With unit covariance the divergence is half the squared distance
+        assert_relative_eq!(
+            current.change_from(&previous, ConvergenceCriterion::KlDivergence),
+            12.5,
+            epsilon = 1e-9
+        );
+    }
+
+    #[test]
+    fn change_of_covariance_is_only_seen_by_kl_divergence() {
+        let previous = belief(array![1.0, 1.0, 0.0, 0.0], 1.0);
+        let current = belief(array![1.0, 1.0, 0.0, 0.0], 2.0);
+        assert_relative_eq!(
+            current.change_from(&previous, ConvergenceCriterion::MeanChange),
+            0.0
+        );
+        // 0.5 * (k * 2 - k + k * ln(1 / 2))
+        let expected = 0.5 * (4.0 - 4.0 * Float::ln(2.0));
+        assert_relative_eq!(
+            current.change_from(&previous, ConvergenceCriterion::KlDivergence),
+            expected,
+            epsilon = 1e-9
+        );
+    }
+}
@@ -0,0 +1,357 @@
+//! Two robots planning with GBP, without any game engine
+
+use std::num::NonZeroUsize;
+
+use gbp_core::{
+    factor::{ExternalVariableId, FactorNode, Priorities},
+    factorgraph::FactorGraph,
+    graphviz::{Annotations, EdgeKind, ExportOptions, Graph},
+    id::{FactorId, VariableId},
+    iteration::{self, Immediate, Participant},
+    state::StateLayout,
+    variable::VariableNode,
+};
+use gbp_linalg::prelude::*;
+use gbp_schedule::GbpScheduleAtIteration;
+
+const VARIABLES: usize = 8;
+const RADIUS: Float = 0.5;
+const SPEED: Float = 1.0;
+const DELTA_T: Float = 0.5;
+
+/// State of the `layout` at `position` with `velocity`. The altitude is
+/// ignored by the planar layouts
+fn state(layout: StateLayout, position: [Float; 3], velocity: [Float; 3]) -> Vector<Float> {
+    let mut state =
+        layout.state_from_planar([position[0], position[1]], [velocity[0], velocity[1]]);
+    if layout == StateLayout::Spatial {
+        state[2] = position[2];
+        state[5] = velocity[2];
+    }
+    state
+}
+
+/// A robot planning to move from `start` to `goal` in a straight line
+fn robot(id: u32, layout: StateLayout, start: [Float; 3], goal: [Float; 3]) -> FactorGraph<u32> {
+    let mut factorgraph = FactorGraph::new(id);
+    let direction = [0, 1, 2].map(|i| goal[i] - start[i]);
+    let length = direction.iter().map(|x| x * x).sum::<Float>().sqrt();
+    let velocity = direction.map(|x| x / length * SPEED);
+
+    let variables = (0..VARIABLES)
+        .map(|i| {
+            #[allow(clippy::cast_precision_loss)]
+            let t = i as Float / (VARIABLES - 1) as Float;
+            let position = [0, 1, 2].map(|i| start[i] + t * direction[i]);
+            let mean = state(layout, position, velocity);
+            // The current state and the horizon are fixed
+            let precision = if i == 0 || i == VARIABLES - 1 {
+                1e30
+            } else {
+                0.0
+            };
+            let variable = VariableNode::new(
+                id,
+                mean,
+                Matrix::<Float>::from_diag_elem(layout.dofs(), precision),
+                layout,
+            );
+            factorgraph.add_variable(variable)
+        })
+        .collect::<Vec<_>>();
+
+    for pair in variables.windows(2) {
+        let factor = FactorNode::new_dynamic_factor(
+            id,
+            layout,
+            0.1,
+            Vector::<Float>::zeros(layout.dofs()),
+            DELTA_T,
+            true,
+        );
+        let factor = FactorId::new(id, factorgraph.add_factor(factor));
+        for &variable in pair {
+            let _ = factorgraph.add_internal_edge(VariableId::new(id, variable), factor);
+        }
+    }
+
+    factorgraph
+}
+
+/// Add interrobot factors to `a`, between every future variable of `a` and
+/// the variable of `b` at the same timestep
+fn connect(
+    a: &mut FactorGraph<u32>,
+    b: &mut FactorGraph<u32>,
+    layout: StateLayout,
+    robot_number: NonZeroUsize,
+    priorities: Priorities,
+) {
+    for i in 1..VARIABLES {
+        let variable = a.nth_variable_index(i).expect("the robot has the variable");
+        let (external_variable, external) = b.nth_variable(i).expect("the robot has the variable");
+        let message = external.prepare_message();
+
+        let factor = FactorNode::new_interrobot_factor(
+            a.id(),
+            layout,
+            0.01,
+            Vector::<Float>::zeros(1),
+            RADIUS.try_into().expect("the radius is positive"),
+            2.2.try_into().expect("the multiplier is positive"),
+            ExternalVariableId::new(b.id(), external_variable),
+            robot_number,
+            priorities,
+            true,
+        );
+        let factor = a.add_factor(factor);
+        let _ = a.add_internal_edge(
+            VariableId::new(a.id(), variable),
+            FactorId::new(a.id(), factor),
+        );
+        b.add_external_edge(FactorId::new(a.id(), factor), i);
+        a.get_factor_mut(factor)
+            .expect("the factor was just added")
+            .receive_message_from(VariableId::new(b.id(), external_variable), message);
+    }
+}
+
+/// Smallest distance between the two robots at the same timestep
+fn closest_approach(a: &FactorGraph<u32>, b: &FactorGraph<u32>) -> Float {
+    a.variables()
+        .zip(b.variables())
+        .map(|((_, a), (_, b))| {
+            let [ax, ay, az] = a.estimated_position_3d();
+            let [bx, by, bz] = b.estimated_position_3d();
+            (ax - bx).hypot(ay - by).hypot(az - bz)
+        })
+        .fold(Float::INFINITY, Float::min)
+}
+
+/// Two robots driving towards each other, slightly offset to the side
+fn head_on() -> (FactorGraph<u32>, FactorGraph<u32>) {
+    head_on_with_offset(StateLayout::Planar, [0.0, 0.05, 0.0])
+}
+
+/// Two robots moving towards each other along the x axis, offset by
+/// `offset` in opposite directions
+fn head_on_with_offset(
+    layout: StateLayout,
+    offset: [Float; 3],
+) -> (FactorGraph<u32>, FactorGraph<u32>) {
+    head_on_with_priorities(layout, offset, Priorities::default())
+}
+
+/// Two robots moving towards each other along the x axis, offset by
+/// `offset` in opposite directions, with the `priorities` of the first robot
+/// and the second robot
+fn head_on_with_priorities(
+    layout: StateLayout,
+    offset: [Float; 3],
+    priorities: Priorities,
+) -> (FactorGraph<u32>, FactorGraph<u32>) {
+    let [_, dy, dz] = offset;
+    let mut a = robot(1, layout, [-2.0, dy, dz], [2.0, dy, dz]);
+    let mut b = robot(2, layout, [2.0, -dy, -dz], [-2.0, -dy, -dz]);
+    connect(&mut a, &mut b, layout, NonZeroUsize::MIN, priorities);
+    connect(
+        &mut b,
+        &mut a,
+        layout,
+        NonZeroUsize::MIN.saturating_add(1),
+        Priorities::new(priorities.external, priorities.own),
+    );
+    (a, b)
+}
+
+fn schedule() -> impl Iterator<Item = GbpScheduleAtIteration> {
+    std::iter::repeat(GbpScheduleAtIteration {
+        internal: true,
+        external: true,
+    })
+    .take(10)
+}
+
+/// Iterate until the robots have planned around each other, and return how
+/// close they plan to get before and after
+fn plan_around_each_other(a: &mut FactorGraph<u32>, b: &mut FactorGraph<u32>) -> (Float, Float) {
+    let before = closest_approach(a, b);
+    let mut transport = Immediate::default();
+    for _ in 0..20 {
+        let mut participants = [Participant::new(a), Participant::new(b)];
+        iteration::iterate(&mut participants, schedule(), &mut transport);
+    }
+    (before, closest_approach(a, b))
+}
+
+#[test]
+fn head_on_robots_plan_around_each_other() {
+    let (mut a, mut b) = head_on();
+    let (before, after) = plan_around_each_other(&mut a, &mut b);
+    assert!(before < 2.0 * RADIUS);
+    assert!(
+        after > 2.0 * RADIUS,
+        "the robots plan to be {after} apart, closer than their radii"
+    );
+}
+
+#[test]
+fn head_on_drones_plan_around_each_other_vertically() {
+    let (mut a, mut b) = head_on_with_offset(StateLayout::Spatial, [0.0, 0.0, 0.05]);
+    let (before, after) = plan_around_each_other(&mut a, &mut b);
+    assert!(before < 2.0 * RADIUS);
+    assert!(
+        after > 2.0 * RADIUS,
+        "the drones plan to be {after} apart, closer than their radii"
+    );
+
+    // The drones are only offset vertically, so they mostly avoid each other
+    // vertically
+    let (y, z) = a
+        .variables()
+        .map(|(_, variable)| variable.estimated_position_3d())
+        .fold((0.0, 0.0), |(y, z): (Float, Float), position| {
+            (y.max(position[1].abs()), z.max(position[2].abs()))
+        });
+    assert!(
+        z > 10.0 * y,
+        "the drone avoids {z} vertically and {y} horizontally"
+    );
+}
+
+#[test]
+fn head_on_robots_with_heading_plan_around_each_other() {
+    let (mut a, mut b) = head_on_with_offset(StateLayout::PlanarPose, [0.0, 0.05, 0.0]);
+    let (before, after) = plan_around_each_other(&mut a, &mut b);
+    assert!(before < 2.0 * RADIUS);
+    assert!(
+        after > 2.0 * RADIUS,
+        "the robots plan to be {after} apart, closer than their radii"
+    );
+
+    // Nothing measures the heading, so every state keeps the heading of the
+    // current state and the horizon, which point along the x axis
+    for (_, variable) in a.variables() {
+        let (heading, _) = variable
+            .estimated_heading()
+            .expect("the layout has a heading");
+        assert!(heading.abs() < 1e-6, "the heading changed to {heading}");
+    }
+}
+
+#[test]
+fn robot_with_higher_priority_yields_less() {
+    let priorities = Priorities::new(
+        4.0.try_into().expect("the priority is positive"),
+        1.0.try_into().expect("the priority is positive"),
+    );
+    let (mut a, mut b) = head_on_with_priorities(StateLayout::Planar, [0.0, 0.05, 0.0], priorities);
+    let (before, after) = plan_around_each_other(&mut a, &mut b);
+    assert!(before < 2.0 * RADIUS);
+    assert!(
+        after > 2.0 * RADIUS,
+        "the robots plan to be {after} apart, closer than their radii"
+    );
+
+    // Both robots start 0.05 to the side of the x axis
+    let deviation = |factorgraph: &FactorGraph<u32>| {
+        factorgraph
+            .variables()
+            .map(|(_, variable)| (variable.estimated_position_3d()[1].abs() - 0.05).abs())
+            .fold(0.0, Float::max)
+    };
+    let (deviation_a, deviation_b) = (deviation(&a), deviation(&b));
+    assert!(
+        deviation_a < deviation_b,
+        "the robot with the higher priority deviates {deviation_a}, the other {deviation_b}"
+    );
+}
+
+#[test]
+fn robots_without_communication_do_not_react() {
+    let (mut a, mut b) = head_on();
+    let before = closest_approach(&a, &b);
+    let mut transport = Immediate::default();
+    for _ in 0..20 {
+        let mut participants = [Participant::new(&mut a), Participant::new(&mut b)];
+        for participant in &mut participants {
+            participant.communicates = false;
+        }
+        iteration::iterate(&mut participants, schedule(), &mut transport);
+    }
+
+    assert!(closest_approach(&a, &b) < 2.0 * RADIUS);
+    assert!((closest_approach(&a, &b) - before).abs() < 0.1);
+}
+
+#[test]
+fn deleting_interrobot_factors_disconnects_the_robots() {
+    let (mut a, mut b) = head_on();
+    a.delete_interrobot_factors_connected_to(b.id());
+    b.delete_interrobot_factors_connected_to(a.id());
+
+    let connected = |factorgraph: &FactorGraph<u32>, other: u32| {
+        factorgraph.variables().any(|(_, variable)| {
+            variable
+                .inbox
+                .keys()
+                .any(|factor| factor.factorgraph_id == other)
+        })
+    };
+    assert!(!connected(&a, b.id()));
+    assert!(!connected(&b, a.id()));
+
+    let mut participants = [Participant::new(&mut a), Participant::new(&mut b)];
+    iteration::iterate(&mut participants, schedule(), &mut Immediate::default());
+    assert_eq!(a.messages_sent().external, 0);
+    assert_eq!(b.messages_sent().external, 0);
+}
+
+#[test]
+fn export_connects_interrobot_factors_across_robots() {
+    let (mut a, mut b) = head_on();
+    let _ = plan_around_each_other(&mut a, &mut b);
+
+    let options = ExportOptions::default().with_annotations(Annotations {
+        beliefs:  true,
+        messages: true,
+    });
+    let graph = Graph::new([(a.id(), &a, true), (b.id(), &b, false)], &options);
+    assert_eq!(graph.robots, ["1", "2"]);
+
+    let interrobot = graph
+        .edges
+        .iter()
+        .filter_map(|edge| match edge.kind {
+            EdgeKind::InterRobot { active } => Some((edge, active)),
+            EdgeKind::Internal => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(interrobot.len(), 2 * (VARIABLES - 1));
+    for (edge, active) in interrobot {
+        let to = graph
+            .node(&edge.to)
+            .expect("the edge ends in an exported node");
+        assert_eq!(to.kind, "variable");
+        assert_eq!(active, to.robot == "2");
+    }
+
+    // After planning every dynamic factor has sent a message to its variables
+    assert!(graph
+        .edges
+        .iter()
+        .filter(|edge| graph
+            .node(&edge.to)
+            .is_some_and(|node| node.kind == "dynamic"))
+        .all(|edge| edge
+            .messages
+            .is_some_and(|messages| messages.to_variable.is_some())));
+    assert!(graph
+        .nodes
+        .iter()
+        .filter(|node| node.kind == "variable")
+        .all(|node| node.belief.is_some()));
+
+    let svg = graph.to_svg();
+    assert_eq!(svg.matches("<circle").count(), 2 * VARIABLES);
+}
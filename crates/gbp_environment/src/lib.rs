@@ -31,6 +31,13 @@ impl TileCoordinates {
 pub struct TileGrid(Vec<String>);
 
 impl TileGrid {
+    /// The characters a tile can be. The box drawing characters are paths
+    /// through the tile, `' '` is a filled tile and `'█'` an open tile
+    pub const TILES: [char; 19] = [
+        '─', '-', '│', '|', '╴', '╶', '╷', '╵', '┌', '┐', '└', '┘', '┬', '┴', '├', '┤', '┼', ' ',
+        '█',
+    ];
+
     pub fn new(tiles: Vec<impl Into<String>>) -> Self {
         Self(tiles.into_iter().map(Into::into).collect())
     }
@@ -67,6 +74,44 @@ impl TileGrid {
         self.0.get(row).and_then(|r| r.chars().nth(col))
     }
 
+    /// Set the tile at the given coordinates.
+    /// Returns `false` if the coordinates are outside the grid
+    pub fn set_tile(&mut self, row: usize, col: usize, tile: char) -> bool {
+        let Some(tiles) = self.0.get_mut(row) else {
+            return false;
+        };
+        let mut chars: Vec<char> = tiles.chars().collect();
+        let Some(existing) = chars.get_mut(col) else {
+            return false;
+        };
+        *existing = tile;
+        *tiles = chars.into_iter().collect();
+        true
+    }
+
+    /// Resize the grid to `nrows` rows and `ncols` columns, keeping the tiles
+    /// that are still inside the grid, and filling the new ones with `fill`
+    pub fn resize(&mut self, nrows: usize, ncols: usize, fill: char) {
+        self.0.resize_with(nrows, String::new);
+        for tiles in &mut self.0 {
+            let mut chars: Vec<char> = tiles.chars().collect();
+            chars.resize(ncols, fill);
+            *tiles = chars.into_iter().collect();
+        }
+    }
+
+    /// The `(row, column, tile)` of every tile that is not one of
+    /// [`TileGrid::TILES`]
+    pub fn unknown_tiles(&self) -> impl Iterator<Item = (usize, usize, char)> + '_ {
+        self.0.iter().enumerate().flat_map(|(row, tiles)| {
+            tiles
+                .chars()
+                .enumerate()
+                .filter(|(_, tile)| !Self::TILES.contains(tile))
+                .map(move |(col, tile)| (row, col, tile))
+        })
+    }
+
     // /// override the index operator to allow for easy access to the grid
     // pub fn get(&self, row: usize, col: usize) -> Option<char> {
     //     self.0.get(row).and_then(|r| r.chars().nth(col))
@@ -435,6 +480,86 @@ impl PlaceableShape {
         }
     }
 
+    /// Scale the size of the shape by `factor`, unlike
+    /// [`PlaceableShape::expanded`] which adds to it.
+    /// Returns `None` if a dimension of the scaled shape is not strictly
+    /// positive and finite, e.g. if `factor` is not
+    #[must_use]
+    pub fn scaled(&self, factor: Float) -> Option<Self> {
+        let scale = |value: StrictlyPositiveFinite<Float>| {
+            StrictlyPositiveFinite::<Float>::new(value.get() * factor).ok()
+        };
+        Some(match self {
+            Self::Circle(circle) => Self::Circle(Circle::new(scale(circle.radius)?)),
+            Self::Triangle(triangle) => Self::Triangle(Triangle::new(
+                triangle.angles.clone(),
+                scale(triangle.radius)?,
+            )),
+            Self::RegularPolygon(polygon) => {
+                Self::RegularPolygon(RegularPolygon::new(polygon.sides, scale(polygon.radius)?))
+            }
+            Self::Rectangle(rectangle) => Self::Rectangle(Rectangle::new(
+                scale(rectangle.width)?,
+                scale(rectangle.height)?,
+            )),
+        })
+    }
+
+    /// The rotation in radians added to the rotation of an [`Obstacle`], to
+    /// get from the frame of the tile to the frame of the shape, where
+    /// [`PlaceableShape::inside`] is checked
+    pub fn rotation_offset(&self) -> f32 {
+        match self {
+            #[allow(clippy::cast_precision_loss)]
+            Self::RegularPolygon(RegularPolygon { sides, .. }) => {
+                std::f32::consts::PI
+                    + if sides % 2 == 0 {
+                        0.0
+                    } else {
+                        std::f32::consts::PI / *sides as f32
+                    }
+            }
+            _ => std::f32::consts::FRAC_PI_2,
+        }
+    }
+
+    /// The corners of the outline of the shape, in the frame where
+    /// [`PlaceableShape::inside`] is checked. A circle is approximated with
+    /// `segments` corners
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    pub fn outline(&self, segments: usize) -> Vec<Vec2> {
+        match self {
+            Self::Circle(circle) => {
+                let radius = circle.radius.get() as f32;
+                (0..segments)
+                    .map(|i| {
+                        Vec2::from_angle(std::f32::consts::TAU * i as f32 / segments as f32)
+                            * radius
+                    })
+                    .collect()
+            }
+            Self::Triangle(triangle) => triangle.points().to_vec(),
+            // `RegularPolygon::inside` checks twice the point against the polygon
+            Self::RegularPolygon(polygon) => polygon
+                .points()
+                .into_iter()
+                .map(|[x, y]| Vec2::new(x as f32, y as f32) / 2.0)
+                .collect(),
+            // `Rectangle::inside` checks x against a quarter of the height, and y
+            // against a quarter of the width
+            Self::Rectangle(rectangle) => {
+                let half =
+                    Vec2::new(rectangle.height.get() as f32, rectangle.width.get() as f32) / 4.0;
+                vec![
+                    Vec2::new(-half.x, -half.y),
+                    Vec2::new(half.x, -half.y),
+                    Vec2::new(half.x, half.y),
+                    Vec2::new(-half.x, half.y),
+                ]
+            }
+        }
+    }
+
     /// Check if a given point is inside the shape
     pub fn inside(&self, point: Vec2) -> bool {
         match self {
@@ -460,6 +585,31 @@ pub struct Obstacle {
 }
 
 impl Obstacle {
+    /// The outline of the obstacle within its tile, as fractions of the tile
+    /// with `y` pointing down, the same frame as the `translation`.
+    /// See [`PlaceableShape::outline`]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn outline(&self, segments: usize) -> Vec<Vec2> {
+        let rotation =
+            Vec2::from_angle(-(self.rotation.as_radians() as f32 + self.shape.rotation_offset()));
+        let translation = Vec2::from(self.translation);
+        self.shape
+            .outline(segments)
+            .into_iter()
+            .map(|point| translation + rotation.rotate(point))
+            .collect()
+    }
+
+    /// Check if a point within the tile of the obstacle is inside it, in the
+    /// same frame as [`Obstacle::outline`]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn contains(&self, point: Vec2) -> bool {
+        let rotation =
+            Vec2::from_angle(self.rotation.as_radians() as f32 + self.shape.rotation_offset());
+        self.shape
+            .inside(rotation.rotate(point - Vec2::from(self.translation)))
+    }
+
     /// Create a new `Obstacle`
     ///
     /// # Panics
@@ -503,6 +653,106 @@ impl Obstacles {
     pub fn iter(&self) -> std::slice::Iter<Obstacle> {
         self.0.iter()
     }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<Obstacle> {
+        self.0.iter_mut()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Obstacle> {
+        self.0.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Obstacle> {
+        self.0.get_mut(index)
+    }
+
+    pub fn push(&mut self, obstacle: Obstacle) {
+        self.0.push(obstacle);
+    }
+
+    /// Remove the obstacle at `index`
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of bounds
+    pub fn remove(&mut self, index: usize) -> Obstacle {
+        self.0.remove(index)
+    }
+
+    /// Keep only the obstacles for which `keep` returns `true`
+    pub fn retain(&mut self, keep: impl FnMut(&Obstacle) -> bool) {
+        self.0.retain(keep);
+    }
+}
+
+/// How a [`DynamicObstacle`] moves, in meters and seconds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Trajectory {
+    /// Move with a constant velocity
+    Linear {
+        /// Velocity in m/s
+        velocity: [f32; 2],
+    },
+    /// Move between waypoints with a constant speed, and return to the start
+    /// after the last waypoint
+    WaypointLoop {
+        /// Waypoints in meters, relative to the start of the obstacle
+        waypoints: Vec<[f32; 2]>,
+        /// Speed in m/s
+        speed:     StrictlyPositiveFinite<f32>,
+    },
+    /// Move with a constant speed, and change to a random direction at a fixed
+    /// interval
+    RandomWalk {
+        /// Speed in m/s
+        speed: StrictlyPositiveFinite<f32>,
+        /// Seconds between each change of direction
+        interval: StrictlyPositiveFinite<f32>,
+        /// When further than this many meters from the start, the obstacle
+        /// turns back towards the start instead of choosing a random direction
+        max_distance: StrictlyPositiveFinite<f32>,
+    },
+}
+
+/// An obstacle moving along a [`Trajectory`]
+///
+/// ## Example
+/// ```yaml
+/// dynamic-obstacles:
+/// - shape: !circle
+///     radius: 0.05
+///   translation:
+///     x: 0.2
+///     y: 0.5
+///   tile-coordinates:
+///     row: 0
+///     col: 0
+///   trajectory: !waypoint-loop
+///     waypoints: [[60.0, 0.0], [60.0, 20.0]]
+///     speed: 3.0
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DynamicObstacle {
+    /// The shape of the obstacle. Dynamic obstacles do not rotate
+    pub shape: PlaceableShape,
+    /// Where the obstacle starts within the tile
+    pub translation: RelativePoint,
+    /// Which tile in the grid the obstacle starts in
+    pub tile_coordinates: TileCoordinates,
+    /// How the obstacle moves
+    pub trajectory: Trajectory,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -521,6 +771,21 @@ pub struct SdfSettings {
     pub resolution: u32,
     pub expansion:  f32,
     pub blur:       f32,
+    /// Distances of the exact signed distance field are clamped to this
+    /// fraction of the tile size, on both sides of the obstacle boundaries
+    #[serde(default = "SdfSettings::default_clamp")]
+    pub clamp:      f32,
+}
+
+impl SdfSettings {
+    const fn default_clamp() -> f32 {
+        0.1
+    }
+
+    /// The clamp of the exact signed distance field in metres
+    pub fn clamp_distance(&self, tile_size: f32) -> f32 {
+        self.clamp * tile_size
+    }
 }
 
 impl Default for SdfSettings {
@@ -529,6 +794,7 @@ impl Default for SdfSettings {
             resolution: 200,
             expansion:  0.1,
             blur:       0.05,
+            clamp:      Self::default_clamp(),
         }
     }
 }
@@ -587,8 +853,11 @@ pub enum EnvironmentType {
 #[derive(Debug, Clone, Serialize, Deserialize, Resource)]
 #[serde(rename_all = "kebab-case")]
 pub struct Environment {
-    pub tiles:     Tiles,
+    pub tiles: Tiles,
     pub obstacles: Obstacles,
+    /// Obstacles moving around in the environment
+    #[serde(default)]
+    pub dynamic_obstacles: Vec<DynamicObstacle>,
 }
 
 impl Default for Environment {
@@ -684,7 +953,7 @@ impl Environment {
         tile_size: f32,
     ) -> Self {
         Self {
-            tiles:     Tiles {
+            tiles: Tiles {
                 grid:     TileGrid(matrix_representation),
                 settings: TileSettings {
                     tile_size,
@@ -694,13 +963,14 @@ impl Environment {
                 },
             },
             obstacles: Obstacles::empty(),
+            dynamic_obstacles: Vec::new(),
         }
     }
 
     #[must_use]
     pub fn intersection() -> Self {
         Self {
-            tiles:     Tiles {
+            tiles: Tiles {
                 grid:     TileGrid::new(vec!["┼"]),
                 settings: TileSettings {
                     tile_size: 100.0,
@@ -710,6 +980,7 @@ impl Environment {
                 },
             },
             obstacles: Obstacles::empty(),
+            dynamic_obstacles: Vec::new(),
         }
     }
 
@@ -731,6 +1002,7 @@ impl Environment {
                 }
             },
             obstacles: Obstacles::empty(),
+            dynamic_obstacles: Vec::new(),
         }
     }
 
@@ -754,6 +1026,7 @@ impl Environment {
                 },
             },
             obstacles: Obstacles::empty(),
+            dynamic_obstacles: Vec::new(),
         }
     }
 
@@ -789,6 +1062,7 @@ impl Environment {
                 },
             },
             obstacles: Obstacles::empty(),
+            dynamic_obstacles: Vec::new(),
         }
     }
 
@@ -811,6 +1085,7 @@ impl Environment {
                 },
             },
             obstacles: Obstacles::empty(),
+            dynamic_obstacles: Vec::new(),
         }
     }
 
@@ -818,7 +1093,7 @@ impl Environment {
     #[allow(clippy::missing_panics_doc)]
     pub fn circle() -> Self {
         Self {
-            tiles:     Tiles::empty()
+            tiles: Tiles::empty()
                 .with_tile_size(100.0)
                 .with_obstacle_height(1.0),
             obstacles: Obstacles(vec![
@@ -871,6 +1146,7 @@ impl Environment {
                     (0.38, 0.432),
                 ),
             ]),
+            dynamic_obstacles: Vec::new(),
         }
     }
 
@@ -885,4 +1161,79 @@ impl Environment {
     pub const fn tile_size(&self) -> f32 {
         self.tiles.settings.tile_size
     }
+
+    /// Width and height of the environment. The environment is centered at
+    /// the origin
+    pub fn dimensions(&self) -> Vec2 {
+        #[allow(clippy::cast_precision_loss)]
+        let tiles = Vec2::new(
+            self.tiles.grid.ncols() as f32,
+            self.tiles.grid.nrows() as f32,
+        );
+        tiles * self.tile_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_and_resize_tiles() {
+        let mut grid = TileGrid::new(vec!["┌┐", "└┘"]);
+        assert!(grid.set_tile(1, 0, '│'));
+        assert!(!grid.set_tile(2, 0, '│'));
+        assert!(!grid.set_tile(0, 2, '│'));
+        assert_eq!(grid.get_tile(1, 0), Some('│'));
+
+        grid.resize(3, 1, ' ');
+        assert_eq!(grid.shape(), (3, 1));
+        assert_eq!(grid.iter().collect::<Vec<_>>(), ["┌", "│", " "]);
+
+        grid.resize(1, 3, '█');
+        assert_eq!(grid.iter().collect::<Vec<_>>(), ["┌██"]);
+    }
+
+    /// The outline of an obstacle should enclose exactly the points that are
+    /// inside it
+    #[test]
+    fn outline_encloses_the_inside_of_the_obstacle() {
+        let shapes = [
+            PlaceableShape::circle(StrictlyPositiveFinite::<Float>::new(0.1).expect("0.1 > 0")),
+            PlaceableShape::triangle(
+                [
+                    Angle::from_degrees(60.0).expect("0 <= 60 <= 360"),
+                    Angle::from_degrees(45.0).expect("0 <= 45 <= 360"),
+                ],
+                StrictlyPositiveFinite::<Float>::new(0.05).expect("0.05 > 0"),
+            ),
+            PlaceableShape::regular_polygon(3, 0.1),
+            PlaceableShape::regular_polygon(4, 0.1),
+            PlaceableShape::regular_polygon(7, 0.1),
+            PlaceableShape::rectangle(0.2, 0.1),
+        ];
+
+        for shape in shapes {
+            for rotation in [0.0, 0.4, 2.0, 5.0] {
+                let scaled = shape
+                    .scaled(1.5)
+                    .expect("1.5 is strictly positive and finite");
+                let obstacle = Obstacle::new((0, 0), scaled, rotation, (0.4, 0.6));
+                let outline = obstacle.outline(32);
+                #[allow(clippy::cast_precision_loss)]
+                let centre = outline.iter().sum::<Vec2>() / outline.len() as f32;
+                for corner in outline {
+                    let towards = corner - centre;
+                    assert!(
+                        obstacle.contains(centre + towards * 0.95),
+                        "{shape:?} at {rotation}"
+                    );
+                    assert!(
+                        !obstacle.contains(centre + towards * 1.05),
+                        "{shape:?} at {rotation}"
+                    );
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,375 @@
+//! Grid search over an occupancy grid rasterised from the [`Colliders`]
+
+use std::{collections::VecDeque, sync::Arc};
+
+use bevy::math::Vec2;
+use rand::RngCore;
+
+use crate::{
+    search::shortest_path, Bounds, Colliders, CollisionProblem, GlobalPlanner, Path,
+    PathfindingError,
+};
+
+/// Column and row of a cell in an [`OccupancyGrid`]
+pub type Cell = (usize, usize);
+
+/// The environment divided into square cells, each either free or occupied
+pub struct OccupancyGrid {
+    bounds:    Bounds,
+    cell_size: f32,
+    cols:      usize,
+    rows:      usize,
+    occupied:  Vec<bool>,
+}
+
+impl OccupancyGrid {
+    /// Rasterise `colliders` within `bounds` into cells of `cell_size`. A cell
+    /// is occupied if a ball of `collision_radius` at its center intersects
+    /// any of the colliders.
+    pub fn new(
+        colliders: &Colliders,
+        bounds: Bounds,
+        cell_size: f32,
+        collision_radius: f32,
+    ) -> Self {
+        let problem = CollisionProblem::new(colliders.clone(), bounds)
+            .with_collision_radius(collision_radius);
+        let size = bounds.size() / cell_size;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let (cols, rows) = (
+            (size.x.ceil() as usize).max(1),
+            (size.y.ceil() as usize).max(1),
+        );
+
+        let mut grid = Self {
+            bounds,
+            cell_size,
+            cols,
+            rows,
+            occupied: vec![false; cols * rows],
+        };
+        for row in 0..rows {
+            for col in 0..cols {
+                grid.occupied[row * cols + col] = !problem.is_free(grid.center((col, row)));
+            }
+        }
+        grid
+    }
+
+    /// The number of columns and rows of the grid
+    pub const fn dimensions(&self) -> (usize, usize) {
+        (self.cols, self.rows)
+    }
+
+    /// Whether `cell` is occupied. Cells outside the grid are occupied
+    pub fn is_occupied(&self, (col, row): Cell) -> bool {
+        col >= self.cols || row >= self.rows || self.occupied[row * self.cols + col]
+    }
+
+    /// The center of `cell` in world coordinates
+    pub fn center(&self, (col, row): Cell) -> Vec2 {
+        #[allow(clippy::cast_precision_loss)]
+        let offset = Vec2::new(col as f32 + 0.5, row as f32 + 0.5);
+        self.bounds.min + offset * self.cell_size
+    }
+
+    /// The cell containing `point`, clamped to the grid
+    pub fn cell_of(&self, point: Vec2) -> Cell {
+        let cell = ((point - self.bounds.min) / self.cell_size).floor();
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        (
+            (cell.x.max(0.0) as usize).min(self.cols - 1),
+            (cell.y.max(0.0) as usize).min(self.rows - 1),
+        )
+    }
+
+    /// The free cell closest to `point`, searching outwards from the cell
+    /// containing it. Robots start and end right next to obstacles, so their
+    /// own cell is often occupied.
+    pub(crate) fn nearest_free(&self, point: Vec2) -> Option<Cell> {
+        let start = self.cell_of(point);
+        let mut visited = vec![false; self.cols * self.rows];
+        let mut queue = VecDeque::from([start]);
+        visited[self.index(start)] = true;
+        while let Some(cell) = queue.pop_front() {
+            if !self.is_occupied(cell) {
+                return Some(cell);
+            }
+            for neighbour in self.adjacent(cell).map(|(neighbour, _)| neighbour) {
+                if !std::mem::replace(&mut visited[self.index(neighbour)], true) {
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+        None
+    }
+
+    const fn index(&self, (col, row): Cell) -> usize {
+        row * self.cols + col
+    }
+
+    const fn cell(&self, index: usize) -> Cell {
+        (index % self.cols, index / self.cols)
+    }
+
+    /// The 8 cells around `cell` inside the grid, with the distance to them in
+    /// cells
+    fn adjacent(&self, (col, row): Cell) -> impl Iterator<Item = (Cell, f32)> + '_ {
+        (-1..=1_isize)
+            .flat_map(|dy| (-1..=1_isize).map(move |dx| (dx, dy)))
+            .filter(|&offset| offset != (0, 0))
+            .filter_map(move |(dx, dy)| {
+                let cell = (col.checked_add_signed(dx)?, row.checked_add_signed(dy)?);
+                (cell.0 < self.cols && cell.1 < self.rows).then(|| {
+                    let distance = if dx != 0 && dy != 0 {
+                        std::f32::consts::SQRT_2
+                    } else {
+                        1.0
+                    };
+                    (cell, distance)
+                })
+            })
+    }
+
+    /// The free cells a robot can move to from `cell`. Moving diagonally is
+    /// only possible if both cells next to the diagonal are free, so paths do
+    /// not cut the corners of obstacles.
+    fn free_neighbours(&self, cell: Cell) -> impl Iterator<Item = (Cell, f32)> + '_ {
+        self.adjacent(cell).filter(move |&(neighbour, _)| {
+            !self.is_occupied(neighbour)
+                && !self.is_occupied((neighbour.0, cell.1))
+                && !self.is_occupied((cell.0, neighbour.1))
+        })
+    }
+
+    /// The free cells sharing a side with `cell`
+    pub(crate) fn orthogonal_neighbours(
+        &self,
+        (col, row): Cell,
+    ) -> impl Iterator<Item = Cell> + '_ {
+        [(-1, 0), (1, 0), (0, -1), (0, 1)]
+            .into_iter()
+            .filter_map(move |(dx, dy)| {
+                Some((col.checked_add_signed(dx)?, row.checked_add_signed(dy)?))
+            })
+            .filter(|&cell| !self.is_occupied(cell))
+    }
+
+    /// The path through the centers of `cells`, with the first and last cell
+    /// replaced by the exact `start` and `end`
+    pub(crate) fn path_through(&self, cells: Vec<Cell>, start: Vec2, end: Vec2) -> Path {
+        let inner = cells.len().saturating_sub(2);
+        let path = std::iter::once(start)
+            .chain(
+                cells
+                    .into_iter()
+                    .skip(1)
+                    .take(inner)
+                    .map(|cell| self.center(cell)),
+            )
+            .chain(std::iter::once(end))
+            .collect();
+        Path(path)
+    }
+
+    /// Whether the straight line between the centers of `a` and `b` only
+    /// passes through free cells
+    fn line_of_sight(&self, a: Cell, b: Cell) -> bool {
+        let (from, to) = (self.center(a), self.center(b));
+        // Sample a few times per cell, to not skip the corner of a cell
+        let per_cell = 4.0;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let steps = (from.distance(to) / self.cell_size * per_cell).ceil() as usize;
+        (0..=steps).all(|i| {
+            #[allow(clippy::cast_precision_loss)]
+            let t = if steps == 0 {
+                0.0
+            } else {
+                i as f32 / steps as f32
+            };
+            !self.is_occupied(self.cell_of(from.lerp(to, t)))
+        })
+    }
+}
+
+/// Global planner searching an [`OccupancyGrid`] with A* or Theta*.
+///
+/// Grid search is deterministic, and on the tile based environments faster
+/// than sampling. A* moves between adjacent cells, so its paths follow the
+/// grid, while Theta* connects cells in line of sight of each other, and finds
+/// shorter paths with fewer waypoints.
+pub struct GridSearch {
+    grid:      Arc<OccupancyGrid>,
+    any_angle: bool,
+}
+
+impl GridSearch {
+    /// A* over `grid`
+    pub const fn a_star(grid: Arc<OccupancyGrid>) -> Self {
+        Self {
+            grid,
+            any_angle: false,
+        }
+    }
+
+    /// Theta* over `grid`
+    pub const fn theta_star(grid: Arc<OccupancyGrid>) -> Self {
+        Self {
+            grid,
+            any_angle: true,
+        }
+    }
+
+    /// The cells of the shortest path from `start` to `goal`
+    fn search(&self, start: Cell, goal: Cell) -> Option<Vec<Cell>> {
+        let grid = &self.grid;
+        let (cols, rows) = grid.dimensions();
+        let distance = |a: usize, b: usize| {
+            let (a, b) = (grid.cell(a), grid.cell(b));
+            #[allow(clippy::cast_precision_loss)]
+            (a.0.abs_diff(b.0) as f32).hypot(a.1.abs_diff(b.1) as f32)
+        };
+        let line_of_sight = |a: usize, b: usize| grid.line_of_sight(grid.cell(a), grid.cell(b));
+        let any_angle = self.any_angle.then_some((
+            &line_of_sight as &dyn Fn(usize, usize) -> bool,
+            &distance as &dyn Fn(usize, usize) -> f32,
+        ));
+
+        let goal_index = grid.index(goal);
+        let path = shortest_path(
+            cols * rows,
+            grid.index(start),
+            goal_index,
+            |index| {
+                grid.free_neighbours(grid.cell(index))
+                    .map(|(cell, step)| (grid.index(cell), step))
+                    .collect::<Vec<_>>()
+            },
+            |index| distance(index, goal_index),
+            any_angle,
+        )?;
+        Some(path.into_iter().map(|index| grid.cell(index)).collect())
+    }
+}
+
+/// Remove the cells in the middle of straight runs of `cells`, keeping only
+/// the cells where the path turns
+pub(crate) fn corners(cells: Vec<Cell>) -> Vec<Cell> {
+    let direction = |a: Cell, b: Cell| {
+        #[allow(clippy::cast_possible_wrap)]
+        (b.0 as isize - a.0 as isize, b.1 as isize - a.1 as isize)
+    };
+    let mut corners = Vec::with_capacity(cells.len());
+    for (i, &cell) in cells.iter().enumerate() {
+        let turns = match (i.checked_sub(1).map(|i| cells[i]), cells.get(i + 1)) {
+            (Some(previous), Some(&next)) => direction(previous, cell) != direction(cell, next),
+            _ => true,
+        };
+        if turns {
+            corners.push(cell);
+        }
+    }
+    corners
+}
+
+impl GlobalPlanner for GridSearch {
+    fn plan(
+        &self,
+        start: Vec2,
+        end: Vec2,
+        _rng: &mut dyn RngCore,
+    ) -> Result<Path, PathfindingError> {
+        let start_cell = self
+            .grid
+            .nearest_free(start)
+            .ok_or(PathfindingError::NoPath)?;
+        let end_cell = self
+            .grid
+            .nearest_free(end)
+            .ok_or(PathfindingError::NoPath)?;
+        let mut cells = self
+            .search(start_cell, end_cell)
+            .ok_or(PathfindingError::NoPath)?;
+        if !self.any_angle {
+            cells = corners(cells);
+        }
+
+        Ok(self.grid.path_through(cells, start, end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parry2d::{
+        na::{Isometry2, Vector2},
+        shape,
+    };
+    use rand::rngs::mock::StepRng;
+
+    use super::*;
+
+    /// A 10 x 10 environment with a wall from the bottom up to y = 3, in the
+    /// middle of it
+    fn wall() -> OccupancyGrid {
+        let mut colliders = Colliders::default();
+        colliders.push(
+            None,
+            Isometry2::translation(0.0, -1.0),
+            Arc::new(shape::Cuboid::new(Vector2::new(0.5, 4.0))),
+        );
+        OccupancyGrid::new(&colliders, Bounds::centered(Vec2::splat(10.0)), 1.0, 0.1)
+    }
+
+    fn plan(planner: &GridSearch) -> Vec<Vec2> {
+        let (start, end) = (Vec2::new(-4.0, -4.0), Vec2::new(4.0, -4.0));
+        planner
+            .plan(start, end, &mut StepRng::new(0, 1))
+            .expect("the wall can be passed above")
+            .0
+    }
+
+    #[test]
+    fn wall_is_rasterised() {
+        let grid = wall();
+        assert_eq!(grid.dimensions(), (10, 10));
+        assert!(grid.is_occupied(grid.cell_of(Vec2::new(0.0, -4.5))));
+        assert!(grid.is_occupied(grid.cell_of(Vec2::new(0.0, 2.5))));
+        assert!(!grid.is_occupied(grid.cell_of(Vec2::new(0.0, 3.5))));
+        assert!(!grid.is_occupied(grid.cell_of(Vec2::new(-2.0, 0.0))));
+    }
+
+    #[test]
+    fn a_star_goes_around_the_wall() {
+        let path = plan(&GridSearch::a_star(Arc::new(wall())));
+        assert_eq!(path.first(), Some(&Vec2::new(-4.0, -4.0)));
+        assert_eq!(path.last(), Some(&Vec2::new(4.0, -4.0)));
+        assert!(path.iter().any(|point| point.y > 3.0));
+    }
+
+    #[test]
+    fn theta_star_needs_fewer_waypoints() {
+        let grid = Arc::new(wall());
+        let a_star = plan(&GridSearch::a_star(Arc::clone(&grid)));
+        let theta_star = plan(&GridSearch::theta_star(grid));
+        let length = |path: &[Vec2]| path.windows(2).map(|w| w[0].distance(w[1])).sum::<f32>();
+        assert!(theta_star.len() <= a_star.len());
+        assert!(length(&theta_star) <= length(&a_star) + 1e-3);
+    }
+
+    #[test]
+    fn enclosed_goal_has_no_path() {
+        let mut colliders = Colliders::default();
+        colliders.push(
+            None,
+            Isometry2::translation(0.0, 0.0),
+            Arc::new(shape::Cuboid::new(Vector2::new(0.5, 5.0))),
+        );
+        let grid = OccupancyGrid::new(&colliders, Bounds::centered(Vec2::splat(10.0)), 1.0, 0.1);
+        let result = GridSearch::theta_star(Arc::new(grid)).plan(
+            Vec2::new(-4.0, 0.0),
+            Vec2::new(4.0, 0.0),
+            &mut StepRng::new(0, 1),
+        );
+        assert!(matches!(result, Err(PathfindingError::NoPath)));
+    }
+}
@@ -1,24 +1,44 @@
 //! Global path planning module
+//!
+//! Every planner implements [`GlobalPlanner`], and is selected by the
+//! [`PlanningStrategy`] of a formation:
+//! - [`rrtstar::RrtStar`] samples the environment with RRT*
+//! - [`grid::GridSearch`] runs A* or Theta* over an [`grid::OccupancyGrid`]
+//! - [`prm::Prm`] searches a probabilistic roadmap, shared between robots
+//!
+//! Except for [`reservation::PrioritisedPlanner`], which plans robots one after
+//! the other around the paths of each other, and is not a [`GlobalPlanner`].
 
+pub mod grid;
+pub mod prm;
+pub mod reservation;
 pub mod rrtstar;
+mod search;
 
 use std::sync::Arc;
 
 use bevy::{
-    ecs::{component::Component, entity::Entity, system::Resource},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        system::{Commands, Resource},
+    },
     math::Vec2,
-    tasks::Task,
+    tasks::{AsyncComputeTaskPool, Task},
 };
+use bevy_prng::WyRand;
 use delegate::delegate;
 use derive_more::Index;
+use gbp_config::{formation::PlanningStrategy, Config};
 use parry2d::{
+    bounding_volume::BoundingVolume,
     na::{self, Isometry2, Vector2},
     query::intersection_test,
     shape,
 };
 use rand::{
     distributions::{Distribution, Uniform},
-    RngCore,
+    RngCore, SeedableRng,
 };
 
 /// **Bevy** [`Resource`] for storing an RRT* Tree
@@ -70,6 +90,186 @@ impl Path {
 #[derive(Debug)]
 pub enum PathfindingError {
     ReachedMaxIterations,
+    /// The start and the end are not connected by any collision free path
+    NoPath,
+}
+
+/// Axis aligned rectangle of the environment, which planners sample and
+/// search within
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    /// The corner with the smallest coordinates
+    pub min: Vec2,
+    /// The corner with the largest coordinates
+    pub max: Vec2,
+}
+
+impl Bounds {
+    /// Bounds of an environment of `dimensions` centered at the origin, as
+    /// the environments of the simulator are
+    pub fn centered(dimensions: Vec2) -> Self {
+        Self {
+            min: -dimensions / 2.0,
+            max: dimensions / 2.0,
+        }
+    }
+
+    /// Width and height of the bounds
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+
+    /// Whether `point` is inside the bounds
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    /// A uniformly distributed point inside the bounds
+    pub fn sample(&self, rng: &mut dyn RngCore) -> Vec2 {
+        Vec2::new(
+            Uniform::new_inclusive(self.min.x, self.max.x).sample(rng),
+            Uniform::new_inclusive(self.min.y, self.max.y).sample(rng),
+        )
+    }
+}
+
+/// A planner finding a collision free path between two points in the
+/// environment
+pub trait GlobalPlanner: Send + Sync {
+    /// Plan a path from `start` to `end`. The path begins with `start` and
+    /// ends with `end`
+    fn plan(&self, start: Vec2, end: Vec2, rng: &mut dyn RngCore)
+        -> Result<Path, PathfindingError>;
+}
+
+/// Standalone function to spawn an async task, running `planner` from `start`
+/// to `end`
+/// - Used to run path-finding tasks that may take longer than a single frame to
+///   complete
+pub fn spawn_pathfinding_task(
+    commands: &mut Commands,
+    start: Vec2,
+    end: Vec2,
+    planner: Arc<dyn GlobalPlanner>,
+    task_target: Entity,
+    rng_source: Option<Box<dyn RngCore + Send>>,
+) {
+    let mut rng_source: Box<dyn RngCore + Send> = match rng_source {
+        Some(rng) => rng,
+        None => Box::new(WyRand::from_entropy()),
+    };
+
+    let task = AsyncComputeTaskPool::get()
+        .spawn(async move { planner.plan(start, end, &mut *rng_source) });
+
+    commands.entity(task_target).insert(PathfindingTask(task));
+}
+
+/// Insert `result` as a finished pathfinding task of `task_target`, for paths
+/// planned outside of a [`GlobalPlanner`]
+pub fn insert_planned_path(
+    commands: &mut Commands,
+    result: Result<Path, PathfindingError>,
+    task_target: Entity,
+) {
+    let task = AsyncComputeTaskPool::get().spawn(async move { result });
+    commands.entity(task_target).insert(PathfindingTask(task));
+}
+
+/// **Bevy** [`Resource`] with the occupancy grid, the roadmap and the
+/// reservation table shared by the robots planning with grid search, PRM and
+/// prioritised planning. The grid and the roadmap are built the first time a
+/// robot needs them, and everything has to be [`cleared`](Self::clear) when the
+/// [`Colliders`] change.
+#[derive(Resource, Default)]
+pub struct SharedPlanners {
+    grid: Option<Arc<grid::OccupancyGrid>>,
+    roadmap: Option<Arc<prm::Prm>>,
+    reservations: reservation::ReservationTable,
+}
+
+impl SharedPlanners {
+    /// Forget the occupancy grid, the roadmap and the reservations
+    pub fn clear(&mut self) {
+        self.grid = None;
+        self.roadmap = None;
+        self.reservations.clear();
+    }
+
+    /// The occupancy grid of `colliders` within `bounds`
+    pub fn grid(
+        &mut self,
+        config: &Config,
+        colliders: &Colliders,
+        bounds: Bounds,
+    ) -> Arc<grid::OccupancyGrid> {
+        Arc::clone(self.grid.get_or_insert_with(|| {
+            let section = &config.global_planner.grid;
+            Arc::new(grid::OccupancyGrid::new(
+                colliders,
+                bounds,
+                section.cell_size.get(),
+                section.collision_radius.get(),
+            ))
+        }))
+    }
+
+    /// The prioritised planner over the occupancy grid, and the table of the
+    /// cells reserved by the robots that have planned with it
+    pub fn prioritised(
+        &mut self,
+        config: &Config,
+        colliders: &Colliders,
+        bounds: Bounds,
+    ) -> (
+        reservation::PrioritisedPlanner,
+        &mut reservation::ReservationTable,
+    ) {
+        let planner = reservation::PrioritisedPlanner::new(
+            self.grid(config, colliders, bounds),
+            config.global_planner.reservation.max_timesteps.get(),
+        );
+        (planner, &mut self.reservations)
+    }
+
+    /// The planner of `strategy`, or `None` if the strategy does not plan each
+    /// robot on its own, i.e. only plans locally or uses
+    /// [`prioritised`](Self::prioritised) planning
+    pub fn planner(
+        &mut self,
+        strategy: PlanningStrategy,
+        config: &Config,
+        colliders: &Colliders,
+        bounds: Bounds,
+    ) -> Option<Arc<dyn GlobalPlanner>> {
+        match strategy {
+            PlanningStrategy::OnlyLocal | PlanningStrategy::Prioritised => None,
+            PlanningStrategy::RrtStar => Some(Arc::new(rrtstar::RrtStar::new(
+                colliders.clone(),
+                bounds,
+                config.rrt.clone(),
+            ))),
+            PlanningStrategy::AStar => Some(Arc::new(grid::GridSearch::a_star(
+                self.grid(config, colliders, bounds),
+            ))),
+            PlanningStrategy::ThetaStar => Some(Arc::new(grid::GridSearch::theta_star(
+                self.grid(config, colliders, bounds),
+            ))),
+            PlanningStrategy::Prm => {
+                let roadmap = self.roadmap.get_or_insert_with(|| {
+                    // Seeded, so every run of a scenario plans on the same roadmap
+                    let mut rng = WyRand::seed_from_u64(config.simulation.prng_seed);
+                    Arc::new(prm::Prm::new(
+                        colliders.clone(),
+                        bounds,
+                        &config.global_planner.prm,
+                        &mut rng,
+                    ))
+                });
+                Some(Arc::clone(roadmap) as Arc<dyn GlobalPlanner>)
+            }
+        }
+    }
 }
 
 /// **Bevy** [`Component`] for storing the pathfinding task
@@ -135,14 +335,16 @@ impl Colliders {
 struct CollisionProblem {
     colliders: Colliders,
     collision_checker: shape::Ball,
+    bounds: Bounds,
 }
 
 impl CollisionProblem {
-    fn new(colliders: Colliders) -> Self {
+    fn new(colliders: Colliders, bounds: Bounds) -> Self {
         let ball = shape::Ball::new(0.1f32);
         Self {
             colliders,
             collision_checker: ball,
+            bounds,
         }
     }
 
@@ -158,7 +360,11 @@ impl CollisionProblem {
 
         let mut intersecting = false;
 
+        let ball_aabb = self.collision_checker.aabb(&ball_pos);
         for collider in self.colliders.iter() {
+            if !collider.aabb().intersects(&ball_aabb) {
+                continue;
+            }
             let isometry = collider.isometry;
             let shape = &collider.shape;
             intersecting = intersection_test(
@@ -177,9 +383,29 @@ impl CollisionProblem {
         !intersecting
     }
 
-    fn random_sample(&self, mut rng: &mut dyn RngCore) -> Vec<f64> {
-        let between = Uniform::new(-2000.0, 2000.0);
-        // let mut rng = rng;
-        vec![between.sample(&mut rng), between.sample(&mut rng)]
+    fn is_free(&self, point: Vec2) -> bool {
+        self.is_feasible(&[f64::from(point.x), f64::from(point.y)])
+    }
+
+    /// Whether the collision checker can move along the straight line from `a`
+    /// to `b`, checked at intervals of its radius
+    fn is_segment_free(&self, a: Vec2, b: Vec2) -> bool {
+        let step = self.collision_checker.radius;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let steps = (a.distance(b) / step).ceil() as usize;
+        (0..=steps).all(|i| {
+            #[allow(clippy::cast_precision_loss)]
+            let t = if steps == 0 {
+                0.0
+            } else {
+                i as f32 / steps as f32
+            };
+            self.is_free(a.lerp(b, t))
+        })
+    }
+
+    fn random_sample(&self, rng: &mut dyn RngCore) -> Vec<f64> {
+        let sample = self.bounds.sample(rng);
+        vec![f64::from(sample.x), f64::from(sample.y)]
     }
 }
@@ -0,0 +1,193 @@
+//! Probabilistic roadmap (PRM), built once and shared between robots
+
+use bevy::math::Vec2;
+use gbp_config::PrmSection;
+use rand::RngCore;
+
+use crate::{
+    search::shortest_path, Bounds, Colliders, CollisionProblem, GlobalPlanner, Path,
+    PathfindingError,
+};
+
+/// Global planner searching a probabilistic roadmap.
+///
+/// The roadmap is a graph of collision free samples of the environment,
+/// connected when the straight line between them is collision free. Building
+/// it is the expensive part, so it is built once and shared by every robot,
+/// which only connect their start and end to it when planning.
+pub struct Prm {
+    collision_solver: CollisionProblem,
+    connection_radius: f32,
+    nodes: Vec<Vec2>,
+    edges: Vec<Vec<(usize, f32)>>,
+}
+
+impl Prm {
+    /// Build a roadmap of `params.samples` collision free samples within
+    /// `bounds`
+    pub fn new(
+        colliders: Colliders,
+        bounds: Bounds,
+        params: &PrmSection,
+        rng: &mut dyn RngCore,
+    ) -> Self {
+        let collision_solver = CollisionProblem::new(colliders, bounds)
+            .with_collision_radius(params.collision_radius.get());
+        let connection_radius = params.connection_radius.get();
+
+        let samples = params.samples.get();
+        // Give up on densely packed environments, instead of sampling forever
+        let max_attempts = samples.saturating_mul(10);
+        let nodes = std::iter::repeat_with(|| bounds.sample(rng))
+            .take(max_attempts)
+            .filter(|&sample| collision_solver.is_free(sample))
+            .take(samples)
+            .collect::<Vec<_>>();
+
+        let mut edges = vec![Vec::new(); nodes.len()];
+        for (i, &a) in nodes.iter().enumerate() {
+            for (j, &b) in nodes.iter().enumerate().skip(i + 1) {
+                let distance = a.distance(b);
+                if distance <= connection_radius && collision_solver.is_segment_free(a, b) {
+                    edges[i].push((j, distance));
+                    edges[j].push((i, distance));
+                }
+            }
+        }
+
+        Self {
+            collision_solver,
+            connection_radius,
+            nodes,
+            edges,
+        }
+    }
+
+    /// The sampled nodes of the roadmap
+    pub fn nodes(&self) -> &[Vec2] {
+        &self.nodes
+    }
+
+    /// The nodes of the roadmap `point` can be connected to
+    fn connections(&self, point: Vec2) -> impl Iterator<Item = (usize, f32)> + '_ {
+        self.nodes.iter().enumerate().filter_map(move |(i, &node)| {
+            let distance = point.distance(node);
+            (distance <= self.connection_radius
+                && self.collision_solver.is_segment_free(point, node))
+            .then_some((i, distance))
+        })
+    }
+}
+
+impl GlobalPlanner for Prm {
+    fn plan(
+        &self,
+        start: Vec2,
+        end: Vec2,
+        _rng: &mut dyn RngCore,
+    ) -> Result<Path, PathfindingError> {
+        if self.collision_solver.is_segment_free(start, end) {
+            return Ok(Path(vec![start, end]));
+        }
+
+        // The start and the end are added as the two last nodes of the roadmap
+        let (start_node, end_node) = (self.nodes.len(), self.nodes.len() + 1);
+        let from_start = self.connections(start).collect::<Vec<_>>();
+        let to_end = self.connections(end).collect::<Vec<_>>();
+        let position = |node: usize| match node {
+            node if node == start_node => start,
+            node if node == end_node => end,
+            node => self.nodes[node],
+        };
+
+        let path = shortest_path(
+            self.nodes.len() + 2,
+            start_node,
+            end_node,
+            |node| {
+                if node == start_node {
+                    return from_start.clone();
+                }
+                let mut neighbours = self.edges[node].clone();
+                if let Some(&(_, distance)) = to_end.iter().find(|&&(n, _)| n == node) {
+                    neighbours.push((end_node, distance));
+                }
+                neighbours
+            },
+            |node| position(node).distance(end),
+            None,
+        )
+        .ok_or(PathfindingError::NoPath)?;
+
+        Ok(Path(path.into_iter().map(position).collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{num::NonZeroUsize, sync::Arc};
+
+    use parry2d::{
+        na::{Isometry2, Vector2},
+        shape,
+    };
+    use rand::{rngs::mock::StepRng, SeedableRng};
+
+    use super::*;
+
+    /// A 10 x 10 environment with a wall from the bottom up to y = 3, in the
+    /// middle of it
+    fn prm(samples: usize) -> Prm {
+        let mut colliders = Colliders::default();
+        colliders.push(
+            None,
+            Isometry2::translation(0.0, -1.0),
+            Arc::new(shape::Cuboid::new(Vector2::new(0.5, 4.0))),
+        );
+        let params = PrmSection {
+            samples: NonZeroUsize::new(samples).expect("samples > 0"),
+            connection_radius: 3.0.try_into().expect("positive and finite"),
+            collision_radius: 0.1.try_into().expect("positive and finite"),
+        };
+        Prm::new(
+            colliders,
+            Bounds::centered(Vec2::splat(10.0)),
+            &params,
+            &mut bevy_prng::WyRand::seed_from_u64(0),
+        )
+    }
+
+    #[test]
+    fn samples_are_collision_free() {
+        let prm = prm(200);
+        assert_eq!(prm.nodes().len(), 200);
+        assert!(prm
+            .nodes()
+            .iter()
+            .all(|node| node.x.abs() > 0.5 || node.y > 3.0));
+    }
+
+    #[test]
+    fn plans_around_the_wall() {
+        let prm = prm(200);
+        let (start, end) = (Vec2::new(-4.0, -4.0), Vec2::new(4.0, -4.0));
+        let path = prm
+            .plan(start, end, &mut StepRng::new(0, 1))
+            .expect("the wall can be passed above")
+            .0;
+        assert_eq!(path.first(), Some(&start));
+        assert_eq!(path.last(), Some(&end));
+        assert!(path.iter().any(|point| point.y > 3.0));
+        assert!(path
+            .windows(2)
+            .all(|w| prm.collision_solver.is_segment_free(w[0], w[1])));
+    }
+
+    #[test]
+    fn straight_line_when_nothing_is_in_the_way() {
+        let prm = prm(10);
+        let (start, end) = (Vec2::new(-4.0, 4.0), Vec2::new(4.0, 4.0));
+        let path = prm.plan(start, end, &mut StepRng::new(0, 1));
+        assert_eq!(path.map(|path| path.0).ok(), Some(vec![start, end]));
+    }
+}
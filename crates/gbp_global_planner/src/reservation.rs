@@ -0,0 +1,354 @@
+//! Prioritised planning around a space-time reservation table
+//!
+//! Robots plan one after the other with A* over the cells of an
+//! [`OccupancyGrid`] and time. Every planned path reserves the cells it passes
+//! through for the time the robot is in them, and robots planning later have
+//! to wait or take another route around the reservations. Planning the robots
+//! in order of priority lets the robots with a higher priority take the
+//! shortest routes.
+
+use std::{
+    collections::{hash_map::Entry, BinaryHeap, HashMap},
+    sync::Arc,
+};
+
+use bevy::{ecs::entity::Entity, math::Vec2};
+
+use crate::{
+    grid::{corners, Cell, OccupancyGrid},
+    Path, PathfindingError,
+};
+
+/// A cell reserved by a robot over an interval of time
+#[derive(Debug, Clone, Copy)]
+struct Reservation {
+    owner: Entity,
+    from:  f32,
+    until: f32,
+}
+
+/// The cells of an [`OccupancyGrid`] reserved by robots over time
+#[derive(Debug, Default)]
+pub struct ReservationTable {
+    cells: HashMap<Cell, Vec<Reservation>>,
+}
+
+impl ReservationTable {
+    /// Whether a robot other than `owner` has reserved `cell` at some point in
+    /// time between `from` and `until`
+    pub fn is_reserved(&self, cell: Cell, from: f32, until: f32, owner: Entity) -> bool {
+        self.cells.get(&cell).is_some_and(|reservations| {
+            reservations.iter().any(|reservation| {
+                reservation.owner != owner && reservation.from < until && from < reservation.until
+            })
+        })
+    }
+
+    /// Reserve `cell` for `owner` from `from` until `until`
+    pub fn reserve(&mut self, owner: Entity, cell: Cell, from: f32, until: f32) {
+        self.cells
+            .entry(cell)
+            .or_default()
+            .push(Reservation { owner, from, until });
+    }
+
+    /// Remove every reservation of `owner`, e.g. before it plans again
+    pub fn release(&mut self, owner: Entity) {
+        self.retain(|reservation| reservation.owner != owner);
+    }
+
+    /// Remove the reservations of the robots `exists` is false for, e.g.
+    /// because they have been despawned
+    pub fn retain_owners(&mut self, exists: impl Fn(Entity) -> bool) {
+        self.retain(|reservation| exists(reservation.owner));
+    }
+
+    /// Remove every reservation that ended before `now`
+    pub fn prune(&mut self, now: f32) {
+        self.retain(|reservation| reservation.until >= now);
+    }
+
+    /// Remove every reservation
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    /// The number of reserved intervals over all cells
+    pub fn len(&self) -> usize {
+        self.cells.values().map(Vec::len).sum()
+    }
+
+    /// Whether no cell is reserved
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    fn retain(&mut self, keep: impl Fn(&Reservation) -> bool) {
+        self.cells.retain(|_, reservations| {
+            reservations.retain(&keep);
+            !reservations.is_empty()
+        });
+    }
+}
+
+/// A cell at a timestep of a robot's path, i.e. the number of moves since it
+/// departed
+type State = (Cell, usize);
+
+/// A state in the open set, ordered so the [`BinaryHeap`] pops the state with
+/// the lowest estimated number of moves first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Open {
+    estimate: usize,
+    state:    State,
+}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // Among equal estimates, prefer the state furthest along in time, as it
+        // is closer to the goal
+        other
+            .estimate
+            .cmp(&self.estimate)
+            .then_with(|| self.state.1.cmp(&other.state.1))
+            .then_with(|| other.state.0.cmp(&self.state.0))
+    }
+}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Planner of paths over an [`OccupancyGrid`] and time, around the cells
+/// reserved in a [`ReservationTable`].
+///
+/// A robot moves to a cell sharing a side with its cell, or waits where it is,
+/// every `step` seconds. During a move it occupies both the cell it leaves and
+/// the cell it enters, so two robots never swap cells or pass through the same
+/// cell at the same time. Once at its goal, the robot keeps it reserved until
+/// it plans again.
+///
+/// The waits are not part of the returned [`Path`], as the tracking factors
+/// follow the path without any notion of time. Robots moving at their target
+/// speed still keep their reservations, as the waits are only needed where the
+/// path of another robot crosses.
+pub struct PrioritisedPlanner {
+    grid: Arc<OccupancyGrid>,
+    max_timesteps: usize,
+}
+
+impl PrioritisedPlanner {
+    /// Plan over `grid`, with paths of at most `max_timesteps` moves
+    pub const fn new(grid: Arc<OccupancyGrid>, max_timesteps: usize) -> Self {
+        Self {
+            grid,
+            max_timesteps,
+        }
+    }
+
+    /// Plan a path for `owner` from `start` to `end` around the reservations
+    /// of the other robots in `table`, departing at time `departure` and moving
+    /// a cell every `step` seconds. The previous reservations of `owner` are
+    /// released, and the cells of the new path reserved.
+    ///
+    /// # Errors
+    ///
+    /// [`PathfindingError::NoPath`] if there is no path around the
+    /// reservations within the maximum number of moves
+    pub fn plan(
+        &self,
+        table: &mut ReservationTable,
+        owner: Entity,
+        start: Vec2,
+        end: Vec2,
+        departure: f32,
+        step: f32,
+    ) -> Result<Path, PathfindingError> {
+        table.release(owner);
+
+        let start_cell = self
+            .grid
+            .nearest_free(start)
+            .ok_or(PathfindingError::NoPath)?;
+        let end_cell = self
+            .grid
+            .nearest_free(end)
+            .ok_or(PathfindingError::NoPath)?;
+        #[allow(clippy::cast_precision_loss)]
+        let time = |timestep: usize| (timestep as f32).mul_add(step, departure);
+
+        let cells = self
+            .search(table, owner, start_cell, end_cell, &time)
+            .ok_or(PathfindingError::NoPath)?;
+
+        for (timestep, &cell) in cells.iter().enumerate() {
+            let until = if timestep + 1 == cells.len() {
+                f32::INFINITY
+            } else {
+                time(timestep + 1)
+            };
+            table.reserve(owner, cell, time(timestep.saturating_sub(1)), until);
+        }
+
+        let mut moves = cells;
+        moves.dedup();
+        Ok(self.grid.path_through(corners(moves), start, end))
+    }
+
+    /// The cell of the robot at every timestep from `start` to `goal`
+    fn search(
+        &self,
+        table: &ReservationTable,
+        owner: Entity,
+        start: Cell,
+        goal: Cell,
+        time: &dyn Fn(usize) -> f32,
+    ) -> Option<Vec<Cell>> {
+        let heuristic = |cell: Cell| cell.0.abs_diff(goal.0) + cell.1.abs_diff(goal.1);
+
+        let mut parent = HashMap::<State, State>::new();
+        let mut open = BinaryHeap::from([Open {
+            estimate: heuristic(start),
+            state:    (start, 0),
+        }]);
+        parent.insert((start, 0), (start, 0));
+
+        while let Some(Open {
+            state: (cell, timestep),
+            ..
+        }) = open.pop()
+        {
+            if cell == goal && !table.is_reserved(goal, time(timestep), f32::INFINITY, owner) {
+                let mut cells = vec![cell];
+                let mut state = (cell, timestep);
+                while state != (start, 0) {
+                    state = parent[&state];
+                    cells.push(state.0);
+                }
+                cells.reverse();
+                return Some(cells);
+            }
+            if timestep >= self.max_timesteps {
+                continue;
+            }
+
+            let (now, next) = (time(timestep), time(timestep + 1));
+            if table.is_reserved(cell, now, next, owner) {
+                continue;
+            }
+            let moves = std::iter::once(cell).chain(self.grid.orthogonal_neighbours(cell));
+            for neighbour in moves {
+                if table.is_reserved(neighbour, now, next, owner) {
+                    continue;
+                }
+                if let Entry::Vacant(entry) = parent.entry((neighbour, timestep + 1)) {
+                    entry.insert((cell, timestep));
+                    open.push(Open {
+                        estimate: timestep + 1 + heuristic(neighbour),
+                        state:    (neighbour, timestep + 1),
+                    });
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use parry2d::{
+        na::{Isometry2, Vector2},
+        shape,
+    };
+
+    use super::*;
+    use crate::{Bounds, Colliders};
+
+    /// A 10 x 2 corridor, one cell wide, with a cell to step aside into below
+    /// its middle
+    fn corridor() -> Arc<OccupancyGrid> {
+        let mut colliders = Colliders::default();
+        for x in [-3.0, 3.0] {
+            colliders.push(
+                None,
+                Isometry2::translation(x, -0.5),
+                Arc::new(shape::Cuboid::new(Vector2::new(2.3, 0.4))),
+            );
+        }
+        Arc::new(OccupancyGrid::new(
+            &colliders,
+            Bounds::centered(Vec2::new(10.0, 2.0)),
+            1.0,
+            0.1,
+        ))
+    }
+
+    fn robot(index: u32) -> Entity {
+        Entity::from_raw(index)
+    }
+
+    #[test]
+    fn reservations_overlap_in_time() {
+        let mut table = ReservationTable::default();
+        table.reserve(robot(0), (1, 1), 2.0, 4.0);
+        assert!(table.is_reserved((1, 1), 3.0, 5.0, robot(1)));
+        assert!(!table.is_reserved((1, 1), 4.0, 5.0, robot(1)));
+        assert!(!table.is_reserved((1, 1), 3.0, 5.0, robot(0)));
+        assert!(!table.is_reserved((1, 2), 3.0, 5.0, robot(1)));
+
+        table.prune(4.5);
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn single_robot_takes_the_shortest_path() {
+        let planner = PrioritisedPlanner::new(corridor(), 100);
+        let mut table = ReservationTable::default();
+        let (start, end) = (Vec2::new(-4.5, 0.5), Vec2::new(4.5, 0.5));
+        let path = planner
+            .plan(&mut table, robot(0), start, end, 0.0, 1.0)
+            .expect("the corridor is free");
+        assert_eq!(path.0, vec![start, end]);
+        // The goal stays reserved after the robot arrives
+        assert!(table.is_reserved((9, 1), 100.0, 200.0, robot(1)));
+    }
+
+    #[test]
+    fn lower_priority_robot_steps_aside() {
+        let planner = PrioritisedPlanner::new(corridor(), 100);
+        let mut table = ReservationTable::default();
+        let (left, right) = (Vec2::new(-4.5, 0.5), Vec2::new(2.5, 0.5));
+        planner
+            .plan(&mut table, robot(0), left, Vec2::new(4.5, 0.5), 0.0, 1.0)
+            .expect("the corridor is free");
+
+        // The second robot goes the opposite way, so it has to let the first
+        // robot pass in one of the cells below the middle of the corridor
+        let path = planner
+            .plan(&mut table, robot(1), right, left, 0.0, 1.0)
+            .expect("the second robot can step aside");
+        assert!(path.0.iter().any(|point| point.y < 0.0));
+        assert_eq!(path.0.first(), Some(&right));
+        assert_eq!(path.0.last(), Some(&left));
+    }
+
+    #[test]
+    fn blocked_goal_has_no_path() {
+        let planner = PrioritisedPlanner::new(corridor(), 100);
+        let mut table = ReservationTable::default();
+        let goal = Vec2::new(4.5, 0.5);
+        planner
+            .plan(&mut table, robot(0), Vec2::new(3.5, 0.5), goal, 0.0, 1.0)
+            .expect("the corridor is free");
+
+        let path = planner.plan(&mut table, robot(1), Vec2::new(-4.5, 0.5), goal, 0.0, 1.0);
+        assert!(matches!(path, Err(PathfindingError::NoPath)));
+        // A failed plan leaves no reservations behind
+        assert!(!table.is_reserved((0, 1), 0.0, 1.0, robot(0)));
+    }
+}
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use bevy::{
     ecs::{entity::Entity, system::Commands},
     math::Vec2,
@@ -7,32 +9,39 @@ use bevy_prng::WyRand;
 use gbp_config::RRTSection;
 use rand::{RngCore, SeedableRng};
 
-use crate::{Colliders, CollisionProblem, Path, PathfindingError, PathfindingTask};
-
-/// Standalone function to spawn an async task for pathfinding
-/// - Used to run path-finding tasks that may take longer than a single frame to
-///   complete
-pub fn spawn_pathfinding_task(
-    commands: &mut Commands,
-    start: Vec2,
-    end: Vec2,
-    // smooth: bool,
-    rrt_params: RRTSection,
-    colliders: Colliders,
-    task_target: Entity,
-    rng_source: Option<Box<dyn RngCore + Send>>,
-) {
-    let mut rng_source: Box<dyn RngCore + Send> = match rng_source {
-        Some(rng) => rng,
-        None => Box::new(WyRand::from_entropy()),
-    };
+use crate::{
+    Bounds, Colliders, CollisionProblem, GlobalPlanner, Path, PathfindingError, PathfindingTask,
+};
 
-    let collision_solver =
-        CollisionProblem::new(colliders).with_collision_radius(rrt_params.collision_radius.get());
+/// Global planner sampling the environment with RRT*
+pub struct RrtStar {
+    collision_solver: CollisionProblem,
+    rrt_params:       RRTSection,
+}
 
-    let task_pool = AsyncComputeTaskPool::get();
+impl RrtStar {
+    /// Plan around `colliders` with samples drawn from within `bounds`
+    pub fn new(colliders: Colliders, bounds: Bounds, rrt_params: RRTSection) -> Self {
+        let collision_solver = CollisionProblem::new(colliders, bounds)
+            .with_collision_radius(rrt_params.collision_radius.get());
+        Self {
+            collision_solver,
+            rrt_params,
+        }
+    }
+}
 
-    let task = task_pool.spawn(async move {
+impl GlobalPlanner for RrtStar {
+    fn plan(
+        &self,
+        start: Vec2,
+        end: Vec2,
+        rng_source: &mut dyn RngCore,
+    ) -> Result<Path, PathfindingError> {
+        let Self {
+            collision_solver,
+            rrt_params,
+        } = self;
         let start = [start.x as f64, start.y as f64];
         let end = [end.x as f64, end.y as f64];
 
@@ -40,7 +49,6 @@ pub fn spawn_pathfinding_task(
             &start,
             &end,
             |x: &[f64]| collision_solver.is_feasible(x),
-            // || collision_solver.random_sample(&mut *rng_source.lock().unwrap()),
             || collision_solver.random_sample(&mut *rng_source),
             rrt_params.step_size.get() as f64,
             rrt_params.max_iterations.get(),
@@ -77,9 +85,31 @@ pub fn spawn_pathfinding_task(
             }
         })
         .map_err(|_| PathfindingError::ReachedMaxIterations)
-    });
+    }
+}
 
-    commands.entity(task_target).insert(PathfindingTask(task));
+/// Standalone function to spawn an async task for pathfinding
+/// - Used to run path-finding tasks that may take longer than a single frame to
+///   complete
+pub fn spawn_pathfinding_task(
+    commands: &mut Commands,
+    start: Vec2,
+    end: Vec2,
+    // smooth: bool,
+    rrt_params: RRTSection,
+    colliders: Colliders,
+    bounds: Bounds,
+    task_target: Entity,
+    rng_source: Option<Box<dyn RngCore + Send>>,
+) {
+    crate::spawn_pathfinding_task(
+        commands,
+        start,
+        end,
+        Arc::new(RrtStar::new(colliders, bounds, rrt_params)),
+        task_target,
+        rng_source,
+    );
 }
 
 /// Standalone function to spawn an async task for pathfinding
@@ -92,6 +122,7 @@ pub fn spawn_pathfinding_task_full_tree(
     // smooth: bool,
     rrt_params: RRTSection,
     colliders: Colliders,
+    bounds: Bounds,
     task_target: Entity,
     rng_source: Option<Box<dyn RngCore + Send>>,
 ) {
@@ -100,8 +131,8 @@ pub fn spawn_pathfinding_task_full_tree(
         None => Box::new(WyRand::from_entropy()),
     };
 
-    let collision_solver =
-        CollisionProblem::new(colliders).with_collision_radius(rrt_params.collision_radius.get());
+    let collision_solver = CollisionProblem::new(colliders, bounds)
+        .with_collision_radius(rrt_params.collision_radius.get());
 
     let task_pool = AsyncComputeTaskPool::get();
 
@@ -0,0 +1,150 @@
+//! Shortest path search shared by the grid and roadmap planners
+
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+/// A node in the open set, ordered so the [`BinaryHeap`] pops the node with
+/// the lowest estimated cost first
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Open {
+    estimate: f32,
+    node:     usize,
+}
+
+impl Eq for Open {}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Line of sight and distance between two nodes, used by any-angle search
+pub type AnyAngle<'a> = (
+    &'a dyn Fn(usize, usize) -> bool,
+    &'a dyn Fn(usize, usize) -> f32,
+);
+
+/// Find the shortest path from `start` to `goal` among `nodes` nodes.
+///
+/// `neighbours` yields every neighbour of a node with the cost of moving to
+/// it, and `heuristic` estimates the remaining cost from a node to `goal`
+/// without overestimating it. When `line_of_sight` between the parent of a
+/// node and its neighbour is given, the neighbour is connected directly to the
+/// parent at the cost of `distance` between them, which turns A* into Theta*.
+///
+/// Returns the nodes of the path from `start` to `goal`, both included.
+pub fn shortest_path<N, I>(
+    nodes: usize,
+    start: usize,
+    goal: usize,
+    mut neighbours: N,
+    heuristic: impl Fn(usize) -> f32,
+    any_angle: Option<AnyAngle>,
+) -> Option<Vec<usize>>
+where
+    N: FnMut(usize) -> I,
+    I: IntoIterator<Item = (usize, f32)>,
+{
+    let mut cost = vec![f32::INFINITY; nodes];
+    let mut parent = vec![usize::MAX; nodes];
+    let mut closed = vec![false; nodes];
+    let mut open = BinaryHeap::new();
+
+    cost[start] = 0.0;
+    parent[start] = start;
+    open.push(Open {
+        estimate: heuristic(start),
+        node:     start,
+    });
+
+    while let Some(Open { node, .. }) = open.pop() {
+        if node == goal {
+            let mut path = vec![goal];
+            while *path.last()? != start {
+                path.push(parent[*path.last()?]);
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if std::mem::replace(&mut closed[node], true) {
+            continue;
+        }
+
+        for (neighbour, step) in neighbours(node) {
+            if closed[neighbour] {
+                continue;
+            }
+            let (via, through) = match any_angle {
+                Some((line_of_sight, distance)) if line_of_sight(parent[node], neighbour) => (
+                    parent[node],
+                    cost[parent[node]] + distance(parent[node], neighbour),
+                ),
+                _ => (node, cost[node] + step),
+            };
+            if through < cost[neighbour] {
+                cost[neighbour] = through;
+                parent[neighbour] = via;
+                open.push(Open {
+                    estimate: through + heuristic(neighbour),
+                    node:     neighbour,
+                });
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 0 - 1 - 2 - 3 with a shortcut 0 - 3 that is more expensive than the
+    /// detour
+    fn neighbours(node: usize) -> Vec<(usize, f32)> {
+        match node {
+            0 => vec![(1, 1.0), (3, 4.0)],
+            1 => vec![(0, 1.0), (2, 1.0)],
+            2 => vec![(1, 1.0), (3, 1.0)],
+            3 => vec![(2, 1.0), (0, 4.0)],
+            _ => vec![],
+        }
+    }
+
+    #[test]
+    fn finds_the_cheapest_path() {
+        let path = shortest_path(4, 0, 3, neighbours, |_| 0.0, None);
+        assert_eq!(path, Some(vec![0, 1, 2, 3]));
+    }
+
+    #[test]
+    fn unreachable_goal_has_no_path() {
+        let path = shortest_path(5, 0, 4, neighbours, |_| 0.0, None);
+        assert_eq!(path, None);
+    }
+
+    #[test]
+    fn line_of_sight_skips_intermediate_nodes() {
+        let line_of_sight = |_: usize, _: usize| true;
+        #[allow(clippy::cast_precision_loss)]
+        let distance = |a: usize, b: usize| a.abs_diff(b) as f32 * 0.5;
+        let path = shortest_path(
+            4,
+            0,
+            3,
+            neighbours,
+            |_| 0.0,
+            Some((&line_of_sight, &distance)),
+        );
+        assert_eq!(path, Some(vec![0, 3]));
+    }
+}
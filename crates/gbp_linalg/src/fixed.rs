@@ -0,0 +1,307 @@
+//! Stack allocated, fixed size vectors and matrices.
+//!
+//! The messages of GBP are between variables with a small, constant number of
+//! degrees of freedom, and factors are connected to at most two variables. The
+//! matrices involved are therefore small enough to live on the stack, where
+//! they can be added, multiplied and inverted without any heap allocations.
+//! [`nalgebra`] is used as the backend, and the functions in this module
+//! convert to and from the heap allocated [`Vector`] and [`Matrix`] of
+//! ndarray, which are used everywhere the dimensions are not known at compile
+//! time.
+
+use crate::{Float, Matrix, MatrixView, Vector, VectorView};
+
+/// Stack allocated column vector with `N` elements
+pub type SVector<const N: usize> = nalgebra::SVector<Float, N>;
+/// Stack allocated matrix with `R` rows and `C` columns
+pub type SMatrix<const R: usize, const C: usize> = nalgebra::SMatrix<Float, R, C>;
+
+/// Largest dimension of a square matrix, that [`CholeskyExt`] handles on the
+/// stack. Larger matrices are converted to a heap allocated matrix of
+/// [`nalgebra`].
+pub const MAX_STACK_DIMENSION: usize = 8;
+
+/// Relative tolerance used to decide if a matrix is symmetric
+const SYMMETRY_TOLERANCE: Float = 1e-9;
+
+/// Copy an ndarray vector into a stack allocated vector.
+/// Returns `None` if the vector does not have `N` elements.
+#[must_use]
+pub fn fixed_vector<const N: usize>(vector: VectorView<Float>) -> Option<SVector<N>> {
+    (vector.len() == N).then(|| SVector::<N>::from_fn(|i, _| vector[i]))
+}
+
+/// Copy an ndarray matrix into a stack allocated matrix.
+/// Returns `None` if the matrix is not `R` by `C`.
+#[must_use]
+pub fn fixed_matrix<const R: usize, const C: usize>(
+    matrix: MatrixView<Float>,
+) -> Option<SMatrix<R, C>> {
+    (matrix.dim() == (R, C)).then(|| SMatrix::<R, C>::from_fn(|r, c| matrix[[r, c]]))
+}
+
+/// Copy a stack allocated vector into a new ndarray vector
+#[must_use]
+pub fn ndarray_vector<const N: usize>(vector: &SVector<N>) -> Vector<Float> {
+    Vector::from_iter(vector.iter().copied())
+}
+
+/// Copy a stack allocated matrix into a new ndarray matrix
+#[must_use]
+pub fn ndarray_matrix<const R: usize, const C: usize>(matrix: &SMatrix<R, C>) -> Matrix<Float> {
+    Matrix::from_shape_fn((R, C), |(r, c)| matrix[(r, c)])
+}
+
+/// Check if `matrix` is symmetric, up to floating point errors
+fn is_symmetric<R: nalgebra::Dim, S: nalgebra::Storage<Float, R, R>>(
+    matrix: &nalgebra::Matrix<Float, R, R, S>,
+) -> bool {
+    let n = matrix.nrows();
+    (0..n).all(|r| {
+        (r + 1..n).all(|c| {
+            let (a, b) = (matrix[(r, c)], matrix[(c, r)]);
+            (a - b).abs() <= SYMMETRY_TOLERANCE * a.abs().max(b.abs()).max(1.0)
+        })
+    })
+}
+
+/// Invert a symmetric positive definite matrix, such as a precision matrix,
+/// using its Cholesky decomposition.
+///
+/// Matrices that are not symmetric positive definite are inverted using LU
+/// decomposition instead. Returns `None` if the matrix is singular.
+#[must_use]
+pub fn invert_spd<const N: usize>(matrix: &SMatrix<N, N>) -> Option<SMatrix<N, N>> {
+    if is_symmetric(matrix) {
+        if let Some(cholesky) = matrix.cholesky() {
+            return Some(cholesky.inverse());
+        }
+    }
+    matrix.try_inverse()
+}
+
+/// Determinant of a symmetric positive definite matrix, using its Cholesky
+/// decomposition. Falls back to LU decomposition, like [`invert_spd`], which
+/// is done on the heap.
+#[must_use]
+pub fn determinant_spd<const N: usize>(matrix: &SMatrix<N, N>) -> Float {
+    if is_symmetric(matrix) {
+        if let Some(cholesky) = matrix.cholesky() {
+            return cholesky.determinant();
+        }
+    }
+    nalgebra::DMatrix::from_column_slice(N, N, matrix.as_slice()).determinant()
+}
+
+/// Extension trait for inverting the square matrices of ndarray through
+/// [`invert_spd`] and [`determinant_spd`]. Matrices up to
+/// [`MAX_STACK_DIMENSION`] are decomposed on the stack, and the only heap
+/// allocation is the returned inverse.
+pub trait CholeskyExt: Sized {
+    /// Invert the matrix, see [`invert_spd`].
+    /// Returns `None` if the matrix is not square or singular.
+    fn cholesky_inverse(&self) -> Option<Self>;
+
+    /// Determinant of the matrix, see [`determinant_spd`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the matrix is not square
+    fn cholesky_determinant(&self) -> Float;
+}
+
+/// Dispatch `$f::<N>` on the dimension of a square ndarray matrix, for every
+/// dimension up to [`MAX_STACK_DIMENSION`]
+macro_rules! dispatch_on_dimension {
+    ($matrix:expr, $f:ident, $fallback:expr) => {
+        match $matrix.nrows() {
+            1 => $f::<1>($matrix),
+            2 => $f::<2>($matrix),
+            3 => $f::<3>($matrix),
+            4 => $f::<4>($matrix),
+            5 => $f::<5>($matrix),
+            6 => $f::<6>($matrix),
+            7 => $f::<7>($matrix),
+            8 => $f::<8>($matrix),
+            _ => $fallback,
+        }
+    };
+}
+
+/// The elements of a row major ndarray matrix, read as a column major matrix,
+/// i.e. the transpose of the matrix. Copies the matrix if it is not stored
+/// contiguously in row major order.
+fn transpose_of<const N: usize>(matrix: &Matrix<Float>) -> Option<SMatrix<N, N>> {
+    matrix.as_slice().map_or_else(
+        || fixed_matrix::<N, N>(matrix.t()),
+        |elements| (elements.len() == N * N).then(|| SMatrix::<N, N>::from_column_slice(elements)),
+    )
+}
+
+fn stack_inverse<const N: usize>(matrix: &Matrix<Float>) -> Option<Matrix<Float>> {
+    // The inverse of the transpose is the transpose of the inverse, so the column
+    // major elements of the inverse of the transpose are the row major elements of
+    // the inverse
+    let inverse = invert_spd(&transpose_of::<N>(matrix)?)?;
+    Matrix::from_shape_vec((N, N), inverse.as_slice().to_vec()).ok()
+}
+
+fn stack_determinant<const N: usize>(matrix: &Matrix<Float>) -> Float {
+    // The determinant of the transpose is the determinant
+    transpose_of::<N>(matrix).map_or(Float::NAN, |transpose| determinant_spd(&transpose))
+}
+
+fn heap_matrix(matrix: &Matrix<Float>) -> nalgebra::DMatrix<Float> {
+    nalgebra::DMatrix::from_fn(matrix.nrows(), matrix.ncols(), |r, c| matrix[[r, c]])
+}
+
+impl CholeskyExt for Matrix<Float> {
+    fn cholesky_inverse(&self) -> Option<Self> {
+        if !self.is_square() {
+            return None;
+        }
+
+        dispatch_on_dimension!(self, stack_inverse, {
+            let matrix = heap_matrix(self);
+            let inverse = if is_symmetric(&matrix) {
+                matrix.clone().cholesky().map(|cholesky| cholesky.inverse())
+            } else {
+                None
+            };
+            inverse
+                .or_else(|| matrix.try_inverse())
+                .map(|inverse| Self::from_shape_fn(self.dim(), |(r, c)| inverse[(r, c)]))
+        })
+    }
+
+    fn cholesky_determinant(&self) -> Float {
+        assert!(self.is_square(), "the determinant requires a square matrix");
+
+        dispatch_on_dimension!(self, stack_determinant, {
+            let matrix = heap_matrix(self);
+            let cholesky = if is_symmetric(&matrix) {
+                matrix.clone().cholesky()
+            } else {
+                None
+            };
+            cholesky.map_or_else(|| matrix.determinant(), |cholesky| cholesky.determinant())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use ndarray::array;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// A symmetric positive definite 4x4 matrix
+    fn spd() -> Matrix<Float> {
+        array![
+            [4.0, 1.0, 0.5, 0.0],
+            [1.0, 3.0, 0.0, 0.2],
+            [0.5, 0.0, 2.0, 0.1],
+            [0.0, 0.2, 0.1, 1.0]
+        ]
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        let matrix = array![[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]];
+        let fixed = fixed_matrix::<2, 3>(matrix.view()).expect("the matrix is 2x3");
+        assert_relative_eq!(fixed[(0, 2)], 3.0);
+        assert_relative_eq!(fixed[(1, 0)], 4.0);
+        assert_eq!(ndarray_matrix(&fixed), matrix);
+
+        let vector = array![1.0, 2.0, 3.0];
+        let fixed = fixed_vector::<3>(vector.view()).expect("the vector has 3 elements");
+        assert_eq!(ndarray_vector(&fixed), vector);
+    }
+
+    #[test]
+    fn conversions_check_dimensions() {
+        assert!(fixed_vector::<4>(array![1.0, 2.0, 3.0].view()).is_none());
+        assert!(fixed_matrix::<2, 2>(spd().view()).is_none());
+    }
+
+    #[test]
+    fn inverse_of_spd_matrix() {
+        let matrix = spd();
+        let inverse = matrix
+            .cholesky_inverse()
+            .expect("the matrix is positive definite");
+        let identity = matrix.dot(&inverse);
+        for ((r, c), x) in identity.indexed_iter() {
+            assert_relative_eq!(*x, if r == c { 1.0 } else { 0.0 }, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn inverse_falls_back_for_indefinite_and_asymmetric_matrices() {
+        // Invertible, but not positive definite
+        let indefinite = array![[0.0, 1.0], [1.0, 0.0]];
+        assert_eq!(indefinite.cholesky_inverse().as_ref(), Some(&indefinite));
+
+        let asymmetric = array![[2.0, 1.0], [0.0, 1.0]];
+        let inverse = asymmetric
+            .cholesky_inverse()
+            .expect("the matrix is invertible");
+        assert_eq!(inverse, array![[0.5, -0.5], [0.0, 1.0]]);
+    }
+
+    #[test]
+    fn inverse_of_column_major_matrix() {
+        // Stored in column major order, so the elements can not be read in place
+        let asymmetric = array![[2.0, 0.0], [1.0, 1.0]].reversed_axes();
+        assert!(asymmetric.as_slice().is_none());
+        let inverse = asymmetric
+            .cholesky_inverse()
+            .expect("the matrix is invertible");
+        assert_eq!(inverse, array![[0.5, -0.5], [0.0, 1.0]]);
+        assert_relative_eq!(asymmetric.cholesky_determinant(), 2.0, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn singular_matrices_have_no_inverse() {
+        assert_eq!(Matrix::<Float>::zeros((4, 4)).cholesky_inverse(), None);
+        assert_eq!(array![[1.0, 2.0], [2.0, 4.0]].cholesky_inverse(), None);
+        assert_eq!(Matrix::<Float>::zeros((2, 3)).cholesky_inverse(), None);
+    }
+
+    #[test]
+    fn large_matrices_are_inverted_on_the_heap() {
+        let n = MAX_STACK_DIMENSION + 2;
+        let matrix = Matrix::<Float>::from_shape_fn((n, n), |(r, c)| {
+            if r == c {
+                2.0
+            } else if r.abs_diff(c) == 1 {
+                -0.5
+            } else {
+                0.0
+            }
+        });
+        let inverse = matrix
+            .cholesky_inverse()
+            .expect("the matrix is positive definite");
+        let identity = matrix.dot(&inverse);
+        for ((r, c), x) in identity.indexed_iter() {
+            assert_relative_eq!(*x, if r == c { 1.0 } else { 0.0 }, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn determinant_matches_lu() {
+        let matrix = spd();
+        let lu = fixed_matrix::<4, 4>(matrix.view())
+            .expect("the matrix is 4x4")
+            .determinant();
+        assert_relative_eq!(matrix.cholesky_determinant(), lu, epsilon = 1e-12);
+        assert_relative_eq!(
+            array![[0.0, 1.0], [1.0, 0.0]].cholesky_determinant(),
+            -1.0,
+            epsilon = 1e-12
+        );
+    }
+}
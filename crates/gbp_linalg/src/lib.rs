@@ -1,5 +1,6 @@
 //! A small collection of extension traits and types for ndarray.
 
+pub mod fixed;
 pub mod pretty_print;
 
 /// `use gbp_linalg::prelude::*` to import all the common symbols from this
@@ -8,8 +9,8 @@ pub mod prelude {
     // pub use ndarray::{array, concatenate, s, Axis};
 
     pub use super::{
-        pretty_print::*, Float, GbpFloat, Matrix, MatrixView, NdarrayVectorExt, Vector, VectorNorm,
-        VectorView,
+        fixed::CholeskyExt, pretty_print::*, Float, GbpFloat, Matrix, MatrixView, NdarrayVectorExt,
+        Vector, VectorNorm, VectorView,
     };
 }
 
@@ -0,0 +1,109 @@
+//! The broad-phase queries of the simulator for 10 to 1000 robots, with every
+//! pair of robots compared to the queries of a [`SpatialGrid`] rebuilt every
+//! tick.
+//!
+//! The robots are spread out with the same density for every number of
+//! robots, about one robot per 10 x 10 metres, as in the larger scenarios.
+//!
+//! ```sh
+//! cargo bench -p gbp_spatial --bench spatial_grid
+//! ```
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use gbp_spatial::SpatialGrid;
+use glam::Vec2;
+
+const ROBOTS: [usize; 6] = [10, 50, 100, 250, 500, 1000];
+const COMMUNICATION_RADIUS: f32 = 20.0;
+const ROBOT_RADIUS: f32 = 2.5;
+
+/// `n` robots spread uniformly at random over a square with room for 10 x 10
+/// metres per robot
+fn robots(n: usize) -> Vec<(Vec2, usize)> {
+    #[allow(clippy::cast_precision_loss)]
+    let size = 10.0 * (n as f32).sqrt();
+    let mut state = 0x9E37_79B9_u32;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        #[allow(clippy::cast_precision_loss)]
+        let unit = state as f32 / u32::MAX as f32;
+        unit * size
+    };
+    (0..n).map(|i| (Vec2::new(next(), next()), i)).collect()
+}
+
+/// Every robot finds the robots within its communication range
+fn bench_neighbours(c: &mut Criterion) {
+    let mut group = c.benchmark_group("neighbours");
+    for n in ROBOTS {
+        let robots = robots(n);
+        group.bench_with_input(BenchmarkId::new("all_pairs", n), &robots, |b, robots| {
+            b.iter(|| {
+                robots
+                    .iter()
+                    .map(|(position, id)| {
+                        robots
+                            .iter()
+                            .filter(|(other, other_id)| {
+                                other_id != id && position.distance(*other) <= COMMUNICATION_RADIUS
+                            })
+                            .count()
+                    })
+                    .sum::<usize>()
+            });
+        });
+        let mut grid = SpatialGrid::default();
+        group.bench_with_input(BenchmarkId::new("spatial_grid", n), &robots, |b, robots| {
+            b.iter(|| {
+                grid.rebuild(COMMUNICATION_RADIUS, black_box(robots).iter().copied());
+                robots
+                    .iter()
+                    .map(|(position, id)| {
+                        grid.within(*position, COMMUNICATION_RADIUS)
+                            .filter(|(_, other_id)| other_id != id)
+                            .count()
+                    })
+                    .sum::<usize>()
+            });
+        });
+    }
+    group.finish();
+}
+
+/// Every pair of robots close enough to collide
+fn bench_collision_pairs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("collision_pairs");
+    for n in ROBOTS {
+        let robots = robots(n);
+        group.bench_with_input(BenchmarkId::new("all_pairs", n), &robots, |b, robots| {
+            b.iter(|| {
+                let mut pairs = 0;
+                for (i, (a, _)) in robots.iter().enumerate() {
+                    for (b, _) in &robots[i + 1..] {
+                        if a.distance(*b) <= 2.0 * ROBOT_RADIUS {
+                            pairs += 1;
+                        }
+                    }
+                }
+                pairs
+            });
+        });
+        // The grid is shared with the neighbour queries, so its cells are
+        // sized for the communication radius
+        let mut grid = SpatialGrid::default();
+        group.bench_with_input(BenchmarkId::new("spatial_grid", n), &robots, |b, robots| {
+            b.iter(|| {
+                grid.rebuild(COMMUNICATION_RADIUS, black_box(robots).iter().copied());
+                grid.pairs_within(2.0 * ROBOT_RADIUS).count()
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_neighbours, bench_collision_pairs);
+criterion_main!(benches);
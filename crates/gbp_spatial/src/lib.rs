@@ -0,0 +1,345 @@
+//! Broad-phase spatial index of points in the plane.
+//!
+//! The simulator rebuilds a [`SpatialGrid`] of the robots every tick, so
+//! finding the robots within communication range, or the pairs of robots that
+//! could collide, does not compare every robot against every other robot.
+
+use glam::Vec2;
+
+/// A uniform grid of square cells, each holding the entries positioned inside
+/// it.
+///
+/// The grid covers the bounding box of its entries, and the entries are kept
+/// sorted by cell in a single vector, which is reused between rebuilds.
+/// Queries visit the cells overlapping the query, and then compare the exact
+/// distance to the entries in them. A cell size close to the radius of the
+/// most common query keeps the number of visited cells and entries low.
+#[derive(Debug, Clone)]
+pub struct SpatialGrid<T> {
+    cell_size: f32,
+    /// Position of the lower left corner of the grid
+    origin:    Vec2,
+    columns:   usize,
+    rows:      usize,
+    /// The entries of cell `i` are `entries[starts[i]..starts[i + 1]]`, with
+    /// the cells in row-major order
+    starts:    Vec<usize>,
+    entries:   Vec<(Vec2, T)>,
+}
+
+impl<T> Default for SpatialGrid<T> {
+    fn default() -> Self {
+        Self {
+            cell_size: 1.0,
+            origin:    Vec2::ZERO,
+            columns:   0,
+            rows:      0,
+            starts:    vec![0],
+            entries:   Vec::new(),
+        }
+    }
+}
+
+impl<T> SpatialGrid<T> {
+    /// Create a grid with cells of side length `cell_size`, holding `entries`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cell_size` is not strictly positive and finite, or a
+    /// position is not finite
+    pub fn new(cell_size: f32, entries: impl IntoIterator<Item = (Vec2, T)>) -> Self {
+        let mut grid = Self::default();
+        grid.rebuild(cell_size, entries);
+        grid
+    }
+
+    /// Replace the entries of the grid with `entries`, in cells of side length
+    /// `cell_size`.
+    ///
+    /// Entries spread out far apart compared to `cell_size` would need a lot of
+    /// empty cells, so the cells are made larger until there are at most a few
+    /// cells per entry.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cell_size` is not strictly positive and finite, or a
+    /// position is not finite
+    pub fn rebuild(&mut self, cell_size: f32, entries: impl IntoIterator<Item = (Vec2, T)>) {
+        assert!(
+            cell_size.is_finite() && cell_size > 0.0,
+            "the cell size must be strictly positive and finite, but is {cell_size}"
+        );
+        self.entries.clear();
+        self.entries.extend(entries);
+        assert!(
+            self.entries
+                .iter()
+                .all(|(position, _)| position.is_finite()),
+            "every position must be finite"
+        );
+
+        let (min, max) = self.entries.iter().fold(
+            (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+            |(min, max), (position, _)| (min.min(*position), max.max(*position)),
+        );
+        let extent = if self.entries.is_empty() {
+            Vec2::ZERO
+        } else {
+            max - min
+        };
+        let max_cells = 4 * self.entries.len() + 16;
+        let mut cell_size = cell_size;
+        #[allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            clippy::cast_precision_loss
+        )]
+        let (columns, rows) = loop {
+            let cells = (extent / cell_size).floor() + 1.0;
+            if cells.x * cells.y <= max_cells as f32 {
+                break (cells.x as usize, cells.y as usize);
+            }
+            cell_size *= 2.0;
+        };
+        self.cell_size = cell_size;
+        self.origin = if self.entries.is_empty() {
+            Vec2::ZERO
+        } else {
+            min
+        };
+        self.columns = columns;
+        self.rows = rows;
+
+        // Counting sort of the entries by cell
+        self.starts.clear();
+        self.starts.resize(columns * rows + 1, 0);
+        for (position, _) in &self.entries {
+            let cell = self.cell_of(*position);
+            self.starts[cell + 1] += 1;
+        }
+        for i in 1..self.starts.len() {
+            self.starts[i] += self.starts[i - 1];
+        }
+        // Place every entry in its cell, by swapping entries into place, so
+        // the entries do not have to be `Clone`
+        let mut next = self.starts.clone();
+        for cell in 0..columns * rows {
+            while next[cell] < self.starts[cell + 1] {
+                let target = self.cell_of(self.entries[next[cell]].0);
+                if target == cell {
+                    next[cell] += 1;
+                } else {
+                    self.entries.swap(next[cell], next[target]);
+                    next[target] += 1;
+                }
+            }
+        }
+    }
+
+    /// Side length of the cells, which can be larger than the one the grid
+    /// was built with, see [`SpatialGrid::rebuild`]
+    pub const fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// The number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the grid has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Every entry, ordered by cell
+    pub fn iter(&self) -> impl Iterator<Item = &(Vec2, T)> {
+        self.entries.iter()
+    }
+
+    /// The entries at most `radius` from `center`
+    pub fn within(&self, center: Vec2, radius: f32) -> impl Iterator<Item = &(Vec2, T)> {
+        self.indices_within(center, radius)
+            .map(|index| &self.entries[index])
+    }
+
+    /// The entries inside the axis aligned rectangle from `min` to `max`,
+    /// borders included
+    pub fn in_rect(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = &(Vec2, T)> {
+        self.indices_in_cells(min, max)
+            .map(|index| &self.entries[index])
+            .filter(move |(position, _)| position.cmpge(min).all() && position.cmple(max).all())
+    }
+
+    /// Every unordered pair of entries at most `radius` apart, each pair
+    /// once
+    pub fn pairs_within(&self, radius: f32) -> impl Iterator<Item = (&(Vec2, T), &(Vec2, T))> {
+        self.entries.iter().enumerate().flat_map(move |(i, a)| {
+            self.indices_within(a.0, radius)
+                .filter(move |&j| i < j)
+                .map(move |j| (a, &self.entries[j]))
+        })
+    }
+
+    fn indices_within(&self, center: Vec2, radius: f32) -> impl Iterator<Item = usize> + '_ {
+        let extent = Vec2::splat(radius);
+        self.indices_in_cells(center - extent, center + extent)
+            .filter(move |&index| self.entries[index].0.distance_squared(center) <= radius * radius)
+    }
+
+    /// The indices of the entries in the cells overlapping the rectangle from
+    /// `min` to `max`
+    fn indices_in_cells(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = usize> + '_ {
+        let (min_column, min_row) = self.clamped_cell(min);
+        let (max_column, max_row) = self.clamped_cell(max);
+        let overlaps = !self.is_empty()
+            && (min - self.origin).cmple(self.extent()).all()
+            && (max - self.origin).cmpge(Vec2::ZERO).all();
+        let rows = if overlaps { min_row..max_row + 1 } else { 0..0 };
+
+        // The cells of a row are consecutive, so their entries are too
+        rows.flat_map(move |row| {
+            let first = row * self.columns + min_column;
+            let last = row * self.columns + max_column;
+            self.starts[first]..self.starts[last + 1]
+        })
+    }
+
+    /// The size of the area covered by the cells
+    #[allow(clippy::cast_precision_loss)]
+    fn extent(&self) -> Vec2 {
+        Vec2::new(self.columns as f32, self.rows as f32) * self.cell_size
+    }
+
+    /// The (column, row) of the cell nearest to `position`
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn clamped_cell(&self, position: Vec2) -> (usize, usize) {
+        // Float to int casts saturate, so positions below the origin end up in
+        // the first column or row
+        let cell = ((position - self.origin) / self.cell_size).floor();
+        (
+            (cell.x as usize).min(self.columns.saturating_sub(1)),
+            (cell.y as usize).min(self.rows.saturating_sub(1)),
+        )
+    }
+
+    /// The index of the cell of `position`, which is inside the grid
+    fn cell_of(&self, position: Vec2) -> usize {
+        let (column, row) = self.clamped_cell(position);
+        row * self.columns + column
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic points spread over a `size` x `size` square centered at
+    /// the origin
+    fn points(n: usize, size: f32) -> Vec<(Vec2, usize)> {
+        let mut state = 0x2545_F491_u32;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            #[allow(clippy::cast_precision_loss)]
+            let unit = state as f32 / u32::MAX as f32;
+            (unit - 0.5) * size
+        };
+        (0..n).map(|i| (Vec2::new(next(), next()), i)).collect()
+    }
+
+    fn sorted(ids: impl Iterator<Item = usize>) -> Vec<usize> {
+        let mut ids = ids.collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids
+    }
+
+    #[test]
+    fn within_matches_brute_force() {
+        let points = points(300, 50.0);
+        let grid = SpatialGrid::new(2.0, points.clone());
+        assert_eq!(grid.len(), points.len());
+
+        // The largest radius covers the whole grid
+        for radius in [0.5, 2.0, 7.5, 100.0] {
+            for &(center, _) in points.iter().step_by(7) {
+                let expected = sorted(
+                    points
+                        .iter()
+                        .filter(|(position, _)| position.distance(center) <= radius)
+                        .map(|(_, id)| *id),
+                );
+                let actual = sorted(grid.within(center, radius).map(|(_, id)| *id));
+                assert_eq!(actual, expected, "radius {radius} around {center}");
+            }
+        }
+    }
+
+    #[test]
+    fn pairs_within_matches_brute_force() {
+        let points = points(200, 30.0);
+        let grid = SpatialGrid::new(1.5, points.clone());
+        let radius = 2.0;
+
+        let mut expected = Vec::new();
+        for (i, a) in points.iter().enumerate() {
+            for b in &points[i + 1..] {
+                if a.0.distance(b.0) <= radius {
+                    expected.push((a.1.min(b.1), a.1.max(b.1)));
+                }
+            }
+        }
+        expected.sort_unstable();
+
+        let mut actual = grid
+            .pairs_within(radius)
+            .map(|(a, b)| (a.1.min(b.1), a.1.max(b.1)))
+            .collect::<Vec<_>>();
+        actual.sort_unstable();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn in_rect_includes_the_borders() {
+        let grid = SpatialGrid::new(1.0, [
+            (Vec2::new(0.0, 0.0), 0),
+            (Vec2::new(2.0, 1.0), 1),
+            (Vec2::new(2.5, 1.0), 2),
+            (Vec2::new(-0.1, 0.5), 3),
+        ]);
+        let inside = sorted(
+            grid.in_rect(Vec2::ZERO, Vec2::new(2.0, 1.0))
+                .map(|(_, id)| *id),
+        );
+        assert_eq!(inside, vec![0, 1]);
+    }
+
+    #[test]
+    fn spread_out_entries_widen_the_cells() {
+        let far = Vec2::new(1.0e6, -1.0e6);
+        let grid = SpatialGrid::new(1.0, [(Vec2::ZERO, 0), (Vec2::new(0.5, 0.5), 1), (far, 2)]);
+        assert!(grid.cell_size() > 1.0);
+        assert_eq!(
+            sorted(grid.within(Vec2::ZERO, 1.0).map(|(_, id)| *id)),
+            vec![0, 1]
+        );
+        assert_eq!(sorted(grid.within(far, 1.0).map(|(_, id)| *id)), vec![2]);
+        // Queries outside the grid find nothing
+        assert_eq!(grid.within(Vec2::new(-10.0, 0.0), 1.0).count(), 0);
+    }
+
+    #[test]
+    fn rebuild_replaces_the_entries() {
+        let mut grid = SpatialGrid::new(1.0, points(50, 10.0));
+        grid.rebuild(4.0, [(Vec2::new(100.0, 100.0), 7)]);
+        assert_eq!(grid.len(), 1);
+        assert!((grid.cell_size() - 4.0).abs() < f32::EPSILON);
+        assert_eq!(grid.within(Vec2::ZERO, 10.0).count(), 0);
+        assert_eq!(grid.within(Vec2::new(99.0, 100.0), 1.0).count(), 1);
+
+        grid.rebuild(4.0, std::iter::empty());
+        assert!(grid.is_empty());
+        assert_eq!(grid.pairs_within(1.0).count(), 0);
+    }
+}
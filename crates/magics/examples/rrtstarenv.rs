@@ -8,7 +8,7 @@ use bevy_prng::WyRand;
 use bevy_rand::{component::EntropyComponent, resource::GlobalEntropy, traits::ForkableRng};
 use gbp_config::Config;
 use gbp_global_planner::{
-    rrtstar::spawn_pathfinding_task, Colliders, Path, PathFinder, PathfindingTask,
+    rrtstar::spawn_pathfinding_task, Bounds, Colliders, Path, PathFinder, PathfindingTask,
 };
 use magics::{
     asset_loader::AssetLoaderPlugin,
@@ -117,6 +117,7 @@ fn rrt_path(
         (With<PathFinder>, Without<PathfindingTask>),
     >,
     colliders: Res<Colliders>,
+    env: Res<gbp_environment::Environment>,
     config: Res<Config>,
     time: Res<Time>,
 ) {
@@ -130,6 +131,7 @@ fn rrt_path(
             // config.rrt.smoothing.enabled,
             config.rrt.clone(),
             colliders.clone(),
+            Bounds::centered(env.dimensions()),
             pathfinder,
             Some(Box::new(prng.clone())),
         );
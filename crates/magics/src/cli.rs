@@ -1,5 +1,7 @@
 //! cli argument parser module
 
+use std::{num::NonZeroUsize, path::PathBuf};
+
 use clap::Parser;
 use gbp_environment::EnvironmentType;
 
@@ -61,12 +63,20 @@ pub struct Cli {
     pub initial_scenario: Option<String>,
 
     /// Run the app without a window for rendering the environment
+    /// The simulation is stepped as fast as possible, and the app exits after
+    /// the results of the scenario have been exported
     #[arg(long, group = "display")]
     pub headless:   bool,
     /// Start the app in fullscreen mode
     #[arg(short, long, group = "display")]
     pub fullscreen: bool,
 
+    /// Record a trace of every fixed timestep to the given file, to be
+    /// replayed or verified with the `replay` subcommand.
+    /// The file is overwritten every time a scenario is (re)loaded
+    #[arg(long, value_name = "PATH")]
+    pub record_trace: Option<PathBuf>,
+
     // /// Enable debug plugins
     // #[arg(short, long)]
     // pub debug: bool,
@@ -86,6 +96,78 @@ pub struct Cli {
     /// Increases logging verbosity each use for up to 3 times
     #[arg(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Subcommands, running something other than the interactive app
+#[derive(Debug, clap::Subcommand)]
+pub enum Command {
+    /// Run a parameter sweep headless, one run for every combination of seed
+    /// and configuration values in the sweep spec
+    Sweep(SweepArgs),
+    /// Replay a trace recorded with `--record-trace`, or verify that
+    /// re-simulating it is deterministic
+    Replay(ReplayArgs),
+    /// Check every scenario in the simulations directory, or the given
+    /// scenario, for errors, without running them.
+    /// Exits with a non-zero status if a scenario is invalid
+    Validate(ValidateArgs),
+}
+
+/// Arguments of the `sweep` subcommand
+#[derive(Debug, clap::Args)]
+pub struct SweepArgs {
+    /// Path to the TOML file describing the sweep
+    #[arg(value_name = "SPEC")]
+    pub spec: PathBuf,
+
+    /// Number of runs to execute in parallel [default: number of cores]
+    #[arg(short, long)]
+    pub jobs: Option<NonZeroUsize>,
+
+    /// Directory to write the exports and index file to, overrides the
+    /// `output-dir` of the sweep spec
+    #[arg(short, long, value_name = "DIR")]
+    pub output_dir: Option<PathBuf>,
+
+    /// Overwrite the results of a previous sweep in the output directory
+    #[arg(short, long)]
+    pub force: bool,
+
+    /// Only print the runs of the sweep, without executing them
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Execute only the run with this id in this process, and print its
+    /// outcome to stdout. Used by the sweep to execute every run in its own
+    /// process
+    #[arg(long, value_name = "ID", hide = true)]
+    pub run: Option<usize>,
+}
+
+/// Arguments of the `replay` subcommand
+#[derive(Debug, clap::Args)]
+pub struct ReplayArgs {
+    /// Path to the trace file
+    #[arg(value_name = "TRACE")]
+    pub trace: PathBuf,
+
+    /// Instead of replaying the trace in a window, re-simulate it headless and
+    /// check that every step is identical to the recording, bit for bit.
+    /// Exits with a non-zero status at the first difference
+    #[arg(long)]
+    pub verify: bool,
+}
+
+/// Arguments of the `validate` subcommand
+#[derive(Debug, clap::Args)]
+pub struct ValidateArgs {
+    /// Directory of scenarios, or of a single scenario, to validate
+    /// [default: the simulations directory]
+    #[arg(value_name = "DIR")]
+    pub dir: Option<PathBuf>,
 }
 
 /// Verbosity level
@@ -7,7 +7,10 @@ use units::sample_rate::SampleRate;
 
 use crate::{
     factorgraph::prelude::FactorGraph,
-    planner::{collisions::resources::RobotRobotCollisions, RobotConnections},
+    planner::{
+        collisions::resources::RobotRobotCollisions, communication::CommunicationChannels,
+        RobotConnections,
+    },
     simulation_loader::{LoadSimulation, ReloadSimulation},
 };
 
@@ -20,6 +23,7 @@ pub struct SampleRates {
     pub robots: Option<SampleRate>,
     pub robot_collisions: Option<SampleRate>,
     pub variables_and_factors: Option<SampleRate>,
+    pub dropped_messages: Option<SampleRate>,
     // pub messages_sent: Option<SampleRate>,
 }
 
@@ -29,6 +33,7 @@ impl Default for SampleRates {
             robots: None,
             robot_collisions: Some(SampleRate::from_hz(5.try_into().expect("1 > 0"))),
             variables_and_factors: Some(SampleRate::from_hz(2.try_into().expect("2 > 0"))),
+            dropped_messages: Some(SampleRate::from_hz(2.try_into().expect("2 > 0"))),
             // messages_sent: Some(SampleRate::from_hz(2.try_into().expect("2 > 0"))),
         }
     }
@@ -60,7 +65,10 @@ impl Plugin for RobotDiagnosticsPlugin {
             .register_diagnostic(Diagnostic::new(Self::MESSAGES_RECEIVED_EXTERNAL_COUNT))
             .register_diagnostic(Diagnostic::new(Self::MESSAGES_SENT_EXTERNAL_COUNT))
             .register_diagnostic(Diagnostic::new(Self::MESSAGES_SENT_INTERNAL_COUNT))
-            .register_diagnostic(Diagnostic::new(Self::ROBOT_COLLISION_COUNT));
+            .register_diagnostic(Diagnostic::new(Self::MESSAGES_LOST_COUNT))
+            .register_diagnostic(Diagnostic::new(Self::MESSAGES_BANDWIDTH_EXCEEDED_COUNT))
+            .register_diagnostic(Diagnostic::new(Self::ROBOT_COLLISION_COUNT))
+            .register_diagnostic(Diagnostic::new(Self::GBP_ITERATION_COUNT));
 
         add_diagnostic_system!(app, self.sample_rates.robots, Self::robots);
         add_diagnostic_system!(
@@ -68,6 +76,16 @@ impl Plugin for RobotDiagnosticsPlugin {
             self.sample_rates.variables_and_factors,
             Self::variables_and_factors
         );
+        add_diagnostic_system!(
+            app,
+            self.sample_rates.variables_and_factors,
+            Self::gbp_iterations
+        );
+        add_diagnostic_system!(
+            app,
+            self.sample_rates.dropped_messages,
+            Self::dropped_messages
+        );
         // add_diagnostic_system!(app, self.sample_rates.messages_sent,
         // Self::messages_sent);
 
@@ -91,6 +109,18 @@ impl RobotDiagnosticsPlugin {
     pub const EXTERNAL_MESSAGES_SENT_COUNT: DiagnosticPath =
         DiagnosticPath::const_new("external_messages_sent_count");
     pub const FACTOR_COUNT: DiagnosticPath = DiagnosticPath::const_new("factor_count");
+    /// Mean number of internal GBP iterations run by each robot in the latest
+    /// timestep
+    pub const GBP_ITERATION_COUNT: DiagnosticPath =
+        DiagnosticPath::const_new("gbp_iteration_count");
+    /// Number of external messages dropped because a link has sent its
+    /// `bandwidth` in the timestep, since the simulation was loaded
+    pub const MESSAGES_BANDWIDTH_EXCEEDED_COUNT: DiagnosticPath =
+        DiagnosticPath::const_new("messages_bandwidth_exceeded_count");
+    /// Number of external messages lost in the communication channels, since
+    /// the simulation was loaded
+    pub const MESSAGES_LOST_COUNT: DiagnosticPath =
+        DiagnosticPath::const_new("messages_lost_count");
     pub const MESSAGES_RECEIVED_EXTERNAL_COUNT: DiagnosticPath =
         DiagnosticPath::const_new("messages_received_internal_count");
     pub const MESSAGES_RECEIVED_INTERNAL_COUNT: DiagnosticPath =
@@ -131,6 +161,23 @@ impl RobotDiagnosticsPlugin {
         });
     }
 
+    #[allow(clippy::cast_precision_loss)]
+    fn gbp_iterations(
+        mut diagnostics: Diagnostics,
+        factorgraphs: Query<&FactorGraph, With<RobotConnections>>,
+    ) {
+        if factorgraphs.is_empty() {
+            return;
+        }
+        diagnostics.add_measurement(&Self::GBP_ITERATION_COUNT, || {
+            let iterations = factorgraphs
+                .iter()
+                .map(|factorgraph| factorgraph.iteration_statistics().latest_timestep)
+                .sum::<usize>();
+            iterations as f64 / factorgraphs.iter().count() as f64
+        });
+    }
+
     // #[allow(clippy::cast_precision_loss)]
     // fn messages_sent(
     //     mut diagnostics: Diagnostics,
@@ -161,6 +208,15 @@ impl RobotDiagnosticsPlugin {
     //     });
     // }
 
+    #[allow(clippy::cast_precision_loss)]
+    fn dropped_messages(mut diagnostics: Diagnostics, channels: Res<CommunicationChannels>) {
+        let dropped = channels.dropped_total();
+        diagnostics.add_measurement(&Self::MESSAGES_LOST_COUNT, || dropped.lost as f64);
+        diagnostics.add_measurement(&Self::MESSAGES_BANDWIDTH_EXCEEDED_COUNT, || {
+            dropped.bandwidth_exceeded as f64
+        });
+    }
+
     #[allow(clippy::cast_precision_loss)]
     fn count_robot_collisions(
         mut diagnostics: Diagnostics,
@@ -229,8 +285,11 @@ impl RobotDiagnosticsPlugin {
             Self::MESSAGES_RECEIVED_EXTERNAL_COUNT,
             Self::MESSAGES_RECEIVED_INTERNAL_COUNT,
             Self::EXTERNAL_MESSAGES_SENT_COUNT,
+            Self::MESSAGES_LOST_COUNT,
+            Self::MESSAGES_BANDWIDTH_EXCEEDED_COUNT,
             Self::ROBOT_COLLISION_COUNT,
             Self::ENVIRONMENT_COLLISION_COUNT,
+            Self::GBP_ITERATION_COUNT,
         ] {
             if let Some(diagnostic) = store.get_mut(path) {
                 diagnostic.clear_history();
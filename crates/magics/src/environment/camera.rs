@@ -4,7 +4,7 @@ use gbp_config::Config;
 
 use crate::{
     movement::{LinearMovementBundle, Local, Orbit, OrbitMovementBundle},
-    simulation_loader::{LoadSimulation, ReloadSimulation},
+    simulation_loader::{LoadSimulation, ReloadSimulation, RestartSimulation},
 };
 
 // const CAMERA_UP: Vec3 = Vec3::NEG_Y;
@@ -30,7 +30,10 @@ impl Plugin for CameraPlugin {
                     )
                         .chain()
                         .run_if(
-                            on_event::<ReloadSimulation>().or_else(on_event::<LoadSimulation>()),
+                            on_event::<ReloadSimulation>()
+                                .or_else(on_event::<LoadSimulation>())
+                                // a restart keeps the camera where it is
+                                .and_then(not(on_event::<RestartSimulation>())),
                         ),
                 ),
             );
@@ -0,0 +1,411 @@
+//! Obstacles moving around in the environment, along the [`Trajectory`] given
+//! in the [`Environment`] of the loaded simulation.
+
+use std::{f32::consts::TAU, sync::Arc};
+
+use bevy::prelude::*;
+use gbp_config::Config;
+use gbp_environment::{
+    Circle, Environment, PlaceableShape, Rectangle, RegularPolygon, TileCoordinates, Trajectory,
+    Triangle,
+};
+use parry2d::shape;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    asset_loader::Materials,
+    bevy_utils::run_conditions::time::virtual_time_is_paused,
+    factorgraph::{factor::dynamic_obstacle::ObstacleSnapshot, prelude::FactorGraph},
+    planner::tracking::PositionTracker,
+    simulation_loader::{self, LoadSimulation, ReloadSimulation},
+};
+
+pub struct DynamicObstaclesPlugin;
+
+impl Plugin for DynamicObstaclesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            spawn_dynamic_obstacles
+                .run_if(on_event::<LoadSimulation>().or_else(on_event::<ReloadSimulation>())),
+        )
+        .add_systems(
+            FixedUpdate,
+            (move_dynamic_obstacles, update_dynamic_obstacle_factors)
+                .chain()
+                .before(crate::planner::robot::iterate_gbp_v2)
+                .run_if(not(virtual_time_is_paused)),
+        );
+    }
+}
+
+/// How an obstacle moves from one timestep to the next.
+/// Created from a [`Trajectory`] and the start position of the obstacle.
+#[derive(Debug)]
+pub enum Motion {
+    /// Constant velocity
+    Linear { velocity: Vec2 },
+    /// Constant speed towards `waypoints[next]`
+    WaypointLoop {
+        /// Absolute positions, starting with the start position of the obstacle
+        waypoints: Vec<Vec2>,
+        speed:     f32,
+        next:      usize,
+    },
+    /// Constant speed in a direction, that changes every `interval` seconds
+    RandomWalk {
+        start: Vec2,
+        speed: f32,
+        interval: f32,
+        max_distance: f32,
+        velocity: Vec2,
+        until_next_turn: f32,
+        rng: StdRng,
+    },
+}
+
+impl Motion {
+    /// Create the motion of an obstacle starting at `start`
+    pub fn new(trajectory: &Trajectory, start: Vec2, mut rng: StdRng) -> Self {
+        match trajectory {
+            Trajectory::Linear { velocity } => Self::Linear {
+                velocity: Vec2::from(*velocity),
+            },
+            Trajectory::WaypointLoop { waypoints, speed } => Self::WaypointLoop {
+                waypoints: std::iter::once(start)
+                    .chain(waypoints.iter().map(|&offset| start + Vec2::from(offset)))
+                    .collect(),
+                speed:     speed.get(),
+                next:      1,
+            },
+            Trajectory::RandomWalk {
+                speed,
+                interval,
+                max_distance,
+            } => {
+                let velocity = Vec2::from_angle(rng.gen_range(0.0..TAU)) * speed.get();
+                Self::RandomWalk {
+                    start,
+                    speed: speed.get(),
+                    interval: interval.get(),
+                    max_distance: max_distance.get(),
+                    velocity,
+                    until_next_turn: interval.get(),
+                    rng,
+                }
+            }
+        }
+    }
+
+    /// Advance the obstacle at `position` by `dt` seconds.
+    /// Returns the new position, and the velocity the obstacle is moving with
+    pub fn step(&mut self, position: Vec2, dt: f32) -> (Vec2, Vec2) {
+        match self {
+            Self::Linear { velocity } => (position + *velocity * dt, *velocity),
+            Self::WaypointLoop {
+                waypoints,
+                speed,
+                next,
+            } => {
+                if waypoints.len() < 2 {
+                    return (position, Vec2::ZERO);
+                }
+                let mut position = position;
+                let mut remaining = *speed * dt;
+                // Bounded, in case all the waypoints are on top of each other
+                for _ in 0..waypoints.len() {
+                    let to_next = waypoints[*next] - position;
+                    let distance = to_next.length();
+                    if distance > remaining {
+                        position += to_next / distance * remaining;
+                        return (position, to_next / distance * *speed);
+                    }
+                    position = waypoints[*next];
+                    remaining -= distance;
+                    *next = (*next + 1) % waypoints.len();
+                }
+                (position, Vec2::ZERO)
+            }
+            Self::RandomWalk {
+                start,
+                speed,
+                interval,
+                max_distance,
+                velocity,
+                until_next_turn,
+                rng,
+            } => {
+                *until_next_turn -= dt;
+                if *until_next_turn <= 0.0 {
+                    *until_next_turn += *interval;
+                    let towards_start = *start - position;
+                    *velocity = if towards_start.length() > *max_distance {
+                        towards_start.normalize() * *speed
+                    } else {
+                        Vec2::from_angle(rng.gen_range(0.0..TAU)) * *speed
+                    };
+                }
+                (position + *velocity * dt, *velocity)
+            }
+        }
+    }
+}
+
+/// Component attached to every dynamic obstacle
+#[derive(Component)]
+pub struct DynamicObstacle {
+    motion:   Motion,
+    velocity: Vec2,
+    /// Radius of the bounding circle of the obstacle
+    radius:   f32,
+    /// Collider of the obstacle, relative to its position
+    shape:    Arc<dyn shape::Shape>,
+}
+
+impl DynamicObstacle {
+    /// The velocity the obstacle moved with in the last timestep
+    #[inline]
+    pub const fn velocity(&self) -> Vec2 {
+        self.velocity
+    }
+
+    /// Radius of the bounding circle of the obstacle
+    #[inline]
+    pub const fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    /// Collider of the obstacle, relative to its position
+    #[inline]
+    pub fn shape(&self) -> &dyn shape::Shape {
+        self.shape.as_ref()
+    }
+}
+
+/// **Bevy** [`Update`] system that spawns the dynamic obstacles of the
+/// [`Environment`], when a simulation is loaded or reloaded. Dynamic
+/// obstacles are [`Reloadable`](simulation_loader::Reloadable), so existing
+/// ones are already despawned by the simulation loader.
+#[allow(clippy::cast_precision_loss)]
+fn spawn_dynamic_obstacles(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    env_config: Res<Environment>,
+    config: Res<Config>,
+    materials: Res<Materials>,
+) {
+    let tile_grid = &env_config.tiles.grid;
+    let tile_size = env_config.tile_size();
+    let obstacle_height = -env_config.obstacle_height();
+
+    let grid_offset_x = tile_grid.ncols() as f32 / 2.0 - 0.5;
+    let grid_offset_z = tile_grid.nrows() as f32 / 2.0 - 0.5;
+
+    for (i, obstacle) in env_config.dynamic_obstacles.iter().enumerate() {
+        let TileCoordinates { row, col } = obstacle.tile_coordinates;
+        let tile_center = Vec2::new(
+            (col as f32 - grid_offset_x) * tile_size,
+            -(row as f32 - grid_offset_z) * tile_size,
+        );
+        let start = tile_center
+            + Vec2::new(
+                (obstacle.translation.x.get() as f32 - 0.5) * tile_size,
+                (0.5 - obstacle.translation.y.get() as f32) * tile_size,
+            );
+
+        let (mesh, shape) = mesh_and_collider(&obstacle.shape, tile_size, obstacle_height);
+        let radius = shape.compute_local_bounding_sphere().radius();
+
+        let rng = StdRng::seed_from_u64(config.simulation.prng_seed.wrapping_add(i as u64));
+        let motion = Motion::new(&obstacle.trajectory, start, rng);
+
+        info!(
+            "Spawning dynamic obstacle {} at {:?} with trajectory {:?}",
+            i, start, obstacle.trajectory
+        );
+
+        commands.spawn((
+            PbrBundle {
+                mesh: meshes.add(mesh),
+                material: materials.obstacle.clone(),
+                transform: Transform::from_xyz(start.x, obstacle_height / 2.0, start.y),
+                ..Default::default()
+            },
+            DynamicObstacle {
+                motion,
+                velocity: Vec2::ZERO,
+                radius,
+                shape,
+            },
+            PositionTracker::new(10000, std::time::Duration::from_millis(100)),
+            simulation_loader::Reloadable,
+        ));
+    }
+}
+
+/// Mesh and collider of a dynamic obstacle. Scaled the same way as the static
+/// obstacles of the environment, but without any rotation
+#[allow(clippy::cast_possible_truncation)]
+fn mesh_and_collider(
+    placeable_shape: &PlaceableShape,
+    tile_size: f32,
+    obstacle_height: f32,
+) -> (Mesh, Arc<dyn shape::Shape>) {
+    match placeable_shape {
+        PlaceableShape::Circle(Circle { radius }) => {
+            let radius = radius.get() as f32 * tile_size;
+            (
+                Cylinder::new(radius, obstacle_height).into(),
+                Arc::new(shape::Ball::new(radius)),
+            )
+        }
+        PlaceableShape::Rectangle(Rectangle { width, height }) => {
+            let width = width.get() as f32 * tile_size / 2.0;
+            let height = height.get() as f32 * tile_size / 2.0;
+            (
+                Cuboid::new(width, obstacle_height, height).into(),
+                Arc::new(shape::Cuboid::new(parry2d::na::Vector2::new(
+                    width / 2.0,
+                    height / 2.0,
+                ))),
+            )
+        }
+        PlaceableShape::RegularPolygon(polygon @ RegularPolygon { sides, radius }) => {
+            let scale = tile_size / 2.0;
+            let mesh = Mesh::from(bevy_more_shapes::Cylinder {
+                height: -obstacle_height,
+                radius_bottom: radius.get() as f32 * scale,
+                radius_top: radius.get() as f32 * scale,
+                radial_segments: *sides as u32,
+                height_segments: 1,
+            });
+            let points: Vec<_> = polygon
+                .points()
+                .iter()
+                .map(|[x, y]| parry2d::math::Point::new(*x as f32 * scale, *y as f32 * scale))
+                .collect();
+            let shape =
+                shape::ConvexPolygon::from_convex_hull(&points).expect("polygon is always convex");
+            (mesh, Arc::new(shape))
+        }
+        PlaceableShape::Triangle(triangle @ Triangle { .. }) => {
+            let [p1, p2, p3] = triangle
+                .points()
+                .map(|point| Vec2::new(point.x as f32 * tile_size, point.y as f32 * tile_size));
+            let mesh = Mesh::try_from(bevy_more_shapes::Prism::new(-obstacle_height, vec![
+                p1, p2, p3,
+            ]))
+            .expect("Failed to create triangle mesh");
+            let shape = shape::Triangle::new(
+                p1.to_array().into(),
+                p2.to_array().into(),
+                p3.to_array().into(),
+            );
+            (mesh, Arc::new(shape))
+        }
+    }
+}
+
+/// **Bevy** [`FixedUpdate`] system that moves every dynamic obstacle along its
+/// trajectory
+fn move_dynamic_obstacles(
+    mut obstacles: Query<(&mut Transform, &mut DynamicObstacle)>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    for (mut transform, mut obstacle) in &mut obstacles {
+        let (position, velocity) = obstacle.motion.step(transform.translation.xz(), dt);
+        transform.translation.x = position.x;
+        transform.translation.z = position.y;
+        obstacle.velocity = velocity;
+    }
+}
+
+/// **Bevy** [`FixedUpdate`] system that gives the current state of every
+/// dynamic obstacle to the dynamic obstacle factors of every robot
+fn update_dynamic_obstacle_factors(
+    obstacles: Query<(&Transform, &DynamicObstacle)>,
+    mut factorgraphs: Query<&mut FactorGraph>,
+) {
+    if obstacles.is_empty() {
+        return;
+    }
+
+    let snapshots: Arc<[ObstacleSnapshot]> = obstacles
+        .iter()
+        .map(|(transform, obstacle)| ObstacleSnapshot {
+            position: transform.translation.xz(),
+            velocity: obstacle.velocity,
+            radius:   obstacle.radius,
+        })
+        .collect();
+
+    for mut factorgraph in &mut factorgraphs {
+        factorgraph.update_dynamic_obstacles(&snapshots);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    const DT: f32 = 0.1;
+
+    fn rng() -> StdRng {
+        StdRng::seed_from_u64(0)
+    }
+
+    #[test]
+    fn linear_moves_with_constant_velocity() {
+        let trajectory = Trajectory::Linear {
+            velocity: [1.0, -2.0],
+        };
+        let mut motion = Motion::new(&trajectory, Vec2::ZERO, rng());
+        let mut position = Vec2::ZERO;
+        for _ in 0..10 {
+            (position, _) = motion.step(position, DT);
+        }
+        assert_relative_eq!(position.x, 1.0, epsilon = 1e-5);
+        assert_relative_eq!(position.y, -2.0, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn waypoint_loop_returns_to_start() {
+        let start = Vec2::new(5.0, 5.0);
+        let trajectory = Trajectory::WaypointLoop {
+            waypoints: vec![[1.0, 0.0], [1.0, 1.0]],
+            speed:     1.0.try_into().unwrap(),
+        };
+        let mut motion = Motion::new(&trajectory, start, rng());
+        // The loop is 1 + 1 + sqrt(2) meters long
+        let steps = ((2.0 + 2.0f32.sqrt()) / DT).round() as usize;
+        let mut position = start;
+        let mut visited_corner = false;
+        for _ in 0..steps {
+            (position, _) = motion.step(position, DT);
+            visited_corner |= position.distance(start + Vec2::new(1.0, 1.0)) < DT;
+        }
+        assert!(visited_corner);
+        assert!(position.distance(start) < DT);
+    }
+
+    #[test]
+    fn random_walk_stays_close_to_start() {
+        let trajectory = Trajectory::RandomWalk {
+            speed: 2.0.try_into().unwrap(),
+            interval: 0.5.try_into().unwrap(),
+            max_distance: 3.0.try_into().unwrap(),
+        };
+        let mut motion = Motion::new(&trajectory, Vec2::ZERO, rng());
+        let mut position = Vec2::ZERO;
+        for _ in 0..10_000 {
+            let velocity;
+            (position, velocity) = motion.step(position, DT);
+            assert_relative_eq!(velocity.length(), 2.0, epsilon = 1e-4);
+            // At most one interval of moving away after crossing `max_distance`
+            assert!(position.length() <= 3.0 + 2.0 * 0.5 + 1e-3);
+        }
+    }
+}
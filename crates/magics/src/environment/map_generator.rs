@@ -13,7 +13,10 @@ use parry2d::{
 };
 
 use crate::{
-    asset_loader::Materials, bevy_utils::run_conditions::event_exists, input::DrawSettingsEvent,
+    asset_loader::Materials,
+    bevy_utils::run_conditions::event_exists,
+    factorgraph::factor::collider_sdf::{Collider, ColliderSdf},
+    input::DrawSettingsEvent,
     simulation_loader::LoadSimulation,
 };
 
@@ -22,6 +25,7 @@ pub struct GenMapPlugin;
 impl Plugin for GenMapPlugin {
     fn build(&self, app: &mut App) {
         app
+            .add_plugins(super::dynamic_obstacles::DynamicObstaclesPlugin)
             .add_event::<events::ObstacleClickedOn>()
             // .init_resource::<Colliders>()
             // .add_systems(Startup, (build_tile_grid, build_obstacles))
@@ -54,6 +58,12 @@ pub mod events {
 #[derive(Debug, Component)]
 pub struct ObstacleMarker;
 
+/// **Bevy** [`Resource`] with the signed distance function over the
+/// [`Colliders`] of the environment. Used by the obstacle factors when
+/// `gbp.obstacle-factor` is set to `colliders`.
+#[derive(Debug, Resource, Clone)]
+pub struct ObstacleColliderSdf(pub Arc<ColliderSdf>);
+
 // #[derive(Clone)]
 // pub struct Collider {
 //     pub associated_mesh: Option<Entity>,
@@ -102,7 +112,23 @@ pub struct ObstacleMarker;
 //     }
 // }
 
-fn insert_colliders_resource(In(colliders): In<Colliders>, mut commands: Commands) {
+fn insert_colliders_resource(
+    In(colliders): In<Colliders>,
+    mut commands: Commands,
+    env_config: Res<Environment>,
+) {
+    // Same distance as the gradient of the signed distance field image spans, so
+    // the two kinds of obstacle factors start to act at the same distance
+    let sdf_settings = &env_config.tiles.settings.sdf;
+    let safety_distance = (sdf_settings.expansion + sdf_settings.blur) * env_config.tile_size();
+    let shapes = colliders.iter().map(|collider| Collider {
+        isometry: collider.isometry,
+        shape:    Arc::clone(&collider.shape),
+    });
+    commands.insert_resource(ObstacleColliderSdf(Arc::new(ColliderSdf::new(
+        shapes,
+        safety_distance,
+    ))));
     commands.insert_resource(colliders);
 }
 
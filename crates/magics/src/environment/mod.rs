@@ -1,5 +1,6 @@
 pub mod camera;
 pub mod cursor;
+pub mod dynamic_obstacles;
 pub mod follow_cameras;
 pub mod map;
 pub mod map_generator;
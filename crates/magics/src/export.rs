@@ -11,20 +11,32 @@ use self::events::TakeSnapshotOfRobot;
 use crate::{
     factorgraph::prelude::FactorGraph,
     goal_area,
-    planner::{self, robot::Radius},
+    planner::{self, communication::DroppedCount, robot::Radius},
     simulation_loader::{LoadSimulation, ReloadSimulation},
 };
 
 #[derive(Default)]
-pub struct ExportPlugin;
+pub struct ExportPlugin {
+    /// Where to save the export, that is triggered when all formations have
+    /// finished, or when <F7> is pressed
+    pub save_at_location: ExportSaveLocation,
+    /// Postfix of the export triggered when all formations have finished, or
+    /// when <F7> is pressed
+    pub postfix: ExportSavePostfix,
+}
 
 impl Plugin for ExportPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<events::Export>()
             .add_event::<events::TakeSnapshotOfRobot>()
             .add_event::<events::OpenLatestExport>()
+            .add_event::<events::Exported>()
             .init_resource::<resources::SnapshottedRobots>()
             .init_resource::<resources::LatestExport>()
+            .insert_resource(resources::DefaultExport {
+                save_at_location: self.save_at_location.clone(),
+                postfix: self.postfix.clone(),
+            })
             // .add_systems(
             //     FixedUpdate,
             //     send_default_export_event.run_if(once_after_delay(Duration::from_secs(60))),
@@ -47,7 +59,7 @@ impl Plugin for ExportPlugin {
     }
 }
 
-mod resources {
+pub(crate) mod resources {
     use super::*;
 
     #[derive(Resource, Deref, DerefMut, Default)]
@@ -55,10 +67,25 @@ mod resources {
 
     #[derive(Resource, Deref, DerefMut, Default)]
     pub(super) struct LatestExport(pub Option<std::path::PathBuf>);
+
+    #[derive(Resource, Default)]
+    pub(crate) struct DefaultExport {
+        pub save_at_location: ExportSaveLocation,
+        pub postfix: ExportSavePostfix,
+    }
 }
 
-fn send_default_export_event(mut evw_export: EventWriter<events::Export>) {
-    evw_export.send(events::Export::default());
+/// **Bevy** system that requests an export with the settings the
+/// [`ExportPlugin`] was added with
+pub(crate) fn send_default_export_event(
+    mut evw_export: EventWriter<events::Export>,
+    default_export: Res<resources::DefaultExport>,
+) {
+    evw_export.send(events::Export {
+        save_at_location: default_export.save_at_location.clone(),
+        postfix: default_export.postfix.clone(),
+        ..Default::default()
+    });
 }
 
 #[derive(Debug, Clone, Default)]
@@ -82,6 +109,10 @@ pub mod events {
 
     #[derive(Event)]
     pub struct TakeSnapshotOfRobot(pub Entity);
+
+    /// Emitted after the export data has been written to disk
+    #[derive(Event, Debug, Clone)]
+    pub struct Exported(pub std::path::PathBuf);
 }
 
 fn open_latest_export(
@@ -117,10 +148,35 @@ pub struct RobotData {
     velocities: Vec<planner::tracking::VelocityMeasurement>,
     collisions: CollisionCountData,
     messages: MessageData,
+    /// Internal GBP iterations run by the robot
+    iterations: crate::factorgraph::factorgraph::IterationStatistics,
+    /// The states the robot planned to be in, when the data was exported
+    planned_states: PlannedStatesData,
     // route: RouteData,
     mission: MissionData,
     planning_strategy: PlanningStrategy,
     color: String,
+    metrics: planner::metrics::RobotMetrics,
+    yielding: YieldingData,
+}
+
+/// How much a robot yielded to the other robots, see
+/// [`planner::metrics::Yielding`]
+#[derive(serde::Serialize)]
+struct YieldingData {
+    /// Priority of the robot when avoiding other robots
+    priority:   f32,
+    /// Distance deviated from the route because of each other robot
+    yielded_to: HashMap<Entity, f32>,
+}
+
+impl From<&planner::metrics::Yielding> for YieldingData {
+    fn from(yielding: &planner::metrics::Yielding) -> Self {
+        Self {
+            priority:   yielding.priority(),
+            yielded_to: yielding.yielded_to().clone(),
+        }
+    }
 }
 
 #[derive(serde::Serialize)]
@@ -231,6 +287,23 @@ struct MessageCount {
     external: usize,
 }
 
+/// Messages dropped by the communication channels between robots, since the
+/// simulation was loaded
+#[derive(serde::Serialize)]
+struct CommunicationData {
+    dropped: DroppedCount,
+    links:   Vec<DroppedOverLink>,
+}
+
+/// Messages dropped over the link from `sender` to `receiver`
+#[derive(serde::Serialize)]
+struct DroppedOverLink {
+    sender:   Entity,
+    receiver: Entity,
+    #[serde(flatten)]
+    dropped:  DroppedCount,
+}
+
 #[derive(serde::Serialize)]
 struct GoalAreaData {
     aabb:    parry2d::bounding_volume::Aabb,
@@ -257,8 +330,12 @@ struct ExportData {
     config: gbp_config::Config,
     // obstacles: Vec<Obstacle>,
     obstacles: HashMap<Entity, Obstacle>,
+    dynamic_obstacles: HashMap<Entity, DynamicObstacleData>,
     collisions: CollisionData,
+    communication: CommunicationData,
     goal_areas: HashMap<Entity, GoalAreaData>,
+    /// Trajectory quality metrics aggregated over all robots
+    metrics: planner::metrics::AggregateMetrics,
 }
 
 #[derive(serde::Serialize)]
@@ -268,6 +345,37 @@ enum Obstacle {
     Polygon { vertices: Vec<[f32; 2]> },
 }
 
+#[derive(serde::Serialize)]
+struct DynamicObstacleData {
+    /// Radius of the bounding circle of the obstacle
+    radius:    f32,
+    positions: Vec<[f32; 2]>,
+}
+
+/// The states a robot plans to be in, from the current state to the horizon
+#[derive(serde::Serialize)]
+struct PlannedStatesData {
+    /// Layout of every state, e.g. if the states have an altitude or heading
+    layout: gbp_config::StateLayout,
+    /// The mean of the belief of every variable, with `layout.dofs()` elements
+    means:  Vec<Vec<f64>>,
+}
+
+impl From<&FactorGraph> for PlannedStatesData {
+    fn from(factorgraph: &FactorGraph) -> Self {
+        Self {
+            layout: factorgraph
+                .first_variable()
+                .map(|(_, variable)| variable.layout())
+                .unwrap_or_default(),
+            means:  factorgraph
+                .variables()
+                .map(|(_, variable)| variable.belief.mean.to_vec())
+                .collect(),
+        }
+    }
+}
+
 #[derive(serde::Serialize)]
 struct GbpIterationData {
     internal: usize,
@@ -282,6 +390,7 @@ struct GbpData {
 fn export(
     mut evr_export: EventReader<events::Export>,
     mut evw_toast: EventWriter<bevy_notify::ToastEvent>,
+    mut evw_exported: EventWriter<events::Exported>,
     mut latest_export: ResMut<resources::LatestExport>,
     mut robot_snapshots: ResMut<resources::SnapshottedRobots>,
     q_robots: Query<(
@@ -294,18 +403,26 @@ fn export(
         &planner::robot::Mission,
         &PlanningStrategy,
         &crate::theme::ColorAssociation,
+        &planner::metrics::InterRobotProximity,
+        &planner::metrics::Yielding,
         // &ColorAssociation,
         // &ColorAssociation,
     )>,
     q_goal_areas: Query<(Entity, &goal_area::components::GoalArea)>,
+    q_dynamic_obstacles: Query<(
+        Entity,
+        &crate::environment::dynamic_obstacles::DynamicObstacle,
+        &planner::tracking::PositionTracker,
+    )>,
     robot_collisions: Res<crate::planner::collisions::resources::RobotRobotCollisions>,
     environment_collisions: Res<crate::planner::collisions::resources::RobotEnvironmentCollisions>,
     sim_manager: Res<crate::simulation_loader::SimulationManager>,
     config: Res<gbp_config::Config>,
-    time_virtual: Res<Time<Virtual>>,
-    time_fixed: Res<Time<Fixed>>,
+    // Grouped, as a system can take at most 16 parameters
+    (time_virtual, time_fixed): (Res<Time<Virtual>>, Res<Time<Fixed>>),
     catppuccin: Res<crate::theme::CatppuccinTheme>,
     obstacles: Res<gbp_global_planner::Colliders>,
+    channels: Res<planner::communication::CommunicationChannels>,
 ) {
     // schema:
     //
@@ -334,6 +451,12 @@ fn export(
     //          "robots": <integer>
     //          "environment": <integer>
     //       },
+    //       "iterations": {
+    //          "timesteps": <integer>,
+    //          "internal": <integer>,
+    //          "latest_timestep": <integer>,
+    //          "converged_timesteps": <integer>
+    //       },
     //       "messages": {
     //          "sent": {
     //              "internal": <integer>,
@@ -366,11 +489,20 @@ fn export(
             mission,
             planning_strategy,
             color_assoc,
+            proximity,
+            yielding,
         ) in q_robots.iter()
         {
             if robot_snapshots.contains_key(&robot_entity) {
                 continue;
             }
+            let metrics = planner::metrics::RobotMetrics::compute(
+                positions,
+                velocities,
+                mission,
+                proximity,
+                time_fixed.elapsed_seconds_f64(),
+            );
             let positions: Vec<[f32; 2]> = positions.positions().map(Into::into).collect();
             // let velocities: Vec<[f32; 2]> =
             // velocities.velocities().map(Into::into).collect();
@@ -439,8 +571,12 @@ fn export(
                         external: graph.messages_received().external,
                     },
                 },
+                iterations: graph.iteration_statistics(),
+                planned_states: graph.into(),
                 planning_strategy: *planning_strategy,
                 color,
+                metrics,
+                yielding: yielding.into(),
             };
 
             robot_snapshots.insert(robot_entity, robot_data);
@@ -548,16 +684,42 @@ fn export(
             })
             .collect();
 
+        let dynamic_obstacles = q_dynamic_obstacles
+            .iter()
+            .map(|(entity, obstacle, positions)| {
+                (entity, DynamicObstacleData {
+                    radius:    obstacle.radius(),
+                    positions: positions.positions().map(Into::into).collect(),
+                })
+            })
+            .collect();
+
         let collisions = CollisionData {
             robots:      robot_collisions.collisions().map_into().collect(),
             environment: environment_collisions.collisions().map_into().collect(),
         };
 
+        let communication = CommunicationData {
+            dropped: channels.dropped_total(),
+            links:   channels
+                .dropped()
+                .map(|((sender, receiver), dropped)| DroppedOverLink {
+                    sender,
+                    receiver,
+                    dropped,
+                })
+                .collect(),
+        };
+
         let goal_areas = q_goal_areas
             .iter()
             .map(|(entity, area)| (entity, area.into()))
             .collect();
 
+        let metrics = planner::metrics::AggregateMetrics::from_robots(
+            robot_snapshots.values().map(|robot| &robot.metrics),
+        );
+
         let export_data = ExportData {
             scenario: environment.to_string(),
             makespan,
@@ -567,8 +729,11 @@ fn export(
             prng_seed: config.simulation.prng_seed,
             config: config.clone(),
             obstacles,
+            dynamic_obstacles,
             collisions,
+            communication,
             goal_areas,
+            metrics,
         };
 
         let json = serde_json::to_string_pretty(&export_data).unwrap();
@@ -596,6 +761,7 @@ fn export(
                 id.to_string()
             }
             ExportSavePostfix::UnixTimestamp => chrono::Utc::now().timestamp().to_string(),
+            ExportSavePostfix::Name(ref name) => name.clone(),
         };
 
         let dirname = match event.save_at_location {
@@ -623,14 +789,17 @@ fn export(
             evw_toast.send(bevy_notify::ToastEvent::success(message));
         }
 
+        evw_exported.send(events::Exported(output_filepath.clone()));
         latest_export.0 = Some(output_filepath);
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ExportSavePostfix {
     Number,
     UnixTimestamp,
+    /// Use the given string as postfix
+    Name(String),
 }
 
 impl Default for ExportSavePostfix {
@@ -661,6 +830,8 @@ fn take_snapshot_of_robot(
         &planner::robot::Mission,
         &PlanningStrategy,
         &crate::theme::ColorAssociation,
+        &planner::metrics::InterRobotProximity,
+        &planner::metrics::Yielding,
     )>,
 
     robot_collisions: &crate::planner::collisions::resources::RobotRobotCollisions,
@@ -669,8 +840,17 @@ fn take_snapshot_of_robot(
     time_fixed: &Time<Fixed>,
     catppuccin: &crate::theme::CatppuccinTheme,
 ) -> anyhow::Result<RobotData> {
-    let Ok((fgraph, positions, velocities, radius, mission, planning_strategy, color_assoc)) =
-        q_robots.get(robot_entity)
+    let Ok((
+        fgraph,
+        positions,
+        velocities,
+        radius,
+        mission,
+        planning_strategy,
+        color_assoc,
+        proximity,
+        yielding,
+    )) = q_robots.get(robot_entity)
     else {
         anyhow::bail!(
             "cannot take snapshot of non-existing robot {:?}",
@@ -678,6 +858,13 @@ fn take_snapshot_of_robot(
         );
     };
 
+    let metrics = planner::metrics::RobotMetrics::compute(
+        positions,
+        velocities,
+        mission,
+        proximity,
+        time_fixed.elapsed_seconds_f64(),
+    );
     let positions: Vec<[f32; 2]> = positions.positions().map(Into::into).collect();
     // let velocities: Vec<[f32; 2]> =
     // velocities.velocities().map(Into::into).collect();
@@ -717,8 +904,12 @@ fn take_snapshot_of_robot(
                 external: fgraph.messages_received().external,
             },
         },
+        iterations: fgraph.iteration_statistics(),
+        planned_states: fgraph.into(),
         planning_strategy: *planning_strategy,
         color,
+        metrics,
+        yielding: yielding.into(),
         mission: MissionData {
             started_at:  mission.started_at(),
             finished_at: mission
@@ -771,6 +962,8 @@ fn await_robot_snapshot_request(
         &planner::robot::Mission,
         &PlanningStrategy,
         &crate::theme::ColorAssociation,
+        &planner::metrics::InterRobotProximity,
+        &planner::metrics::Yielding,
     )>,
 
     robot_collisions: Res<crate::planner::collisions::resources::RobotRobotCollisions>,
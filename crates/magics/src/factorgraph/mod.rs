@@ -1,137 +1,98 @@
-#![warn(missing_docs)]
-//! ...
-use derive_more::{Add, AddAssign};
-
-pub mod factor;
-#[allow(clippy::module_inception)]
-pub mod factorgraph;
-pub mod graphviz;
-pub mod id;
-pub mod message;
-pub mod node;
-pub mod variable;
-
-/// Degrees of Freedom of the ground robot.
-/// The robot has 4 degrees, of freedom:
-/// 1. position.x
-/// 2. position.y
-/// 3. velocity.x
-/// 4. velocity.y
-/// [x, y, x', y']
-pub const DOFS: usize = 4;
-
-/// prelude module bringing entire public API into score
-#[allow(unused_imports)]
-pub mod prelude {
-    pub use super::{factorgraph::FactorGraph, message::Message, DOFS};
-}
-
-#[derive(Debug, Clone, Copy, Add, AddAssign, serde::Serialize)]
-pub struct MessagesSent {
-    pub internal: usize,
-    pub external: usize,
-}
-
-impl std::fmt::Display for MessagesSent {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "[internal: {}, external: {}]",
-            self.internal, self.external
-        )
-    }
-}
-
-impl std::iter::Sum for MessagesSent {
-    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
-        iter.fold(Self::new(), |a, b| a + b)
-    }
+//! **Bevy** adapter for the factorgraphs of [`gbp_core`].
+//!
+//! Every robot is an entity, and its factorgraph is attached to it as a
+//! [`FactorGraph`](factorgraph::FactorGraph) component, with the entity as the
+//! id of the factorgraph. The modules mirror those of [`gbp_core`], with the
+//! graph id fixed to [`FactorGraphId`].
+use bevy::ecs::entity::Entity;
+pub use gbp_core::{graphviz, MessageCount, MessagesReceived, MessagesSent, DOFS};
+
+/// type alias used to represent the id of the factorgraph
+/// Since we use **Bevy** we can use the `Entity` id of the whatever entity the
+/// the factorgraph is attached to as a Component, as its unique identifier.
+pub type FactorGraphId = Entity;
+
+pub mod factor {
+    pub use gbp_core::factor::*;
+
+    use super::FactorGraphId;
+
+    pub type FactorNode = gbp_core::factor::FactorNode<FactorGraphId>;
+    pub type ExternalVariableId = gbp_core::factor::ExternalVariableId<FactorGraphId>;
 }
 
-impl MessagesSent {
-    pub fn new() -> Self {
-        Self {
-            internal: 0,
-            external: 0,
+#[allow(clippy::module_inception)]
+pub mod factorgraph {
+    use bevy::ecs::component::Component;
+    use gbp_core::factor::{Factor, FactorKind};
+    pub use gbp_core::factorgraph::{
+        FactorIndex, IterationStatistics, NodeCount, NodeIndex, VariableIndex,
+    };
+
+    use super::FactorGraphId;
+
+    /// A factor graph is a bipartite graph consisting of two types of nodes:
+    /// factors and variables.
+    /// Attached as a component to the robot it plans for.
+    #[derive(Component, Debug, derive_more::Deref, derive_more::DerefMut)]
+    pub struct FactorGraph(gbp_core::factorgraph::FactorGraph<FactorGraphId>);
+
+    impl FactorGraph {
+        /// Construct a new empty factorgraph with a given id
+        #[must_use]
+        pub fn new(id: FactorGraphId) -> Self {
+            Self(gbp_core::factorgraph::FactorGraph::new(id))
         }
-    }
-}
-
-impl Default for MessagesSent {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-#[derive(Debug, Clone, Copy, Add, AddAssign, serde::Serialize)]
-pub struct MessagesReceived {
-    pub internal: usize,
-    pub external: usize,
-}
 
-impl MessagesReceived {
-    pub fn new() -> Self {
-        Self {
-            internal: 0,
-            external: 0,
+        /// Enable or disable the factors of each kind, as given by `settings`,
+        /// and the user defined factors of each kind, as given by their section
+        /// in `user_factors`
+        pub fn change_factor_enabled(
+            &mut self,
+            settings: gbp_config::FactorsEnabledSection,
+            user_factors: &gbp_config::UserFactorsSection,
+        ) {
+            self.set_factors_enabled(|kind| match kind {
+                FactorKind::Dynamic(_) => settings.dynamic,
+                FactorKind::Obstacle(_) => settings.obstacle,
+                FactorKind::InterRobot(_) => settings.interrobot,
+                FactorKind::Tracking(_) => settings.tracking,
+                FactorKind::DynamicObstacle(_) => settings.dynamic_obstacle,
+                FactorKind::Velocity(_) => settings.velocity,
+                FactorKind::Acceleration(_) => settings.acceleration,
+                FactorKind::User(factor) => user_factors
+                    .get(factor.name())
+                    .map_or(true, |section| section.enabled),
+            });
         }
     }
 }
 
-impl Default for MessagesReceived {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-impl std::iter::Sum for MessagesReceived {
-    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
-        iter.fold(Self::new(), |a, b| a + b)
-    }
-}
+pub mod id {
+    use super::FactorGraphId;
 
-impl std::fmt::Display for MessagesReceived {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "[internal: {}, external: {}]",
-            self.internal, self.external
-        )
-    }
+    pub type FactorId = gbp_core::id::FactorId<FactorGraphId>;
+    pub type VariableId = gbp_core::id::VariableId<FactorGraphId>;
 }
 
-#[derive(Debug, Clone, Copy, Add, AddAssign)]
-pub struct MessageCount {
-    // pub sent:     usize,
-    // pub received: usize,
-    pub sent:     MessagesSent,
-    pub received: MessagesReceived,
-}
+pub mod message {
+    pub use gbp_core::message::{InformationVec, Mean, Message, Payload, PrecisionMatrix};
 
-impl MessageCount {
-    pub fn reset(&mut self) {
-        self.sent = MessagesSent::new();
-        self.received = MessagesReceived::new();
-    }
+    use super::FactorGraphId;
 
-    pub fn new() -> Self {
-        Self {
-            sent:     MessagesSent::new(),
-            received: MessagesReceived::new(),
-            // sent:     0,
-            // received: 0,
-        }
-    }
+    pub type FactorToVariableMessage = gbp_core::message::FactorToVariableMessage<FactorGraphId>;
+    pub type VariableToFactorMessage = gbp_core::message::VariableToFactorMessage<FactorGraphId>;
+    pub type ExternalMessage = gbp_core::message::ExternalMessage<FactorGraphId>;
 }
 
-impl Default for MessageCount {
-    fn default() -> Self {
-        Self::new()
-    }
+pub mod variable {
+    pub use gbp_core::variable::{VariableBelief, VariablePrior};
+
+    pub type VariableNode = gbp_core::variable::VariableNode<super::FactorGraphId>;
 }
 
-impl std::iter::Sum for MessageCount {
-    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
-        iter.fold(Self::new(), |a, b| a + b)
-    }
+/// prelude module bringing entire public API into score
+#[allow(unused_imports)]
+pub mod prelude {
+    pub use super::{factorgraph::FactorGraph, message::Message, DOFS};
 }
@@ -0,0 +1,170 @@
+//! Module for running simulations headless, i.e. without a window or renderer.
+//!
+//! Intended for running experiments on machines without a GPU. The app is
+//! built from [`MinimalPlugins`] and only the plugins needed to run a
+//! scenario and export its results. Instead of advancing in wall-clock time,
+//! every frame advances the simulation by exactly one fixed timestep, so
+//! [`FixedUpdate`] runs as fast as the CPU allows.
+use std::time::Duration;
+
+use bevy::{
+    app::{AppExit, PluginGroupBuilder},
+    prelude::*,
+    time::TimeUpdateStrategy,
+};
+use bevy_notify::{ToastEvent, ToastLevel};
+use gbp_config::Config;
+
+use crate::{
+    asset_loader::{Materials, Meshes},
+    despawn_entity_after::DespawnEntityAfterPlugin,
+    environment::map_generator::GenMapPlugin,
+    export::{self, events::Exported, ExportPlugin},
+    goal_area::GoalAreaPlugin,
+    pause_play::PausePlay,
+    planner::PlannerPlugin,
+    simulation_loader::{LoadSimulation, ReloadSimulation, SimulationLoaderPlugin},
+    theme::CatppuccinTheme,
+};
+
+/// **Bevy** [`PluginGroup`] with every plugin needed to run a simulation
+/// headless, starting from [`MinimalPlugins`].
+///
+/// The simulation loader and export plugins are given, so the caller decides
+/// which simulation is run, and where its results are exported to.
+pub struct HeadlessPlugins {
+    pub simulation_loader: SimulationLoaderPlugin,
+    pub export: ExportPlugin,
+}
+
+impl HeadlessPlugins {
+    /// Create a new `HeadlessPlugins` group, exporting to the current working
+    /// directory
+    #[must_use]
+    pub fn new(simulation_loader: SimulationLoaderPlugin) -> Self {
+        Self {
+            simulation_loader,
+            export: ExportPlugin::default(),
+        }
+    }
+}
+
+impl PluginGroup for HeadlessPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            // bevy builtin plugins
+            .add_group(MinimalPlugins)
+            .add(bevy::log::LogPlugin::default())
+            .add(bevy::asset::AssetPlugin::default())
+            .add(bevy::transform::TransformPlugin)
+            .add(bevy::hierarchy::HierarchyPlugin)
+            .add(bevy::input::InputPlugin)
+            // our plugins
+            .add(HeadlessPlugin)
+            .add(DespawnEntityAfterPlugin)
+            .add(self.simulation_loader)
+            .add(GenMapPlugin)
+            .add(PlannerPlugin)
+            .add(self.export)
+            .add(GoalAreaPlugin)
+    }
+}
+
+/// **Bevy** [`Plugin`] providing stand-ins for the resources and events that
+/// the simulation plugins expect the rendering and ui plugins to provide.
+///
+/// Has to be added after [`MinimalPlugins`] and [`AssetPlugin`], but before
+/// any of the simulation plugins. Use [`HeadlessPlugins`] to get the order
+/// right.
+#[derive(Default)]
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        // Meshes and materials are still created by the spawners, they are just never
+        // rendered.
+        app.init_asset::<Mesh>()
+            .init_asset::<StandardMaterial>()
+            .insert_resource(CatppuccinTheme {
+                flavour: catppuccin::Flavour::Macchiato,
+            })
+            .init_resource::<Meshes>()
+            .init_resource::<Materials>()
+            // There is no one to show toasts to, so they are logged instead
+            .add_event::<ToastEvent>()
+            // Pausing is ignored, as there is no one to unpause the simulation again
+            .add_event::<PausePlay>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+                Config::default().simulation.hz.recip(),
+            )))
+            .add_systems(
+                Update,
+                (
+                    step_one_fixed_timestep_per_frame.run_if(
+                        on_event::<LoadSimulation>().or_else(on_event::<ReloadSimulation>()),
+                    ),
+                    log_toasts,
+                    // If the scenario has not finished before the configured max time, the
+                    // results are exported anyway. The application exits once the export has
+                    // been written.
+                    (log_max_time_exceeded, export::send_default_export_event)
+                        .chain()
+                        .run_if(virtual_time_exceeds_max_time.and_then(run_once())),
+                    exit_when_exported.run_if(on_event::<Exported>()),
+                ),
+            );
+    }
+}
+
+/// **Bevy** system that makes every frame advance time by exactly one fixed
+/// timestep of the loaded simulation. Together with the
+/// [`bevy::app::ScheduleRunnerPlugin`] looping without waiting, this
+/// runs [`FixedUpdate`] as fast as the CPU allows.
+fn step_one_fixed_timestep_per_frame(
+    config: Res<Config>,
+    mut time_update_strategy: ResMut<TimeUpdateStrategy>,
+    mut time_virtual: ResMut<Time<Virtual>>,
+) {
+    let timestep = Duration::from_secs_f64(config.simulation.hz.recip());
+    *time_update_strategy = TimeUpdateStrategy::ManualDuration(timestep);
+    // The default max delta of 250 ms would otherwise clamp low update rates
+    time_virtual.set_max_delta(timestep.max(time_virtual.max_delta()));
+}
+
+/// **Bevy** system that logs every toast, as there is no ui to show them in.
+fn log_toasts(mut evr_toast: EventReader<ToastEvent>) {
+    for toast in evr_toast.read() {
+        match toast.options.level {
+            ToastLevel::Error => error!("{}", toast.caption),
+            ToastLevel::Warning => warn!("{}", toast.caption),
+            _ => info!("{}", toast.caption),
+        }
+    }
+}
+
+/// Returns true if the virtual time has exceeded the max configured
+/// simulation time.
+#[inline]
+fn virtual_time_exceeds_max_time(time: Res<Time<Virtual>>, config: Res<Config>) -> bool {
+    time.elapsed_seconds() > config.simulation.max_time.get()
+}
+
+/// **Bevy** system that logs why the simulation is ending.
+fn log_max_time_exceeded(config: Res<Config>) {
+    info!(
+        "ending simulation, reason: time elapsed exceeds configured max time: {} seconds",
+        config.simulation.max_time.get()
+    );
+}
+
+/// **Bevy** system that exits the application after the first export has been
+/// written to disk.
+fn exit_when_exported(
+    mut evr_exported: EventReader<Exported>,
+    mut evw_app_exit: EventWriter<AppExit>,
+) {
+    for Exported(path) in evr_exported.read() {
+        info!("exported {}, exiting", path.display());
+        evw_app_exit.send(AppExit);
+    }
+}
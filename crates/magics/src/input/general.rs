@@ -1,9 +1,7 @@
-use std::collections::HashMap;
-
-use bevy::{app::AppExit, prelude::*, tasks::IoTaskPool};
+use bevy::{app::AppExit, prelude::*};
 use bevy_notify::prelude::*;
 use chrono::Duration;
-use gbp_config::{Config, DrawSetting};
+use gbp_config::{Config, DrawSetting, GraphvizFormat};
 use leafwing_input_manager::prelude::*;
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
@@ -15,12 +13,9 @@ use super::{
 };
 use crate::{
     bevy_utils::run_conditions::event_exists,
-    factorgraph::{
-        graphviz::{ExportGraph, NodeKind},
-        prelude::FactorGraph,
-    },
+    factorgraph::{graphviz::Graph, prelude::FactorGraph},
     pause_play::PausePlay,
-    planner::{robot::RadioAntenna, RobotConnections, RobotId},
+    planner::{robot::RadioAntenna, RobotConnections},
     simulation_loader::SaveSettings,
     theme::CatppuccinTheme,
 };
@@ -193,160 +188,17 @@ fn bind_general_input(mut commands: Commands) {
 fn export_factorgraphs_as_graphviz(
     query: Query<(Entity, &FactorGraph, &RadioAntenna), With<RobotConnections>>,
     config: &Config,
-) -> Option<String> {
+) -> Option<Graph> {
     if query.is_empty() {
         // There are no factorgraph in the scene/world
         warn!("There are no factorgraphs in the scene/world");
         return None;
     }
 
-    // let _external_edge_length = 8.0;
-    // let _internal_edge_length = 1.0;
-    let cluster_margin = 16;
-
-    let mut buf = String::with_capacity(4 * 1024); // 4 kB
-    let mut append_line_to_output = |line: &str| {
-        buf.push_str(line);
-        buf.push('\n');
-    };
-    append_line_to_output("graph {");
-    append_line_to_output("  dpi=96;");
-    append_line_to_output(r#"  label="factorgraph""#);
-    append_line_to_output("  node [style=filled];");
-    append_line_to_output("  layout=neato;");
-
-    // A hashmap used to keep track of which variable in another robots factorgraph,
-    // is connected to a interrobot factor in the current robots factorgraph.
-    let mut all_external_connections =
-        HashMap::<RobotId, HashMap<usize, (RobotId, usize, bool)>>::with_capacity(
-            query.iter().len(),
-        );
-
-    for (robot_id, factorgraph, antenna) in query.iter() {
-        let (nodes, edges) = factorgraph.export_graph();
-
-        // append_line_to_output(&format!(r#"  subgraph "cluster_{:?}" {{"#, robot_id));
-        append_line_to_output(&format!(r#"  subgraph "{:?}" {{"#, robot_id));
-        append_line_to_output(&format!("  margin={}", cluster_margin));
-        append_line_to_output(&format!(r#"  label="{:?}""#, robot_id));
-        // Add all nodes
-        for node in &nodes {
-            let pos = match node.kind {
-                NodeKind::Variable { x, y } => Some((x, y)),
-                _ => None,
-            };
-
-            let label = match node.kind {
-                NodeKind::Variable { .. } => format!("v{}", node.index),
-                NodeKind::InterRobotFactor { .. } => "fr".to_string(),
-                NodeKind::DynamicFactor => "fd".to_string(),
-                NodeKind::ObstacleFactor => "fo".to_string(),
-                NodeKind::TrackingFactor => "ft".to_string(),
-            };
-
-            let line = {
-                let mut line = String::with_capacity(32);
-                line.push_str(&format!(
-                    r#""{:?}_{:?}" [label="{}", fillcolor="{}", shape={}, width="{}""#,
-                    robot_id,
-                    node.index,
-                    label,
-                    // node.index,
-                    node.color(),
-                    node.shape(),
-                    node.width()
-                ));
-                if let Some((x, y)) = pos {
-                    // line.push_str(&format!(r#", pos="{x},{y}!""#));
-                    // line.push_str(&format!(r#", pos="{x}, {y}""#));
-                }
-                line.push(']');
-                line
-            };
-
-            append_line_to_output(&line);
-
-            // let mut line = String::with_capacity(32);
-            // line.push_str(&format!(
-            //    r#""{:?}_{:?}" [label="{}", fillcolor="{}", shape={},
-            // width="{}""#,    robot_id,
-            //    node.index * 10,
-            //    "fp",
-            //    // node.index,
-            //    "purple",
-            //    // node.color(),
-            //    node.shape(),
-            //    node.width()
-            //));
-        }
-        append_line_to_output("}");
-
-        append_line_to_output("");
-        // Add all internal edges
-        for edge in &edges {
-            let line = format!(
-                r#""{:?}_{:?}" -- "{:?}_{:?}""#,
-                robot_id, edge.from, robot_id, edge.to
-            );
-            append_line_to_output(&line);
-        }
-
-        let external_connections: HashMap<usize, (RobotId, usize, bool)> = nodes
-            .into_iter()
-            .filter_map(|node| match node.kind {
-                NodeKind::InterRobotFactor {
-                    active,
-                    external_variable_id,
-                } => Some((
-                    node.index,
-                    (
-                        external_variable_id.factorgraph_id,
-                        external_variable_id.variable_index.index(),
-                        antenna.active,
-                        // connection.id_of_robot_connected_with,
-                        // connection
-                        //     .index_of_connected_variable_in_other_robots_factorgraph
-                        //     .index(),
-                    ),
-                )),
-                _ => None,
-            })
-            .collect();
-
-        all_external_connections.insert(robot_id, external_connections);
-    }
-
-    // Add edges between interrobot factors and the variable they are connected to
-    // in another robots graph
-    for (from_robot_id, from_connections) in all_external_connections {
-        for (from_factor, (to_robot_id, to_variable_index, active)) in from_connections {
-            append_line_to_output(&format!(
-                r#" "{:?}_{:?}" -- "{:?}_{:?}" [len={}, style={}, color="{}", penwidth=3.0]"#,
-                from_robot_id,
-                from_factor,
-                to_robot_id,
-                to_variable_index,
-                if active {
-                    config.graphviz.interrobot.active.len
-                } else {
-                    config.graphviz.interrobot.inactive.len
-                },
-                if active {
-                    &config.graphviz.interrobot.active.style
-                } else {
-                    &config.graphviz.interrobot.inactive.style
-                },
-                if active {
-                    &config.graphviz.interrobot.active.color
-                } else {
-                    &config.graphviz.interrobot.inactive.color
-                }
-            ));
-        }
-    }
-
-    append_line_to_output("}"); // closing '}' for starting "graph {"
-    Some(buf)
+    let robots = query
+        .iter()
+        .map(|(robot_id, factorgraph, antenna)| (robot_id, &**factorgraph, antenna.active));
+    Some(Graph::new(robots, &config.graphviz.export_options()))
 }
 
 fn cycle_theme(
@@ -406,7 +258,7 @@ fn handle_export_graph(
         ));
     }
 
-    let Some(output) = export_factorgraphs_as_graphviz(q, config) else {
+    let Some(graph) = export_factorgraphs_as_graphviz(q, config) else {
         warn!("There are no factorgraphs in the world");
         // toast_event.send(ToastEvent::warning(
         //     "There are no factorgraphs in the world".to_string(),
@@ -418,65 +270,49 @@ fn handle_export_graph(
         return Ok(());
     };
 
-    let dot_output_path = std::path::PathBuf::from("factorgraphs.dot");
-    if dot_output_path.exists() {
-        warn!(
-            "output destination: ./{:#?} already exists!",
-            dot_output_path
-        );
-        warn!("overwriting ./{:#?}", dot_output_path);
+    let export_location = std::path::PathBuf::from(&config.graphviz.export_location);
+    std::fs::create_dir_all(&export_location)?;
+    // Timestamped to the millisecond, and suffixed with a counter if an export with
+    // the same timestamp exists, so earlier exports are not overwritten
+    let stem = format!("factorgraphs_{}", chrono::Utc::now().timestamp_millis());
+    let exported = |path: &std::path::Path| {
+        config
+            .graphviz
+            .formats
+            .iter()
+            .any(|format| path.with_extension(format.extension()).exists())
+    };
+    let mut output_path = export_location.join(&stem);
+    for n in 1.. {
+        if !exported(&output_path) {
+            break;
+        }
+        output_path = export_location.join(format!("{stem}_{n}"));
     }
-    info!("exporting all factorgraphs to ./{:#?}", dot_output_path);
-    // toast_event.send(ToastEvent::info(format!(
-    //     "exporting all factorgraphs to ./{:#?}",
-    //     dot_output_path
-    // )));
-    export_graph_finished_event.send(ExportFactorGraphAsGraphvizFinished::Success(
-        dot_output_path.to_string_lossy().to_string(),
-    ));
 
-    std::fs::write(&dot_output_path, output.as_bytes())?;
-
-    IoTaskPool::get()
-        .spawn(async move {
-            let extension = "png";
-            let image_output_path = dot_output_path.with_extension(extension);
-            let args = [
-                "-T",
-                extension,
-                //"png",
-                "-o",
-                image_output_path.to_str().expect("is valid UTF8"),
-                dot_output_path.to_str().expect("is valid UTF8"),
-            ];
-            let Ok(output) = std::process::Command::new("dot").args(args).output() else {
-                // let error_msg = format!(
-                //     "failed to compile ./{:?} with dot. reason: dot was not found in $PATH",
-                //     dot_output_path
-                // );
-                error!(
-                    "failed to compile ./{:?} with dot. reason: dot was not found in $PATH",
-                    dot_output_path
-                );
-
-                // toast_event.send(ToastEvent::error(error_msg));
-
-                return;
-            };
-
-            if output.status.success() {
-                info!(
-                    "compiled {:?} to {:?} with dot",
-                    dot_output_path, image_output_path
-                );
-            } else {
-                error!(
-                    "attempting to compile graph with dot, returned a non-zero exit status: {:?}",
-                    output
-                );
-            }
-        })
-        .detach();
+    for format in &config.graphviz.formats {
+        let output = match format {
+            GraphvizFormat::Dot => graph.to_dot(&config.graphviz.dot_style()),
+            GraphvizFormat::Json => serde_json::to_string_pretty(&graph)?,
+            GraphvizFormat::Svg => graph.to_svg(),
+        };
+        let output_path = output_path.with_extension(format.extension());
+        info!("exporting all factorgraphs to {:?}", output_path);
+        std::fs::write(&output_path, output.as_bytes())?;
+    }
+
+    let extensions = config
+        .graphviz
+        .formats
+        .iter()
+        .map(|format| format.extension())
+        .collect::<Vec<_>>()
+        .join(",");
+    export_graph_finished_event.send(ExportFactorGraphAsGraphvizFinished::Success(format!(
+        "{}.{{{}}}",
+        output_path.display(),
+        extensions
+    )));
 
     Ok(())
 }
@@ -491,7 +327,7 @@ fn export_graph_finished_system(
         match event {
             ExportFactorGraphAsGraphvizFinished::Success(path) => {
                 toast_event.send(ToastEvent::info(format!(
-                    "successfully exported factorgraphs to ./{:#?}",
+                    "successfully exported factorgraphs to {}",
                     path
                 )));
             }
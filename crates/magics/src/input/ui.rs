@@ -41,6 +41,8 @@ pub enum UiAction {
     ToggleBottomPanel,
     #[display(fmt = "Toggle Metrics Window")]
     ToggleMetricsWindow,
+    #[display(fmt = "Toggle Environment Editor")]
+    ToggleEditorWindow,
     ChangeScaleKind,
 }
 
@@ -52,7 +54,10 @@ impl UiAction {
             Self::ToggleTopPanel => InputKind::PhysicalKey(KeyCode::KeyK),
             Self::ToggleBottomPanel => InputKind::PhysicalKey(KeyCode::KeyJ),
             Self::ChangeScaleKind => InputKind::PhysicalKey(KeyCode::KeyU),
-            Self::ToggleMetricsWindow => InputKind::PhysicalKey(KeyCode::KeyD), // d for diagnostics
+            // d for diagnostics
+            Self::ToggleMetricsWindow => InputKind::PhysicalKey(KeyCode::KeyD),
+            // b for build
+            Self::ToggleEditorWindow => InputKind::PhysicalKey(KeyCode::KeyB),
         };
 
         UserInput::Single(input_kind)
@@ -102,6 +107,10 @@ fn handle_ui_actions(
         ui_state.metrics_window_visible = !ui_state.metrics_window_visible;
     }
 
+    if action_state.just_pressed(&UiAction::ToggleEditorWindow) {
+        ui_state.editor_window_visible = !ui_state.editor_window_visible;
+    }
+
     if action_state.just_pressed(&UiAction::ChangeScaleKind) {
         ui_state.scale_type = match ui_state.scale_type {
             UiScaleType::None => UiScaleType::Custom,
@@ -11,15 +11,19 @@ pub mod environment;
 pub mod export;
 pub mod factorgraph;
 pub mod goal_area;
+pub mod headless;
 pub mod input;
 pub mod moveable_object;
 pub mod movement;
 pub mod pause_play;
 pub mod planner;
 pub mod simulation_loader;
+pub mod sweep;
 pub mod theme;
+pub mod trace;
 pub mod ui;
 pub(crate) mod utils;
+pub mod validation;
 
 pub(crate) mod escape_codes;
 pub(crate) mod macros;
@@ -8,6 +8,7 @@ mod diagnostic;
 mod environment;
 mod factorgraph;
 pub mod goal_area;
+mod headless;
 mod input;
 mod moveable_object;
 mod movement;
@@ -16,10 +17,13 @@ pub(crate) mod pause_play;
 
 pub mod planner;
 pub(crate) mod simulation_loader;
+mod sweep;
 
 pub(crate) mod theme;
+mod trace;
 pub(crate) mod ui;
 pub(crate) mod utils;
+mod validation;
 
 pub mod export;
 
@@ -36,6 +40,7 @@ static ALLOC: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
 use std::path::Path;
 
+use anyhow::Context;
 use bevy::{
     asset::AssetMetaCheck,
     input::common_conditions::input_just_pressed,
@@ -198,7 +203,10 @@ fn main() -> anyhow::Result<()> {
     }
 
     if cli.list_scenarios {
-        let scenario_dir = Path::new("./config/simulations");
+        let scenario_dir = cli
+            .simulations_dir
+            .clone()
+            .unwrap_or_else(|| simulation_loader::SIMULATIONS_DIR.into());
         assert!(scenario_dir.exists());
         let mut directories = Vec::new();
         let entries = scenario_dir.read_dir()?; // .sort_by(|a, b| a.file_name().cmp(&b.file_name()));
@@ -249,6 +257,76 @@ fn main() -> anyhow::Result<()> {
         );
     }
 
+    if let Some(cli::Command::Sweep(ref args)) = cli.command {
+        let spec = sweep::SweepSpec::from_file(&args.spec)?;
+        let options = sweep::SweepOptions {
+            spec_path: args.spec.clone(),
+            simulations_dir: cli
+                .simulations_dir
+                .clone()
+                .unwrap_or_else(|| simulation_loader::SIMULATIONS_DIR.into()),
+            output_dir: args.output_dir.clone(),
+            jobs: args.jobs.unwrap_or_else(|| {
+                std::thread::available_parallelism().unwrap_or(std::num::NonZeroUsize::MIN)
+            }),
+            force: args.force,
+            dry_run: args.dry_run,
+        };
+
+        if let Some(id) = args.run {
+            return sweep::run_single(&spec, &options, id);
+        }
+        return sweep::run(&spec, &options);
+    }
+
+    if let Some(cli::Command::Validate(ref args)) = cli.command {
+        let dir = args
+            .dir
+            .clone()
+            .or_else(|| cli.simulations_dir.clone())
+            .unwrap_or_else(|| simulation_loader::SIMULATIONS_DIR.into());
+        return validation::run(&dir);
+    }
+
+    let replay = match cli.command {
+        Some(cli::Command::Replay(ref args)) => {
+            let trace = trace::Trace::from_file(&args.trace)?;
+            if args.verify {
+                let verification = trace::verify::verify(&trace)?;
+                if let Some(divergence) = verification.divergence {
+                    anyhow::bail!(
+                        "re-simulation of {} diverged after {} identical steps, {}",
+                        args.trace.display(),
+                        verification.identical_steps,
+                        divergence
+                    );
+                }
+                eprintln!(
+                    "{}: all {} steps of {} were re-simulated identically",
+                    "info".green(),
+                    verification.identical_steps,
+                    args.trace.display()
+                );
+                return Ok(());
+            }
+            anyhow::ensure!(
+                !cli.headless,
+                "a trace can only be replayed with a window, use --verify to re-simulate it \
+                 headless"
+            );
+            Some(trace)
+        }
+        _ => None,
+    };
+
+    let simulations_dir = cli
+        .simulations_dir
+        .clone()
+        .unwrap_or_else(|| simulation_loader::SIMULATIONS_DIR.into());
+    if replay.is_none() {
+        ensure_valid_simulation(&simulations_dir)?;
+    }
+
     // let (config, formation, environment): (Config, FormationGroup, Environment) =
     // if cli.default {     (
     //         Config::default(),
@@ -359,39 +437,68 @@ fn main() -> anyhow::Result<()> {
     //    DefaultPlugins.set(window_plugin).set(image_plugin)
     //};
 
-    app
-        //.add_plugins(default_plugins)
-        // bevy builtin plugins
-        .add_plugins(DefaultPlugins
-            .set(window_plugin)
-            .set(image_plugin)
+    let simulation_loader = match replay {
+        Some(ref trace) => simulation_loader::SimulationLoaderPlugin {
+            show_toasts: true,
+            initial_simulation: simulation_loader::InitialSimulation::Preloaded(Box::new(
+                trace.header.simulation()?,
+            )),
+            reload_after: None,
+            ..Default::default()
+        },
+        None => simulation_loader::SimulationLoaderPlugin::new(
+            !cli.headless,
+            cli.initial_scenario.clone(),
         )
-        // third-party plugins
-        .add_plugins((
-            bevy_egui::EguiPlugin,
-            bevy_mod_picking::DefaultPickingPlugins,
-        ))
-
-        // our plugins
-        .add_plugins((
-            // simulation_loader::SimulationLoaderPlugin::default(),
-            despawn_entity_after::DespawnEntityAfterPlugin,
-            simulation_loader::SimulationLoaderPlugin::new(true, cli.initial_scenario.clone()),
-            pause_play::PausePlayPlugin::default(),
-            theme::ThemePlugin,
-            asset_loader::AssetLoaderPlugin,
-            environment::EnvironmentPlugin,
-            movement::MovementPlugin,
-            input::InputPlugin,
-            ui::EguiInterfacePlugin,
-            planner::PlannerPlugin,
-            bevy_notify::NotifyPlugin::default(),
-            export::ExportPlugin::default(),
-            bevy_fullscreen::ToggleFullscreenPlugin::default(),
-            goal_area::GoalAreaPlugin,
-        ))
-        .add_systems(Update, draw_coordinate_system.run_if(input_just_pressed(KeyCode::F1)))
-        .add_systems(PostUpdate, end_simulation.run_if(virtual_time_exceeds_max_time));
+        .simulations_dir(simulations_dir),
+    };
+
+    if cli.headless {
+        eprintln!("running headless, no window or renderer will be created");
+        app.add_plugins(headless::HeadlessPlugins::new(simulation_loader));
+    } else {
+        app
+            //.add_plugins(default_plugins)
+            // bevy builtin plugins
+            .add_plugins(DefaultPlugins
+                .set(window_plugin)
+                .set(image_plugin)
+            )
+            // third-party plugins
+            .add_plugins((
+                bevy_egui::EguiPlugin,
+                bevy_mod_picking::DefaultPickingPlugins,
+            ))
+
+            // our plugins
+            .add_plugins((
+                // simulation_loader::SimulationLoaderPlugin::default(),
+                despawn_entity_after::DespawnEntityAfterPlugin,
+                simulation_loader,
+                pause_play::PausePlayPlugin::default(),
+                theme::ThemePlugin,
+                asset_loader::AssetLoaderPlugin,
+                environment::EnvironmentPlugin,
+                movement::MovementPlugin,
+                input::InputPlugin,
+                ui::EguiInterfacePlugin,
+                planner::PlannerPlugin,
+                bevy_notify::NotifyPlugin::default(),
+                export::ExportPlugin::default(),
+                bevy_fullscreen::ToggleFullscreenPlugin::default(),
+                goal_area::GoalAreaPlugin,
+            ))
+            .add_systems(Update, draw_coordinate_system.run_if(input_just_pressed(KeyCode::F1)))
+            .add_systems(PostUpdate, end_simulation.run_if(virtual_time_exceeds_max_time));
+    }
+
+    if let Some(trace) = replay {
+        app.add_plugins(trace::replay::TraceReplayPlugin { trace });
+    } else if let Some(path) = cli.record_trace {
+        app.add_plugins(trace::TraceRecorderPlugin {
+            output: trace::TraceOutput::File(path),
+        });
+    }
 
     if let Some(schedule) = cli.schedule_graph {
         match schedule {
@@ -429,6 +536,27 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Check that `dir` contains at least one valid simulation, before building
+/// the app. Stops at the first valid simulation, and prints the reports of
+/// every simulation if none of them are valid.
+fn ensure_valid_simulation(dir: &Path) -> anyhow::Result<()> {
+    let scenario_dirs = validation::scenario_dirs(dir)
+        .with_context(|| format!("failed to read simulations directory {}", dir.display()))?;
+    let mut invalid = Vec::new();
+    for scenario_dir in scenario_dirs {
+        let report = validation::validate_scenario(&scenario_dir);
+        if report.is_valid() {
+            return Ok(());
+        }
+        invalid.push(report);
+    }
+
+    for report in &invalid {
+        eprintln!("{report}");
+    }
+    anyhow::bail!("no valid simulations found in {}", dir.display())
+}
+
 /// Returns true if the time has exceeded the max configured simulation time.
 ///
 /// # Example
@@ -1,16 +1,22 @@
-use std::{collections::HashMap, ops::Deref, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Deref,
+    time::Duration,
+};
 
 use bevy::{prelude::*, time::common_conditions::on_timer};
 use bevy_mod_picking::prelude::*;
 use gbp_global_planner::Colliders;
-use parry2d::{
-    bounding_volume::BoundingVolume,
-    na::{Complex, Isometry, Unit},
-};
+use parry2d::{bounding_volume::BoundingVolume, na::Isometry2};
 
 use self::events::RobotCollisionClickedOn;
-use super::{robot::Ball, RobotConnections};
+use super::{
+    robot::Ball,
+    spatial_index::{rebuild_robot_spatial_index, RobotSpatialIndex},
+    RobotConnections,
+};
 use crate::{
+    environment::dynamic_obstacles::DynamicObstacle,
     // environment::map_generator::Colliders,
     simulation_loader::{LoadSimulation, ReloadSimulation},
 };
@@ -34,9 +40,29 @@ impl Plugin for RobotCollisionsPlugin {
                 (
                     update_robot_robot_collisions,
                     update_robot_environment_collisions.run_if(resource_exists::<Colliders>),
+                    update_robot_dynamic_obstacle_collisions,
+                )
+                    .after(rebuild_robot_spatial_index),
+            )
+            .add_systems(
+                Update,
+                (
+                    on_obstacle_clicked_on,
+                    record_aabb_when_two_robots_collide,
+                    record_aabb_when_robot_collides_with_environment,
+                    log_information_when_robot_robot_collision_mesh_clicked_on,
                 ),
             )
             .add_systems(
+                PostUpdate,
+                clear_robot_robot_collisions
+                    .run_if(on_event::<LoadSimulation>().or_else(on_event::<ReloadSimulation>())),
+            );
+
+        // The collisions are still recorded when running headless, they are just not
+        // rendered
+        if app.is_plugin_added::<bevy::window::WindowPlugin>() {
+            app.add_systems(
                 Update,
                 (
                     // update_robot_robot_collisions.run_if(on_timer(Self::UPDATE_EVERY)),
@@ -49,19 +75,11 @@ impl Plugin for RobotCollisionsPlugin {
                     // ),
                     render_robot_environment_collisions,
                     toggle_visibility_of_robot_environment_collisions,
-                    on_obstacle_clicked_on,
-                    record_aabb_when_two_robots_collide,
-                    record_aabb_when_robot_collides_with_environment,
                     // toggle_visibility_of_robot_environment_collisions
                     //     .run_if(input_just_pressed(KeyCode::F10)),
-                    log_information_when_robot_robot_collision_mesh_clicked_on,
                 ),
-            )
-            .add_systems(
-                PostUpdate,
-                clear_robot_robot_collisions
-                    .run_if(on_event::<LoadSimulation>().or_else(on_event::<ReloadSimulation>())),
             );
+        }
     }
 }
 
@@ -71,71 +89,53 @@ fn clear_robot_robot_collisions(mut robot_collisions: ResMut<resources::RobotRob
 
 fn update_robot_robot_collisions(
     mut robot_collisions: ResMut<resources::RobotRobotCollisions>,
-    robots: Query<(Entity, &Transform, &Ball), With<RobotConnections>>,
-    // PERF: store bounding spheres in a Local<> vec to reuse the allocation between system calls
-    mut aabbs: Local<
-        Vec<(
-            Entity,
-            Isometry<f32, Unit<Complex<f32>>, 2>,
-            parry2d::bounding_volume::BoundingSphere,
-        )>,
-    >,
+    index: Res<RobotSpatialIndex>,
     mut evw_robots_collided: EventWriter<events::RobotRobotCollision>,
     time_virtual: Res<Time<Virtual>>,
 ) {
-    aabbs.clear();
-
-    let iter = robots.iter().map(|(entity, tf, ball)| {
-        let position = parry2d::na::Isometry2::translation(tf.translation.x, tf.translation.z); // bevy uses xzy coordinates
-        let bounding_volume = ball.bounding_sphere(&position);
-        (entity, position, bounding_volume)
-        // (entity, ball.aabb(&position))
-    });
-
-    aabbs.extend(iter);
-
-    if aabbs.len() < 2 {
-        // No collisions if there is less than two robots
-        return;
+    // The broad phase only reports the robots close enough to collide, so the
+    // pairs that were colliding, but are no longer reported, have moved apart
+    let mut moved_apart: HashSet<(Entity, Entity)> = robot_collisions.colliding().collect();
+
+    for (a, b) in index.collision_candidates() {
+        // Order the pair, so it is recorded under the same key every time
+        let ((r_position, r), (c_position, c)) = if a.1.entity < b.1.entity {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        let r_isometry = Isometry2::translation(r_position.x, r_position.y);
+        let c_isometry = Isometry2::translation(c_position.x, c_position.y);
+        let r_ball = parry2d::shape::Ball::new(r.radius);
+        let c_ball = parry2d::shape::Ball::new(c.radius);
+
+        let is_colliding = r_ball
+            .bounding_sphere(&r_isometry)
+            .intersects(&c_ball.bounding_sphere(&c_isometry));
+        moved_apart.remove(&(r.entity, c.entity));
+        let collision_status = robot_collisions.update(r.entity, c.entity, is_colliding);
+
+        if collision_status == CollisionStatus::Hit {
+            let intersection = r_ball
+                .aabb(&r_isometry)
+                .intersection(&c_ball.aabb(&c_isometry))
+                .expect("the robots just hit each other, so they intersect");
+
+            println!(
+                "send robot collided event with intersection: {:?}",
+                &intersection
+            );
+            evw_robots_collided.send(events::RobotRobotCollision {
+                robot_a: c.entity,
+                robot_b: r.entity,
+                intersection,
+                happened_at: time_virtual.elapsed_seconds(),
+            });
+        }
     }
 
-    for (r, c) in
-        seq::upper_triangular_exclude_diagonal(aabbs.len().try_into().expect("more than one robot"))
-            .expect("more than one robot")
-    {
-        let is_colliding = aabbs[r].2.intersects(&aabbs[c].2);
-        // aabbs[r].1.intersection()
-        let collision_status = robot_collisions.update(aabbs[r].0, aabbs[c].0, is_colliding);
-
-        match collision_status {
-            CollisionStatus::Hit => {
-                // ehh...
-                let r_ball = robots.get(aabbs[r].0).unwrap().2;
-                let c_ball = robots.get(aabbs[c].0).unwrap().2;
-                let r_aabb = r_ball.aabb(&aabbs[r].1);
-                let c_aabb = c_ball.aabb(&aabbs[c].1);
-                let intersection = r_aabb
-                    .intersection(&c_aabb)
-                    .expect("the robots just hit each other, so they intersect");
-
-                // let intersection = aabbs[r]
-                //     .1
-                //     .intersection(&aabbs[c].1)
-                //     .expect("the robots just hit each other, so they intersect");
-
-                println!(
-                    "send robot collided event with intersection: {:?}",
-                    &intersection
-                );
-                evw_robots_collided.send(events::RobotRobotCollision {
-                    robot_a: aabbs[c].0,
-                    robot_b: aabbs[r].0,
-                    intersection,
-                    happened_at: time_virtual.elapsed_seconds(),
-                });
-            }
-            _ => {}
-        }
+    for (r, c) in moved_apart {
+        robot_collisions.update(r, c, false);
     }
 }
 
@@ -196,6 +196,14 @@ pub mod resources {
             // self.inner.values().map(|c| c.collisions()).sum::<usize>()
         }
 
+        /// The pairs colliding as of their last update
+        pub(super) fn colliding(&self) -> impl '_ + Iterator<Item = (Entity, Entity)> {
+            self.inner
+                .iter()
+                .filter(|(_, history)| history.is_colliding())
+                .map(|(pair, _)| *pair)
+        }
+
         pub(super) fn clear(&mut self) {
             self.inner.clear();
         }
@@ -279,6 +287,14 @@ pub mod resources {
             // self.inner.values().map(|c| c.collisions()).sum::<usize>()
         }
 
+        /// The pairs colliding as of their last update
+        pub(super) fn colliding(&self) -> impl '_ + Iterator<Item = (Entity, Entity)> {
+            self.inner
+                .iter()
+                .filter(|(_, history)| history.is_colliding())
+                .map(|(pair, _)| *pair)
+        }
+
         pub fn collisions(
             &self,
         ) -> impl '_ + Iterator<Item = ((Entity, Entity), &[parry2d::bounding_volume::Aabb])> // TODO:
@@ -367,71 +383,111 @@ pub mod events {
 
 fn update_robot_environment_collisions(
     env_colliders: Res<Colliders>,
-    robots: Query<(Entity, &Transform, &Ball), With<RobotConnections>>,
+    index: Res<RobotSpatialIndex>,
     mut robot_environment_collisions: ResMut<resources::RobotEnvironmentCollisions>,
-    mut aabbs: Local<
-        Vec<(
-            Entity,
-            Isometry<f32, Unit<Complex<f32>>, 2>,
-            parry2d::bounding_volume::BoundingSphere,
-        )>,
-    >,
     mut evw_robot_environment_collision: EventWriter<events::RobotEnvironmentCollision>,
 ) {
-    // aabbs.clear();
-    //
-    // let iter = robots.iter().map(|(entity, tf, ball)| {
-    //     let position = parry2d::na::Isometry2::translation(tf.translation.x,
-    // tf.translation.z); // bevy uses xzy coordinates
-    // // (entity, ball.aabb(&position))     (entity, position,
-    // ball.bounding_sphere(&position)) });
-    //
-    // aabbs.extend(iter);
-    //
-    // if aabbs.is_empty() {
-    //     // No collisions if there are no robots
-    //     return;
-    // }
-
-    // println!("#env colliders: {}", env_colliders.len());
-
-    // check every robot aabb against every environment aabb
+    // The dynamic obstacles share the collision history, but are checked by
+    // `update_robot_dynamic_obstacle_collisions`
+    let obstacles: HashSet<Entity> = env_colliders
+        .iter()
+        .filter_map(|env_collider| env_collider.associated_mesh)
+        .collect();
+    let mut moved_apart: HashSet<(Entity, Entity)> = robot_environment_collisions
+        .colliding()
+        .filter(|(_, obstacle)| obstacles.contains(obstacle))
+        .collect();
+
+    // Only the robots within their radius of the bounding box of a collider can
+    // collide with it
+    let margin = Vec2::splat(index.max_radius());
+    for env_collider in env_colliders.iter() {
+        let env_mesh_id = env_collider
+            .associated_mesh
+            .expect("Environment collider should have an associated mesh.");
+        let env_aabb = env_collider.aabb();
+        let (mins, maxs) = (
+            Vec2::new(env_aabb.mins.x, env_aabb.mins.y) - margin,
+            Vec2::new(env_aabb.maxs.x, env_aabb.maxs.y) + margin,
+        );
+
+        for (position, robot) in index.in_rect(mins, maxs) {
+            let robot_pos = parry2d::na::Isometry2::translation(position.x, position.y);
+            let ball = parry2d::shape::Ball::new(robot.radius);
+            let is_colliding: bool = parry2d::query::intersection_test(
+                &env_collider.isometry,
+                env_collider.shape.as_ref(),
+                &robot_pos,
+                &ball,
+            )
+            .expect("used shapes are supported");
+            moved_apart.remove(&(robot.entity, env_mesh_id));
+            let collision_status =
+                robot_environment_collisions.update(robot.entity, env_mesh_id, is_colliding);
+
+            if collision_status == CollisionStatus::Hit {
+                let intersection = ball.aabb(&robot_pos).intersection(&env_aabb).unwrap();
+                evw_robot_environment_collision.send(events::RobotEnvironmentCollision {
+                    robot: robot.entity,
+                    obstacle: env_mesh_id,
+                    intersection,
+                });
+
+                warn!(
+                    "robot {:?} collided with environment collision event with intersection: {:?}",
+                    &robot.entity, &intersection
+                );
+            }
+        }
+    }
 
+    for (robot, obstacle) in moved_apart {
+        robot_environment_collisions.update(robot, obstacle, false);
+    }
+}
+
+/// Same as [`update_robot_environment_collisions`], but for the dynamic
+/// obstacles, which are not part of the static [`Colliders`]
+fn update_robot_dynamic_obstacle_collisions(
+    obstacles: Query<(Entity, &Transform, &DynamicObstacle)>,
+    robots: Query<(Entity, &Transform, &Ball), With<RobotConnections>>,
+    mut robot_environment_collisions: ResMut<resources::RobotEnvironmentCollisions>,
+    mut evw_robot_environment_collision: EventWriter<events::RobotEnvironmentCollision>,
+) {
     for (robot_id, tf, ball) in &robots {
         let robot_pos = parry2d::na::Isometry2::translation(tf.translation.x, tf.translation.z);
 
-        for env_collider in env_colliders.iter() {
+        for (obstacle_id, obstacle_tf, obstacle) in &obstacles {
+            let obstacle_pos = parry2d::na::Isometry2::translation(
+                obstacle_tf.translation.x,
+                obstacle_tf.translation.z,
+            );
             let is_colliding: bool = parry2d::query::intersection_test(
-                &env_collider.isometry,
-                env_collider.shape.as_ref(),
+                &obstacle_pos,
+                obstacle.shape(),
                 &robot_pos,
                 ball.deref(),
             )
             .expect("used shapes are supported");
-            let env_mesh_id = env_collider
-                .associated_mesh
-                .expect("Environment collider should have an associated mesh.");
             let collision_status =
-                robot_environment_collisions.update(robot_id, env_mesh_id, is_colliding);
-
-            match collision_status {
-                CollisionStatus::Hit => {
-                    let robot_aabb = ball.aabb(&robot_pos);
-                    let env_aabb = env_collider.aabb();
-                    let intersection = robot_aabb.intersection(&env_aabb).unwrap();
-                    evw_robot_environment_collision.send(events::RobotEnvironmentCollision {
-                        robot: robot_id,
-                        obstacle: env_mesh_id,
-                        intersection,
-                    });
-
-                    warn!(
-                        "robot {:?} collided with environment collision event with intersection: \
-                         {:?}",
-                        &robot_id, &intersection
-                    );
-                }
-                _ => {}
+                robot_environment_collisions.update(robot_id, obstacle_id, is_colliding);
+
+            if collision_status == CollisionStatus::Hit {
+                let robot_aabb = ball.aabb(&robot_pos);
+                let obstacle_aabb = obstacle.shape().compute_aabb(&obstacle_pos);
+                let intersection = robot_aabb
+                    .intersection(&obstacle_aabb)
+                    .expect("colliding shapes have intersecting aabbs");
+                evw_robot_environment_collision.send(events::RobotEnvironmentCollision {
+                    robot: robot_id,
+                    obstacle: obstacle_id,
+                    intersection,
+                });
+
+                warn!(
+                    "robot {:?} collided with dynamic obstacle {:?} with intersection: {:?}",
+                    &robot_id, &obstacle_id, &intersection
+                );
             }
         }
     }
@@ -490,6 +546,10 @@ impl CollisionHistory {
     fn collisions(&self) -> usize {
         self.times
     }
+
+    const fn is_colliding(&self) -> bool {
+        matches!(self.state, CollisionState::Colliding)
+    }
 }
 
 /// Marker components
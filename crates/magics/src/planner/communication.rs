@@ -0,0 +1,435 @@
+//! Communication channels between robots.
+//!
+//! Every message sent from the factorgraph of one robot to the factorgraph of
+//! another robot passes through a directed link between the two robots. A link
+//! can delay, drop and rate limit the messages sent over it, as configured in
+//! the `[robot.communication]` section of the config. With the default config
+//! every message is delivered in the same iteration it is sent. The dropped
+//! messages are counted per link and reason, see [`Channels::dropped`].
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use bevy::prelude::*;
+use gbp_config::CommunicationSection;
+use gbp_core::iteration::Transport;
+use rand::Rng;
+
+use super::RobotId;
+use crate::{
+    factorgraph::message::ExternalMessage,
+    simulation_loader::{LoadSimulation, ReloadSimulation},
+};
+
+pub struct CommunicationPlugin;
+
+impl Plugin for CommunicationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CommunicationChannels>().add_systems(
+            Update,
+            clear_communication_channels
+                .run_if(on_event::<LoadSimulation>().or_else(on_event::<ReloadSimulation>())),
+        );
+    }
+}
+
+/// A directed link between two robots, `(sender, receiver)`
+pub type Link = (RobotId, RobotId);
+
+/// **Bevy** resource with the messages in transit between robots
+#[derive(Resource, Deref, DerefMut)]
+pub struct CommunicationChannels(Channels<ExternalMessage>);
+
+impl Default for CommunicationChannels {
+    fn default() -> Self {
+        Self(Channels::new())
+    }
+}
+
+/// [`Transport`] sending the external messages of a GBP iteration over the
+/// communication channels, between robots at the given positions
+pub struct ChannelTransport<'a, R> {
+    pub channels:  &'a mut Channels<ExternalMessage>,
+    /// Position of every robot, used for the distance dependent message loss
+    pub positions: &'a HashMap<RobotId, Vec2>,
+    pub config:    &'a CommunicationSection,
+    pub rng:       &'a mut R,
+}
+
+impl<R: Rng> Transport<RobotId> for ChannelTransport<'_, R> {
+    fn send(&mut self, message: ExternalMessage) {
+        let (sender, receiver) = message.link();
+        let distance = self
+            .positions
+            .get(&sender)
+            .zip(self.positions.get(&receiver))
+            .map_or(0.0, |(sender, receiver)| sender.distance(*receiver));
+        // A dropped message is never delivered, and is counted by the channels
+        let _ = self.channels.send(
+            (sender, receiver),
+            message,
+            distance,
+            self.config,
+            &mut *self.rng,
+        );
+    }
+
+    fn deliver(&mut self) -> Vec<ExternalMessage> {
+        self.channels.deliver()
+    }
+}
+
+fn clear_communication_channels(mut channels: ResMut<CommunicationChannels>) {
+    channels.clear();
+}
+
+/// Why a message was not put in transit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dropped {
+    /// Lost with `drop-probability` or because of the `distance-loss`
+    Lost,
+    /// The link has already sent `bandwidth` messages this timestep
+    BandwidthExceeded,
+}
+
+/// Number of messages dropped over a link, by reason, see [`Dropped`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct DroppedCount {
+    pub lost: usize,
+    pub bandwidth_exceeded: usize,
+}
+
+impl DroppedCount {
+    /// Number of messages dropped for any reason
+    #[must_use]
+    pub const fn total(&self) -> usize {
+        self.lost + self.bandwidth_exceeded
+    }
+
+    fn count(&mut self, dropped: Dropped) {
+        match dropped {
+            Dropped::Lost => self.lost += 1,
+            Dropped::BandwidthExceeded => self.bandwidth_exceeded += 1,
+        }
+    }
+}
+
+impl std::ops::Add for DroppedCount {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            lost: self.lost + rhs.lost,
+            bandwidth_exceeded: self.bandwidth_exceeded + rhs.bandwidth_exceeded,
+        }
+    }
+}
+
+/// A message in transit over a link
+#[derive(Debug)]
+struct InTransit<M> {
+    deliver_at: f64,
+    message:    M,
+}
+
+/// Queue of messages in transit over a single link
+#[derive(Debug)]
+struct LinkQueue<M> {
+    in_transit: VecDeque<InTransit<M>>,
+    sent_this_timestep: usize,
+}
+
+impl<M> Default for LinkQueue<M> {
+    fn default() -> Self {
+        Self {
+            in_transit: VecDeque::new(),
+            sent_this_timestep: 0,
+        }
+    }
+}
+
+/// Per link queues of messages in transit
+#[derive(Debug)]
+pub struct Channels<M> {
+    links:    BTreeMap<Link, LinkQueue<M>>,
+    /// Messages dropped over every link since the channels were cleared
+    dropped:  BTreeMap<Link, DroppedCount>,
+    /// Start of the current timestep in seconds
+    now:      f64,
+    /// Duration of a timestep in seconds
+    timestep: f64,
+}
+
+impl<M> Channels<M> {
+    /// Margin used when comparing delivery times, so a delay of a whole number
+    /// of timesteps is not lost to floating point errors
+    const EPSILON: f64 = 1e-9;
+
+    /// Create channels without any messages in transit
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            links:    BTreeMap::new(),
+            dropped:  BTreeMap::new(),
+            now:      0.0,
+            timestep: 0.0,
+        }
+    }
+
+    /// Start a new timestep at `now` seconds, lasting `timestep` seconds.
+    /// Resets the bandwidth of every link.
+    pub fn start_timestep(&mut self, now: f64, timestep: f64) {
+        self.now = now;
+        self.timestep = timestep;
+        self.links.retain(|_, queue| !queue.in_transit.is_empty());
+        for queue in self.links.values_mut() {
+            queue.sent_this_timestep = 0;
+        }
+    }
+
+    /// Send `message` over `link` between two robots `distance` meters apart.
+    /// A dropped message is counted in [`Self::dropped`].
+    ///
+    /// # Errors
+    ///
+    /// Returns why the message was dropped, if it was not put in transit
+    pub fn send(
+        &mut self,
+        link: Link,
+        message: M,
+        distance: f32,
+        config: &CommunicationSection,
+        rng: &mut impl Rng,
+    ) -> Result<(), Dropped> {
+        let result = self.try_send(link, message, distance, config, rng);
+        if let Err(dropped) = result {
+            self.dropped.entry(link).or_default().count(dropped);
+        }
+        result
+    }
+
+    fn try_send(
+        &mut self,
+        link: Link,
+        message: M,
+        distance: f32,
+        config: &CommunicationSection,
+        rng: &mut impl Rng,
+    ) -> Result<(), Dropped> {
+        let queue = self.links.entry(link).or_default();
+        if config
+            .bandwidth
+            .is_some_and(|bandwidth| queue.sent_this_timestep >= bandwidth.get())
+        {
+            return Err(Dropped::BandwidthExceeded);
+        }
+        queue.sent_this_timestep += 1;
+
+        // Only sample when there is a chance of losing the message, so the
+        // sequence of random numbers is the same as without this subsystem
+        let loss = config
+            .distance_loss
+            .map_or(0.0, |distance_loss| distance_loss.probability(distance));
+        let keep = (1.0 - config.drop_probability.get()) * (1.0 - loss);
+        if keep < 1.0 && !rng.gen_bool(keep.clamp(0.0, 1.0)) {
+            return Err(Dropped::Lost);
+        }
+
+        queue.in_transit.push_back(InTransit {
+            deliver_at: self.now + config.delay.as_secs(self.timestep),
+            message,
+        });
+
+        Ok(())
+    }
+
+    /// Take every message that has arrived by the current timestep, in the
+    /// order they were sent over each link
+    pub fn deliver(&mut self) -> Vec<M> {
+        let mut delivered = Vec::new();
+        for queue in self.links.values_mut() {
+            while queue
+                .in_transit
+                .front()
+                .is_some_and(|in_transit| in_transit.deliver_at <= self.now + Self::EPSILON)
+            {
+                let in_transit = queue.in_transit.pop_front().expect("front is some");
+                delivered.push(in_transit.message);
+            }
+        }
+
+        delivered
+    }
+
+    /// Number of messages currently in transit
+    pub fn in_transit(&self) -> usize {
+        self.links
+            .values()
+            .map(|queue| queue.in_transit.len())
+            .sum()
+    }
+
+    /// Number of messages dropped over every link that has dropped any,
+    /// since the channels were cleared
+    pub fn dropped(&self) -> impl Iterator<Item = (Link, DroppedCount)> + '_ {
+        self.dropped.iter().map(|(&link, &count)| (link, count))
+    }
+
+    /// Number of messages dropped over all links, since the channels were
+    /// cleared
+    pub fn dropped_total(&self) -> DroppedCount {
+        self.dropped
+            .values()
+            .fold(DroppedCount::default(), |total, &count| total + count)
+    }
+
+    /// Drop every message in transit, and reset the number of dropped
+    /// messages
+    pub fn clear(&mut self) {
+        self.links.clear();
+        self.dropped.clear();
+    }
+}
+
+impl<M> Default for Channels<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use gbp_config::{CommunicationDelay, DistanceLoss};
+    use pretty_assertions::assert_eq;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    const TIMESTEP: f64 = 0.1;
+
+    fn link() -> Link {
+        (Entity::from_raw(1), Entity::from_raw(2))
+    }
+
+    fn rng() -> rand::rngs::StdRng {
+        rand::rngs::StdRng::seed_from_u64(0)
+    }
+
+    #[test]
+    fn default_config_delivers_immediately() {
+        let config = CommunicationSection::default();
+        let mut channels = Channels::new();
+        channels.start_timestep(1.0, TIMESTEP);
+        for message in 0..3 {
+            channels
+                .send(link(), message, 5.0, &config, &mut rng())
+                .unwrap();
+        }
+        assert_eq!(channels.deliver(), vec![0, 1, 2]);
+        assert_eq!(channels.in_transit(), 0);
+    }
+
+    #[test]
+    fn delayed_messages_arrive_in_a_later_timestep() {
+        let mut config = CommunicationSection::default();
+        config.delay = CommunicationDelay::Timesteps(2);
+        let mut channels = Channels::new();
+
+        channels.start_timestep(1.0, TIMESTEP);
+        channels.send(link(), 42, 5.0, &config, &mut rng()).unwrap();
+        assert!(channels.deliver().is_empty());
+
+        channels.start_timestep(1.1, TIMESTEP);
+        assert!(channels.deliver().is_empty());
+
+        channels.start_timestep(1.2, TIMESTEP);
+        assert_eq!(channels.deliver(), vec![42]);
+
+        // 150 ms is rounded up to the next timestep
+        config.delay = CommunicationDelay::Seconds(0.15);
+        channels.send(link(), 7, 5.0, &config, &mut rng()).unwrap();
+        channels.start_timestep(1.3, TIMESTEP);
+        assert!(channels.deliver().is_empty());
+        channels.start_timestep(1.4, TIMESTEP);
+        assert_eq!(channels.deliver(), vec![7]);
+    }
+
+    #[test]
+    fn bandwidth_is_per_link_and_timestep() {
+        let mut config = CommunicationSection::default();
+        config.bandwidth = NonZeroUsize::new(2);
+        let other_link = (Entity::from_raw(2), Entity::from_raw(1));
+        let mut channels = Channels::new();
+
+        channels.start_timestep(0.0, TIMESTEP);
+        assert_eq!(channels.send(link(), 0, 5.0, &config, &mut rng()), Ok(()));
+        assert_eq!(channels.send(link(), 1, 5.0, &config, &mut rng()), Ok(()));
+        assert_eq!(
+            channels.send(link(), 2, 5.0, &config, &mut rng()),
+            Err(Dropped::BandwidthExceeded)
+        );
+        assert_eq!(
+            channels.send(other_link, 3, 5.0, &config, &mut rng()),
+            Ok(())
+        );
+
+        channels.start_timestep(0.1, TIMESTEP);
+        assert_eq!(channels.send(link(), 4, 5.0, &config, &mut rng()), Ok(()));
+    }
+
+    #[test]
+    fn lost_messages() {
+        let mut config = CommunicationSection::default();
+        config.drop_probability = 1.0.try_into().unwrap();
+        let mut channels = Channels::new();
+        channels.start_timestep(0.0, TIMESTEP);
+        assert_eq!(
+            channels.send(link(), 0, 5.0, &config, &mut rng()),
+            Err(Dropped::Lost)
+        );
+
+        config.drop_probability = 0.0.try_into().unwrap();
+        config.distance_loss = Some(DistanceLoss {
+            start: 10.0,
+            end:   20.0,
+        });
+        assert_eq!(channels.send(link(), 1, 5.0, &config, &mut rng()), Ok(()));
+        assert_eq!(
+            channels.send(link(), 2, 25.0, &config, &mut rng()),
+            Err(Dropped::Lost)
+        );
+    }
+
+    #[test]
+    fn dropped_messages_are_counted_per_link_and_reason() {
+        let mut config = CommunicationSection::default();
+        config.bandwidth = NonZeroUsize::new(1);
+        let other_link = (Entity::from_raw(2), Entity::from_raw(1));
+        let mut channels = Channels::new();
+
+        channels.start_timestep(0.0, TIMESTEP);
+        for message in 0..3 {
+            let _ = channels.send(link(), message, 5.0, &config, &mut rng());
+        }
+        config.drop_probability = 1.0.try_into().unwrap();
+        let _ = channels.send(other_link, 3, 5.0, &config, &mut rng());
+
+        // The counts outlive the timestep
+        channels.start_timestep(0.1, TIMESTEP);
+        assert_eq!(channels.dropped().collect::<Vec<_>>(), vec![
+            (link(), DroppedCount {
+                lost: 0,
+                bandwidth_exceeded: 2,
+            }),
+            (other_link, DroppedCount {
+                lost: 1,
+                bandwidth_exceeded: 0,
+            }),
+        ]);
+        assert_eq!(channels.dropped_total().total(), 3);
+
+        channels.clear();
+        assert_eq!(channels.dropped().count(), 0);
+    }
+}
@@ -0,0 +1,451 @@
+//! Kinematics of the robots, i.e. how a robot moves to follow the trajectory
+//! planned by its factorgraph.
+//!
+//! A [`MotionModel::Holonomic`] robot is moved exactly along the mean of its
+//! planned trajectory, like in the gbpplanner paper. Every other model has a
+//! tracking controller, that steers the robot towards the next variable of its
+//! factorgraph within the velocity and acceleration limits of the model. The
+//! state the robot actually ends up in is then fed back into the factorgraph
+//! as the prior of the current variable.
+
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use gbp_config::formation::{
+    AckermannLimits, DifferentialDriveLimits, MotionModel, UnicycleLimits,
+};
+use gbp_linalg::prelude::*;
+
+use super::robot::{Mission, RobotConnections, T0};
+use crate::factorgraph::prelude::FactorGraph;
+
+/// Velocity command of a nonholonomic robot
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Command {
+    /// Forward speed along the heading in m/s
+    pub speed: f32,
+    /// Angular speed in rad/s, positive is counterclockwise
+    pub angular_speed: f32,
+}
+
+/// A nonholonomic motion model, driven by a forward speed and an angular
+/// speed. Implement this trait to add another model.
+pub trait NonHolonomic {
+    /// Whether the model is able to turn without moving forward
+    fn turns_in_place(&self) -> bool {
+        true
+    }
+
+    /// The command closest to `desired`, that the model is able to execute
+    /// `dt` seconds after executing `current`
+    fn constrain(&self, current: Command, desired: Command, dt: f32) -> Command;
+}
+
+/// Move `current` towards `target`, by at most `max_step`
+fn approach(current: f32, target: f32, max_step: f32) -> f32 {
+    current + (target - current).clamp(-max_step, max_step)
+}
+
+impl NonHolonomic for UnicycleLimits {
+    fn constrain(&self, current: Command, desired: Command, dt: f32) -> Command {
+        let max_speed = self.max_speed.get();
+        let max_angular_speed = self.max_angular_speed.get();
+        Command {
+            speed: approach(
+                current.speed,
+                desired.speed.clamp(-max_speed, max_speed),
+                self.max_acceleration.get() * dt,
+            ),
+            angular_speed: approach(
+                current.angular_speed,
+                desired
+                    .angular_speed
+                    .clamp(-max_angular_speed, max_angular_speed),
+                self.max_angular_acceleration.get() * dt,
+            ),
+        }
+    }
+}
+
+impl NonHolonomic for DifferentialDriveLimits {
+    fn constrain(&self, current: Command, desired: Command, dt: f32) -> Command {
+        let half_separation = self.wheel_separation.get() / 2.0;
+        let wheel_speeds = |command: Command| {
+            let turn = command.angular_speed * half_separation;
+            (command.speed - turn, command.speed + turn)
+        };
+
+        // Scale both wheels by the same factor, so the curvature of the desired
+        // path is kept when a wheel would exceed its maximum speed
+        let (left, right) = wheel_speeds(desired);
+        let fastest = left.abs().max(right.abs());
+        let max_wheel_speed = self.max_wheel_speed.get();
+        let scale = if fastest > max_wheel_speed {
+            max_wheel_speed / fastest
+        } else {
+            1.0
+        };
+
+        let (current_left, current_right) = wheel_speeds(current);
+        let max_step = self.max_wheel_acceleration.get() * dt;
+        let left = approach(current_left, left * scale, max_step);
+        let right = approach(current_right, right * scale, max_step);
+
+        Command {
+            speed: (left + right) / 2.0,
+            angular_speed: (right - left) / (2.0 * half_separation),
+        }
+    }
+}
+
+impl NonHolonomic for AckermannLimits {
+    fn turns_in_place(&self) -> bool {
+        false
+    }
+
+    fn constrain(&self, current: Command, desired: Command, dt: f32) -> Command {
+        let max_speed = self.max_speed.get();
+        let speed = approach(
+            current.speed,
+            desired.speed.clamp(-max_speed, max_speed),
+            self.max_acceleration.get() * dt,
+        );
+
+        let wheelbase = self.wheelbase.get();
+        let max_steering_angle = self.max_steering_angle.get();
+        let steering_angle = if speed.abs() < f32::EPSILON {
+            0.0
+        } else {
+            (desired.angular_speed * wheelbase / speed)
+                .atan()
+                .clamp(-max_steering_angle, max_steering_angle)
+        };
+
+        Command {
+            speed,
+            angular_speed: speed * steering_angle.tan() / wheelbase,
+        }
+    }
+}
+
+/// Gain of the heading controller in 1/s, i.e. how aggressively a robot turns
+/// towards the direction it wants to move in
+const HEADING_GAIN: f32 = 4.0;
+
+/// Wrap `angle` to the interval (-pi, pi]
+fn wrap_angle(angle: f32) -> f32 {
+    let wrapped = (angle + PI).rem_euclid(2.0 * PI) - PI;
+    if wrapped <= -PI {
+        wrapped + 2.0 * PI
+    } else {
+        wrapped
+    }
+}
+
+/// Tracking controller, turning the velocity a robot wants to move with into a
+/// command for a robot facing `heading`.
+/// Robots able to turn in place slow down while they are not facing the
+/// direction they want to move in.
+#[must_use]
+pub fn track(desired_velocity: Vec2, heading: f32, turns_in_place: bool) -> Command {
+    if desired_velocity.length_squared() < f32::EPSILON {
+        return Command::default();
+    }
+
+    let heading_error = wrap_angle(desired_velocity.y.atan2(desired_velocity.x) - heading);
+    let speed = if turns_in_place {
+        desired_velocity.length() * heading_error.cos().max(0.0)
+    } else {
+        desired_velocity.length()
+    };
+
+    Command {
+        speed,
+        angular_speed: HEADING_GAIN * heading_error,
+    }
+}
+
+/// Pose of a nonholonomic robot in the plane
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Pose {
+    pub position: Vec2,
+    /// Heading in radians, counterclockwise from the x-axis
+    pub heading:  f32,
+}
+
+impl Pose {
+    /// Integrate `command` over `dt` seconds. The robot moves along a circular
+    /// arc, which is exact for a command held constant over the interval.
+    #[must_use]
+    pub fn integrate(self, command: Command, dt: f32) -> Self {
+        let heading = self.heading + command.angular_speed * dt;
+        let displacement = if command.angular_speed.abs() < 1e-6 {
+            command.speed * dt * Vec2::new(self.heading.cos(), self.heading.sin())
+        } else {
+            let radius = command.speed / command.angular_speed;
+            radius
+                * Vec2::new(
+                    heading.sin() - self.heading.sin(),
+                    self.heading.cos() - heading.cos(),
+                )
+        };
+
+        Self {
+            position: self.position + displacement,
+            heading:  wrap_angle(heading),
+        }
+    }
+}
+
+/// **Bevy** component with the kinematic state of a robot
+#[derive(Debug, Component)]
+pub struct Kinematics {
+    model:   MotionModel,
+    heading: f32,
+    command: Command,
+}
+
+impl Kinematics {
+    /// Create the kinematic state of a robot at rest, facing `heading`
+    #[must_use]
+    pub const fn new(model: MotionModel, heading: f32) -> Self {
+        Self {
+            model,
+            heading,
+            command: Command {
+                speed: 0.0,
+                angular_speed: 0.0,
+            },
+        }
+    }
+
+    /// The motion model of the robot
+    #[inline]
+    pub const fn model(&self) -> MotionModel {
+        self.model
+    }
+
+    /// Heading in radians, counterclockwise from the x-axis
+    #[inline]
+    pub const fn heading(&self) -> f32 {
+        self.heading
+    }
+
+    /// Move a robot at `position` for `dt` seconds, with the velocity it wants
+    /// to move with. Returns the new position and velocity of the robot.
+    pub fn step(&mut self, position: Vec2, desired_velocity: Vec2, dt: f32) -> (Vec2, Vec2) {
+        let model: &dyn NonHolonomic = match self.model {
+            MotionModel::Holonomic => {
+                return (position + desired_velocity * dt, desired_velocity);
+            }
+            MotionModel::Unicycle(ref limits) => limits,
+            MotionModel::DifferentialDrive(ref limits) => limits,
+            MotionModel::Ackermann(ref limits) => limits,
+        };
+
+        let desired = track(desired_velocity, self.heading, model.turns_in_place());
+        self.command = model.constrain(self.command, desired, dt);
+        let pose = Pose {
+            position,
+            heading: self.heading,
+        }
+        .integrate(self.command, dt);
+        self.heading = pose.heading;
+
+        let velocity = self.command.speed * Vec2::new(pose.heading.cos(), pose.heading.sin());
+        (pose.position, velocity)
+    }
+}
+
+/// Called `Robot::updateCurrent` in **gbpplanner**.
+/// Moves every robot towards the next variable of its factorgraph, and sets
+/// the prior of the current variable to the state the robot ends up in.
+pub fn follow_planned_trajectory(
+    mut query: Query<
+        (
+            &mut FactorGraph,
+            &mut Transform,
+            &mut Kinematics,
+            &T0,
+            &Mission,
+        ),
+        With<RobotConnections>,
+    >,
+    time_fixed: Res<Time<Fixed>>,
+) {
+    let dt = time_fixed.delta_seconds();
+    for (mut factorgraph, mut transform, mut kinematics, &t0, mission) in &mut query {
+        if mission.state.idle() {
+            continue;
+        }
+
+        let time_scale = dt / *t0;
+        let (current_variable_index, current_variable) = factorgraph
+            .nth_variable(0)
+            .expect("factorgraph should have a current variable");
+        let (_, next_variable) = factorgraph
+            .nth_variable(1)
+            .expect("factorgraph should have a next variable");
+
+        let change_in_state =
+            Float::from(time_scale) * (&next_variable.belief.mean - &current_variable.belief.mean);
+
+        // bevy uses xzy coordinates, so the y component is at the z coordinate
+        #[allow(clippy::cast_possible_truncation)]
+        let mean_updated = if matches!(kinematics.model(), MotionModel::Holonomic) {
+            let [dx, dy, dz] = current_variable.layout().position(change_in_state.view());
+            transform.translation += Vec3::new(dx as f32, dz as f32, dy as f32);
+            &current_variable.belief.mean + &change_in_state
+        } else {
+            let desired_velocity =
+                Vec2::new(change_in_state[0] as f32, change_in_state[1] as f32) / dt;
+            let (position, velocity) =
+                kinematics.step(transform.translation.xz(), desired_velocity, dt);
+            transform.translation.x = position.x;
+            transform.translation.z = position.y;
+            // The velocity points along the heading of the robot, so the heading of
+            // the planar pose layout follows the kinematic heading
+            let mut mean = current_variable.belief.mean.clone();
+            current_variable.layout().set_planar(
+                &mut mean,
+                position.as_dvec2().to_array(),
+                velocity.as_dvec2().to_array(),
+            );
+            mean
+        };
+
+        let external_factor_messages =
+            factorgraph.change_prior_of_variable(current_variable_index, mean_updated);
+        assert!(
+            external_factor_messages.is_empty(),
+            "the current variable is not connected to any external factors"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use typed_floats::StrictlyPositiveFinite;
+
+    use super::*;
+
+    fn positive(value: f32) -> StrictlyPositiveFinite<f32> {
+        StrictlyPositiveFinite::new(value).unwrap()
+    }
+
+    fn unicycle() -> UnicycleLimits {
+        UnicycleLimits {
+            max_speed: positive(2.0),
+            max_acceleration: positive(1.0),
+            max_angular_speed: positive(PI),
+            max_angular_acceleration: positive(10.0),
+        }
+    }
+
+    #[test]
+    fn integrate_along_an_arc() {
+        let pose = Pose::default();
+        // A quarter of a circle with radius 1
+        let command = Command {
+            speed: PI / 2.0,
+            angular_speed: PI / 2.0,
+        };
+        let pose = pose.integrate(command, 1.0);
+        assert_relative_eq!(pose.position.x, 1.0, epsilon = 1e-5);
+        assert_relative_eq!(pose.position.y, 1.0, epsilon = 1e-5);
+        assert_relative_eq!(pose.heading, PI / 2.0, epsilon = 1e-5);
+
+        let straight = Pose::default().integrate(
+            Command {
+                speed: 2.0,
+                angular_speed: 0.0,
+            },
+            0.5,
+        );
+        assert_relative_eq!(straight.position.x, 1.0);
+        assert_relative_eq!(straight.position.y, 0.0);
+    }
+
+    #[test]
+    fn unicycle_respects_limits() {
+        let limits = unicycle();
+        let desired = Command {
+            speed: 10.0,
+            angular_speed: -10.0,
+        };
+        let command = limits.constrain(Command::default(), desired, 0.1);
+        assert_relative_eq!(command.speed, 0.1);
+        assert_relative_eq!(command.angular_speed, -1.0);
+
+        let command = (0..100).fold(command, |command, _| {
+            limits.constrain(command, desired, 0.1)
+        });
+        assert_relative_eq!(command.speed, 2.0);
+        assert_relative_eq!(command.angular_speed, -PI);
+    }
+
+    #[test]
+    fn differential_drive_keeps_curvature() {
+        let limits = DifferentialDriveLimits {
+            wheel_separation: positive(1.0),
+            max_wheel_speed: positive(1.0),
+            max_wheel_acceleration: positive(100.0),
+        };
+        let desired = Command {
+            speed: 2.0,
+            angular_speed: 2.0,
+        };
+        let command = limits.constrain(Command::default(), desired, 1.0);
+        // The outer wheel would be driven at 3 m/s, so both are scaled by 1/3
+        assert_relative_eq!(command.speed, 2.0 / 3.0, epsilon = 1e-6);
+        assert_relative_eq!(command.angular_speed, 2.0 / 3.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn ackermann_does_not_turn_in_place() {
+        let limits = AckermannLimits {
+            wheelbase: positive(1.0),
+            max_speed: positive(2.0),
+            max_acceleration: positive(100.0),
+            max_steering_angle: positive(PI / 4.0),
+        };
+        let turn = Command {
+            speed: 0.0,
+            angular_speed: 1.0,
+        };
+        assert_eq!(
+            limits.constrain(Command::default(), turn, 0.1),
+            Command::default()
+        );
+
+        let sharp_turn = Command {
+            speed: 1.0,
+            angular_speed: 100.0,
+        };
+        let command = limits.constrain(
+            Command {
+                speed: 1.0,
+                angular_speed: 0.0,
+            },
+            sharp_turn,
+            0.1,
+        );
+        // Limited by the steering angle, tan(pi / 4) = 1
+        assert_relative_eq!(command.angular_speed, 1.0, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn unicycle_converges_to_the_desired_velocity() {
+        let mut kinematics = Kinematics::new(MotionModel::Unicycle(unicycle()), PI / 2.0);
+        let desired_velocity = Vec2::new(1.0, 0.0);
+        let mut position = Vec2::ZERO;
+        let mut velocity = Vec2::ZERO;
+        for _ in 0..200 {
+            (position, velocity) = kinematics.step(position, desired_velocity, 0.05);
+        }
+        assert_relative_eq!(kinematics.heading(), 0.0, epsilon = 1e-3);
+        assert_relative_eq!(velocity.x, 1.0, epsilon = 1e-3);
+        assert!(position.x > 5.0);
+    }
+}
@@ -0,0 +1,665 @@
+//! Trajectory quality metrics of the robots.
+//!
+//! The metrics are computed from the samples recorded by the trackers in
+//! [`super::tracking`], and follow the definitions used by the analysis
+//! scripts in `./scripts`, e.g. `ldj.py`, `distance-travelled.py` and
+//! `perpendicular-path-deviation.py`, so the numbers shown in the ui, written
+//! in the export and plotted by the scripts agree.
+//!
+//! The inter-robot clearance can not be computed from the position samples,
+//! as they are not sampled at the same time for every robot. It is instead
+//! recorded every fixed timestep by the [`InterRobotProximity`] component.
+//! Likewise, how much each robot yields to the other robots is recorded every
+//! fixed timestep by the [`Yielding`] component.
+
+use std::{collections::HashMap, time::Duration};
+
+use bevy::{prelude::*, time::common_conditions::on_timer};
+use gbp_config::Config;
+
+use super::{
+    robot::{Mission, Radius},
+    spatial_index::{rebuild_robot_spatial_index, RobotSpatialIndex},
+    tracking::{PositionTracker, VelocityTracker},
+};
+use crate::simulation_loader::{LoadSimulation, ReloadSimulation};
+
+pub struct MetricsPlugin;
+
+impl Plugin for MetricsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TrajectoryMetrics>()
+            .add_systems(
+                FixedUpdate,
+                (track_inter_robot_proximity, track_yielding).after(rebuild_robot_spatial_index),
+            )
+            .add_systems(
+                Update,
+                (
+                    update_trajectory_metrics.run_if(on_timer(Duration::from_millis(500))),
+                    clear_trajectory_metrics.run_if(
+                        on_event::<LoadSimulation>().or_else(on_event::<ReloadSimulation>()),
+                    ),
+                ),
+            );
+    }
+}
+
+/// Component recording how close a robot has been to the other robots.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct InterRobotProximity {
+    /// Smallest distance between the surface of the robot and the surface of
+    /// any other robot within the safety distance. `None` if no other robot
+    /// has been within the safety distance.
+    min_clearance: Option<f32>,
+    /// Seconds spent with another robot within the safety distance
+    time_within_safety_distance: f64,
+    /// Whether another robot is within the safety distance in the current
+    /// timestep
+    within_safety_distance: bool,
+}
+
+impl InterRobotProximity {
+    /// Smallest clearance to any other robot within the safety distance
+    /// recorded so far. Negative if the robot has collided with another robot.
+    #[inline]
+    pub const fn min_clearance(&self) -> Option<f32> {
+        self.min_clearance
+    }
+
+    /// Seconds spent with another robot within the safety distance
+    #[inline]
+    pub const fn time_within_safety_distance(&self) -> f64 {
+        self.time_within_safety_distance
+    }
+
+    fn record_clearance(&mut self, clearance: f32) {
+        self.min_clearance = Some(
+            self.min_clearance
+                .map_or(clearance, |min| min.min(clearance)),
+        );
+    }
+}
+
+/// **Bevy** system that records the clearance between every pair of robots
+/// within the safety distance of each other, and the time each robot spends
+/// with another robot within its safety distance. The safety distance is the
+/// same as the one used by the interrobot factors, i.e. the robot radius times
+/// `robot.inter-robot-safety-distance-multiplier`.
+fn track_inter_robot_proximity(
+    mut q_robots: Query<(&Radius, &mut InterRobotProximity)>,
+    index: Res<RobotSpatialIndex>,
+    config: Res<Config>,
+    time: Res<Time>,
+) {
+    let multiplier = config.robot.inter_robot_safety_distance_multiplier.get();
+
+    // Robots further apart than the largest safety distance are neither within
+    // the safety distance of each other nor colliding
+    let max_distance = multiplier.max(2.0) * index.max_radius();
+    for ((position_a, a), (position_b, b)) in index.pairs_within(max_distance) {
+        let Ok([(radius_a, mut proximity_a), (radius_b, mut proximity_b)]) =
+            q_robots.get_many_mut([a.entity, b.entity])
+        else {
+            continue;
+        };
+        let distance = position_a.distance(*position_b);
+        let clearance = distance - radius_a.0 - radius_b.0;
+        proximity_a.record_clearance(clearance);
+        proximity_b.record_clearance(clearance);
+
+        if distance < multiplier * radius_a.0 {
+            proximity_a.within_safety_distance = true;
+        }
+        if distance < multiplier * radius_b.0 {
+            proximity_b.within_safety_distance = true;
+        }
+    }
+
+    for (_, mut proximity) in &mut q_robots {
+        if proximity.within_safety_distance {
+            proximity.time_within_safety_distance += time.delta_seconds_f64();
+            proximity.within_safety_distance = false;
+        }
+    }
+}
+
+/// Component recording how much a robot has deviated from its route to avoid
+/// each of the other robots, i.e. how much it has yielded to them.
+///
+/// Every timestep the robot moves further away from its route, the distance is
+/// attributed to the other robots within its safety distance, weighted by how
+/// far within the safety distance they are. Deviations while no other robot is
+/// within the safety distance, e.g. to avoid obstacles, are not attributed to
+/// any robot.
+#[derive(Component, Debug, Clone)]
+pub struct Yielding {
+    /// Priority of the robot when avoiding other robots
+    priority: f32,
+    /// Distance deviated from the route attributed to each other robot.
+    /// SI unit: m
+    yielded_to: HashMap<Entity, f32>,
+    /// Distance to the route in the previous timestep
+    previous_distance_to_route: Option<f32>,
+}
+
+impl Yielding {
+    /// Create a new `Yielding` for a robot with `priority`
+    pub fn new(priority: f32) -> Self {
+        Self {
+            priority,
+            yielded_to: HashMap::new(),
+            previous_distance_to_route: None,
+        }
+    }
+
+    /// Priority of the robot when avoiding other robots
+    #[inline]
+    pub const fn priority(&self) -> f32 {
+        self.priority
+    }
+
+    /// Distance deviated from the route attributed to each other robot
+    #[inline]
+    pub const fn yielded_to(&self) -> &HashMap<Entity, f32> {
+        &self.yielded_to
+    }
+
+    /// Record the current `distance_to_route`, and attribute the increase
+    /// since the previous timestep to the `interactions` with other robots,
+    /// given as the other robot and the weight of the interaction
+    fn record(
+        &mut self,
+        distance_to_route: f32,
+        interactions: impl IntoIterator<Item = (Entity, f32)>,
+    ) {
+        let deviation = self
+            .previous_distance_to_route
+            .map_or(0.0, |previous| (distance_to_route - previous).max(0.0));
+        self.previous_distance_to_route = Some(distance_to_route);
+        if deviation <= 0.0 {
+            return;
+        }
+
+        let interactions = interactions.into_iter().collect::<Vec<_>>();
+        let total_weight = interactions.iter().map(|(_, weight)| weight).sum::<f32>();
+        if total_weight <= 0.0 {
+            return;
+        }
+
+        for (other, weight) in interactions {
+            *self.yielded_to.entry(other).or_default() += deviation * weight / total_weight;
+        }
+    }
+}
+
+/// **Bevy** system that records how much every robot yields to the other
+/// robots, see [`Yielding`]. The safety distance is the same as the one used
+/// by the interrobot factors.
+fn track_yielding(
+    mut q_robots: Query<(Entity, &Transform, &Radius, &Mission, &mut Yielding)>,
+    index: Res<RobotSpatialIndex>,
+    config: Res<Config>,
+) {
+    let multiplier = config.robot.inter_robot_safety_distance_multiplier.get();
+
+    for (entity, transform, radius, mission, mut yielding) in &mut q_robots {
+        let position = transform.translation.xz();
+        let waypoints = mission
+            .waypoints()
+            .map(super::robot::StateVector::position)
+            .collect::<Vec<_>>();
+        let Some(distance_to_route) = distance_to_route(position, &route_lines(&waypoints)) else {
+            continue;
+        };
+
+        let safety_distance = multiplier * radius.0;
+        let interactions = index
+            .within(position, safety_distance)
+            .filter(|(_, other)| other.entity != entity)
+            .map(|(other_position, other)| {
+                (
+                    other.entity,
+                    1.0 - position.distance(*other_position) / safety_distance,
+                )
+            })
+            .filter(|(_, weight)| *weight > 0.0);
+        yielding.record(distance_to_route, interactions);
+    }
+}
+
+/// Trajectory quality metrics of a single robot
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct RobotMetrics {
+    /// Seconds from the robot started its mission until it finished it, or
+    /// until now if it has not finished yet
+    pub makespan: f64,
+    /// Distance travelled in meters
+    pub distance_travelled: f32,
+    /// Log dimensionless jerk of the velocity profile, see
+    /// [`log_dimensionless_jerk`]. `None` if too few velocities have been
+    /// recorded.
+    pub ldj: Option<f64>,
+    /// Deviation from the planned route, see [`perpendicular_deviation`].
+    /// `None` if no positions have been recorded.
+    pub perpendicular_deviation: Option<f32>,
+    /// Smallest clearance to any other robot within the safety distance,
+    /// negative if they collided
+    pub min_clearance: Option<f32>,
+    /// Seconds spent with another robot within the safety distance
+    pub time_within_safety_distance: f64,
+    #[serde(skip)]
+    started_at: f64,
+    #[serde(skip)]
+    finished_at: f64,
+}
+
+impl RobotMetrics {
+    /// Compute the metrics of a robot from its trackers and mission.
+    /// `now` is used as the finish time, if the mission has not finished yet.
+    pub fn compute(
+        positions: &PositionTracker,
+        velocities: &VelocityTracker,
+        mission: &Mission,
+        proximity: &InterRobotProximity,
+        now: f64,
+    ) -> Self {
+        let started_at = mission.started_at();
+        let finished_at = mission.finished_at().unwrap_or(now);
+
+        let positions = positions.positions().collect::<Vec<_>>();
+        let (velocities, timestamps): (Vec<_>, Vec<_>) = velocities
+            .measurements()
+            .map(|m| (Vec2::new(m.velocity.x, m.velocity.z), m.timestamp))
+            .unzip();
+        let waypoints = mission
+            .waypoints()
+            .map(super::robot::StateVector::position)
+            .collect::<Vec<_>>();
+
+        Self {
+            makespan: finished_at - started_at,
+            distance_travelled: distance_travelled(&positions),
+            ldj: log_dimensionless_jerk(&velocities, &timestamps),
+            perpendicular_deviation: perpendicular_deviation(&positions, &waypoints),
+            min_clearance: proximity.min_clearance(),
+            time_within_safety_distance: proximity.time_within_safety_distance(),
+            started_at,
+            finished_at,
+        }
+    }
+}
+
+/// Summary statistics of a metric across robots
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct Summary {
+    pub mean:   f64,
+    pub median: f64,
+    pub min:    f64,
+    pub max:    f64,
+    /// Sample standard deviation, `0.0` if there is only a single sample
+    pub stdev:  f64,
+}
+
+impl Summary {
+    /// Summarise `samples`. Returns `None` if there are no samples.
+    pub fn from_samples(samples: impl IntoIterator<Item = f64>) -> Option<Self> {
+        let mut samples = samples.into_iter().collect::<Vec<_>>();
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_by(f64::total_cmp);
+
+        #[allow(clippy::cast_precision_loss)]
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let mid = samples.len() / 2;
+        let median = if samples.len() % 2 == 0 {
+            (samples[mid - 1] + samples[mid]) / 2.0
+        } else {
+            samples[mid]
+        };
+        let stdev = if samples.len() < 2 {
+            0.0
+        } else {
+            (samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+        };
+
+        Some(Self {
+            mean,
+            median,
+            min: samples[0],
+            max: samples[samples.len() - 1],
+            stdev,
+        })
+    }
+}
+
+/// Trajectory quality metrics aggregated over all robots
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AggregateMetrics {
+    /// Number of robots the metrics are aggregated over
+    pub robots: usize,
+    /// Seconds from the first robot started its mission until the last robot
+    /// finished its mission
+    pub makespan: f64,
+    pub distance_travelled: Option<Summary>,
+    pub ldj: Option<Summary>,
+    pub perpendicular_deviation: Option<Summary>,
+    /// Smallest clearance between any two robots, negative if any collided
+    pub min_clearance: Option<f32>,
+    /// Seconds spent within the safety distance of another robot, summed over
+    /// all robots
+    pub time_within_safety_distance: f64,
+}
+
+impl AggregateMetrics {
+    /// Aggregate the metrics of every robot in `robots`
+    pub fn from_robots<'a>(robots: impl IntoIterator<Item = &'a RobotMetrics>) -> Self {
+        let robots = robots.into_iter().collect::<Vec<_>>();
+
+        let started_at = robots.iter().map(|r| r.started_at).min_by(f64::total_cmp);
+        let finished_at = robots.iter().map(|r| r.finished_at).max_by(f64::total_cmp);
+        let makespan = started_at
+            .zip(finished_at)
+            .map_or(0.0, |(started_at, finished_at)| finished_at - started_at);
+
+        Self {
+            robots: robots.len(),
+            makespan,
+            distance_travelled: Summary::from_samples(
+                robots.iter().map(|r| f64::from(r.distance_travelled)),
+            ),
+            ldj: Summary::from_samples(robots.iter().filter_map(|r| r.ldj)),
+            perpendicular_deviation: Summary::from_samples(
+                robots
+                    .iter()
+                    .filter_map(|r| r.perpendicular_deviation)
+                    .map(f64::from),
+            ),
+            min_clearance: robots
+                .iter()
+                .filter_map(|r| r.min_clearance)
+                .min_by(f32::total_cmp),
+            time_within_safety_distance: robots.iter().map(|r| r.time_within_safety_distance).sum(),
+        }
+    }
+}
+
+/// **Bevy** resource with the latest metrics of every robot spawned in the
+/// current simulation, including the robots that have since been despawned.
+#[derive(Resource, Debug, Default)]
+pub struct TrajectoryMetrics {
+    robots: HashMap<Entity, RobotMetrics>,
+}
+
+impl TrajectoryMetrics {
+    /// The latest metrics of every robot
+    pub fn robots(&self) -> impl Iterator<Item = (Entity, &RobotMetrics)> + '_ {
+        self.robots
+            .iter()
+            .map(|(entity, metrics)| (*entity, metrics))
+    }
+
+    /// The metrics aggregated over every robot
+    pub fn aggregate(&self) -> AggregateMetrics {
+        AggregateMetrics::from_robots(self.robots.values())
+    }
+}
+
+/// **Bevy** system that recomputes the metrics of every robot
+fn update_trajectory_metrics(
+    q_robots: Query<(
+        Entity,
+        &PositionTracker,
+        &VelocityTracker,
+        &Mission,
+        &InterRobotProximity,
+    )>,
+    mut trajectory_metrics: ResMut<TrajectoryMetrics>,
+    time_fixed: Res<Time<Fixed>>,
+) {
+    let now = time_fixed.elapsed_seconds_f64();
+    for (entity, positions, velocities, mission, proximity) in &q_robots {
+        let metrics = RobotMetrics::compute(positions, velocities, mission, proximity, now);
+        trajectory_metrics.robots.insert(entity, metrics);
+    }
+}
+
+fn clear_trajectory_metrics(mut trajectory_metrics: ResMut<TrajectoryMetrics>) {
+    trajectory_metrics.robots.clear();
+}
+
+/// Total length of the path through `positions`
+pub fn distance_travelled(positions: &[Vec2]) -> f32 {
+    positions
+        .windows(2)
+        .map(|pair| pair[0].distance(pair[1]))
+        .sum()
+}
+
+/// Log dimensionless jerk (LDJ) of a velocity profile, a measure of the
+/// smoothness of a trajectory. Closer to zero is smoother.
+///
+/// The jerk is estimated with central differences, and its square integrated
+/// with Simpson's rule, the same way as with `numpy.gradient` and
+/// `scipy.integrate.simpson` in `scripts/ldj.py`.
+///
+/// Returns `None` if there are fewer than 3 samples, the timestamps are not
+/// strictly increasing, or the robot never moved.
+pub fn log_dimensionless_jerk(velocities: &[Vec2], timestamps: &[f64]) -> Option<f64> {
+    assert_eq!(
+        velocities.len(),
+        timestamps.len(),
+        "every velocity has a timestamp"
+    );
+    if velocities.len() < 3 || timestamps.windows(2).any(|pair| pair[1] <= pair[0]) {
+        return None;
+    }
+
+    let t_start = timestamps[0];
+    let t_final = timestamps[timestamps.len() - 1];
+    #[allow(clippy::cast_precision_loss)]
+    let dt = (t_final - t_start) / (timestamps.len() - 1) as f64;
+
+    let vx = velocities
+        .iter()
+        .map(|v| f64::from(v.x))
+        .collect::<Vec<_>>();
+    let vy = velocities
+        .iter()
+        .map(|v| f64::from(v.y))
+        .collect::<Vec<_>>();
+    let jx = gradient(&gradient(&vx, dt), dt);
+    let jy = gradient(&gradient(&vy, dt), dt);
+    let squared_jerk = jx
+        .iter()
+        .zip(jy.iter())
+        .map(|(jx, jy)| jx.powi(2) + jy.powi(2))
+        .collect::<Vec<_>>();
+
+    let integral_squared_jerk = simpson(&squared_jerk, dt);
+    let v_max = vx
+        .iter()
+        .zip(vy.iter())
+        .map(|(vx, vy)| vx.hypot(*vy))
+        .fold(0.0, f64::max);
+    if v_max == 0.0 {
+        return None;
+    }
+
+    let ldj = -((t_final - t_start).powi(3) / v_max.powi(2) * integral_squared_jerk).ln();
+    ldj.is_finite().then_some(ldj)
+}
+
+/// Deviation of `positions` from the planned route through `waypoints`.
+///
+/// Every position is projected onto the closest of the lines through each
+/// pair of consecutive waypoints, and the deviation is the square root of the
+/// mean distance to the projections, as in
+/// `scripts/perpendicular-path-deviation.py`.
+///
+/// Returns `None` if there are no positions or fewer than 2 distinct
+/// waypoints.
+pub fn perpendicular_deviation(positions: &[Vec2], waypoints: &[Vec2]) -> Option<f32> {
+    let lines = route_lines(waypoints);
+    if positions.is_empty() || lines.is_empty() {
+        return None;
+    }
+
+    let error: f32 = positions
+        .iter()
+        .filter_map(|&position| distance_to_route(position, &lines))
+        .sum();
+
+    #[allow(clippy::cast_precision_loss)]
+    Some((error / positions.len() as f32).sqrt())
+}
+
+/// The lines through each pair of consecutive distinct `waypoints`, as an
+/// origin and a direction
+fn route_lines(waypoints: &[Vec2]) -> Vec<(Vec2, Vec2)> {
+    waypoints
+        .windows(2)
+        .filter(|pair| pair[0] != pair[1])
+        .map(|pair| (pair[0], (pair[1] - pair[0]).normalize()))
+        .collect()
+}
+
+/// Distance from `position` to its projection onto the closest of the `lines`
+/// of a route. Returns `None` if there are no lines.
+fn distance_to_route(position: Vec2, lines: &[(Vec2, Vec2)]) -> Option<f32> {
+    lines
+        .iter()
+        .map(|&(origin, direction)| {
+            let projection = origin + direction * (position - origin).dot(direction);
+            position.distance(projection)
+        })
+        .min_by(f32::total_cmp)
+}
+
+/// Numerical derivative of uniformly spaced samples, using central differences
+/// in the interior and one-sided differences at the boundaries.
+/// Equivalent to `numpy.gradient(samples, dt)`.
+fn gradient(samples: &[f64], dt: f64) -> Vec<f64> {
+    let n = samples.len();
+    if n < 2 {
+        return vec![0.0; n];
+    }
+
+    (0..n)
+        .map(|i| match i {
+            0 => (samples[1] - samples[0]) / dt,
+            i if i == n - 1 => (samples[n - 1] - samples[n - 2]) / dt,
+            i => (samples[i + 1] - samples[i - 1]) / (2.0 * dt),
+        })
+        .collect()
+}
+
+/// Integral of uniformly spaced samples using the composite Simpson's rule.
+/// For an even number of samples the last interval is integrated with the
+/// correction by Cartwright, equivalent to `scipy.integrate.simpson`.
+fn simpson(samples: &[f64], dt: f64) -> f64 {
+    let n = samples.len();
+    match n {
+        0 | 1 => 0.0,
+        2 => dt * (samples[0] + samples[1]) / 2.0,
+        n if n % 2 == 1 => {
+            let inner = samples[1..n - 1]
+                .iter()
+                .enumerate()
+                .map(|(i, y)| if i % 2 == 0 { 4.0 * y } else { 2.0 * y })
+                .sum::<f64>();
+            dt / 3.0 * (samples[0] + inner + samples[n - 1])
+        }
+        n => {
+            let last_interval = 5.0 * dt / 12.0 * samples[n - 1] + 2.0 * dt / 3.0 * samples[n - 2]
+                - dt / 12.0 * samples[n - 3];
+            simpson(&samples[..n - 1], dt) + last_interval
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn distance_travelled_along_a_polyline() {
+        let positions = [Vec2::ZERO, Vec2::new(3.0, 4.0), Vec2::new(3.0, 0.0)];
+        assert_relative_eq!(distance_travelled(&positions), 9.0);
+        assert_relative_eq!(distance_travelled(&positions[..1]), 0.0);
+    }
+
+    #[test]
+    fn simpson_integrates_polynomials() {
+        // integral of x^2 from 0 to 1, with an odd and an even number of samples
+        for n in [11, 12] {
+            #[allow(clippy::cast_precision_loss)]
+            let dt = 1.0 / (n - 1) as f64;
+            #[allow(clippy::cast_precision_loss)]
+            let samples = (0..n).map(|i| (i as f64 * dt).powi(2)).collect::<Vec<_>>();
+            assert_relative_eq!(simpson(&samples, dt), 1.0 / 3.0, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn constant_velocity_has_no_jerk() {
+        let velocities = vec![Vec2::new(1.0, 0.0); 10];
+        #[allow(clippy::cast_precision_loss)]
+        let timestamps = (0..10).map(|i| i as f64 * 0.1).collect::<Vec<_>>();
+        // zero jerk means an infinite ldj, which is not a meaningful value
+        assert_eq!(log_dimensionless_jerk(&velocities, &timestamps), None);
+
+        #[allow(clippy::cast_precision_loss)]
+        let accelerating = (0..10)
+            .map(|i| Vec2::new(1.0 + (i as f32 * 0.3).sin(), 0.0))
+            .collect::<Vec<_>>();
+        let ldj = log_dimensionless_jerk(&accelerating, &timestamps).unwrap();
+        assert!(ldj < 0.0);
+    }
+
+    #[test]
+    fn perpendicular_deviation_from_route() {
+        let waypoints = [Vec2::ZERO, Vec2::new(10.0, 0.0), Vec2::new(10.0, 10.0)];
+        let on_route = [Vec2::new(2.0, 0.0), Vec2::new(10.0, 5.0)];
+        assert_relative_eq!(perpendicular_deviation(&on_route, &waypoints).unwrap(), 0.0);
+
+        let off_route = [Vec2::new(2.0, 1.0), Vec2::new(5.0, 4.0)];
+        // distances to the closest line are 1 and 4
+        assert_relative_eq!(
+            perpendicular_deviation(&off_route, &waypoints).unwrap(),
+            2.5f32.sqrt()
+        );
+        assert_eq!(perpendicular_deviation(&[], &waypoints), None);
+    }
+
+    #[test]
+    fn yielding_is_attributed_by_weight() {
+        let (a, b) = (Entity::from_raw(1), Entity::from_raw(2));
+        let mut yielding = Yielding::new(1.0);
+        yielding.record(0.0, []);
+        // moving towards the route is not yielding
+        yielding.record(1.0, [(a, 0.5), (b, 1.5)]);
+        yielding.record(0.5, [(a, 1.0)]);
+        yielding.record(1.5, [(a, 0.5), (b, 1.5)]);
+        // deviating without any other robot nearby is not attributed
+        yielding.record(2.5, []);
+
+        assert_relative_eq!(yielding.yielded_to()[&a], 0.25 + 0.25);
+        assert_relative_eq!(yielding.yielded_to()[&b], 0.75 + 0.75);
+    }
+
+    #[test]
+    fn summary_statistics() {
+        let summary = Summary::from_samples([4.0, 1.0, 3.0, 2.0]).unwrap();
+        assert_relative_eq!(summary.mean, 2.5);
+        assert_relative_eq!(summary.median, 2.5);
+        assert_relative_eq!(summary.min, 1.0);
+        assert_relative_eq!(summary.max, 4.0);
+        assert_relative_eq!(summary.stdev, (5.0f64 / 3.0).sqrt());
+        assert_eq!(Summary::from_samples([]), None);
+    }
+}
@@ -1,12 +1,18 @@
 pub mod collisions;
+pub mod communication;
+pub mod kinematics;
+pub mod metrics;
 pub mod mission;
 pub mod robot;
+pub mod spatial_index;
 pub mod spawner;
 pub mod tracking;
+pub mod user_factors;
 mod visualiser;
 
 use bevy::prelude::*;
 pub use robot::{RobotConnections, RobotId};
+pub use user_factors::{RegisterUserFactor, UserFactorDefinition};
 pub use visualiser::{
     factorgraphs::VariableVisualiser, waypoints::WaypointVisualiser, RobotTracker,
 };
@@ -20,10 +26,17 @@ impl Plugin for PlannerPlugin {
         app.add_plugins((
             RobotPlugin,
             RobotSpawnerPlugin,
-            VisualiserPlugin,
+            spatial_index::SpatialIndexPlugin,
             collisions::RobotCollisionsPlugin,
+            communication::CommunicationPlugin,
             tracking::TrackingPlugin,
+            metrics::MetricsPlugin,
             mission::MissionPlugin,
         ));
+
+        // Nothing to visualise when running headless
+        if app.is_plugin_added::<bevy::window::WindowPlugin>() {
+            app.add_plugins(VisualiserPlugin);
+        }
     }
 }
@@ -13,32 +13,38 @@ use bevy::{
 use bevy_prng::WyRand;
 use bevy_rand::{component::EntropyComponent, prelude::GlobalEntropy};
 use gbp_config::{
-    formation::{CheckIntersectionWith, IntersectionDistance, PlanningStrategy, ReachedWhen},
-    Config,
+    formation::{
+        CheckIntersectionWith, IntersectionDistance, MotionModel, PlanningStrategy, ReachedWhen,
+    },
+    Config, ObstacleFactorKind, RobotProfile,
 };
-use gbp_global_planner::PathfindingTask;
+use gbp_core::iteration::Participant;
+use gbp_global_planner::{Bounds, PathfindingTask, SharedPlanners};
 use gbp_linalg::prelude::*;
 use itertools::Itertools;
-use ndarray::{array, concatenate, s, Axis};
+use ndarray::{array, s};
 use rand::Rng;
+use typed_floats::StrictlyPositiveFinite;
 
 use super::{
     collisions::resources::{RobotEnvironmentCollisions, RobotRobotCollisions},
+    communication::{ChannelTransport, CommunicationChannels},
+    spatial_index::{rebuild_robot_spatial_index, RobotSpatialIndex},
     spawner::RobotClickedOn,
+    user_factors::{attach_user_factors, UserFactorRegistry},
 };
 use crate::{
     bevy_utils::run_conditions::time::virtual_time_is_paused,
     export::events::TakeSnapshotOfRobot,
     factorgraph::{
-        factor::{ExternalVariableId, FactorNode},
+        factor::{collider_sdf::ColliderSdf, ExternalVariableId, FactorNode, Priorities},
         factorgraph::{FactorGraph, NodeIndex, VariableIndex},
         id::{FactorId, VariableId},
         message::{FactorToVariableMessage, VariableToFactorMessage},
         variable::VariableNode,
-        DOFS,
     },
     pause_play::PausePlay,
-    simulation_loader::{LoadSimulation, ReloadSimulation, SdfImage},
+    simulation_loader::{DistanceField, LoadSimulation, ReloadSimulation, SdfImage},
 };
 
 pub type RobotId = Entity;
@@ -52,6 +58,8 @@ impl Plugin for RobotPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<GbpIterationSchedule>()
             .init_resource::<RobotNumberGenerator>()
+            .init_resource::<UserFactorRegistry>()
+            .init_resource::<SharedPlanners>()
             .insert_state(ManualModeState::Disabled)
             .add_event::<RobotSpawned>()
             .add_event::<RobotDespawned>()
@@ -86,7 +94,8 @@ impl Plugin for RobotPlugin {
                 FixedUpdate,
                 // Update,
                 (
-                    update_robot_neighbours,
+                    attach_user_factors,
+                    update_robot_neighbours.after(rebuild_robot_spatial_index),
                     delete_interrobot_factors,
                     create_interrobot_factors,
                     update_failed_comms,
@@ -97,7 +106,8 @@ impl Plugin for RobotPlugin {
                     // iterate_gbp,
                     // update_prior_of_horizon_state_v2,
                     update_prior_of_horizon_state,
-                    update_prior_of_current_state_v3,
+                    // update_prior_of_current_state_v3,
+                    super::kinematics::follow_planned_trajectory,
                     iterate_gbp_v2,
                     // update_prior_of_current_state,
                     // despawn_robots,
@@ -323,6 +333,40 @@ impl CreateVariableTimesteps for GbpplannerVariableTimesteps {
 #[derive(Component, Debug, Deref, DerefMut)]
 pub struct Radius(pub f32);
 
+/// Component with the robot profile a robot was spawned from, giving its
+/// speed, planning horizon, communication radius and factor sigmas
+#[derive(Component, Debug, Clone, Deref, DerefMut)]
+pub struct Profile(pub RobotProfile);
+
+/// Component with the hard limits on the speed and acceleration of a robot,
+/// respected by its velocity and acceleration limit factors.
+/// `None` if the robot is not limited
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct MotionLimits {
+    /// SI unit: m/s
+    pub max_speed: Option<f32>,
+    /// SI unit: m/s^2
+    pub max_acceleration: Option<f32>,
+}
+
+impl MotionLimits {
+    /// The limits of a robot moving with `motion_model`. The limits of the
+    /// motion model take precedence over the limits of the robot profile
+    #[must_use]
+    pub fn new(profile: &RobotProfile, motion_model: &MotionModel) -> Self {
+        Self {
+            max_speed: motion_model
+                .max_speed()
+                .or(profile.max_speed)
+                .map(StrictlyPositiveFinite::get),
+            max_acceleration: motion_model
+                .max_acceleration()
+                .or(profile.max_acceleration)
+                .map(StrictlyPositiveFinite::get),
+        }
+    }
+}
+
 /// Represents a robotic route consisting of several waypoints that define
 /// positions and velocities the robot should achieve as it progresses along the
 /// path.
@@ -559,17 +603,36 @@ impl FromWorld for GbpIterationSchedule {
     }
 }
 
+/// A robot waiting for a path from prioritised planning
+struct PrioritisedRequest {
+    robot:    Entity,
+    start:    Vec2,
+    end:      Vec2,
+    priority: f32,
+    speed:    f32,
+}
+
+#[allow(clippy::too_many_arguments)]
 fn progress_missions(
     mut commands: Commands,
-    mut q: Query<(Entity, &mut Mission, &PlanningStrategy)>,
+    mut q: Query<(Entity, &mut Mission, &PlanningStrategy, &Profile)>,
     mut pathfinders: Query<(Entity, &mut EntropyComponent<WyRand>), Without<PathfindingTask>>,
     mut tasks: Query<&mut PathfindingTask>,
     mut factorgraphs: Query<(&mut FactorGraph, &VariableTimesteps)>,
     config: Res<Config>,
     time: Res<Time>,
     colliders: Res<gbp_global_planner::Colliders>,
+    env: Res<gbp_environment::Environment>,
+    mut planners: ResMut<SharedPlanners>,
 ) {
-    for (robot_entity, mut mission, plannning_strategy) in &mut q {
+    if colliders.is_changed() || env.is_changed() {
+        // Grids and roadmaps built from the old colliders are stale
+        planners.clear();
+    }
+    let bounds = Bounds::centered(env.dimensions());
+    let mut prioritised = Vec::<PrioritisedRequest>::new();
+
+    for (robot_entity, mut mission, plannning_strategy, profile) in &mut q {
         match (mission.state, plannning_strategy) {
             (MissionState::Idle { .. }, PlanningStrategy::OnlyLocal) => {
                 // no need to do anything
@@ -579,7 +642,11 @@ fn progress_missions(
                 MissionState::Idle {
                     waiting_for_waypoints: false,
                 },
-                PlanningStrategy::RrtStar,
+                strategy @ (PlanningStrategy::RrtStar
+                | PlanningStrategy::AStar
+                | PlanningStrategy::ThetaStar
+                | PlanningStrategy::Prm
+                | PlanningStrategy::Prioritised),
             ) => {
                 // disable tracking factors
                 // factorgraphs.iter_mut().for_each(|(mut factorgraph, _)| {
@@ -612,15 +679,27 @@ fn progress_missions(
                     );
 
                     // dbg!(&colliders);
-                    gbp_global_planner::rrtstar::spawn_pathfinding_task(
-                        &mut commands,
-                        start,
-                        end,
-                        config.rrt.clone(),
-                        colliders.clone(),
-                        pathfinder,
-                        Some(Box::new(prng.clone())),
-                    );
+                    if matches!(strategy, PlanningStrategy::Prioritised) {
+                        // Planned below, once every robot ready to plan is known
+                        prioritised.push(PrioritisedRequest {
+                            robot: pathfinder,
+                            start,
+                            end,
+                            priority: profile.priority.get(),
+                            speed: profile.target_speed.get(),
+                        });
+                    } else if let Some(planner) =
+                        planners.planner(*strategy, &config, &colliders, bounds)
+                    {
+                        gbp_global_planner::spawn_pathfinding_task(
+                            &mut commands,
+                            start,
+                            end,
+                            planner,
+                            pathfinder,
+                            Some(Box::new(prng.clone())),
+                        );
+                    }
                 }
 
                 mission.state = MissionState::Idle {
@@ -635,7 +714,11 @@ fn progress_missions(
                 MissionState::Idle {
                     waiting_for_waypoints: true,
                 },
-                PlanningStrategy::RrtStar,
+                PlanningStrategy::RrtStar
+                | PlanningStrategy::AStar
+                | PlanningStrategy::ThetaStar
+                | PlanningStrategy::Prm
+                | PlanningStrategy::Prioritised,
             ) => {
                 // check if rrt job finished, and the advance to active state
                 // TODO:
@@ -660,7 +743,7 @@ fn progress_missions(
                                             dir = Vec2::ZERO;
                                         }
 
-                                        let vel = config.robot.target_speed.get() * dir;
+                                        let vel = profile.target_speed.get() * dir;
                                         Vec4::new(from.x, from.y, vel.x, vel.y)
                                     })
                                     .map_into()
@@ -743,9 +826,8 @@ fn progress_missions(
                                     // part be the normalized direction times max_speed
                                     // let next = next.length() * 0.8 * dir_normalized;
                                     let next = {
-                                        let l = (config.robot.target_speed
-                                            * config.robot.planning_horizon)
-                                            .get();
+                                        let l =
+                                            (profile.target_speed * profile.planning_horizon).get();
                                         let max = dir.length() * 0.9;
                                         let s = if l < max { l } else { max };
                                         start + s * dir_normalized
@@ -755,11 +837,12 @@ fn progress_missions(
                                         .map(|i| i as f32 / n as f32)
                                         .map(|r| {
                                             let pos = start.xy().lerp(next.xy(), r);
-                                            let vel =
-                                                config.robot.target_speed.get() * dir_normalized;
-                                            Vec4::new(pos.x, pos.y, vel.x, vel.y)
+                                            let vel = profile.target_speed.get() * dir_normalized;
+                                            config.gbp.state_layout.state_from_planar(
+                                                pos.as_dvec2().to_array(),
+                                                vel.as_dvec2().to_array(),
+                                            )
                                         })
-                                        .map(|it| it.as_dvec4().to_array())
                                         .collect_vec();
                                     // means
                                     //    }
@@ -809,6 +892,52 @@ fn progress_missions(
             (MissionState::Completed, _) => {}
         }
     }
+
+    if prioritised.is_empty() {
+        return;
+    }
+
+    // Robots with a higher priority plan first, and get the shortest routes
+    prioritised.sort_by(|a, b| b.priority.total_cmp(&a.priority));
+    let now = time.elapsed_seconds();
+    let cell_size = config.global_planner.grid.cell_size.get();
+    let grid = planners.grid(&config, &colliders, bounds);
+    let (planner, reservations) = planners.prioritised(&config, &colliders, bounds);
+    reservations.prune(now);
+    reservations.retain_owners(|robot| q.contains(robot));
+
+    for request in prioritised {
+        let step = cell_size / request.speed;
+        match planner.plan(
+            reservations,
+            request.robot,
+            request.start,
+            request.end,
+            now,
+            step,
+        ) {
+            Ok(path) => {
+                gbp_global_planner::insert_planned_path(&mut commands, Ok(path), request.robot);
+            }
+            Err(err) => {
+                warn!(
+                    "prioritised planning failed for robot {:?}: {:?}, planning without the other \
+                     robots",
+                    request.robot, err
+                );
+                gbp_global_planner::spawn_pathfinding_task(
+                    &mut commands,
+                    request.start,
+                    request.end,
+                    Arc::new(gbp_global_planner::grid::GridSearch::a_star(Arc::clone(
+                        &grid,
+                    ))),
+                    request.robot,
+                    None,
+                );
+            }
+        }
+    }
 }
 
 #[derive(Debug, Component)]
@@ -1036,6 +1165,10 @@ pub struct RobotBundle {
     /// circle that fully encompass the shape of the robot. **constraint**:
     /// > 0.0
     pub radius: Radius,
+    /// Hard limits on the speed and acceleration of the robot
+    pub limits: MotionLimits,
+    /// The robot profile the robot was spawned from
+    pub profile: Profile,
 
     pub ball: Ball,
     pub antenna: RadioAntenna,
@@ -1141,7 +1274,11 @@ impl RobotBundle {
         config: &Config,
         env_config: &gbp_environment::Environment,
         radius: f32,
+        limits: MotionLimits,
+        profile: RobotProfile,
         sdf: &SdfImage,
+        distance_field: &Arc<DistanceField>,
+        collider_sdf: Option<&Arc<ColliderSdf>>,
         started_at: f64,
         waypoints: min_len_vec::TwoOrMore<StateVector>,
         // use_tracking: bool,
@@ -1165,10 +1302,13 @@ impl RobotBundle {
         let horizon = start
             + f32::min(
                 start2goal.length(),
-                (config.robot.planning_horizon * config.robot.target_speed).get(),
+                (profile.planning_horizon * profile.target_speed).get(),
             ) * start2goal.normalize();
 
+        let layout = config.gbp.state_layout;
         let mut factorgraph = FactorGraph::new(robot_id);
+        factorgraph.set_damping(config.gbp.damping.get());
+        factorgraph.set_convergence(config.gbp.convergence);
         let last_variable_timestep = *variable_timesteps
             .last()
             .expect("Know that variable_timesteps has at least one element");
@@ -1192,7 +1332,11 @@ impl RobotBundle {
                 }
                 // PlanningStrategy::RrtStar => Vec4::ZERO,
                 // FIXME: why unwind like a worm?
-                PlanningStrategy::RrtStar => start,
+                PlanningStrategy::RrtStar
+                | PlanningStrategy::AStar
+                | PlanningStrategy::ThetaStar
+                | PlanningStrategy::Prm
+                | PlanningStrategy::Prioritised => start,
             };
 
             let sigma = if i == 0 || i == n_variables - 1 {
@@ -1207,22 +1351,21 @@ impl RobotBundle {
                 Float::INFINITY
             };
 
-            let precision_matrix = Matrix::<Float>::from_diag_elem(DOFS, sigma);
+            let precision_matrix = Matrix::<Float>::from_diag_elem(layout.dofs(), sigma);
 
-            let mean = array![
-                Float::from(mean.x),
-                Float::from(mean.y),
+            // The waypoints are in the plane, with the velocity in the last two elements
+            let mean = layout.state_from_planar([Float::from(mean.x), Float::from(mean.y)], [
                 Float::from(mean.z),
-                Float::from(mean.w)
-            ];
+                Float::from(mean.w),
+            ]);
             init_variable_means.push(mean.slice(s![..2]).to_owned());
 
-            let variable = VariableNode::new(factorgraph.id(), mean, precision_matrix, DOFS);
+            let variable = VariableNode::new(factorgraph.id(), mean, precision_matrix, layout);
             let variable_index = factorgraph.add_variable(variable);
             variable_node_indices.push(variable_index);
         }
 
-        let t0 = radius / 2.0 / config.robot.target_speed.get();
+        let t0 = radius / 2.0 / profile.target_speed.get();
 
         // Create Dynamic factors between variables
         for i in 0..variable_timesteps.len() - 1 {
@@ -1231,11 +1374,12 @@ impl RobotBundle {
             // let delta_t = config.simulation.t0.get()
             let delta_t = t0 * (variable_timesteps[i + 1] - variable_timesteps[i]) as f32;
 
-            let measurement = Vector::<Float>::zeros(DOFS);
+            let measurement = Vector::<Float>::zeros(layout.dofs());
 
             let dynamic_factor = FactorNode::new_dynamic_factor(
                 factorgraph.id(),
-                Float::from(config.gbp.sigma_factor_dynamics),
+                layout,
+                Float::from(profile.sigmas.dynamics),
                 measurement,
                 Float::from(delta_t),
                 config.gbp.factors_enabled.dynamic,
@@ -1263,18 +1407,48 @@ impl RobotBundle {
             height: tile_size * nrows as f64,
         };
 
+        if config.gbp.obstacle_factor == ObstacleFactorKind::Colliders && collider_sdf.is_none() {
+            warn!("no colliders to create obstacle factors from, falling back to the sdf image");
+        }
+
         // Create Obstacle factors for all variables excluding start and
         // horizon state
         #[allow(clippy::needless_range_loop)]
         for i in 1..variable_timesteps.len() - 1 {
-            let obstacle_factor = FactorNode::new_obstacle_factor(
-                factorgraph.id(),
-                Float::from(config.gbp.sigma_factor_obstacle),
-                array![0.0],
-                sdf.clone(),
-                world_size,
-                config.gbp.factors_enabled.obstacle,
-            );
+            let obstacle_factor = match (config.gbp.obstacle_factor, collider_sdf) {
+                (ObstacleFactorKind::Colliders, Some(collider_sdf)) => {
+                    FactorNode::new_collider_obstacle_factor(
+                        factorgraph.id(),
+                        layout,
+                        Float::from(profile.sigmas.obstacle),
+                        array![0.0],
+                        Arc::clone(collider_sdf),
+                        config.gbp.factors_enabled.obstacle,
+                    )
+                }
+                (ObstacleFactorKind::DistanceField, _) => {
+                    FactorNode::new_distance_field_obstacle_factor(
+                        factorgraph.id(),
+                        layout,
+                        Float::from(profile.sigmas.obstacle),
+                        array![0.0],
+                        Arc::clone(distance_field),
+                        world_size,
+                        config.gbp.sdf_sampling,
+                        config.gbp.factors_enabled.obstacle,
+                    )
+                }
+                _ => FactorNode::new_obstacle_factor(
+                    factorgraph.id(),
+                    layout,
+                    Float::from(profile.sigmas.obstacle),
+                    array![0.0],
+                    sdf.clone(),
+                    world_size,
+                    config.gbp.sdf_sampling,
+                    config.gbp.factors_enabled.obstacle,
+                ),
+            };
 
             let factor_node_index = factorgraph.add_factor(obstacle_factor);
             let factor_id = FactorId::new(factorgraph.id(), factor_node_index);
@@ -1284,6 +1458,87 @@ impl RobotBundle {
             );
         }
 
+        // Create Dynamic Obstacle factors for all variables excluding start and
+        // horizon state, if the environment has any dynamic obstacles to avoid.
+        // The safety distance is measured from the bounding circle of the
+        // obstacle, so only half of the interrobot safety distance is used
+        if !env_config.dynamic_obstacles.is_empty() {
+            let dynamic_obstacle_safety_distance = Float::from(
+                radius * config.robot.inter_robot_safety_distance_multiplier.get() / 2.0,
+            );
+            #[allow(clippy::needless_range_loop, clippy::cast_precision_loss)]
+            for i in 1..variable_timesteps.len() - 1 {
+                let dynamic_obstacle_factor = FactorNode::new_dynamic_obstacle_factor(
+                    factorgraph.id(),
+                    layout,
+                    Float::from(profile.sigmas.dynamic_obstacle),
+                    array![0.0],
+                    t0 * variable_timesteps[i] as f32,
+                    dynamic_obstacle_safety_distance,
+                    config.gbp.factors_enabled.dynamic_obstacle,
+                );
+
+                let factor_node_index = factorgraph.add_factor(dynamic_obstacle_factor);
+                let factor_id = FactorId::new(factorgraph.id(), factor_node_index);
+                let _ = factorgraph.add_internal_edge(
+                    VariableId::new(factorgraph.id(), variable_node_indices[i]),
+                    factor_id,
+                );
+            }
+        }
+
+        // Create Velocity limit factors for all variables excluding start and
+        // horizon state
+        if let Some(max_speed) = limits.max_speed {
+            #[allow(clippy::needless_range_loop)]
+            for i in 1..variable_timesteps.len() - 1 {
+                let velocity_factor = FactorNode::new_velocity_factor(
+                    factorgraph.id(),
+                    layout,
+                    Float::from(profile.sigmas.velocity),
+                    array![0.0],
+                    Float::from(max_speed),
+                    config.gbp.factors_enabled.velocity,
+                );
+
+                let factor_node_index = factorgraph.add_factor(velocity_factor);
+                let factor_id = FactorId::new(factorgraph.id(), factor_node_index);
+                let _ = factorgraph.add_internal_edge(
+                    VariableId::new(factorgraph.id(), variable_node_indices[i]),
+                    factor_id,
+                );
+            }
+        }
+
+        // Create Acceleration limit factors between variables, like the dynamic factors
+        if let Some(max_acceleration) = limits.max_acceleration {
+            for i in 0..variable_timesteps.len() - 1 {
+                #[allow(clippy::cast_precision_loss)]
+                let delta_t = t0 * (variable_timesteps[i + 1] - variable_timesteps[i]) as f32;
+
+                let acceleration_factor = FactorNode::new_acceleration_factor(
+                    factorgraph.id(),
+                    layout,
+                    Float::from(profile.sigmas.acceleration),
+                    array![0.0],
+                    Float::from(max_acceleration),
+                    Float::from(delta_t),
+                    config.gbp.factors_enabled.acceleration,
+                );
+
+                let factor_node_index = factorgraph.add_factor(acceleration_factor);
+                let factor_id = FactorId::new(factorgraph.id(), factor_node_index);
+                let _ = factorgraph.add_internal_edge(
+                    VariableId::new(factorgraph.id(), variable_node_indices[i + 1]),
+                    factor_id,
+                );
+                let _ = factorgraph.add_internal_edge(
+                    VariableId::new(factorgraph.id(), variable_node_indices[i]),
+                    factor_id,
+                );
+            }
+        }
+
         let mission = match planning_strategy {
             PlanningStrategy::OnlyLocal => Mission::local(
                 waypoints.try_into().unwrap(),
@@ -1291,7 +1546,11 @@ impl RobotBundle {
                 finished_when_intersects,
                 waypoint_reached_when_intersects,
             ),
-            PlanningStrategy::RrtStar => Mission::global(
+            PlanningStrategy::RrtStar
+            | PlanningStrategy::AStar
+            | PlanningStrategy::ThetaStar
+            | PlanningStrategy::Prm
+            | PlanningStrategy::Prioritised => Mission::global(
                 waypoints.try_into().unwrap(),
                 started_at,
                 finished_when_intersects,
@@ -1304,8 +1563,10 @@ impl RobotBundle {
         // if config.gbp.factors_enabled.tracking {
         for i in 1..variable_timesteps.len() - 1 {
             // for var_ix in &variable_node_indices[1..] {
-            let init_linearisation_point =
-                concatenate![Axis(0), init_variable_means[i].clone(), array![0.0, 0.0]];
+            let init_linearisation_point = layout
+                .state_from_planar([init_variable_means[i][0], init_variable_means[i][1]], [
+                    0.0, 0.0,
+                ]);
             // println!("init_linearisation_point: {:?}", init_linearisation_point);
             let initial_route = mission.active_route().unwrap();
             let waypoints = initial_route
@@ -1315,7 +1576,8 @@ impl RobotBundle {
                 .collect::<Vec<Vec2>>();
             let tracking_factor = FactorNode::new_tracking_factor(
                 factorgraph.id(),
-                Float::from(config.gbp.sigma_factor_tracking),
+                layout,
+                Float::from(profile.sigmas.tracking),
                 array![0.0],
                 init_linearisation_point,
                 // config.gbp.tracking_smoothing as f64,
@@ -1337,8 +1599,10 @@ impl RobotBundle {
         Self {
             factorgraph,
             radius: Radius(radius),
+            limits,
+            profile: Profile(profile),
             ball: Ball(parry2d::shape::Ball::new(radius)),
-            antenna: RadioAntenna::new(config.robot.communication.radius.get(), true),
+            antenna: RadioAntenna::new(profile.communication_radius.get(), true),
             connections: RobotConnections::new(),
             // route,
             // initial_state,
@@ -1356,29 +1620,29 @@ impl RobotBundle {
 }
 
 #[derive(Component, Debug)]
-pub struct VariableTimesteps(Vec<u32>);
+pub struct VariableTimesteps(pub(super) Vec<u32>);
+
+impl VariableTimesteps {
+    /// Timestep of every variable, from the current state to the horizon state
+    #[must_use]
+    pub fn as_slice(&self) -> &[u32] {
+        &self.0
+    }
+}
 
 /// Called `Simulator::calculateRobotNeighbours` in **gbpplanner**
+/// The communication radius is the one of the antenna of each robot, as robots
+/// of different robot profiles can have different communication radii
 fn update_robot_neighbours(
-    robots: Query<(Entity, &Transform), With<RobotConnections>>,
-    mut query: Query<(Entity, &Transform, &mut RobotConnections)>,
-    config: Res<Config>,
+    index: Res<RobotSpatialIndex>,
+    mut query: Query<(Entity, &Transform, &RadioAntenna, &mut RobotConnections)>,
 ) {
-    // TODO: use kdtree to speed up, and to have something in the report
-    for (robot_id, transform, mut robotstate) in &mut query {
-        robotstate.robots_within_comms_range = robots
-            .iter()
-            .filter_map(|(other_robot_id, other_transform)| {
-                if other_robot_id == robot_id
-                    || config.robot.communication.radius.get()
-                        < transform.translation.distance(other_transform.translation)
-                {
-                    // Do not compute the distance to self
-                    None
-                } else {
-                    Some(other_robot_id)
-                }
-            })
+    for (robot_id, transform, antenna, mut robotstate) in &mut query {
+        robotstate.robots_within_comms_range = index
+            .within(transform.translation.xz(), antenna.radius)
+            .map(|(_, other)| other.entity)
+            // Do not connect to self
+            .filter(|&other| other != robot_id)
             .collect();
     }
 }
@@ -1438,8 +1702,26 @@ fn delete_interrobot_factors(mut query: Query<(Entity, &mut FactorGraph, &mut Ro
     }
 }
 
+/// What a robot needs to know about another robot, to create interrobot
+/// factors to the variables of the other robot
+struct OtherRobot {
+    /// Every variable, from the current state to the horizon state
+    variable_indices: Vec<NodeIndex>,
+    t0: f32,
+    variable_timesteps: Vec<u32>,
+    priority: StrictlyPositiveFinite<f32>,
+}
+
 fn create_interrobot_factors(
-    mut query: Query<(Entity, &mut FactorGraph, &mut RobotConnections, &Radius)>,
+    mut query: Query<(
+        Entity,
+        &mut FactorGraph,
+        &mut RobotConnections,
+        &Radius,
+        &Profile,
+        &T0,
+        &VariableTimesteps,
+    )>,
     config: Res<Config>,
     mut robot_number_gen: ResMut<RobotNumberGenerator>,
 ) {
@@ -1448,7 +1730,7 @@ fn create_interrobot_factors(
     // {a -> [b, c, d], b -> [a, c], c -> [a, b], d -> [c]}
     let new_connections_to_establish: HashMap<RobotId, Vec<RobotId>> = query
         .iter()
-        .map(|(entity, _, robotstate, _)| {
+        .map(|(entity, _, robotstate, ..)| {
             let new_connections = robotstate
                 .robots_within_comms_range
                 .difference(&robotstate.robots_connected_with)
@@ -1459,72 +1741,71 @@ fn create_interrobot_factors(
         })
         .collect();
 
-    // let number_of_variables = variable_timesteps.len();
-
-    // PERF(kpbaks): store a slice instead of a Vec<NodeIndex>
-    let variable_indices_of_each_factorgraph: HashMap<RobotId, Vec<NodeIndex>> = query
+    // Robots of different robot profiles can have a different number of
+    // variables, spread over different planning horizons, so the timesteps of
+    // every factorgraph are needed to pair up their variables
+    let other_robots: HashMap<RobotId, OtherRobot> = query
         .iter()
-        .map(|(robot_id, factorgraph, _, _)| {
-            let variable_indices = factorgraph
-                .variable_indices_ordered_by_creation()
-                .skip(1) // skip current variable
-                .collect::<Vec<_>>();
-
-            let num_variables = factorgraph.node_count().variables;
-            debug_assert_eq!(num_variables - 1, variable_indices.len());
-            (robot_id, variable_indices)
-        })
+        .map(
+            |(robot_id, factorgraph, _, _, profile, t0, variable_timesteps)| {
+                let variable_indices = factorgraph
+                    .variable_indices_ordered_by_creation()
+                    .collect::<Vec<_>>();
+                debug_assert_eq!(variable_indices.len(), variable_timesteps.as_slice().len());
+                (robot_id, OtherRobot {
+                    variable_indices,
+                    t0: t0.0,
+                    variable_timesteps: variable_timesteps.as_slice().to_vec(),
+                    priority: profile.priority,
+                })
+            },
+        )
         .collect();
 
-    // for (robot_id, node_indices) in &variable_indices_of_each_factorgraph {
-    //     println!(
-    //         "robot: {:?}, #node_indices = {}",
-    //         robot_id,
-    //         node_indices.len()
-    //     );
-    // }
-    // debug_assert!(variable_indices_of_each_factorgraph.values().all_equal());
-
     let mut external_edges_to_add = Vec::new();
 
-    for (robot_id, mut factorgraph, mut robotstate, radius) in &mut query {
-        let num_variables = factorgraph.node_count().variables;
+    for (robot_id, mut factorgraph, mut robotstate, radius, profile, t0, variable_timesteps) in
+        &mut query
+    {
         for other_robot_id in new_connections_to_establish
             .get(&robot_id)
             .expect("the key is in the map")
         {
-            let other_variable_indices = variable_indices_of_each_factorgraph
+            let other_robot = other_robots
                 .get(other_robot_id)
                 .expect("the key is in the map");
+            let priorities = Priorities::new(
+                Float::from(profile.priority.get())
+                    .try_into()
+                    .expect("priority > 0.0"),
+                Float::from(other_robot.priority.get())
+                    .try_into()
+                    .expect("priority > 0.0"),
+            );
 
-            for i in 1..num_variables {
-                let initial_measurement = Vector::<Float>::zeros(DOFS);
-                // let eps = 0.2 * config.robot.radius.get();
-                // let eps = 0.2 * radius.0;
-                // let safety_radius = 2.0f32.mul_add(config.robot.radius.get(), eps);
-                // let safety_radius = 2.0f32.mul_add(radius.0, eps);
-                // TODO: should it be i - 1 or i?
+            for (i, j) in paired_variables(
+                t0.0,
+                variable_timesteps.as_slice(),
+                other_robot.t0,
+                &other_robot.variable_timesteps,
+            ) {
+                let initial_measurement = Vector::<Float>::zeros(config.gbp.state_layout.dofs());
                 let external_variable_id = ExternalVariableId::new(
                     *other_robot_id,
-                    VariableIndex(other_variable_indices[i - 1]),
+                    VariableIndex(other_robot.variable_indices[j]),
                 );
-                // let connection =
-                //     InterRobotFactorConnection::new(*other_robot_id, other_variable_indices[i
-                // - 1]);
-                //
                 let interrobot_factor = FactorNode::new_interrobot_factor(
                     factorgraph.id(),
-                    Float::from(config.gbp.sigma_factor_interrobot),
+                    config.gbp.state_layout,
+                    Float::from(profile.sigmas.interrobot),
                     initial_measurement,
                     Float::from(radius.0).try_into().expect("> 0.0"),
                     Float::from(config.robot.inter_robot_safety_distance_multiplier.get())
                         .try_into()
                         .expect("> 0.0"),
-                    // Float::from(safety_radius)
-                    //     .try_into()
-                    //     .expect("safe radius is positive and finite"),
                     external_variable_id,
                     robot_number_gen.next(),
+                    priorities,
                     config.gbp.factors_enabled.interrobot,
                 );
 
@@ -1537,7 +1818,7 @@ fn create_interrobot_factors(
                 let factor_id = FactorId::new(robot_id, factor_index);
                 let graph_id = factorgraph.id();
                 factorgraph.add_internal_edge(VariableId::new(graph_id, variable_index), factor_id);
-                external_edges_to_add.push((robot_id, factor_index, *other_robot_id, i));
+                external_edges_to_add.push((robot_id, factor_index, *other_robot_id, j));
             }
 
             robotstate.robots_connected_with.insert(*other_robot_id);
@@ -1550,7 +1831,7 @@ fn create_interrobot_factors(
         // TODO: use query.get_mut()
         let mut other_factorgraph = query
             .iter_mut()
-            .find(|(id, _, _, _)| *id == other_robot_id)
+            .find(|(id, ..)| *id == other_robot_id)
             .expect("the other_robot_id should be in the query")
             .1;
 
@@ -1570,7 +1851,7 @@ fn create_interrobot_factors(
         // TODO: use query.get_mut()
         let mut factorgraph = query
             .iter_mut()
-            .find(|(id, _, _, _)| *id == robot_id)
+            .find(|(id, ..)| *id == robot_id)
             .expect("the robot_id should be in the query")
             .1;
 
@@ -1585,6 +1866,55 @@ fn create_interrobot_factors(
     }
 }
 
+/// Pairs of variables to connect with interrobot factors, between the
+/// variables of a robot, and the variables of another robot. Given as the
+/// index `i` of the variable of the robot, and the index `j` of the variable of
+/// the other robot. The current state variables are never paired.
+///
+/// Robots with the same variable timesteps and the same `t0` pair up the
+/// variables with the same index, as in **gbpplanner**. Otherwise, e.g. for
+/// robots of different robot profiles, every variable is paired with the
+/// variable of the other robot closest to it in time. Variables planned further
+/// ahead than the horizon of the other robot are not paired.
+#[allow(clippy::cast_precision_loss)]
+fn paired_variables(
+    t0: f32,
+    variable_timesteps: &[u32],
+    other_t0: f32,
+    other_variable_timesteps: &[u32],
+) -> Vec<(usize, usize)> {
+    if variable_timesteps == other_variable_timesteps && (t0 - other_t0).abs() <= f32::EPSILON {
+        return (1..variable_timesteps.len()).map(|i| (i, i)).collect();
+    }
+
+    let time = |t0: f32, timestep: u32| t0 * timestep as f32;
+    let Some(&other_horizon) = other_variable_timesteps.last() else {
+        return vec![];
+    };
+    let other_horizon = time(other_t0, other_horizon);
+
+    variable_timesteps
+        .iter()
+        .enumerate()
+        .skip(1)
+        .filter(|(_, &timestep)| time(t0, timestep) <= other_horizon + f32::EPSILON)
+        .filter_map(|(i, &timestep)| {
+            let t = time(t0, timestep);
+            let j = other_variable_timesteps
+                .iter()
+                .enumerate()
+                .skip(1)
+                .min_by(|(_, &a), (_, &b)| {
+                    (time(other_t0, a) - t)
+                        .abs()
+                        .total_cmp(&(time(other_t0, b) - t).abs())
+                })
+                .map(|(j, _)| j)?;
+            Some((i, j))
+        })
+        .collect()
+}
+
 /// At random turn on/off the robots "radio".
 /// When the radio is turned of the robot will not be able to communicate with
 /// any other robot. The probability of failure is set by the user in the config
@@ -1766,7 +2096,7 @@ fn iterate_gbp_external_sync(
     }
 }
 
-fn iterate_gbp_v2(
+pub(crate) fn iterate_gbp_v2(
     mut query: Query<
         (
             &mut FactorGraph,
@@ -1776,7 +2106,11 @@ fn iterate_gbp_v2(
         ),
         With<RobotConnections>,
     >,
+    q_transforms: Query<(Entity, &Transform), With<RobotConnections>>,
+    mut channels: ResMut<CommunicationChannels>,
+    mut prng: ResMut<GlobalEntropy<WyRand>>,
     config: Res<Config>,
+    time: Res<Time>,
 ) {
     let schedule_config = gbp_schedule::GbpScheduleParams {
         internal: config.gbp.iteration_schedule.internal as u8,
@@ -1784,78 +2118,49 @@ fn iterate_gbp_v2(
     };
     let schedule = config.gbp.iteration_schedule.schedule.get(schedule_config);
 
+    channels.start_timestep(time.elapsed_seconds_f64(), time.delta_seconds_f64());
+    for (mut factorgraph, _, _, mission) in &mut query {
+        if !mission.state.idle() {
+            factorgraph.start_timestep();
+        }
+    }
+
+    // Messages to other robots are sent over their communication channel, which
+    // may delay or drop them, instead of being delivered directly
+    let positions: HashMap<RobotId, Vec2> = q_transforms
+        .iter()
+        .map(|(robot_id, transform)| (robot_id, transform.translation.xz()))
+        .collect();
+    let mut transport = ChannelTransport {
+        channels:  &mut channels,
+        positions: &positions,
+        config:    &config.robot.communication,
+        rng:       prng.as_mut(),
+    };
+
     for gbp_schedule::GbpScheduleAtIteration { internal, external } in schedule {
         if internal {
             query
                 .par_iter_mut()
-                .for_each(|(mut factorgraph, _, antenna, mission)| {
-                    // if antenna.active {
-                    // if matches!(mission.state, MissionState::Active) {
-                    if !mission.state.idle() {
-                        factorgraph.internal_factor_iteration();
-                        factorgraph.internal_variable_iteration();
+                .for_each(|(mut factorgraph, _, _, mission)| {
+                    // Once the beliefs have converged, the remaining internal iterations of
+                    // the timestep are skipped
+                    if !mission.state.idle() && !factorgraph.converged() {
+                        factorgraph.internal_iteration();
                     }
-                    //}
-                    // }
                 });
         }
 
         if external {
-            let mut messages_to_external_variables = vec![];
-            for (mut factorgraph, _, antenna, mission) in query.iter_mut() {
-                if !antenna.active || mission.state.idle() {
-                    continue;
-                }
-                messages_to_external_variables
-                    .extend(factorgraph.external_factor_iteration().drain(..));
-            }
-
-            // Send messages to external variables
-            for message in messages_to_external_variables.into_iter() {
-                let Ok((mut external_factorgraph, _, antenna, mission)) =
-                    query.get_mut(message.to.factorgraph_id)
-                else {
-                    continue;
-                };
-
-                // cannot receive any new messages if antenna is turned off
-                if !antenna.active || mission.state.idle() {
-                    continue;
-                }
-
-                if let Some(variable) =
-                    external_factorgraph.get_variable_mut(message.to.variable_index)
-                {
-                    variable.receive_message_from(message.from, message.message);
-                }
-            }
-
-            let mut messages_to_external_factors = vec![];
-            for (mut factorgraph, _, antenna, mission) in query.iter_mut() {
-                if !antenna.active || mission.state.idle() {
-                    continue;
-                }
-                messages_to_external_factors
-                    .extend(factorgraph.external_variable_iteration().drain(..));
-            }
-
-            // Send messages to external factors
-            for message in messages_to_external_factors.into_iter() {
-                let Ok((mut external_factorgraph, _, antenna, mission)) =
-                    query.get_mut(message.to.factorgraph_id)
-                else {
-                    continue;
-                };
-
-                // cannot receive any new messages if antenna is turned off
-                if !antenna.active || mission.state.idle() {
-                    continue;
-                }
-
-                if let Some(factor) = external_factorgraph.get_factor_mut(message.to.factor_index) {
-                    factor.receive_message_from(message.from, message.message);
-                }
-            }
+            let mut participants: Vec<Participant<RobotId>> = query
+                .iter_mut()
+                .map(|(factorgraph, _, antenna, mission)| Participant {
+                    factorgraph:  &mut **factorgraph.into_inner(),
+                    active:       !mission.state.idle(),
+                    communicates: antenna.active,
+                })
+                .collect();
+            gbp_core::iteration::external_iteration(&mut participants, &mut transport);
         }
     }
 }
@@ -2191,6 +2496,7 @@ fn update_prior_of_horizon_state(
             &mut FinishedPath,
             &Radius,
             &RadioAntenna,
+            &Profile,
             // &GbpIterationSchedule,
         ),
         With<RobotConnections>,
@@ -2204,11 +2510,12 @@ fn update_prior_of_horizon_state(
 ) {
     let delta_t = Float::from(time.delta_seconds());
 
-    let max_speed = Float::from(config.robot.target_speed.get());
-
     let mut robots_to_despawn = Vec::new();
 
-    for (robot_id, mut factorgraph, mission, mut finished_path, radius, antenna) in &mut query {
+    for (robot_id, mut factorgraph, mission, mut finished_path, radius, antenna, profile) in
+        &mut query
+    {
+        let max_speed = Float::from(profile.target_speed.get());
         if finished_path.0 || mission.state.idle()
         // || !antenna.active
         {
@@ -2239,7 +2546,7 @@ fn update_prior_of_horizon_state(
         let (horizon_variable_index, horizon_variable) = factorgraph.last_variable_mut().unwrap();
         // dbg!(&horizon_variable_index);
         // dbg!(&horizon_variable.belief.mean);
-        let estimated_position = horizon_variable.belief.mean.slice(s![..2]); // the mean is a 4x1 vector with [x, y, x', y']
+        let estimated_position = horizon_variable.belief.mean.slice(s![..2]); // x and y are the first two elements of every state layout
 
         let next_waypoint_pos = array![
             Float::from(next_waypoint.position().x),
@@ -2255,7 +2562,13 @@ fn update_prior_of_horizon_state(
         let new_position = estimated_position.into_owned() + (&new_velocity * delta_t);
 
         // Update horizon state with new position and velocity
-        let new_mean = concatenate![Axis(0), new_position, new_velocity];
+        let mut new_mean = horizon_variable.belief.mean.clone();
+        horizon_variable
+            .layout()
+            .set_planar(&mut new_mean, [new_position[0], new_position[1]], [
+                new_velocity[0],
+                new_velocity[1],
+            ]);
         // dbg!(&new_mean);
 
         // let time_scale = time_fixed.delta_seconds() / config.simulation.t0.get();
@@ -2282,61 +2595,6 @@ fn update_prior_of_horizon_state(
     }
 }
 
-/// Called `Robot::updateCurrent` in **gbpplanner**
-fn update_prior_of_current_state_v3(
-    mut query: Query<
-        (
-            &mut FactorGraph,
-            &mut Transform,
-            &T0,
-            &Mission,
-            &RadioAntenna,
-        ),
-        With<RobotConnections>,
-    >,
-    config: Res<Config>,
-    time_fixed: Res<Time<Fixed>>,
-) {
-    // let mut messages_to_external_factors: Vec<FactorToVariableMessage> = vec![];
-
-    for (mut factorgraph, mut transform, &t0, mission, antenna) in &mut query {
-        if mission.state.idle()
-        // || !antenna.active
-        {
-            continue;
-        }
-
-        let time_scale = time_fixed.delta_seconds() / *t0;
-        let (current_variable_index, current_variable) = factorgraph
-            .nth_variable(0)
-            .expect("factorgraph should have a current variable");
-        let (_, next_variable) = factorgraph
-            .nth_variable(1)
-            .expect("factorgraph should have a next variable");
-
-        let change_in_state =
-            Float::from(time_scale) * (&next_variable.belief.mean - &current_variable.belief.mean);
-        let mean_updated = &current_variable.belief.mean + &change_in_state;
-
-        let external_factor_messages =
-            factorgraph.change_prior_of_variable(current_variable_index, mean_updated);
-        assert!(
-            external_factor_messages.is_empty(),
-            "the current variable is not connected to any external factors"
-        );
-        // messages_to_external_factors.extend(external_factor_messages);
-
-        #[allow(clippy::cast_possible_truncation)]
-        // bevy uses xzy coordinates, so the y component is put at the z coordinate
-        let position_increment =
-            Vec3::new(change_in_state[0] as f32, 0.0, change_in_state[1] as f32);
-
-        transform.translation.x += change_in_state[0] as f32;
-        transform.translation.z += change_in_state[1] as f32;
-        // transform.translation += position_increment;
-    }
-}
-
 // /// Called `Robot::updateCurrent` in **gbpplanner**
 // fn update_prior_of_current_state_v2(
 //     mut query: Query<(&mut FactorGraph, &mut Transform), With<RobotState>>,
@@ -2418,6 +2676,7 @@ fn update_prior_of_current_state(
         let (_, next_variable) = factorgraph
             .nth_variable(1)
             .expect("factorgraph should have a next variable");
+        let layout = current_variable.layout();
         let mean_of_current_variable = current_variable.belief.mean.clone();
         let change_in_state =
             Float::from(scale) * (&next_variable.belief.mean - &mean_of_current_variable);
@@ -2435,9 +2694,10 @@ fn update_prior_of_current_state(
             // continue;
         }
 
+        // bevy uses xzy coordinates, so the altitude is the y coordinate
+        let [dx, dy, dz] = layout.position(change_in_state.view());
         #[allow(clippy::cast_possible_truncation)]
-        let position_increment =
-            Vec3::new(change_in_state[0] as f32, 0.0, change_in_state[1] as f32);
+        let position_increment = Vec3::new(dx as f32, dz as f32, dy as f32);
 
         transform.translation += position_increment;
     }
@@ -2572,10 +2832,27 @@ fn on_robot_clicked(
         let (_, current_variable) = factorgraph
             .first_variable()
             .expect("factorgraph should have >= 2 variables");
-        let [px, py] = current_variable.estimated_position();
-        let [vx, vy] = current_variable.estimated_velocity();
-        println!("    {}: [{:.4}, {:.4}]", "position".cyan(), px, py);
-        println!("    {}: [{:.4}, {:.4}]", "velocity".cyan(), vx, vy);
+        let [px, py, pz] = current_variable.estimated_position_3d();
+        let [vx, vy, vz] = current_variable.estimated_velocity_3d();
+        println!("    {}: {}", "layout".cyan(), current_variable.layout());
+        println!(
+            "    {}: [{:.4}, {:.4}, {:.4}]",
+            "position".cyan(),
+            px,
+            py,
+            pz
+        );
+        println!(
+            "    {}: [{:.4}, {:.4}, {:.4}]",
+            "velocity".cyan(),
+            vx,
+            vy,
+            vz
+        );
+        if let Some((heading, angular_velocity)) = current_variable.estimated_heading() {
+            println!("    {}: {:.4}", "heading".cyan(), heading);
+            println!("    {}: {:.4}", "angular velocity".cyan(), angular_velocity);
+        }
 
         println!(
             "    {}: {:?}",
@@ -2620,6 +2897,22 @@ fn on_robot_clicked(
             "tracking".yellow(),
             factor_counts.tracking
         );
+        println!(
+            "        {}: {}",
+            "dynamic obstacle".yellow(),
+            factor_counts.dynamic_obstacle
+        );
+        println!(
+            "        {}: {}",
+            "velocity".yellow(),
+            factor_counts.velocity
+        );
+        println!(
+            "        {}: {}",
+            "acceleration".yellow(),
+            factor_counts.acceleration
+        );
+        println!("        {}: {}", "user".yellow(), factor_counts.user);
 
         println!("  {}:", "messages".magenta());
         // let message_count = factorgraph.message_count();
@@ -2707,3 +3000,57 @@ fn on_gbp_schedule_changed(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn paired_variables_with_the_same_timesteps_share_indices() {
+        let timesteps = [0, 1, 2, 3, 5, 7];
+        assert_eq!(paired_variables(0.5, &timesteps, 0.5, &timesteps), vec![
+            (1, 1),
+            (2, 2),
+            (3, 3),
+            (4, 4),
+            (5, 5)
+        ]);
+    }
+
+    #[test]
+    fn paired_variables_with_the_same_timesteps_and_different_t0_are_closest_in_time() {
+        // variables at 0.0, 0.5, 1.0, 1.5, 2.5, 3.5 s with a t0 of 0.5 s, and at
+        // 0.0, 0.7, 1.4, 2.1, 3.5, 4.9 s with a t0 of 0.7 s
+        let timesteps = [0, 1, 2, 3, 5, 7];
+        assert_eq!(paired_variables(0.5, &timesteps, 0.7, &timesteps), vec![
+            (1, 1),
+            (2, 1),
+            (3, 2),
+            (4, 3),
+            (5, 4)
+        ]);
+        assert_eq!(paired_variables(0.7, &timesteps, 0.5, &timesteps), vec![
+            (1, 1),
+            (2, 3),
+            (3, 4),
+            (4, 5)
+        ]);
+    }
+
+    #[test]
+    fn paired_variables_with_different_timesteps_are_closest_in_time() {
+        // variables at 0.0, 1.0, 2.0, 3.0, 4.0 s and 0.0, 1.5, 3.0 s
+        let timesteps = [0, 2, 4, 6, 8];
+        let other_timesteps = [0, 3, 6];
+        assert_eq!(
+            paired_variables(0.5, &timesteps, 0.5, &other_timesteps),
+            vec![(1, 1), (2, 1), (3, 2)]
+        );
+        assert_eq!(
+            paired_variables(0.5, &other_timesteps, 0.5, &timesteps),
+            vec![(1, 1), (2, 3)]
+        );
+    }
+}
@@ -0,0 +1,86 @@
+//! Broad-phase spatial index of the robots, rebuilt every fixed timestep and
+//! shared by the systems looking for robots near a robot or an obstacle.
+
+use bevy::prelude::*;
+use gbp_spatial::SpatialGrid;
+
+use super::{
+    robot::{Ball, RadioAntenna},
+    RobotConnections,
+};
+
+#[derive(Default)]
+pub struct SpatialIndexPlugin;
+
+impl Plugin for SpatialIndexPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RobotSpatialIndex>()
+            .add_systems(FixedUpdate, rebuild_robot_spatial_index);
+    }
+}
+
+/// A robot in the [`RobotSpatialIndex`]
+#[derive(Debug, Clone, Copy)]
+pub struct IndexedRobot {
+    pub entity: Entity,
+    /// Radius of the ball of the robot
+    pub radius: f32,
+}
+
+/// The position of every robot in the plane at the start of the fixed
+/// timestep, in a [`SpatialGrid`] with cells the size of the largest
+/// communication radius
+#[derive(Debug, Default, Resource, Deref)]
+pub struct RobotSpatialIndex {
+    #[deref]
+    grid:       SpatialGrid<IndexedRobot>,
+    max_radius: f32,
+}
+
+impl RobotSpatialIndex {
+    /// Radius of the largest robot
+    pub const fn max_radius(&self) -> f32 {
+        self.max_radius
+    }
+
+    /// Every pair of robots close enough for their balls to intersect, each
+    /// pair once
+    pub fn collision_candidates(
+        &self,
+    ) -> impl Iterator<Item = (&(Vec2, IndexedRobot), &(Vec2, IndexedRobot))> {
+        self.grid.pairs_within(2.0 * self.max_radius)
+    }
+}
+
+/// Rebuild the [`RobotSpatialIndex`] from the current position of the robots
+pub fn rebuild_robot_spatial_index(
+    mut index: ResMut<RobotSpatialIndex>,
+    robots: Query<(Entity, &Transform, &Ball, &RadioAntenna), With<RobotConnections>>,
+) {
+    let (max_radius, max_communication_radius) = robots.iter().fold(
+        (0.0_f32, 0.0_f32),
+        |(radius, communication), (_, _, ball, antenna)| {
+            (radius.max(ball.radius), communication.max(antenna.radius))
+        },
+    );
+    // Most queries are for the robots within communication range, but the
+    // cells should also fit the largest robot
+    let cell_size = max_communication_radius.max(2.0 * max_radius);
+    let cell_size = if cell_size > 0.0 { cell_size } else { 1.0 };
+
+    let RobotSpatialIndex {
+        grid,
+        max_radius: index_max_radius,
+    } = index.as_mut();
+    *index_max_radius = max_radius;
+    // bevy uses xzy coordinates
+    grid.rebuild(
+        cell_size,
+        robots.iter().map(|(entity, transform, ball, _)| {
+            (transform.translation.xz(), IndexedRobot {
+                entity,
+                radius: ball.radius,
+            })
+        }),
+    );
+}
@@ -19,14 +19,15 @@ use super::{
 use crate::{
     // asset_loader::SceneAssets,
     asset_loader::Meshes,
-    environment::FollowCameraMe,
+    environment::{map_generator::ObstacleColliderSdf, FollowCameraMe},
     pause_play::PausePlay,
-    planner::robot::{RobotBundle, Route, StateVector},
+    planner::robot::{MotionLimits, RobotBundle, Route, StateVector},
     simulation_loader::{
-        self, EndSimulation, LoadSimulation, ReloadSimulation, Sdf, SimulationManager,
+        self, EndSimulation, LoadSimulation, ObstacleDistanceField, ReloadSimulation, Sdf,
+        SimulationManager,
     },
     theme::{CatppuccinTheme, ColorAssociation, ColorFromCatppuccinColourExt, DisplayColour},
-    utils::get_variable_timesteps,
+    utils::{get_evenly_spaced_variable_timesteps, get_variable_timesteps},
 };
 
 pub struct RobotSpawnerPlugin;
@@ -423,6 +424,8 @@ fn spawn_formation(
     theme: Res<CatppuccinTheme>,
     simulation_manager: Res<SimulationManager>,
     sdf: Res<Sdf>,
+    distance_field: Res<ObstacleDistanceField>,
+    collider_sdf: Option<Res<ObstacleColliderSdf>>,
     mut prng: ResMut<GlobalEntropy<bevy_prng::WyRand>>,
     mut mesh_assets: ResMut<Assets<Mesh>>,
     // time_virtual: Res<Time<Virtual>>,
@@ -443,10 +446,23 @@ fn spawn_formation(
             WorldDimensions::new(width, height)
         };
 
+        let mut profile = match config.robot_profile(formation.robot_profile.as_deref()) {
+            Ok(profile) => profile,
+            Err(err) => {
+                error!(
+                    "failed to spawn formation {}, reason: {}, skipping",
+                    event.formation_group_index, err
+                );
+                continue;
+            }
+        };
+        let motion_model = formation.motion_model_of(&profile);
+        profile.priority = formation.priority_of(&profile);
+
         let max_placement_attempts = NonZeroUsize::new(1000).expect("1000 is not zero");
 
         let radii = (0..formation.robots)
-            .map(|_| prng.gen_range(config.robot.radius.range()))
+            .map(|_| prng.gen_range(profile.radius.range()))
             .collect::<Vec<_>>();
 
         let Some((initial_position_for_each_robot, waypoint_positions_for_each_robot)) = formation
@@ -477,7 +493,7 @@ fn spawn_formation(
             )
             .map(|(from, to)| {
                 let d = *to - *from;
-                let v = d.normalize_or_zero() * config.robot.target_speed.get();
+                let v = d.normalize_or_zero() * profile.target_speed.get();
                 Vec4::new(from.x, from.y, v.x, v.y)
             })
             .collect();
@@ -491,7 +507,7 @@ fn spawn_formation(
                     .zip(b.iter())
                     .map(|(from, to)| {
                         let d = *to - *from;
-                        let v = d.normalize_or_zero() * config.robot.target_speed.get();
+                        let v = d.normalize_or_zero() * profile.target_speed.get();
                         Vec4::new(from.x, from.y, v.x, v.y)
                     })
                     .collect::<Vec<_>>()
@@ -556,17 +572,22 @@ fn spawn_formation(
             // let t0: f32 = radii[i] / 2.0 / config.robot.max_speed.get();
 
             // let divisor: f32 = (min_radius / 2.0 / config.robot.max_speed.get()).into();
-            let divisor: f32 = (max_radius / 2.0 / config.robot.target_speed.get()).into();
+            let divisor: f32 = (max_radius / 2.0 / profile.target_speed.get()).into();
 
-            let lookahead_horizon: u32 = (config.robot.planning_horizon.get() / divisor) as u32;
-            let lookahead_horizon: u32 = config.robot.planning_horizon.get() as u32;
+            let lookahead_horizon: u32 = (profile.planning_horizon.get() / divisor) as u32;
+            let lookahead_horizon: u32 = profile.planning_horizon.get() as u32;
             let lookahead_horizon: u32 =
-                (config.robot.target_speed * config.robot.planning_horizon).get() as u32;
+                (profile.target_speed * profile.planning_horizon).get() as u32;
             // let lookahead_horizon: u32 = (config.robot.planning_horizon.get()
             //     / radii.iter().map(ordered_float::OrderedFloat).min().unwrap())
             //     as u32;
             let lookahead_multiple = config.gbp.lookahead_multiple as u32;
-            let variable_timesteps = get_variable_timesteps(lookahead_horizon, lookahead_multiple);
+            let variable_timesteps = match profile.variables {
+                Some(variables) => {
+                    get_evenly_spaced_variable_timesteps(lookahead_horizon, variables)
+                }
+                None => get_variable_timesteps(lookahead_horizon, lookahead_multiple),
+            };
 
             let robotbundle = RobotBundle::new(
                 robot_entity,
@@ -576,7 +597,11 @@ fn spawn_formation(
                 &config,
                 &env_config,
                 radii[i],
+                MotionLimits::new(&profile, &motion_model),
+                profile.clone(),
                 &sdf.0,
+                &distance_field.0,
+                collider_sdf.as_ref().map(|collider_sdf| &collider_sdf.0),
                 time_fixed.elapsed().as_secs_f64(),
                 waypoints.try_into().unwrap(),
                 // config
@@ -626,6 +651,12 @@ fn spawn_formation(
                 // super::tracking::VelocityTracker::new(1000, Duration::from_millis(50)),
                 super::tracking::PositionTracker::new(10000, Duration::from_millis(100)),
                 super::tracking::VelocityTracker::new(10000, Duration::from_millis(100)),
+                super::metrics::InterRobotProximity::default(),
+                super::metrics::Yielding::new(profile.priority.get()),
+                super::kinematics::Kinematics::new(
+                    motion_model,
+                    initial_pose.w.atan2(initial_pose.z),
+                ),
                 PickableBundle::default(),
                 On::<Pointer<Click>>::send_event::<RobotClickedOn>(),
                 ColorAssociation { name: random_color },
@@ -0,0 +1,515 @@
+//! Factor kinds defined outside of the simulator.
+//!
+//! A crate building on the simulator adds a factor kind by implementing
+//! [`UserFactorDefinition`] for it, and registering it with
+//! [`RegisterUserFactor::register_user_factor`]:
+//!
+//! ```ignore
+//! app.add_plugins(magics::planner::PlannerPlugin)
+//!     .register_user_factor::<SpeedLimit>();
+//! ```
+//!
+//! Every robot spawned afterwards has the factors of every registered kind
+//! attached to its factorgraph. Each kind is configured by its own section in
+//! the `[factors]` table of the config, see [`gbp_config::UserFactorSection`].
+//! The factors are drawn in the colour returned by
+//! [`Factor::color`](crate::factorgraph::factor::Factor::color), and can be
+//! enabled and disabled from the settings panel like the built-in factors.
+
+use std::ops::Range;
+
+use bevy::prelude::*;
+use gbp_config::{Config, UserFactorSection};
+use gbp_core::{
+    factor::{Attachment, Factor, UserFactor},
+    state::StateLayout,
+};
+use gbp_linalg::prelude::*;
+use serde::de::DeserializeOwned;
+
+use super::robot::{Radius, RobotId, VariableTimesteps, T0};
+use crate::factorgraph::{factor::FactorNode, factorgraph::FactorGraph};
+
+/// A factor kind defined outside of the simulator
+pub trait UserFactorDefinition: 'static {
+    /// Name of the factor kind, and of its section in the `[factors]` table of
+    /// the config. Must be the same as the
+    /// [`Factor::name`](crate::factorgraph::factor::Factor::name) of
+    /// [`Self::Factor`]
+    const NAME: &'static str;
+    /// Sigma of the factors, unless `sigma` is set in the section of the kind
+    const SIGMA: f32;
+    /// Which variables the factors are attached to, unless `attach-to` is set
+    /// in the section of the kind
+    const ATTACHMENT: Attachment = Attachment::Inbetween;
+
+    /// Settings of the factor kind, read from the section of the kind
+    type Settings: DeserializeOwned;
+    /// The factor attached to the factorgraphs
+    type Factor: UserFactor;
+
+    /// The measurement every factor is initialised with
+    #[must_use]
+    fn measurement(_settings: &Self::Settings, _context: &UserFactorContext) -> Vector<Float> {
+        Vector::<Float>::zeros(1)
+    }
+
+    /// Create the factor attached to the `variables` of a robot, ordered from
+    /// the current state to the horizon state.
+    /// Returns `None` to not attach a factor to those variables
+    fn create(
+        settings: &Self::Settings,
+        context: &UserFactorContext,
+        variables: Range<usize>,
+    ) -> Option<Self::Factor>;
+}
+
+/// The robot user defined factors are attached to
+#[derive(Debug, Clone, Copy)]
+pub struct UserFactorContext<'a> {
+    /// Id of the robot
+    pub robot_id: RobotId,
+    /// Layout of the state of the variables of the robot
+    pub layout: StateLayout,
+    /// Radius of the robot
+    pub radius: f32,
+    /// Time between the current state and the first planned state
+    pub t0: f32,
+    /// Timestep of every variable, from the current state to the horizon state
+    pub variable_timesteps: &'a [u32],
+    /// The config the robot was spawned with
+    pub config: &'a Config,
+}
+
+/// Error returned when the factors of a user defined kind could not be
+/// attached to a factorgraph
+#[derive(Debug, thiserror::Error)]
+pub enum AttachUserFactorsError {
+    /// The settings of the section of the kind are invalid
+    #[error("invalid settings: {0}")]
+    Settings(#[from] toml::de::Error),
+    /// The factors are connected to a different number of variables than
+    /// given by the attachment
+    #[error(
+        "the factors have {neighbours} neighbours, but are attached to {attachment} variables"
+    )]
+    Neighbours {
+        /// Variables the factors were attached to
+        attachment: Attachment,
+        /// Neighbours of the factors
+        neighbours: usize,
+    },
+}
+
+type AttachFn = fn(
+    &mut FactorGraph,
+    &UserFactorContext,
+    &UserFactorSection,
+) -> Result<usize, AttachUserFactorsError>;
+
+/// Resource with every factor kind registered with
+/// [`RegisterUserFactor::register_user_factor`]
+#[derive(Resource, Default)]
+pub struct UserFactorRegistry {
+    kinds: Vec<(&'static str, AttachFn)>,
+}
+
+impl UserFactorRegistry {
+    /// Names of the registered factor kinds, in the order they were registered
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.kinds.iter().map(|&(name, _)| name)
+    }
+
+    /// Returns `true` if no factor kinds are registered
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_empty()
+    }
+}
+
+/// Extension trait to register user defined factor kinds on an [`App`]
+pub trait RegisterUserFactor {
+    /// Attach the factors of kind `D` to every robot spawned from now on
+    fn register_user_factor<D: UserFactorDefinition>(&mut self) -> &mut Self;
+}
+
+impl RegisterUserFactor for App {
+    fn register_user_factor<D: UserFactorDefinition>(&mut self) -> &mut Self {
+        let mut registry = self
+            .world
+            .get_resource_or_insert_with(UserFactorRegistry::default);
+        if registry.names().any(|name| name == D::NAME) {
+            warn!("the factor kind {} is already registered", D::NAME);
+        } else {
+            registry.kinds.push((D::NAME, attach::<D>));
+        }
+        self
+    }
+}
+
+/// Attach the factors of kind `D` to `factorgraph`, configured by `section`.
+/// Returns the number of attached factors
+fn attach<D: UserFactorDefinition>(
+    factorgraph: &mut FactorGraph,
+    context: &UserFactorContext,
+    section: &UserFactorSection,
+) -> Result<usize, AttachUserFactorsError> {
+    let settings: D::Settings = toml::Value::Table(section.settings.clone()).try_into()?;
+    let strength = Float::from(section.sigma.unwrap_or(D::SIGMA));
+    let attachment = section.attach_to.unwrap_or(D::ATTACHMENT);
+    let measurement = D::measurement(&settings, context);
+
+    let factorgraph_id = factorgraph.id();
+    let mut mismatch = None;
+    let attached = factorgraph.attach_user_factors(attachment, |variables| {
+        let factor = D::create(&settings, context, variables)?;
+        debug_assert_eq!(factor.name(), D::NAME);
+        if factor.neighbours() != attachment.neighbours() {
+            mismatch = Some(factor.neighbours());
+            return None;
+        }
+        Some(FactorNode::new_user_factor(
+            factorgraph_id,
+            context.layout,
+            strength,
+            measurement.clone(),
+            factor,
+            section.enabled,
+        ))
+    });
+
+    match mismatch {
+        Some(neighbours) => Err(AttachUserFactorsError::Neighbours {
+            attachment,
+            neighbours,
+        }),
+        None => Ok(attached.len()),
+    }
+}
+
+/// **Bevy** [`FixedUpdate`] system that attaches the factors of every
+/// registered kind to the factorgraphs of newly spawned robots
+pub(super) fn attach_user_factors(
+    mut robots: Query<
+        (Entity, &mut FactorGraph, &Radius, &T0, &VariableTimesteps),
+        Added<FactorGraph>,
+    >,
+    registry: Res<UserFactorRegistry>,
+    config: Res<Config>,
+) {
+    if registry.is_empty() {
+        return;
+    }
+
+    for (robot_id, mut factorgraph, radius, t0, variable_timesteps) in &mut robots {
+        let context = UserFactorContext {
+            robot_id,
+            layout: config.gbp.state_layout,
+            radius: radius.0,
+            t0: t0.0,
+            variable_timesteps: variable_timesteps.as_slice(),
+            config: &config,
+        };
+
+        for &(name, attach) in &registry.kinds {
+            let section = config.factors.get(name).cloned().unwrap_or_default();
+            match attach(&mut *factorgraph, &context, &section) {
+                Ok(attached) => debug!("attached {attached} {name} factors to robot {robot_id:?}"),
+                Err(err) => {
+                    error!("failed to attach the {name} factors to robot {robot_id:?}: {err}");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use gbp_core::factor::{FactorState, Measurement};
+    use ndarray::array;
+    use pretty_assertions::assert_eq;
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::factorgraph::variable::VariableNode;
+
+    const VARIABLES: usize = 4;
+
+    /// Pulls the x position of its first variable towards `x`
+    #[derive(Debug)]
+    struct Wall {
+        x: Float,
+        neighbours: usize,
+    }
+
+    impl std::fmt::Display for Wall {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            writeln!(f, "x: {}", self.x)
+        }
+    }
+
+    impl Factor for Wall {
+        fn name(&self) -> &'static str {
+            "wall"
+        }
+
+        fn color(&self) -> [u8; 3] {
+            [255, 0, 0]
+        }
+
+        fn jacobian_delta(&self) -> Float {
+            1e-8
+        }
+
+        fn neighbours(&self) -> usize {
+            self.neighbours
+        }
+
+        fn skip(&self, _state: &FactorState) -> bool {
+            false
+        }
+
+        fn linear(&self) -> bool {
+            true
+        }
+
+        fn jacobian(&self, state: &FactorState, _x: &Vector<Float>) -> Cow<'_, Matrix<Float>> {
+            let mut jacobian = Matrix::<Float>::zeros((1, self.neighbours * state.layout.dofs()));
+            jacobian[[0, 0]] = 1.0;
+            Cow::Owned(jacobian)
+        }
+
+        fn measure(&self, _state: &FactorState, x: &Vector<Float>) -> Measurement {
+            Measurement::new(array![x[0] - self.x])
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct WallSettings {
+        #[serde(default)]
+        x: Float,
+        #[serde(default = "WallSettings::default_neighbours")]
+        neighbours: usize,
+    }
+
+    impl WallSettings {
+        const fn default_neighbours() -> usize {
+            1
+        }
+    }
+
+    struct WallDefinition;
+
+    impl UserFactorDefinition for WallDefinition {
+        type Factor = Wall;
+        type Settings = WallSettings;
+
+        const NAME: &'static str = "wall";
+        const SIGMA: f32 = 0.5;
+
+        fn create(
+            settings: &Self::Settings,
+            _context: &UserFactorContext,
+            _variables: Range<usize>,
+        ) -> Option<Self::Factor> {
+            Some(Wall {
+                x: settings.x,
+                neighbours: settings.neighbours,
+            })
+        }
+    }
+
+    /// The `[factors]` table of a config
+    #[derive(Deserialize)]
+    struct Factors {
+        factors: gbp_config::UserFactorsSection,
+    }
+
+    /// An app with the wall factors registered, configured by the `[factors]`
+    /// table in `factors`, and a robot spawned with [`VARIABLES`] variables.
+    /// Returns the app after the user factors are attached, and the robot
+    fn robot_with_walls(factors: &str) -> (App, Entity) {
+        let Factors { factors } = toml::from_str(factors).expect("the [factors] table is valid");
+        let config = Config {
+            factors,
+            ..Config::default()
+        };
+
+        let mut app = App::new();
+        app.insert_resource(config)
+            .register_user_factor::<WallDefinition>()
+            .add_systems(Update, attach_user_factors);
+
+        let robot = app.world.spawn_empty().id();
+        let mut factorgraph = FactorGraph::new(robot);
+        for _ in 0..VARIABLES {
+            let layout = StateLayout::Planar;
+            factorgraph.add_variable(VariableNode::new(
+                robot,
+                Vector::<Float>::zeros(layout.dofs()),
+                Matrix::<Float>::eye(layout.dofs()),
+                layout,
+            ));
+        }
+        #[allow(clippy::cast_possible_truncation)]
+        let variable_timesteps = VariableTimesteps((0..VARIABLES as u32).collect());
+        app.world
+            .entity_mut(robot)
+            .insert((factorgraph, Radius(1.0), T0(0.5), variable_timesteps));
+
+        app.update();
+        (app, robot)
+    }
+
+    /// The variables every wall factor of `robot` is attached to, by their
+    /// position from the current state, and the sigma and `x` of the factor
+    fn walls(app: &App, robot: Entity) -> Vec<(Vec<usize>, Float, Float)> {
+        let factorgraph = app
+            .world
+            .get::<FactorGraph>(robot)
+            .expect("the robot has a factorgraph");
+        let nth = |variable_index| {
+            (0..VARIABLES)
+                .position(|nth| factorgraph.nth_variable_index(nth) == Some(variable_index))
+                .expect("the factor is attached to a variable of the robot")
+        };
+        factorgraph
+            .user_factors_of::<Wall>()
+            .map(|(index, wall)| {
+                let factor = factorgraph.get_factor(index).expect("the factor exists");
+                let variables = factor
+                    .inbox
+                    .keys()
+                    .map(|variable_id| nth(variable_id.variable_index))
+                    .collect();
+                (variables, factor.state.strength, wall.x)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn defaults_of_the_definition() {
+        let (mut app, robot) = robot_with_walls("[factors]");
+        assert_eq!(walls(&app, robot), vec![
+            (vec![1], 0.5, 0.0),
+            (vec![2], 0.5, 0.0)
+        ]);
+
+        // Only attached to newly spawned robots
+        app.update();
+        assert_eq!(walls(&app, robot).len(), 2);
+    }
+
+    #[test]
+    fn section_of_the_kind() {
+        let (app, robot) = robot_with_walls(
+            r#"
+            [factors.wall]
+            sigma = 0.25
+            attach-to = "future"
+            x = 2.0
+        "#,
+        );
+        assert_eq!(walls(&app, robot), vec![
+            (vec![1], 0.25, 2.0),
+            (vec![2], 0.25, 2.0),
+            (vec![3], 0.25, 2.0)
+        ]);
+
+        let (app, robot) = robot_with_walls(
+            r#"
+            [factors.other]
+            sigma = 0.25
+        "#,
+        );
+        assert_eq!(walls(&app, robot).len(), 2);
+    }
+
+    #[test]
+    fn attachments() {
+        let attached_to = |attachment: &str, neighbours: usize| {
+            let (app, robot) = robot_with_walls(&format!(
+                "[factors.wall]\nattach-to = \"{attachment}\"\nneighbours = {neighbours}"
+            ));
+            walls(&app, robot)
+                .into_iter()
+                .map(|(variables, _, _)| variables)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(attached_to("every", 1), vec![
+            vec![0],
+            vec![1],
+            vec![2],
+            vec![3]
+        ]);
+        assert_eq!(attached_to("inbetween", 1), vec![vec![1], vec![2]]);
+        assert_eq!(attached_to("horizon", 1), vec![vec![3]]);
+        assert_eq!(attached_to("consecutive", 2), vec![
+            vec![0, 1],
+            vec![1, 2],
+            vec![2, 3]
+        ]);
+
+        // Factors with a different number of neighbours than the attachment are not
+        // attached
+        assert!(attached_to("consecutive", 1).is_empty());
+        assert!(attached_to("every", 2).is_empty());
+    }
+
+    #[test]
+    fn attach_errors_and_disabled_sections() {
+        let config = Config::default();
+        let context = UserFactorContext {
+            robot_id: Entity::PLACEHOLDER,
+            layout: StateLayout::Planar,
+            radius: 1.0,
+            t0: 0.5,
+            variable_timesteps: &[0, 1, 2],
+            config: &config,
+        };
+        let section = |section: &str| -> UserFactorSection {
+            toml::from_str(section).expect("the section is valid")
+        };
+        let mut factorgraph = FactorGraph::new(Entity::PLACEHOLDER);
+        for _ in 0..3 {
+            let layout = StateLayout::Planar;
+            factorgraph.add_variable(VariableNode::new(
+                Entity::PLACEHOLDER,
+                Vector::<Float>::zeros(layout.dofs()),
+                Matrix::<Float>::eye(layout.dofs()),
+                layout,
+            ));
+        }
+
+        assert!(matches!(
+            attach::<WallDefinition>(&mut factorgraph, &context, &section("x = \"left\"")),
+            Err(AttachUserFactorsError::Settings(_))
+        ));
+        assert!(matches!(
+            attach::<WallDefinition>(
+                &mut factorgraph,
+                &context,
+                &section("attach-to = \"consecutive\"")
+            ),
+            Err(AttachUserFactorsError::Neighbours {
+                attachment: Attachment::Consecutive,
+                neighbours: 1,
+            })
+        ));
+        assert_eq!(factorgraph.user_factors_of::<Wall>().count(), 0);
+
+        let disabled = section("enabled = false");
+        assert_eq!(
+            attach::<WallDefinition>(&mut factorgraph, &context, &disabled).ok(),
+            Some(1)
+        );
+        assert!(factorgraph
+            .user_factors_of::<Wall>()
+            .all(|(index, _)| factorgraph
+                .get_factor(index)
+                .is_some_and(|factor| !factor.enabled)));
+    }
+}
@@ -250,10 +250,10 @@ fn create_factorgraph_visualizer(
         //};
 
         for (i, (index, variable)) in factorgraph.variables().enumerate() {
-            let [x, y] = variable.estimated_position();
+            let [x, y, z] = variable.estimated_position_3d();
             #[allow(clippy::cast_possible_truncation)]
             // let transform = Vec3::new(x as f32, -config.visualisation.height.objects, y as f32);
-            let transform = Vec3::new(x as f32, -radius.0, y as f32);
+            let transform = Vec3::new(x as f32, z as f32 - radius.0, y as f32);
             let robottracker = RobotTracker {
                 robot_id: *robot_id,
                 variable_index: index.index(),
@@ -323,10 +323,13 @@ fn update_factorgraph_visualizers(
                     continue;
                 }
 
-                // else update the transform
-                let [x, y] = v.estimated_position();
-                transform.translation =
-                    Vec3::new(x as f32, -config.visualisation.height.objects, y as f32);
+                // else update the transform, lifted by the altitude of the spatial layout
+                let [x, y, z] = v.estimated_position_3d();
+                transform.translation = Vec3::new(
+                    x as f32,
+                    z as f32 - config.visualisation.height.objects,
+                    y as f32,
+                );
             }
         }
     }
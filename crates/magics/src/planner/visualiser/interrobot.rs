@@ -40,7 +40,7 @@ fn visualize_interrobot_factors(
 
     for (factorgraph, antenna) in &q {
         for (variable, interrobot) in factorgraph.variable_and_inter_robot_factors() {
-            let estimated_position = variable.estimated_position_vec2();
+            let estimated_position = variable.estimated_position_vec3();
             // get the estimated position of the variable that the interrobot factor is
             // connected to in the external factor graph
             let Ok((external_factorgraph, _)) = q.get(interrobot.external_variable.factorgraph_id)
@@ -52,10 +52,10 @@ fn visualize_interrobot_factors(
                 .get_variable(interrobot.external_variable.variable_index)
                 .expect("external variable exists");
 
-            let external_position = external_variable.estimated_position_vec2();
-            let dir = (external_position - estimated_position).normalize();
+            let external_position = external_variable.estimated_position_vec3();
+            let dir = (external_position - estimated_position).xy().normalize();
             // rotate 90deg clockwise
-            let dir = Vec2::new(dir.y, -dir.x);
+            let dir = Vec2::new(dir.y, -dir.x).extend(0.0);
             // let distance_sq = estimated_position.distance_squared(external_position);
             let safety_dist = interrobot.safety_distance();
 
@@ -69,7 +69,11 @@ fn visualize_interrobot_factors(
                     let ratio = dist / safety_dist as f32;
                     let color = gradient.at(ratio as f64);
                     let color = Color::rgb(color.r as f32, color.g as f32, color.b as f32);
-                    gizmos.line(start.extend(height).xzy(), end.extend(height).xzy(), color);
+                    gizmos.line(
+                        start.xzy() + Vec3::Y * height,
+                        end.xzy() + Vec3::Y * height,
+                        color,
+                    );
                 }
             }
 
@@ -81,7 +85,7 @@ fn visualize_interrobot_factors(
                 };
                 gizmos
                     .circle(
-                        estimated_position.extend(height).xzy(),
+                        estimated_position.xzy() + Vec3::Y * height,
                         Direction3d::Y,
                         safety_dist as f32,
                         color,
@@ -0,0 +1,81 @@
+//! Visualize where the planned trajectories exceed the speed and acceleration
+//! limits of the robots, as measured by the velocity and acceleration limit
+//! factors.
+
+use bevy::prelude::*;
+use gbp_config::Config;
+
+use crate::factorgraph::prelude::FactorGraph;
+
+pub struct LimitViolationVisualizerPlugin;
+
+impl Plugin for LimitViolationVisualizerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, visualize_limit_violations.run_if(enabled));
+    }
+}
+
+/// Color of a violation, from yellow for a barely exceeded limit to red for a
+/// limit exceeded by 100% or more
+fn violation_color(violation: f32) -> Color {
+    let violation = violation.clamp(0.0, 1.0);
+    Color::rgb(1.0, 1.0 - violation, 0.0)
+}
+
+/// Draw a circle around every variable planned to move faster than its robot
+/// is able to, and a line between every two consecutive variables planned to
+/// change velocity faster than the robot is able to. The color reflects how
+/// much the limit is exceeded, see [`violation_color`]
+#[allow(clippy::cast_possible_truncation)]
+fn visualize_limit_violations(
+    mut gizmos: Gizmos,
+    factorgraphs: Query<(&FactorGraph, &crate::planner::robot::Radius)>,
+    config: Res<Config>,
+) {
+    let height = config.visualisation.height.objects;
+    // Lifted by the altitude of the spatial layout
+    let translation = |position: Vec3| position.xzy() - Vec3::Y * height;
+
+    for (factorgraph, radius) in &factorgraphs {
+        if config.gbp.factors_enabled.velocity {
+            for (variable, velocity_factor) in factorgraph.variable_and_their_velocity_factors() {
+                let violation = velocity_factor.violation(variable.estimated_velocity_3d());
+                if violation <= 0.0 {
+                    continue;
+                }
+
+                gizmos.circle(
+                    translation(variable.estimated_position_vec3()),
+                    Direction3d::Y,
+                    radius.0,
+                    violation_color(violation as f32),
+                );
+            }
+        }
+
+        if config.gbp.factors_enabled.acceleration {
+            for ([from, to], acceleration_factor) in
+                factorgraph.variables_and_their_acceleration_factors()
+            {
+                let violation = acceleration_factor
+                    .violation(from.estimated_velocity_3d(), to.estimated_velocity_3d());
+                if violation <= 0.0 {
+                    continue;
+                }
+
+                gizmos.line(
+                    translation(from.estimated_position_vec3()),
+                    translation(to.estimated_position_vec3()),
+                    violation_color(violation as f32),
+                );
+            }
+        }
+    }
+}
+
+/// **Bevy** run condition for drawing limit violations
+#[inline]
+fn enabled(config: Res<Config>) -> bool {
+    config.visualisation.draw.limit_violations
+        && (config.gbp.factors_enabled.velocity || config.gbp.factors_enabled.acceleration)
+}
@@ -3,6 +3,7 @@ mod communication;
 pub mod communication_radius;
 pub mod factorgraphs;
 mod interrobot;
+mod limits;
 mod obstacle;
 mod robot;
 mod tracer;
@@ -44,6 +45,7 @@ impl Plugin for VisualiserPlugin {
             interrobot::InterRobotFactorVisualizerPlugin,
             collider::ColliderVisualizerPlugin,
             tracking::TrackingVisualizerPlugin,
+            limits::LimitViolationVisualizerPlugin,
         ));
     }
 }
@@ -145,8 +145,10 @@ fn visualize_obstacle_factors(
                 color,
             );
 
+            // The obstacles are in the plane, but the variable is lifted by the altitude
+            // of the spatial layout
             gizmos.line(
-                variable.estimated_position_vec2().extend(height).xzy(),
+                variable.estimated_position_vec3().xzy() + Vec3::Y * height,
                 pos,
                 color,
             );
@@ -56,7 +56,7 @@ fn visualise_tracking_factors(
 
         for (variable, tracking_factor) in factorgraph.variable_and_their_tracking_factors() {
             let last_measurement = tracking_factor.last_measurement();
-            let estimated_position = variable.estimated_position_vec2();
+            let estimated_position = variable.estimated_position_vec3();
 
             let color = gradient.at(last_measurement.value);
             let color = Color::rgba(
@@ -67,9 +67,7 @@ fn visualise_tracking_factors(
             );
 
             // line from estimated position to last measurement
-            let start = estimated_position
-                .extend(-config.visualisation.height.objects)
-                .xzy();
+            let start = estimated_position.xzy() - Vec3::Y * config.visualisation.height.objects;
             let end = last_measurement
                 .pos
                 .extend(-config.visualisation.height.objects)
@@ -85,11 +85,11 @@ fn init_uncertainty(
             factorgraph.variables().for_each(|(index, v)| {
                 // let mean = v.belief.mean();
                 #[allow(clippy::cast_possible_truncation)]
-                let [x, y] = v.estimated_position();
+                let [x, y, z] = v.estimated_position_3d();
                 #[allow(clippy::cast_possible_truncation)]
                 let transform = Vec3::new(
                     x as f32,
-                    -config.visualisation.height.objects,
+                    z as f32 - config.visualisation.height.objects,
                     // 2.0f32.mul_add(-Z_FIGHTING_OFFSET, -config.visualisation.height.objects), /*
                     // just under
                     // * the
@@ -229,7 +229,7 @@ fn update_uncertainty(
                     continue;
                 }
 
-                let mean = &v.belief.mean;
+                let [x, y, z] = v.estimated_position_3d();
                 let covariance = &v.belief.covariance_matrix;
                 // pretty_print_matrix!(covariance);
 
@@ -260,9 +260,9 @@ fn update_uncertainty(
                 // transform, covariance);
 
                 *transform = Transform::from_translation(Vec3::new(
-                    mean[0] as f32,
-                    -config.visualisation.height.objects,
-                    mean[1] as f32,
+                    x as f32,
+                    z as f32 - config.visualisation.height.objects,
+                    y as f32,
                 ))
                 .with_rotation(Quat::from_rotation_x(std::f32::consts::FRAC_PI_2));
 
@@ -342,14 +342,15 @@ fn update_velocity_uncertainty(mut gizmos: Gizmos, q: Query<&FactorGraph>) {
             let covariance = v.belief.covariance_matrix.view();
 
             // Draw a velocity vector
-            let pos = v.estimated_position_vec2();
-            let vx = covariance[(2, 2)] * 300.0;
-            let vy = covariance[(3, 3)] * 300.0;
+            let [x, y, z] = v.estimated_position_3d();
+            let offset = v.layout().velocity_offset();
+            let vx = covariance[(offset, offset)] * 300.0;
+            let vy = covariance[(offset + 1, offset + 1)] * 300.0;
 
             // dbg!(&covariance);
 
-            let start = Vec3::new(pos.x, 0.0, pos.y);
-            let end = Vec3::new(pos.x + vx as f32, 0.0, pos.y + vy as f32);
+            let start = Vec3::new(x as f32, z as f32, y as f32);
+            let end = start + Vec3::new(vx as f32, 0.0, vy as f32);
             // dbg!((&start, &end));
             gizmos.arrow(start, end, Color::RED);
         }
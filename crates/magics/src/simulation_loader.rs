@@ -1,6 +1,8 @@
 use std::{
     collections::{BTreeMap, VecDeque},
-    time::Duration,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
 };
 
 use bevy::{
@@ -13,20 +15,27 @@ use gbp_config::{Config, FormationGroup};
 use gbp_environment::Environment;
 use smol_str::SmolStr;
 
+use crate::validation::{self, Report};
+
 /// Which simulation to load initially
 #[derive(Debug, Default)]
 pub enum InitialSimulation {
-    /// Use the first simulation found in the `config/simulations` folder
+    /// Use the first valid simulation found in the simulations directory
     /// Ordered lexiographically
     #[default]
     FirstFoundInFolder,
-    /// Use the simulation with the given name
+    /// Use the simulation with the given name, or the first valid one if it
+    /// is not valid
     Name(String),
+    /// Use the given simulation, without loading any simulations from the
+    /// simulations directory
+    Preloaded(Box<Simulation>),
 }
 
 #[derive(Debug)]
 pub struct SimulationLoaderPlugin {
-    // pub simulations_dir: std::path::PathBuf,
+    /// Directory with a subdirectory for every simulation
+    pub simulations_dir: PathBuf,
     pub show_toasts: bool,
     pub initial_simulation: InitialSimulation,
     pub reload_after: Option<Duration>,
@@ -35,6 +44,7 @@ pub struct SimulationLoaderPlugin {
 impl Default for SimulationLoaderPlugin {
     fn default() -> Self {
         Self {
+            simulations_dir: SIMULATIONS_DIR.into(),
             show_toasts: true,
             initial_simulation: InitialSimulation::FirstFoundInFolder,
             reload_after: None,
@@ -47,14 +57,24 @@ impl SimulationLoaderPlugin {
         self.reload_after = Some(duration);
         self
     }
+
+    /// Load the simulations from `dir` instead of [`SIMULATIONS_DIR`]
+    pub fn simulations_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.simulations_dir = dir.into();
+        self
+    }
 }
 
-pub type SdfImage = image::ImageBuffer<image::Rgb<u8>, Vec<u8>>;
+pub use gbp_core::factor::{DistanceField, SdfImage};
 pub type RawImage = image::ImageBuffer<image::Rgb<u8>, Vec<u8>>;
 
 #[derive(Debug, Clone, Resource, Deref, DerefMut)]
 pub struct Sdf(pub SdfImage);
 
+/// The exact signed distance field of the environment, in metres
+#[derive(Debug, Clone, Resource, Deref, DerefMut)]
+pub struct ObstacleDistanceField(pub Arc<DistanceField>);
+
 #[derive(Debug, Clone, Resource, Deref, DerefMut)]
 pub struct Raw(pub RawImage);
 
@@ -64,11 +84,13 @@ pub struct Raw(pub RawImage);
 // struct Simulations(BTreeMap<String, Simulation>);
 type Simulations = BTreeMap<String, Simulation>;
 
-const SIMULATIONS_DIR: &'static str = "./config/simulations";
+/// The directory the simulations are loaded from, if no other is given
+pub const SIMULATIONS_DIR: &str = "./config/simulations";
 
 impl SimulationLoaderPlugin {
     pub fn new(show_toasts: bool, initial_simulation: Option<String>) -> Self {
         Self {
+            simulations_dir: SIMULATIONS_DIR.into(),
             show_toasts,
             initial_simulation: initial_simulation
                 .map_or(InitialSimulation::FirstFoundInFolder, |name| {
@@ -127,86 +149,44 @@ fn elapsed_virtual_time_exceeds(
 
 impl Plugin for SimulationLoaderPlugin {
     fn build(&self, app: &mut App) {
-        let reader =
-            std::fs::read_dir(SIMULATIONS_DIR).expect("failed to read simulation directory");
-
-        let simulations: BTreeMap<_, _> = reader
-            .map(|dir| {
-                let dir = dir.unwrap();
-                let name = dir
-                    .file_name()
-                    .into_string()
-                    .expect("failed to parse simulation name");
-                // println!("about to load: {name:?}");
-                let config_path = dir.path().join("config.toml");
-                let config = Config::from_file(config_path)
-                    .expect(format!("failed to load config for simulation: {name:?}").as_str());
-                let environment_path = dir.path().join("environment.yaml");
-                let environment = Environment::from_file(environment_path).expect(
-                    format!("failed to load environment for simulation: {name:?}").as_str(),
-                );
-                let formation_path = dir.path().join("formation.yaml");
-                let formation = FormationGroup::from_yaml_file(formation_path)
-                    .expect(format!("failed to load formation for simulation: {name:?}").as_str());
-
-                // println!("name: {name:?}");
-                let sdf_image_buffer = env_to_png::env_to_sdf_image(
-                    &environment,
-                    env_to_png::PixelsPerTile::new(
-                        environment.tiles.settings.sdf.resolution as u32,
-                    ),
-                    env_to_png::Percentage::new(environment.tiles.settings.sdf.expansion),
-                    env_to_png::Percentage::new(environment.tiles.settings.sdf.blur),
-                )
-                .expect("it all just works");
-
-                // let sdf_path = PathBuf::new()
-                //     .join("crates/magics/assets/imgs/obstacles")
-                //     .join(format!("{}.sdf.png", config.environment_image));
-                // info!("sdf_path: {sdf_path:?}");
-                // let sdf_image_buffer =
-                // image::io::Reader::open(sdf_path).unwrap().decode().unwrap();
-                // println!(
-                //     "sdf_image_buffer: {:?} channels: {:?}",
-                //     sdf_image_buffer.dimensions(),
-                //     sdf_image_buffer.color()
-                // );
-
-                // let raw_path = PathBuf::new()
-                //     .join("crates/magics/assets/imgs/obstacles")
-                //     .join(format!("{}.png", config.environment_image));
-                // let raw_image_buffer =
-                // image::io::Reader::open(raw_path).unwrap().decode().unwrap();
-
-                let simulation = Simulation {
-                    name: name.clone(),
-                    config,
-                    environment,
-                    formation_group: formation,
-                    sdf: Sdf(sdf_image_buffer.into()),
-                    // raw: Raw(raw_image_buffer.into()),
-                };
-
-                // println!("loaded: {name:?}");
-
-                (name, simulation)
-            })
-            .collect();
+        let (simulations, invalid) = if let InitialSimulation::Preloaded(ref simulation) =
+            self.initial_simulation
+        {
+            let simulations =
+                std::iter::once((simulation.name.clone(), simulation.as_ref().clone())).collect();
+            (simulations, Vec::new())
+        } else {
+            load_simulations(&self.simulations_dir)
+        };
 
-        assert!(
-            !simulations.is_empty(),
-            "No simulations found in {}",
-            SIMULATIONS_DIR
-        );
+        // The reports of the invalid simulations have been logged while loading
+        // them. The binary checks the simulations directory before building the
+        // app, see `ensure_valid_simulation` in `main.rs`
+        if simulations.is_empty() {
+            error!(
+                "no valid simulations found in {}, no simulation is loaded",
+                self.simulations_dir.display()
+            );
+            return;
+        }
 
-        let initial_simulation = match &self.initial_simulation {
-            InitialSimulation::FirstFoundInFolder => simulations
+        let first = || {
+            simulations
                 .first_key_value()
                 .map(|(_, v)| v)
-                .expect("there is 1 or more simulations"),
-            InitialSimulation::Name(name) => {
-                simulations.get(name).expect("simulation with name exists")
-            }
+                .expect("there is 1 or more simulations")
+        };
+        let initial_simulation = match &self.initial_simulation {
+            InitialSimulation::FirstFoundInFolder => first(),
+            InitialSimulation::Name(name) => simulations.get(name).unwrap_or_else(|| {
+                error!(
+                    "simulation '{name}' does not exist or is invalid, loading the first instead"
+                );
+                first()
+            }),
+            InitialSimulation::Preloaded(simulation) => simulations
+                .get(&simulation.name)
+                .expect("the preloaded simulation was inserted above"),
         };
 
         // let initial_simulation = simulations.first_key_value().map(|(_, v)|
@@ -216,6 +196,7 @@ impl Plugin for SimulationLoaderPlugin {
         let formation_group = initial_simulation.formation_group.clone();
         let environment = initial_simulation.environment.clone();
         let sdf = initial_simulation.sdf.clone();
+        let distance_field = initial_simulation.distance_field.clone();
         // let raw = initial_simulation.raw.clone();
 
         let initial_simulation_name = initial_simulation.name.clone();
@@ -229,12 +210,19 @@ impl Plugin for SimulationLoaderPlugin {
             .insert_resource(formation_group)
             .insert_resource(environment)
             .insert_resource(sdf)
+            .insert_resource(distance_field)
             // .insert_resource(raw)
             .add_event::<ReloadSimulation>()
             .add_event::<LoadSimulation>()
             .add_event::<EndSimulation>()
+            .add_event::<RestartSimulation>()
             .add_event::<SaveSettings>()
-            .insert_resource(SimulationManager::new(simulations, Some(initial_simulation_name)))
+            .insert_resource(SimulationManager::new(
+                self.simulations_dir.clone(),
+                simulations,
+                invalid,
+                Some(initial_simulation_name),
+            ))
             .add_systems(Update, handle_requests.run_if(on_real_timer(Duration::from_millis(500))))
             .add_systems(
                 Update,
@@ -246,6 +234,15 @@ impl Plugin for SimulationLoaderPlugin {
                 )
             );
 
+        // A preloaded simulation has no directory to watch
+        if !matches!(self.initial_simulation, InitialSimulation::Preloaded(_)) {
+            app.insert_resource(SimulationsWatcher::new(self.simulations_dir.clone()))
+                .add_systems(
+                    Update,
+                    watch_simulations_dir.run_if(on_real_timer(Duration::from_secs(1))),
+                );
+        }
+
         if let Some(after) = self.reload_after {
             app.add_systems(
                 FixedUpdate,
@@ -261,6 +258,41 @@ impl Plugin for SimulationLoaderPlugin {
     }
 }
 
+/// Load every simulation in `dir`, by name, together with the reports of the
+/// simulations that are not valid. The warnings of the valid simulations are
+/// logged.
+fn load_simulations(dir: &Path) -> (Simulations, Vec<Report>) {
+    let dirs = match validation::scenario_dirs(dir) {
+        Ok(dirs) => dirs,
+        Err(err) => {
+            error!(
+                "failed to read simulations directory {}: {err}",
+                dir.display()
+            );
+            return (Simulations::new(), Vec::new());
+        }
+    };
+
+    let mut simulations = Simulations::new();
+    let mut invalid = Vec::new();
+    for dir in dirs {
+        match Simulation::try_from_directory(&dir) {
+            Ok((simulation, report)) => {
+                for warning in report.warnings() {
+                    warn!("simulation '{}': {warning}", report.name);
+                }
+                simulations.insert(simulation.name.clone(), simulation);
+            }
+            Err(report) => {
+                error!("{report}");
+                invalid.push(report);
+            }
+        }
+    }
+
+    (simulations, invalid)
+}
+
 #[derive(Debug, Clone)]
 pub struct Simulation {
     pub name: String,
@@ -269,21 +301,114 @@ pub struct Simulation {
     pub formation_group: FormationGroup,
     // pub sdf: Handle<Image>,
     pub sdf: Sdf,
+    pub distance_field: ObstacleDistanceField,
     // pub raw: Raw,
 }
 
+impl Simulation {
+    /// Load and validate a simulation from a directory containing a
+    /// `config.toml`, `environment.yaml` and `formation.yaml` file, together
+    /// with the warnings found while validating it.
+    ///
+    /// # Errors
+    ///
+    /// A report of every issue found, if any of the files are missing or
+    /// cannot be parsed, or the simulation has an error, see
+    /// [`validation::check`]
+    pub fn try_from_directory(dir: impl AsRef<Path>) -> Result<(Self, Report), Report> {
+        let scenario = validation::read_scenario(dir.as_ref())?;
+        let obstacles = validation::rasterise(&scenario.environment).map_err(|issue| Report {
+            name:   scenario.name.clone(),
+            issues: vec![issue],
+        })?;
+        let report = Report {
+            name:   scenario.name.clone(),
+            issues: validation::check(&scenario, &obstacles),
+        };
+        if !report.is_valid() {
+            return Err(report);
+        }
+
+        let validation::Scenario {
+            name,
+            config,
+            environment,
+            formation_group,
+        } = scenario;
+        let simulation =
+            Self::with_obstacles(name, config, environment, formation_group, &obstacles);
+        Ok((simulation, report))
+    }
+
+    /// Create a simulation from its already parsed parts, generating the
+    /// blurred sdf image and the exact signed distance field of the
+    /// environment.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sdf of the environment could not be generated
+    pub fn new(
+        name: String,
+        config: Config,
+        environment: Environment,
+        formation_group: FormationGroup,
+    ) -> Self {
+        let image = validation::rasterise(&environment)
+            .unwrap_or_else(|issue| panic!("failed to generate the sdf of {name}: {issue}"));
+        Self::with_obstacles(name, config, environment, formation_group, &image)
+    }
+
+    /// Create a simulation from its already parsed parts, and the image of the
+    /// obstacles of the environment, see [`validation::rasterise`]
+    fn with_obstacles(
+        name: String,
+        config: Config,
+        environment: Environment,
+        formation_group: FormationGroup,
+        image: &RawImage,
+    ) -> Self {
+        let settings = &environment.tiles.settings.sdf;
+        let tile_size = environment.tile_size();
+        let resolution = env_to_png::PixelsPerTile::new(settings.resolution);
+
+        let sdf_image_buffer = env_to_png::blur_image(
+            image,
+            resolution,
+            env_to_png::Percentage::new(settings.blur),
+        );
+        let clamp = settings.clamp_distance(tile_size);
+        let distances =
+            env_to_png::image_to_distance_field(image, tile_size / resolution.get() as f32, clamp);
+
+        Self {
+            name,
+            config,
+            environment,
+            formation_group,
+            sdf: Sdf(sdf_image_buffer.into()),
+            distance_field: ObstacleDistanceField(Arc::new(DistanceField::new(distances, clamp))),
+        }
+    }
+}
+
 #[derive(Debug, Resource)]
 pub struct SimulationManager {
     // _phantom_data: PhantomData<()>,
-    // simulations_dir: std::path::PathBuf,
+    /// Directory with a subdirectory for every simulation
+    simulations_dir: PathBuf,
     // names: Vec<String>,
     names: Vec<SmolStr>,
     simulations: Vec<Simulation>,
+    /// The simulations that could not be loaded
+    invalid: Vec<InvalidSimulation>,
     // simulations: Simulations,
     active: Option<usize>,
     // reload_requested: Option<()>,
     requests: VecDeque<Request>,
     simulations_loaded: usize,
+    /// Whether the files of the active simulation have changed on disk since
+    /// it was loaded
+    active_changed_on_disk: bool,
 }
 
 // impl FromWorld for SimulationManager {
@@ -347,17 +472,45 @@ pub struct SimulationManager {
 //     }
 // }
 
+/// A simulation in the simulations directory that could not be loaded
+#[derive(Debug, Clone)]
+pub struct InvalidSimulation {
+    pub name:   SmolStr,
+    /// The errors found in the simulation, one per line
+    pub reason: String,
+}
+
+impl From<Report> for InvalidSimulation {
+    fn from(report: Report) -> Self {
+        Self {
+            name:   report.name.as_str().into(),
+            reason: report
+                .errors()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Request {
     LoadInitial,
     Load(SimulationId),
     Reload,
+    /// Load the simulation again, after its files have changed on disk
+    Restart(SimulationId),
     End,
 }
 
 impl SimulationManager {
     #[must_use]
-    fn new(simulations: Simulations, initial: Option<String>) -> Self {
+    fn new(
+        simulations_dir: PathBuf,
+        simulations: Simulations,
+        invalid: Vec<Report>,
+        initial: Option<String>,
+    ) -> Self {
         let names: Vec<SmolStr> = simulations.keys().cloned().map(Into::into).collect();
         let simulations = simulations.into_values().collect();
 
@@ -370,12 +523,15 @@ impl SimulationManager {
 
         let active = Some(initial_index);
         Self {
+            simulations_dir,
             names,
             simulations,
+            invalid: invalid.into_iter().map(Into::into).collect(),
             active,
             // active: None,
             requests,
             simulations_loaded: 0,
+            active_changed_on_disk: false,
         }
     }
 
@@ -405,6 +561,16 @@ impl SimulationManager {
             .zip(self.names.iter().map(Clone::clone))
     }
 
+    /// The directory the files of the simulation with the given name are in
+    pub fn directory_of(&self, name: &str) -> PathBuf {
+        self.simulations_dir.join(name)
+    }
+
+    /// The simulations that could not be loaded, and why
+    pub fn invalid(&self) -> &[InvalidSimulation] {
+        &self.invalid
+    }
+
     pub fn reload(&mut self) {
         // let Some(active_simulation_id) = self.active else {
         //     return;
@@ -420,6 +586,96 @@ impl SimulationManager {
         // }
     }
 
+    /// Whether the files of the active simulation have changed on disk since
+    /// it was loaded, see [`SimulationManager::restart`]
+    pub const fn active_changed_on_disk(&self) -> bool {
+        self.active_changed_on_disk
+    }
+
+    /// Load the active simulation again with the changes made to its files on
+    /// disk, keeping the camera and the draw settings
+    pub fn restart(&mut self) {
+        if let Some(active) = self.active {
+            self.requests
+                .push_back(Request::Restart(SimulationId(active)));
+        }
+    }
+
+    /// Load the simulation with the given name again, after its files were
+    /// written by the simulator itself, e.g. by the environment editor. Like a
+    /// change on disk, the active simulation is only applied when it is
+    /// restarted, see [`SimulationManager::restart`]
+    ///
+    /// # Errors
+    ///
+    /// Returns the report of the simulation if it is not valid, keeping its
+    /// previous version
+    pub fn reload_from_disk(&mut self, name: &str) -> Result<(), Report> {
+        let (simulation, _) = Simulation::try_from_directory(&self.directory_of(name))?;
+        if self.update(simulation) {
+            self.active_changed_on_disk = true;
+        }
+        Ok(())
+    }
+
+    /// Replace the simulation with the same name, or insert it if it is new.
+    /// Returns `true` if it is the active simulation
+    fn update(&mut self, simulation: Simulation) -> bool {
+        self.invalid
+            .retain(|invalid| invalid.name.as_str() != simulation.name);
+
+        match self
+            .names
+            .binary_search_by(|name| name.as_str().cmp(simulation.name.as_str()))
+        {
+            Ok(index) => {
+                self.simulations[index] = simulation;
+                self.active == Some(index)
+            }
+            Err(index) => {
+                self.names.insert(index, simulation.name.as_str().into());
+                self.simulations.insert(index, simulation);
+
+                // keep the ids after the inserted simulation pointing to the same simulation
+                let shift = |id: &mut usize| {
+                    if *id >= index {
+                        *id += 1;
+                    }
+                };
+                if let Some(active) = self.active.as_mut() {
+                    shift(active);
+                }
+                for request in &mut self.requests {
+                    if let Request::Load(SimulationId(id)) | Request::Restart(SimulationId(id)) =
+                        request
+                    {
+                        shift(id);
+                    }
+                }
+                false
+            }
+        }
+    }
+
+    /// Record that the simulation in the report could not be loaded. A
+    /// simulation that was valid before keeps its last valid version.
+    /// Returns `true` if it was valid before
+    fn invalidate(&mut self, report: Report) -> bool {
+        let invalid = InvalidSimulation::from(report);
+        if self.names.contains(&invalid.name) {
+            return true;
+        }
+
+        match self
+            .invalid
+            .binary_search_by(|other| other.name.cmp(&invalid.name))
+        {
+            Ok(index) => self.invalid[index] = invalid,
+            Err(index) => self.invalid.insert(index, invalid),
+        }
+        false
+    }
+
     pub fn load(&mut self, id: SimulationId) {
         // self.active = Some(id.0);
         self.requests.push_back(Request::Load(id));
@@ -495,6 +751,12 @@ pub struct ReloadSimulation(pub SimulationId);
 #[derive(Event)]
 pub struct EndSimulation(pub SimulationId);
 
+/// Sent together with [`LoadSimulation`] when the active simulation is loaded
+/// again after its files have changed on disk, see
+/// [`SimulationManager::restart`]
+#[derive(Event)]
+pub struct RestartSimulation(pub SimulationId);
+
 #[derive(Event)]
 pub struct SaveSettings;
 
@@ -597,6 +859,7 @@ fn handle_requests(
     mut evw_load_simulation: EventWriter<LoadSimulation>,
     mut evw_reload_simulation: EventWriter<ReloadSimulation>,
     mut evw_end_simulation: EventWriter<EndSimulation>,
+    mut evw_restart_simulation: EventWriter<RestartSimulation>,
     mut evw_toast: EventWriter<ToastEvent>,
     mut time_virtual: ResMut<Time<Virtual>>,
     mut time_fixed: ResMut<Time<Fixed>>,
@@ -605,6 +868,7 @@ fn handle_requests(
     // mut variable_timesteps: ResMut<VariableTimesteps>,
     mut environment: ResMut<Environment>,
     mut sdf: ResMut<Sdf>,
+    mut distance_field: ResMut<ObstacleDistanceField>,
     // mut raw: ResMut<Raw>,
     mut rng: ResMut<bevy_rand::prelude::GlobalEntropy<bevy_prng::WyRand>>,
     reloadable_entities: Query<Entity, With<Reloadable>>,
@@ -630,20 +894,27 @@ fn handle_requests(
             warn!("simulation already loaded with id: {}", id.0);
             evw_toast.send(ToastEvent::warning("simulation already loaded"));
         }
-        Request::Load(id) => {
+        Request::Load(id) | Request::Restart(id) => {
             for entity in &reloadable_entities {
                 // commands.entity(entity).despawn_recursive();
                 commands.entity(entity).despawn();
             }
             simulation_manager.active = Some(id.0);
+            simulation_manager.active_changed_on_disk = false;
             // load config
 
+            let draw = config.visualisation.draw;
+            *config = simulation_manager.simulations[id.0].config.clone();
+            if matches!(request, Request::Restart(_)) {
+                config.visualisation.draw = draw;
+                evw_restart_simulation.send(RestartSimulation(id));
+            }
             // app.insert_resource(Time::<Fixed>::from_hz(hz))
             *time_fixed = Time::<Fixed>::from_hz(config.simulation.hz);
-            *config = simulation_manager.simulations[id.0].config.clone();
             // config.simulation.t0 =
             *environment = simulation_manager.simulations[id.0].environment.clone();
             *sdf = simulation_manager.simulations[id.0].sdf.clone();
+            *distance_field = simulation_manager.simulations[id.0].distance_field.clone();
 
             time_virtual.set_relative_speed(config.simulation.time_scale.get());
             // *raw = simulation_manager.simulations[id.0].raw.clone();
@@ -656,8 +927,14 @@ fn handle_requests(
             simulation_manager.simulations_loaded += 1;
             let simulation_name = &simulation_manager.names[id.0];
 
+            let verb = if matches!(request, Request::Restart(_)) {
+                "restarted"
+            } else {
+                "loaded"
+            };
+
             evw_toast.send(ToastEvent {
-                caption: format!("simulation loaded: {}", simulation_name),
+                caption: format!("simulation {verb}: {}", simulation_name),
                 options: ToastOptions {
                     level: ToastLevel::Success,
                     show_progress_bar: false,
@@ -710,7 +987,7 @@ fn handle_requests(
     }
 
     match request {
-        Request::Load(_) | Request::Reload => {
+        Request::Load(_) | Request::Restart(_) | Request::Reload => {
             let is_paused = time_virtual.is_paused();
             // let relative_speed = time_virtual.
 
@@ -739,16 +1016,25 @@ fn load_next_simulation(mut simulation_manager: ResMut<SimulationManager>) {
 
 /// Save the current state of the `Config` resource to the config.toml of the
 /// current scenario from which it was originally loaded from
-fn save_settings(mut simulation_manager: ResMut<SimulationManager>, config: Res<Config>) {
+fn save_settings(
+    mut simulation_manager: ResMut<SimulationManager>,
+    watcher: Option<ResMut<SimulationsWatcher>>,
+    config: Res<Config>,
+) {
     let Some(name) = simulation_manager.active_name() else {
         return;
     };
 
-    let dir = std::path::Path::new(SIMULATIONS_DIR).join(name);
+    let dir = simulation_manager.directory_of(name);
 
     // serialize to toml
     let toml = toml::to_string_pretty(config.as_ref()).unwrap();
     std::fs::write(dir.join("config.toml"), toml).unwrap();
+    // the saved config is already the one in use, so it is not a change to offer a
+    // restart for
+    if let Some(mut watcher) = watcher {
+        watcher.acknowledge(&dir);
+    }
 
     // update the simulation manager instance of the config object, such that if the
     // user loads another scenario, and then this, the current, again the changes
@@ -757,3 +1043,159 @@ fn save_settings(mut simulation_manager: ResMut<SimulationManager>, config: Res<
     simulation_manager.simulations[ix].config = config.clone();
     info!("saved settings to: {}", dir.join("config.toml").display());
 }
+
+/// Polls the files of every simulation in the simulations directory for
+/// changes, by their modification time
+#[derive(Debug, Resource)]
+pub(crate) struct SimulationsWatcher {
+    dir:      PathBuf,
+    /// When the files of each simulation directory were last modified
+    modified: BTreeMap<PathBuf, SystemTime>,
+}
+
+impl SimulationsWatcher {
+    fn new(dir: PathBuf) -> Self {
+        let mut watcher = Self {
+            dir,
+            modified: BTreeMap::new(),
+        };
+        // the simulations have just been loaded, so only the changes made after
+        // this are of interest
+        let _ = watcher.changed();
+        watcher
+    }
+
+    /// The simulation directories with files that have changed since the last
+    /// call
+    fn changed(&mut self) -> Vec<PathBuf> {
+        let Ok(dirs) = validation::scenario_dirs(&self.dir) else {
+            return Vec::new();
+        };
+
+        dirs.into_iter()
+            .filter(|dir| {
+                last_modified(dir).is_some_and(|modified| {
+                    self.modified.insert(dir.clone(), modified) != Some(modified)
+                })
+            })
+            .collect()
+    }
+
+    /// Record the files in `dir` as unchanged, e.g. after writing to them
+    pub(crate) fn acknowledge(&mut self, dir: &Path) {
+        if let Some(modified) = last_modified(dir) {
+            self.modified.insert(dir.to_path_buf(), modified);
+        }
+    }
+}
+
+/// The most recent modification time of the files of the simulation in `dir`
+fn last_modified(dir: &Path) -> Option<SystemTime> {
+    [
+        validation::CONFIG_FILE,
+        validation::ENVIRONMENT_FILE,
+        validation::FORMATION_FILE,
+    ]
+    .into_iter()
+    .filter_map(|file| {
+        std::fs::metadata(dir.join(file))
+            .and_then(|m| m.modified())
+            .ok()
+    })
+    .max()
+}
+
+/// Load the simulations whose files have changed on disk again, regenerating
+/// their sdf. The active simulation is not restarted right away, as that would
+/// throw away the current run, instead a restart is offered in the settings
+/// panel, see [`SimulationManager::restart`]
+fn watch_simulations_dir(
+    mut watcher: ResMut<SimulationsWatcher>,
+    mut simulation_manager: ResMut<SimulationManager>,
+    mut evw_toast: EventWriter<ToastEvent>,
+) {
+    for dir in watcher.changed() {
+        match Simulation::try_from_directory(&dir) {
+            Ok((simulation, report)) => {
+                for warning in report.warnings() {
+                    warn!("simulation '{}': {warning}", report.name);
+                }
+                let name = simulation.name.clone();
+                info!("simulation '{name}' changed on disk, loaded it again");
+
+                if simulation_manager.update(simulation) {
+                    simulation_manager.active_changed_on_disk = true;
+                    evw_toast.send(ToastEvent {
+                        caption: format!(
+                            "simulation '{name}' changed on disk, restart it from the settings \
+                             panel to apply the changes"
+                        ),
+                        options: ToastOptions {
+                            level: ToastLevel::Info,
+                            duration: Some(Duration::from_secs(10)),
+                            ..Default::default()
+                        },
+                    });
+                } else {
+                    evw_toast.send(ToastEvent::info(format!(
+                        "simulation '{name}' changed on disk"
+                    )));
+                }
+            }
+            Err(report) => {
+                error!("{report}");
+                let name = report.name.clone();
+                let caption = if simulation_manager.invalidate(report) {
+                    format!("simulation '{name}' has errors, keeping its previous version")
+                } else {
+                    format!("simulation '{name}' has errors")
+                };
+                evw_toast.send(ToastEvent::error(caption));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn watcher_notices_changed_simulations() {
+        let dir = std::env::temp_dir().join(format!("magics-watcher-{}", std::process::id()));
+        let scenario = dir.join("scenario");
+        std::fs::create_dir_all(&scenario).expect("can create the simulation directory");
+        let touch = |file: &str, secs: u64| {
+            let file = std::fs::File::create(scenario.join(file)).expect("can create the file");
+            file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+                .expect("can set the modification time");
+        };
+        touch(validation::CONFIG_FILE, 1);
+        touch(validation::ENVIRONMENT_FILE, 2);
+        touch(validation::FORMATION_FILE, 3);
+
+        let mut watcher = SimulationsWatcher::new(dir.clone());
+        assert!(
+            watcher.changed().is_empty(),
+            "nothing changed since it was created"
+        );
+
+        touch(validation::ENVIRONMENT_FILE, 4);
+        assert_eq!(watcher.changed(), vec![scenario.clone()]);
+        assert!(
+            watcher.changed().is_empty(),
+            "a change is only reported once"
+        );
+
+        touch(validation::CONFIG_FILE, 5);
+        watcher.acknowledge(&scenario);
+        assert!(
+            watcher.changed().is_empty(),
+            "an acknowledged change is not reported"
+        );
+
+        std::fs::remove_dir_all(&dir).expect("can remove the simulation directory");
+    }
+}
@@ -0,0 +1,184 @@
+//! Override individual fields of a serializable value, by their key path.
+//!
+//! A key path is a `.` separated list of keys, matching the names used in the
+//! configuration files, e.g. `gbp.iteration-schedule.internal`. Elements of a
+//! list are selected by their index, e.g. `formations.0.robots`, or all of
+//! them at once with `*`, e.g. `formations.*.robots`.
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Error returned when a key path could not be used to override a value
+#[derive(Debug, thiserror::Error)]
+pub enum KeyPathError {
+    #[error("key path '{path}' is empty")]
+    Empty { path: String },
+    #[error("key path '{path}' does not exist, no key '{key}'")]
+    NoSuchKey { path: String, key: String },
+    #[error("key path '{path}' does not exist, index {index} is out of bounds")]
+    IndexOutOfBounds { path: String, index: usize },
+    #[error("key path '{path}' does not exist, '{key}' is not a table or a list")]
+    NotAContainer { path: String, key: String },
+    #[error("failed to (de)serialize while overriding '{path}': {source}")]
+    Serde {
+        path:   String,
+        source: serde_json::Error,
+    },
+}
+
+/// Set the field at `path` in `value` to `new`.
+///
+/// # Errors
+///
+/// Returns an error if `path` does not point to an existing field.
+/// New fields are never created, as a typo in a key path would otherwise go
+/// unnoticed.
+pub fn set(
+    value: &mut serde_json::Value,
+    path: &str,
+    new: &serde_json::Value,
+) -> Result<(), KeyPathError> {
+    if path.is_empty() {
+        return Err(KeyPathError::Empty {
+            path: path.to_string(),
+        });
+    }
+
+    let keys = path.split('.').collect::<Vec<_>>();
+    set_recursive(value, path, &keys, new)
+}
+
+fn set_recursive(
+    value: &mut serde_json::Value,
+    path: &str,
+    keys: &[&str],
+    new: &serde_json::Value,
+) -> Result<(), KeyPathError> {
+    let Some((&key, rest)) = keys.split_first() else {
+        *value = new.clone();
+        return Ok(());
+    };
+
+    match value {
+        serde_json::Value::Object(map) => {
+            let child = map.get_mut(key).ok_or_else(|| KeyPathError::NoSuchKey {
+                path: path.to_string(),
+                key:  key.to_string(),
+            })?;
+            set_recursive(child, path, rest, new)
+        }
+        serde_json::Value::Array(list) if key == "*" => list
+            .iter_mut()
+            .try_for_each(|child| set_recursive(child, path, rest, new)),
+        serde_json::Value::Array(list) => {
+            let index = key.parse::<usize>().map_err(|_| KeyPathError::NoSuchKey {
+                path: path.to_string(),
+                key:  key.to_string(),
+            })?;
+            let child = list.get_mut(index).ok_or(KeyPathError::IndexOutOfBounds {
+                path: path.to_string(),
+                index,
+            })?;
+            set_recursive(child, path, rest, new)
+        }
+        _ => Err(KeyPathError::NotAContainer {
+            path: path.to_string(),
+            key:  key.to_string(),
+        }),
+    }
+}
+
+/// Return a copy of `target` with every `(key path, value)` pair in
+/// `overrides` applied.
+///
+/// The value is round tripped through its serialized form, so the result is
+/// validated exactly like a value read from a configuration file.
+///
+/// # Errors
+///
+/// Returns an error if a key path does not exist, or if the overridden value
+/// can not be deserialized into `T`, e.g. because of a wrong type.
+pub fn apply<T>(target: &T, overrides: &[(String, serde_json::Value)]) -> Result<T, KeyPathError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let mut value = serde_json::to_value(target).map_err(|source| KeyPathError::Serde {
+        path: String::new(),
+        source,
+    })?;
+
+    for (path, new) in overrides {
+        set(&mut value, path, new)?;
+    }
+
+    serde_json::from_value(value).map_err(|source| KeyPathError::Serde {
+        path: overrides
+            .iter()
+            .map(|(path, _)| path.as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn set_nested_key() {
+        let mut value = json!({ "gbp": { "iteration-schedule": { "internal": 10 } } });
+        set(&mut value, "gbp.iteration-schedule.internal", &json!(50)).unwrap();
+        assert_eq!(
+            value,
+            json!({ "gbp": { "iteration-schedule": { "internal": 50 } } })
+        );
+    }
+
+    #[test]
+    fn set_list_elements() {
+        let mut value = json!({ "formations": [{ "robots": 1 }, { "robots": 2 }] });
+        set(&mut value, "formations.1.robots", &json!(5)).unwrap();
+        assert_eq!(
+            value,
+            json!({ "formations": [{ "robots": 1 }, { "robots": 5 }] })
+        );
+
+        set(&mut value, "formations.*.robots", &json!(8)).unwrap();
+        assert_eq!(
+            value,
+            json!({ "formations": [{ "robots": 8 }, { "robots": 8 }] })
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_not_created() {
+        let mut value = json!({ "robot": { "radius": 1.0 } });
+        assert!(matches!(
+            set(&mut value, "robot.raduis", &json!(2.0)),
+            Err(KeyPathError::NoSuchKey { .. })
+        ));
+        assert!(matches!(
+            set(&mut value, "robot.radius.min", &json!(2.0)),
+            Err(KeyPathError::NotAContainer { .. })
+        ));
+        assert_eq!(value, json!({ "robot": { "radius": 1.0 } }));
+    }
+
+    #[test]
+    fn apply_to_config() {
+        let config = gbp_config::Config::default();
+        let overrides = vec![
+            ("gbp.iteration-schedule.internal".to_string(), json!(42)),
+            ("simulation.prng-seed".to_string(), json!(7)),
+        ];
+        let overridden = apply(&config, &overrides).unwrap();
+        assert_eq!(overridden.gbp.iteration_schedule.internal, 42);
+        assert_eq!(overridden.simulation.prng_seed, 7);
+
+        let wrong_type = vec![("simulation.prng-seed".to_string(), json!("seven"))];
+        assert!(apply(&config, &wrong_type).is_err());
+    }
+}
@@ -0,0 +1,406 @@
+//! Parameter sweeps, i.e. running a scenario many times headless with
+//! different seeds and configuration values.
+//!
+//! Every run is executed in its own child process of the same executable, in
+//! parallel across cores, on a copy of the scenario with its overrides
+//! applied. A panic aborts the process in release builds, so the child process
+//! is what keeps a panicking run from bringing down the rest of the sweep. The
+//! scenario files themselves are never modified. One export is written per run,
+//! together with an `index.json` file mapping each run to its seed, overrides
+//! and export. See [`spec`] for the format of the sweep spec.
+
+pub mod key_path;
+pub mod spec;
+
+use std::{
+    collections::BTreeMap,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use anyhow::Context;
+use bevy::{
+    app::AppExit,
+    ecs::event::{Events, ManualEventReader},
+    log::LogPlugin,
+    prelude::*,
+};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+pub use self::spec::{Run, SweepSpec};
+use crate::{
+    export::{events::Exported, ExportPlugin, ExportSaveLocation, ExportSavePostfix},
+    headless::HeadlessPlugins,
+    simulation_loader::{InitialSimulation, Simulation, SimulationLoaderPlugin},
+    validation,
+};
+
+/// Name of the index file written to the output directory of a sweep
+pub const INDEX_FILENAME: &str = "index.json";
+
+/// Options for running a sweep, in addition to the sweep spec
+#[derive(Debug, Clone)]
+pub struct SweepOptions {
+    /// Path of the sweep spec, read again by the process of every run
+    pub spec_path: PathBuf,
+    /// Directory containing the scenario named in the spec
+    pub simulations_dir: PathBuf,
+    /// Overrides the output directory of the spec
+    pub output_dir: Option<PathBuf>,
+    /// Number of runs to execute in parallel
+    pub jobs: NonZeroUsize,
+    /// Overwrite the results of a previous sweep in the output directory
+    pub force: bool,
+    /// Only print the runs, without executing them
+    pub dry_run: bool,
+}
+
+/// Entry of a run in the index file
+#[derive(Debug, Serialize)]
+struct RunRecord {
+    id: usize,
+    seed: u64,
+    config: BTreeMap<String, serde_json::Value>,
+    formation: BTreeMap<String, serde_json::Value>,
+    /// Path of the export, relative to the index file.
+    /// `None` if the run failed.
+    export: Option<PathBuf>,
+    /// Why the run failed, `None` if it succeeded
+    error: Option<String>,
+    /// Wall-clock duration of the run in seconds
+    duration: f64,
+}
+
+/// Outcome of a run, printed as JSON to stdout by the process of the run
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum RunOutcome {
+    /// The path of the export
+    Exported(PathBuf),
+    /// Why the run failed
+    Failed(String),
+}
+
+/// The index file written to the output directory of a sweep
+#[derive(Debug, Serialize)]
+struct Index<'a> {
+    spec: &'a SweepSpec,
+    runs: Vec<RunRecord>,
+}
+
+/// Run every run of the sweep described by `spec`.
+///
+/// # Errors
+///
+/// Returns an error if the scenario can not be found, an override does not
+/// apply to the scenario, the output directory already contains the results
+/// of another sweep, or the index file could not be written.
+/// A failing run is recorded in the index file, and does not stop the sweep.
+pub fn run(spec: &SweepSpec, options: &SweepOptions) -> anyhow::Result<()> {
+    let (scenario, report) = load_scenario(spec, options)?;
+    for warning in report.warnings() {
+        eprintln!("{}: {warning}", "warning".yellow());
+    }
+
+    let runs = spec.runs();
+    // Apply every override up front, so a typo in a key path is caught before
+    // hours have been spent on the runs before it. The simulations are not kept
+    // around, as each of them holds its own copy of the sdf image.
+    for run in &runs {
+        simulation_for_run(&scenario, run)?;
+    }
+
+    let output_dir = options
+        .output_dir
+        .clone()
+        .unwrap_or_else(|| spec.output_dir());
+
+    if options.dry_run {
+        for run in &runs {
+            println!("{} {}", run.name().green().bold(), describe(run));
+        }
+        return Ok(());
+    }
+
+    let index_path = output_dir.join(INDEX_FILENAME);
+    anyhow::ensure!(
+        options.force || !index_path.exists(),
+        "{} already exists, use --force to overwrite",
+        index_path.display()
+    );
+    std::fs::create_dir_all(&output_dir)
+        .with_context(|| format!("failed to create {}", output_dir.display()))?;
+
+    eprintln!(
+        "{}: running {} runs of '{}' with {} jobs",
+        "info".green(),
+        runs.len(),
+        spec.scenario,
+        options.jobs
+    );
+
+    let next_run = AtomicUsize::new(0);
+    let records = Mutex::new(Vec::with_capacity(runs.len()));
+    std::thread::scope(|scope| {
+        for _ in 0..options.jobs.get() {
+            scope.spawn(|| loop {
+                let i = next_run.fetch_add(1, Ordering::Relaxed);
+                let Some(run) = runs.get(i) else {
+                    break;
+                };
+
+                let seed = run.seed.unwrap_or(scenario.config.simulation.prng_seed);
+                let record = execute(run, seed, options, &output_dir);
+                match record.error {
+                    Some(ref error) => {
+                        eprintln!("{}: {} failed: {}", "error".red(), run.name(), error);
+                    }
+                    None => eprintln!(
+                        "{}: {} finished in {:.1}s {}",
+                        "info".green(),
+                        run.name(),
+                        record.duration,
+                        describe(run)
+                    ),
+                }
+
+                records
+                    .lock()
+                    .expect("no run panics while holding the lock")
+                    .push(record);
+            });
+        }
+    });
+
+    let mut records = records.into_inner().expect("all runs have finished");
+    records.sort_by_key(|record| record.id);
+    let failed = records
+        .iter()
+        .filter(|record| record.error.is_some())
+        .count();
+
+    let index = Index {
+        spec,
+        runs: records,
+    };
+    let json = serde_json::to_string_pretty(&index)?;
+    std::fs::write(&index_path, json)
+        .with_context(|| format!("failed to write {}", index_path.display()))?;
+
+    eprintln!(
+        "{}: sweep finished, {} of {} runs failed, index written to {}",
+        if failed == 0 {
+            "info".green()
+        } else {
+            "warn".yellow()
+        },
+        failed,
+        runs.len(),
+        index_path.display()
+    );
+
+    Ok(())
+}
+
+/// Execute the run with `id` of the sweep described by `spec` in this
+/// process, and print its outcome to stdout for the process running the
+/// sweep, see [`run`]
+///
+/// # Errors
+///
+/// Returns an error if the scenario can not be loaded, or the sweep has no
+/// run with `id`. A failing run is printed as the outcome instead.
+pub fn run_single(spec: &SweepSpec, options: &SweepOptions, id: usize) -> anyhow::Result<()> {
+    let (scenario, _) = load_scenario(spec, options)?;
+    let run = spec
+        .runs()
+        .into_iter()
+        .find(|run| run.id == id)
+        .with_context(|| format!("the sweep has no run with id {id}"))?;
+    let output_dir = options
+        .output_dir
+        .clone()
+        .unwrap_or_else(|| spec.output_dir());
+
+    let outcome = match simulation_for_run(&scenario, &run)
+        .and_then(|simulation| run_headless(&run, simulation, &output_dir))
+    {
+        Ok(path) => RunOutcome::Exported(path),
+        Err(err) => RunOutcome::Failed(format!("{err:#}")),
+    };
+    println!("{}", serde_json::to_string(&outcome)?);
+
+    Ok(())
+}
+
+/// Load the scenario of the sweep, together with the warnings found while
+/// validating it
+fn load_scenario(
+    spec: &SweepSpec,
+    options: &SweepOptions,
+) -> anyhow::Result<(Simulation, validation::Report)> {
+    let scenario_dir = options.simulations_dir.join(&spec.scenario);
+    anyhow::ensure!(
+        scenario_dir.is_dir(),
+        "scenario '{}' does not exist in {}",
+        spec.scenario,
+        options.simulations_dir.display()
+    );
+    Simulation::try_from_directory(&scenario_dir).map_err(|report| anyhow::anyhow!("{report}"))
+}
+
+/// Copy of `scenario` with the seed and overrides of `run` applied
+fn simulation_for_run(scenario: &Simulation, run: &Run) -> anyhow::Result<Simulation> {
+    let mut simulation = scenario.clone();
+    simulation.config = key_path::apply(&scenario.config, &run.config)
+        .with_context(|| format!("failed to override the config of {}", run.name()))?;
+    simulation.formation_group = key_path::apply(&scenario.formation_group, &run.formation)
+        .with_context(|| format!("failed to override the formation of {}", run.name()))?;
+    if let Some(seed) = run.seed {
+        simulation.config.simulation.prng_seed = seed;
+    }
+
+    Ok(simulation)
+}
+
+/// Human readable description of the seed and overrides of a run
+fn describe(run: &Run) -> String {
+    let seed = run.seed.map_or_else(
+        || "seed: scenario".to_string(),
+        |seed| format!("seed: {seed}"),
+    );
+    std::iter::once(seed)
+        .chain(
+            run.config
+                .iter()
+                .chain(run.formation.iter())
+                .map(|(path, value)| format!("{path}: {value}")),
+        )
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Execute a single run in a child process, see [`execute_in_child`]
+fn execute(run: &Run, seed: u64, options: &SweepOptions, output_dir: &Path) -> RunRecord {
+    let started_at = Instant::now();
+    let result = execute_in_child(run, options, output_dir);
+
+    let (export, error) = match result {
+        Ok(path) => (
+            Some(
+                path.strip_prefix(output_dir)
+                    .map_or_else(|_| path.clone(), Path::to_path_buf),
+            ),
+            None,
+        ),
+        Err(err) => (None, Some(format!("{err:#}"))),
+    };
+
+    RunRecord {
+        id: run.id,
+        seed,
+        config: run.config.iter().cloned().collect(),
+        formation: run.formation.iter().cloned().collect(),
+        export,
+        error,
+        duration: started_at.elapsed().as_secs_f64(),
+    }
+}
+
+/// Execute a run in a child process running [`run_single`], such that a
+/// crash of the run does not bring down the other runs of the sweep.
+/// The output of the child process on stderr is passed through.
+fn execute_in_child(
+    run: &Run,
+    options: &SweepOptions,
+    output_dir: &Path,
+) -> anyhow::Result<PathBuf> {
+    let executable = std::env::current_exe().context("failed to find the current executable")?;
+    let output = std::process::Command::new(executable)
+        .arg("--simulations-dir")
+        .arg(&options.simulations_dir)
+        .arg("sweep")
+        .arg(&options.spec_path)
+        .arg("--output-dir")
+        .arg(output_dir)
+        .arg("--run")
+        .arg(run.id.to_string())
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("failed to start the process of {}", run.name()))?;
+
+    // Bevy and the simulation may print to stdout as well, so the outcome is
+    // the last line that parses as one
+    let outcome = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .rev()
+        .find_map(|line| serde_json::from_str::<RunOutcome>(line).ok());
+    match outcome {
+        Some(RunOutcome::Exported(path)) => Ok(path),
+        Some(RunOutcome::Failed(error)) => Err(anyhow::anyhow!(error)),
+        None => anyhow::bail!(
+            "the process of the run exited without an outcome, {}",
+            output.status
+        ),
+    }
+}
+
+/// Build a headless app for the run, and update it until the results have
+/// been exported. Returns the path of the export.
+fn run_headless(run: &Run, simulation: Simulation, output_dir: &Path) -> anyhow::Result<PathBuf> {
+    let simulation_loader = SimulationLoaderPlugin {
+        show_toasts: false,
+        initial_simulation: InitialSimulation::Preloaded(Box::new(simulation)),
+        reload_after: None,
+        ..Default::default()
+    };
+    let export = ExportPlugin {
+        save_at_location: ExportSaveLocation::At(output_dir.to_path_buf()),
+        postfix: ExportSavePostfix::Name(run.name()),
+    };
+
+    let mut app = App::new();
+    // The global logger can only be set once per process, and the output of
+    // parallel runs would be interleaved anyway.
+    app.add_plugins(
+        HeadlessPlugins {
+            simulation_loader,
+            export,
+        }
+        .build()
+        .disable::<LogPlugin>(),
+    );
+    app.finish();
+    app.cleanup();
+
+    // The app is updated manually instead of with `App::run()`, to keep access
+    // to the world after the run has finished.
+    let mut evr_exported = ManualEventReader::<Exported>::default();
+    let mut evr_app_exit = ManualEventReader::<AppExit>::default();
+    loop {
+        app.update();
+
+        if let Some(Exported(path)) = evr_exported
+            .read(app.world.resource::<Events<Exported>>())
+            .last()
+        {
+            return Ok(path.clone());
+        }
+
+        if evr_app_exit
+            .read(app.world.resource::<Events<AppExit>>())
+            .next()
+            .is_some()
+        {
+            anyhow::bail!("the app exited before the results were exported");
+        }
+    }
+}
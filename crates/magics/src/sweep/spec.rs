@@ -0,0 +1,227 @@
+//! The sweep spec, describing which runs a sweep consists of.
+//!
+//! ## Example
+//! ```toml
+//! scenario = "Iteration Amount Experiment"
+//! seeds = [0, 31, 227, 252, 805]
+//! output-dir = "./experiments/iteration-amount"
+//!
+//! [config]
+//! "gbp.iteration-schedule.internal" = [1, 2, 3, 5, 8]
+//! "gbp.iteration-schedule.external" = [1, 2, 3, 5, 8]
+//!
+//! [formation]
+//! "formations.*.robots" = [5, 10, 15]
+//! ```
+//!
+//! Every combination of seed and grid values becomes a run, so the example
+//! above results in 5 * 5 * 5 * 3 = 375 runs.
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// Error returned when a sweep spec could not be read
+#[derive(Debug, thiserror::Error)]
+pub enum ParseError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("TOML error: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("the value grid of '{0}' is empty, it would result in zero runs")]
+    EmptyGrid(String),
+}
+
+/// A grid of values to try for each key path
+pub type ValueGrid = BTreeMap<String, Vec<serde_json::Value>>;
+
+/// Specification of a parameter sweep
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SweepSpec {
+    /// Name of the scenario in the simulations directory to sweep over
+    pub scenario:   String,
+    /// Seeds to run every combination of the value grids with.
+    /// If empty, the `prng-seed` of the scenario is used.
+    #[serde(default)]
+    pub seeds:      Vec<u64>,
+    /// Directory to write the exports and the index file to.
+    /// Defaults to `./experiments/<scenario>`
+    #[serde(default)]
+    pub output_dir: Option<PathBuf>,
+    /// Value grids over fields in the `config.toml` of the scenario
+    #[serde(default)]
+    pub config:     ValueGrid,
+    /// Value grids over fields in the `formation.yaml` of the scenario
+    #[serde(default)]
+    pub formation:  ValueGrid,
+}
+
+impl SweepSpec {
+    /// Attempt to read and parse a sweep spec from a TOML file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file could not be read, is not a valid sweep
+    /// spec, or if any of the value grids are empty
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self, ParseError> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::parse(contents.as_str())
+    }
+
+    /// Attempt to parse a sweep spec from a TOML string
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the string is not a valid sweep spec, or if any of
+    /// the value grids are empty
+    pub fn parse(contents: &str) -> Result<Self, ParseError> {
+        let spec: Self = toml::from_str(contents)?;
+        if let Some((path, _)) = spec
+            .config
+            .iter()
+            .chain(spec.formation.iter())
+            .find(|(_, values)| values.is_empty())
+        {
+            return Err(ParseError::EmptyGrid(path.clone()));
+        }
+
+        Ok(spec)
+    }
+
+    /// Directory to write the exports and the index file to
+    pub fn output_dir(&self) -> PathBuf {
+        self.output_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("./experiments").join(&self.scenario))
+    }
+
+    /// Expand the spec into the cartesian product of its seeds and value grids
+    pub fn runs(&self) -> Vec<Run> {
+        let seeds = if self.seeds.is_empty() {
+            vec![None]
+        } else {
+            self.seeds.iter().copied().map(Some).collect()
+        };
+
+        let config = cartesian_product(&self.config);
+        let formation = cartesian_product(&self.formation);
+
+        let mut runs = Vec::with_capacity(seeds.len() * config.len() * formation.len());
+        for seed in seeds {
+            for config in &config {
+                for formation in &formation {
+                    runs.push(Run {
+                        id: runs.len(),
+                        seed,
+                        config: config.clone(),
+                        formation: formation.clone(),
+                    });
+                }
+            }
+        }
+
+        runs
+    }
+}
+
+/// A `(key path, value)` pair
+pub type Override = (String, serde_json::Value);
+
+/// A single run of a sweep
+#[derive(Debug, Clone)]
+pub struct Run {
+    /// Index of the run in the sweep
+    pub id: usize,
+    /// Seed to use, `None` if the seed of the scenario is used
+    pub seed: Option<u64>,
+    /// Overrides of the `config.toml` of the scenario
+    pub config: Vec<Override>,
+    /// Overrides of the `formation.yaml` of the scenario
+    pub formation: Vec<Override>,
+}
+
+impl Run {
+    /// Name of the run, used as postfix of its export
+    pub fn name(&self) -> String {
+        format!("run-{:04}", self.id)
+    }
+}
+
+/// Every combination of one value for each key path in `grid`.
+/// An empty grid has exactly one combination, with no overrides.
+fn cartesian_product(grid: &ValueGrid) -> Vec<Vec<Override>> {
+    grid.iter()
+        .fold(vec![Vec::new()], |combinations, (path, values)| {
+            combinations
+                .iter()
+                .flat_map(|combination| {
+                    values.iter().map(move |value| {
+                        let mut combination = combination.clone();
+                        combination.push((path.clone(), value.clone()));
+                        combination
+                    })
+                })
+                .collect()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn runs_are_the_cartesian_product() {
+        let spec = SweepSpec::parse(
+            r#"
+            scenario = "Circle Experiment"
+            seeds = [0, 31]
+
+            [config]
+            "gbp.iteration-schedule.internal" = [5, 10, 50]
+
+            [formation]
+            "formations.*.robots" = [5, 10]
+            "#,
+        )
+        .unwrap();
+
+        let runs = spec.runs();
+        assert_eq!(runs.len(), 2 * 3 * 2);
+        assert_eq!(runs[0].seed, Some(0));
+        assert_eq!(runs[0].config, vec![(
+            "gbp.iteration-schedule.internal".to_string(),
+            json!(5)
+        )]);
+        assert_eq!(runs[1].formation, vec![(
+            "formations.*.robots".to_string(),
+            json!(10)
+        )]);
+        assert_eq!(runs.last().unwrap().seed, Some(31));
+        assert!(runs.iter().enumerate().all(|(i, run)| run.id == i));
+    }
+
+    #[test]
+    fn no_seeds_and_no_grids_is_a_single_run() {
+        let spec = SweepSpec::parse(r#"scenario = "Circle Experiment""#).unwrap();
+        let runs = spec.runs();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].seed, None);
+        assert!(runs[0].config.is_empty() && runs[0].formation.is_empty());
+    }
+
+    #[test]
+    fn empty_grids_are_rejected() {
+        let spec = SweepSpec::parse(
+            r#"
+            scenario = "Circle Experiment"
+            [config]
+            "robot.target-speed" = []
+            "#,
+        );
+        assert!(matches!(spec, Err(ParseError::EmptyGrid(_))));
+    }
+}
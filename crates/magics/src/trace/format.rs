@@ -0,0 +1,387 @@
+//! The binary format of a trace file.
+//!
+//! A trace file starts with [`MAGIC`], followed by a [`TraceHeader`] and one
+//! [`TraceStep`] for every fixed timestep of the recorded run, each encoded
+//! with `bincode`. Steps are appended as the run progresses, so a trace of a
+//! run that was killed is still readable up to its last complete step.
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+/// Bytes every trace file starts with
+pub const MAGIC: [u8; 8] = *b"MAGTRACE";
+
+/// Version of the trace format, bumped on every incompatible change
+pub const VERSION: u32 = 1;
+
+/// Everything needed to re-simulate the recorded run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceHeader {
+    /// Version of the trace format
+    pub version:     u32,
+    /// Name of the recorded scenario
+    pub scenario:    String,
+    /// Config of the scenario, as TOML
+    pub config:      String,
+    /// Environment of the scenario, as YAML
+    pub environment: String,
+    /// Formation group of the scenario, as YAML
+    pub formation:   String,
+}
+
+/// A robot spawned during a step
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RobotSpawn {
+    /// Index of the robot in the trace, in order of spawning
+    pub robot:  u32,
+    /// Radius of the robot
+    pub radius: f32,
+    /// Colour the robot is drawn with
+    pub colour: [u8; 3],
+}
+
+/// State of a robot at the end of a step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RobotState {
+    /// Index of the robot in the trace
+    pub robot:    u32,
+    /// Position of the robot
+    pub position: [f32; 2],
+    /// Whether the radio antenna of the robot was active during the step
+    pub comms:    bool,
+    /// Mean of the belief of every variable in the factorgraph of the robot,
+    /// ordered by creation
+    pub beliefs:  Vec<Vec<f64>>,
+}
+
+/// Everything that happened in a single fixed timestep
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct TraceStep {
+    /// Number of the step, starting at 0 when the scenario is loaded
+    pub step:      u64,
+    /// Robots spawned since the previous step
+    pub spawned:   Vec<RobotSpawn>,
+    /// Robots despawned since the previous step
+    pub despawned: Vec<u32>,
+    /// Every robot alive at the end of the step, ordered by index
+    pub robots:    Vec<RobotState>,
+}
+
+/// The first difference found between a re-simulated step and its recording
+#[derive(Debug, Clone, PartialEq, derive_more::Display)]
+pub enum Divergence {
+    /// Different robots were spawned
+    #[display(fmt = "step {step}: robots spawned differ")]
+    Spawned { step: u64 },
+    /// Different robots were despawned
+    #[display(fmt = "step {step}: robots despawned differ")]
+    Despawned { step: u64 },
+    /// A different number of robots are alive
+    #[display(fmt = "step {step}: {actual} robots alive, {expected} recorded")]
+    RobotCount {
+        step:     u64,
+        expected: usize,
+        actual:   usize,
+    },
+    /// A different robot is alive at the same place in the order of robots
+    #[display(fmt = "step {step}: robot {actual} alive, robot {expected} recorded")]
+    Robot {
+        step:     u64,
+        expected: u32,
+        actual:   u32,
+    },
+    /// The comms of a robot were decided differently
+    #[display(fmt = "step {step}: comms of robot {robot} differ")]
+    Comms { step: u64, robot: u32 },
+    /// A robot is at a different position
+    #[display(fmt = "step {step}: position of robot {robot} differs")]
+    Position { step: u64, robot: u32 },
+    /// The re-simulation ended before the end of the trace
+    #[display(fmt = "step {step}: the re-simulation ended before the end of the trace")]
+    Ended { step: u64 },
+    /// The belief of a variable of a robot differs
+    #[display(fmt = "step {step}: belief of variable {variable} of robot {robot} differs")]
+    Belief {
+        step:     u64,
+        robot:    u32,
+        variable: usize,
+    },
+}
+
+/// Bitwise equality, as `0.0 == -0.0` and `NaN != NaN` with `==`
+fn bitwise_eq_f32(a: &[f32], b: &[f32]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.to_bits() == b.to_bits())
+}
+
+/// Bitwise equality, as `0.0 == -0.0` and `NaN != NaN` with `==`
+fn bitwise_eq_f64(a: &[f64], b: &[f64]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.to_bits() == b.to_bits())
+}
+
+impl TraceStep {
+    /// Compare this step bit for bit with the `expected` recording of it.
+    /// Returns the first difference, or `None` if they are identical.
+    pub fn diverges_from(&self, expected: &Self) -> Option<Divergence> {
+        let step = expected.step;
+        let spawned_eq = self.spawned.len() == expected.spawned.len()
+            && self.spawned.iter().zip(&expected.spawned).all(|(a, b)| {
+                a.robot == b.robot
+                    && a.radius.to_bits() == b.radius.to_bits()
+                    && a.colour == b.colour
+            });
+        if !spawned_eq {
+            return Some(Divergence::Spawned { step });
+        }
+        if self.despawned != expected.despawned {
+            return Some(Divergence::Despawned { step });
+        }
+        if self.robots.len() != expected.robots.len() {
+            return Some(Divergence::RobotCount {
+                step,
+                expected: expected.robots.len(),
+                actual: self.robots.len(),
+            });
+        }
+
+        for (actual, expected) in self.robots.iter().zip(&expected.robots) {
+            let robot = expected.robot;
+            if actual.robot != robot {
+                return Some(Divergence::Robot {
+                    step,
+                    expected: robot,
+                    actual: actual.robot,
+                });
+            }
+            if actual.comms != expected.comms {
+                return Some(Divergence::Comms { step, robot });
+            }
+            if !bitwise_eq_f32(&actual.position, &expected.position) {
+                return Some(Divergence::Position { step, robot });
+            }
+            let variable =
+                (0..expected.beliefs.len().max(actual.beliefs.len())).find(|&i| {
+                    match (actual.beliefs.get(i), expected.beliefs.get(i)) {
+                        (Some(a), Some(b)) => !bitwise_eq_f64(a, b),
+                        _ => true,
+                    }
+                });
+            if let Some(variable) = variable {
+                return Some(Divergence::Belief {
+                    step,
+                    robot,
+                    variable,
+                });
+            }
+        }
+
+        None
+    }
+}
+
+/// Writes a trace one step at a time
+pub struct TraceWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> TraceWriter<W> {
+    /// Start a trace by writing the magic bytes and `header`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to `writer` fails
+    pub fn new(mut writer: W, header: &TraceHeader) -> anyhow::Result<Self> {
+        writer.write_all(&MAGIC)?;
+        bincode::serialize_into(&mut writer, header)?;
+        Ok(Self { writer })
+    }
+
+    /// Append a step to the trace
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to the underlying writer fails
+    pub fn write_step(&mut self, step: &TraceStep) -> anyhow::Result<()> {
+        bincode::serialize_into(&mut self.writer, step)?;
+        Ok(())
+    }
+
+    /// Flush the underlying writer
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if flushing the underlying writer fails
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// A trace read back into memory
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    pub header: TraceHeader,
+    pub steps:  Vec<TraceStep>,
+}
+
+impl Trace {
+    /// Read a trace from `reader`. A trailing, partially written step is
+    /// ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `reader` does not contain a trace of the supported
+    /// version, or reading from it fails
+    pub fn read(reader: impl Read) -> anyhow::Result<Self> {
+        let mut reader = BufReader::new(reader);
+        let mut magic = [0; MAGIC.len()];
+        reader
+            .read_exact(&mut magic)
+            .context("failed to read the magic bytes")?;
+        anyhow::ensure!(magic == MAGIC, "not a trace file");
+
+        let header: TraceHeader =
+            bincode::deserialize_from(&mut reader).context("failed to read the trace header")?;
+        anyhow::ensure!(
+            header.version == VERSION,
+            "unsupported trace version {}, expected {}",
+            header.version,
+            VERSION
+        );
+
+        let mut steps = Vec::new();
+        while !reader.fill_buf()?.is_empty() {
+            match bincode::deserialize_from(&mut reader) {
+                Ok(step) => steps.push(step),
+                Err(err) => match *err {
+                    bincode::ErrorKind::Io(ref io)
+                        if io.kind() == std::io::ErrorKind::UnexpectedEof =>
+                    {
+                        // The recording was interrupted in the middle of writing a step
+                        break;
+                    }
+                    _ => {
+                        return Err(err).with_context(|| {
+                            format!("failed to read step {} of the trace", steps.len())
+                        })
+                    }
+                },
+            }
+        }
+
+        Ok(Self { header, steps })
+    }
+
+    /// Read a trace from the file at `path`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file could not be opened, or does not contain a
+    /// valid trace
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        Self::read(file).with_context(|| format!("failed to read trace {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> TraceHeader {
+        TraceHeader {
+            version:     VERSION,
+            scenario:    "test".to_string(),
+            config:      "[simulation]".to_string(),
+            environment: "tiles: []".to_string(),
+            formation:   "formations: []".to_string(),
+        }
+    }
+
+    fn step(step: u64) -> TraceStep {
+        TraceStep {
+            step,
+            spawned: vec![RobotSpawn {
+                robot:  0,
+                radius: 2.0,
+                colour: [1, 2, 3],
+            }],
+            despawned: vec![],
+            robots: vec![RobotState {
+                robot:    0,
+                position: [1.0, -2.5],
+                comms:    true,
+                beliefs:  vec![vec![1.0, 2.0, 0.5, -0.5], vec![1.5, 2.5, 0.5, -0.5]],
+            }],
+        }
+    }
+
+    fn encode(steps: &[TraceStep]) -> Vec<u8> {
+        let mut writer = TraceWriter::new(Vec::new(), &header()).expect("writing to a vec");
+        for step in steps {
+            writer.write_step(step).expect("writing to a vec");
+        }
+        writer.writer
+    }
+
+    #[test]
+    fn roundtrip() {
+        let steps = vec![step(0), step(1), step(2)];
+        let trace = Trace::read(encode(&steps).as_slice()).expect("a valid trace");
+        assert_eq!(trace.header, header());
+        assert_eq!(trace.steps, steps);
+    }
+
+    #[test]
+    fn truncated_step_is_ignored() {
+        let mut bytes = encode(&[step(0), step(1)]);
+        bytes.truncate(bytes.len() - 3);
+        let trace = Trace::read(bytes.as_slice()).expect("a valid trace");
+        assert_eq!(trace.steps, vec![step(0)]);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(Trace::read(b"not a trace at all".as_slice()).is_err());
+    }
+
+    #[test]
+    fn divergence_is_bitwise() {
+        let expected = step(4);
+        assert_eq!(expected.diverges_from(&expected), None);
+
+        let mut actual = expected.clone();
+        actual.robots[0].beliefs[1][2] = 0.5 + f64::EPSILON;
+        assert_eq!(
+            actual.diverges_from(&expected),
+            Some(Divergence::Belief {
+                step:     4,
+                robot:    0,
+                variable: 1,
+            })
+        );
+
+        // Equal with `==`, but not bit for bit
+        let mut actual = expected.clone();
+        actual.robots[0].position[0] = -0.0;
+        let mut expected = expected;
+        expected.robots[0].position[0] = 0.0;
+        assert_eq!(
+            actual.diverges_from(&expected),
+            Some(Divergence::Position { step: 4, robot: 0 })
+        );
+
+        let mut actual = expected.clone();
+        actual.robots[0].comms = false;
+        assert_eq!(
+            actual.diverges_from(&expected),
+            Some(Divergence::Comms { step: 4, robot: 0 })
+        );
+    }
+}
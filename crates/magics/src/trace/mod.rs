@@ -0,0 +1,81 @@
+//! Traces of simulation runs, for replaying them and checking that they are
+//! deterministic.
+//!
+//! A trace records every fixed timestep of a run: which robots spawned and
+//! despawned, whether the comms of each robot were on or off, and the mean of
+//! the belief of every variable of every robot. The header of the trace holds
+//! the scenario the run was started from, so the run can be re-simulated from
+//! the trace alone.
+//!
+//! - [`record`] records traces, with `--record-trace <PATH>`
+//! - [`replay`] scrubs through a recorded trace in the ui, with `replay <PATH>`
+//! - [`verify`] re-simulates a trace headless, and compares it bit for bit to
+//!   the recording, with `replay --verify <PATH>`
+//!
+//! Re-simulation is headless, where every frame advances the simulation by
+//! exactly one fixed timestep. With a window, systems outside of
+//! [`FixedUpdate`](bevy::app::FixedUpdate) run once per rendered frame instead,
+//! so only traces recorded with `--headless` can be expected to verify.
+
+pub mod format;
+pub mod record;
+pub mod replay;
+pub mod verify;
+
+use anyhow::Context;
+use gbp_config::{Config, FormationGroup};
+use gbp_environment::Environment;
+
+pub use self::{
+    format::{Divergence, Trace, TraceHeader, TraceStep},
+    record::{TraceOutput, TraceRecorderPlugin},
+};
+use crate::simulation_loader::Simulation;
+
+impl TraceHeader {
+    /// The simulation the trace was recorded from
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the config, environment or formation group of the
+    /// header could not be parsed
+    pub fn simulation(&self) -> anyhow::Result<Simulation> {
+        let config = Config::parse(&self.config).context("failed to parse the config")?;
+        let environment =
+            Environment::parse(&self.environment).context("failed to parse the environment")?;
+        let formation_group = FormationGroup::parse_from_yaml(&self.formation)
+            .context("failed to parse the formation group")?;
+
+        Ok(Simulation::new(
+            self.scenario.clone(),
+            config,
+            environment,
+            formation_group,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_roundtrips_the_scenario() {
+        let header = TraceHeader {
+            version:     format::VERSION,
+            scenario:    "default".to_string(),
+            config:      toml::to_string(&Config::default()).expect("the default config is valid"),
+            environment: serde_yaml::to_string(&Environment::default())
+                .expect("the default environment is valid"),
+            formation:   serde_yaml::to_string(&FormationGroup::default())
+                .expect("the default formation group is valid"),
+        };
+
+        let simulation = header.simulation().expect("the header is valid");
+        assert_eq!(simulation.name, "default");
+        assert_eq!(
+            simulation.config.simulation.prng_seed,
+            Config::default().simulation.prng_seed
+        );
+    }
+}
@@ -0,0 +1,256 @@
+//! Recording of traces, one [`TraceStep`] per fixed timestep
+
+use std::{collections::HashMap, fs::File, io::BufWriter, path::PathBuf};
+
+use bevy::prelude::*;
+use gbp_config::Config;
+use gbp_environment::Environment;
+
+use super::format::{RobotSpawn, RobotState, TraceHeader, TraceStep, TraceWriter, VERSION};
+use crate::{
+    bevy_utils::run_conditions::time::virtual_time_is_paused,
+    factorgraph::prelude::FactorGraph,
+    planner::robot::{RadioAntenna, Radius, RobotConnections},
+    simulation_loader::{LoadSimulation, ReloadSimulation, SimulationManager},
+    theme::{CatppuccinTheme, ColorAssociation},
+};
+
+/// Where the recorded trace goes
+#[derive(Debug, Clone)]
+pub enum TraceOutput {
+    /// Stream the trace to a file. Every time a simulation is (re)loaded the
+    /// file is overwritten, so it always holds the trace of the latest run.
+    File(PathBuf),
+    /// Keep the recorded steps in memory, to be taken with
+    /// [`TraceRecorder::take_steps`]
+    Memory,
+}
+
+/// **Bevy** [`Plugin`] recording a trace of every fixed timestep of the
+/// loaded simulation
+pub struct TraceRecorderPlugin {
+    pub output: TraceOutput,
+}
+
+impl Plugin for TraceRecorderPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TraceRecorder::new(self.output.clone()))
+            .add_systems(
+                PostUpdate,
+                start_trace
+                    .run_if(on_event::<LoadSimulation>().or_else(on_event::<ReloadSimulation>())),
+            )
+            .add_systems(
+                FixedPostUpdate,
+                record_step.run_if(not(virtual_time_is_paused)),
+            )
+            .add_systems(Last, flush_trace);
+    }
+}
+
+/// Destination of the steps of a trace
+enum Sink {
+    File {
+        path:   PathBuf,
+        writer: Option<TraceWriter<BufWriter<File>>>,
+    },
+    Memory(Vec<TraceStep>),
+}
+
+/// **Bevy** [`Resource`] holding the state of the trace being recorded
+#[derive(Resource)]
+pub struct TraceRecorder {
+    sink:       Sink,
+    /// Whether a simulation has been loaded, and steps are being recorded
+    recording:  bool,
+    /// Index of every robot alive in the trace
+    robots:     HashMap<Entity, u32>,
+    next_robot: u32,
+    step:       u64,
+}
+
+impl TraceRecorder {
+    fn new(output: TraceOutput) -> Self {
+        let sink = match output {
+            TraceOutput::File(path) => Sink::File { path, writer: None },
+            TraceOutput::Memory => Sink::Memory(Vec::new()),
+        };
+        Self {
+            sink,
+            recording: false,
+            robots: HashMap::new(),
+            next_robot: 0,
+            step: 0,
+        }
+    }
+
+    /// Take the steps recorded in memory since the last call.
+    /// Always empty when recording to a file.
+    pub fn take_steps(&mut self) -> Vec<TraceStep> {
+        match self.sink {
+            Sink::Memory(ref mut steps) => std::mem::take(steps),
+            Sink::File { .. } => Vec::new(),
+        }
+    }
+}
+
+/// **Bevy** system starting a new trace whenever a simulation is (re)loaded
+fn start_trace(
+    mut recorder: ResMut<TraceRecorder>,
+    simulation_manager: Res<SimulationManager>,
+    config: Res<Config>,
+    environment: Res<Environment>,
+) {
+    let recorder = recorder.as_mut();
+    recorder.robots.clear();
+    recorder.next_robot = 0;
+    recorder.step = 0;
+    recorder.recording = true;
+
+    match recorder.sink {
+        Sink::File {
+            ref path,
+            ref mut writer,
+        } => {
+            // The header holds the config as it is when the simulation starts, as it can be
+            // changed from the ui before a reload
+            let header = || -> anyhow::Result<TraceHeader> {
+                let formation_group = simulation_manager
+                    .active_formation_group()
+                    .ok_or_else(|| anyhow::anyhow!("no active simulation"))?;
+                Ok(TraceHeader {
+                    version:     VERSION,
+                    scenario:    simulation_manager
+                        .active_name()
+                        .unwrap_or_default()
+                        .to_string(),
+                    config:      toml::to_string(config.as_ref())?,
+                    environment: serde_yaml::to_string(environment.as_ref())?,
+                    formation:   serde_yaml::to_string(formation_group)?,
+                })
+            };
+
+            let started = header().and_then(|header| {
+                let file = File::create(path)?;
+                TraceWriter::new(BufWriter::new(file), &header)
+            });
+            match started {
+                Ok(started) => {
+                    info!("recording trace to {}", path.display());
+                    *writer = Some(started);
+                }
+                Err(err) => {
+                    error!("failed to start trace {}: {err:#}", path.display());
+                    *writer = None;
+                    recorder.recording = false;
+                }
+            }
+        }
+        Sink::Memory(ref mut steps) => steps.clear(),
+    }
+}
+
+/// **Bevy** system recording the robots spawned and despawned, the comms and
+/// the beliefs of every robot after the planner has run for the fixed
+/// timestep
+#[allow(clippy::type_complexity)]
+fn record_step(
+    mut recorder: ResMut<TraceRecorder>,
+    robots: Query<
+        (
+            Entity,
+            &Transform,
+            &FactorGraph,
+            &RadioAntenna,
+            &Radius,
+            &ColorAssociation,
+        ),
+        With<RobotConnections>,
+    >,
+    theme: Res<CatppuccinTheme>,
+) {
+    if !recorder.recording {
+        return;
+    }
+    let recorder = recorder.as_mut();
+
+    let mut step = TraceStep {
+        step: recorder.step,
+        ..Default::default()
+    };
+
+    // Robots are numbered in the order they spawn. Robots spawning in the same
+    // step are ordered by entity, as queries do not iterate in a fixed order.
+    let mut spawned = robots
+        .iter()
+        .filter(|(entity, ..)| !recorder.robots.contains_key(entity))
+        .map(|(entity, _, _, _, radius, colour)| (entity, radius.0, colour.name))
+        .collect::<Vec<_>>();
+    spawned.sort_by_key(|(entity, ..)| *entity);
+    for (entity, radius, colour) in spawned {
+        let robot = recorder.next_robot;
+        recorder.next_robot += 1;
+        recorder.robots.insert(entity, robot);
+        let (r, g, b) = theme.get_display_colour(&colour).into();
+        step.spawned.push(RobotSpawn {
+            robot,
+            radius,
+            colour: [r, g, b],
+        });
+    }
+
+    recorder.robots.retain(|&entity, &mut robot| {
+        let alive = robots.contains(entity);
+        if !alive {
+            step.despawned.push(robot);
+        }
+        alive
+    });
+    step.despawned.sort_unstable();
+
+    step.robots = robots
+        .iter()
+        .map(|(entity, transform, factorgraph, antenna, ..)| RobotState {
+            robot:    recorder.robots[&entity],
+            position: transform.translation.xz().to_array(),
+            comms:    antenna.active,
+            beliefs:  factorgraph
+                .variables()
+                .map(|(_, variable)| variable.belief.mean.to_vec())
+                .collect(),
+        })
+        .collect();
+    step.robots.sort_by_key(|state| state.robot);
+
+    match recorder.sink {
+        Sink::File {
+            ref path,
+            ref mut writer,
+        } => {
+            if let Some(ref mut trace) = writer {
+                if let Err(err) = trace.write_step(&step) {
+                    error!("failed to write to trace {}: {err:#}", path.display());
+                    *writer = None;
+                    recorder.recording = false;
+                }
+            }
+        }
+        Sink::Memory(ref mut steps) => steps.push(step),
+    }
+
+    recorder.step += 1;
+}
+
+/// **Bevy** system flushing the trace file once per frame, so it is complete
+/// up to the latest step if the app is killed
+fn flush_trace(mut recorder: ResMut<TraceRecorder>) {
+    if let Sink::File {
+        path,
+        writer: Some(writer),
+    } = &mut recorder.sink
+    {
+        if let Err(err) = writer.flush() {
+            error!("failed to flush trace {}: {err:#}", path.display());
+        }
+    }
+}
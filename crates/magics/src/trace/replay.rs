@@ -0,0 +1,194 @@
+//! Replay of a recorded trace in the ui, with a timeline to scrub through it
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_egui::egui;
+use gbp_config::Config;
+
+use super::format::{RobotSpawn, Trace};
+use crate::ui::UiState;
+
+/// **Bevy** [`Plugin`] drawing the robots of a trace step by step.
+///
+/// The scenario of the trace has to be loaded, e.g. with
+/// [`InitialSimulation::Preloaded`](crate::simulation_loader::InitialSimulation::Preloaded)
+/// and the header of the trace, so the environment is shown. The simulation
+/// itself is kept paused, as the robots are drawn from the trace instead.
+pub struct TraceReplayPlugin {
+    pub trace: Trace,
+}
+
+impl Plugin for TraceReplayPlugin {
+    fn build(&self, app: &mut App) {
+        let robots = self
+            .trace
+            .steps
+            .iter()
+            .flat_map(|step| step.spawned.iter())
+            .map(|spawn| (spawn.robot, *spawn))
+            .collect();
+
+        app.insert_resource(Replay {
+            trace: self.trace.clone(),
+            robots,
+            cursor: 0,
+            playing: false,
+        })
+        .add_systems(
+            Update,
+            (advance_replay, draw_replay, ui_replay_window).chain(),
+        )
+        .add_systems(Last, keep_simulation_paused);
+    }
+}
+
+/// **Bevy** [`Resource`] with the trace being replayed, and the step shown
+#[derive(Resource)]
+pub struct Replay {
+    trace:   Trace,
+    /// How every robot in the trace looks, by index
+    robots:  HashMap<u32, RobotSpawn>,
+    /// Index of the step shown
+    cursor:  usize,
+    /// Whether the replay advances in real time
+    playing: bool,
+}
+
+/// **Bevy** system pausing the simulation whenever it is unpaused, e.g. when
+/// a scenario is (re)loaded, as the robots come from the trace
+fn keep_simulation_paused(mut time: ResMut<Time<Virtual>>) {
+    if !time.is_paused() {
+        time.pause();
+    }
+}
+
+/// **Bevy** system advancing the replay in real time, at the fixed timestep
+/// the trace was recorded with
+fn advance_replay(
+    mut replay: ResMut<Replay>,
+    time: Res<Time<Real>>,
+    config: Res<Config>,
+    mut elapsed: Local<f64>,
+) {
+    if !replay.playing {
+        *elapsed = 0.0;
+        return;
+    }
+
+    *elapsed += time.delta_seconds_f64();
+    let timestep = config.simulation.hz.recip();
+    while *elapsed >= timestep {
+        *elapsed -= timestep;
+        if replay.cursor + 1 < replay.trace.steps.len() {
+            replay.cursor += 1;
+        } else {
+            replay.playing = false;
+            break;
+        }
+    }
+}
+
+/// **Bevy** system drawing every robot alive in the current step, with the
+/// means of the beliefs of its variables. Robots with their comms off are drawn
+/// greyed out.
+#[allow(clippy::cast_possible_truncation)]
+fn draw_replay(mut gizmos: Gizmos, replay: Res<Replay>, config: Res<Config>) {
+    let Some(step) = replay.trace.steps.get(replay.cursor) else {
+        return;
+    };
+
+    let height = -config.visualisation.height.objects;
+    for robot in &step.robots {
+        let Some(spawn) = replay.robots.get(&robot.robot) else {
+            continue;
+        };
+        let [r, g, b] = spawn.colour;
+        let colour = if robot.comms {
+            Color::rgb_u8(r, g, b)
+        } else {
+            Color::GRAY
+        };
+
+        let position = Vec2::from_array(robot.position);
+        gizmos.circle(
+            position.extend(height).xzy(),
+            Direction3d::Y,
+            spawn.radius,
+            colour,
+        );
+
+        let means = robot
+            .beliefs
+            .iter()
+            .filter_map(|mean| match mean.as_slice() {
+                [x, y, ..] => Some(Vec2::new(*x as f32, *y as f32).extend(height).xzy()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        gizmos.linestrip(means.iter().copied(), colour);
+        for mean in means {
+            gizmos.circle(mean, Direction3d::Y, spawn.radius / 4.0, colour);
+        }
+    }
+}
+
+/// **Bevy** system rendering the timeline of the replay
+#[allow(clippy::cast_precision_loss)]
+fn ui_replay_window(
+    mut egui_ctx: bevy_egui::EguiContexts,
+    mut replay: ResMut<Replay>,
+    mut ui_state: ResMut<UiState>,
+    config: Res<Config>,
+) {
+    let steps = replay.trace.steps.len();
+    egui::Window::new("Replay")
+        .collapsible(true)
+        .movable(true)
+        .title_bar(true)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            ui_state.mouse_over.floating_window = ui.rect_contains_pointer(ui.max_rect())
+                && config.interaction.ui_focus_cancels_inputs;
+
+            ui.label(format!("scenario: {}", replay.trace.header.scenario));
+            if steps == 0 {
+                ui.label("the trace contains no steps");
+                return;
+            }
+
+            ui.horizontal(|ui| {
+                let label = if replay.playing { "pause" } else { "play" };
+                if ui.button(label).clicked() {
+                    if !replay.playing && replay.cursor + 1 == steps {
+                        // Start over when the end has been reached
+                        replay.cursor = 0;
+                    }
+                    replay.playing = !replay.playing;
+                }
+                if ui.button("<").clicked() {
+                    replay.cursor = replay.cursor.saturating_sub(1);
+                }
+                if ui.button(">").clicked() {
+                    replay.cursor = (replay.cursor + 1).min(steps - 1);
+                }
+            });
+
+            let mut cursor = replay.cursor;
+            let slider = egui::Slider::new(&mut cursor, 0..=steps - 1).text("step");
+            if ui.add(slider).changed() {
+                replay.cursor = cursor;
+                replay.playing = false;
+            }
+
+            let step = &replay.trace.steps[replay.cursor];
+            ui.label(format!(
+                "time: {:.2} s",
+                step.step as f64 / config.simulation.hz
+            ));
+            ui.label(format!("robots: {}", step.robots.len()));
+            ui.label(format!(
+                "comms off: {}",
+                step.robots.iter().filter(|robot| !robot.comms).count()
+            ));
+        });
+}
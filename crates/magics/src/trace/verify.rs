@@ -0,0 +1,111 @@
+//! Bit for bit verification of a trace, by re-simulating it headless
+
+use bevy::{
+    app::AppExit,
+    ecs::event::{Events, ManualEventReader},
+    log::LogPlugin,
+    prelude::*,
+};
+
+use super::{
+    format::{Divergence, Trace},
+    record::{TraceOutput, TraceRecorder, TraceRecorderPlugin},
+};
+use crate::{
+    export::{ExportPlugin, ExportSaveLocation, ExportSavePostfix},
+    headless::HeadlessPlugins,
+    simulation_loader::{InitialSimulation, SimulationLoaderPlugin},
+};
+
+/// Outcome of verifying a trace
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verification {
+    /// Number of steps of the trace that were re-simulated identically
+    pub identical_steps: usize,
+    /// The first difference to the trace, `None` if every step was identical
+    pub divergence:      Option<Divergence>,
+}
+
+/// Re-simulate the scenario in the header of `trace` headless, and compare
+/// every step with the recording, stopping at the first difference.
+///
+/// # Errors
+///
+/// Returns an error if the scenario in the header of the trace could not be
+/// parsed
+pub fn verify(trace: &Trace) -> anyhow::Result<Verification> {
+    let simulation = trace.header.simulation()?;
+
+    let simulation_loader = SimulationLoaderPlugin {
+        show_toasts: false,
+        initial_simulation: InitialSimulation::Preloaded(Box::new(simulation)),
+        reload_after: None,
+        ..Default::default()
+    };
+    // The re-simulation may run to the end of the scenario and export its
+    // results, which are of no interest here
+    let export = ExportPlugin {
+        save_at_location: ExportSaveLocation::At(std::env::temp_dir()),
+        postfix: ExportSavePostfix::Name("trace-verification".to_string()),
+    };
+
+    let mut app = App::new();
+    app.add_plugins(
+        HeadlessPlugins {
+            simulation_loader,
+            export,
+        }
+        .build()
+        .disable::<LogPlugin>(),
+    )
+    .add_plugins(TraceRecorderPlugin {
+        output: TraceOutput::Memory,
+    });
+    app.finish();
+    app.cleanup();
+
+    let mut evr_app_exit = ManualEventReader::<AppExit>::default();
+    let mut expected = trace.steps.iter();
+    let mut identical_steps = 0;
+    loop {
+        app.update();
+
+        let steps = app.world.resource_mut::<TraceRecorder>().take_steps();
+        for actual in &steps {
+            let Some(expected) = expected.next() else {
+                // The recording ended here, e.g. because the recorded app was closed
+                return Ok(Verification {
+                    identical_steps,
+                    divergence: None,
+                });
+            };
+            if let Some(divergence) = actual.diverges_from(expected) {
+                return Ok(Verification {
+                    identical_steps,
+                    divergence: Some(divergence),
+                });
+            }
+            identical_steps += 1;
+        }
+
+        if identical_steps == trace.steps.len() {
+            return Ok(Verification {
+                identical_steps,
+                divergence: None,
+            });
+        }
+
+        if evr_app_exit
+            .read(app.world.resource::<Events<AppExit>>())
+            .next()
+            .is_some()
+        {
+            return Ok(Verification {
+                identical_steps,
+                divergence: Some(Divergence::Ended {
+                    step: identical_steps as u64,
+                }),
+            });
+        }
+    }
+}
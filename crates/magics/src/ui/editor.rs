@@ -0,0 +1,754 @@
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy_egui::egui::{self, Color32};
+use bevy_notify::ToastEvent;
+use gbp_config::Config;
+use gbp_environment::{Environment, Obstacle, PlaceableShape, Rotation};
+use gbp_linalg::Float;
+use smol_str::SmolStr;
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+use super::UiState;
+use crate::{
+    simulation_loader::{SimulationManager, SimulationsWatcher},
+    theme::{CatppuccinTheme, FromCatppuccinColourExt},
+    validation,
+};
+
+/// **Bevy** `Plugin` to add a window to the UI, for editing the tiles and
+/// obstacles of the environment of the active simulation, and saving them to
+/// its `environment.yaml`
+pub struct EnvironmentEditorPlugin;
+
+impl Plugin for EnvironmentEditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EnvironmentEditor>()
+            .add_systems(PostUpdate, render);
+    }
+}
+
+/// The tiles that can be painted, and what they look like. The ascii
+/// alternatives `-` and `|` are left out
+const PALETTE: [(char, &str); 17] = [
+    ('─', "horizontal path"),
+    ('│', "vertical path"),
+    ('╴', "dead end, open to the left"),
+    ('╶', "dead end, open to the right"),
+    ('╵', "dead end, open to the top"),
+    ('╷', "dead end, open to the bottom"),
+    ('┌', "corner between the right and the bottom"),
+    ('┐', "corner between the left and the bottom"),
+    ('└', "corner between the right and the top"),
+    ('┘', "corner between the left and the top"),
+    ('┬', "junction to the left, right and bottom"),
+    ('┴', "junction to the left, right and top"),
+    ('├', "junction to the top, bottom and right"),
+    ('┤', "junction to the top, bottom and left"),
+    ('┼', "crossing"),
+    (' ', "filled tile"),
+    ('█', "open tile"),
+];
+
+/// The tile new tiles are filled with, when the grid is made larger
+const FILL: char = ' ';
+
+/// Pixels per tile of the preview of the environment. Lower than the
+/// resolution of the sdf used by the simulation, to keep it quick to generate
+const PREVIEW_RESOLUTION: u32 = 20;
+
+/// Corners used to draw the outline of circular obstacles
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// Factor an obstacle is scaled by, for every step of scaling it
+const SCALE_STEP: Float = 1.1;
+
+/// Points of scrolling that scale an obstacle by [`SCALE_STEP`], about a notch
+/// of a mouse wheel
+const SCROLL_PER_SCALE_STEP: Float = 50.0;
+
+/// The smallest and largest size the obstacles can be scaled to, see
+/// [`shape_size`]
+const SHAPE_SIZE: std::ops::RangeInclusive<Float> = 0.01..=1.0;
+
+/// The kinds of [`PlaceableShape`] that can be placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, derive_more::Display)]
+enum ShapeKind {
+    #[display(fmt = "Circle")]
+    Circle,
+    #[display(fmt = "Triangle")]
+    Triangle,
+    #[display(fmt = "Regular Polygon")]
+    RegularPolygon,
+    #[display(fmt = "Rectangle")]
+    Rectangle,
+}
+
+impl ShapeKind {
+    /// A new shape of this kind, with a size similar to the obstacles in the
+    /// existing environments
+    fn shape(self, sides: usize) -> PlaceableShape {
+        let radius = |radius: Float| {
+            typed_floats::StrictlyPositiveFinite::<Float>::new(radius).expect("radius > 0")
+        };
+        match self {
+            Self::Circle => PlaceableShape::circle(radius(0.05)),
+            Self::Triangle => PlaceableShape::triangle(
+                [
+                    angle::Angle::from_degrees(60.0).expect("0 <= 60 <= 360"),
+                    angle::Angle::from_degrees(60.0).expect("0 <= 60 <= 360"),
+                ],
+                radius(0.05),
+            ),
+            Self::RegularPolygon => PlaceableShape::regular_polygon(sides, 0.1),
+            Self::Rectangle => PlaceableShape::rectangle(0.2, 0.1),
+        }
+    }
+}
+
+/// The largest radius, width or height of `shape`, relative to the size of a
+/// tile
+fn shape_size(shape: &PlaceableShape) -> Float {
+    match shape {
+        PlaceableShape::Circle(circle) => circle.radius.get(),
+        PlaceableShape::Triangle(triangle) => triangle.radius.get(),
+        PlaceableShape::RegularPolygon(polygon) => polygon.radius.get(),
+        PlaceableShape::Rectangle(rectangle) => rectangle.width.get().max(rectangle.height.get()),
+    }
+}
+
+/// Scale `shape` by `factor`, as far as it can be without its size leaving
+/// [`SHAPE_SIZE`]. Returns `None` if the shape can not be scaled any further
+fn scaled_within_bounds(shape: &PlaceableShape, factor: Float) -> Option<PlaceableShape> {
+    let size = shape_size(shape);
+    let scaled_size = (size * factor).clamp(*SHAPE_SIZE.start(), *SHAPE_SIZE.end());
+    if (scaled_size - size).abs() <= Float::EPSILON * size {
+        return None;
+    }
+    shape.scaled(scaled_size / size)
+}
+
+/// What dragging and clicking in the map of the editor does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tool {
+    /// Select obstacles, and move them by dragging
+    Select,
+    /// Paint the tiles with the given tile
+    Paint(char),
+    /// Place obstacles of the given kind
+    Place(ShapeKind),
+}
+
+/// **Bevy** [`Resource`] with the state of the environment editor
+#[derive(Resource)]
+struct EnvironmentEditor {
+    /// The simulation whose environment is being edited
+    simulation: Option<SmolStr>,
+    /// The copy of the environment being edited
+    environment: Option<Environment>,
+    /// Whether the environment has changes that have not been saved
+    unsaved: bool,
+    tool: Tool,
+    /// Sides of the regular polygons being placed
+    sides: usize,
+    /// Index of the selected obstacle
+    selected: Option<usize>,
+    /// Whether the selected obstacle is being moved
+    moving: bool,
+    /// The size of the grid to resize to, (rows, columns)
+    size: (usize, usize),
+    /// Show the sdf of the environment, instead of the obstacles
+    preview_sdf: bool,
+    /// The image of the environment, drawn under the grid
+    texture: Option<egui::TextureHandle>,
+    /// Whether the environment has changed since the texture was generated
+    stale: bool,
+}
+
+impl Default for EnvironmentEditor {
+    fn default() -> Self {
+        Self {
+            simulation: None,
+            environment: None,
+            unsaved: false,
+            tool: Tool::Select,
+            sides: 4,
+            selected: None,
+            moving: false,
+            size: (1, 1),
+            preview_sdf: false,
+            texture: None,
+            stale: true,
+        }
+    }
+}
+
+impl EnvironmentEditor {
+    /// Start editing the environment of `simulation`, discarding any changes
+    fn edit(&mut self, simulation: SmolStr, environment: Environment) {
+        self.size = environment.tiles.grid.shape();
+        self.simulation = Some(simulation);
+        self.environment = Some(environment);
+        self.unsaved = false;
+        self.selected = None;
+        self.moving = false;
+        self.stale = true;
+    }
+
+    /// Record that the environment has changed
+    fn changed(&mut self) {
+        self.unsaved = true;
+        self.stale = true;
+    }
+
+    /// The tools, and the tiles and shapes they use
+    fn toolbar(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.tool, Tool::Select, "Select")
+                .on_hover_text(
+                    "Click an obstacle to select it, and drag to move it. Scroll to scale it, \
+                     scroll while holding shift to rotate it, and press delete to remove it",
+                );
+        });
+
+        ui.label("Paint");
+        ui.horizontal_wrapped(|ui| {
+            for (tile, description) in PALETTE {
+                let text = if tile == FILL {
+                    "fill".to_string()
+                } else {
+                    tile.to_string()
+                };
+                ui.selectable_value(
+                    &mut self.tool,
+                    Tool::Paint(tile),
+                    egui::RichText::new(text).monospace(),
+                )
+                .on_hover_text(description);
+            }
+        });
+
+        ui.label("Place");
+        ui.horizontal_wrapped(|ui| {
+            for kind in ShapeKind::iter() {
+                ui.selectable_value(&mut self.tool, Tool::Place(kind), kind.to_string());
+            }
+            if self.tool == Tool::Place(ShapeKind::RegularPolygon) {
+                ui.add(
+                    egui::DragValue::new(&mut self.sides)
+                        .clamp_range(3..=12)
+                        .suffix(" sides"),
+                );
+            }
+        });
+    }
+
+    /// Widgets to resize the grid
+    fn grid_size(&mut self, ui: &mut egui::Ui) {
+        let Some(environment) = self.environment.as_mut() else {
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            ui.label("Grid");
+            ui.add(
+                egui::DragValue::new(&mut self.size.0)
+                    .clamp_range(1..=50)
+                    .suffix(" rows"),
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.size.1)
+                    .clamp_range(1..=50)
+                    .suffix(" columns"),
+            );
+
+            let (nrows, ncols) = self.size;
+            if ui
+                .add_enabled(
+                    environment.tiles.grid.shape() != self.size,
+                    egui::Button::new("Resize"),
+                )
+                .on_hover_text("Obstacles in tiles outside the new grid are removed")
+                .clicked()
+            {
+                environment.tiles.grid.resize(nrows, ncols, FILL);
+                environment.obstacles.retain(|obstacle| {
+                    obstacle.tile_coordinates.row < nrows && obstacle.tile_coordinates.col < ncols
+                });
+                self.selected = None;
+                self.unsaved = true;
+                self.stale = true;
+            }
+        });
+    }
+
+    /// Widgets to rotate, scale and remove the selected obstacle
+    fn selected_obstacle(&mut self, ui: &mut egui::Ui) {
+        let Some(environment) = self.environment.as_mut() else {
+            return;
+        };
+        let Some(index) = self.selected else {
+            ui.label("No obstacle selected");
+            return;
+        };
+        let Some(obstacle) = environment.obstacles.get_mut(index) else {
+            self.selected = None;
+            return;
+        };
+
+        let mut changed = false;
+        let mut remove = false;
+        ui.horizontal(|ui| {
+            let name = match obstacle.shape {
+                PlaceableShape::Circle(_) => "Circle".to_string(),
+                PlaceableShape::Triangle(_) => "Triangle".to_string(),
+                PlaceableShape::RegularPolygon(ref polygon) => {
+                    format!("Regular Polygon with {} sides", polygon.sides)
+                }
+                PlaceableShape::Rectangle(_) => "Rectangle".to_string(),
+            };
+            ui.label(format!(
+                "{name} in tile ({}, {})",
+                obstacle.tile_coordinates.row, obstacle.tile_coordinates.col
+            ));
+
+            remove = ui.button("Remove").clicked();
+        });
+
+        ui.horizontal(|ui| {
+            let mut degrees = obstacle.rotation.as_degrees();
+            if ui
+                .add(egui::Slider::new(&mut degrees, 0.0..=360.0).text("rotation"))
+                .changed()
+            {
+                obstacle.rotation = Rotation::new(degrees.round());
+                changed = true;
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("scale");
+            for (text, factor) in [("-", SCALE_STEP.recip()), ("+", SCALE_STEP)] {
+                if ui.button(text).clicked() {
+                    if let Some(shape) = scaled_within_bounds(&obstacle.shape, factor) {
+                        obstacle.shape = shape;
+                        changed = true;
+                    }
+                }
+            }
+        });
+
+        if remove {
+            environment.obstacles.remove(index);
+            self.selected = None;
+            changed = true;
+        }
+        if changed {
+            self.changed();
+        }
+    }
+
+    /// The map of the environment, where tiles are painted and obstacles placed
+    /// and moved
+    #[allow(
+        clippy::too_many_lines,
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn canvas(&mut self, ui: &mut egui::Ui, theme: &CatppuccinTheme) {
+        let Some(environment) = self.environment.as_mut() else {
+            return;
+        };
+
+        let (nrows, ncols) = environment.tiles.grid.shape();
+        let tile_size = (ui.available_width() / ncols as f32)
+            .min(600.0 / nrows as f32)
+            .max(4.0);
+        let (response, painter) = ui.allocate_painter(
+            egui::vec2(tile_size * ncols as f32, tile_size * nrows as f32),
+            egui::Sense::click_and_drag(),
+        );
+        let rect = response.rect;
+
+        // the position in tiles of a point on the canvas, with `y` pointing down
+        let to_tiles =
+            |pos: egui::Pos2| Vec2::new(pos.x - rect.min.x, pos.y - rect.min.y) / tile_size;
+        let to_canvas = |tiles: Vec2| rect.min + egui::vec2(tiles.x, tiles.y) * tile_size;
+        let tile_at = |tiles: Vec2| {
+            let in_grid = tiles.x >= 0.0
+                && tiles.y >= 0.0
+                && tiles.x < ncols as f32
+                && tiles.y < nrows as f32;
+            in_grid.then(|| (tiles.y as usize, tiles.x as usize))
+        };
+        // the center of an obstacle in tiles
+        let centre_of = |obstacle: &Obstacle| {
+            Vec2::new(
+                obstacle.tile_coordinates.col as f32,
+                obstacle.tile_coordinates.row as f32,
+            ) + Vec2::from(obstacle.translation)
+        };
+        let obstacle_at = |obstacles: &gbp_environment::Obstacles, tiles: Vec2| {
+            // the last drawn obstacle is on top
+            obstacles
+                .iter()
+                .enumerate()
+                .rev()
+                .find_map(|(index, obstacle)| {
+                    let within = tiles
+                        - Vec2::new(
+                            obstacle.tile_coordinates.col as f32,
+                            obstacle.tile_coordinates.row as f32,
+                        );
+                    obstacle.contains(within).then_some(index)
+                })
+        };
+
+        let pointer = response.interact_pointer_pos().map(to_tiles);
+        let mut changed = false;
+        match self.tool {
+            Tool::Paint(tile) => {
+                if response.clicked() || response.dragged() {
+                    if let Some((row, col)) = pointer.and_then(tile_at) {
+                        if environment.tiles.grid.get_tile(row, col) != Some(tile) {
+                            changed = environment.tiles.grid.set_tile(row, col, tile);
+                        }
+                    }
+                }
+            }
+            Tool::Place(kind) => {
+                if response.clicked() {
+                    if let Some(((row, col), tiles)) =
+                        pointer.and_then(|tiles| tile_at(tiles).map(|tile| (tile, tiles)))
+                    {
+                        let within = tiles - tiles.floor();
+                        environment.obstacles.push(Obstacle::new(
+                            (row, col),
+                            kind.shape(self.sides),
+                            0.0,
+                            (rounded(within.x), rounded(within.y)),
+                        ));
+                        self.selected = Some(environment.obstacles.len() - 1);
+                        changed = true;
+                    }
+                }
+            }
+            Tool::Select => {
+                if response.clicked() || response.drag_started() {
+                    self.selected =
+                        pointer.and_then(|tiles| obstacle_at(&environment.obstacles, tiles));
+                    self.moving = response.drag_started() && self.selected.is_some();
+                }
+                if !response.dragged() {
+                    self.moving = false;
+                }
+
+                if let Some(obstacle) = self
+                    .selected
+                    .and_then(|index| environment.obstacles.get_mut(index))
+                {
+                    if self.moving {
+                        let delta = response.drag_delta() / tile_size;
+                        if delta != egui::Vec2::ZERO {
+                            // keep the centre just inside the grid, so it is in a tile
+                            let bounds = Vec2::new(ncols as f32, nrows as f32) - 1e-3;
+                            let centre = (centre_of(obstacle) + Vec2::new(delta.x, delta.y))
+                                .clamp(Vec2::ZERO, bounds);
+                            let within = centre - centre.floor();
+                            obstacle.tile_coordinates.col = centre.x as usize;
+                            obstacle.tile_coordinates.row = centre.y as usize;
+                            obstacle.translation = (rounded(within.x), rounded(within.y))
+                                .try_into()
+                                .expect("the fraction of a tile is in [0, 1]");
+                            changed = true;
+                        }
+                    }
+
+                    let (scroll, shift) =
+                        ui.input(|input| (input.raw_scroll_delta, input.modifiers.shift));
+                    let scroll = scroll.x + scroll.y;
+                    if response.hovered() && scroll != 0.0 {
+                        if shift {
+                            let degrees = (obstacle.rotation.as_degrees()
+                                + 5.0 * Float::from(scroll.signum()))
+                            .rem_euclid(360.0);
+                            obstacle.rotation = Rotation::new(degrees);
+                            changed = true;
+                        } else {
+                            // Scale by how far has been scrolled this frame,
+                            // instead of by a step every frame with any scrolling
+                            let steps = Float::from(scroll) / SCROLL_PER_SCALE_STEP;
+                            if let Some(shape) =
+                                scaled_within_bounds(&obstacle.shape, SCALE_STEP.powf(steps))
+                            {
+                                obstacle.shape = shape;
+                                changed = true;
+                            }
+                        }
+                    }
+
+                    if response.hovered() && ui.input(|input| input.key_pressed(egui::Key::Delete))
+                    {
+                        if let Some(index) = self.selected.take() {
+                            environment.obstacles.remove(index);
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(ref texture) = self.texture {
+            painter.image(
+                texture.id(),
+                rect,
+                egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                Color32::WHITE,
+            );
+        }
+
+        let grid_stroke = egui::Stroke::new(
+            1.0,
+            Color32::from_catppuccin_colour_with_alpha(theme.overlay0(), 0.5),
+        );
+        for col in 0..=ncols {
+            let x = rect.min.x + col as f32 * tile_size;
+            painter.line_segment(
+                [egui::pos2(x, rect.min.y), egui::pos2(x, rect.max.y)],
+                grid_stroke,
+            );
+        }
+        for row in 0..=nrows {
+            let y = rect.min.y + row as f32 * tile_size;
+            painter.line_segment(
+                [egui::pos2(rect.min.x, y), egui::pos2(rect.max.x, y)],
+                grid_stroke,
+            );
+        }
+
+        if let Some((row, col)) = response.hover_pos().map(to_tiles).and_then(tile_at) {
+            let min = to_canvas(Vec2::new(col as f32, row as f32));
+            painter.rect_stroke(
+                egui::Rect::from_min_size(min, egui::vec2(tile_size, tile_size)),
+                0.0,
+                egui::Stroke::new(2.0, Color32::from_catppuccin_colour(theme.blue())),
+            );
+            response
+                .clone()
+                .on_hover_text_at_pointer(format!("({row}, {col})"));
+        }
+
+        for (index, obstacle) in environment.obstacles.iter().enumerate() {
+            let colour = if self.selected == Some(index) {
+                theme.red()
+            } else {
+                theme.peach()
+            };
+            let tile = Vec2::new(
+                obstacle.tile_coordinates.col as f32,
+                obstacle.tile_coordinates.row as f32,
+            );
+            let outline = obstacle
+                .outline(CIRCLE_SEGMENTS)
+                .into_iter()
+                .map(|corner| to_canvas(tile + corner))
+                .collect();
+            painter.add(egui::Shape::closed_line(
+                outline,
+                egui::Stroke::new(2.0, Color32::from_catppuccin_colour(colour)),
+            ));
+            painter.circle_filled(
+                to_canvas(centre_of(obstacle)),
+                2.0,
+                Color32::from_catppuccin_colour(colour),
+            );
+        }
+
+        if changed {
+            self.changed();
+        }
+        // generating the texture while dragging would make it stutter
+        if self.stale && !response.dragged() {
+            self.update_texture(ui.ctx());
+        }
+    }
+
+    /// Generate the image of the environment drawn under the grid again
+    fn update_texture(&mut self, ctx: &egui::Context) {
+        let Some(ref environment) = self.environment else {
+            return;
+        };
+        self.stale = false;
+
+        let settings = &environment.tiles.settings.sdf;
+        let resolution = env_to_png::PixelsPerTile::new(PREVIEW_RESOLUTION);
+        let image = if self.preview_sdf {
+            env_to_png::env_to_sdf_image(
+                environment,
+                resolution,
+                env_to_png::Percentage::new(settings.expansion),
+                env_to_png::Percentage::new(settings.blur),
+            )
+        } else {
+            env_to_png::env_to_image(environment, resolution, env_to_png::Percentage::new(0.0))
+        };
+
+        match image {
+            Ok(image) => {
+                let size = [image.width() as usize, image.height() as usize];
+                let image = egui::ColorImage::from_rgb(size, image.as_raw());
+                match self.texture {
+                    Some(ref mut texture) => texture.set(image, egui::TextureOptions::NEAREST),
+                    None => {
+                        self.texture = Some(ctx.load_texture(
+                            "environment-editor",
+                            image,
+                            egui::TextureOptions::NEAREST,
+                        ));
+                    }
+                }
+            }
+            Err(err) => error!("failed to draw the environment: {err}"),
+        }
+    }
+}
+
+/// Round to the three decimals the obstacles of the existing environments are
+/// written with
+fn rounded(value: f32) -> Float {
+    (Float::from(value) * 1000.0).round() / 1000.0
+}
+
+/// Write `environment` to the `environment.yaml` in `dir`
+fn save(environment: &Environment, dir: &Path) -> anyhow::Result<PathBuf> {
+    let environment = environment.clone().validate()?;
+    let path = dir.join(validation::ENVIRONMENT_FILE);
+    std::fs::write(&path, serde_yaml::to_string(&environment)?)?;
+    Ok(path)
+}
+
+/// **Bevy** system to render the environment editor window
+#[allow(clippy::too_many_lines)]
+fn render(
+    mut egui_ctx: bevy_egui::EguiContexts,
+    mut editor: ResMut<EnvironmentEditor>,
+    mut ui_state: ResMut<UiState>,
+    mut simulation_manager: ResMut<SimulationManager>,
+    mut watcher: Option<ResMut<SimulationsWatcher>>,
+    config: Res<Config>,
+    theme: Res<CatppuccinTheme>,
+    mut evw_toast: EventWriter<ToastEvent>,
+) {
+    if !ui_state.editor_window_visible {
+        ui_state.mouse_over.editor_window = false;
+        return;
+    }
+
+    // follow the active simulation, unless there are changes to save first
+    if let Some(active) = simulation_manager.active() {
+        let other = editor.simulation.as_deref() != Some(active.name.as_str());
+        if editor.environment.is_none() || (other && !editor.unsaved) {
+            editor.edit(active.name.as_str().into(), active.environment.clone());
+        }
+    }
+
+    let mut open = true;
+    egui::Window::new("Environment Editor")
+        .open(&mut open)
+        .collapsible(true)
+        .default_width(400.0)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            ui_state.mouse_over.editor_window = ui.rect_contains_pointer(ui.max_rect())
+                && config.interaction.ui_focus_cancels_inputs;
+
+            let Some(simulation) = editor.simulation.clone() else {
+                ui.label("No simulation to edit");
+                return;
+            };
+
+            ui.horizontal(|ui| {
+                ui.strong(simulation.as_str());
+                if editor.unsaved {
+                    ui.label("(unsaved)");
+                }
+            });
+
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(editor.unsaved, egui::Button::new("Save"))
+                    .on_hover_text(
+                        "Write the environment to the environment.yaml of the simulation, and \
+                         restart it from the settings panel to use it",
+                    )
+                    .clicked()
+                {
+                    let dir = simulation_manager.directory_of(simulation.as_str());
+                    let saved = editor
+                        .environment
+                        .as_ref()
+                        .map(|environment| save(environment, &dir));
+                    match saved {
+                        Some(Ok(path)) => {
+                            editor.unsaved = false;
+                            info!("saved environment to: {}", path.display());
+                            // the saved environment is loaded here, so it is not a change for
+                            // the watcher to load again
+                            if let Some(watcher) = watcher.as_mut() {
+                                watcher.acknowledge(&dir);
+                            }
+                            if let Err(report) =
+                                simulation_manager.reload_from_disk(simulation.as_str())
+                            {
+                                error!("{report}");
+                            }
+                            evw_toast.send(ToastEvent::success(format!(
+                                "saved environment of {simulation}"
+                            )));
+                        }
+                        Some(Err(err)) => {
+                            error!("failed to save environment of {simulation}: {err}");
+                            evw_toast.send(ToastEvent::error(format!(
+                                "failed to save environment: {err}"
+                            )));
+                        }
+                        None => {}
+                    }
+                }
+
+                if ui
+                    .add_enabled(editor.unsaved, egui::Button::new("Revert"))
+                    .on_hover_text("Discard the changes since the environment was last saved")
+                    .clicked()
+                {
+                    if let Some(environment) = simulation_manager
+                        .id_from_name(simulation.as_str())
+                        .and_then(|id| simulation_manager.get_environment_for(id))
+                    {
+                        editor.edit(simulation.clone(), environment.clone());
+                    }
+                }
+
+                let preview = ui
+                    .checkbox(&mut editor.preview_sdf, "Preview SDF")
+                    .on_hover_text("Show the expanded and blurred sdf used by the simulation");
+                if preview.changed() {
+                    editor.stale = true;
+                }
+            });
+
+            ui.separator();
+            editor.toolbar(ui);
+            editor.grid_size(ui);
+            ui.separator();
+            editor.selected_obstacle(ui);
+            ui.separator();
+            editor.canvas(ui, &theme);
+        });
+
+    if !open {
+        ui_state.editor_window_visible = false;
+        ui_state.mouse_over.editor_window = false;
+    }
+}
@@ -12,7 +12,10 @@ use egui_plot::{Line, Plot, PlotPoints};
 use gbp_config::Config;
 
 use super::UiState;
-use crate::diagnostic::prelude::RobotDiagnosticsPlugin;
+use crate::{
+    diagnostic::prelude::RobotDiagnosticsPlugin,
+    planner::metrics::{Summary, TrajectoryMetrics},
+};
 
 pub struct MetricsPlugin {
     wait_duration: Duration,
@@ -68,10 +71,65 @@ impl Plugin for MetricsPlugin {
 }
 
 impl MetricsPlugin {
+    /// Show the trajectory quality metrics aggregated over all robots, with the
+    /// same definitions as used in the export
+    fn trajectory_metrics(ui: &mut egui::Ui, trajectory_metrics: &TrajectoryMetrics) {
+        let aggregate = trajectory_metrics.aggregate();
+        let optional =
+            |value: Option<f32>| value.map_or_else(|| "-".to_string(), |v| format!("{v:.3}"));
+
+        ui.label(format!("makespan: {:.2} s", aggregate.makespan));
+        ui.label(format!(
+            "min clearance: {} m",
+            optional(aggregate.min_clearance)
+        ));
+        ui.label(format!(
+            "time within safety distance: {:.2} s",
+            aggregate.time_within_safety_distance
+        ));
+
+        egui::Grid::new("trajectory_metrics")
+            .striped(true)
+            .num_columns(4)
+            .show(ui, |ui| {
+                for header in [
+                    format!("{} robots", aggregate.robots),
+                    "mean".into(),
+                    "min".into(),
+                    "max".into(),
+                ] {
+                    ui.strong(header);
+                }
+                ui.end_row();
+
+                for (name, summary) in [
+                    ("distance [m]", aggregate.distance_travelled),
+                    ("ldj", aggregate.ldj),
+                    ("deviation [m]", aggregate.perpendicular_deviation),
+                ] {
+                    ui.label(name);
+                    match summary {
+                        Some(Summary { mean, min, max, .. }) => {
+                            for value in [mean, min, max] {
+                                ui.label(format!("{value:.3}"));
+                            }
+                        }
+                        None => {
+                            for _ in 0..3 {
+                                ui.label("-");
+                            }
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+
     /// **Bevy** system to render the metrics window widget
     fn render(
         mut egui_ctx: bevy_egui::EguiContexts,
         diagnostics: Res<DiagnosticsStore>,
+        trajectory_metrics: Res<TrajectoryMetrics>,
         config: Res<Config>,
         mut ui_state: ResMut<UiState>,
         mut current_pos: Local<egui::Pos2>,
@@ -102,7 +160,19 @@ impl MetricsPlugin {
                     ("robots", &RobotDiagnosticsPlugin::ROBOT_COUNT),
                     ("variables", &RobotDiagnosticsPlugin::VARIABLE_COUNT),
                     ("factors", &RobotDiagnosticsPlugin::FACTOR_COUNT),
+                    (
+                        "gbp iterations",
+                        &RobotDiagnosticsPlugin::GBP_ITERATION_COUNT,
+                    ),
                     ("collisions", &RobotDiagnosticsPlugin::ROBOT_COLLISION_COUNT),
+                    (
+                        "messages lost",
+                        &RobotDiagnosticsPlugin::MESSAGES_LOST_COUNT,
+                    ),
+                    (
+                        "messages over bandwidth",
+                        &RobotDiagnosticsPlugin::MESSAGES_BANDWIDTH_EXCEEDED_COUNT,
+                    ),
                 ] {
                     #[allow(clippy::cast_possible_truncation)]
                     if let Some(value) = diagnostics
@@ -113,6 +183,9 @@ impl MetricsPlugin {
                     }
                 }
 
+                ui.separator();
+                Self::trajectory_metrics(ui, &trajectory_metrics);
+
                 // if let Some(messages_sent) =
                 // diagnostics.get(&RobotDiagnosticsPlugin::MESSAGES_SENT_COUNT) {
                 //     #[allow(clippy::cast_precision_loss)]
@@ -2,6 +2,7 @@ pub mod controls;
 mod custom;
 mod data;
 mod decoration;
+mod editor;
 mod metrics;
 mod scale;
 // mod selected_entity;
@@ -19,8 +20,8 @@ pub use decoration::ToUiString;
 use strum_macros::EnumIter;
 
 use self::{
-    controls::ControlsPanelPlugin, data::DataPanelPlugin, metrics::MetricsPlugin,
-    scale::ScaleUiPlugin, settings::SettingsPanelPlugin,
+    controls::ControlsPanelPlugin, data::DataPanelPlugin, editor::EnvironmentEditorPlugin,
+    metrics::MetricsPlugin, scale::ScaleUiPlugin, settings::SettingsPanelPlugin,
 };
 use crate::{theme::CatppuccinThemeVisualsExt, AppState};
 
@@ -61,7 +62,7 @@ impl Plugin for EguiInterfacePlugin {
                 ScaleUiPlugin::default(),
 
 
-                MetricsPlugin::default()            ))
+                MetricsPlugin::default(), EnvironmentEditorPlugin            ))
             // .add_systems(OnEnter(SimulationState::Loading), load_fonts)
             // .add_systems(Startup, load_fonts)
             // .add_systems(OnEnter(AppState::Loading), load_fonts)
@@ -130,6 +131,10 @@ fn hide_panels(mut ui_state: ResMut<UiState>) {
     if ui_state.metrics_window_visible {
         ui_state.metrics_window_visible = false;
     }
+
+    if ui_state.editor_window_visible {
+        ui_state.editor_window_visible = false;
+    }
 }
 
 /// **Bevy** [`Resource`] to block actions from being performed
@@ -193,6 +198,7 @@ pub struct MouseOverPanel {
     pub top_panel:       bool,
     pub bottom_panel:    bool,
     pub metrics_window:  bool,
+    pub editor_window:   bool,
     pub floating_window: bool,
 }
 
@@ -230,6 +236,8 @@ pub struct UiState {
     pub bottom_panel_visible: bool,
     /// Whether the metrics window is open
     pub metrics_window_visible: bool,
+    /// Whether the environment editor window is open
+    pub editor_window_visible: bool,
     /// The type of UI scaling to use
     pub scale_type: UiScaleType,
     /// When `scale_type` is `Custom`, the percentage to scale by
@@ -268,6 +276,7 @@ impl Default for UiState {
             top_panel_visible: false,
             bottom_panel_visible: false,
             metrics_window_visible: false,
+            editor_window_visible: false,
             scale_type: UiScaleType::default(),
             scale_percent: Self::DEFAULT_SCALE_PERCENTAGE,
             // scale_percent: 100, // start at default factor 1.0 = 100%
@@ -335,6 +344,7 @@ fn action_block(mut action_block: ResMut<ActionBlock>, ui_state: Res<UiState>) {
         || (ui_state.top_panel_visible && ui_state.mouse_over.top_panel)
         || (ui_state.bottom_panel_visible && ui_state.mouse_over.bottom_panel)
         || (ui_state.mouse_over.floating_window)
+        || (ui_state.editor_window_visible && ui_state.mouse_over.editor_window)
     {
         action_block.block();
     } else {
@@ -25,7 +25,10 @@ use crate::{
         screenshot::TakeScreenshot, ChangingBinding, DrawSettingsEvent, ExportFactorGraphAsGraphviz,
     },
     pause_play::PausePlay,
-    planner::robot::RadioAntenna,
+    planner::{
+        robot::{Profile, RadioAntenna},
+        user_factors::UserFactorRegistry,
+    },
     simulation_loader::{SaveSettings, SimulationId, SimulationManager},
     theme::{CatppuccinTheme, CycleTheme, FromCatppuccinColourExt},
 };
@@ -489,10 +492,15 @@ fn ui_settings_panel(
 
                             ui.end_row();
 
-                            let mut update_enabled_factors = |settings: gbp_config::FactorsEnabledSection| {
+                            let user_factor_names = world
+                                .get_resource::<UserFactorRegistry>()
+                                .map(|registry| registry.names().collect::<Vec<_>>())
+                                .unwrap_or_default();
+
+                            let mut update_enabled_factors = |settings: gbp_config::FactorsEnabledSection, user_factors: &gbp_config::UserFactorsSection| {
                                 let mut query = world.query::<&mut FactorGraph>();
                                 for mut fgraph in query.iter_mut(world) {
-                                    fgraph.change_factor_enabled(settings);
+                                    fgraph.change_factor_enabled(settings, user_factors);
                                 }
                             };
 
@@ -502,7 +510,7 @@ fn ui_settings_panel(
                             update_float(ui, &mut config.gbp.sigma_factor_dynamics);
                             custom::float_right(ui, |ui| {
                                 if custom::toggle_ui(ui, &mut config.gbp.factors_enabled.dynamic).clicked() {
-                                    update_enabled_factors(config.gbp.factors_enabled.clone());
+                                    update_enabled_factors(config.gbp.factors_enabled.clone(), &config.factors);
                                 }
                             });
                             ui.end_row();
@@ -511,7 +519,7 @@ fn ui_settings_panel(
                             update_float(ui, &mut config.gbp.sigma_factor_interrobot);
                             custom::float_right(ui, |ui| {
                                 if custom::toggle_ui(ui, &mut config.gbp.factors_enabled.interrobot).clicked() {
-                                    update_enabled_factors(config.gbp.factors_enabled.clone());
+                                    update_enabled_factors(config.gbp.factors_enabled.clone(), &config.factors);
                                 }
                             });
                             ui.end_row();
@@ -520,7 +528,7 @@ fn ui_settings_panel(
                             update_float(ui, &mut config.gbp.sigma_factor_obstacle);
                             custom::float_right(ui, |ui| {
                                 if custom::toggle_ui(ui, &mut config.gbp.factors_enabled.obstacle).clicked() {
-                                    update_enabled_factors(config.gbp.factors_enabled.clone());
+                                    update_enabled_factors(config.gbp.factors_enabled.clone(), &config.factors);
                                 }
                             });
                             ui.end_row();
@@ -529,10 +537,55 @@ fn ui_settings_panel(
                             update_float(ui, &mut config.gbp.sigma_factor_tracking);
                             custom::float_right(ui, |ui| {
                                 if custom::toggle_ui(ui, &mut config.gbp.factors_enabled.tracking).clicked() {
-                                    update_enabled_factors(config.gbp.factors_enabled.clone());
+                                    update_enabled_factors(config.gbp.factors_enabled.clone(), &config.factors);
+                                }
+                            });
+                            ui.end_row();
+
+                            ui.label("Dynamic Obstacle");
+                            update_float(ui, &mut config.gbp.sigma_factor_dynamic_obstacle);
+                            custom::float_right(ui, |ui| {
+                                if custom::toggle_ui(ui, &mut config.gbp.factors_enabled.dynamic_obstacle).clicked() {
+                                    update_enabled_factors(config.gbp.factors_enabled.clone(), &config.factors);
+                                }
+                            });
+                            ui.end_row();
+
+                            ui.label("Velocity");
+                            update_float(ui, &mut config.gbp.sigma_factor_velocity);
+                            custom::float_right(ui, |ui| {
+                                if custom::toggle_ui(ui, &mut config.gbp.factors_enabled.velocity).clicked() {
+                                    update_enabled_factors(config.gbp.factors_enabled.clone(), &config.factors);
                                 }
                             });
                             ui.end_row();
+
+                            ui.label("Acceleration");
+                            update_float(ui, &mut config.gbp.sigma_factor_acceleration);
+                            custom::float_right(ui, |ui| {
+                                if custom::toggle_ui(ui, &mut config.gbp.factors_enabled.acceleration).clicked() {
+                                    update_enabled_factors(config.gbp.factors_enabled.clone(), &config.factors);
+                                }
+                            });
+                            ui.end_row();
+
+                            // Factor kinds registered outside of the simulator
+                            for name in user_factor_names {
+                                ui.label(name);
+                                let section = config.factors.entry(name.to_string()).or_default();
+                                if let Some(sigma) = section.sigma.as_mut() {
+                                    update_float(ui, sigma);
+                                } else {
+                                    ui.label("default");
+                                }
+                                let toggled = custom::float_right(ui, |ui| {
+                                    custom::toggle_ui(ui, &mut section.enabled).clicked()
+                                }).inner;
+                                if toggled {
+                                    update_enabled_factors(config.gbp.factors_enabled.clone(), &config.factors);
+                                }
+                                ui.end_row();
+                            }
                         });
                         //
                         //custom::grid("factors_enabled_grid", 2).show(ui, |ui| {
@@ -609,6 +662,11 @@ fn ui_settings_panel(
                                         .trailing_fill(true));
                                 if slider_response.enabled() && slider_response.changed() {
                                     config.robot.target_speed = max_speed.try_into().expect("slider range set to [0.1, 10.0]");
+                                    // Robots of a robot profile keep the speed of their profile
+                                    let mut query = world.query::<&mut Profile>();
+                                    for mut profile in query.iter_mut(world).filter(|profile| profile.name.is_none()) {
+                                        profile.target_speed = config.robot.target_speed;
+                                    }
                                 }
                             });
 
@@ -716,9 +774,11 @@ fn ui_settings_panel(
                                 config.robot.communication.radius = comms_radius.try_into().expect("slider range set to [0.1, 100.0]");
                                 // TODO: this should not be done with a query here, but there is not
                                 // much time left.
-                                let mut query = world.query::<&mut RadioAntenna>();
-                                for mut antenna in query.iter_mut(world) {
+                                // Robots of a robot profile keep the radius of their profile
+                                let mut query = world.query::<(&mut RadioAntenna, &mut Profile)>();
+                                for (mut antenna, mut profile) in query.iter_mut(world).filter(|(_, profile)| profile.name.is_none()) {
                                     antenna.radius = comms_radius;
+                                    profile.communication_radius = config.robot.communication.radius;
                                 }
                             }
                         });
@@ -945,6 +1005,13 @@ fn ui_settings_panel(
                                             }
                                         });
                                     }
+
+                                    // Simulations that could not be loaded are greyed out, with
+                                    // the errors found in them shown on hover
+                                    for invalid in simulation_manager.invalid() {
+                                        let button = egui::Button::new(invalid.name.as_str()).wrap(false);
+                                        ui.add_enabled(false, button).on_disabled_hover_text(invalid.reason.as_str());
+                                    }
                                 });
                             });
                             ui.end_row();
@@ -952,6 +1019,16 @@ fn ui_settings_panel(
 
                         ui.end_row();
 
+                        if simulation_manager.active_changed_on_disk() {
+                            ui.label("Changed on Disk");
+                            ui.centered_and_justified(|ui| {
+                                if ui.button("Restart").on_hover_text("Restart the active simulation with the changes made to its files, keeping the camera and draw settings").clicked() {
+                                    simulation_manager.restart();
+                                }
+                            });
+                            ui.end_row();
+                        }
+
                         ui.label("Simulation Time");
 
                         custom::rect_label(
@@ -74,6 +74,42 @@ pub fn get_variable_timesteps(lookahead_horizon: u32, lookahead_multiple: u32) -
     timesteps
 }
 
+/// Compute the timesteps of `variables` variables spaced evenly from the
+/// current state (0) to the horizon state at `lookahead_horizon`. Used for
+/// robot profiles that set the number of variables, instead of spacing them by
+/// a lookahead multiple as [`get_variable_timesteps`] does.
+/// The timesteps are integers, so if there are more variables than timesteps
+/// the horizon is moved further ahead, to have every variable at its own
+/// timestep.
+/// ## Example:
+/// ```rust
+/// let lookahead_horizon = 20;
+/// let variables = std::num::NonZeroUsize::new(5).unwrap();
+/// assert_eq!(
+///     get_evenly_spaced_variable_timesteps(lookahead_horizon, variables),
+///     vec![0, 5, 10, 15, 20]
+/// );
+/// ```
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss,
+    clippy::cast_possible_truncation
+)]
+pub fn get_evenly_spaced_variable_timesteps(
+    lookahead_horizon: u32,
+    variables: std::num::NonZeroUsize,
+) -> Vec<u32> {
+    let intervals = variables.get() - 1;
+    if intervals == 0 {
+        return vec![0];
+    }
+    let lookahead_horizon = lookahead_horizon.max(intervals as u32);
+
+    (0..=intervals)
+        .map(|i| (lookahead_horizon as f32 * i as f32 / intervals as f32).round() as u32)
+        .collect()
+}
+
 // pub fn static_matrix_to_dynamic<T: na::Scalar, R: na::Const, C: na:: Const,
 // usize>(     m: na::Matrix<T, R, C, na::ArrayStorage<T>>,
 // ) -> na::Matrix<T> {
@@ -131,6 +167,21 @@ mod tests {
             vec![0, 1, 2, 3, 4, 5, 7, 9, 11, 13, 15, 18, 20],
         );
     }
+
+    #[test]
+    fn test_get_evenly_spaced_variable_timesteps() {
+        let variables = std::num::NonZeroUsize::new(5).expect("5 > 0");
+        assert_eq!(get_evenly_spaced_variable_timesteps(20, variables), vec![
+            0, 5, 10, 15, 20
+        ]);
+        assert_eq!(get_evenly_spaced_variable_timesteps(10, variables), vec![
+            0, 3, 5, 8, 10
+        ]);
+        // More variables than timesteps
+        assert_eq!(get_evenly_spaced_variable_timesteps(2, variables), vec![
+            0, 1, 2, 3, 4
+        ]);
+    }
 }
 
 // pub fn get_variable_timesteps(lookahead_horizon: usize, lookahead_multiple:
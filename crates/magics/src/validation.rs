@@ -0,0 +1,872 @@
+//! Validation of the scenarios in the simulations directory.
+//!
+//! Every scenario is checked for mistakes that would otherwise only show up
+//! as a panic, or as robots that never reach their goal, once the scenario has
+//! been loaded. Each problem found is an [`Issue`], and the issues of a
+//! scenario are collected in a [`Report`]. A scenario with an issue of
+//! [`Severity::Error`] can not be loaded, while one with only warnings can.
+
+use std::{
+    collections::BTreeSet,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use bevy::math::Vec2;
+use colored::Colorize;
+use gbp_config::{
+    formation::{
+        CheckIntersectionWith, InitialPlacementStrategy, ProjectionStrategy, WorldDimensions,
+    },
+    geometry::{Point, Shape},
+    Config, FormationGroup, RobotProfile,
+};
+use gbp_environment::Environment;
+use rand::SeedableRng;
+
+use crate::utils::{get_evenly_spaced_variable_timesteps, get_variable_timesteps};
+
+/// The file of a scenario the config is read from
+pub const CONFIG_FILE: &str = "config.toml";
+/// The file of a scenario the environment is read from
+pub const ENVIRONMENT_FILE: &str = "environment.yaml";
+/// The file of a scenario the formations are read from
+pub const FORMATION_FILE: &str = "formation.yaml";
+
+/// How bad an [`Issue`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The scenario can be loaded, but probably does not do what was intended
+    Warning,
+    /// The scenario can not be loaded
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A position in a file, both starting from 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line:   usize,
+    pub column: usize,
+}
+
+impl Location {
+    /// The location of the byte at `offset` in `contents`
+    fn of_offset(contents: &str, offset: usize) -> Self {
+        let before = contents.get(..offset).unwrap_or(contents);
+        let line = before.matches('\n').count() + 1;
+        let column = before
+            .rsplit('\n')
+            .next()
+            .map_or(0, |line| line.chars().count())
+            + 1;
+        Self { line, column }
+    }
+}
+
+/// A problem found in one of the files of a scenario
+#[derive(Debug, Clone)]
+pub struct Issue {
+    pub severity: Severity,
+    /// The file of the scenario, e.g. [`FORMATION_FILE`]
+    pub file:     &'static str,
+    /// Where in `file` the problem is, if known
+    pub location: Option<Location>,
+    pub message:  String,
+}
+
+impl Issue {
+    fn error(file: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            file,
+            location: None,
+            message: message.into(),
+        }
+    }
+
+    fn warning(file: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(file, message)
+        }
+    }
+
+    const fn at(mut self, location: Option<Location>) -> Self {
+        self.location = location;
+        self
+    }
+
+    /// The error of parsing the TOML in `contents`
+    fn toml(file: &'static str, contents: &str, error: &toml::de::Error) -> Self {
+        Self::error(file, error.message()).at(error
+            .span()
+            .map(|span| Location::of_offset(contents, span.start)))
+    }
+
+    /// The error of parsing the YAML of `file`
+    fn yaml(file: &'static str, error: &serde_yaml::Error) -> Self {
+        let location = error.location().map(|location| Location {
+            line:   location.line(),
+            column: location.column(),
+        });
+        // The location is already part of the issue, so it is stripped from the
+        // end of the message
+        let message = error.to_string();
+        let message = location
+            .and_then(|location| {
+                message.strip_suffix(&format!(
+                    " at line {} column {}",
+                    location.line, location.column
+                ))
+            })
+            .unwrap_or(&message)
+            .to_string();
+        Self::error(file, message).at(location)
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(Location { line, column }) = self.location {
+            write!(f, ":{line}:{column}")?;
+        }
+        write!(f, ": {}: {}", self.severity, self.message)
+    }
+}
+
+/// Every [`Issue`] found in a scenario
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// Name of the scenario, i.e. the basename of its directory
+    pub name:   String,
+    pub issues: Vec<Issue>,
+}
+
+impl Report {
+    /// Whether the scenario has no errors, and can be loaded
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Issue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Issue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Warning)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "scenario '{}': {} error(s), {} warning(s)",
+            self.name,
+            self.errors().count(),
+            self.warnings().count()
+        )?;
+        for issue in &self.issues {
+            write!(f, "\n  {issue}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The parsed files of a scenario
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    /// Name of the scenario, i.e. the basename of its directory
+    pub name: String,
+    pub config: Config,
+    pub environment: Environment,
+    pub formation_group: FormationGroup,
+}
+
+/// The name of the scenario in `dir`
+fn scenario_name(dir: &Path) -> String {
+    dir.file_name().map_or_else(
+        || dir.display().to_string(),
+        |basename| basename.to_string_lossy().into_owned(),
+    )
+}
+
+/// Read and parse the files of the scenario in `dir`
+///
+/// # Errors
+///
+/// A report with every file that could not be read or parsed
+pub fn read_scenario(dir: &Path) -> Result<Scenario, Report> {
+    let mut issues = Vec::new();
+    let mut read = |file: &'static str| {
+        std::fs::read_to_string(dir.join(file))
+            .map_err(|err| issues.push(Issue::error(file, format!("could not be read: {err}"))))
+            .ok()
+    };
+    let (config, environment, formation_group) = (
+        read(CONFIG_FILE),
+        read(ENVIRONMENT_FILE),
+        read(FORMATION_FILE),
+    );
+
+    let config = config.and_then(|contents| {
+        Config::parse(&contents)
+            .map_err(|err| {
+                issues.push(match err {
+                    gbp_config::ParseError::Toml(err) => Issue::toml(CONFIG_FILE, &contents, &err),
+                    err => Issue::error(CONFIG_FILE, err.to_string()),
+                });
+            })
+            .ok()
+    });
+    let environment = environment.and_then(|contents| {
+        Environment::parse(&contents)
+            .map_err(|err| {
+                issues.push(match err {
+                    gbp_environment::ParseError::Yaml(err) => Issue::yaml(ENVIRONMENT_FILE, &err),
+                    err => Issue::error(ENVIRONMENT_FILE, err.to_string()),
+                });
+            })
+            .ok()
+    });
+    let formation_group = formation_group.and_then(|contents| {
+        FormationGroup::parse_from_yaml(&contents)
+            .map_err(|err| {
+                issues.push(match err {
+                    gbp_config::formation::ParseError::Yaml(err) => {
+                        Issue::yaml(FORMATION_FILE, &err)
+                    }
+                    err => Issue::error(FORMATION_FILE, err.to_string()),
+                });
+            })
+            .ok()
+    });
+
+    let name = scenario_name(dir);
+    match (config, environment, formation_group) {
+        (Some(config), Some(environment), Some(formation_group)) => Ok(Scenario {
+            name,
+            config,
+            environment,
+            formation_group,
+        }),
+        _ => Err(Report { name, issues }),
+    }
+}
+
+/// Rasterise the obstacles of `environment` into an image, with the
+/// resolution and expansion of its sdf settings
+///
+/// # Errors
+///
+/// If the sdf settings are out of range, or a tile of the grid is missing
+pub fn rasterise(environment: &Environment) -> Result<image::RgbImage, Issue> {
+    let settings = &environment.tiles.settings.sdf;
+    if settings.resolution == 0 {
+        return Err(Issue::error(
+            ENVIRONMENT_FILE,
+            "the sdf resolution must be at least 1 pixel per tile",
+        ));
+    }
+    for (name, value) in [("expansion", settings.expansion), ("blur", settings.blur)] {
+        if !(0.0..=1.0).contains(&value) {
+            return Err(Issue::error(
+                ENVIRONMENT_FILE,
+                format!("the sdf {name} must be between 0 and 1, but is {value}"),
+            ));
+        }
+    }
+
+    env_to_png::env_to_image(
+        environment,
+        env_to_png::PixelsPerTile::new(settings.resolution),
+        env_to_png::Percentage::new(settings.expansion),
+    )
+    .map_err(|err| {
+        Issue::error(
+            ENVIRONMENT_FILE,
+            format!("could not rasterise the obstacles: {err}"),
+        )
+    })
+}
+
+/// Check the parsed `scenario`, with its obstacles rasterised in `obstacles`,
+/// see [`rasterise`]
+#[allow(clippy::too_many_lines, clippy::cast_precision_loss)]
+pub fn check(scenario: &Scenario, obstacles: &image::RgbImage) -> Vec<Issue> {
+    let mut issues = unknown_tiles(scenario);
+    let Scenario {
+        config,
+        environment,
+        formation_group,
+        ..
+    } = scenario;
+
+    let tile_size = f64::from(environment.tile_size());
+    let world_dims = WorldDimensions::new(
+        tile_size * environment.tiles.grid.ncols() as f64,
+        tile_size * environment.tiles.grid.nrows() as f64,
+    );
+    let regions = Regions::new(obstacles);
+    let mut rng = rand::rngs::StdRng::seed_from_u64(config.simulation.prng_seed);
+    // Robots of formations spawning at the same time, which must not overlap
+    let mut spawned = Vec::<(std::time::Duration, usize, Vec2, f32)>::new();
+
+    for (index, formation) in formation_group.formations.iter().enumerate() {
+        let error =
+            |message: String| Issue::error(FORMATION_FILE, format!("formation {index}: {message}"));
+        let warning = |message: String| {
+            Issue::warning(FORMATION_FILE, format!("formation {index}: {message}"))
+        };
+
+        let profile = match config.robot_profile(formation.robot_profile.as_deref()) {
+            Ok(profile) => profile,
+            Err(err) => {
+                issues.push(error(err.to_string()));
+                continue;
+            }
+        };
+
+        let variables = variable_count(config, &profile);
+        for (field, reached_when) in [
+            (
+                "waypoint-reached-when-intersects",
+                formation.waypoint_reached_when_intersects,
+            ),
+            (
+                "finished-when-intersects",
+                formation.finished_when_intersects,
+            ),
+        ] {
+            if let CheckIntersectionWith::Variable(nth) = reached_when.intersects_with {
+                if nth.get() >= variables {
+                    issues.push(error(format!(
+                        "{field} checks variable {nth}, but the robots only have {variables} \
+                         variables, numbered from 0"
+                    )));
+                }
+            }
+        }
+
+        if let Some(message) = unsupported_shapes(formation) {
+            issues.push(error(message.into()));
+            continue;
+        }
+
+        let outside = shape_points(&formation.initial_position.shape)
+            .into_iter()
+            .chain(
+                formation
+                    .waypoints
+                    .iter()
+                    .flat_map(|waypoint| shape_points(&waypoint.shape)),
+            )
+            .any(|point| !(0.0..=1.0).contains(&point.x) || !(0.0..=1.0).contains(&point.y));
+        if outside {
+            issues.push(warning(
+                "a position lies outside the environment, i.e. outside [0, 1]".into(),
+            ));
+        }
+
+        if formation.robots == 0 {
+            issues.push(warning("spawns no robots".into()));
+            continue;
+        }
+
+        // The largest robots of the profile are the hardest to place
+        let radius = profile.radius.max.get();
+        let radii = vec![radius; formation.robots];
+        let Some((initial_positions, waypoints)) =
+            formation.as_positions(world_dims, &radii, &mut rng)
+        else {
+            issues.push(error(format!(
+                "the {} robots of radius {radius} do not fit along the initial position without \
+                 overlapping",
+                formation.robots
+            )));
+            continue;
+        };
+
+        spawned.extend(
+            initial_positions
+                .iter()
+                .map(|&position| (formation.delay, index, position, radius)),
+        );
+
+        let search_radius = radius * regions.pixels_per_metre(world_dims).max_element();
+        let region_at = |position: Vec2| {
+            regions.region_at(regions.to_pixel(position, world_dims), search_radius)
+        };
+        // Robots get as close as they can to a waypoint inside an obstacle,
+        // which is not an error in itself
+        let mut spawns_inside = 0;
+        let mut waypoints_inside = BTreeSet::new();
+        'robots: for (robot, &start) in initial_positions.iter().enumerate() {
+            let Some(region) = region_at(start) else {
+                spawns_inside += 1;
+                continue;
+            };
+            for (nth, waypoint) in waypoints.iter().enumerate() {
+                match region_at(waypoint[robot]) {
+                    None => {
+                        waypoints_inside.insert(nth);
+                    }
+                    Some(goal) if goal != region => {
+                        issues.push(error(format!(
+                            "waypoint {nth} of robot {robot} can not be reached from where it \
+                             spawns"
+                        )));
+                        break 'robots;
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        if spawns_inside > 0 {
+            issues.push(warning(format!(
+                "{spawns_inside} robot(s) spawn inside an obstacle"
+            )));
+        }
+        issues.extend(
+            waypoints_inside
+                .into_iter()
+                .map(|nth| warning(format!("waypoint {nth} lies inside an obstacle"))),
+        );
+    }
+
+    issues.extend(overlapping_spawns(&spawned));
+    issues
+}
+
+/// Read, parse and check the scenario in `dir`
+pub fn validate_scenario(dir: &Path) -> Report {
+    let scenario = match read_scenario(dir) {
+        Ok(scenario) => scenario,
+        Err(report) => return report,
+    };
+    let issues = match rasterise(&scenario.environment) {
+        Ok(obstacles) => check(&scenario, &obstacles),
+        Err(issue) => vec![issue],
+    };
+    Report {
+        name: scenario.name,
+        issues,
+    }
+}
+
+/// The directories of the scenarios in `dir`, ordered by name
+///
+/// # Errors
+///
+/// If `dir` could not be read
+pub fn scenario_dirs(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut dirs = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    dirs.retain(|path| path.is_dir());
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// Validate the scenario in `dir`, or every scenario in it if it is a
+/// directory of scenarios, and print the issues found
+///
+/// # Errors
+///
+/// Returns an error if `dir` could not be read, or a scenario is not valid
+pub fn run(dir: &Path) -> anyhow::Result<()> {
+    let dirs = if dir.join(CONFIG_FILE).is_file() {
+        vec![dir.to_path_buf()]
+    } else {
+        scenario_dirs(dir).with_context(|| format!("failed to read {}", dir.display()))?
+    };
+    anyhow::ensure!(!dirs.is_empty(), "no scenarios found in {}", dir.display());
+
+    let reports = dirs
+        .iter()
+        .map(|dir| validate_scenario(dir))
+        .collect::<Vec<_>>();
+    for report in &reports {
+        let status = if !report.is_valid() {
+            "invalid".red()
+        } else if report.warnings().next().is_some() {
+            "ok".yellow()
+        } else {
+            "ok".green()
+        };
+        println!("{:7} {}", status.bold(), report.name);
+        for issue in &report.issues {
+            println!("        {issue}");
+        }
+    }
+
+    let invalid = reports.iter().filter(|report| !report.is_valid()).count();
+    anyhow::ensure!(
+        invalid == 0,
+        "{invalid} of {} scenarios are invalid",
+        reports.len()
+    );
+    Ok(())
+}
+
+/// The tiles of the environment that are not known, see
+/// [`gbp_environment::TileGrid::TILES`]
+fn unknown_tiles(scenario: &Scenario) -> Vec<Issue> {
+    let grid = &scenario.environment.tiles.grid;
+    grid.unknown_tiles()
+        .map(|(row, col, tile)| {
+            Issue::error(
+                ENVIRONMENT_FILE,
+                format!("unknown tile {tile:?} in row {row}, column {col} of the grid"),
+            )
+        })
+        .collect()
+}
+
+/// Why the shapes of `formation` can not be turned into positions, if they
+/// can not
+fn unsupported_shapes(formation: &gbp_config::formation::Formation) -> Option<&'static str> {
+    let initial = &formation.initial_position;
+    let same_shape =
+        |shape: &Shape| std::mem::discriminant(shape) == std::mem::discriminant(&initial.shape);
+    match initial.shape {
+        Shape::Polygon(_) => Some("a polygon is not supported as the initial position"),
+        _ if !formation
+            .waypoints
+            .iter()
+            .all(|waypoint| same_shape(&waypoint.shape)) =>
+        {
+            Some("every waypoint must have the same shape as the initial position")
+        }
+        Shape::Circle { .. }
+            if matches!(
+                initial.placement_strategy,
+                InitialPlacementStrategy::Random { .. }
+            ) =>
+        {
+            Some("random placement along a circle is not supported")
+        }
+        Shape::Circle { .. }
+            if formation.waypoints.iter().any(|waypoint| {
+                matches!(waypoint.projection_strategy, ProjectionStrategy::Identity)
+            }) =>
+        {
+            Some("the identity projection is not supported for a circle")
+        }
+        _ => None,
+    }
+}
+
+/// The points defining `shape`, in relative coordinates
+fn shape_points(shape: &Shape) -> Vec<Point> {
+    match shape {
+        Shape::Circle { center, .. } => vec![*center],
+        Shape::Polygon(points) => points.iter().copied().collect(),
+        Shape::LineSegment((start, end)) => vec![*start, *end],
+    }
+}
+
+/// The number of variables of the robots of `profile`, as in the spawner
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn variable_count(config: &Config, profile: &RobotProfile) -> usize {
+    let lookahead_horizon = (profile.target_speed * profile.planning_horizon).get() as u32;
+    match profile.variables {
+        Some(variables) => get_evenly_spaced_variable_timesteps(lookahead_horizon, variables),
+        None => get_variable_timesteps(lookahead_horizon, config.gbp.lookahead_multiple as u32),
+    }
+    .len()
+}
+
+/// The robots in `spawned` overlapping a robot spawning at the same time, one
+/// issue per pair of formations
+fn overlapping_spawns(spawned: &[(std::time::Duration, usize, Vec2, f32)]) -> Vec<Issue> {
+    let mut overlapping = BTreeSet::new();
+    for (i, (delay, formation, position, radius)) in spawned.iter().enumerate() {
+        for (other_delay, other_formation, other_position, other_radius) in &spawned[i + 1..] {
+            if delay == other_delay && position.distance(*other_position) < radius + other_radius {
+                overlapping.insert((*formation, *other_formation));
+            }
+        }
+    }
+
+    overlapping
+        .into_iter()
+        .map(|(a, b)| {
+            let message = if a == b {
+                format!("formation {a}: robots overlap when they spawn")
+            } else {
+                format!("formations {a} and {b}: robots overlap when they spawn")
+            };
+            Issue::error(FORMATION_FILE, message)
+        })
+        .collect()
+}
+
+/// The connected regions of free space in an image of the obstacles, where
+/// black pixels are obstacles. The space outside the image is free, so every
+/// free pixel on the border of the image is in the same region as the outside.
+struct Regions {
+    width:  u32,
+    height: u32,
+    /// The region of every pixel, in row-major order
+    labels: Vec<u32>,
+}
+
+impl Regions {
+    const OBSTACLE: u32 = u32::MAX;
+    const OUTSIDE: u32 = 0;
+
+    fn new(image: &image::RgbImage) -> Self {
+        const UNLABELLED: u32 = u32::MAX - 1;
+        let (width, height) = image.dimensions();
+        let mut labels = image
+            .pixels()
+            .map(|pixel| {
+                if pixel[0] < 128 {
+                    Self::OBSTACLE
+                } else {
+                    UNLABELLED
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let index = |x: u32, y: u32| (y * width + x) as usize;
+        let fill = |labels: &mut [u32], seeds: Vec<(u32, u32)>, label: u32| {
+            let mut stack = seeds;
+            while let Some((x, y)) = stack.pop() {
+                if labels[index(x, y)] != UNLABELLED {
+                    continue;
+                }
+                labels[index(x, y)] = label;
+                if x > 0 {
+                    stack.push((x - 1, y));
+                }
+                if x + 1 < width {
+                    stack.push((x + 1, y));
+                }
+                if y > 0 {
+                    stack.push((x, y - 1));
+                }
+                if y + 1 < height {
+                    stack.push((x, y + 1));
+                }
+            }
+        };
+
+        let border = (0..width)
+            .flat_map(|x| [(x, 0), (x, height.saturating_sub(1))])
+            .chain((0..height).flat_map(|y| [(0, y), (width.saturating_sub(1), y)]))
+            .collect();
+        fill(&mut labels, border, Self::OUTSIDE);
+
+        let mut next = Self::OUTSIDE + 1;
+        for y in 0..height {
+            for x in 0..width {
+                if labels[index(x, y)] == UNLABELLED {
+                    fill(&mut labels, vec![(x, y)], next);
+                    next += 1;
+                }
+            }
+        }
+
+        Self {
+            width,
+            height,
+            labels,
+        }
+    }
+
+    /// How many pixels there are per metre of the world, along each axis
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    fn pixels_per_metre(&self, world_dims: WorldDimensions) -> Vec2 {
+        Vec2::new(
+            self.width as f32 / world_dims.width() as f32,
+            self.height as f32 / world_dims.height() as f32,
+        )
+    }
+
+    /// The pixel coordinates of the world `position`, the same as in the
+    /// obstacle factor, with the y axis flipped
+    #[allow(clippy::cast_possible_truncation)]
+    fn to_pixel(&self, position: Vec2, world_dims: WorldDimensions) -> Vec2 {
+        let half = Vec2::new(world_dims.width() as f32, world_dims.height() as f32) / 2.0;
+        Vec2::new(position.x + half.x, half.y - position.y) * self.pixels_per_metre(world_dims)
+    }
+
+    /// The region of the free pixel nearest to `pixel`, at most
+    /// `search_radius` pixels away, or `None` if there is no such pixel
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    fn region_at(&self, pixel: Vec2, search_radius: f32) -> Option<u32> {
+        let (width, height) = (self.width as f32, self.height as f32);
+        if pixel.x < 0.0 || pixel.y < 0.0 || pixel.x >= width || pixel.y >= height {
+            return Some(Self::OUTSIDE);
+        }
+
+        let label = |x: u32, y: u32| self.labels[(y * self.width + x) as usize];
+        let (x, y) = (pixel.x as u32, pixel.y as u32);
+        if label(x, y) != Self::OBSTACLE {
+            return Some(label(x, y));
+        }
+
+        let reach = search_radius.ceil() as u32;
+        let columns = x.saturating_sub(reach)..=(x + reach).min(self.width - 1);
+        let rows = y.saturating_sub(reach)..=(y + reach).min(self.height - 1);
+        rows.flat_map(|row| columns.clone().map(move |column| (column, row)))
+            .filter(|&(column, row)| label(column, row) != Self::OBSTACLE)
+            .map(|(column, row)| {
+                let distance = Vec2::new(column as f32, row as f32).distance(pixel);
+                (distance, label(column, row))
+            })
+            .filter(|(distance, _)| *distance <= search_radius)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, label)| label)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 10 x 10 image, free except for a wall along `x = 5`, with a gap in it
+    /// if `gap`, and an enclosed free pixel at (2, 2)
+    fn walled(gap: bool) -> image::RgbImage {
+        image::RgbImage::from_fn(10, 10, |x, y| {
+            let wall = x == 5 && !(gap && y == 5);
+            let enclosure = (1..=3).contains(&x) && (1..=3).contains(&y) && (x, y) != (2, 2);
+            if wall || enclosure {
+                image::Rgb([0, 0, 0])
+            } else {
+                image::Rgb([255, 255, 255])
+            }
+        })
+    }
+
+    #[test]
+    fn location_of_offset() {
+        let contents = "a = 1\nbb = [\n  2,\n]\n";
+        assert_eq!(Location::of_offset(contents, 0), Location {
+            line:   1,
+            column: 1,
+        });
+        assert_eq!(Location::of_offset(contents, 8), Location {
+            line:   2,
+            column: 3,
+        });
+        assert_eq!(Location::of_offset(contents, 15), Location {
+            line:   3,
+            column: 3,
+        });
+    }
+
+    #[test]
+    fn toml_errors_have_a_location() {
+        let contents = "[simulation]\nmax-time = forever\n";
+        let err =
+            toml::from_str::<toml::Table>(contents).expect_err("bare words are not valid TOML");
+        let issue = Issue::toml(CONFIG_FILE, contents, &err);
+        assert_eq!(issue.severity, Severity::Error);
+        assert_eq!(issue.location.map(|location| location.line), Some(2));
+        assert!(issue.to_string().starts_with("config.toml:2:"));
+    }
+
+    #[test]
+    fn yaml_errors_have_a_location() {
+        let err = FormationGroup::parse_from_yaml("formations:\n- robots: many\n");
+        let Err(gbp_config::formation::ParseError::Yaml(err)) = err else {
+            panic!("the formation is not valid");
+        };
+        let issue = Issue::yaml(FORMATION_FILE, &err);
+        assert_eq!(issue.location.map(|location| location.line), Some(2));
+        assert!(!issue.message.contains("at line"));
+    }
+
+    #[test]
+    fn regions_are_separated_by_walls() {
+        let regions = Regions::new(&walled(false));
+        let left = regions.region_at(Vec2::new(7.5, 2.5), 0.0);
+        assert_eq!(left, Some(Regions::OUTSIDE));
+        // Both sides of the wall touch the border, and so the outside
+        assert_eq!(regions.region_at(Vec2::new(0.5, 8.5), 0.0), left);
+        assert_eq!(regions.region_at(Vec2::new(-3.0, 4.0), 0.0), left);
+
+        let enclosed = regions.region_at(Vec2::new(2.5, 2.5), 0.0);
+        assert!(enclosed.is_some());
+        assert_ne!(enclosed, left);
+        // Inside the wall of the enclosure, the nearest free pixel decides
+        assert_eq!(regions.region_at(Vec2::new(2.5, 1.5), 1.0), enclosed);
+        assert_eq!(regions.region_at(Vec2::new(5.5, 2.5), 0.5), None);
+    }
+
+    #[test]
+    fn overlapping_spawns_at_the_same_time() {
+        let now = std::time::Duration::ZERO;
+        let later = std::time::Duration::from_secs(1);
+        let spawned = [
+            (now, 0, Vec2::new(0.0, 0.0), 1.0),
+            (now, 0, Vec2::new(3.0, 0.0), 1.0),
+            (later, 1, Vec2::new(0.5, 0.0), 1.0),
+            (now, 2, Vec2::new(4.5, 0.0), 1.0),
+        ];
+        let issues = overlapping_spawns(&spawned);
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].message.starts_with("formations 0 and 2"));
+    }
+
+    #[test]
+    fn variable_beyond_the_horizon() {
+        let mut scenario = Scenario {
+            name: "default".into(),
+            config: Config::default(),
+            environment: Environment::default(),
+            formation_group: FormationGroup::default(),
+        };
+        let obstacles =
+            rasterise(&scenario.environment).expect("the default sdf settings are valid");
+        let before = check(&scenario, &obstacles);
+
+        let variables = variable_count(
+            &scenario.config,
+            &scenario
+                .config
+                .robot_profile(None)
+                .expect("the default profile exists"),
+        );
+        let nth = std::num::NonZeroUsize::new(variables).expect("there are at least 2 variables");
+        scenario
+            .formation_group
+            .formations
+            .as_mut_slice()
+            .iter_mut()
+            .for_each(|formation| {
+                formation.finished_when_intersects.intersects_with =
+                    CheckIntersectionWith::Variable(nth);
+            });
+        let after = check(&scenario, &obstacles);
+
+        let beyond = after
+            .iter()
+            .filter(|issue| issue.message.contains("finished-when-intersects"))
+            .count();
+        assert_eq!(beyond, scenario.formation_group.formations.len());
+        assert_eq!(after.len(), before.len() + beyond);
+    }
+}